@@ -0,0 +1,320 @@
+use super::prelude::*;
+
+use self::layer2::ethernet::{Ethernet, EthernetTypeId, Layer3Id};
+use self::layer3::arp::Arp;
+#[cfg(feature = "ipv6")]
+use self::layer3::InternetProtocolId;
+#[cfg(feature = "ipv6")]
+use self::layer3::ipv6::IPv6;
+
+use std;
+use std::collections::HashMap;
+
+#[cfg(feature = "ipv6")]
+const NEIGHBOR_SOLICITATION: u8 = 135;
+#[cfg(feature = "ipv6")]
+const NEIGHBOR_ADVERTISEMENT: u8 = 136;
+#[cfg(feature = "ipv6")]
+const SOURCE_LINK_LAYER_ADDRESS: u8 = 1;
+#[cfg(feature = "ipv6")]
+const TARGET_LINK_LAYER_ADDRESS: u8 = 2;
+#[cfg(feature = "ipv6")]
+const NEIGHBOR_DISCOVERY_HEADER_LENGTH: usize = 24; //type, code, checksum, flags, target address
+
+///
+/// Protocol an address binding was learned from
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BindingSource {
+    Arp,
+    ///
+    /// IPv6 neighbor discovery (neighbor solicitation and advertisement)
+    ///
+    Ndp
+}
+
+///
+/// An ip address observed being claimed by a mac address
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Binding {
+    pub ip: std::net::IpAddr,
+    pub mac: MacAddress,
+    pub source: BindingSource,
+    pub first_seen: std::time::SystemTime,
+    pub last_seen: std::time::SystemTime,
+    pub packets: usize
+}
+
+///
+/// An ip address claimed by more than one mac address, a sign of spoofing or of an address moving
+/// between hosts
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Conflict<'a> {
+    pub ip: std::net::IpAddr,
+    pub bindings: std::vec::Vec<&'a Binding>
+}
+
+///
+/// Ip to mac bindings observed in ARP and neighbor discovery traffic
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ArpTable {
+    bindings: HashMap<(std::net::IpAddr, MacAddress), Binding>
+}
+
+fn arp_binding(arp: &Arp) -> Option<(std::net::IpAddr, MacAddress)> {
+    if arp.sender_ip().is_unspecified() {
+        //probe, the sender has no address yet
+        None
+    } else {
+        Some( (std::net::IpAddr::V4(*arp.sender_ip()), *arp.sender_mac()) )
+    }
+}
+
+#[cfg(feature = "ipv6")]
+fn link_layer_option(mut options: &[u8], option_type: u8) -> Option<MacAddress> {
+    while options.len() >= 2 {
+        let length = options[1] as usize * 8;
+        if length == 0 || length > options.len() {
+            return None
+        }
+        if options[0] == option_type && length >= 2 + MAC_LENGTH {
+            return Some(MacAddress(array_ref![options, 2, MAC_LENGTH].clone()))
+        }
+        options = &options[length..];
+    }
+    None
+}
+
+#[cfg(feature = "ipv6")]
+fn ndp_binding(ipv6: &IPv6, src_mac: &MacAddress) -> Option<(std::net::IpAddr, MacAddress)> {
+    let message = ipv6.payload();
+    if *ipv6.protocol() != InternetProtocolId::Icmpv6 || message.len() < NEIGHBOR_DISCOVERY_HEADER_LENGTH {
+        return None
+    }
+    let options = &message[NEIGHBOR_DISCOVERY_HEADER_LENGTH..];
+
+    match message[0] {
+        NEIGHBOR_SOLICITATION => {
+            if ipv6.src_ip().is_unspecified() {
+                //duplicate address detection, the sender has no address yet
+                None
+            } else {
+                link_layer_option(options, SOURCE_LINK_LAYER_ADDRESS).map(|mac| (*ipv6.src_ip(), mac))
+            }
+        }
+        NEIGHBOR_ADVERTISEMENT => {
+            let target = std::net::Ipv6Addr::from(array_ref![message, 8, 16].clone());
+            let mac = link_layer_option(options, TARGET_LINK_LAYER_ADDRESS).unwrap_or(*src_mac);
+            Some( (std::net::IpAddr::V6(target), mac) )
+        }
+        _ => None
+    }
+}
+
+impl ArpTable {
+    ///
+    /// All bindings, ordered by ip and then first time seen
+    ///
+    pub fn bindings(&self) -> std::vec::Vec<&Binding> {
+        let mut bindings = self.bindings.values().collect::<std::vec::Vec<_>>();
+        bindings.sort_by(|a, b| a.ip.cmp(&b.ip).then_with(|| a.first_seen.cmp(&b.first_seen)));
+        bindings
+    }
+
+    ///
+    /// Mac addresses seen claiming `ip`, in the order they first claimed it
+    ///
+    pub fn macs(&self, ip: &std::net::IpAddr) -> std::vec::Vec<MacAddress> {
+        self.bindings().into_iter().filter(|b| b.ip == *ip).map(|b| b.mac).collect()
+    }
+
+    ///
+    /// Ip addresses claimed by more than one mac address
+    ///
+    pub fn conflicts<'a>(&'a self) -> std::vec::Vec<Conflict<'a>> {
+        let mut conflicts: std::vec::Vec<Conflict<'a>> = vec![];
+        for binding in self.bindings() {
+            match conflicts.last_mut() {
+                Some(ref mut c) if c.ip == binding.ip => {
+                    c.bindings.push(binding);
+                    continue
+                }
+                _ => {}
+            }
+            conflicts.push(Conflict {
+                ip: binding.ip,
+                bindings: vec![binding]
+            });
+        }
+        conflicts.retain(|c| c.bindings.len() > 1);
+        conflicts
+    }
+
+    fn observe(&mut self, ip: std::net::IpAddr, mac: MacAddress, source: BindingSource, timestamp: std::time::SystemTime) {
+        let binding = self.bindings.entry( (ip, mac) ).or_insert_with(|| Binding {
+            ip: ip,
+            mac: mac,
+            source: source,
+            first_seen: timestamp,
+            last_seen: timestamp,
+            packets: 0
+        });
+        if timestamp < binding.first_seen {
+            binding.first_seen = timestamp;
+        }
+        if timestamp > binding.last_seen {
+            binding.last_seen = timestamp;
+        }
+        binding.packets += 1;
+    }
+
+    pub fn add_record(&mut self, record: &PcapRecord) {
+        let l2 = match Ethernet::parse(record.payload()) {
+            Ok( (_, l2) ) => l2,
+            Err(e) => {
+                debug!("Could not parse ethernet: {:?}", e);
+                return
+            }
+        };
+
+        let observed = match *l2.ether_type() {
+            EthernetTypeId::L3(Layer3Id::Arp) => {
+                Arp::parse(l2.payload()).ok()
+                    .and_then(|(_, arp)| arp_binding(&arp))
+                    .map(|(ip, mac)| (ip, mac, BindingSource::Arp))
+            }
+            #[cfg(feature = "ipv6")]
+            EthernetTypeId::L3(Layer3Id::IPv6) => {
+                IPv6::parse(l2.payload()).ok()
+                    .and_then(|(_, ipv6)| ndp_binding(&ipv6, l2.src_mac()))
+                    .map(|(ip, mac)| (ip, mac, BindingSource::Ndp))
+            }
+            _ => None
+        };
+
+        if let Some( (ip, mac, source) ) = observed {
+            self.observe(ip, mac, source, *record.timestamp());
+        }
+    }
+
+    pub fn from_records<'a, I>(records: I) -> ArpTable where I: IntoIterator<Item=&'a PcapRecord> {
+        let mut table = ArpTable::default();
+        for record in records {
+            table.add_record(record);
+        }
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    fn arp_record(seconds: u32, sender_mac: u8, sender_ip: [u8; 4]) -> PcapRecord {
+        let payload = vec![
+            0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8, //dst mac, broadcast
+            0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, sender_mac, //src mac
+            0x08u8, 0x06u8, //arp
+            //arp
+            0x00u8, 0x01u8, //hardware type, ethernet
+            0x08u8, 0x00u8, //protocol type, ipv4
+            0x06u8, //hardware length
+            0x04u8, //protocol length
+            0x00u8, 0x02u8, //operation, reply
+            0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, sender_mac, //sender mac
+            sender_ip[0], sender_ip[1], sender_ip[2], sender_ip[3], //sender ip
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, //target mac
+            0x0Au8, 0x00u8, 0x00u8, 0xFEu8 //target ip 10.0.0.254
+        ];
+        PcapRecord::new(PcapRecord::convert_packet_time(seconds, 0), payload.len() as u32, payload.len() as u32, payload)
+    }
+
+    #[test]
+    fn arp_bindings() {
+        let _ = env_logger::try_init();
+
+        let records = vec![
+            arp_record(1, 0x01, [10, 0, 0, 1]),
+            arp_record(2, 0x02, [10, 0, 0, 2]),
+            arp_record(3, 0x01, [10, 0, 0, 1]),
+            arp_record(4, 0x03, [0, 0, 0, 0]) //probe
+        ];
+        let table = ArpTable::from_records(&records);
+
+        let bindings = table.bindings();
+        assert_eq!(bindings.len(), 2);
+        assert_eq!(bindings[0].ip, "10.0.0.1".parse::<std::net::IpAddr>().unwrap());
+        assert_eq!(bindings[0].mac, MacAddress([0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x01u8]));
+        assert_eq!(bindings[0].source, BindingSource::Arp);
+        assert_eq!(bindings[0].first_seen, PcapRecord::convert_packet_time(1, 0));
+        assert_eq!(bindings[0].last_seen, PcapRecord::convert_packet_time(3, 0));
+        assert_eq!(bindings[0].packets, 2);
+        assert!(table.conflicts().is_empty());
+    }
+
+    #[test]
+    fn arp_conflicts() {
+        let _ = env_logger::try_init();
+
+        let records = vec![
+            arp_record(1, 0x01, [10, 0, 0, 1]),
+            arp_record(2, 0x02, [10, 0, 0, 2]),
+            arp_record(3, 0x03, [10, 0, 0, 1])
+        ];
+        let table = ArpTable::from_records(&records);
+
+        let ip = "10.0.0.1".parse::<std::net::IpAddr>().unwrap();
+        let conflicts = table.conflicts();
+
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].ip, ip);
+        assert_eq!(table.macs(&ip), vec![
+            MacAddress([0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x01u8]),
+            MacAddress([0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x03u8])
+        ]);
+    }
+
+    #[cfg(feature = "ipv6")]
+    #[test]
+    fn ndp_bindings() {
+        let _ = env_logger::try_init();
+
+        let payload = vec![
+            0x33u8, 0x33u8, 0x00u8, 0x00u8, 0x00u8, 0x01u8, //dst mac, all nodes
+            0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x0Au8, //src mac
+            0x86u8, 0xDDu8, //ipv6
+            //ipv6
+            0x60u8, 0x00u8, 0x00u8, 0x00u8, //version, traffic class, flow label
+            0x00u8, 0x20u8, //payload length, 32
+            0x3Au8, //next header, icmpv6
+            0xFFu8, //hop limit
+            0xFEu8, 0x80u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8,
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x0Au8, //src ip fe80::a
+            0xFFu8, 0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8,
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x01u8, //dst ip ff02::1
+            //icmpv6
+            0x88u8, //neighbor advertisement
+            0x00u8, //code
+            0x00u8, 0x00u8, //checksum
+            0x20u8, 0x00u8, 0x00u8, 0x00u8, //override
+            0x20u8, 0x01u8, 0x0Du8, 0xB8u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8,
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x0Au8, //target 2001:db8::a
+            0x02u8, 0x01u8, //target link layer address, 8 bytes
+            0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x0Bu8 //mac
+        ];
+        let record = PcapRecord::new(PcapRecord::convert_packet_time(1, 0), payload.len() as u32, payload.len() as u32, payload);
+
+        let table = ArpTable::from_records(vec![&record]);
+        let bindings = table.bindings();
+
+        assert_eq!(bindings.len(), 1);
+        assert_eq!(bindings[0].ip, "2001:db8::a".parse::<std::net::IpAddr>().unwrap());
+        assert_eq!(bindings[0].mac, MacAddress([0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x0Bu8]));
+        assert_eq!(bindings[0].source, BindingSource::Ndp);
+    }
+}
@@ -0,0 +1,476 @@
+use super::prelude::*;
+use super::super::flow::FlowKey;
+use super::super::global_header::GlobalHeader;
+use super::super::link_type::LinkType;
+use super::super::parsed::{ParseConfig, ParsedRecord};
+
+use self::layer2::ethernet::{Ethernet, EthernetTypeId, Layer3Id};
+use self::layer3::{InternetProtocolId, Layer3};
+use self::layer3::ipv4::IPv4;
+#[cfg(feature = "ipv6")]
+use self::layer3::ipv6::IPv6;
+use self::layer4::tcp::Tcp;
+use self::layer4::udp::Udp;
+
+use std;
+use std::collections::HashMap;
+
+const IPV4_CHECKSUM_OFFSET: usize = 10;
+const TCP_CHECKSUM_OFFSET: usize = 16;
+const UDP_CHECKSUM_OFFSET: usize = 6;
+
+///
+/// Outcome of verifying a single checksum
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChecksumStatus {
+    Valid,
+    Invalid,
+    ///
+    /// Checksum is zero or holds only the pseudo header sum, as left by transmit checksum
+    /// offload on the capturing host
+    ///
+    LikelyOffload,
+    ///
+    /// Checksum is optional and was not computed by the sender (UDP over IPv4)
+    ///
+    NotPresent
+}
+
+///
+/// Tally of checksum outcomes
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ChecksumCounts {
+    pub valid: usize,
+    pub invalid: usize,
+    pub likely_offload: usize,
+    pub not_present: usize
+}
+
+impl ChecksumCounts {
+    pub fn add(&mut self, status: ChecksumStatus) {
+        match status {
+            ChecksumStatus::Valid => self.valid += 1,
+            ChecksumStatus::Invalid => self.invalid += 1,
+            ChecksumStatus::LikelyOffload => self.likely_offload += 1,
+            ChecksumStatus::NotPresent => self.not_present += 1
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.valid + self.invalid + self.likely_offload + self.not_present
+    }
+}
+
+///
+/// Checksums verified for a single packet
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PacketChecksums {
+    pub key: FlowKey,
+    pub ipv4: Option<ChecksumStatus>,
+    pub tcp: Option<ChecksumStatus>,
+    pub udp: Option<ChecksumStatus>
+}
+
+///
+/// Add 16 bit big endian words of `data` to a running one's complement sum, padding an odd
+/// trailing byte with zero
+///
+pub fn sum_words(initial: u32, data: &[u8]) -> u32 {
+    let mut sum = initial;
+    for chunk in data.chunks(2) {
+        let word = if chunk.len() == 2 {
+            ((chunk[0] as u32) << 8) | chunk[1] as u32
+        } else {
+            (chunk[0] as u32) << 8
+        };
+        sum = sum.wrapping_add(word);
+    }
+    sum
+}
+
+///
+/// Fold carries of a running sum back into 16 bits
+///
+pub fn fold(sum: u32) -> u16 {
+    let mut folded = sum;
+    while folded > 0xFFFF {
+        folded = (folded & 0xFFFF) + (folded >> 16);
+    }
+    folded as u16
+}
+
+///
+/// Internet checksum (RFC 1071) of a slice of bytes
+///
+pub fn internet_checksum(data: &[u8]) -> u16 {
+    !fold(sum_words(0, data))
+}
+
+fn checksum_field(data: &[u8], offset: usize) -> Option<u16> {
+    if data.len() >= offset + 2 {
+        Some(((data[offset] as u16) << 8) | data[offset + 1] as u16)
+    } else {
+        None
+    }
+}
+
+fn ip_octets(ip: &std::net::IpAddr) -> std::vec::Vec<u8> {
+    match *ip {
+        std::net::IpAddr::V4(ref v4) => v4.octets().to_vec(),
+        std::net::IpAddr::V6(ref v6) => v6.octets().to_vec()
+    }
+}
+
+fn pseudo_header_sum(src: &std::net::IpAddr, dst: &std::net::IpAddr, protocol: u8, length: usize) -> u32 {
+    let sum = sum_words(0, &ip_octets(src));
+    let sum = sum_words(sum, &ip_octets(dst));
+    sum.wrapping_add(protocol as u32)
+        .wrapping_add((length as u32) >> 16)
+        .wrapping_add((length as u32) & 0xFFFF)
+}
+
+fn segment_status(segment: &[u8], offset: usize, pseudo_sum: u32, optional: bool) -> ChecksumStatus {
+    match checksum_field(segment, offset) {
+        None => ChecksumStatus::Invalid,
+        Some(0) if optional => ChecksumStatus::NotPresent,
+        Some(field) => {
+            if fold(sum_words(pseudo_sum, segment)) == 0xFFFF {
+                ChecksumStatus::Valid
+            } else if field == 0 || field == fold(pseudo_sum) {
+                ChecksumStatus::LikelyOffload
+            } else {
+                ChecksumStatus::Invalid
+            }
+        }
+    }
+}
+
+fn validate_segment(
+    protocol: &InternetProtocolId,
+    src_ip: std::net::IpAddr,
+    dst_ip: std::net::IpAddr,
+    vlan: Vlan,
+    segment: &[u8],
+    udp_optional: bool
+) -> Result<PacketChecksums, errors::Error> {
+    let (ports, tcp, udp) = match *protocol {
+        InternetProtocolId::Tcp => {
+            let (_, tcp) = Tcp::parse(segment)
+                .map_err(|e| errors::Error::from_nom(e, errors::Layer::Tcp, segment))?;
+            let status = segment_status(segment, TCP_CHECKSUM_OFFSET, pseudo_header_sum(&src_ip, &dst_ip, 6, segment.len()), false);
+            ( (tcp.src_port(), tcp.dst_port()), Some(status), None )
+        }
+        InternetProtocolId::Udp => {
+            let (_, udp) = Udp::parse(segment)
+                .map_err(|e| errors::Error::from_nom(e, errors::Layer::Udp, segment))?;
+            let status = segment_status(segment, UDP_CHECKSUM_OFFSET, pseudo_header_sum(&src_ip, &dst_ip, 17, segment.len()), udp_optional);
+            ( (udp.src_port(), udp.dst_port()), None, Some(status) )
+        }
+        _ => ( (0, 0), None, None )
+    };
+
+    Ok(PacketChecksums {
+        key: FlowKey {
+            src_ip: src_ip,
+            src_port: ports.0,
+            dst_ip: dst_ip,
+            dst_port: ports.1,
+            protocol: Some(protocol.clone()),
+            vlan: vlan,
+            inner_vlans: vec![]
+        },
+        ipv4: None,
+        tcp: tcp,
+        udp: udp
+    })
+}
+
+fn validate_ipv4(packet: &[u8], vlan: Vlan) -> Result<PacketChecksums, errors::Error> {
+    let (_, ipv4) = IPv4::parse(packet).map_err(|e| errors::Error::from_nom(e, errors::Layer::IPv4, packet))?;
+    let header_length = ((packet[0] & 0x0F) as usize) * 4;
+
+    if packet.len() < header_length {
+        return Err(errors::Error::from_kind(errors::ErrorKind::IPv4Length(header_length as u8)))
+    }
+
+    let header = &packet[..header_length];
+    let ipv4_status = if internet_checksum(header) == 0 {
+        ChecksumStatus::Valid
+    } else if checksum_field(header, IPV4_CHECKSUM_OFFSET) == Some(0) {
+        ChecksumStatus::LikelyOffload
+    } else {
+        ChecksumStatus::Invalid
+    };
+
+    let mut checksums = validate_segment(ipv4.protocol(), *ipv4.src_ip(), *ipv4.dst_ip(), vlan, ipv4.payload(), true)?;
+    checksums.ipv4 = Some(ipv4_status);
+    Ok(checksums)
+}
+
+#[cfg(feature = "ipv6")]
+fn validate_ipv6(packet: &[u8], vlan: Vlan) -> Result<PacketChecksums, errors::Error> {
+    let (_, ipv6) = IPv6::parse(packet).map_err(|e| errors::Error::from_nom(e, errors::Layer::IPv6, packet))?;
+    validate_segment(ipv6.protocol(), *ipv6.src_ip(), *ipv6.dst_ip(), vlan, ipv6.payload(), false)
+}
+
+///
+/// Verify the IPv4 header checksum and TCP/UDP checksums of an ethernet frame
+///
+pub fn validate_ethernet(frame: &[u8]) -> Result<PacketChecksums, errors::Error> {
+    let (_, l2) = Ethernet::parse(frame)
+        .map_err(|e| errors::Error::from_nom(e, errors::Layer::Ethernet, frame))?;
+    match *l2.ether_type() {
+        EthernetTypeId::L3(Layer3Id::IPv4) => validate_ipv4(l2.payload(), l2.vlan()),
+        #[cfg(feature = "ipv6")]
+        EthernetTypeId::L3(Layer3Id::IPv6) => validate_ipv6(l2.payload(), l2.vlan()),
+        ref other => Err(errors::Error::from_kind(errors::ErrorKind::EthernetType(other.clone())))
+    }
+}
+
+///
+/// Verify the IPv4 header checksum and TCP/UDP checksums of a record parsed according to
+/// `config`, e.g. with the link type of a linux cooked or raw ip capture
+///
+pub fn validate_record(record: &PcapRecord, config: &ParseConfig) -> Result<PacketChecksums, errors::Error> {
+    let parsed = ParsedRecord::parse_with(record.clone(), config)?;
+    let packet = &record.payload()[parsed.offsets().layer3.unwrap_or(0)..];
+    match parsed.layer3() {
+        Some(&Layer3::IPv4(_)) => validate_ipv4(packet, parsed.vlan()),
+        #[cfg(feature = "ipv6")]
+        Some(&Layer3::IPv6(_)) => validate_ipv6(packet, parsed.vlan()),
+        None => Err(parsed.layer2().unsupported())
+    }
+}
+
+///
+/// Capture wide and per flow summary of checksum validation. Records are taken to be ethernet
+/// frames unless the report is for the header of a capture of another link type.
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChecksumReport {
+    config: ParseConfig,
+    ipv4: ChecksumCounts,
+    tcp: ChecksumCounts,
+    udp: ChecksumCounts,
+    flows: HashMap<FlowKey, ChecksumCounts>,
+    unparsed: usize
+}
+
+impl ChecksumReport {
+    ///
+    /// Report on records of the capture with global header `header`, parsed with the layer 2
+    /// parser of its link type
+    ///
+    pub fn for_header(header: &GlobalHeader) -> ChecksumReport {
+        ChecksumReport {
+            config: ParseConfig::for_header(header),
+            ..ChecksumReport::default()
+        }
+    }
+
+    pub fn ipv4(&self) -> &ChecksumCounts { &self.ipv4 }
+    pub fn tcp(&self) -> &ChecksumCounts { &self.tcp }
+    pub fn udp(&self) -> &ChecksumCounts { &self.udp }
+    pub fn flows(&self) -> &HashMap<FlowKey, ChecksumCounts> { &self.flows }
+    ///
+    /// Number of records that could not be parsed far enough to check any checksum
+    ///
+    pub fn unparsed(&self) -> usize { self.unparsed }
+
+    ///
+    /// Flows with at least one checksum that is neither valid nor explained by offload
+    ///
+    pub fn corrupt_flows(&self) -> std::vec::Vec<(&FlowKey, &ChecksumCounts)> {
+        self.flows.iter().filter(|&(_, c)| c.invalid > 0).collect()
+    }
+
+    pub fn add_record(&mut self, record: &PcapRecord) {
+        let checksums = if self.config.link_type == LinkType::Ethernet {
+            validate_ethernet(record.payload())
+        } else {
+            validate_record(record, &self.config)
+        };
+        match checksums {
+            Ok(checksums) => {
+                let flow = self.flows.entry(checksums.key).or_insert_with(ChecksumCounts::default);
+                if let Some(s) = checksums.ipv4 {
+                    self.ipv4.add(s);
+                    flow.add(s);
+                }
+                if let Some(s) = checksums.tcp {
+                    self.tcp.add(s);
+                    flow.add(s);
+                }
+                if let Some(s) = checksums.udp {
+                    self.udp.add(s);
+                    flow.add(s);
+                }
+            }
+            Err(e) => {
+                debug!("Could not validate checksums: {}", e);
+                self.unparsed += 1;
+            }
+        }
+    }
+
+    pub fn from_records<'a, I>(records: I) -> ChecksumReport where I: IntoIterator<Item=&'a PcapRecord> {
+        let mut report = ChecksumReport::default();
+        for record in records {
+            report.add_record(record);
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    const ETHERNET_HEADER_LENGTH: usize = 14;
+    const IPV4_HEADER_LENGTH: usize = 20;
+
+    const TCP_RAW_DATA: &'static [u8] = &[
+        0x01u8, 0x02u8, 0x03u8, 0x04u8, 0x05u8, 0x06u8, //dst mac 01:02:03:04:05:06
+        0xFFu8, 0xFEu8, 0xFDu8, 0xFCu8, 0xFBu8, 0xFAu8, //src mac FF:FE:FD:FC:FB:FA
+        0x08u8, 0x00u8, //ipv4
+        //ipv4
+        0x45u8, //version and header length
+        0x00u8, //tos
+        0x00u8, 0x2Cu8, //length, 20 bytes for header, 24 bytes for tcp
+        0x00u8, 0x00u8, //id
+        0x00u8, 0x00u8, //flags
+        0x64u8, //ttl
+        0x06u8, //protocol, tcp
+        0x00u8, 0x00u8, //checksum
+        0x01u8, 0x02u8, 0x03u8, 0x04u8, //src ip 1.2.3.4
+        0x0Au8, 0x0Bu8, 0x0Cu8, 0x0Du8, //dst ip 10.11.12.13
+        //tcp
+        0xC6u8, 0xB7u8, //src port, 50871
+        0x00u8, 0x50u8, //dst port, 80
+        0x00u8, 0x00u8, 0x00u8, 0x01u8, //sequence number, 1
+        0x00u8, 0x00u8, 0x00u8, 0x02u8, //acknowledgement number, 2
+        0x50u8, 0x00u8, //header and flags, 0
+        0x00u8, 0x00u8, //window
+        0x00u8, 0x00u8, //check
+        0x00u8, 0x00u8, //urgent
+        //no options
+        //payload
+        0x01u8, 0x02u8, 0x03u8, 0x04u8
+    ];
+
+    fn with_valid_checksums() -> std::vec::Vec<u8> {
+        fill_checksums(TCP_RAW_DATA.to_vec(), IPV4_HEADER_LENGTH)
+    }
+
+    ///
+    /// `frame` with its ipv4 header of `header_length` bytes and its tcp segment checksummed
+    ///
+    fn fill_checksums(mut frame: std::vec::Vec<u8>, header_length: usize) -> std::vec::Vec<u8> {
+        let ip_checksum = internet_checksum(&frame[ETHERNET_HEADER_LENGTH..ETHERNET_HEADER_LENGTH + header_length]);
+        frame[ETHERNET_HEADER_LENGTH + IPV4_CHECKSUM_OFFSET] = (ip_checksum >> 8) as u8;
+        frame[ETHERNET_HEADER_LENGTH + IPV4_CHECKSUM_OFFSET + 1] = ip_checksum as u8;
+
+        let segment_start = ETHERNET_HEADER_LENGTH + header_length;
+        let src = "1.2.3.4".parse::<std::net::IpAddr>().unwrap();
+        let dst = "10.11.12.13".parse::<std::net::IpAddr>().unwrap();
+        let pseudo = pseudo_header_sum(&src, &dst, 6, frame.len() - segment_start);
+        let tcp_checksum = !fold(sum_words(pseudo, &frame[segment_start..]));
+        frame[segment_start + TCP_CHECKSUM_OFFSET] = (tcp_checksum >> 8) as u8;
+        frame[segment_start + TCP_CHECKSUM_OFFSET + 1] = tcp_checksum as u8;
+
+        frame
+    }
+
+    #[test]
+    fn rfc1071_checksum() {
+        assert_eq!(internet_checksum(&[0x00u8, 0x01u8, 0xF2u8, 0x03u8, 0xF4u8, 0xF5u8, 0xF6u8, 0xF7u8]), 0x220D);
+    }
+
+    #[test]
+    fn validate_valid_checksums() {
+        let _ = env_logger::try_init();
+
+        let checksums = validate_ethernet(&with_valid_checksums()).expect("Could not validate");
+
+        assert_eq!(checksums.ipv4, Some(ChecksumStatus::Valid));
+        assert_eq!(checksums.tcp, Some(ChecksumStatus::Valid));
+        assert_eq!(checksums.udp, None);
+        assert_eq!(checksums.key.src_port, 50871);
+        assert_eq!(checksums.key.dst_port, 80);
+    }
+
+    #[test]
+    fn validate_ipv4_options() {
+        let _ = env_logger::try_init();
+
+        let mut frame = TCP_RAW_DATA[..ETHERNET_HEADER_LENGTH + IPV4_HEADER_LENGTH].to_vec();
+        frame[ETHERNET_HEADER_LENGTH] = 0x46u8; //version and header length, 6 words
+        frame[ETHERNET_HEADER_LENGTH + 3] = 0x30u8; //length, 24 bytes for header, 24 bytes for tcp
+        frame.extend_from_slice(&[0x01u8, 0x01u8, 0x01u8, 0x00u8]); //options, 3 no-ops and end of list
+        frame.extend_from_slice(&TCP_RAW_DATA[ETHERNET_HEADER_LENGTH + IPV4_HEADER_LENGTH..]);
+        let frame = fill_checksums(frame, IPV4_HEADER_LENGTH + 4);
+
+        let checksums = validate_ethernet(&frame).expect("Could not validate");
+
+        assert_eq!(checksums.ipv4, Some(ChecksumStatus::Valid));
+        assert_eq!(checksums.tcp, Some(ChecksumStatus::Valid));
+        assert_eq!(checksums.key.src_port, 50871);
+        assert_eq!(checksums.key.dst_port, 80);
+    }
+
+    #[test]
+    fn report_raw_ip() {
+        let _ = env_logger::try_init();
+
+        let packet = with_valid_checksums()[ETHERNET_HEADER_LENGTH..].to_vec();
+        let record = PcapRecord::new(std::time::UNIX_EPOCH, packet.len() as u32, packet.len() as u32, packet);
+
+        let mut report = ChecksumReport::for_header(&GlobalHeader::new(LinkType::Raw, 65535));
+        report.add_record(&record);
+
+        assert_eq!(report.ipv4().valid, 1);
+        assert_eq!(report.tcp().valid, 1);
+        assert_eq!(report.unparsed(), 0);
+    }
+
+    #[test]
+    fn validate_offloaded_checksums() {
+        let _ = env_logger::try_init();
+
+        let checksums = validate_ethernet(TCP_RAW_DATA).expect("Could not validate");
+
+        assert_eq!(checksums.ipv4, Some(ChecksumStatus::LikelyOffload));
+        assert_eq!(checksums.tcp, Some(ChecksumStatus::LikelyOffload));
+    }
+
+    #[test]
+    fn report_corrupt_flow() {
+        let _ = env_logger::try_init();
+
+        let valid = with_valid_checksums();
+        let mut corrupt = valid.clone();
+        let last = corrupt.len() - 1;
+        corrupt[last] ^= 0xFF;
+
+        let records = vec![
+            PcapRecord::new(std::time::UNIX_EPOCH, valid.len() as u32, valid.len() as u32, valid),
+            PcapRecord::new(std::time::UNIX_EPOCH, corrupt.len() as u32, corrupt.len() as u32, corrupt),
+            PcapRecord::new(std::time::UNIX_EPOCH, 2, 2, vec![0u8, 1u8])
+        ];
+
+        let report = ChecksumReport::from_records(&records);
+
+        assert_eq!(report.ipv4().valid, 2);
+        assert_eq!(report.tcp().valid, 1);
+        assert_eq!(report.tcp().invalid, 1);
+        assert_eq!(report.unparsed(), 1);
+        assert_eq!(report.flows().len(), 1);
+        assert_eq!(report.corrupt_flows().len(), 1);
+        assert_eq!(report.corrupt_flows()[0].1.total(), 4);
+    }
+}
@@ -0,0 +1,373 @@
+use super::prelude::*;
+use super::checksum::internet_checksum;
+use super::super::global_header::GlobalHeader;
+use super::super::parsed::{self, ParseConfig};
+
+use self::layer2::ethernet::{EthernetTypeId, Layer3Id};
+use self::layer3::InternetProtocolId;
+use self::layer3::ipv4::IPv4;
+
+use std;
+use std::collections::HashMap;
+
+///
+/// Time to wait for the rest of a datagram's fragments, as linux does
+///
+pub const DEFAULT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+const DONT_FRAGMENT_FLAG: u8 = 0x40;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct FragmentKey {
+    vlan_id: u16,
+    src_ip: std::net::IpAddr,
+    dst_ip: std::net::IpAddr,
+    protocol: InternetProtocolId,
+    id: u16
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct PendingDatagram {
+    first_seen: std::time::SystemTime,
+    ///
+    /// Link layer and ip headers of the first fragment, reused for the reassembled datagram
+    ///
+    headers: Option<(std::vec::Vec<u8>, std::vec::Vec<u8>)>,
+    fragments: std::vec::Vec<(usize, std::vec::Vec<u8>)>,
+    length: Option<usize>
+}
+
+impl PendingDatagram {
+    ///
+    /// Payload of the whole datagram, once every byte of it has arrived
+    ///
+    fn payload(&self) -> Option<std::vec::Vec<u8>> {
+        let length = self.length?;
+        let mut fragments = self.fragments.iter().collect::<std::vec::Vec<_>>();
+        fragments.sort_by_key(|&&(offset, _)| offset);
+
+        let mut payload = std::vec::Vec::with_capacity(length);
+        for &&(offset, ref data) in fragments.iter() {
+            if offset > payload.len() {
+                return None
+            }
+            let skip = payload.len() - offset;
+            if skip < data.len() {
+                payload.extend_from_slice(&data[skip..]);
+            }
+        }
+        if payload.len() < length {
+            return None
+        }
+        payload.truncate(length);
+        Some(payload)
+    }
+}
+
+///
+/// Packet passed on by a `Defragmenter`, either unfragmented or reassembled from `fragments` fragments
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Datagram {
+    pub record: PcapRecord,
+    pub fragments: usize
+}
+
+impl Datagram {
+    pub fn is_reassembled(&self) -> bool {
+        self.fragments > 1
+    }
+}
+
+///
+/// Reassembles fragmented ipv4 datagrams into a single record, holding fragments until the rest
+/// of their datagram arrives or `timeout` passes. Fragments are read with the layer 2 parser of
+/// their record's link type, or of the capture a defragmenter is made `for_header`. Records must be passed
+/// in capture order, as held fragments expire by the time of the records after them.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Defragmenter {
+    pending: HashMap<FragmentKey, PendingDatagram>,
+    timeout: std::time::Duration,
+    config: Option<ParseConfig>
+}
+
+impl Default for Defragmenter {
+    fn default() -> Defragmenter {
+        Defragmenter::with_timeout(DEFAULT_TIMEOUT)
+    }
+}
+
+///
+/// Fragment key, ip header and offset of the ip header for a record holding an ipv4 fragment,
+/// keyed on the vlan id of ethernet frames so retagging a vlan's priority doesn't split a datagram
+///
+fn fragment(record: &PcapRecord, config: &ParseConfig) -> Option<(FragmentKey, IPv4, usize)> {
+    let layer2 = parsed::parse_layer2(record.payload(), config, &mut vec![]).ok()?;
+    if layer2.ether_type() != Some(EthernetTypeId::L3(Layer3Id::IPv4)) {
+        return None
+    }
+    let (_, ipv4) = IPv4::parse(layer2.payload()).ok()?;
+    if !ipv4.is_fragment() {
+        return None
+    }
+    let key = FragmentKey {
+        vlan_id: layer2.ethernet().map(|e| e.vlan().id()).unwrap_or(0),
+        src_ip: *ipv4.src_ip(),
+        dst_ip: *ipv4.dst_ip(),
+        protocol: ipv4.protocol().clone(),
+        id: ipv4.id()
+    };
+    Some( (key, ipv4, layer2.header_length()) )
+}
+
+impl Defragmenter {
+    pub fn with_timeout(timeout: std::time::Duration) -> Defragmenter {
+        Defragmenter {
+            pending: HashMap::new(),
+            timeout: timeout,
+            config: None
+        }
+    }
+
+    ///
+    /// Defragmenter for records of the capture with global header `header`, parsed with the layer 2
+    /// parser of its link type. Other defragmenters parse each record with the layer 2 parser of
+    /// the record's own link type.
+    ///
+    pub fn for_header(header: &GlobalHeader) -> Defragmenter {
+        Defragmenter {
+            config: Some(ParseConfig::for_header(header)),
+            ..Defragmenter::default()
+        }
+    }
+
+    ///
+    /// Number of datagrams still missing fragments
+    ///
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    fn expire(&mut self, now: std::time::SystemTime) {
+        let timeout = self.timeout;
+        let before = self.pending.len();
+        self.pending.retain(|_, p| now.duration_since(p.first_seen).map(|age| age <= timeout).unwrap_or(true));
+        if self.pending.len() < before {
+            debug!("Dropped {} datagrams missing fragments", before - self.pending.len());
+        }
+    }
+
+    ///
+    /// Pass on a record that isn't a fragment, hold a fragment, or, for the fragment completing a
+    /// datagram, return the reassembled datagram with the time of that last fragment
+    ///
+    pub fn defragment(&mut self, record: PcapRecord) -> Option<Datagram> {
+        let timestamp = *record.timestamp();
+        self.expire(timestamp);
+
+        let config = self.config.unwrap_or_else(|| ParseConfig::for_link_type(record.link_type()));
+        let (key, ipv4, l3_offset) = match fragment(&record, &config) {
+            Some(fragment) => fragment,
+            None => return Some(Datagram {
+                record: record,
+                fragments: 1
+            })
+        };
+
+        let payload = {
+            let pending = self.pending.entry(key.clone()).or_insert_with(|| PendingDatagram {
+                first_seen: timestamp,
+                headers: None,
+                fragments: vec![],
+                length: None
+            });
+            let offset = ipv4.fragment_offset();
+            if offset == 0 {
+                let headers = record.payload();
+                pending.headers = Some( (headers[..l3_offset].to_vec(), headers[l3_offset..l3_offset + ipv4.header_length()].to_vec()) );
+            }
+            if !ipv4.more_fragments() {
+                pending.length = Some(offset + ipv4.payload().len());
+            }
+            pending.fragments.push( (offset, ipv4.payload().clone()) );
+
+            match (&pending.headers, pending.payload()) {
+                (&Some(_), Some(payload)) => payload,
+                _ => return None
+            }
+        };
+
+        let pending = self.pending.remove(&key)?;
+        let (mut frame, mut ip_header) = pending.headers?;
+        let total_length = ip_header.len() + payload.len();
+        if total_length > std::u16::MAX as usize {
+            debug!("Dropped reassembled datagram of {} bytes", total_length);
+            return None
+        }
+
+        ip_header[2] = (total_length >> 8) as u8;
+        ip_header[3] = total_length as u8;
+        ip_header[6] &= DONT_FRAGMENT_FLAG;
+        ip_header[7] = 0;
+        ip_header[10] = 0;
+        ip_header[11] = 0;
+        let checksum = internet_checksum(&ip_header);
+        ip_header[10] = (checksum >> 8) as u8;
+        ip_header[11] = checksum as u8;
+
+        frame.extend_from_slice(&ip_header);
+        frame.extend_from_slice(&payload);
+        let mut reassembled = PcapRecord::new(timestamp, frame.len() as u32, frame.len() as u32, frame);
+        reassembled.set_interface_id(record.interface_id());
+        reassembled.set_link_type(record.link_type());
+        reassembled.set_timestamp_resolution(record.timestamp_resolution());
+        Some(Datagram {
+            record: reassembled,
+            fragments: pending.fragments.len()
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    extern crate env_logger;
+
+    use super::*;
+    use super::super::super::link_type::LinkType;
+    use super::super::super::parsed::ParsedRecord;
+
+    ///
+    /// Udp datagram from 10.0.0.1:5000 to 10.0.0.2:53 carrying `data`, split into ipv4 fragments of
+    /// at most `size` bytes (a multiple of 8)
+    ///
+    pub fn fragments(seconds: u32, id: u16, data: &[u8], size: usize) -> std::vec::Vec<PcapRecord> {
+        let udp_length = 8 + data.len();
+        let mut datagram = vec![
+            0x13u8, 0x88u8, //src port, 5000
+            0x00u8, 0x35u8, //dst port, 53
+            (udp_length >> 8) as u8, udp_length as u8, //length
+            0x00u8, 0x00u8 //checksum
+        ];
+        datagram.extend_from_slice(data);
+
+        datagram.chunks(size).enumerate().map(|(idx, chunk)| {
+            let ip_length = 20 + chunk.len();
+            let offset = idx * size / 8;
+            let more = if (idx + 1) * size < datagram.len() { 0x20u8 } else { 0x00u8 };
+            let mut payload = vec![
+                0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x02u8, //dst mac
+                0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x01u8, //src mac
+                0x08u8, 0x00u8, //ipv4
+                //ipv4
+                0x45u8, //version, header length
+                0x00u8, //tos
+                (ip_length >> 8) as u8, ip_length as u8, //total length
+                (id >> 8) as u8, id as u8, //id
+                more | (offset >> 8) as u8, offset as u8, //flags, fragment offset
+                0x40u8, //ttl
+                0x11u8, //udp
+                0x00u8, 0x00u8, //checksum
+                0x0Au8, 0x00u8, 0x00u8, 0x01u8, //src ip
+                0x0Au8, 0x00u8, 0x00u8, 0x02u8 //dst ip
+            ];
+            payload.extend_from_slice(chunk);
+            PcapRecord::new(PcapRecord::convert_packet_time(seconds, idx as u32), payload.len() as u32, payload.len() as u32, payload)
+        }).collect()
+    }
+
+    #[test]
+    fn defragment_out_of_order() {
+        let _ = env_logger::try_init();
+
+        let data = (0..40u8).collect::<std::vec::Vec<_>>();
+        let mut records = fragments(1, 7, &data, 16);
+        assert_eq!(records.len(), 3);
+        records.swap(0, 2);
+
+        let mut defragmenter = Defragmenter::default();
+        assert_eq!(defragmenter.defragment(records[0].clone()), None);
+        assert_eq!(defragmenter.defragment(records[1].clone()), None);
+        assert_eq!(defragmenter.pending(), 1);
+
+        let datagram = defragmenter.defragment(records[2].clone()).expect("Not reassembled");
+        assert_eq!(defragmenter.pending(), 0);
+        assert!(datagram.is_reassembled());
+        assert_eq!(datagram.fragments, 3);
+        assert_eq!(datagram.record.payload().len(), 14 + 20 + 8 + 40);
+        assert_eq!(internet_checksum(&datagram.record.payload()[14..34]), 0);
+
+        let parsed = ParsedRecord::try_from(datagram.record).expect("Could not parse reassembled datagram");
+        assert!(parsed.layer4().is_some());
+        assert_eq!(parsed.payload(), &data[..]);
+    }
+
+    #[test]
+    fn defragment_timeout() {
+        let _ = env_logger::try_init();
+
+        let records = fragments(1, 7, &[0u8; 24], 16);
+        let unfragmented = fragments(100, 8, &[0u8; 8], 16);
+
+        let mut defragmenter = Defragmenter::default();
+        assert_eq!(defragmenter.defragment(records[0].clone()), None);
+
+        let datagram = defragmenter.defragment(unfragmented[0].clone()).expect("Held unfragmented packet");
+        assert!(!datagram.is_reassembled());
+        assert_eq!(defragmenter.pending(), 0);
+        assert_eq!(defragmenter.defragment(records[1].clone()), None);
+    }
+
+    #[test]
+    fn defragment_vlan_priority() {
+        let _ = env_logger::try_init();
+
+        let tagged = |pcp: u8, id: u8, record: &PcapRecord| {
+            let mut payload = record.payload().clone();
+            payload.splice(12..12, vec![0x81u8, 0x00u8, pcp, id]);
+            PcapRecord::new(*record.timestamp(), payload.len() as u32, payload.len() as u32, payload)
+        };
+        let records = fragments(1, 7, &[0u8; 24], 16);
+
+        //fragments retagged with another priority on the way are of one datagram
+        let mut defragmenter = Defragmenter::default();
+        assert_eq!(defragmenter.defragment(tagged(0x00u8, 0x64u8, &records[0])), None);
+        let datagram = defragmenter.defragment(tagged(0xA0u8, 0x64u8, &records[1])).expect("Not reassembled");
+        assert_eq!(datagram.fragments, 2);
+        assert_eq!(datagram.record.payload()[14], 0x00u8);
+
+        //fragments of another vlan are of another datagram
+        let mut defragmenter = Defragmenter::default();
+        assert_eq!(defragmenter.defragment(tagged(0x00u8, 0x64u8, &records[0])), None);
+        assert_eq!(defragmenter.defragment(tagged(0x00u8, 0x65u8, &records[1])), None);
+        assert_eq!(defragmenter.pending(), 2);
+    }
+
+    #[test]
+    fn defragment_raw_ip() {
+        let _ = env_logger::try_init();
+
+        let header = GlobalHeader::new(LinkType::Raw, 65535);
+        let records = fragments(1, 7, &[0u8; 24], 16).into_iter().map(|record| {
+            let payload = record.payload()[14..].to_vec();
+            PcapRecord::new(*record.timestamp(), payload.len() as u32, payload.len() as u32, payload).with_header(&header)
+        }).collect::<std::vec::Vec<_>>();
+
+        //fragments without ethernet headers pass through a defragmenter for ethernet
+        let datagram = Defragmenter::for_header(&GlobalHeader::new(LinkType::Ethernet, 65535)).defragment(records[0].clone()).expect("Held raw ip fragment");
+        assert!(!datagram.is_reassembled());
+
+        //records carry their link type, so a default defragmenter reassembles them too
+        let mut defragmenter = Defragmenter::default();
+        assert_eq!(defragmenter.defragment(records[0].clone()), None);
+        assert!(defragmenter.defragment(records[1].clone()).expect("Not reassembled").is_reassembled());
+
+        let mut defragmenter = Defragmenter::for_header(&header);
+        assert_eq!(defragmenter.defragment(records[0].clone()), None);
+        let datagram = defragmenter.defragment(records[1].clone()).expect("Not reassembled");
+        assert_eq!(datagram.fragments, 2);
+        assert_eq!(datagram.record.payload().len(), 20 + 8 + 24);
+        assert_eq!(datagram.record.link_type(), LinkType::Raw);
+        assert_eq!(internet_checksum(&datagram.record.payload()[..20]), 0);
+    }
+}
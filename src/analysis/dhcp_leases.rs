@@ -0,0 +1,306 @@
+use super::prelude::*;
+
+use self::layer2::ethernet::{Ethernet, EthernetTypeId, Layer3Id};
+use self::layer3::InternetProtocolId;
+use self::layer3::ipv4::IPv4;
+use self::layer4::udp::Udp;
+use self::layer7::dhcp::{Dhcp, DhcpMessageType};
+
+use std;
+use std::collections::HashMap;
+
+///
+/// A single DISCOVER/OFFER/REQUEST/ACK exchange, identified by client mac and transaction id
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Exchange {
+    pub client_mac: MacAddress,
+    pub xid: u32,
+    pub hostname: Option<String>,
+    pub offered_ip: Option<std::net::Ipv4Addr>,
+    pub server: Option<std::net::Ipv4Addr>,
+    pub discovered: Option<std::time::SystemTime>,
+    pub offered: Option<std::time::SystemTime>,
+    pub requested: Option<std::time::SystemTime>,
+    pub acknowledged: Option<std::time::SystemTime>,
+    ///
+    /// Time the server refused the request (NAK) or the client declined the offer (DECLINE)
+    ///
+    pub rejected: Option<std::time::SystemTime>
+}
+
+impl Exchange {
+    fn new(client_mac: MacAddress, xid: u32) -> Exchange {
+        Exchange {
+            client_mac: client_mac,
+            xid: xid,
+            hostname: None,
+            offered_ip: None,
+            server: None,
+            discovered: None,
+            offered: None,
+            requested: None,
+            acknowledged: None,
+            rejected: None
+        }
+    }
+}
+
+///
+/// Address assigned to a client by an acknowledged exchange
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Lease {
+    pub client_mac: MacAddress,
+    pub ip: std::net::Ipv4Addr,
+    pub hostname: Option<String>,
+    pub lease_time: Option<std::time::Duration>,
+    pub server: Option<std::net::Ipv4Addr>,
+    pub xid: u32,
+    pub acknowledged: std::time::SystemTime,
+    pub released: Option<std::time::SystemTime>
+}
+
+impl Lease {
+    pub fn expires(&self) -> Option<std::time::SystemTime> {
+        self.lease_time.map(|t| self.acknowledged + t)
+    }
+}
+
+///
+/// DHCP leases observed in a capture, the latest acknowledged lease per client
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DhcpLeases {
+    exchanges: HashMap<(MacAddress, u32), Exchange>,
+    leases: HashMap<MacAddress, Lease>
+}
+
+fn parse_dhcp(record: &PcapRecord) -> Option<Dhcp> {
+    let l2 = match Ethernet::parse(record.payload()) {
+        Ok( (_, l2) ) => l2,
+        Err(e) => {
+            debug!("Could not parse ethernet: {:?}", e);
+            return None
+        }
+    };
+    if *l2.ether_type() != EthernetTypeId::L3(Layer3Id::IPv4) {
+        return None
+    }
+    let l3 = IPv4::parse(l2.payload()).ok().map(|(_, l3)| l3).filter(|l3| *l3.protocol() == InternetProtocolId::Udp)?;
+    let l4 = Udp::parse(l3.payload()).ok().map(|(_, l4)| l4).filter(|l4| Dhcp::is_dhcp_port(l4))?;
+
+    match Dhcp::parse(l4.payload()) {
+        Ok( (_, dhcp) ) => Some(dhcp),
+        Err(e) => {
+            debug!("Could not parse dhcp: {:?}", e);
+            None
+        }
+    }
+}
+
+impl DhcpLeases {
+    ///
+    /// Current leases, ordered by time acknowledged
+    ///
+    pub fn leases(&self) -> std::vec::Vec<&Lease> {
+        let mut leases = self.leases.values().collect::<std::vec::Vec<_>>();
+        leases.sort_by(|a, b| a.acknowledged.cmp(&b.acknowledged));
+        leases
+    }
+
+    pub fn lease(&self, client_mac: &MacAddress) -> Option<&Lease> {
+        self.leases.get(client_mac)
+    }
+
+    ///
+    /// All exchanges, ordered by the time of their first message
+    ///
+    pub fn exchanges(&self) -> std::vec::Vec<&Exchange> {
+        let mut exchanges = self.exchanges.values().collect::<std::vec::Vec<_>>();
+        exchanges.sort_by_key(|e| {
+            vec![e.discovered, e.offered, e.requested, e.acknowledged, e.rejected].into_iter().filter_map(|t| t).min()
+        });
+        exchanges
+    }
+
+    ///
+    /// Exchanges that never received an ACK
+    ///
+    pub fn pending(&self) -> std::vec::Vec<&Exchange> {
+        self.exchanges().into_iter().filter(|e| e.acknowledged.is_none()).collect()
+    }
+
+    fn observe(&mut self, dhcp: Dhcp, timestamp: std::time::SystemTime) {
+        let message_type = match dhcp.message_type() {
+            Some(t) => t,
+            None => {
+                debug!("Bootp message {} without dhcp message type", dhcp.xid());
+                return
+            }
+        };
+        let client_mac = *dhcp.client_mac();
+
+        if message_type == DhcpMessageType::Release {
+            if let Some(lease) = self.leases.get_mut(&client_mac) {
+                lease.released = Some(timestamp);
+            }
+            return
+        }
+        if message_type == DhcpMessageType::Inform {
+            return
+        }
+
+        let exchange = self.exchanges.entry( (client_mac, dhcp.xid()) ).or_insert_with(|| Exchange::new(client_mac, dhcp.xid()));
+        if let Some(hostname) = dhcp.hostname() {
+            exchange.hostname = Some(hostname);
+        }
+        if let Some(server) = dhcp.server_identifier() {
+            exchange.server = Some(server);
+        }
+
+        match message_type {
+            DhcpMessageType::Discover => exchange.discovered = Some(timestamp),
+            DhcpMessageType::Offer => {
+                exchange.offered = Some(timestamp);
+                exchange.offered_ip = Some(*dhcp.your_ip());
+            }
+            DhcpMessageType::Request => exchange.requested = Some(timestamp),
+            DhcpMessageType::Ack => {
+                exchange.acknowledged = Some(timestamp);
+                if dhcp.your_ip().is_unspecified() {
+                    //ack to an inform, no address was assigned
+                    return
+                }
+                self.leases.insert(client_mac, Lease {
+                    client_mac: client_mac,
+                    ip: *dhcp.your_ip(),
+                    hostname: exchange.hostname.clone(),
+                    lease_time: dhcp.lease_time(),
+                    server: exchange.server,
+                    xid: dhcp.xid(),
+                    acknowledged: timestamp,
+                    released: None
+                });
+            }
+            DhcpMessageType::Nak | DhcpMessageType::Decline => exchange.rejected = Some(timestamp),
+            _ => {}
+        }
+    }
+
+    pub fn add_record(&mut self, record: &PcapRecord) {
+        if let Some(dhcp) = parse_dhcp(record) {
+            self.observe(dhcp, *record.timestamp());
+        }
+    }
+
+    pub fn from_records<'a, I>(records: I) -> DhcpLeases where I: IntoIterator<Item=&'a PcapRecord> {
+        let mut leases = DhcpLeases::default();
+        for record in records {
+            leases.add_record(record);
+        }
+        leases
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+    use layer7::dhcp::tests::message;
+
+    fn dhcp_record(seconds: u32, op: u8, xid: u32, your_ip: [u8; 4], options: &[u8]) -> PcapRecord {
+        let dhcp = message(op, xid, your_ip, options);
+        let udp_length = 8 + dhcp.len();
+        let ip_length = 20 + udp_length;
+        let mut payload = vec![
+            0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8, //dst mac, broadcast
+            0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x01u8, //src mac
+            0x08u8, 0x00u8, //ipv4
+            //ipv4
+            0x45u8, //version, header length
+            0x00u8, //tos
+            (ip_length >> 8) as u8, ip_length as u8, //total length
+            0x00u8, 0x00u8, //id
+            0x00u8, 0x00u8, //flags, fragment offset
+            0x40u8, //ttl
+            0x11u8, //udp
+            0x00u8, 0x00u8, //checksum
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, //src ip 0.0.0.0
+            0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8, //dst ip 255.255.255.255
+            //udp
+            0x00u8, 0x44u8, //src port 68
+            0x00u8, 0x43u8, //dst port 67
+            (udp_length >> 8) as u8, udp_length as u8, //length
+            0x00u8, 0x00u8 //checksum
+        ];
+        payload.extend_from_slice(&dhcp);
+        PcapRecord::new(PcapRecord::convert_packet_time(seconds, 0), payload.len() as u32, payload.len() as u32, payload)
+    }
+
+    const SERVER: [u8; 6] = [0x36u8, 0x04u8, 0xC0u8, 0xA8u8, 0x01u8, 0x01u8]; //server identifier 192.168.1.1
+
+    #[test]
+    fn dhcp_lease() {
+        let _ = env_logger::try_init();
+
+        let records = vec![
+            dhcp_record(1, 1, 0x10, [0, 0, 0, 0], &[
+                0x35u8, 0x01u8, 0x01u8, //discover
+                0x0Cu8, 0x04u8, b'h', b'o', b's', b't' //hostname
+            ]),
+            dhcp_record(2, 2, 0x10, [192, 168, 1, 100], &[0x35u8, 0x01u8, 0x02u8, SERVER[0], SERVER[1], SERVER[2], SERVER[3], SERVER[4], SERVER[5]]),
+            dhcp_record(3, 1, 0x10, [0, 0, 0, 0], &[
+                0x35u8, 0x01u8, 0x03u8, //request
+                0x32u8, 0x04u8, 0xC0u8, 0xA8u8, 0x01u8, 0x64u8 //requested ip 192.168.1.100
+            ]),
+            dhcp_record(4, 2, 0x10, [192, 168, 1, 100], &[
+                0x35u8, 0x01u8, 0x05u8, //ack
+                0x33u8, 0x04u8, 0x00u8, 0x00u8, 0x0Eu8, 0x10u8 //lease time, 3600
+            ])
+        ];
+        let leases = DhcpLeases::from_records(&records);
+
+        let mac = MacAddress([0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x01u8]);
+        let lease = leases.lease(&mac).expect("No lease");
+
+        assert_eq!(leases.leases().len(), 1);
+        assert_eq!(lease.ip, std::net::Ipv4Addr::new(192, 168, 1, 100));
+        assert_eq!(lease.hostname, Some("host".to_string()));
+        assert_eq!(lease.server, Some(std::net::Ipv4Addr::new(192, 168, 1, 1)));
+        assert_eq!(lease.lease_time, Some(std::time::Duration::from_secs(3600)));
+        assert_eq!(lease.acknowledged, PcapRecord::convert_packet_time(4, 0));
+        assert_eq!(lease.expires(), Some(PcapRecord::convert_packet_time(3604, 0)));
+        assert!(leases.pending().is_empty());
+
+        let exchange = leases.exchanges()[0];
+        assert_eq!(exchange.xid, 0x10);
+        assert_eq!(exchange.offered_ip, Some(std::net::Ipv4Addr::new(192, 168, 1, 100)));
+        assert_eq!(exchange.discovered, Some(PcapRecord::convert_packet_time(1, 0)));
+    }
+
+    #[test]
+    fn dhcp_pending_and_released() {
+        let _ = env_logger::try_init();
+
+        let records = vec![
+            dhcp_record(1, 1, 0x10, [0, 0, 0, 0], &[0x35u8, 0x01u8, 0x03u8]), //request
+            dhcp_record(2, 2, 0x10, [0, 0, 0, 0], &[0x35u8, 0x01u8, 0x06u8]), //nak
+            dhcp_record(3, 1, 0x20, [0, 0, 0, 0], &[0x35u8, 0x01u8, 0x03u8]), //request
+            dhcp_record(4, 2, 0x20, [192, 168, 1, 101], &[0x35u8, 0x01u8, 0x05u8]), //ack
+            dhcp_record(5, 1, 0x30, [0, 0, 0, 0], &[0x35u8, 0x01u8, 0x07u8]) //release
+        ];
+        let leases = DhcpLeases::from_records(&records);
+
+        let pending = leases.pending();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].xid, 0x10);
+        assert_eq!(pending[0].rejected, Some(PcapRecord::convert_packet_time(2, 0)));
+
+        let mac = MacAddress([0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x01u8]);
+        let lease = leases.lease(&mac).expect("No lease");
+        assert_eq!(lease.ip, std::net::Ipv4Addr::new(192, 168, 1, 101));
+        assert_eq!(lease.released, Some(PcapRecord::convert_packet_time(5, 0)));
+    }
+}
@@ -0,0 +1,245 @@
+use super::prelude::*;
+use super::super::flow::FlowKey;
+use super::super::parsed::ParsedRecord;
+
+use self::layer3::Ecn;
+use self::layer4::Layer4;
+use self::layer4::tcp::{FLAG_ACK, FLAG_CWR, FLAG_ECE, FLAG_SYN};
+
+use std;
+use std::collections::HashMap;
+
+///
+/// Ecn codepoints and tcp congestion signals seen in one direction of a flow
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct EcnCounts {
+    pub not_ect: usize,
+    pub ect0: usize,
+    pub ect1: usize,
+    ///
+    /// Packets marked congestion experienced by a router along the path
+    ///
+    pub ce: usize,
+    ///
+    /// Tcp segments echoing congestion back to the sender, outside the handshake
+    ///
+    pub ece: usize,
+    ///
+    /// Tcp segments acknowledging an echo by reducing the congestion window, outside the handshake
+    ///
+    pub cwr: usize
+}
+
+impl EcnCounts {
+    fn add(&mut self, ecn: Ecn) {
+        match ecn {
+            Ecn::NotEct => self.not_ect += 1,
+            Ecn::Ect0 => self.ect0 += 1,
+            Ecn::Ect1 => self.ect1 += 1,
+            Ecn::Ce => self.ce += 1
+        }
+    }
+
+    pub fn packets(&self) -> usize {
+        self.not_ect + self.ect0 + self.ect1 + self.ce
+    }
+}
+
+///
+/// Congestion signalling on a flow, keyed from the initiating side
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FlowEcn {
+    pub key: FlowKey,
+    pub client: EcnCounts,
+    pub server: EcnCounts,
+    ///
+    /// Client SYN set ECE and CWR, offering ecn, for tcp flows
+    ///
+    pub offered: bool,
+    ///
+    /// Server SYN-ACK set ECE, agreeing to ecn, for tcp flows
+    ///
+    pub accepted: bool
+}
+
+impl FlowEcn {
+    pub fn negotiated(&self) -> bool {
+        self.offered && self.accepted
+    }
+
+    pub fn congestion_experienced(&self) -> usize {
+        self.client.ce + self.server.ce
+    }
+}
+
+///
+/// Ecn markings and tcp ECE/CWR flags per tcp and udp flow, e.g. to check an active queue
+/// management deployment is marking rather than dropping
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EcnAnalysis {
+    flows: HashMap<FlowKey, FlowEcn>
+}
+
+impl EcnAnalysis {
+    ///
+    /// All flows, in no particular order
+    ///
+    pub fn flows(&self) -> std::vec::Vec<&FlowEcn> {
+        self.flows.values().collect()
+    }
+
+    ///
+    /// Signalling for the flow `key` belongs to, in either direction
+    ///
+    pub fn flow(&self, key: &FlowKey) -> Option<&FlowEcn> {
+        self.flows.get(key).or_else(|| self.flows.get(&key.reversed()))
+    }
+
+    ///
+    /// Packets marked congestion experienced across all flows
+    ///
+    pub fn congestion_experienced(&self) -> usize {
+        self.flows.values().map(|f| f.congestion_experienced()).sum()
+    }
+
+    ///
+    /// Flows with at least one packet marked congestion experienced
+    ///
+    pub fn congested_flows(&self) -> std::vec::Vec<&FlowEcn> {
+        self.flows.values().filter(|f| f.congestion_experienced() > 0).collect()
+    }
+
+    pub fn add_record(&mut self, record: &PcapRecord) {
+        let parsed = match ParsedRecord::try_from(record.clone()) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                debug!("Could not parse record: {:?}", e);
+                return
+            }
+        };
+        let key = match parsed.flow_key() {
+            Ok(key) => key,
+            Err(e) => {
+                debug!("Record is not part of a flow: {:?}", e);
+                return
+            }
+        };
+        let ecn = match parsed.layer3() {
+            Some(l3) => l3.ecn(),
+            None => return
+        };
+        let tcp = match parsed.layer4() {
+            Some(&Layer4::Tcp(ref tcp)) => Some(tcp),
+            _ => None
+        };
+
+        let reverse = key.reversed();
+        let to_server = if self.flows.contains_key(&key) {
+            true
+        } else if self.flows.contains_key(&reverse) {
+            false
+        } else {
+            //the sender of a syn ack is the server, otherwise assume the first sender seen is the client
+            !tcp.map(|t| t.has_flags(FLAG_SYN | FLAG_ACK)).unwrap_or(false)
+        };
+        let flow_key = if to_server { key } else { reverse };
+
+        let flow = self.flows.entry(flow_key.clone()).or_insert_with(|| FlowEcn {
+            key: flow_key,
+            client: EcnCounts::default(),
+            server: EcnCounts::default(),
+            offered: false,
+            accepted: false
+        });
+        let counts = if to_server { &mut flow.client } else { &mut flow.server };
+        counts.add(ecn);
+
+        if let Some(tcp) = tcp {
+            if tcp.has_flags(FLAG_SYN | FLAG_ACK) {
+                flow.accepted = tcp.has_flags(FLAG_ECE) && !tcp.has_flags(FLAG_CWR);
+            } else if tcp.has_flags(FLAG_SYN) {
+                flow.offered = tcp.has_flags(FLAG_ECE | FLAG_CWR);
+            } else {
+                if tcp.has_flags(FLAG_ECE) {
+                    counts.ece += 1;
+                }
+                if tcp.has_flags(FLAG_CWR) {
+                    counts.cwr += 1;
+                }
+            }
+        }
+    }
+
+    pub fn from_records<'a, I>(records: I) -> EcnAnalysis where I: IntoIterator<Item=&'a PcapRecord> {
+        let mut analysis = EcnAnalysis::default();
+        for record in records {
+            analysis.add_record(record);
+        }
+        analysis
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+    use super::super::reassembly::tests::tcp_record;
+
+    const TOS_OFFSET: usize = 15;
+
+    ///
+    /// Set the ecn codepoint of `record`
+    ///
+    fn marked(record: PcapRecord, ecn: u8) -> PcapRecord {
+        let mut payload = record.payload().clone();
+        payload[TOS_OFFSET] = ecn;
+        PcapRecord::new(*record.timestamp(), payload.len() as u32, payload.len() as u32, payload)
+    }
+
+    #[test]
+    fn ecn_signals() {
+        let _ = env_logger::try_init();
+
+        let records = vec![
+            tcp_record(1, true, 1, 0xC2, &[]), //syn, ece, cwr
+            tcp_record(2, false, 1, 0x52, &[]), //syn ack, ece
+            tcp_record(3, true, 2, 0x10, &[]), //ack
+            marked(tcp_record(4, true, 2, 0x18, b"data"), 0x02), //ect(0)
+            marked(tcp_record(5, true, 6, 0x18, b"data"), 0x03), //ce
+            marked(tcp_record(6, false, 2, 0x50, &[]), 0x02), //ack, ece
+            marked(tcp_record(7, true, 10, 0x98, b"data"), 0x02) //psh ack, cwr
+        ];
+        let analysis = EcnAnalysis::from_records(&records);
+
+        let flows = analysis.flows();
+        assert_eq!(flows.len(), 1);
+        let flow = flows[0];
+        assert_eq!(flow.key.dst_port, 80);
+        assert!(flow.negotiated());
+        assert_eq!(flow.client, EcnCounts { not_ect: 2, ect0: 2, ect1: 0, ce: 1, ece: 0, cwr: 1 });
+        assert_eq!(flow.server, EcnCounts { not_ect: 1, ect0: 1, ect1: 0, ce: 0, ece: 1, cwr: 0 });
+        assert_eq!(analysis.congestion_experienced(), 1);
+        assert_eq!(analysis.congested_flows().len(), 1);
+        assert_eq!(analysis.flow(&flow.key.reversed()), Some(flow));
+    }
+
+    #[test]
+    fn ecn_not_negotiated() {
+        let _ = env_logger::try_init();
+
+        let records = vec![
+            tcp_record(1, true, 1, 0xC2, &[]), //syn, ece, cwr
+            tcp_record(2, false, 1, 0x12, &[]) //syn ack
+        ];
+        let analysis = EcnAnalysis::from_records(&records);
+
+        let flows = analysis.flows();
+        assert!(flows[0].offered);
+        assert!(!flows[0].negotiated());
+        assert_eq!(analysis.congestion_experienced(), 0);
+    }
+}
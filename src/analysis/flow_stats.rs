@@ -0,0 +1,1286 @@
+use super::prelude::*;
+use super::super::flow::{FlowKey, FlowKeying};
+#[cfg(feature = "tunnels")]
+use super::super::tunnel::{TunnelInfo, TunnelType};
+use super::super::parsed::ParsedRecord;
+use super::defrag::Defragmenter;
+
+use self::layer3::{InternetProtocolId, Layer3};
+use self::layer4::Layer4;
+#[cfg(feature = "icmp")]
+use self::layer4::icmp::{Icmp, IcmpError};
+use self::layer4::tcp::{Tcp, FLAG_ACK, FLAG_FIN, FLAG_RST, FLAG_SYN};
+use self::layer7::detect::{self, Protocol};
+
+use std;
+use std::collections::{BTreeMap, HashMap};
+
+///
+/// How the tcp three way handshake of a connection went
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HandshakeOutcome {
+    ///
+    /// SYN, SYN-ACK and the final ACK were all seen
+    ///
+    Completed,
+    ///
+    /// SYN was never answered, e.g. a filtered port or unreachable host
+    ///
+    Unanswered,
+    ///
+    /// SYN was answered with a RST, i.e. the port is closed
+    ///
+    Refused,
+    ///
+    /// SYN-ACK was seen without the client's final ACK
+    ///
+    Unacknowledged,
+    ///
+    /// No SYN or SYN-ACK was seen, the connection began before the capture
+    ///
+    Midstream
+}
+
+///
+/// How far a tcp connection got in closing
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CloseState {
+    Open,
+    ///
+    /// Only one side sent a FIN
+    ///
+    HalfClosed,
+    Closed,
+    Reset
+}
+
+///
+/// Tcp control segments seen on a connection
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct TcpObservations {
+    pub syn: bool,
+    pub syn_ack: bool,
+    pub handshake_ack: bool,
+    pub client_fin: bool,
+    pub server_fin: bool,
+    pub client_rst: bool,
+    pub server_rst: bool
+}
+
+impl TcpObservations {
+    fn observe(&mut self, tcp: &Tcp, to_server: bool) {
+        if tcp.has_flags(FLAG_SYN | FLAG_ACK) {
+            self.syn_ack = true;
+        } else if tcp.has_flags(FLAG_SYN) {
+            self.syn = true;
+        } else if to_server && tcp.has_flags(FLAG_ACK) && !tcp.has_flags(FLAG_RST) && self.syn_ack {
+            self.handshake_ack = true;
+        }
+        if tcp.has_flags(FLAG_FIN) {
+            if to_server { self.client_fin = true } else { self.server_fin = true }
+        }
+        if tcp.has_flags(FLAG_RST) {
+            if to_server { self.client_rst = true } else { self.server_rst = true }
+        }
+    }
+
+    pub fn handshake(&self) -> HandshakeOutcome {
+        match (self.syn, self.syn_ack, self.handshake_ack) {
+            (_, true, true) => HandshakeOutcome::Completed,
+            (_, true, false) => HandshakeOutcome::Unacknowledged,
+            (true, false, _) if self.server_rst => HandshakeOutcome::Refused,
+            (true, false, _) => HandshakeOutcome::Unanswered,
+            (false, false, _) => HandshakeOutcome::Midstream
+        }
+    }
+
+    pub fn close_state(&self) -> CloseState {
+        if self.client_rst || self.server_rst {
+            CloseState::Reset
+        } else if self.client_fin && self.server_fin {
+            CloseState::Closed
+        } else if self.client_fin || self.server_fin {
+            CloseState::HalfClosed
+        } else {
+            CloseState::Open
+        }
+    }
+}
+
+///
+/// How long a receiver has to advertise a zero window before it is considered stalled
+///
+pub const STALLED_RECEIVER_THRESHOLD: std::time::Duration = std::time::Duration::from_secs(1);
+
+///
+/// Receive windows advertised by one side of a tcp connection, scaled once both sides have offered
+/// window scaling
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct WindowStats {
+    ///
+    /// Shift count from the window scale option of this side's syn
+    ///
+    pub scale: Option<u8>,
+    pub min_window: Option<u32>,
+    pub max_window: Option<u32>,
+    ///
+    /// Times the window closed to zero, and how long it stayed closed before reopening
+    ///
+    pub zero_windows: usize,
+    pub zero_window_time: std::time::Duration,
+    pub longest_zero_window: std::time::Duration,
+    zero_since: Option<std::time::SystemTime>
+}
+
+impl WindowStats {
+    fn observe(&mut self, window: u32, timestamp: std::time::SystemTime) {
+        self.min_window = Some(self.min_window.map_or(window, |w| std::cmp::min(w, window)));
+        self.max_window = Some(self.max_window.map_or(window, |w| std::cmp::max(w, window)));
+        match (window, self.zero_since) {
+            (0, None) => {
+                self.zero_windows += 1;
+                self.zero_since = Some(timestamp);
+            }
+            (0, Some(_)) => {}
+            (_, Some(since)) => {
+                let closed = timestamp.duration_since(since).unwrap_or_default();
+                self.zero_window_time += closed;
+                self.longest_zero_window = std::cmp::max(self.longest_zero_window, closed);
+                self.zero_since = None;
+            }
+            (_, None) => {}
+        }
+    }
+
+    ///
+    /// When the window closed, if it has not reopened since
+    ///
+    pub fn zero_window_since(&self) -> Option<std::time::SystemTime> {
+        self.zero_since
+    }
+
+    ///
+    /// Receiver kept its window closed for at least `STALLED_RECEIVER_THRESHOLD`, or never reopened it
+    ///
+    pub fn is_stalled(&self) -> bool {
+        self.zero_since.is_some() || self.longest_zero_window >= STALLED_RECEIVER_THRESHOLD
+    }
+}
+
+///
+/// Receive windows advertised by each side of a tcp connection
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct WindowObservations {
+    pub client: WindowStats,
+    pub server: WindowStats
+}
+
+impl WindowObservations {
+    fn observe(&mut self, tcp: &Tcp, to_server: bool, timestamp: std::time::SystemTime) {
+        //a reset carries no meaningful window
+        if tcp.has_flags(FLAG_RST) {
+            return
+        }
+        //windows are never scaled on syns, and only scaled at all when both sides offered scaling
+        //https://tools.ietf.org/html/rfc7323#section-2.2
+        let syn = tcp.has_flags(FLAG_SYN);
+        let both_scaled = self.client.scale.is_some() && self.server.scale.is_some();
+        let sender = if to_server { &mut self.client } else { &mut self.server };
+        if syn {
+            sender.scale = tcp.window_scale();
+        }
+        let shift = match (syn, both_scaled) {
+            (false, true) => sender.scale.unwrap_or(0),
+            _ => 0
+        };
+        sender.observe((tcp.window() as u32) << shift, timestamp);
+    }
+
+    ///
+    /// Either side stalled with a closed window
+    ///
+    pub fn is_stalled(&self) -> bool {
+        self.client.is_stalled() || self.server.is_stalled()
+    }
+}
+
+///
+/// Icmp error reported about one of a flow's datagrams
+///
+#[cfg(feature = "icmp")]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct IcmpReport {
+    pub timestamp: std::time::SystemTime,
+    ///
+    /// Router or host that sent the error
+    ///
+    pub reporter: std::net::IpAddr,
+    pub error: IcmpError
+}
+
+///
+/// Packet and byte counts for both directions of a flow, keyed from the initiating side
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FlowStats {
+    pub key: FlowKey,
+    pub first_seen: std::time::SystemTime,
+    pub last_seen: std::time::SystemTime,
+    pub client_packets: usize,
+    pub client_bytes: usize,
+    pub server_packets: usize,
+    pub server_bytes: usize,
+    ///
+    /// Control segments seen, for tcp flows
+    ///
+    pub tcp: Option<TcpObservations>,
+    ///
+    /// Receive windows advertised, for tcp flows
+    ///
+    pub window: Option<WindowObservations>,
+    ///
+    /// Icmp errors quoting the flow's datagrams, in either direction
+    ///
+    #[cfg(feature = "icmp")]
+    pub icmp_errors: std::vec::Vec<IcmpReport>,
+    ///
+    /// Packets reassembled from ip fragments, each counted once
+    ///
+    pub reassembled_packets: usize,
+    ///
+    /// Packets sent to broadcast and multicast addresses, in either direction
+    ///
+    pub broadcast_packets: usize,
+    pub multicast_packets: usize,
+    ///
+    /// Application protocol identified from the signature of the first payload it was detectable
+    /// in, for tcp flows
+    ///
+    pub protocol: Option<Protocol>,
+    ///
+    /// Tunnels the flow was first seen in, outermost first, with their outer addressing and ids
+    ///
+    #[cfg(feature = "tunnels")]
+    pub tunnels: std::vec::Vec<TunnelInfo>,
+    ///
+    /// Tunnel adjacent to the keyed headers, whose id (VNI, TEID or GRE key) identifies the tenant or
+    /// subscriber of overlay flows
+    ///
+    #[cfg(feature = "tunnels")]
+    pub tunnel: Option<TunnelInfo>
+}
+
+impl FlowStats {
+    pub fn packets(&self) -> usize {
+        self.client_packets + self.server_packets
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.client_bytes + self.server_bytes
+    }
+
+    pub fn handshake(&self) -> Option<HandshakeOutcome> {
+        self.tcp.map(|t| t.handshake())
+    }
+
+    pub fn close_state(&self) -> Option<CloseState> {
+        self.tcp.map(|t| t.close_state())
+    }
+
+    pub fn is_stalled(&self) -> bool {
+        self.window.map(|w| w.is_stalled()).unwrap_or(false)
+    }
+
+    ///
+    /// Datagrams of the flow were reported unreachable, or ran out of hops on the way
+    ///
+    #[cfg(feature = "icmp")]
+    pub fn is_blackholed(&self) -> bool {
+        self.icmp_errors.iter().any(|r| match r.error {
+            IcmpError::Unreachable(_) => true,
+            IcmpError::TimeExceeded(code) => code == 0,
+            IcmpError::PacketTooBig(_) => false
+        })
+    }
+
+    ///
+    /// Smallest next hop mtu reported for datagrams too big to forward, if any were
+    ///
+    #[cfg(feature = "icmp")]
+    pub fn path_mtu(&self) -> Option<u32> {
+        self.icmp_errors.iter().filter_map(|r| match r.error {
+            IcmpError::PacketTooBig(mtu) if mtu > 0 => Some(mtu),
+            _ => None
+        }).min()
+    }
+}
+
+///
+/// Totals for all flows on one vlan
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct VlanStats {
+    pub flows: usize,
+    pub packets: usize,
+    pub bytes: usize
+}
+
+///
+/// Bounds on the flows a table holds, so ingesting an endless capture stream can't exhaust memory.
+/// Unbounded by default.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct FlowLimits {
+    ///
+    /// Most flows held, evicting the least recently seen flow to make room for a new one
+    ///
+    pub max_flows: Option<usize>,
+    ///
+    /// Time without packets after which a flow is evicted, measured against record timestamps as
+    /// records are added. A later packet on an evicted flow starts a new flow.
+    ///
+    pub idle_timeout: Option<std::time::Duration>
+}
+
+impl FlowLimits {
+    fn is_bounded(&self) -> bool {
+        self.max_flows.is_some() || self.idle_timeout.is_some()
+    }
+}
+
+///
+/// Called with each flow as it is evicted from a bounded table, once its stats are final
+///
+pub type EvictCallback = Box<dyn FnMut(FlowStats) + Send>;
+
+///
+/// Statistics for each tcp and udp flow in a capture, both directions of a flow aggregated together
+///
+#[derive(Default)]
+pub struct FlowTable {
+    flows: HashMap<FlowKey, FlowStats>,
+    keying: FlowKeying,
+    defragmenter: Defragmenter,
+    limits: FlowLimits,
+    //flows of a bounded table by when they were last added to, least recent first
+    recency: BTreeMap<u64, FlowKey>,
+    last_added: HashMap<FlowKey, u64>,
+    added: u64,
+    evicted: usize,
+    on_evict: Option<EvictCallback>
+}
+
+impl std::fmt::Debug for FlowTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("FlowTable")
+            .field("flows", &self.flows)
+            .field("keying", &self.keying)
+            .field("limits", &self.limits)
+            .field("evicted", &self.evicted)
+            .finish()
+    }
+}
+
+impl FlowTable {
+    ///
+    /// Table distinguishing flows by vlan and tunnel headers according to `keying`, e.g. to keep
+    /// tenants on a trunk apart or to key tunnelled traffic on its tunnel endpoints
+    ///
+    pub fn with_keying(keying: FlowKeying) -> FlowTable {
+        FlowTable {
+            keying: keying,
+            ..FlowTable::default()
+        }
+    }
+
+    ///
+    /// Bound the flows held by the table
+    ///
+    pub fn limits(mut self, limits: FlowLimits) -> FlowTable {
+        self.limits = limits;
+        self
+    }
+
+    ///
+    /// Deliver each evicted flow to `callback`, e.g. to export it, rather than dropping it
+    ///
+    pub fn on_evict<F>(mut self, callback: F) -> FlowTable where F: FnMut(FlowStats) + Send + 'static {
+        self.on_evict = Some(Box::new(callback));
+        self
+    }
+
+    ///
+    /// Number of flows evicted so far, including by `flush`
+    ///
+    pub fn evicted(&self) -> usize { self.evicted }
+
+    ///
+    /// All flows, ordered by time first seen
+    ///
+    pub fn flows(&self) -> std::vec::Vec<&FlowStats> {
+        let mut flows = self.flows.values().collect::<std::vec::Vec<_>>();
+        flows.sort_by(|a, b| a.first_seen.cmp(&b.first_seen));
+        flows
+    }
+
+    ///
+    /// Stats for the flow `key` belongs to, in either direction
+    ///
+    pub fn flow(&self, key: &FlowKey) -> Option<&FlowStats> {
+        self.flows.get(key).or_else(|| self.flows.get(&key.reversed()))
+    }
+
+    ///
+    /// Number of tcp flows with each handshake outcome
+    ///
+    pub fn handshakes(&self) -> HashMap<HandshakeOutcome, usize> {
+        let mut handshakes = HashMap::new();
+        for outcome in self.flows.values().filter_map(|f| f.handshake()) {
+            *handshakes.entry(outcome).or_insert(0) += 1;
+        }
+        handshakes
+    }
+
+    ///
+    /// Packets in all flows by how they were delivered, e.g. to spot a broadcast storm
+    ///
+    pub fn delivery_totals(&self) -> HashMap<Delivery, usize> {
+        let mut totals = HashMap::new();
+        for flow in self.flows.values() {
+            *totals.entry(Delivery::Broadcast).or_insert(0) += flow.broadcast_packets;
+            *totals.entry(Delivery::Multicast).or_insert(0) += flow.multicast_packets;
+            *totals.entry(Delivery::Unicast).or_insert(0) += flow.packets() - flow.broadcast_packets - flow.multicast_packets;
+        }
+        totals
+    }
+
+    ///
+    /// Totals for each vlan keyed on, by vlan ids outermost first
+    ///
+    pub fn vlan_stats(&self) -> HashMap<std::vec::Vec<u16>, VlanStats> {
+        let mut vlans = HashMap::new();
+        for flow in self.flows.values() {
+            let stats = vlans.entry(flow.key.vlan_ids()).or_insert_with(VlanStats::default);
+            stats.flows += 1;
+            stats.packets += flow.packets();
+            stats.bytes += flow.bytes();
+        }
+        vlans
+    }
+
+    ///
+    /// Flows grouped by the type and id of their tunnel, leaving out flows not seen in a tunnel
+    ///
+    #[cfg(feature = "tunnels")]
+    pub fn flows_by_tunnel(&self) -> HashMap<(TunnelType, Option<u32>), std::vec::Vec<&FlowStats>> {
+        let mut tunnels: HashMap<_, std::vec::Vec<&FlowStats>> = HashMap::new();
+        for flow in self.flows() {
+            if let Some(ref tunnel) = flow.tunnel {
+                tunnels.entry( (tunnel.tunnel_type, tunnel.id) ).or_insert_with(std::vec::Vec::new).push(flow);
+            }
+        }
+        tunnels
+    }
+
+    pub fn add_record(&mut self, record: &PcapRecord) {
+        let datagram = match self.defragmenter.defragment(record.clone()) {
+            Some(datagram) => datagram,
+            None => return
+        };
+        let record = &datagram.record;
+        let parsed = match ParsedRecord::try_from(record.clone()) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                debug!("Could not parse record: {:?}", e);
+                return
+            }
+        };
+        let key = match parsed.flow_key_with(self.keying) {
+            Ok(key) => key,
+            Err(e) => {
+                #[cfg(feature = "icmp")]
+                {
+                    if self.add_icmp_error(&parsed, *record.timestamp()).is_some() {
+                        return
+                    }
+                }
+                debug!("Record is not part of a flow: {:?}", e);
+                return
+            }
+        };
+        let tcp = match parsed.keyed_layers(self.keying.tunnels).2 {
+            Some(&Layer4::Tcp(ref tcp)) => Some(tcp),
+            _ => None
+        };
+        let timestamp = *record.timestamp();
+        if let Some(idle_timeout) = self.limits.idle_timeout {
+            self.evict_idle(timestamp, idle_timeout);
+        }
+
+        let reverse = key.reversed();
+        let to_server = if self.flows.contains_key(&key) {
+            true
+        } else if self.flows.contains_key(&reverse) {
+            false
+        } else {
+            //the sender of a syn ack is the server, otherwise assume the first sender seen is the client
+            !tcp.map(|t| t.has_flags(FLAG_SYN | FLAG_ACK)).unwrap_or(false)
+        };
+        let flow_key = if to_server { key } else { reverse };
+
+        if self.limits.is_bounded() {
+            let is_new = !self.flows.contains_key(&flow_key);
+            if let (true, Some(max_flows)) = (is_new, self.limits.max_flows) {
+                while self.flows.len() >= max_flows && self.evict_least_recent() {}
+            }
+            self.touch(&flow_key);
+        }
+
+        #[cfg(feature = "tunnels")]
+        let tunnel_keying = self.keying.tunnels;
+        let stats = self.flows.entry(flow_key.clone()).or_insert_with(|| FlowStats {
+            key: flow_key,
+            first_seen: timestamp,
+            last_seen: timestamp,
+            client_packets: 0,
+            client_bytes: 0,
+            server_packets: 0,
+            server_bytes: 0,
+            tcp: tcp.map(|_| TcpObservations::default()),
+            window: tcp.map(|_| WindowObservations::default()),
+            #[cfg(feature = "icmp")]
+            icmp_errors: vec![],
+            reassembled_packets: 0,
+            broadcast_packets: 0,
+            multicast_packets: 0,
+            protocol: None,
+            #[cfg(feature = "tunnels")]
+            tunnels: parsed.tunnels().iter().map(|t| t.tunnel().clone()).collect(),
+            #[cfg(feature = "tunnels")]
+            tunnel: parsed.keyed_tunnel(tunnel_keying).cloned()
+        });
+        if timestamp < stats.first_seen {
+            stats.first_seen = timestamp;
+        }
+        if timestamp > stats.last_seen {
+            stats.last_seen = timestamp;
+        }
+        if to_server {
+            stats.client_packets += 1;
+            stats.client_bytes += record.payload().len();
+        } else {
+            stats.server_packets += 1;
+            stats.server_bytes += record.payload().len();
+        }
+        if datagram.is_reassembled() {
+            stats.reassembled_packets += 1;
+        }
+        match parsed.delivery() {
+            Delivery::Broadcast => stats.broadcast_packets += 1,
+            Delivery::Multicast => stats.multicast_packets += 1,
+            Delivery::Unicast => {}
+        }
+        if let (Some(observations), Some(tcp)) = (stats.tcp.as_mut(), tcp) {
+            observations.observe(tcp, to_server);
+        }
+        if let (Some(observations), Some(tcp)) = (stats.window.as_mut(), tcp) {
+            observations.observe(tcp, to_server, timestamp);
+        }
+        if let (None, Some(tcp)) = (stats.protocol, tcp) {
+            stats.protocol = detect::detect(tcp.payload());
+        }
+    }
+
+    ///
+    /// Mark the flow `key` as the most recently added to
+    ///
+    fn touch(&mut self, key: &FlowKey) {
+        if let Some(previous) = self.last_added.insert(key.clone(), self.added) {
+            self.recency.remove(&previous);
+        }
+        self.recency.insert(self.added, key.clone());
+        self.added += 1;
+    }
+
+    fn evict(&mut self, key: &FlowKey) {
+        if let Some(previous) = self.last_added.remove(key) {
+            self.recency.remove(&previous);
+        }
+        if let Some(stats) = self.flows.remove(key) {
+            self.evicted += 1;
+            if let Some(ref mut callback) = self.on_evict {
+                callback(stats);
+            }
+        }
+    }
+
+    ///
+    /// Evict the least recently added to flow, returning false when there are none
+    ///
+    fn evict_least_recent(&mut self) -> bool {
+        let key = match self.recency.values().next() {
+            Some(key) => key.clone(),
+            None => return false
+        };
+        self.evict(&key);
+        true
+    }
+
+    ///
+    /// Evict flows without packets for `idle_timeout` before `now`. Flows are checked in the order
+    /// they were last added to, stopping at the first that isn't idle.
+    ///
+    fn evict_idle(&mut self, now: std::time::SystemTime, idle_timeout: std::time::Duration) {
+        loop {
+            let key = match self.recency.values().next() {
+                Some(key) => key.clone(),
+                None => return
+            };
+            let idle = self.flows.get(&key)
+                .map(|f| now.duration_since(f.last_seen).map(|d| d >= idle_timeout).unwrap_or(false))
+                .unwrap_or(true);
+            if !idle {
+                return
+            }
+            self.evict(&key);
+        }
+    }
+
+    ///
+    /// Evict every flow, e.g. at the end of a capture stream so the callback sees all flows
+    ///
+    pub fn flush(&mut self) {
+        let mut keys = self.flows.keys().cloned().collect::<std::vec::Vec<_>>();
+        keys.sort_by(|a, b| self.flows[a].first_seen.cmp(&self.flows[b].first_seen));
+        for key in keys {
+            self.evict(&key);
+        }
+    }
+
+    ///
+    /// Attach an icmp error to the flow of the datagram it quotes, if that flow has been seen
+    ///
+    #[cfg(feature = "icmp")]
+    fn add_icmp_error(&mut self, parsed: &ParsedRecord, timestamp: std::time::SystemTime) -> Option<()> {
+        let (reporter, icmp, error) = match parsed.keyed_layers(self.keying.tunnels).1? {
+            &Layer3::IPv4(ref ipv4) if *ipv4.protocol() == InternetProtocolId::Icmp => {
+                let (_, icmp) = Icmp::parse(ipv4.payload()).ok()?;
+                let error = icmp.error()?;
+                (*ipv4.src_ip(), icmp, error)
+            }
+            #[cfg(feature = "ipv6")]
+            &Layer3::IPv6(ref ipv6) if *ipv6.protocol() == InternetProtocolId::Icmpv6 => {
+                let (_, icmp) = Icmp::parse(ipv6.payload()).ok()?;
+                let error = icmp.error_v6()?;
+                (*ipv6.src_ip(), icmp, error)
+            }
+            _ => return None
+        };
+        let original = icmp.original()?;
+        let (vlan, inner_vlans) = parsed.keyed_vlans(self.keying);
+        let key = FlowKey {
+            src_ip: original.src_ip,
+            src_port: original.src_port?,
+            dst_ip: original.dst_ip,
+            dst_port: original.dst_port?,
+            protocol: original.protocol.clone(),
+            vlan: vlan,
+            inner_vlans: inner_vlans
+        };
+        let reverse = key.reversed();
+        let flow_key = if self.flows.contains_key(&key) { key } else { reverse };
+
+        self.flows.get_mut(&flow_key)?.icmp_errors.push(IcmpReport {
+            timestamp: timestamp,
+            reporter: reporter,
+            error: error
+        });
+        Some(())
+    }
+
+    pub fn from_records<'a, I>(records: I) -> FlowTable where I: IntoIterator<Item=&'a PcapRecord> {
+        let mut table = FlowTable::default();
+        for record in records {
+            table.add_record(record);
+        }
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+    use super::super::super::flow::{TunnelKeying, VlanKeying};
+    use super::super::reassembly::tests::tcp_record;
+
+    const SYN: u8 = 0x02;
+    const SYN_ACK: u8 = 0x12;
+    const ACK: u8 = 0x10;
+    const FIN_ACK: u8 = 0x11;
+    const RST_ACK: u8 = 0x14;
+
+    ///
+    /// Insert a vlan tag with `tpid` after the mac addresses
+    ///
+    fn tagged(record: PcapRecord, tpid: u16, vlan: u16) -> PcapRecord {
+        let mut payload = record.payload().clone();
+        let tag = [(tpid >> 8) as u8, tpid as u8, (vlan >> 8) as u8, vlan as u8];
+        for (idx, b) in tag.iter().enumerate() {
+            payload.insert(12 + idx, *b);
+        }
+        PcapRecord::new(*record.timestamp(), payload.len() as u32, payload.len() as u32, payload)
+    }
+
+    ///
+    /// Wrap the frame of `record` in vxlan between 192.0.2.1 and 192.0.2.2
+    ///
+    #[cfg(feature = "tunnels")]
+    fn vxlan(record: PcapRecord, vni: u8) -> PcapRecord {
+        let inner = record.payload();
+        let udp_length = 8 + 8 + inner.len();
+        let ip_length = 20 + udp_length;
+        let mut payload = vec![
+            0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x01u8, 0x02u8, //dst mac
+            0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x01u8, 0x01u8, //src mac
+            0x08u8, 0x00u8, //ipv4
+            //ipv4
+            0x45u8, //version, header length
+            0x00u8, //tos
+            (ip_length >> 8) as u8, ip_length as u8, //total length
+            0x00u8, 0x00u8, //id
+            0x00u8, 0x00u8, //flags, fragment offset
+            0x40u8, //ttl
+            0x11u8, //udp
+            0x00u8, 0x00u8, //checksum
+            0xC0u8, 0x00u8, 0x02u8, 0x01u8, //src ip
+            0xC0u8, 0x00u8, 0x02u8, 0x02u8, //dst ip
+            //udp
+            0xC3u8, 0x50u8, //src port, 50000
+            0x12u8, 0xB5u8, //dst port, 4789
+            (udp_length >> 8) as u8, udp_length as u8, //length
+            0x00u8, 0x00u8, //checksum
+            //vxlan
+            0x08u8, 0x00u8, 0x00u8, 0x00u8, //vni present
+            0x00u8, 0x00u8, vni, 0x00u8 //vni
+        ];
+        payload.extend_from_slice(inner);
+        PcapRecord::new(*record.timestamp(), payload.len() as u32, payload.len() as u32, payload)
+    }
+
+    ///
+    /// Replace the advertised window of `record`
+    ///
+    fn with_window(record: PcapRecord, window: u16) -> PcapRecord {
+        let mut payload = record.payload().clone();
+        payload[48] = (window >> 8) as u8;
+        payload[49] = window as u8;
+        PcapRecord::new(*record.timestamp(), payload.len() as u32, payload.len() as u32, payload)
+    }
+
+    ///
+    /// Add a window scale option, padded with a nop, to `record`
+    ///
+    fn with_window_scale(record: PcapRecord, scale: u8) -> PcapRecord {
+        let mut payload = record.payload().clone();
+        for (idx, b) in [0x01u8, 0x03u8, 0x03u8, scale].iter().enumerate() {
+            payload.insert(54 + idx, *b);
+        }
+        let ip_length = payload.len() - 14;
+        payload[16] = (ip_length >> 8) as u8;
+        payload[17] = ip_length as u8;
+        payload[46] = 0x60u8; //header length, 6 words
+        PcapRecord::new(*record.timestamp(), payload.len() as u32, payload.len() as u32, payload)
+    }
+
+    ///
+    /// Icmp error from 10.0.0.254 to the sender of `record`, quoting its ip header and first eight
+    /// bytes of payload
+    ///
+    #[cfg(feature = "icmp")]
+    fn icmp_error(seconds: u32, message_type: u8, code: u8, rest_of_header: u32, record: &PcapRecord) -> PcapRecord {
+        let quoted = &record.payload()[14..14 + 28];
+        let ip_length = 20 + 8 + quoted.len();
+        let mut payload = vec![
+            record.payload()[6], record.payload()[7], record.payload()[8], record.payload()[9], record.payload()[10], record.payload()[11], //dst mac
+            0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0xFEu8, //src mac
+            0x08u8, 0x00u8, //ipv4
+            //ipv4
+            0x45u8, //version, header length
+            0x00u8, //tos
+            (ip_length >> 8) as u8, ip_length as u8, //total length
+            0x00u8, 0x00u8, //id
+            0x00u8, 0x00u8, //flags, fragment offset
+            0x40u8, //ttl
+            0x01u8, //icmp
+            0x00u8, 0x00u8, //checksum
+            0x0Au8, 0x00u8, 0x00u8, 0xFEu8, //src ip
+            quoted[12], quoted[13], quoted[14], quoted[15], //dst ip
+            //icmp
+            message_type,
+            code,
+            0x00u8, 0x00u8, //checksum
+            (rest_of_header >> 24) as u8, (rest_of_header >> 16) as u8, (rest_of_header >> 8) as u8, rest_of_header as u8
+        ];
+        payload.extend_from_slice(quoted);
+        PcapRecord::new(PcapRecord::convert_packet_time(seconds, 0), payload.len() as u32, payload.len() as u32, payload)
+    }
+
+    fn outcome(records: std::vec::Vec<PcapRecord>) -> (HandshakeOutcome, CloseState) {
+        let table = FlowTable::from_records(&records);
+        let flows = table.flows();
+        assert_eq!(flows.len(), 1);
+        (flows[0].handshake().expect("Not tcp"), flows[0].close_state().expect("Not tcp"))
+    }
+
+    #[test]
+    fn flow_stats() {
+        let _ = env_logger::try_init();
+
+        let records = vec![
+            tcp_record(1, true, 1, SYN, &[]),
+            tcp_record(2, false, 1, SYN_ACK, &[]),
+            tcp_record(3, true, 2, ACK, &[]),
+            tcp_record(4, true, 2, ACK, b"data"),
+            tcp_record(5, true, 6, FIN_ACK, &[])
+        ];
+        let table = FlowTable::from_records(&records);
+
+        let flows = table.flows();
+        assert_eq!(flows.len(), 1);
+        assert_eq!(flows[0].key.dst_port, 80);
+        assert_eq!(flows[0].client_packets, 4);
+        assert_eq!(flows[0].server_packets, 1);
+        assert_eq!(flows[0].client_bytes, 4 * 54 + 4);
+        assert_eq!(flows[0].first_seen, PcapRecord::convert_packet_time(1, 0));
+        assert_eq!(flows[0].last_seen, PcapRecord::convert_packet_time(5, 0));
+        assert_eq!(flows[0].handshake(), Some(HandshakeOutcome::Completed));
+        assert_eq!(flows[0].close_state(), Some(CloseState::HalfClosed));
+        assert_eq!(table.flow(&flows[0].key.reversed()), Some(flows[0]));
+        assert_eq!(table.handshakes().get(&HandshakeOutcome::Completed), Some(&1));
+        assert_eq!(flows[0].protocol, None);
+        assert_eq!(flows[0].broadcast_packets + flows[0].multicast_packets, 0);
+    }
+
+    ///
+    /// Rewrite the tcp segment of `record` as a udp datagram between the same ports
+    ///
+    fn as_udp(record: PcapRecord) -> PcapRecord {
+        let payload = record.payload();
+        let data = &payload[54..];
+        let udp_length = 8 + data.len();
+        let ip_length = 20 + udp_length;
+        let mut udp = payload[..38].to_vec();
+        udp[16] = (ip_length >> 8) as u8;
+        udp[17] = ip_length as u8;
+        udp[23] = 0x11u8;
+        udp.extend_from_slice(&[(udp_length >> 8) as u8, udp_length as u8, 0x00u8, 0x00u8]);
+        udp.extend_from_slice(data);
+        PcapRecord::new(*record.timestamp(), udp.len() as u32, udp.len() as u32, udp)
+    }
+
+    #[test]
+    fn tcp_and_udp_on_same_ports() {
+        let _ = env_logger::try_init();
+
+        let records = vec![
+            tcp_record(1, true, 1, SYN, &[]),
+            as_udp(tcp_record(2, true, 0, 0, b"query")),
+            tcp_record(3, false, 1, SYN_ACK, &[]),
+            as_udp(tcp_record(4, false, 0, 0, b"answer"))
+        ];
+        let table = FlowTable::from_records(&records);
+
+        let flows = table.flows();
+        assert_eq!(flows.len(), 2);
+        assert_ne!(flows[0].key, flows[1].key);
+        for flow in flows {
+            assert_eq!(flow.key.src_port.min(flow.key.dst_port), 80);
+            assert_eq!(flow.client_packets, 1);
+            assert_eq!(flow.server_packets, 1);
+        }
+        let tcp = table.flows().into_iter().find(|f| f.key.protocol == Some(InternetProtocolId::Tcp)).expect("Could not find tcp flow");
+        assert_eq!(tcp.handshake(), Some(HandshakeOutcome::Unacknowledged));
+        let udp = table.flows().into_iter().find(|f| f.key.protocol == Some(InternetProtocolId::Udp)).expect("Could not find udp flow");
+        assert_eq!(udp.handshake(), None);
+    }
+
+    ///
+    /// Replace the destination mac of `record`
+    ///
+    fn to_mac(record: PcapRecord, mac: [u8; 6]) -> PcapRecord {
+        let mut payload = record.payload().clone();
+        payload[..6].copy_from_slice(&mac);
+        PcapRecord::new(*record.timestamp(), payload.len() as u32, payload.len() as u32, payload)
+    }
+
+    #[test]
+    fn window_behavior() {
+        let _ = env_logger::try_init();
+
+        let records = vec![
+            with_window_scale(with_window(tcp_record(1, true, 1, SYN, &[]), 64240), 7),
+            with_window_scale(with_window(tcp_record(2, false, 1, SYN_ACK, &[]), 65160), 2),
+            with_window(tcp_record(3, true, 2, ACK, &[]), 502),
+            with_window(tcp_record(4, false, 2, ACK, b"data"), 0),
+            with_window(tcp_record(5, false, 2, ACK, &[]), 0),
+            with_window(tcp_record(6, false, 2, ACK, &[]), 100),
+            with_window(tcp_record(7, true, 2, ACK, &[]), 0)
+        ];
+        let table = FlowTable::from_records(&records);
+
+        let flows = table.flows();
+        assert_eq!(flows.len(), 1);
+        let window = flows[0].window.expect("Not tcp");
+
+        assert_eq!(window.client.scale, Some(7));
+        assert_eq!(window.server.scale, Some(2));
+        assert_eq!(window.client.max_window, Some(64256));
+        assert_eq!(window.client.min_window, Some(0));
+        assert_eq!(window.server.max_window, Some(65160));
+        assert_eq!(window.server.min_window, Some(0));
+
+        assert_eq!(window.server.zero_windows, 1);
+        assert_eq!(window.server.longest_zero_window, std::time::Duration::from_secs(2));
+        assert_eq!(window.server.zero_window_time, std::time::Duration::from_secs(2));
+        assert_eq!(window.server.zero_window_since(), None);
+        assert!(window.server.is_stalled());
+
+        assert_eq!(window.client.zero_windows, 1);
+        assert_eq!(window.client.zero_window_since(), Some(PcapRecord::convert_packet_time(7, 0)));
+        assert!(window.client.is_stalled());
+        assert!(flows[0].is_stalled());
+    }
+
+    #[test]
+    fn window_unscaled() {
+        let _ = env_logger::try_init();
+
+        let records = vec![
+            with_window_scale(tcp_record(1, true, 1, SYN, &[]), 7),
+            tcp_record(2, false, 1, SYN_ACK, &[]),
+            with_window(tcp_record(3, true, 2, ACK, &[]), 0),
+            with_window(tcp_record(3, true, 2, ACK, &[]), 502)
+        ];
+        let table = FlowTable::from_records(&records);
+
+        let flows = table.flows();
+        let window = flows[0].window.expect("Not tcp");
+        assert_eq!(window.client.scale, Some(7));
+        assert_eq!(window.server.scale, None);
+        assert_eq!(window.client.min_window, Some(0));
+        assert_eq!(window.client.max_window, Some(0xFFFF));
+        assert_eq!(window.client.zero_windows, 1);
+        assert!(!flows[0].is_stalled());
+    }
+
+    #[cfg(feature = "icmp")]
+    #[test]
+    fn icmp_errors() {
+        let _ = env_logger::try_init();
+
+        let data = tcp_record(3, true, 2, ACK, b"data");
+        let records = vec![
+            tcp_record(1, true, 1, SYN, &[]),
+            tcp_record(2, false, 1, SYN_ACK, &[]),
+            data.clone(),
+            icmp_error(4, 3, 4, 1400, &data),
+            icmp_error(5, 3, 4, 1280, &data),
+            icmp_error(6, 8, 0, 0, &data) //echo request, not an error
+        ];
+        let table = FlowTable::from_records(&records);
+
+        let flows = table.flows();
+        assert_eq!(flows.len(), 1);
+        assert_eq!(flows[0].packets(), 3);
+        assert_eq!(flows[0].icmp_errors.len(), 2);
+        assert_eq!(flows[0].icmp_errors[0], IcmpReport {
+            timestamp: PcapRecord::convert_packet_time(4, 0),
+            reporter: "10.0.0.254".parse().expect("Could not parse ip address"),
+            error: IcmpError::PacketTooBig(1400)
+        });
+        assert_eq!(flows[0].path_mtu(), Some(1280));
+        assert!(!flows[0].is_blackholed());
+
+        let records = vec![
+            tcp_record(1, false, 1, ACK, b"data"),
+            icmp_error(2, 11, 0, 0, &tcp_record(1, false, 1, ACK, b"data"))
+        ];
+        let table = FlowTable::from_records(&records);
+
+        let flows = table.flows();
+        assert_eq!(flows.len(), 1);
+        assert_eq!(flows[0].icmp_errors[0].error, IcmpError::TimeExceeded(0));
+        assert!(flows[0].is_blackholed());
+        assert_eq!(flows[0].path_mtu(), None);
+
+        let unrelated = FlowTable::from_records(&[icmp_error(1, 3, 3, 0, &data)]);
+        assert!(unrelated.flows().is_empty());
+    }
+
+    #[test]
+    fn delivery_counts() {
+        let _ = env_logger::try_init();
+
+        let records = vec![
+            tcp_record(1, true, 1, ACK, &[]),
+            to_mac(tcp_record(2, true, 1, ACK, &[]), [0xFFu8; 6]),
+            to_mac(tcp_record(3, true, 1, ACK, &[]), [0xFFu8; 6]),
+            to_mac(tcp_record(4, false, 1, ACK, &[]), [0x01u8, 0x00u8, 0x5Eu8, 0x00u8, 0x00u8, 0x01u8])
+        ];
+        let table = FlowTable::from_records(&records);
+
+        let flows = table.flows();
+        assert_eq!(flows.len(), 1);
+        assert_eq!(flows[0].broadcast_packets, 2);
+        assert_eq!(flows[0].multicast_packets, 1);
+
+        let totals = table.delivery_totals();
+        assert_eq!(totals.get(&Delivery::Unicast), Some(&1));
+        assert_eq!(totals.get(&Delivery::Broadcast), Some(&2));
+        assert_eq!(totals.get(&Delivery::Multicast), Some(&1));
+    }
+
+    #[test]
+    fn detected_protocol() {
+        let _ = env_logger::try_init();
+
+        let records = vec![
+            tcp_record(1, true, 1, SYN, &[]),
+            tcp_record(2, false, 1, SYN_ACK, &[]),
+            tcp_record(3, false, 2, ACK, b"SSH-2.0-OpenSSH_8.9\r\n"),
+            tcp_record(4, true, 2, ACK, b"GET / HTTP/1.1\r\n\r\n")
+        ];
+        let table = FlowTable::from_records(&records);
+
+        let flows = table.flows();
+        assert_eq!(flows[0].key.dst_port, 80);
+        assert_eq!(flows[0].protocol, Some(Protocol::Ssh));
+    }
+
+    #[test]
+    fn handshake_outcomes() {
+        let _ = env_logger::try_init();
+
+        assert_eq!(outcome(vec![
+            tcp_record(1, true, 1, SYN, &[]),
+            tcp_record(2, true, 1, SYN, &[])
+        ]), (HandshakeOutcome::Unanswered, CloseState::Open));
+        assert_eq!(outcome(vec![
+            tcp_record(1, true, 1, SYN, &[]),
+            tcp_record(2, false, 0, RST_ACK, &[])
+        ]), (HandshakeOutcome::Refused, CloseState::Reset));
+        assert_eq!(outcome(vec![
+            tcp_record(1, false, 1, SYN_ACK, &[]),
+            tcp_record(2, true, 2, RST_ACK, &[])
+        ]), (HandshakeOutcome::Unacknowledged, CloseState::Reset));
+        assert_eq!(outcome(vec![
+            tcp_record(1, true, 10, ACK, b"data"),
+            tcp_record(2, false, 10, FIN_ACK, &[]),
+            tcp_record(3, true, 14, FIN_ACK, &[])
+        ]), (HandshakeOutcome::Midstream, CloseState::Closed));
+    }
+
+    #[test]
+    fn vlan_keying() {
+        let _ = env_logger::try_init();
+
+        let records = vec![
+            tagged(tcp_record(1, true, 1, SYN, &[]), 0x8100, 10),
+            tagged(tcp_record(2, false, 1, SYN_ACK, &[]), 0x8100, 10),
+            tagged(tcp_record(3, true, 1, SYN, &[]), 0x8100, 20)
+        ];
+
+        let table = FlowTable::from_records(&records);
+        assert_eq!(table.flows().len(), 2);
+        assert_eq!(table.flows()[0].key.vlan.id(), 10);
+        assert_eq!(table.flows()[0].handshake(), Some(HandshakeOutcome::Unacknowledged));
+        assert_eq!(table.flows()[1].handshake(), Some(HandshakeOutcome::Unanswered));
+        let vlans = table.vlan_stats();
+        assert_eq!(vlans.get(&vec![10]), Some(&VlanStats { flows: 1, packets: 2, bytes: 2 * 58 }));
+        assert_eq!(vlans.get(&vec![20]), Some(&VlanStats { flows: 1, packets: 1, bytes: 58 }));
+
+        let mut table = FlowTable::with_keying(FlowKeying { vlans: VlanKeying::None, ..FlowKeying::default() });
+        for record in records.iter() {
+            table.add_record(record);
+        }
+        assert_eq!(table.flows().len(), 1);
+        assert_eq!(table.flows()[0].packets(), 3);
+    }
+
+    #[test]
+    fn vlan_keying_priority() {
+        let _ = env_logger::try_init();
+
+        let records = vec![
+            tagged(tcp_record(1, true, 1, SYN, &[]), 0x8100, 10),
+            tagged(tcp_record(2, false, 1, SYN_ACK, &[]), 0x8100, 0xF000 | 10), //priority 7, drop eligible
+            tagged(tcp_record(3, true, 1, ACK, &[]), 0x88a8, 10)
+        ];
+
+        let table = FlowTable::from_records(&records);
+        assert_eq!(table.flows().len(), 1);
+        assert_eq!(table.flows()[0].packets(), 3);
+        assert_eq!(table.flows()[0].key.vlan, Vlan::new(10, 0, false, 0));
+
+        let mut table = FlowTable::with_keying(FlowKeying { vlans: VlanKeying::Typed, ..FlowKeying::default() });
+        for record in records.iter() {
+            table.add_record(record);
+        }
+        let flows = table.flows();
+        assert_eq!(flows.len(), 2);
+        assert_eq!(flows.iter().find(|f| f.key.c_vlan().is_some()).map(|f| f.packets()), Some(2));
+        assert_eq!(flows.iter().find(|f| f.key.s_vlan().is_some()).map(|f| f.packets()), Some(1));
+    }
+
+    #[test]
+    fn qinq_keying() {
+        let _ = env_logger::try_init();
+
+        let records = vec![
+            tagged(tagged(tcp_record(1, true, 1, SYN, &[]), 0x8100, 100), 0x88a8, 10),
+            tagged(tagged(tcp_record(2, true, 1, SYN, &[]), 0x8100, 200), 0x88a8, 10)
+        ];
+
+        let table = FlowTable::from_records(&records);
+        assert_eq!(table.flows().len(), 1);
+
+        let mut table = FlowTable::with_keying(FlowKeying { vlans: VlanKeying::Stack, ..FlowKeying::default() });
+        for record in records.iter() {
+            table.add_record(record);
+        }
+        assert_eq!(table.flows().len(), 2);
+        assert_eq!(table.flows()[0].key.vlan_ids(), vec![10, 100]);
+        assert_eq!(table.vlan_stats().get(&vec![10, 200]).map(|v| v.flows), Some(1));
+
+        //a third tag inside the customer tag is not keyed on
+        let records = vec![
+            tagged(tagged(tagged(tcp_record(1, true, 1, SYN, &[]), 0x8100, 5), 0x8100, 100), 0x88a8, 10),
+            tagged(tagged(tagged(tcp_record(2, true, 1, SYN, &[]), 0x8100, 6), 0x8100, 100), 0x88a8, 10),
+            tagged(tcp_record(3, true, 1, SYN, &[]), 0x8100, 100)
+        ];
+        let mut table = FlowTable::with_keying(FlowKeying { vlans: VlanKeying::Typed, ..FlowKeying::default() });
+        for record in records.iter() {
+            table.add_record(record);
+        }
+        let flows = table.flows();
+        assert_eq!(flows.len(), 2);
+        let qinq = flows.iter().find(|f| f.key.s_vlan().is_some()).expect("No 802.1ad flow");
+        assert_eq!(qinq.key.s_vlan().map(|v| v.id()), Some(10));
+        assert_eq!(qinq.key.c_vlan().map(|v| v.id()), Some(100));
+        assert_eq!(qinq.packets(), 2);
+        let dot1q = flows.iter().find(|f| f.key.s_vlan().is_none()).expect("No 802.1q flow");
+        assert_eq!(dot1q.key.c_vlan().map(|v| v.id()), Some(100));
+    }
+
+    #[cfg(feature = "tunnels")]
+    #[test]
+    fn tunnel_keying() {
+        let _ = env_logger::try_init();
+
+        let records = vec![
+            vxlan(tcp_record(1, true, 1, SYN, &[]), 10),
+            vxlan(tcp_record(2, false, 1, SYN_ACK, &[]), 10),
+            vxlan(tcp_record(3, true, 2, ACK, &[]), 10)
+        ];
+
+        let table = FlowTable::from_records(&records);
+        let flows = table.flows();
+        assert_eq!(flows.len(), 1);
+        assert_eq!(flows[0].key.dst_port, 80);
+        assert_eq!(flows[0].packets(), 3);
+        assert_eq!(flows[0].handshake(), Some(HandshakeOutcome::Completed));
+        assert_eq!(flows[0].tunnels.len(), 1);
+        assert_eq!(flows[0].tunnels[0].id, Some(10));
+        assert_eq!(flows[0].tunnels[0].src_ip, "192.0.2.1".parse::<std::net::IpAddr>().expect("Could not parse ip address"));
+        assert_eq!(flows[0].tunnel.as_ref(), flows[0].tunnels.last());
+
+        let mut tenants = records.clone();
+        tenants.push(vxlan(tagged(tcp_record(4, true, 1, SYN, &[]), 0x8100, 100), 20));
+        tenants.push(tagged(tcp_record(5, true, 1, SYN, &[]), 0x8100, 200));
+        let table = FlowTable::from_records(&tenants);
+        let groups = table.flows_by_tunnel();
+        assert_eq!(table.flows().len(), 3);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups.get(&(TunnelType::Vxlan, Some(10))).map(|f| f.len()), Some(1));
+        assert_eq!(groups.get(&(TunnelType::Vxlan, Some(20))).map(|f| f[0].key.vlan.id()), Some(100));
+
+        let mut table = FlowTable::with_keying(FlowKeying { tunnels: TunnelKeying::Outer, ..FlowKeying::default() });
+        for record in records.iter() {
+            table.add_record(record);
+        }
+        let flows = table.flows();
+        assert_eq!(flows.len(), 1);
+        assert_eq!(flows[0].key.dst_ip, "192.0.2.2".parse::<std::net::IpAddr>().expect("Could not parse ip address"));
+        assert_eq!(flows[0].handshake(), None);
+        assert_eq!(flows[0].tunnel.as_ref().and_then(|t| t.id), Some(10));
+    }
+
+    #[test]
+    fn reassembled_packets() {
+        let _ = env_logger::try_init();
+
+        let records = super::super::defrag::tests::fragments(1, 7, &[0u8; 40], 16);
+        let table = FlowTable::from_records(&records);
+
+        let flows = table.flows();
+        assert_eq!(flows.len(), 1);
+        assert_eq!(flows[0].packets(), 1);
+        assert_eq!(flows[0].reassembled_packets, 1);
+        assert_eq!(flows[0].bytes(), 14 + 20 + 8 + 40);
+    }
+
+    #[test]
+    fn bounded_table() {
+        let _ = env_logger::try_init();
+
+        let evicted = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let sink = evicted.clone();
+        let mut table = FlowTable::default()
+            .limits(FlowLimits { max_flows: Some(2), idle_timeout: None })
+            .on_evict(move |flow| sink.lock().expect("Poisoned").push(flow));
+        let evicted_vlans = || evicted.lock().expect("Poisoned").iter().map(|f: &FlowStats| f.key.vlan.id()).collect::<std::vec::Vec<_>>();
+
+        table.add_record(&tagged(tcp_record(1, true, 1, SYN, &[]), 0x8100, 1));
+        table.add_record(&tagged(tcp_record(2, true, 1, SYN, &[]), 0x8100, 2));
+        table.add_record(&tagged(tcp_record(3, false, 1, SYN_ACK, &[]), 0x8100, 1));
+        table.add_record(&tagged(tcp_record(4, true, 1, SYN, &[]), 0x8100, 3));
+
+        //vlan 2 was least recently seen
+        assert_eq!(table.flows().len(), 2);
+        assert_eq!(table.evicted(), 1);
+        assert_eq!(evicted_vlans(), vec![2]);
+
+        table.flush();
+        assert!(table.flows().is_empty());
+        assert_eq!(table.evicted(), 3);
+        assert_eq!(evicted_vlans(), vec![2, 1, 3]);
+        assert_eq!(evicted.lock().expect("Poisoned")[1].packets(), 2);
+    }
+
+    #[test]
+    fn idle_eviction() {
+        let _ = env_logger::try_init();
+
+        let mut table = FlowTable::default()
+            .limits(FlowLimits { max_flows: None, idle_timeout: Some(std::time::Duration::from_secs(10)) });
+
+        table.add_record(&tagged(tcp_record(1, true, 1, SYN, &[]), 0x8100, 1));
+        table.add_record(&tagged(tcp_record(5, true, 1, SYN, &[]), 0x8100, 2));
+        table.add_record(&tagged(tcp_record(12, true, 2, ACK, &[]), 0x8100, 1));
+
+        //vlan 1 was idle for 11 seconds, so its latest packet starts a new flow
+        assert_eq!(table.evicted(), 1);
+        let flows = table.flows();
+        assert_eq!(flows.len(), 2);
+        assert_eq!(flows[1].key.vlan.id(), 1);
+        assert_eq!(flows[1].packets(), 1);
+        assert_eq!(flows[1].first_seen, PcapRecord::convert_packet_time(12, 0));
+    }
+}
@@ -0,0 +1,158 @@
+use super::prelude::*;
+use super::super::flow::FlowKey;
+use super::reassembly::{TcpConnection, TcpConnections};
+
+use self::layer7::http::{Request, Response};
+
+use std;
+
+///
+/// A request and, if seen, the response it received
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct HttpTransaction {
+    pub key: FlowKey,
+    pub method: String,
+    pub uri: String,
+    pub host: Option<String>,
+    pub request_time: std::time::SystemTime,
+    ///
+    /// Request body length, after removing any chunked transfer coding
+    ///
+    pub request_size: usize,
+    pub status: Option<u16>,
+    pub response_time: Option<std::time::SystemTime>,
+    pub response_size: Option<usize>
+}
+
+impl HttpTransaction {
+    ///
+    /// Time from the start of the request to the start of its response
+    ///
+    pub fn latency(&self) -> Option<std::time::Duration> {
+        self.response_time.and_then(|t| t.duration_since(self.request_time).ok())
+    }
+}
+
+///
+/// HTTP/1.x transactions in a capture, pairing requests with responses in order on each connection
+/// so pipelined and keep-alive connections carrying several requests are handled
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HttpTransactions {
+    connections: TcpConnections
+}
+
+fn connection_transactions(connection: &TcpConnection) -> std::vec::Vec<HttpTransaction> {
+    let to_server = connection.to_server.reassemble();
+    let to_client = connection.to_client.reassemble();
+
+    let mut transactions = vec![];
+    let mut current = &to_server.data()[..];
+    while let Ok( (rem, request) ) = Request::parse(current) {
+        let offset = to_server.data().len() - current.len();
+        transactions.push(HttpTransaction {
+            key: connection.key.clone(),
+            method: request.method().clone(),
+            uri: request.uri().clone(),
+            host: request.header("host").map(|h| h.to_string()),
+            request_time: to_server.timestamp_at(offset).unwrap_or(connection.first_seen),
+            request_size: request.body().len(),
+            status: None,
+            response_time: None,
+            response_size: None
+        });
+        current = rem;
+    }
+
+    let mut current = &to_client.data()[..];
+    for transaction in transactions.iter_mut() {
+        loop {
+            let offset = to_client.data().len() - current.len();
+            let (rem, response) = match Response::parse_for(current, &transaction.method) {
+                Ok(r) => r,
+                Err(_) => return transactions
+            };
+            current = rem;
+            if response.is_informational() {
+                continue
+            }
+            transaction.status = Some(response.status());
+            transaction.response_time = to_client.timestamp_at(offset);
+            transaction.response_size = Some(response.body().len());
+            break
+        }
+    }
+    transactions
+}
+
+impl HttpTransactions {
+    ///
+    /// All transactions, ordered by connection and then by request order within each connection
+    ///
+    pub fn transactions(&self) -> std::vec::Vec<HttpTransaction> {
+        self.connections.connections().into_iter().flat_map(connection_transactions).collect()
+    }
+
+    pub fn add_record(&mut self, record: &PcapRecord) {
+        self.connections.add_record(record);
+    }
+
+    pub fn from_records<'a, I>(records: I) -> HttpTransactions where I: IntoIterator<Item=&'a PcapRecord> {
+        let mut transactions = HttpTransactions::default();
+        for record in records {
+            transactions.add_record(record);
+        }
+        transactions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+    use super::super::reassembly::tests::tcp_record;
+
+    #[test]
+    fn pipelined_transactions() {
+        let _ = env_logger::try_init();
+
+        let first = b"GET /a HTTP/1.1\r\nHost: example.com\r\n\r\n";
+        let second = b"HEAD /b HTTP/1.1\r\nHost: example.com\r\n\r\n";
+        let records = vec![
+            tcp_record(1, true, 1, 0x18, first),
+            tcp_record(2, true, 1 + first.len() as u32, 0x18, second),
+            tcp_record(3, false, 1, 0x18, b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok"),
+            tcp_record(5, false, 66, 0x18, b"HTTP/1.1 404 Not Found\r\nContent-Length: 9\r\n\r\n")
+        ];
+        let transactions = HttpTransactions::from_records(&records).transactions();
+
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(transactions[0].method, "GET");
+        assert_eq!(transactions[0].uri, "/a");
+        assert_eq!(transactions[0].host, Some("example.com".to_string()));
+        assert_eq!(transactions[0].status, Some(200));
+        assert_eq!(transactions[0].response_size, Some(2));
+        assert_eq!(transactions[0].latency(), Some(std::time::Duration::from_secs(2)));
+        assert_eq!(transactions[1].method, "HEAD");
+        assert_eq!(transactions[1].status, Some(404));
+        assert_eq!(transactions[1].response_size, Some(0));
+        assert_eq!(transactions[1].latency(), Some(std::time::Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn unanswered_transaction() {
+        let _ = env_logger::try_init();
+
+        let records = vec![
+            tcp_record(1, true, 1, 0x18, b"POST /upload HTTP/1.1\r\nContent-Length: 3\r\n\r\nabc")
+        ];
+        let transactions = HttpTransactions::from_records(&records).transactions();
+
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(transactions[0].request_size, 3);
+        assert_eq!(transactions[0].status, None);
+        assert_eq!(transactions[0].latency(), None);
+    }
+}
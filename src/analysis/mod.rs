@@ -0,0 +1,29 @@
+pub mod prelude {
+    pub use super::super::prelude::*;
+    pub use super::super::{layer2, layer3, layer4, layer7};
+}
+
+pub mod arp_table;
+#[cfg(feature = "flows")]
+pub mod checksum;
+#[cfg(feature = "flows")]
+pub mod defrag;
+#[cfg(feature = "l7-dhcp")]
+pub mod dhcp_leases;
+#[cfg(feature = "flows")]
+pub mod ecn;
+#[cfg(feature = "flows")]
+pub mod flow_stats;
+#[cfg(all(feature = "l7-http", feature = "flows"))]
+pub mod http_transactions;
+#[cfg(feature = "l7-dns")]
+pub mod passive_dns;
+#[cfg(feature = "flows")]
+pub mod payload_digest;
+#[cfg(feature = "flows")]
+pub mod reassembly;
+#[cfg(feature = "ipv6")]
+pub mod router_advertisements;
+pub mod throughput;
+#[cfg(all(feature = "l7-tls", feature = "flows"))]
+pub mod tls_sessions;
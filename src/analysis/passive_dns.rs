@@ -0,0 +1,237 @@
+use super::prelude::*;
+use super::super::parsed::ParsedRecord;
+
+use self::layer4::Layer4;
+use self::layer7::dns::{self, Dns, RecordData, RecordType};
+
+use std;
+use std::collections::HashMap;
+
+///
+/// A distinct (name, type, data) answer and when it was observed
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PassiveDnsRecord {
+    pub name: String,
+    pub record_type: RecordType,
+    pub data: RecordData,
+    pub first_seen: std::time::SystemTime,
+    pub last_seen: std::time::SystemTime,
+    pub count: usize
+}
+
+fn json_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c)
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn json_time(t: &std::time::SystemTime) -> String {
+    let since_epoch = t.duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+    format!("{}.{:06}", since_epoch.as_secs(), since_epoch.subsec_micros())
+}
+
+impl PassiveDnsRecord {
+    ///
+    /// Record as a single line json object, times in seconds since the epoch
+    ///
+    pub fn to_json(&self) -> String {
+        format!("{{\"name\":{},\"rrtype\":{},\"rdata\":{},\"first_seen\":{},\"last_seen\":{},\"count\":{}}}",
+            json_string(&self.name),
+            json_string(&self.record_type.to_string()),
+            json_string(&self.data.to_string()),
+            json_time(&self.first_seen),
+            json_time(&self.last_seen),
+            self.count
+        )
+    }
+}
+
+///
+/// Passive DNS dataset aggregated from the answers of observed DNS responses
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PassiveDns {
+    records: HashMap<(String, RecordType, RecordData), PassiveDnsRecord>
+}
+
+fn dns_payload(parsed: &ParsedRecord) -> Option<&[u8]> {
+    let payload = parsed.payload();
+    match parsed.layer4() {
+        Some(&Layer4::Udp(ref udp)) if udp.src_port() == dns::PORT || udp.dst_port() == dns::PORT => Some(payload),
+        Some(&Layer4::Tcp(ref tcp)) if tcp.src_port() == dns::PORT || tcp.dst_port() == dns::PORT => {
+            //messages over tcp are prefixed with their length, only whole messages in a segment are read
+            if payload.len() > 2 && ((payload[0] as usize) << 8 | payload[1] as usize) == payload.len() - 2 {
+                Some(&payload[2..])
+            } else {
+                None
+            }
+        }
+        _ => None
+    }
+}
+
+impl PassiveDns {
+    ///
+    /// All records, ordered by name, type and first time seen
+    ///
+    pub fn records(&self) -> std::vec::Vec<&PassiveDnsRecord> {
+        let mut records = self.records.values().collect::<std::vec::Vec<_>>();
+        records.sort_by(|a, b| {
+            a.name.cmp(&b.name)
+                .then_with(|| a.record_type.cmp(&b.record_type))
+                .then_with(|| a.first_seen.cmp(&b.first_seen))
+        });
+        records
+    }
+
+    ///
+    /// Write records as newline delimited json, one object per line
+    ///
+    pub fn write_ndjson<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        for record in self.records() {
+            writeln!(writer, "{}", record.to_json())?;
+        }
+        Ok(())
+    }
+
+    fn observe(&mut self, dns: &Dns, timestamp: std::time::SystemTime) {
+        if !dns.is_response() {
+            return
+        }
+        for answer in dns.answers() {
+            let name = answer.name().to_lowercase();
+            let key = (name.clone(), answer.record_type(), answer.data().clone());
+            let record = self.records.entry(key).or_insert_with(|| PassiveDnsRecord {
+                name: name,
+                record_type: answer.record_type(),
+                data: answer.data().clone(),
+                first_seen: timestamp,
+                last_seen: timestamp,
+                count: 0
+            });
+            if timestamp < record.first_seen {
+                record.first_seen = timestamp;
+            }
+            if timestamp > record.last_seen {
+                record.last_seen = timestamp;
+            }
+            record.count += 1;
+        }
+    }
+
+    pub fn add_record(&mut self, record: &PcapRecord) {
+        let parsed = match ParsedRecord::try_from(record.clone()) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                debug!("Could not parse record: {:?}", e);
+                return
+            }
+        };
+        if let Some(payload) = dns_payload(&parsed) {
+            match Dns::parse(payload) {
+                Ok( (_, dns) ) => self.observe(&dns, *record.timestamp()),
+                Err(e) => debug!("Could not parse dns: {:?}", e)
+            }
+        }
+    }
+
+    pub fn from_records<'a, I>(records: I) -> PassiveDns where I: IntoIterator<Item=&'a PcapRecord> {
+        let mut passive_dns = PassiveDns::default();
+        for record in records {
+            passive_dns.add_record(record);
+        }
+        passive_dns
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+    use layer7::dns::tests::RESPONSE_DATA;
+
+    fn dns_record(seconds: u32, dns: &[u8]) -> PcapRecord {
+        let udp_length = 8 + dns.len();
+        let ip_length = 20 + udp_length;
+        let mut payload = vec![
+            0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x01u8, //dst mac
+            0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x02u8, //src mac
+            0x08u8, 0x00u8, //ipv4
+            //ipv4
+            0x45u8, //version, header length
+            0x00u8, //tos
+            (ip_length >> 8) as u8, ip_length as u8, //total length
+            0x00u8, 0x00u8, //id
+            0x00u8, 0x00u8, //flags, fragment offset
+            0x40u8, //ttl
+            0x11u8, //udp
+            0x00u8, 0x00u8, //checksum
+            0x08u8, 0x08u8, 0x08u8, 0x08u8, //src ip 8.8.8.8
+            0x0Au8, 0x00u8, 0x00u8, 0x01u8, //dst ip 10.0.0.1
+            //udp
+            0x00u8, 0x35u8, //src port 53
+            0xC3u8, 0x50u8, //dst port 50000
+            (udp_length >> 8) as u8, udp_length as u8, //length
+            0x00u8, 0x00u8 //checksum
+        ];
+        payload.extend_from_slice(dns);
+        PcapRecord::new(PcapRecord::convert_packet_time(seconds, 0), payload.len() as u32, payload.len() as u32, payload)
+    }
+
+    #[test]
+    fn passive_dns_records() {
+        let _ = env_logger::try_init();
+
+        let mut query = RESPONSE_DATA[..33].to_vec();
+        query[2] = 0x01u8; //query, recursion desired
+        query[7] = 0x00u8; //no answers
+
+        let records = vec![
+            dns_record(1, &query),
+            dns_record(2, RESPONSE_DATA),
+            dns_record(5, RESPONSE_DATA)
+        ];
+        let passive_dns = PassiveDns::from_records(&records);
+
+        let dataset = passive_dns.records();
+        assert_eq!(dataset.len(), 2);
+        assert_eq!(dataset[0].name, "web.example.com");
+        assert_eq!(dataset[0].record_type, RecordType::A);
+        assert_eq!(dataset[0].data, RecordData::A(std::net::Ipv4Addr::new(93, 184, 216, 34)));
+        assert_eq!(dataset[0].first_seen, PcapRecord::convert_packet_time(2, 0));
+        assert_eq!(dataset[0].last_seen, PcapRecord::convert_packet_time(5, 0));
+        assert_eq!(dataset[0].count, 2);
+        assert_eq!(dataset[1].name, "www.example.com");
+        assert_eq!(dataset[1].record_type, RecordType::Cname);
+    }
+
+    #[test]
+    fn passive_dns_ndjson() {
+        let _ = env_logger::try_init();
+
+        let passive_dns = PassiveDns::from_records(vec![&dns_record(2, RESPONSE_DATA)]);
+
+        let mut out = vec![];
+        passive_dns.write_ndjson(&mut out).expect("Could not write");
+
+        assert_eq!(String::from_utf8(out).expect("Invalid utf8"), concat!(
+            "{\"name\":\"web.example.com\",\"rrtype\":\"A\",\"rdata\":\"93.184.216.34\",\"first_seen\":2.000000,\"last_seen\":2.000000,\"count\":1}\n",
+            "{\"name\":\"www.example.com\",\"rrtype\":\"CNAME\",\"rdata\":\"web.example.com\",\"first_seen\":2.000000,\"last_seen\":2.000000,\"count\":1}\n"
+        ));
+        assert_eq!(json_string("a\"b\\\u{1}"), "\"a\\\"b\\\\\\u0001\"");
+    }
+}
@@ -0,0 +1,281 @@
+use super::prelude::*;
+use super::super::flow::FlowKey;
+use super::super::parsed::ParsedRecord;
+
+use self::layer4::Layer4;
+
+use std;
+use std::collections::{HashMap, HashSet};
+
+///
+/// Incremental digest over payload bytes. Sha-256 and xxHash64 are provided with the `sha2` and
+/// `twox-hash` features, other algorithms can be plugged in by implementing this trait.
+///
+pub trait PayloadDigest: Clone + Default {
+    fn update(&mut self, data: &[u8]);
+    fn finish(&self) -> std::vec::Vec<u8>;
+}
+
+///
+/// Sha-256, for matching against published indicators of compromise
+///
+#[cfg(feature = "sha2")]
+#[derive(Clone, Default)]
+pub struct Sha256(sha2::Sha256);
+
+#[cfg(feature = "sha2")]
+impl PayloadDigest for Sha256 {
+    fn update(&mut self, data: &[u8]) {
+        sha2::Digest::update(&mut self.0, data);
+    }
+    fn finish(&self) -> std::vec::Vec<u8> {
+        sha2::Digest::finalize(self.0.clone()).to_vec()
+    }
+}
+
+#[cfg(feature = "sha2")]
+impl std::fmt::Debug for Sha256 {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Sha256")
+    }
+}
+
+///
+/// xxHash64 with a zero seed, a fast non cryptographic digest for deduplication
+///
+#[cfg(feature = "twox-hash")]
+#[derive(Clone, Default)]
+pub struct XxHash64(twox_hash::XxHash64);
+
+#[cfg(feature = "twox-hash")]
+impl PayloadDigest for XxHash64 {
+    fn update(&mut self, data: &[u8]) {
+        std::hash::Hasher::write(&mut self.0, data);
+    }
+    fn finish(&self) -> std::vec::Vec<u8> {
+        let hash = std::hash::Hasher::finish(&self.0);
+        (0..8).rev().map(|idx| (hash >> (idx * 8)) as u8).collect()
+    }
+}
+
+#[cfg(feature = "twox-hash")]
+impl std::fmt::Debug for XxHash64 {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "XxHash64")
+    }
+}
+
+///
+/// Lowercase hex of a digest, as indicator lists publish them
+///
+pub fn to_hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn l4_payload(parsed: &ParsedRecord) -> Option<&[u8]> {
+    match parsed.layer4() {
+        Some(&Layer4::Tcp(ref tcp)) => Some(tcp.payload()),
+        Some(&Layer4::Udp(ref udp)) => Some(udp.payload()),
+        None => None
+    }
+}
+
+///
+/// Digest of the tcp or udp payload of a record, if it has a non empty one
+///
+pub fn record_digest<D: PayloadDigest>(record: &PcapRecord) -> Option<std::vec::Vec<u8>> {
+    let parsed = ParsedRecord::try_from(record.clone()).ok()?;
+    let payload = l4_payload(&parsed).filter(|p| !p.is_empty())?;
+    let mut digest = D::default();
+    digest.update(payload);
+    Some(digest.finish())
+}
+
+///
+/// Digests of the payload of a flow: of each packet, and of everything each side sent in capture order
+///
+#[derive(Clone, Debug)]
+pub struct FlowDigest<D> {
+    pub key: FlowKey,
+    pub first_seen: std::time::SystemTime,
+    client: D,
+    server: D,
+    packets: HashSet<std::vec::Vec<u8>>
+}
+
+impl<D: PayloadDigest> FlowDigest<D> {
+    pub fn client_digest(&self) -> std::vec::Vec<u8> {
+        self.client.finish()
+    }
+
+    pub fn server_digest(&self) -> std::vec::Vec<u8> {
+        self.server.finish()
+    }
+
+    ///
+    /// Distinct digests of the payload of each packet in the flow
+    ///
+    pub fn packet_digests(&self) -> &HashSet<std::vec::Vec<u8>> {
+        &self.packets
+    }
+
+    ///
+    /// Whether `digest` is that of a packet payload or of either side's payload in this flow
+    ///
+    pub fn contains(&self, digest: &[u8]) -> bool {
+        self.packets.contains(digest) || self.client_digest() == digest || self.server_digest() == digest
+    }
+}
+
+///
+/// Payload digests of each tcp and udp flow in a capture, for content level deduplication and
+/// indicator matching
+///
+#[derive(Clone, Debug)]
+pub struct PayloadDigests<D> {
+    flows: HashMap<FlowKey, FlowDigest<D>>
+}
+
+impl<D> Default for PayloadDigests<D> {
+    fn default() -> PayloadDigests<D> {
+        PayloadDigests {
+            flows: HashMap::new()
+        }
+    }
+}
+
+impl<D: PayloadDigest> PayloadDigests<D> {
+    ///
+    /// All flows, ordered by time first seen
+    ///
+    pub fn flows(&self) -> std::vec::Vec<&FlowDigest<D>> {
+        let mut flows = self.flows.values().collect::<std::vec::Vec<_>>();
+        flows.sort_by(|a, b| a.first_seen.cmp(&b.first_seen));
+        flows
+    }
+
+    ///
+    /// Digests for the flow `key` belongs to, in either direction
+    ///
+    pub fn flow(&self, key: &FlowKey) -> Option<&FlowDigest<D>> {
+        self.flows.get(key).or_else(|| self.flows.get(&key.reversed()))
+    }
+
+    ///
+    /// Flows carrying a payload with `digest`, ordered by time first seen
+    ///
+    pub fn matching(&self, digest: &[u8]) -> std::vec::Vec<&FlowDigest<D>> {
+        self.flows().into_iter().filter(|f| f.contains(digest)).collect()
+    }
+
+    pub fn add_record(&mut self, record: &PcapRecord) {
+        let parsed = match ParsedRecord::try_from(record.clone()) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                debug!("Could not parse record: {:?}", e);
+                return
+            }
+        };
+        let key = match parsed.flow_key() {
+            Ok(key) => key,
+            Err(_) => return
+        };
+        let payload = match l4_payload(&parsed) {
+            Some(payload) => payload,
+            None => return
+        };
+
+        let reverse = key.reversed();
+        let (flow_key, to_server) = if self.flows.contains_key(&reverse) { (reverse, false) } else { (key, true) };
+        let flow = self.flows.entry(flow_key.clone()).or_insert_with(|| FlowDigest {
+            key: flow_key,
+            first_seen: *record.timestamp(),
+            client: D::default(),
+            server: D::default(),
+            packets: HashSet::new()
+        });
+        if payload.is_empty() {
+            return
+        }
+
+        let mut digest = D::default();
+        digest.update(payload);
+        flow.packets.insert(digest.finish());
+        if to_server {
+            flow.client.update(payload);
+        } else {
+            flow.server.update(payload);
+        }
+    }
+
+    pub fn from_records<'a, I>(records: I) -> PayloadDigests<D> where I: IntoIterator<Item=&'a PcapRecord> {
+        let mut digests = PayloadDigests::default();
+        for record in records {
+            digests.add_record(record);
+        }
+        digests
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+    use super::super::reassembly::tests::tcp_record;
+
+    ///
+    /// Sum of bytes, enough to tell payloads apart in tests without a digest feature
+    ///
+    #[derive(Clone, Debug, Default)]
+    struct Sum(u64);
+
+    impl PayloadDigest for Sum {
+        fn update(&mut self, data: &[u8]) {
+            self.0 += data.iter().map(|b| *b as u64).sum::<u64>();
+        }
+        fn finish(&self) -> std::vec::Vec<u8> {
+            vec![(self.0 >> 8) as u8, self.0 as u8]
+        }
+    }
+
+    #[test]
+    fn flow_payload_digests() {
+        let _ = env_logger::try_init();
+
+        let records = vec![
+            tcp_record(1, true, 1, 0x02, &[]),
+            tcp_record(2, true, 2, 0x18, &[1, 2]),
+            tcp_record(3, false, 1, 0x18, &[10]),
+            tcp_record(4, true, 4, 0x18, &[3])
+        ];
+        let digests = PayloadDigests::<Sum>::from_records(&records);
+
+        let flows = digests.flows();
+        assert_eq!(flows.len(), 1);
+        assert_eq!(flows[0].key.dst_port, 80);
+        assert_eq!(flows[0].client_digest(), vec![0, 6]);
+        assert_eq!(flows[0].server_digest(), vec![0, 10]);
+        assert_eq!(flows[0].packet_digests().len(), 2);
+        assert_eq!(digests.matching(&[0, 3]).len(), 1);
+        assert!(digests.matching(&[0, 4]).is_empty());
+        assert_eq!(record_digest::<Sum>(&records[1]), Some(vec![0, 3]));
+        assert_eq!(record_digest::<Sum>(&records[0]), None);
+    }
+
+    #[cfg(feature = "sha2")]
+    #[test]
+    fn sha256_digest() {
+        let mut digest = Sha256::default();
+        digest.update(b"a");
+        digest.update(b"bc");
+
+        assert_eq!(to_hex(&digest.finish()), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    }
+
+    #[cfg(feature = "twox-hash")]
+    #[test]
+    fn xxhash64_digest() {
+        assert_eq!(to_hex(&XxHash64::default().finish()), "ef46db3751d8e999");
+    }
+}
@@ -0,0 +1,287 @@
+use super::prelude::*;
+use super::super::flow::FlowKey;
+use super::super::parsed::ParsedRecord;
+
+use self::layer4::Layer4;
+use self::layer4::tcp::{Tcp, FLAG_ACK, FLAG_SYN};
+
+use std;
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Segment {
+    sequence_number: u32,
+    timestamp: std::time::SystemTime,
+    payload: std::vec::Vec<u8>
+}
+
+///
+/// Payload segments sent in one direction of a tcp connection
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct TcpStream {
+    segments: std::vec::Vec<Segment>
+}
+
+///
+/// Payload of one direction of a connection, in sequence order with retransmissions removed
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ReassembledStream {
+    data: std::vec::Vec<u8>,
+    timestamps: std::vec::Vec<(usize, std::time::SystemTime)>,
+    gaps: usize
+}
+
+impl ReassembledStream {
+    pub fn data(&self) -> &std::vec::Vec<u8> {
+        &self.data
+    }
+
+    ///
+    /// Number of places where bytes were never captured, data on either side is joined
+    ///
+    pub fn gaps(&self) -> usize {
+        self.gaps
+    }
+
+    ///
+    /// Time the segment carrying byte `offset` of the stream was captured
+    ///
+    pub fn timestamp_at(&self, offset: usize) -> Option<std::time::SystemTime> {
+        match self.timestamps.binary_search_by(|&(start, _)| start.cmp(&offset)) {
+            Ok(idx) => Some(self.timestamps[idx].1),
+            Err(0) => None,
+            Err(idx) => Some(self.timestamps[idx - 1].1)
+        }.filter(|_| offset < self.data.len())
+    }
+}
+
+impl TcpStream {
+    pub fn add_segment(&mut self, sequence_number: u32, timestamp: std::time::SystemTime, payload: &[u8]) {
+        if !payload.is_empty() {
+            self.segments.push(Segment {
+                sequence_number: sequence_number,
+                timestamp: timestamp,
+                payload: payload.into()
+            });
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    pub fn reassemble(&self) -> ReassembledStream {
+        let mut stream = ReassembledStream::default();
+        let base = match self.segments.first() {
+            Some(s) => s.sequence_number,
+            None => return stream
+        };
+
+        //offsets relative to the first segment seen, which may be negative if it arrived out of order
+        let mut segments = self.segments.iter()
+            .map(|s| (s.sequence_number.wrapping_sub(base) as i32 as i64, s))
+            .collect::<std::vec::Vec<_>>();
+        segments.sort_by_key(|&(offset, s)| (offset, s.timestamp));
+
+        let mut next = segments[0].0;
+        for (offset, segment) in segments {
+            let end = offset + segment.payload.len() as i64;
+            if end <= next {
+                continue
+            }
+            if offset > next {
+                stream.gaps += 1;
+            }
+            let skip = if offset < next { (next - offset) as usize } else { 0 };
+            stream.timestamps.push( (stream.data.len(), segment.timestamp) );
+            stream.data.extend_from_slice(&segment.payload[skip..]);
+            next = end;
+        }
+        stream
+    }
+}
+
+///
+/// Both directions of a tcp connection, keyed from client to server
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TcpConnection {
+    pub key: FlowKey,
+    pub to_server: TcpStream,
+    pub to_client: TcpStream,
+    pub first_seen: std::time::SystemTime
+}
+
+///
+/// Tcp connections in a capture with their payload collected for reassembly
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TcpConnections {
+    connections: HashMap<FlowKey, TcpConnection>
+}
+
+///
+/// Flow key and tcp header of a record, if it is a tcp segment
+///
+pub fn tcp_segment(record: &PcapRecord) -> Option<(FlowKey, Tcp)> {
+    let parsed = match ParsedRecord::try_from(record.clone()) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            debug!("Could not parse record: {:?}", e);
+            return None
+        }
+    };
+    match (parsed.flow_key(), parsed.layer4()) {
+        (Ok(key), Some(&Layer4::Tcp(ref tcp))) => Some( (key, tcp.clone()) ),
+        _ => None
+    }
+}
+
+impl TcpConnections {
+    ///
+    /// All connections, ordered by time first seen
+    ///
+    pub fn connections(&self) -> std::vec::Vec<&TcpConnection> {
+        let mut connections = self.connections.values().collect::<std::vec::Vec<_>>();
+        connections.sort_by(|a, b| a.first_seen.cmp(&b.first_seen));
+        connections
+    }
+
+    pub fn connection(&self, key: &FlowKey) -> Option<&TcpConnection> {
+        self.connections.get(key)
+    }
+
+    pub fn add_record(&mut self, record: &PcapRecord) {
+        let (key, tcp) = match tcp_segment(record) {
+            Some(segment) => segment,
+            None => return
+        };
+        let timestamp = *record.timestamp();
+
+        let reverse = key.reversed();
+        let (connection, to_server) = if self.connections.contains_key(&key) {
+            (self.connections.get_mut(&key), true)
+        } else if self.connections.contains_key(&reverse) {
+            (self.connections.get_mut(&reverse), false)
+        } else {
+            //the sender of a syn is the client, the sender of a syn ack the server, otherwise assume
+            //the first sender seen is the client
+            let to_server = !tcp.has_flags(FLAG_SYN | FLAG_ACK);
+            let client_key = if to_server { key } else { reverse };
+            let connection = self.connections.entry(client_key.clone()).or_insert_with(|| TcpConnection {
+                key: client_key,
+                to_server: TcpStream::default(),
+                to_client: TcpStream::default(),
+                first_seen: timestamp
+            });
+            (Some(connection), to_server)
+        };
+
+        if let Some(connection) = connection {
+            //a syn consumes one sequence number ahead of the payload
+            let sequence_number = if tcp.has_flags(FLAG_SYN) { tcp.sequence_number().wrapping_add(1) } else { tcp.sequence_number() };
+            let stream = if to_server { &mut connection.to_server } else { &mut connection.to_client };
+            stream.add_segment(sequence_number, timestamp, tcp.payload());
+        }
+    }
+
+    pub fn from_records<'a, I>(records: I) -> TcpConnections where I: IntoIterator<Item=&'a PcapRecord> {
+        let mut connections = TcpConnections::default();
+        for record in records {
+            connections.add_record(record);
+        }
+        connections
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    ///
+    /// Tcp segment between client 10.0.0.1:50000 and server 10.0.0.2:80
+    ///
+    pub fn tcp_record(seconds: u32, to_server: bool, sequence_number: u32, flags: u8, data: &[u8]) -> PcapRecord {
+        let ip_length = 20 + 20 + data.len();
+        let (src_ip, dst_ip, src_port, dst_port) = if to_server {
+            (1u8, 2u8, 50000u16, 80u16)
+        } else {
+            (2u8, 1u8, 80u16, 50000u16)
+        };
+        let mut payload = vec![
+            0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, dst_ip, //dst mac
+            0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, src_ip, //src mac
+            0x08u8, 0x00u8, //ipv4
+            //ipv4
+            0x45u8, //version, header length
+            0x00u8, //tos
+            (ip_length >> 8) as u8, ip_length as u8, //total length
+            0x00u8, 0x00u8, //id
+            0x00u8, 0x00u8, //flags, fragment offset
+            0x40u8, //ttl
+            0x06u8, //tcp
+            0x00u8, 0x00u8, //checksum
+            0x0Au8, 0x00u8, 0x00u8, src_ip, //src ip
+            0x0Au8, 0x00u8, 0x00u8, dst_ip, //dst ip
+            //tcp
+            (src_port >> 8) as u8, src_port as u8, //src port
+            (dst_port >> 8) as u8, dst_port as u8, //dst port
+            (sequence_number >> 24) as u8, (sequence_number >> 16) as u8, (sequence_number >> 8) as u8, sequence_number as u8, //sequence number
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, //acknowledgement number
+            0x50u8, flags, //header length, flags
+            0xFFu8, 0xFFu8, //window
+            0x00u8, 0x00u8, //checksum
+            0x00u8, 0x00u8 //urgent
+        ];
+        payload.extend_from_slice(data);
+        PcapRecord::new(PcapRecord::convert_packet_time(seconds, 0), payload.len() as u32, payload.len() as u32, payload)
+    }
+
+    #[test]
+    fn reassemble_out_of_order() {
+        let _ = env_logger::try_init();
+
+        let records = vec![
+            tcp_record(1, true, 0xFFFFFFFE, 0x02, &[]), //syn
+            tcp_record(2, false, 100, 0x12, &[]), //syn ack
+            tcp_record(3, true, 0xFFFFFFFF, 0x18, b"abc"),
+            tcp_record(4, true, 5, 0x18, b"ghi"),
+            tcp_record(5, true, 2, 0x18, b"def"),
+            tcp_record(6, true, 2, 0x18, b"def"), //retransmission
+            tcp_record(7, false, 101, 0x18, b"xyz")
+        ];
+        let connections = TcpConnections::from_records(&records);
+
+        assert_eq!(connections.connections().len(), 1);
+        let connection = connections.connections()[0];
+        assert_eq!(connection.key.dst_port, 80);
+
+        let to_server = connection.to_server.reassemble();
+        assert_eq!(to_server.data(), &b"abcdefghi".to_vec());
+        assert_eq!(to_server.gaps(), 0);
+        assert_eq!(to_server.timestamp_at(0), Some(PcapRecord::convert_packet_time(3, 0)));
+        assert_eq!(to_server.timestamp_at(4), Some(PcapRecord::convert_packet_time(5, 0)));
+        assert_eq!(to_server.timestamp_at(8), Some(PcapRecord::convert_packet_time(4, 0)));
+        assert_eq!(to_server.timestamp_at(9), None);
+        assert_eq!(connection.to_client.reassemble().data(), &b"xyz".to_vec());
+    }
+
+    #[test]
+    fn reassemble_gap() {
+        let _ = env_logger::try_init();
+
+        let records = vec![
+            tcp_record(1, true, 10, 0x18, b"abc"),
+            tcp_record(2, true, 16, 0x18, b"ghi")
+        ];
+        let connections = TcpConnections::from_records(&records);
+        let stream = connections.connections()[0].to_server.reassemble();
+
+        assert_eq!(stream.data(), &b"abcghi".to_vec());
+        assert_eq!(stream.gaps(), 1);
+    }
+}
@@ -0,0 +1,470 @@
+use super::prelude::*;
+use super::super::filter::Cidr;
+
+use self::layer2::ethernet::{Ethernet, EthernetTypeId, Layer3Id};
+use self::layer3::InternetProtocolId;
+use self::layer3::ipv6::IPv6;
+
+use std;
+use std::collections::HashMap;
+
+const ROUTER_ADVERTISEMENT: u8 = 134;
+const NEIGHBOR_SOLICITATION: u8 = 135;
+const NEIGHBOR_ADVERTISEMENT: u8 = 136;
+const SOURCE_LINK_LAYER_ADDRESS: u8 = 1;
+const TARGET_LINK_LAYER_ADDRESS: u8 = 2;
+const PREFIX_INFORMATION: u8 = 3;
+const PREFIX_INFORMATION_LENGTH: usize = 32;
+const ROUTER_ADVERTISEMENT_HEADER_LENGTH: usize = 16; //type, code, checksum, hop limit, flags, lifetime, reachable time, retransmit timer
+const NEIGHBOR_DISCOVERY_HEADER_LENGTH: usize = 24; //type, code, checksum, flags, target address
+const MANAGED_FLAG: u8 = 0x80;
+const OTHER_FLAG: u8 = 0x40;
+const ON_LINK_FLAG: u8 = 0x80;
+const AUTONOMOUS_FLAG: u8 = 0x40;
+
+///
+/// Prefix from a router advertisement's prefix information option
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Prefix {
+    pub network: Cidr,
+    ///
+    /// Addresses in the prefix are on the link, so reachable without a router
+    ///
+    pub on_link: bool,
+    ///
+    /// Hosts may form addresses in the prefix with stateless autoconfiguration
+    ///
+    pub autonomous: bool,
+    ///
+    /// Seconds the prefix is valid and preferred for, 0xffffffff meaning forever
+    ///
+    pub valid_lifetime: u32,
+    pub preferred_lifetime: u32
+}
+
+///
+/// Router seen sending advertisements, with what it last advertised
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Router {
+    pub ip: std::net::IpAddr,
+    pub mac: MacAddress,
+    pub hop_limit: u8,
+    ///
+    /// Addresses (managed) or other configuration (other) are available from dhcpv6
+    ///
+    pub managed: bool,
+    pub other: bool,
+    ///
+    /// Seconds the router should be used as a default router for, zero if it should not be
+    ///
+    pub lifetime: u16,
+    pub prefixes: std::vec::Vec<Prefix>,
+    pub first_seen: std::time::SystemTime,
+    pub last_seen: std::time::SystemTime,
+    pub advertisements: usize
+}
+
+impl Router {
+    ///
+    /// Prefixes advertised as still valid
+    ///
+    pub fn valid_prefixes(&self) -> std::vec::Vec<Cidr> {
+        self.prefixes.iter().filter(|p| p.valid_lifetime > 0).map(|p| p.network).collect()
+    }
+}
+
+///
+/// Address a host probed with duplicate address detection before using it, as it does for every
+/// address it autoconfigures
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ProbedAddress {
+    pub ip: std::net::IpAddr,
+    pub mac: MacAddress,
+    ///
+    /// Advertised prefix the address was formed in, if any router advertised one containing it
+    ///
+    pub prefix: Option<Cidr>,
+    pub first_seen: std::time::SystemTime,
+    ///
+    /// Another host answered the probe, claiming the address
+    ///
+    pub duplicate: bool
+}
+
+///
+/// Valid prefix advertised by some routers but not by others advertising prefixes on the same link,
+/// the mark of a rogue or misconfigured router
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PrefixConflict<'a> {
+    pub prefix: Cidr,
+    pub advertised_by: std::vec::Vec<&'a Router>,
+    pub missing_from: std::vec::Vec<&'a Router>
+}
+
+///
+/// Routers, prefixes and autoconfigured addresses observed in ipv6 neighbor discovery traffic
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RouterAdvertisements {
+    routers: HashMap<(std::net::IpAddr, MacAddress), Router>,
+    probes: HashMap<std::net::IpAddr, ProbedAddress>
+}
+
+///
+/// Neighbor discovery options as (type, option including its type and length), stopping at the first
+/// malformed option
+///
+fn options(mut options: &[u8]) -> std::vec::Vec<(u8, &[u8])> {
+    let mut found = vec![];
+    while options.len() >= 2 {
+        let length = options[1] as usize * 8;
+        if length == 0 || length > options.len() {
+            break
+        }
+        found.push( (options[0], &options[..length]) );
+        options = &options[length..];
+    }
+    found
+}
+
+fn link_layer_option(message: &[u8], option_type: u8) -> Option<MacAddress> {
+    options(message).into_iter()
+        .filter(|&(t, o)| t == option_type && o.len() >= 2 + MAC_LENGTH)
+        .map(|(_, o)| MacAddress(array_ref![o, 2, MAC_LENGTH].clone()))
+        .next()
+}
+
+fn be_u32(bytes: &[u8]) -> u32 {
+    (bytes[0] as u32) << 24 | (bytes[1] as u32) << 16 | (bytes[2] as u32) << 8 | bytes[3] as u32
+}
+
+fn prefix_information(option: &[u8]) -> Option<Prefix> {
+    if option.len() < PREFIX_INFORMATION_LENGTH {
+        return None
+    }
+    let network = std::net::IpAddr::V6(std::net::Ipv6Addr::from(array_ref![option, 16, 16].clone()));
+    Some(Prefix {
+        network: Cidr::new(network, option[2]).ok()?,
+        on_link: option[3] & ON_LINK_FLAG != 0,
+        autonomous: option[3] & AUTONOMOUS_FLAG != 0,
+        valid_lifetime: be_u32(&option[4..8]),
+        preferred_lifetime: be_u32(&option[8..12])
+    })
+}
+
+impl RouterAdvertisements {
+    ///
+    /// All routers, ordered by ip and then first time seen
+    ///
+    pub fn routers(&self) -> std::vec::Vec<&Router> {
+        let mut routers = self.routers.values().collect::<std::vec::Vec<_>>();
+        routers.sort_by(|a, b| a.ip.cmp(&b.ip).then_with(|| a.first_seen.cmp(&b.first_seen)));
+        routers
+    }
+
+    ///
+    /// Valid prefixes advertised by any router, ordered by network
+    ///
+    pub fn prefixes(&self) -> std::vec::Vec<Cidr> {
+        let mut prefixes = self.routers.values().flat_map(|r| r.valid_prefixes()).collect::<std::vec::Vec<_>>();
+        prefixes.sort_by(|a, b| a.network().cmp(b.network()).then_with(|| a.prefix().cmp(&b.prefix())));
+        prefixes.dedup();
+        prefixes
+    }
+
+    ///
+    /// Addresses probed with duplicate address detection, ordered by ip
+    ///
+    pub fn probed_addresses(&self) -> std::vec::Vec<&ProbedAddress> {
+        let mut probes = self.probes.values().collect::<std::vec::Vec<_>>();
+        probes.sort_by(|a, b| a.ip.cmp(&b.ip));
+        probes
+    }
+
+    ///
+    /// Prefixes not advertised by every router advertising prefixes. Empty when a single router
+    /// advertises, or all routers agree.
+    ///
+    pub fn conflicts<'a>(&'a self) -> std::vec::Vec<PrefixConflict<'a>> {
+        let routers = self.routers().into_iter()
+            .filter(|r| !r.valid_prefixes().is_empty())
+            .collect::<std::vec::Vec<_>>();
+        self.prefixes().into_iter().filter_map(|prefix| {
+            let (advertised_by, missing_from): (std::vec::Vec<&Router>, std::vec::Vec<&Router>) = routers.iter()
+                .partition(|r| r.valid_prefixes().contains(&prefix));
+            if missing_from.is_empty() {
+                None
+            } else {
+                Some(PrefixConflict {
+                    prefix: prefix,
+                    advertised_by: advertised_by,
+                    missing_from: missing_from
+                })
+            }
+        }).collect()
+    }
+
+    ///
+    /// More than one router advertised prefixes, and they disagree on them
+    ///
+    pub fn is_rogue_suspected(&self) -> bool {
+        !self.conflicts().is_empty()
+    }
+
+    fn observe_router_advertisement(&mut self, ipv6: &IPv6, src_mac: &MacAddress, timestamp: std::time::SystemTime) {
+        let message = &ipv6.payload()[..];
+        let advertised = options(&message[ROUTER_ADVERTISEMENT_HEADER_LENGTH..]);
+        let mac = link_layer_option(&message[ROUTER_ADVERTISEMENT_HEADER_LENGTH..], SOURCE_LINK_LAYER_ADDRESS).unwrap_or(*src_mac);
+        let ip = *ipv6.src_ip();
+
+        let router = self.routers.entry( (ip, mac) ).or_insert_with(|| Router {
+            ip: ip,
+            mac: mac,
+            hop_limit: 0,
+            managed: false,
+            other: false,
+            lifetime: 0,
+            prefixes: vec![],
+            first_seen: timestamp,
+            last_seen: timestamp,
+            advertisements: 0
+        });
+        if timestamp < router.first_seen {
+            router.first_seen = timestamp;
+        }
+        if timestamp >= router.last_seen {
+            router.last_seen = timestamp;
+            router.hop_limit = message[4];
+            router.managed = message[5] & MANAGED_FLAG != 0;
+            router.other = message[5] & OTHER_FLAG != 0;
+            router.lifetime = (message[6] as u16) << 8 | message[7] as u16;
+        }
+        router.advertisements += 1;
+
+        let prefixes = advertised.into_iter()
+            .filter(|&(t, _)| t == PREFIX_INFORMATION)
+            .filter_map(|(_, o)| prefix_information(o));
+        for prefix in prefixes {
+            match router.prefixes.iter().position(|p| p.network == prefix.network) {
+                Some(idx) => router.prefixes[idx] = prefix,
+                None => router.prefixes.push(prefix)
+            }
+        }
+    }
+
+    fn observe_neighbor_discovery(&mut self, ipv6: &IPv6, src_mac: &MacAddress, timestamp: std::time::SystemTime) {
+        let message = &ipv6.payload()[..];
+        let target = std::net::IpAddr::V6(std::net::Ipv6Addr::from(array_ref![message, 8, 16].clone()));
+
+        match message[0] {
+            NEIGHBOR_SOLICITATION if ipv6.src_ip().is_unspecified() => {
+                //duplicate address detection, the sender has no address yet
+                let prefix = self.prefixes().into_iter().filter(|p| p.contains(&target)).max_by_key(|p| p.prefix());
+                self.probes.entry(target).or_insert_with(|| ProbedAddress {
+                    ip: target,
+                    mac: *src_mac,
+                    prefix: prefix,
+                    first_seen: timestamp,
+                    duplicate: false
+                });
+            }
+            NEIGHBOR_ADVERTISEMENT => {
+                let mac = link_layer_option(&message[NEIGHBOR_DISCOVERY_HEADER_LENGTH..], TARGET_LINK_LAYER_ADDRESS).unwrap_or(*src_mac);
+                if let Some(probe) = self.probes.get_mut(&target) {
+                    if probe.mac != mac && timestamp >= probe.first_seen {
+                        probe.duplicate = true;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn add_record(&mut self, record: &PcapRecord) {
+        let l2 = match Ethernet::parse(record.payload()) {
+            Ok( (_, l2) ) => l2,
+            Err(e) => {
+                debug!("Could not parse ethernet: {:?}", e);
+                return
+            }
+        };
+        if *l2.ether_type() != EthernetTypeId::L3(Layer3Id::IPv6) {
+            return
+        }
+        let ipv6 = match IPv6::parse(l2.payload()) {
+            Ok( (_, ipv6) ) => ipv6,
+            Err(e) => {
+                debug!("Could not parse ipv6: {:?}", e);
+                return
+            }
+        };
+        if *ipv6.protocol() != InternetProtocolId::Icmpv6 {
+            return
+        }
+
+        let message_type = ipv6.payload().first().cloned();
+        let length = ipv6.payload().len();
+        match message_type {
+            Some(ROUTER_ADVERTISEMENT) if length >= ROUTER_ADVERTISEMENT_HEADER_LENGTH => {
+                self.observe_router_advertisement(&ipv6, l2.src_mac(), *record.timestamp())
+            }
+            Some(NEIGHBOR_SOLICITATION) | Some(NEIGHBOR_ADVERTISEMENT) if length >= NEIGHBOR_DISCOVERY_HEADER_LENGTH => {
+                self.observe_neighbor_discovery(&ipv6, l2.src_mac(), *record.timestamp())
+            }
+            _ => {}
+        }
+    }
+
+    pub fn from_records<'a, I>(records: I) -> RouterAdvertisements where I: IntoIterator<Item=&'a PcapRecord> {
+        let mut advertisements = RouterAdvertisements::default();
+        for record in records {
+            advertisements.add_record(record);
+        }
+        advertisements
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    ///
+    /// Icmpv6 `message` from `src_mac` and `src_ip` to all nodes
+    ///
+    fn icmpv6_record(seconds: u32, src_mac: u8, src_ip: [u8; 16], message: &[u8]) -> PcapRecord {
+        let mut payload = vec![
+            0x33u8, 0x33u8, 0x00u8, 0x00u8, 0x00u8, 0x01u8, //dst mac, all nodes
+            0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, src_mac, //src mac
+            0x86u8, 0xDDu8, //ipv6
+            //ipv6
+            0x60u8, 0x00u8, 0x00u8, 0x00u8, //version, traffic class, flow label
+            (message.len() >> 8) as u8, message.len() as u8, //payload length
+            0x3Au8, //next header, icmpv6
+            0xFFu8 //hop limit
+        ];
+        payload.extend_from_slice(&src_ip);
+        payload.extend_from_slice(&[
+            0xFFu8, 0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8,
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x01u8 //dst ip ff02::1
+        ]);
+        payload.extend_from_slice(message);
+        PcapRecord::new(PcapRecord::convert_packet_time(seconds, 0), payload.len() as u32, payload.len() as u32, payload)
+    }
+
+    fn link_local(host: u8) -> [u8; 16] {
+        [0xFEu8, 0x80u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, host]
+    }
+
+    ///
+    /// Router advertisement from fe80::`host` advertising 2001:db8:`subnet`::/64 for autoconfiguration
+    ///
+    fn router_advertisement(seconds: u32, host: u8, subnet: u8) -> PcapRecord {
+        let message = [
+            0x86u8, //router advertisement
+            0x00u8, //code
+            0x00u8, 0x00u8, //checksum
+            0x40u8, //hop limit, 64
+            0x40u8, //other configuration
+            0x07u8, 0x08u8, //router lifetime, 1800
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, //reachable time
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, //retransmit timer
+            //prefix information
+            0x03u8, 0x04u8, //type, length 32 bytes
+            0x40u8, //prefix length, 64
+            0xC0u8, //on link, autonomous
+            0x00u8, 0x27u8, 0x8Du8, 0x00u8, //valid lifetime, 2592000
+            0x00u8, 0x09u8, 0x3Au8, 0x80u8, //preferred lifetime, 604800
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, //reserved
+            0x20u8, 0x01u8, 0x0Du8, 0xB8u8, 0x00u8, subnet, 0x00u8, 0x00u8,
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, //prefix
+            //source link layer address
+            0x01u8, 0x01u8, //type, length 8 bytes
+            0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, host //mac
+        ];
+        icmpv6_record(seconds, host, link_local(host), &message)
+    }
+
+    ///
+    /// Neighbor solicitation (135) or advertisement (136) for 2001:db8:1::`target`
+    ///
+    fn neighbor_discovery(seconds: u32, message_type: u8, src_mac: u8, src_ip: [u8; 16], target: u8) -> PcapRecord {
+        let message = [
+            message_type,
+            0x00u8, //code
+            0x00u8, 0x00u8, //checksum
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, //flags
+            0x20u8, 0x01u8, 0x0Du8, 0xB8u8, 0x00u8, 0x01u8, 0x00u8, 0x00u8,
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, target //target
+        ];
+        icmpv6_record(seconds, src_mac, src_ip, &message)
+    }
+
+    #[test]
+    fn router_inventory() {
+        let _ = env_logger::try_init();
+
+        let records = vec![
+            router_advertisement(1, 0x01, 0x01),
+            neighbor_discovery(2, NEIGHBOR_SOLICITATION, 0x0A, [0u8; 16], 0x0A),
+            neighbor_discovery(3, NEIGHBOR_SOLICITATION, 0x0B, [0u8; 16], 0x0B),
+            neighbor_discovery(3, NEIGHBOR_ADVERTISEMENT, 0x0C, link_local(0x0C), 0x0B),
+            router_advertisement(4, 0x01, 0x01)
+        ];
+        let advertisements = RouterAdvertisements::from_records(&records);
+
+        let routers = advertisements.routers();
+        assert_eq!(routers.len(), 1);
+        assert_eq!(routers[0].ip, "fe80::1".parse::<std::net::IpAddr>().unwrap());
+        assert_eq!(routers[0].mac, MacAddress([0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x01u8]));
+        assert_eq!(routers[0].hop_limit, 64);
+        assert!(!routers[0].managed);
+        assert!(routers[0].other);
+        assert_eq!(routers[0].lifetime, 1800);
+        assert_eq!(routers[0].advertisements, 2);
+        assert_eq!(routers[0].last_seen, PcapRecord::convert_packet_time(4, 0));
+        assert_eq!(routers[0].prefixes, vec![Prefix {
+            network: "2001:db8:1::/64".parse().unwrap(),
+            on_link: true,
+            autonomous: true,
+            valid_lifetime: 2592000,
+            preferred_lifetime: 604800
+        }]);
+
+        let probes = advertisements.probed_addresses();
+        assert_eq!(probes.len(), 2);
+        assert_eq!(probes[0].ip, "2001:db8:1::a".parse::<std::net::IpAddr>().unwrap());
+        assert_eq!(probes[0].prefix, "2001:db8:1::/64".parse().ok());
+        assert!(!probes[0].duplicate);
+        assert!(probes[1].duplicate);
+
+        assert!(advertisements.conflicts().is_empty());
+        assert!(!advertisements.is_rogue_suspected());
+    }
+
+    #[test]
+    fn rogue_router() {
+        let _ = env_logger::try_init();
+
+        let records = vec![
+            router_advertisement(1, 0x01, 0x01),
+            router_advertisement(2, 0x66, 0x66)
+        ];
+        let advertisements = RouterAdvertisements::from_records(&records);
+
+        assert_eq!(advertisements.routers().len(), 2);
+        assert_eq!(advertisements.prefixes().len(), 2);
+
+        let conflicts = advertisements.conflicts();
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(conflicts[0].prefix, "2001:db8:1::/64".parse().unwrap());
+        assert_eq!(conflicts[0].advertised_by[0].ip, "fe80::1".parse::<std::net::IpAddr>().unwrap());
+        assert_eq!(conflicts[0].missing_from[0].ip, "fe80::66".parse::<std::net::IpAddr>().unwrap());
+        assert!(advertisements.is_rogue_suspected());
+    }
+}
@@ -0,0 +1,389 @@
+use super::prelude::*;
+#[cfg(feature = "flows")]
+use super::super::flow::FlowKey;
+#[cfg(feature = "flows")]
+use super::super::parsed::ParsedRecord;
+
+use std;
+#[cfg(feature = "flows")]
+use std::collections::HashMap;
+use std::collections::VecDeque;
+
+///
+/// Window rates are measured over, unless configured otherwise
+///
+pub const DEFAULT_WINDOW: std::time::Duration = std::time::Duration::from_secs(1);
+
+///
+/// Inter-arrival times are counted in buckets of powers of two microseconds, the last bucket
+/// holding everything from about 35 minutes on
+///
+const INTER_ARRIVAL_BUCKETS: usize = 32;
+
+///
+/// Throughput in bits (of original, not captured, length) and packets per second
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Rate {
+    pub bits_per_second: f64,
+    pub packets_per_second: f64
+}
+
+impl Rate {
+    fn over(bytes: u64, packets: u64, duration: std::time::Duration) -> Rate {
+        let seconds = duration.as_secs_f64();
+        Rate {
+            bits_per_second: (bytes * 8) as f64 / seconds,
+            packets_per_second: packets as f64 / seconds
+        }
+    }
+}
+
+///
+/// Distribution of the time between consecutive packets, with exact minimum, maximum and mean
+/// and percentiles to within a power of two
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct InterArrival {
+    count: u64,
+    total: std::time::Duration,
+    min: Option<std::time::Duration>,
+    max: Option<std::time::Duration>,
+    buckets: [u64; INTER_ARRIVAL_BUCKETS]
+}
+
+impl InterArrival {
+    fn bucket(gap: std::time::Duration) -> usize {
+        let micros = gap.as_secs() * 1_000_000 + gap.subsec_micros() as u64;
+        let bucket = 64 - micros.leading_zeros() as usize;
+        std::cmp::min(bucket, INTER_ARRIVAL_BUCKETS - 1)
+    }
+
+    fn add(&mut self, gap: std::time::Duration) {
+        self.count += 1;
+        self.total += gap;
+        self.min = Some(self.min.map_or(gap, |m| std::cmp::min(m, gap)));
+        self.max = Some(self.max.map_or(gap, |m| std::cmp::max(m, gap)));
+        self.buckets[InterArrival::bucket(gap)] += 1;
+    }
+
+    ///
+    /// Number of gaps measured, one less than the packets seen
+    ///
+    pub fn count(&self) -> u64 { self.count }
+    pub fn min(&self) -> Option<std::time::Duration> { self.min }
+    pub fn max(&self) -> Option<std::time::Duration> { self.max }
+
+    pub fn mean(&self) -> Option<std::time::Duration> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(self.total / self.count as u32)
+        }
+    }
+
+    ///
+    /// Gaps counted per bucket. Bucket 0 holds gaps under a microsecond, and bucket `i` gaps of
+    /// at least 2^(i-1) and under 2^i microseconds.
+    ///
+    pub fn buckets(&self) -> &[u64] { &self.buckets }
+
+    ///
+    /// Upper bound of the bucket holding the `percentile`th gap (0 to 100), capped at the maximum gap
+    ///
+    pub fn percentile(&self, percentile: f64) -> Option<std::time::Duration> {
+        let max = self.max?;
+        let rank = ((percentile / 100.0) * self.count as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (bucket, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                let upper = std::time::Duration::from_micros(1u64 << bucket);
+                return Some(std::cmp::min(upper, max))
+            }
+        }
+        Some(max)
+    }
+}
+
+///
+/// Throughput of a stream of packets: totals, the rate over a sliding window ending at the latest
+/// packet, the peak of that rate and the distribution of inter-arrival times. Packets timestamped
+/// before the latest packet are counted as arriving with it.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct RateMeter {
+    window: std::time::Duration,
+    in_window: VecDeque<(std::time::SystemTime, u32)>,
+    window_bytes: u64,
+    bytes: u64,
+    packets: u64,
+    first: Option<std::time::SystemTime>,
+    last: Option<std::time::SystemTime>,
+    peak: Rate,
+    inter_arrival: InterArrival
+}
+
+impl Default for RateMeter {
+    fn default() -> RateMeter {
+        RateMeter::with_window(DEFAULT_WINDOW)
+    }
+}
+
+impl RateMeter {
+    pub fn with_window(window: std::time::Duration) -> RateMeter {
+        RateMeter {
+            window: window,
+            in_window: VecDeque::new(),
+            window_bytes: 0,
+            bytes: 0,
+            packets: 0,
+            first: None,
+            last: None,
+            peak: Rate::default(),
+            inter_arrival: InterArrival::default()
+        }
+    }
+
+    pub fn window(&self) -> std::time::Duration { self.window }
+    pub fn bytes(&self) -> u64 { self.bytes }
+    pub fn packets(&self) -> u64 { self.packets }
+    pub fn first_seen(&self) -> Option<std::time::SystemTime> { self.first }
+    pub fn last_seen(&self) -> Option<std::time::SystemTime> { self.last }
+    pub fn inter_arrival(&self) -> &InterArrival { &self.inter_arrival }
+
+    ///
+    /// Time from the first to the latest packet
+    ///
+    pub fn duration(&self) -> std::time::Duration {
+        match (self.first, self.last) {
+            (Some(first), Some(last)) => last.duration_since(first).unwrap_or_default(),
+            _ => std::time::Duration::default()
+        }
+    }
+
+    ///
+    /// Rate over the window ending at the latest packet
+    ///
+    pub fn current(&self) -> Rate {
+        Rate::over(self.window_bytes, self.in_window.len() as u64, self.window)
+    }
+
+    ///
+    /// Highest window rates seen. Bits and packets per second peak independently, so may have
+    /// peaked at different times.
+    ///
+    pub fn peak(&self) -> Rate { self.peak }
+
+    ///
+    /// Rate from the first to the latest packet, or None until packets span some time
+    ///
+    pub fn average(&self) -> Option<Rate> {
+        let duration = self.duration();
+        if duration == std::time::Duration::default() {
+            None
+        } else {
+            Some(Rate::over(self.bytes, self.packets, duration))
+        }
+    }
+
+    ///
+    /// Count a packet of `length` bytes, on the wire, arriving at `timestamp`
+    ///
+    pub fn add(&mut self, timestamp: std::time::SystemTime, length: u32) {
+        let timestamp = match self.last {
+            Some(last) => {
+                let timestamp = std::cmp::max(last, timestamp);
+                self.inter_arrival.add(timestamp.duration_since(last).unwrap_or_default());
+                timestamp
+            }
+            None => {
+                self.first = Some(timestamp);
+                timestamp
+            }
+        };
+        self.last = Some(timestamp);
+        self.bytes += length as u64;
+        self.packets += 1;
+
+        self.in_window.push_back((timestamp, length));
+        self.window_bytes += length as u64;
+        while let Some(&(oldest, oldest_length)) = self.in_window.front() {
+            if timestamp.duration_since(oldest).unwrap_or_default() < self.window {
+                break;
+            }
+            self.in_window.pop_front();
+            self.window_bytes -= oldest_length as u64;
+        }
+
+        let current = self.current();
+        if current.bits_per_second > self.peak.bits_per_second {
+            self.peak.bits_per_second = current.bits_per_second;
+        }
+        if current.packets_per_second > self.peak.packets_per_second {
+            self.peak.packets_per_second = current.packets_per_second;
+        }
+    }
+}
+
+///
+/// Throughput of a whole capture and, with flows, of each tcp and udp flow (both directions
+/// together), for capacity analysis
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Throughput {
+    window: std::time::Duration,
+    capture: RateMeter,
+    #[cfg(feature = "flows")]
+    flows: HashMap<FlowKey, RateMeter>
+}
+
+impl Default for Throughput {
+    fn default() -> Throughput {
+        Throughput::with_window(DEFAULT_WINDOW)
+    }
+}
+
+impl Throughput {
+    pub fn with_window(window: std::time::Duration) -> Throughput {
+        Throughput {
+            window: window,
+            capture: RateMeter::with_window(window),
+            #[cfg(feature = "flows")]
+            flows: HashMap::new()
+        }
+    }
+
+    pub fn capture(&self) -> &RateMeter { &self.capture }
+
+    ///
+    /// All flows, keyed from the side seen sending first, in no particular order
+    ///
+    #[cfg(feature = "flows")]
+    pub fn flows(&self) -> std::vec::Vec<(&FlowKey, &RateMeter)> {
+        self.flows.iter().collect()
+    }
+
+    ///
+    /// Throughput of the flow `key` belongs to, in either direction
+    ///
+    #[cfg(feature = "flows")]
+    pub fn flow(&self, key: &FlowKey) -> Option<&RateMeter> {
+        self.flows.get(key).or_else(|| self.flows.get(&key.reversed()))
+    }
+
+    ///
+    /// Flows with the highest peak bits per second, highest first
+    ///
+    #[cfg(feature = "flows")]
+    pub fn busiest_flows(&self, count: usize) -> std::vec::Vec<(&FlowKey, &RateMeter)> {
+        let mut flows = self.flows();
+        flows.sort_by(|a, b| {
+            b.1.peak().bits_per_second.partial_cmp(&a.1.peak().bits_per_second).unwrap_or(std::cmp::Ordering::Equal)
+        });
+        flows.truncate(count);
+        flows
+    }
+
+    pub fn add_record(&mut self, record: &PcapRecord) {
+        self.capture.add(*record.timestamp(), record.original_length());
+
+        #[cfg(feature = "flows")]
+        {
+            let key = match ParsedRecord::try_from(record.clone()).and_then(|p| p.flow_key()) {
+                Ok(key) => key,
+                Err(e) => {
+                    debug!("Record is not part of a flow: {:?}", e);
+                    return
+                }
+            };
+            let reverse = key.reversed();
+            let flow_key = if self.flows.contains_key(&reverse) { reverse } else { key };
+            let window = self.window;
+            self.flows.entry(flow_key)
+                .or_insert_with(|| RateMeter::with_window(window))
+                .add(*record.timestamp(), record.original_length());
+        }
+    }
+
+    pub fn from_records<'a, I>(records: I) -> Throughput where I: IntoIterator<Item=&'a PcapRecord> {
+        let mut throughput = Throughput::default();
+        for record in records {
+            throughput.add_record(record);
+        }
+        throughput
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    fn at(millis: u64) -> std::time::SystemTime {
+        std::time::UNIX_EPOCH + std::time::Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn rate_meter() {
+        let _ = env_logger::try_init();
+
+        let mut meter = RateMeter::default();
+        assert_eq!(meter.average(), None);
+
+        //a burst of 4 packets in 300ms, then one a second later
+        for millis in &[0, 100, 200, 300] {
+            meter.add(at(*millis), 1000);
+        }
+        assert_eq!(meter.current(), Rate { bits_per_second: 32000.0, packets_per_second: 4.0 });
+        meter.add(at(1300), 500);
+        meter.add(at(1200), 500); //out of order, counted at 1300
+
+        assert_eq!(meter.packets(), 6);
+        assert_eq!(meter.bytes(), 5000);
+        assert_eq!(meter.duration(), std::time::Duration::from_millis(1300));
+        assert_eq!(meter.current(), Rate { bits_per_second: 8000.0, packets_per_second: 2.0 });
+        assert_eq!(meter.peak(), Rate { bits_per_second: 32000.0, packets_per_second: 4.0 });
+        let average = meter.average().expect("No average");
+        assert!((average.packets_per_second - 6.0 / 1.3).abs() < 1e-9);
+
+        let inter_arrival = meter.inter_arrival();
+        assert_eq!(inter_arrival.count(), 5);
+        assert_eq!(inter_arrival.min(), Some(std::time::Duration::from_millis(0)));
+        assert_eq!(inter_arrival.max(), Some(std::time::Duration::from_millis(1000)));
+        assert_eq!(inter_arrival.mean(), Some(std::time::Duration::from_millis(260)));
+        assert_eq!(inter_arrival.buckets()[0], 1);
+        assert_eq!(inter_arrival.buckets()[17], 3); //100ms, between 2^16 and 2^17 microseconds
+        assert_eq!(inter_arrival.percentile(50.0), Some(std::time::Duration::from_micros(1 << 17)));
+        assert_eq!(inter_arrival.percentile(100.0), Some(std::time::Duration::from_millis(1000)));
+        assert_eq!(InterArrival::default().percentile(50.0), None);
+    }
+
+    #[cfg(feature = "flows")]
+    #[test]
+    fn flow_throughput() {
+        use super::super::reassembly::tests::tcp_record;
+
+        let _ = env_logger::try_init();
+
+        let records = vec![
+            tcp_record(1, true, 1, 0x02, &[]), //syn
+            tcp_record(1, false, 1, 0x12, &[]), //syn ack
+            tcp_record(2, true, 2, 0x18, &[0u8; 946]) //1000 bytes
+        ];
+        let throughput = Throughput::from_records(&records);
+
+        assert_eq!(throughput.capture().packets(), 3);
+        assert_eq!(throughput.capture().bytes(), 54 + 54 + 1000);
+
+        let flows = throughput.flows();
+        assert_eq!(flows.len(), 1);
+        let (key, meter) = flows[0];
+        assert_eq!(key.dst_port, 80);
+        assert_eq!(throughput.flow(&key.reversed()), Some(meter));
+        assert_eq!(meter.packets(), 3);
+        assert_eq!(meter.peak().bits_per_second, 8000.0); //the handshake a second earlier is out of the window
+        assert_eq!(throughput.busiest_flows(5).len(), 1);
+    }
+}
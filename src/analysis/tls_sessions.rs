@@ -0,0 +1,265 @@
+use super::prelude::*;
+use super::super::flow::FlowKey;
+use super::super::parsed::ParsedRecord;
+
+use self::layer4::Layer4;
+use self::layer7::tls::{ClientHello, Handshake, ServerHello, TlsRecord, TlsVersion};
+
+use std;
+use std::collections::HashMap;
+
+///
+/// TLS metadata for a single connection, keyed from client to server
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TlsSession {
+    pub key: FlowKey,
+    pub server_name: Option<String>,
+    ///
+    /// Highest version offered by the client
+    ///
+    pub offered_version: TlsVersion,
+    pub offered_alpn: std::vec::Vec<String>,
+    ///
+    /// Negotiated version, cipher suite and application protocol, once the server hello is seen
+    ///
+    pub version: Option<TlsVersion>,
+    pub cipher_suite: Option<u16>,
+    pub alpn: Option<String>,
+    ///
+    /// Whether the server resumed a previous session rather than performing a full handshake
+    ///
+    pub resumed: bool,
+    pub client_hello: std::time::SystemTime,
+    pub server_hello: Option<std::time::SystemTime>,
+    session_id: std::vec::Vec<u8>
+}
+
+impl TlsSession {
+    fn new(key: FlowKey, hello: &ClientHello, timestamp: std::time::SystemTime) -> TlsSession {
+        let offered_version = hello.supported_versions().into_iter().max().unwrap_or(hello.version());
+        TlsSession {
+            key: key,
+            server_name: hello.server_name(),
+            offered_version: offered_version,
+            offered_alpn: hello.alpn_protocols(),
+            version: None,
+            cipher_suite: None,
+            alpn: None,
+            resumed: false,
+            client_hello: timestamp,
+            server_hello: None,
+            session_id: hello.session_id().clone()
+        }
+    }
+
+    fn negotiate(&mut self, hello: &ServerHello, timestamp: std::time::SystemTime) {
+        let version = hello.selected_version();
+        self.resumed = if version == TlsVersion::Tls13 {
+            hello.accepted_pre_shared_key()
+        } else {
+            //the server echoes the client's session id only when resuming that session
+            !self.session_id.is_empty() && *hello.session_id() == self.session_id
+        };
+        self.version = Some(version);
+        self.cipher_suite = Some(hello.cipher_suite());
+        self.alpn = hello.alpn_protocol();
+        self.server_hello = Some(timestamp);
+    }
+}
+
+///
+/// Capture wide distribution of negotiated TLS parameters
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TlsStatistics {
+    pub sessions: usize,
+    ///
+    /// Sessions where the server hello was seen
+    ///
+    pub negotiated: usize,
+    pub resumed: usize,
+    pub versions: HashMap<TlsVersion, usize>,
+    pub cipher_suites: HashMap<u16, usize>
+}
+
+///
+/// TLS sessions observed in a capture, from client and server hellos
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TlsSessions {
+    sessions: HashMap<FlowKey, TlsSession>
+}
+
+impl TlsSessions {
+    ///
+    /// All sessions, ordered by time of the client hello
+    ///
+    pub fn sessions(&self) -> std::vec::Vec<&TlsSession> {
+        let mut sessions = self.sessions.values().collect::<std::vec::Vec<_>>();
+        sessions.sort_by(|a, b| a.client_hello.cmp(&b.client_hello));
+        sessions
+    }
+
+    pub fn session(&self, key: &FlowKey) -> Option<&TlsSession> {
+        self.sessions.get(key)
+    }
+
+    pub fn statistics(&self) -> TlsStatistics {
+        let mut statistics = TlsStatistics::default();
+        for session in self.sessions.values() {
+            statistics.sessions += 1;
+            if let (Some(version), Some(cipher_suite)) = (session.version, session.cipher_suite) {
+                statistics.negotiated += 1;
+                *statistics.versions.entry(version).or_insert(0) += 1;
+                *statistics.cipher_suites.entry(cipher_suite).or_insert(0) += 1;
+            }
+            if session.resumed {
+                statistics.resumed += 1;
+            }
+        }
+        statistics
+    }
+
+    fn observe(&mut self, key: &FlowKey, handshake: &Handshake, timestamp: std::time::SystemTime) {
+        match *handshake {
+            Handshake::ClientHello(ref hello) => {
+                self.sessions.insert(key.clone(), TlsSession::new(key.clone(), hello, timestamp));
+            }
+            Handshake::ServerHello(ref hello) => {
+                match self.sessions.get_mut(&key.reversed()) {
+                    Some(session) => session.negotiate(hello, timestamp),
+                    None => debug!("Server hello without client hello for {:?}", key)
+                }
+            }
+            Handshake::Other(_) => {}
+        }
+    }
+
+    pub fn add_record(&mut self, record: &PcapRecord) {
+        let parsed = match ParsedRecord::try_from(record.clone()) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                debug!("Could not parse record: {:?}", e);
+                return
+            }
+        };
+        if let Some(&Layer4::Tcp(ref tcp)) = parsed.layer4() {
+            let key = match parsed.flow_key() {
+                Ok(key) => key,
+                Err(_) => return
+            };
+            let mut current = &tcp.payload()[..];
+            while let Ok( (rem, tls) ) = TlsRecord::parse(current) {
+                for handshake in tls.handshakes() {
+                    self.observe(&key, &handshake, *record.timestamp());
+                }
+                current = rem;
+            }
+        }
+    }
+
+    pub fn from_records<'a, I>(records: I) -> TlsSessions where I: IntoIterator<Item=&'a PcapRecord> {
+        let mut sessions = TlsSessions::default();
+        for record in records {
+            sessions.add_record(record);
+        }
+        sessions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+    use layer3::InternetProtocolId;
+    use layer7::tls::tests::{CLIENT_HELLO_DATA, SERVER_HELLO_DATA};
+
+    fn tcp_record(seconds: u32, client_port: u16, to_server: bool, tls: &[u8]) -> PcapRecord {
+        let ip_length = 20 + 20 + tls.len();
+        let (src_ip, dst_ip, src_port, dst_port) = if to_server {
+            ([10u8, 0, 0, 1], [10u8, 0, 0, 2], client_port, 443)
+        } else {
+            ([10u8, 0, 0, 2], [10u8, 0, 0, 1], 443, client_port)
+        };
+        let mut payload = vec![
+            0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x01u8, //dst mac
+            0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x02u8, //src mac
+            0x08u8, 0x00u8, //ipv4
+            //ipv4
+            0x45u8, //version, header length
+            0x00u8, //tos
+            (ip_length >> 8) as u8, ip_length as u8, //total length
+            0x00u8, 0x00u8, //id
+            0x00u8, 0x00u8, //flags, fragment offset
+            0x40u8, //ttl
+            0x06u8, //tcp
+            0x00u8, 0x00u8, //checksum
+            src_ip[0], src_ip[1], src_ip[2], src_ip[3], //src ip
+            dst_ip[0], dst_ip[1], dst_ip[2], dst_ip[3], //dst ip
+            //tcp
+            (src_port >> 8) as u8, src_port as u8, //src port
+            (dst_port >> 8) as u8, dst_port as u8, //dst port
+            0x00u8, 0x00u8, 0x00u8, 0x01u8, //sequence number
+            0x00u8, 0x00u8, 0x00u8, 0x01u8, //acknowledgement number
+            0x50u8, 0x18u8, //header length, psh ack
+            0xFFu8, 0xFFu8, //window
+            0x00u8, 0x00u8, //checksum
+            0x00u8, 0x00u8 //urgent
+        ];
+        payload.extend_from_slice(tls);
+        PcapRecord::new(PcapRecord::convert_packet_time(seconds, 0), payload.len() as u32, payload.len() as u32, payload)
+    }
+
+    #[test]
+    fn tls_session() {
+        let _ = env_logger::try_init();
+
+        let records = vec![
+            tcp_record(1, 50000, true, CLIENT_HELLO_DATA),
+            tcp_record(2, 50000, false, SERVER_HELLO_DATA)
+        ];
+        let sessions = TlsSessions::from_records(&records);
+
+        let key = FlowKey {
+            src_ip: "10.0.0.1".parse().unwrap(),
+            src_port: 50000,
+            dst_ip: "10.0.0.2".parse().unwrap(),
+            dst_port: 443,
+            protocol: Some(InternetProtocolId::Tcp),
+            vlan: Vlan::untagged(),
+            inner_vlans: vec![]
+        };
+        let session = sessions.session(&key).expect("No session");
+
+        assert_eq!(session.server_name, Some("example.com".to_string()));
+        assert_eq!(session.offered_version, TlsVersion::Tls13);
+        assert_eq!(session.offered_alpn, vec!["h2".to_string()]);
+        assert_eq!(session.version, Some(TlsVersion::Tls13));
+        assert_eq!(session.cipher_suite, Some(0x1301));
+        assert!(session.resumed);
+        assert_eq!(session.server_hello, Some(PcapRecord::convert_packet_time(2, 0)));
+    }
+
+    #[test]
+    fn tls_statistics() {
+        let _ = env_logger::try_init();
+
+        let records = vec![
+            tcp_record(1, 50000, true, CLIENT_HELLO_DATA),
+            tcp_record(2, 50000, false, SERVER_HELLO_DATA),
+            tcp_record(3, 50001, true, CLIENT_HELLO_DATA)
+        ];
+        let sessions = TlsSessions::from_records(&records);
+        let statistics = sessions.statistics();
+
+        assert_eq!(sessions.sessions().len(), 2);
+        assert_eq!(sessions.sessions()[1].version, None);
+        assert_eq!(statistics.sessions, 2);
+        assert_eq!(statistics.negotiated, 1);
+        assert_eq!(statistics.resumed, 1);
+        assert_eq!(statistics.versions.get(&TlsVersion::Tls13), Some(&1));
+        assert_eq!(statistics.cipher_suites.get(&0x1301), Some(&1));
+    }
+}
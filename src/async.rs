@@ -0,0 +1,340 @@
+use super::prelude::*;
+#[cfg(feature = "stream")]
+use super::futures_core::Stream;
+use super::global_header::GlobalHeader;
+use super::tokio::io::{AsyncRead, ReadBuf};
+
+use self::nom::*;
+
+use std;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+const READ_CHUNK: usize = 64 * 1024;
+
+enum Step {
+    Header(usize, GlobalHeader),
+    Record(usize, PcapRecord),
+    NeedMore,
+    Failed(errors::Error)
+}
+
+///
+/// Reads a libpcap capture from an `AsyncRead` source (file, socket), buffering internally and
+/// yielding one record at a time once enough bytes have arrived. With the `stream` feature, it is a
+/// `Stream` of records, ending after the first error.
+///
+pub struct AsyncCaptureReader<R> {
+    reader: R,
+    buffer: std::vec::Vec<u8>,
+    position: usize,
+    header: Option<GlobalHeader>,
+    finished: bool
+}
+
+impl<R> AsyncCaptureReader<R> where R: AsyncRead + Unpin {
+    pub fn new(reader: R) -> AsyncCaptureReader<R> {
+        AsyncCaptureReader {
+            reader: reader,
+            buffer: vec![],
+            position: 0,
+            header: None,
+            finished: false
+        }
+    }
+
+    ///
+    /// Global header of the capture, available once the first record has been polled for
+    ///
+    pub fn header(&self) -> Option<&GlobalHeader> { self.header.as_ref() }
+
+    fn step(&self) -> Step {
+        let available = &self.buffer[self.position..];
+        match self.header {
+            None => match GlobalHeader::parse(available) {
+                Ok( (rem, header) ) => Step::Header(available.len() - rem.len(), header),
+                Err(nom::Err::Incomplete(_)) => Step::NeedMore,
+                Err(e) => Step::Failed(errors::Error::from_nom(e, errors::Layer::GlobalHeader, available))
+            },
+            Some(ref header) => match PcapRecord::parse_for(available, header) {
+                Ok( (rem, record) ) => Step::Record(available.len() - rem.len(), record),
+                Err(nom::Err::Incomplete(_)) => Step::NeedMore,
+                Err(e) => Step::Failed(errors::Error::from_nom(e, errors::Layer::Record, available))
+            }
+        }
+    }
+
+    fn poll_fill(&mut self, cx: &mut Context) -> Poll<Result<usize, errors::Error>> {
+        if self.position > 0 {
+            self.buffer.drain(..self.position);
+            self.position = 0;
+        }
+
+        let start = self.buffer.len();
+        self.buffer.resize(start + READ_CHUNK, 0);
+
+        let result = {
+            let mut read_buf = ReadBuf::new(&mut self.buffer[start..]);
+            match Pin::new(&mut self.reader).poll_read(cx, &mut read_buf) {
+                Poll::Ready(Ok(())) => Poll::Ready(Ok(read_buf.filled().len())),
+                Poll::Ready(Err(e)) => Poll::Ready(Err(e.into())),
+                Poll::Pending => Poll::Pending
+            }
+        };
+
+        let filled = match result {
+            Poll::Ready(Ok(n)) => n,
+            _ => 0
+        };
+        self.buffer.truncate(start + filled);
+
+        result
+    }
+
+    ///
+    /// Poll for the next record. Returns `None` once the source is exhausted; a source that ends
+    /// part way through a record yields an `IncompleteParse` error first.
+    ///
+    pub fn poll_next_record(&mut self, cx: &mut Context) -> Poll<Option<Result<PcapRecord, errors::Error>>> {
+        loop {
+            if self.finished {
+                return Poll::Ready(None)
+            }
+
+            match self.step() {
+                Step::Header(consumed, header) => {
+                    debug!("Global header version {}.{}, with endianness {:?}", header.version_major(), header.version_minor(), header.endianness());
+                    self.position += consumed;
+                    self.header = Some(header);
+                }
+                Step::Record(consumed, record) => {
+                    self.position += consumed;
+                    return Poll::Ready(Some(Ok(record)))
+                }
+                Step::Failed(e) => {
+                    self.finished = true;
+                    return Poll::Ready(Some(Err(e)))
+                }
+                Step::NeedMore => {
+                    match self.poll_fill(cx) {
+                        Poll::Pending => return Poll::Pending,
+                        Poll::Ready(Err(e)) => {
+                            self.finished = true;
+                            return Poll::Ready(Some(Err(e)))
+                        }
+                        Poll::Ready(Ok(0)) => {
+                            self.finished = true;
+                            let remaining = self.buffer.len() - self.position;
+                            if remaining == 0 {
+                                return Poll::Ready(None)
+                            } else {
+                                debug!("Source ended with {} bytes of a partial record", remaining);
+                                return Poll::Ready(Some(Err(errors::Error::from_kind(errors::ErrorKind::IncompleteParse(remaining)))))
+                            }
+                        }
+                        Poll::Ready(Ok(n)) => {
+                            trace!("Read {} bytes from source", n);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    ///
+    /// Poll for the next record, converted to a flow
+    ///
+    #[cfg(feature = "flows")]
+    pub fn poll_next_flow(&mut self, cx: &mut Context) -> Poll<Option<Result<Flow, errors::Error>>> {
+        self.poll_next_record(cx).map(|opt| opt.map(|res| res.and_then(Flow::try_from)))
+    }
+
+    ///
+    /// Future resolving to the next record
+    ///
+    pub fn next_record<'a>(&'a mut self) -> NextRecord<'a, R> {
+        NextRecord { reader: self }
+    }
+
+    ///
+    /// Future resolving to the next record, converted to a flow
+    ///
+    #[cfg(feature = "flows")]
+    pub fn next_flow<'a>(&'a mut self) -> NextFlow<'a, R> {
+        NextFlow { reader: self }
+    }
+}
+
+#[cfg(feature = "stream")]
+impl<R> Stream for AsyncCaptureReader<R> where R: AsyncRead + Unpin {
+    type Item = Result<PcapRecord, errors::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_next_record(cx)
+    }
+}
+
+pub struct NextRecord<'a, R: 'a> {
+    reader: &'a mut AsyncCaptureReader<R>
+}
+
+impl<'a, R> Future for NextRecord<'a, R> where R: AsyncRead + Unpin {
+    type Output = Option<Result<PcapRecord, errors::Error>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        self.get_mut().reader.poll_next_record(cx)
+    }
+}
+
+#[cfg(feature = "flows")]
+pub struct NextFlow<'a, R: 'a> {
+    reader: &'a mut AsyncCaptureReader<R>
+}
+
+#[cfg(feature = "flows")]
+impl<'a, R> Future for NextFlow<'a, R> where R: AsyncRead + Unpin {
+    type Output = Option<Result<Flow, errors::Error>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        self.get_mut().reader.poll_next_flow(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    const RAW_DATA: &'static [u8] = &[
+        0x4du8, 0x3c, 0x2b, 0x1au8, //magic number
+        0x00u8, 0x04u8, //version major, 4
+        0x00u8, 0x02u8, //version minor, 2
+        0x00u8, 0x00u8, 0x00u8, 0x00u8, //zone, 0
+        0x00u8, 0x00u8, 0x00u8, 0x04u8, //sig figs, 4
+        0x00u8, 0x00u8, 0x06u8, 0x13u8, //snap length, 1555
+        0x00u8, 0x00u8, 0x00u8, 0x01u8, //network, 1
+        //record
+        0x5Bu8, 0x11u8, 0x6Du8, 0xE3u8, //seconds, 1527868899
+        0x00u8, 0x02u8, 0x51u8, 0xF5u8, //microseconds, 152053
+        0x00u8, 0x00u8, 0x00u8, 0x3Au8, //actual length, 58: 14 (ethernet) + 20 (ipv4 header) + 20 (tcp header) + 4 (tcp payload)
+        0x00u8, 0x00u8, 0x00u8, 0x3Au8, //original length, 58
+        //ethernet
+        0x01u8, 0x02u8, 0x03u8, 0x04u8, 0x05u8, 0x06u8, //dst mac 01:02:03:04:05:06
+        0xFFu8, 0xFEu8, 0xFDu8, 0xFCu8, 0xFBu8, 0xFAu8, //src mac FF:FE:FD:FC:FB:FA
+        0x08u8, 0x00u8, //ipv4
+        //ipv4
+        0x45u8, //version and header length
+        0x00u8, //tos
+        0x00u8, 0x2Cu8, //length, 20 bytes for header, 24 bytes for tcp
+        0x00u8, 0x00u8, //id
+        0x00u8, 0x00u8, //flags
+        0x64u8, //ttl
+        0x06u8, //protocol, tcp
+        0x00u8, 0x00u8, //checksum
+        0x01u8, 0x02u8, 0x03u8, 0x04u8, //src ip 1.2.3.4
+        0x0Au8, 0x0Bu8, 0x0Cu8, 0x0Du8, //dst ip 10.11.12.13
+        //tcp
+        0xC6u8, 0xB7u8, //src port, 50871
+        0x00u8, 0x50u8, //dst port, 80
+        0x00u8, 0x00u8, 0x00u8, 0x01u8, //sequence number, 1
+        0x00u8, 0x00u8, 0x00u8, 0x02u8, //acknowledgement number, 2
+        0x50u8, 0x00u8, //header and flags, 0
+        0x00u8, 0x00u8, //window
+        0x00u8, 0x00u8, //check
+        0x00u8, 0x00u8, //urgent
+        //no options
+        //payload
+        0xfcu8, 0xfdu8, 0xfeu8, 0xffu8
+    ];
+
+    ///
+    /// Source returning a few bytes per read, to exercise buffering across partial reads
+    ///
+    struct Trickle {
+        data: std::vec::Vec<u8>,
+        position: usize
+    }
+
+    impl AsyncRead for Trickle {
+        fn poll_read(mut self: Pin<&mut Self>, _cx: &mut Context, buf: &mut ReadBuf) -> Poll<std::io::Result<()>> {
+            let end = std::cmp::min(self.position + 7, self.data.len());
+            let start = self.position;
+            buf.put_slice(&self.data[start..end]);
+            self.position = end;
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn poll_ready<F: Future + Unpin>(mut future: F) -> F::Output {
+        let waker = std::task::Waker::noop();
+        let mut cx = Context::from_waker(&waker);
+        match Pin::new(&mut future).poll(&mut cx) {
+            Poll::Ready(v) => v,
+            Poll::Pending => panic!("Source should always be ready")
+        }
+    }
+
+    #[test]
+    fn read_records() {
+        let _ = env_logger::try_init();
+
+        let mut data = RAW_DATA.to_vec();
+        data.extend_from_slice(&RAW_DATA[24..]);
+        let mut reader = AsyncCaptureReader::new(Trickle { data: data, position: 0 });
+
+        let first = poll_ready(reader.next_record()).expect("No record").expect("Failed to parse");
+        assert_eq!(first.actual_length(), 58);
+        assert_eq!(reader.header().map(|h| h.endianness()), Some(Endianness::Big));
+
+        #[cfg(feature = "flows")]
+        {
+            let flow = poll_ready(reader.next_flow()).expect("No record").expect("Failed to convert");
+            assert_eq!(flow.source.port, 50871);
+        }
+        #[cfg(not(feature = "flows"))]
+        {
+            let second = poll_ready(reader.next_record()).expect("No record").expect("Failed to parse");
+            assert_eq!(second.actual_length(), 58);
+        }
+
+        assert!(poll_ready(reader.next_record()).is_none());
+    }
+
+    #[cfg(feature = "stream")]
+    #[test]
+    fn stream_records() {
+        let _ = env_logger::try_init();
+
+        let mut data = RAW_DATA.to_vec();
+        data.extend_from_slice(&RAW_DATA[24..]);
+        let mut reader = AsyncCaptureReader::new(Trickle { data: data, position: 0 });
+
+        let waker = std::task::Waker::noop();
+        let mut cx = Context::from_waker(&waker);
+        let mut records = vec![];
+        loop {
+            match Pin::new(&mut reader).poll_next(&mut cx) {
+                Poll::Ready(Some(record)) => records.push(record.expect("Failed to parse")),
+                Poll::Ready(None) => break,
+                Poll::Pending => panic!("Source should always be ready")
+            }
+        }
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].actual_length(), 58);
+    }
+
+    #[test]
+    fn read_truncated_record() {
+        let _ = env_logger::try_init();
+
+        let data = RAW_DATA[..RAW_DATA.len() - 2].to_vec();
+        let mut reader = AsyncCaptureReader::new(Trickle { data: data, position: 0 });
+
+        let err = poll_ready(reader.next_record()).expect("No result").err().expect("Parsed truncated record");
+        assert_eq!(format!("{}", err), "Incomplete parse of payload, 72 bytes remain");
+
+        assert!(poll_ready(reader.next_record()).is_none());
+    }
+}
@@ -0,0 +1,417 @@
+use super::prelude::*;
+use super::super::global_header::{GlobalHeader, TimestampPrecision, NATIVE_ENDIAN};
+use super::super::libc;
+use super::super::link_type::LinkType;
+
+use self::nom::*;
+
+use std;
+use std::collections::VecDeque;
+
+pub const DEFAULT_SNAP_LENGTH: u32 = 262144;
+pub const DEFAULT_BLOCK_SIZE: u32 = 1 << 20;
+pub const DEFAULT_BLOCK_COUNT: u32 = 32;
+pub const DEFAULT_BLOCK_TIMEOUT: u32 = 100;
+
+///
+/// Frame size given to the kernel when requesting the ring. TPACKET_V3 packs packets into blocks
+/// regardless, so this only sets how many frames the ring is accounted as holding.
+///
+const FRAME_SIZE: u32 = 2048;
+
+///
+/// Offset of the block header within a TPACKET_V3 block descriptor, after its version and private
+/// area offset
+///
+const BLOCK_HEADER_OFFSET: usize = 8;
+
+///
+/// Options for a live capture
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CaptureOptions {
+    ///
+    /// Longest payload kept of each packet, with 0 keeping whole packets
+    ///
+    pub snap_length: u32,
+    pub promiscuous: bool,
+    ///
+    /// Size in bytes of each block of the ring, a multiple of the page size
+    ///
+    pub block_size: u32,
+    pub block_count: u32,
+    ///
+    /// How long the kernel waits to fill a block before handing over what it has, in milliseconds
+    ///
+    pub block_timeout: u32,
+    ///
+    /// How long to wait for a packet before giving up, or forever if not set
+    ///
+    pub read_timeout: Option<std::time::Duration>
+}
+
+impl Default for CaptureOptions {
+    fn default() -> CaptureOptions {
+        CaptureOptions {
+            snap_length: DEFAULT_SNAP_LENGTH,
+            promiscuous: false,
+            block_size: DEFAULT_BLOCK_SIZE,
+            block_count: DEFAULT_BLOCK_COUNT,
+            block_timeout: DEFAULT_BLOCK_TIMEOUT,
+            read_timeout: None
+        }
+    }
+}
+
+///
+/// Packet counts the kernel keeps for a capture socket, reset each time they are read
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CaptureStatistics {
+    pub packets: u32,
+    ///
+    /// Packets dropped because the ring was full
+    ///
+    pub drops: u32
+}
+
+///
+/// Libpcap link type of an interface with hardware type `hatype` (ARPHRD_). Packets are read from
+/// the link layer header on, so hardware types are only mapped where that header matches the link
+/// type; others are kept as `Unknown`.
+///
+fn link_type(hatype: u16) -> LinkType {
+    match hatype {
+        libc::ARPHRD_ETHER | libc::ARPHRD_LOOPBACK => LinkType::Ethernet,
+        libc::ARPHRD_NONE => LinkType::Raw,
+        libc::ARPHRD_IEEE80211_RADIOTAP => LinkType::Ieee80211Radiotap,
+        other => LinkType::Unknown(other as u32)
+    }
+}
+
+fn setsockopt<T>(fd: libc::c_int, level: libc::c_int, name: libc::c_int, value: &T) -> errors::Result<()> {
+    let result = unsafe {
+        libc::setsockopt(fd, level, name, value as *const T as *const libc::c_void, std::mem::size_of::<T>() as libc::socklen_t)
+    };
+    if result < 0 {
+        return Err(std::io::Error::last_os_error().into())
+    }
+    Ok(())
+}
+
+///
+/// Parse the packets of a TPACKET_V3 block handed over by the kernel, keeping at most
+/// `snap_length` bytes of each (or whole packets with 0). Fields are in native byte order.
+///
+fn parse_block(block: &[u8], snap_length: u32) -> errors::Result<std::vec::Vec<PcapRecord>> {
+    let (_, (packets, first)) = do_parse!(block,
+        _descriptor: take!(BLOCK_HEADER_OFFSET) >>
+        _status: u32!(NATIVE_ENDIAN) >>
+        packets: u32!(NATIVE_ENDIAN) >>
+        first: u32!(NATIVE_ENDIAN) >>
+        ( (packets, first) )
+    ).map_err(|e| errors::Error::from_nom(e, errors::Layer::Record, block))?;
+
+    let mut records = std::vec::Vec::with_capacity(packets as usize);
+    let mut offset = first as usize;
+    for _ in 0..packets {
+        layer_span!("record", index = records.len(), offset = offset);
+
+        let packet = block.get(offset..).ok_or_else(|| -> errors::Error { format!("Packet offset {} is outside the block", offset).into() })?;
+        let (_, (next, seconds, nanoseconds, captured, length, mac)) = do_parse!(packet,
+            next: u32!(NATIVE_ENDIAN) >>
+            seconds: u32!(NATIVE_ENDIAN) >>
+            nanoseconds: u32!(NATIVE_ENDIAN) >>
+            captured: u32!(NATIVE_ENDIAN) >>
+            length: u32!(NATIVE_ENDIAN) >>
+            _status: u32!(NATIVE_ENDIAN) >>
+            mac: u16!(NATIVE_ENDIAN) >>
+            ( (next, seconds, nanoseconds, captured, length, mac) )
+        ).map_err(|e| errors::Error::from_nom(e, errors::Layer::Record, packet))?;
+
+        let data = packet.get(mac as usize..mac as usize + captured as usize)
+            .ok_or_else(|| -> errors::Error { format!("Packet at offset {} runs past the block", offset).into() })?;
+        let kept = if snap_length > 0 { std::cmp::min(captured, snap_length) } else { captured };
+        records.push(PcapRecord::new(
+            TimestampPrecision::Nanosecond.to_timestamp(seconds, nanoseconds),
+            kept,
+            length,
+            data[..kept as usize].to_vec()
+        ));
+        offset += next as usize;
+    }
+
+    Ok(records)
+}
+
+///
+/// Live capture on a Linux interface, reading packets from a TPACKET_V3 ring shared with the kernel
+/// rather than a syscall per packet. Requires CAP_NET_RAW. Each block is copied out as records and
+/// handed back to the kernel as soon as it is read, so records feed the usual parsing and flow
+/// conversion.
+///
+pub struct AfPacketCapture {
+    fd: libc::c_int,
+    ring: *mut libc::c_void,
+    block_size: usize,
+    block_count: usize,
+    block: usize,
+    header: GlobalHeader,
+    read_timeout: Option<std::time::Duration>,
+    pending: VecDeque<PcapRecord>
+}
+
+//the ring is only accessed through the capture, which owns it
+unsafe impl Send for AfPacketCapture {}
+
+impl AfPacketCapture {
+    ///
+    /// Open a ring on `interface` and start capturing on it
+    ///
+    pub fn open(interface: &str, options: &CaptureOptions) -> errors::Result<AfPacketCapture> {
+        let name = std::ffi::CString::new(interface)?;
+        let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        if index == 0 {
+            return Err(format!("No interface {}: {}", interface, std::io::Error::last_os_error()).into())
+        }
+
+        let protocol = (libc::ETH_P_ALL as u16).to_be();
+        let fd = unsafe { libc::socket(libc::AF_PACKET, libc::SOCK_RAW, protocol as libc::c_int) };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error().into())
+        }
+        //closed on drop from here on
+        let mut capture = AfPacketCapture {
+            fd: fd,
+            ring: std::ptr::null_mut(),
+            block_size: options.block_size as usize,
+            block_count: options.block_count as usize,
+            block: 0,
+            header: GlobalHeader::new(LinkType::Unknown(0), options.snap_length),
+            read_timeout: options.read_timeout,
+            pending: VecDeque::new()
+        };
+
+        setsockopt(fd, libc::SOL_PACKET, libc::PACKET_VERSION, &(libc::tpacket_versions::TPACKET_V3 as libc::c_int))?;
+        let request = libc::tpacket_req3 {
+            tp_block_size: options.block_size,
+            tp_block_nr: options.block_count,
+            tp_frame_size: FRAME_SIZE,
+            tp_frame_nr: (options.block_size / FRAME_SIZE) * options.block_count,
+            tp_retire_blk_tov: options.block_timeout,
+            tp_sizeof_priv: 0,
+            tp_feature_req_word: 0
+        };
+        setsockopt(fd, libc::SOL_PACKET, libc::PACKET_RX_RING, &request)?;
+
+        let ring = unsafe {
+            libc::mmap(std::ptr::null_mut(), capture.block_size * capture.block_count,
+                       libc::PROT_READ | libc::PROT_WRITE, libc::MAP_SHARED, fd, 0)
+        };
+        if ring == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error().into())
+        }
+        capture.ring = ring;
+
+        let mut address: libc::sockaddr_ll = unsafe { std::mem::zeroed() };
+        address.sll_family = libc::AF_PACKET as u16;
+        address.sll_protocol = protocol;
+        address.sll_ifindex = index as libc::c_int;
+        let length = std::mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t;
+        if unsafe { libc::bind(fd, &address as *const libc::sockaddr_ll as *const libc::sockaddr, length) } < 0 {
+            return Err(std::io::Error::last_os_error().into())
+        }
+
+        if options.promiscuous {
+            let membership = libc::packet_mreq {
+                mr_ifindex: index as libc::c_int,
+                mr_type: libc::PACKET_MR_PROMISC as u16,
+                mr_alen: 0,
+                mr_address: [0u8; 8]
+            };
+            setsockopt(fd, libc::SOL_PACKET, libc::PACKET_ADD_MEMBERSHIP, &membership)?;
+        }
+
+        //the bound address reports the interface's hardware type
+        let mut bound: libc::sockaddr_ll = unsafe { std::mem::zeroed() };
+        let mut length = length;
+        if unsafe { libc::getsockname(fd, &mut bound as *mut libc::sockaddr_ll as *mut libc::sockaddr, &mut length) } < 0 {
+            return Err(std::io::Error::last_os_error().into())
+        }
+        capture.header = GlobalHeader::new(link_type(bound.sll_hatype), options.snap_length)
+            .with_precision(TimestampPrecision::Nanosecond);
+        debug!("Capturing on {} with link type {}, {} blocks of {} bytes", interface, capture.header.link_type(), options.block_count, options.block_size);
+
+        Ok(capture)
+    }
+
+    ///
+    /// Header equivalent to the capture, e.g. to write the records out with a `CaptureWriter`.
+    /// Timestamps have nanosecond precision.
+    ///
+    pub fn header(&self) -> &GlobalHeader { &self.header }
+    pub fn link_type(&self) -> LinkType { self.header.link_type() }
+
+    fn block_status(&self) -> *mut u32 {
+        unsafe { (self.ring as *mut u8).add(self.block * self.block_size + BLOCK_HEADER_OFFSET) as *mut u32 }
+    }
+
+    ///
+    /// Wait for the kernel to hand over a block, returning whether one may be ready
+    ///
+    fn wait(&self) -> errors::Result<bool> {
+        let mut poll = libc::pollfd {
+            fd: self.fd,
+            events: libc::POLLIN | libc::POLLERR,
+            revents: 0
+        };
+        let timeout = self.read_timeout.map(|t| t.as_millis() as libc::c_int).unwrap_or(-1);
+        let result = unsafe { libc::poll(&mut poll, 1, timeout) };
+        if result < 0 {
+            let e = std::io::Error::last_os_error();
+            if e.kind() == std::io::ErrorKind::Interrupted {
+                return Ok(true)
+            }
+            return Err(e.into())
+        }
+        Ok(result > 0)
+    }
+
+    ///
+    /// Next captured packet, or `None` if none arrived within the read timeout
+    ///
+    pub fn next_record(&mut self) -> errors::Result<Option<PcapRecord>> {
+        loop {
+            if let Some(record) = self.pending.pop_front() {
+                return Ok(Some(record.with_header(&self.header)))
+            }
+
+            let status = self.block_status();
+            if unsafe { std::ptr::read_volatile(status) } & libc::TP_STATUS_USER == 0 {
+                if !self.wait()? {
+                    return Ok(None)
+                }
+                continue
+            }
+            std::sync::atomic::fence(std::sync::atomic::Ordering::Acquire);
+
+            let block = unsafe {
+                std::slice::from_raw_parts((self.ring as *const u8).add(self.block * self.block_size), self.block_size)
+            };
+            let records = parse_block(block, self.header.snap_length());
+
+            //hand the block back even if it couldn't be parsed, so the ring keeps moving
+            std::sync::atomic::fence(std::sync::atomic::Ordering::Release);
+            unsafe { std::ptr::write_volatile(status, libc::TP_STATUS_KERNEL) };
+            self.block = (self.block + 1) % self.block_count;
+
+            self.pending.extend(records?);
+        }
+    }
+
+    ///
+    /// Packets received and dropped since the capture opened or statistics were last read
+    ///
+    pub fn statistics(&self) -> errors::Result<CaptureStatistics> {
+        let mut stats: libc::tpacket_stats_v3 = unsafe { std::mem::zeroed() };
+        let mut length = std::mem::size_of::<libc::tpacket_stats_v3>() as libc::socklen_t;
+        let result = unsafe {
+            libc::getsockopt(self.fd, libc::SOL_PACKET, libc::PACKET_STATISTICS,
+                             &mut stats as *mut libc::tpacket_stats_v3 as *mut libc::c_void, &mut length)
+        };
+        if result < 0 {
+            return Err(std::io::Error::last_os_error().into())
+        }
+        Ok(CaptureStatistics {
+            packets: stats.tp_packets,
+            drops: stats.tp_drops
+        })
+    }
+}
+
+impl Drop for AfPacketCapture {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.ring.is_null() {
+                libc::munmap(self.ring, self.block_size * self.block_count);
+            }
+            libc::close(self.fd);
+        }
+    }
+}
+
+impl Iterator for AfPacketCapture {
+    type Item = errors::Result<PcapRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_record() {
+            Ok(record) => record.map(Ok),
+            Err(e) => Some(Err(e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    fn put_u32(bytes: &mut [u8], offset: usize, value: u32) {
+        bytes[offset..offset + 4].copy_from_slice(&value.to_ne_bytes());
+    }
+
+    fn put_packet(bytes: &mut [u8], offset: usize, next: u32, seconds: u32, payload: &[u8], length: u32) {
+        put_u32(bytes, offset, next);
+        put_u32(bytes, offset + 4, seconds);
+        put_u32(bytes, offset + 8, 500);
+        put_u32(bytes, offset + 12, payload.len() as u32);
+        put_u32(bytes, offset + 16, length);
+        bytes[offset + 24..offset + 26].copy_from_slice(&66u16.to_ne_bytes());
+        bytes[offset + 66..offset + 66 + payload.len()].copy_from_slice(payload);
+    }
+
+    #[test]
+    fn parse_ring_block() {
+        let _ = env_logger::try_init();
+
+        let mut block = vec![0u8; 512];
+        put_u32(&mut block, 0, 1); //version
+        put_u32(&mut block, 8, libc::TP_STATUS_USER);
+        put_u32(&mut block, 12, 2); //packets
+        put_u32(&mut block, 16, 48); //first packet
+        put_packet(&mut block, 48, 80, 1527868899, &[0x01u8, 0x02u8, 0x03u8, 0x04u8], 60);
+        put_packet(&mut block, 128, 0, 1527868900, &[0x05u8, 0x06u8], 2);
+
+        let records = parse_block(&block, 0).expect("Could not parse");
+        assert_eq!(records.len(), 2);
+        assert_eq!(*records[0].timestamp(), TimestampPrecision::Nanosecond.to_timestamp(1527868899, 500));
+        assert_eq!(records[0].actual_length(), 4);
+        assert_eq!(records[0].original_length(), 60);
+        assert_eq!(records[0].payload(), &vec![0x01u8, 0x02u8, 0x03u8, 0x04u8]);
+        assert_eq!(records[1].payload(), &vec![0x05u8, 0x06u8]);
+
+        let records = parse_block(&block, 3).expect("Could not parse");
+        assert_eq!(records[0].actual_length(), 3);
+        assert_eq!(records[0].original_length(), 60);
+
+        put_u32(&mut block, 16, 600);
+        assert!(parse_block(&block, 0).is_err());
+    }
+
+    #[test]
+    fn hardware_link_types() {
+        assert_eq!(link_type(libc::ARPHRD_ETHER), LinkType::Ethernet);
+        assert_eq!(link_type(libc::ARPHRD_LOOPBACK), LinkType::Ethernet);
+        assert_eq!(link_type(libc::ARPHRD_NONE), LinkType::Raw);
+        assert_eq!(link_type(0xFFFF), LinkType::Unknown(0xFFFF));
+    }
+
+    #[test]
+    fn open_missing_interface() {
+        let _ = env_logger::try_init();
+
+        let e = AfPacketCapture::open("no-such-if0", &CaptureOptions::default()).err().expect("Opened missing interface");
+        assert!(format!("{}", e).contains("no-such-if0"));
+    }
+}
@@ -0,0 +1,240 @@
+use super::prelude::*;
+use super::super::global_header::GlobalHeader;
+use super::super::libc;
+use super::super::link_type::LinkType;
+
+use std;
+
+pub const DEFAULT_SNAP_LENGTH: u32 = 262144;
+pub const DEFAULT_READ_TIMEOUT: u32 = 1000; //milliseconds
+
+///
+/// Size libpcap requires of the buffer it writes error messages to, PCAP_ERRBUF_SIZE
+///
+const ERRBUF_SIZE: usize = 256;
+
+const NEXT_PACKET: libc::c_int = 1;
+const NEXT_TIMEOUT: libc::c_int = 0;
+const NEXT_EOF: libc::c_int = -2;
+
+#[allow(non_camel_case_types)]
+enum pcap_t {}
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+struct pcap_pkthdr {
+    ts: libc::timeval,
+    caplen: u32,
+    len: u32
+}
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+struct pcap_stat {
+    ps_recv: libc::c_uint,
+    ps_drop: libc::c_uint,
+    ps_ifdrop: libc::c_uint
+}
+
+#[cfg_attr(windows, link(name = "wpcap"))]
+#[cfg_attr(not(windows), link(name = "pcap"))]
+extern "C" {
+    fn pcap_open_live(device: *const libc::c_char, snaplen: libc::c_int, promisc: libc::c_int, to_ms: libc::c_int, errbuf: *mut libc::c_char) -> *mut pcap_t;
+    fn pcap_next_ex(p: *mut pcap_t, header: *mut *mut pcap_pkthdr, data: *mut *const u8) -> libc::c_int;
+    fn pcap_datalink(p: *mut pcap_t) -> libc::c_int;
+    fn pcap_snapshot(p: *mut pcap_t) -> libc::c_int;
+    fn pcap_stats(p: *mut pcap_t, stats: *mut pcap_stat) -> libc::c_int;
+    fn pcap_geterr(p: *mut pcap_t) -> *mut libc::c_char;
+    fn pcap_close(p: *mut pcap_t);
+}
+
+///
+/// Options for a live capture
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CaptureOptions {
+    pub snap_length: u32,
+    ///
+    /// How long libpcap waits to fill a buffer before handing over what it has, in milliseconds
+    ///
+    pub read_timeout: u32,
+    pub promiscuous: bool
+}
+
+impl Default for CaptureOptions {
+    fn default() -> CaptureOptions {
+        CaptureOptions {
+            snap_length: DEFAULT_SNAP_LENGTH,
+            read_timeout: DEFAULT_READ_TIMEOUT,
+            promiscuous: false
+        }
+    }
+}
+
+///
+/// Packet counts libpcap keeps for a capture, since it opened
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CaptureStatistics {
+    pub packets: u32,
+    ///
+    /// Packets dropped because the capture buffer was full
+    ///
+    pub drops: u32,
+    ///
+    /// Packets dropped by the interface or its driver, where the platform reports it
+    ///
+    pub interface_drops: u32
+}
+
+fn message(message: *const libc::c_char) -> String {
+    unsafe { std::ffi::CStr::from_ptr(message) }.to_string_lossy().into_owned()
+}
+
+///
+/// Record of a packet libpcap returned, copying the captured bytes out of its buffer
+///
+fn to_record(header: &pcap_pkthdr, data: &[u8]) -> PcapRecord {
+    PcapRecord::new(
+        PcapRecord::convert_packet_time(header.ts.tv_sec as u32, header.ts.tv_usec as u32),
+        header.caplen,
+        header.len,
+        data.to_vec()
+    )
+}
+
+///
+/// Live capture through the system libpcap (Npcap on Windows), for platforms without AF_PACKET.
+/// Linking requires the libpcap development library, or the Npcap SDK on Windows.
+///
+pub struct LibpcapCapture {
+    handle: *mut pcap_t,
+    header: GlobalHeader
+}
+
+//libpcap handles may be used from any one thread at a time
+unsafe impl Send for LibpcapCapture {}
+
+impl LibpcapCapture {
+    ///
+    /// Open `interface` through libpcap and start capturing on it
+    ///
+    pub fn open(interface: &str, options: &CaptureOptions) -> errors::Result<LibpcapCapture> {
+        let name = std::ffi::CString::new(interface)?;
+        let mut errbuf = [0 as libc::c_char; ERRBUF_SIZE];
+        let handle = unsafe {
+            pcap_open_live(name.as_ptr(), options.snap_length as libc::c_int, options.promiscuous as libc::c_int,
+                           options.read_timeout as libc::c_int, errbuf.as_mut_ptr())
+        };
+        if handle.is_null() {
+            return Err(format!("Could not open {}: {}", interface, message(errbuf.as_ptr())).into())
+        }
+
+        let (link_type, snap_length) = unsafe { (pcap_datalink(handle), pcap_snapshot(handle)) };
+        let header = GlobalHeader::new(LinkType::new(link_type as u32), snap_length as u32);
+        debug!("Capturing on {} with link type {}", interface, header.link_type());
+
+        Ok(LibpcapCapture {
+            handle: handle,
+            header: header
+        })
+    }
+
+    ///
+    /// Header equivalent to the capture, e.g. to write the records out with a `CaptureWriter`
+    ///
+    pub fn header(&self) -> &GlobalHeader { &self.header }
+    pub fn link_type(&self) -> LinkType { self.header.link_type() }
+
+    ///
+    /// Next captured packet, waiting for one to arrive, or `None` once libpcap has no more
+    ///
+    pub fn next_record(&mut self) -> errors::Result<Option<PcapRecord>> {
+        loop {
+            let mut header: *mut pcap_pkthdr = std::ptr::null_mut();
+            let mut data: *const u8 = std::ptr::null();
+            match unsafe { pcap_next_ex(self.handle, &mut header, &mut data) } {
+                NEXT_PACKET => {
+                    let (header, data) = unsafe {
+                        let header = &*header;
+                        (header, std::slice::from_raw_parts(data, header.caplen as usize))
+                    };
+                    return Ok(Some(to_record(header, data).with_header(&self.header)))
+                }
+                NEXT_TIMEOUT => continue,
+                NEXT_EOF => return Ok(None),
+                _ => return Err(message(unsafe { pcap_geterr(self.handle) }).into())
+            }
+        }
+    }
+
+    ///
+    /// Packets received and dropped since the capture opened
+    ///
+    pub fn statistics(&self) -> errors::Result<CaptureStatistics> {
+        let mut stats = pcap_stat {
+            ps_recv: 0,
+            ps_drop: 0,
+            ps_ifdrop: 0
+        };
+        if unsafe { pcap_stats(self.handle, &mut stats) } < 0 {
+            return Err(message(unsafe { pcap_geterr(self.handle) }).into())
+        }
+        Ok(CaptureStatistics {
+            packets: stats.ps_recv,
+            drops: stats.ps_drop,
+            interface_drops: stats.ps_ifdrop
+        })
+    }
+}
+
+impl Drop for LibpcapCapture {
+    fn drop(&mut self) {
+        unsafe { pcap_close(self.handle) }
+    }
+}
+
+impl Iterator for LibpcapCapture {
+    type Item = errors::Result<PcapRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_record() {
+            Ok(record) => record.map(Ok),
+            Err(e) => Some(Err(e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    #[test]
+    fn convert_packet() {
+        let _ = env_logger::try_init();
+
+        let header = pcap_pkthdr {
+            ts: libc::timeval {
+                tv_sec: 1527868899,
+                tv_usec: 152053
+            },
+            caplen: 4,
+            len: 60
+        };
+        let record = to_record(&header, &[0x01u8, 0x02u8, 0x03u8, 0x04u8]);
+        assert_eq!(*record.timestamp(), PcapRecord::convert_packet_time(1527868899, 152053));
+        assert_eq!(record.actual_length(), 4);
+        assert_eq!(record.original_length(), 60);
+        assert_eq!(record.payload(), &vec![0x01u8, 0x02u8, 0x03u8, 0x04u8]);
+    }
+
+    #[test]
+    fn open_missing_interface() {
+        let _ = env_logger::try_init();
+
+        let e = LibpcapCapture::open("no-such-if0", &CaptureOptions::default()).err().expect("Opened missing interface");
+        assert!(format!("{}", e).contains("no-such-if0"));
+    }
+}
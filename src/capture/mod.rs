@@ -0,0 +1,8 @@
+pub mod prelude {
+    pub use super::super::prelude::*;
+}
+
+#[cfg(all(feature = "afpacket", target_os = "linux"))]
+pub mod afpacket;
+#[cfg(feature = "libpcap")]
+pub mod libpcap;
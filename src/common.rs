@@ -1,14 +1,109 @@
+use super::errors;
+use super::nom::Endianness;
+
 use std;
 
 pub const MAC_LENGTH: usize = 6;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct MacAddress(pub [u8; MAC_LENGTH]);
 
-pub type Vlan = u16;
+const VLAN_ID_MASK: u16 = 0x0FFFu16;
+
+///
+/// Vlan a frame was tagged with, taken from an 802.1Q tag: vlan identifier, priority code point,
+/// drop eligible indicator, and the tag protocol identifier that announced the tag. Untagged frames
+/// use `Vlan::untagged()`, which has a zero tpid.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Vlan {
+    id: u16,
+    pcp: u8,
+    dei: bool,
+    tpid: u16
+}
 
 pub type Port = u16;
 
+///
+/// Whether a frame is addressed to one host, every host on the segment, or a group of hosts
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Delivery {
+    Unicast,
+    Broadcast,
+    Multicast
+}
+
+impl Delivery {
+    ///
+    /// Delivery of a frame from its destination mac, or its destination ip when that is broadcast or
+    /// multicast behind a unicast mac
+    ///
+    pub fn classify(dst_mac: &MacAddress, dst_ip: Option<&std::net::IpAddr>) -> Delivery {
+        let broadcast_ip = match dst_ip {
+            Some(&std::net::IpAddr::V4(ref ip)) => ip.is_broadcast(),
+            _ => false
+        };
+        if dst_mac.is_broadcast() || broadcast_ip {
+            Delivery::Broadcast
+        } else if dst_mac.is_multicast() || dst_ip.map(|ip| ip.is_multicast()).unwrap_or(false) {
+            Delivery::Multicast
+        } else {
+            Delivery::Unicast
+        }
+    }
+
+    ///
+    /// Delivery of a packet without a destination mac, e.g. raw ip, from its destination ip
+    ///
+    pub fn classify_ip(dst_ip: &std::net::IpAddr) -> Delivery {
+        Delivery::classify(&MacAddress([0u8; MAC_LENGTH]), Some(dst_ip))
+    }
+}
+
+impl MacAddress {
+    ///
+    /// ff:ff:ff:ff:ff:ff
+    ///
+    pub fn is_broadcast(&self) -> bool {
+        self.0 == [0xFFu8; MAC_LENGTH]
+    }
+
+    ///
+    /// Group address, i.e. the least significant bit of the first octet is set. Broadcast is also multicast.
+    ///
+    pub fn is_multicast(&self) -> bool {
+        self.0[0] & 0x01u8 != 0
+    }
+
+    ///
+    /// Locally administered address, i.e. the second least significant bit of the first octet is set
+    ///
+    pub fn is_locally_administered(&self) -> bool {
+        self.0[0] & 0x02u8 != 0
+    }
+
+    ///
+    /// Organizationally unique identifier, the first three octets
+    ///
+    pub fn oui(&self) -> u32 {
+        u32::from(self.0[0]) << 16 | u32::from(self.0[1]) << 8 | u32::from(self.0[2])
+    }
+
+    ///
+    /// Vendor of a universally administered unicast address, from the embedded OUI table
+    ///
+    #[cfg(feature = "oui")]
+    pub fn vendor(&self) -> Option<&'static str> {
+        if self.is_multicast() || self.is_locally_administered() {
+            None
+        } else {
+            super::oui::vendor(self.oui())
+        }
+    }
+}
+
 impl std::fmt::Display for MacAddress {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
@@ -22,6 +117,111 @@ impl std::fmt::Display for MacAddress {
     }
 }
 
+impl Vlan {
+    pub fn new(id: u16, pcp: u8, dei: bool, tpid: u16) -> Vlan {
+        Vlan {
+            id: id & VLAN_ID_MASK,
+            pcp: pcp & 0x07u8,
+            dei: dei,
+            tpid: tpid
+        }
+    }
+
+    ///
+    /// Split tag control information into priority (3 bits), drop eligibility (1 bit) and id (12 bits)
+    ///
+    pub fn from_tci(tpid: u16, tci: u16) -> Vlan {
+        Vlan {
+            id: tci & VLAN_ID_MASK,
+            pcp: (tci >> 13) as u8,
+            dei: tci & 0x1000u16 != 0,
+            tpid: tpid
+        }
+    }
+
+    pub fn untagged() -> Vlan {
+        Vlan::default()
+    }
+
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+    pub fn pcp(&self) -> u8 {
+        self.pcp
+    }
+    pub fn dei(&self) -> bool {
+        self.dei
+    }
+    pub fn tpid(&self) -> u16 {
+        self.tpid
+    }
+    pub fn is_tagged(&self) -> bool {
+        self.tpid != 0
+    }
+
+    ///
+    /// Tag control information as it appears on the wire
+    ///
+    pub fn tci(&self) -> u16 {
+        (self.pcp as u16) << 13 | if self.dei { 0x1000u16 } else { 0 } | self.id
+    }
+}
+
+impl std::fmt::Display for Vlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.id)
+    }
+}
+
+impl std::str::FromStr for MacAddress {
+    type Err = errors::Error;
+
+    ///
+    /// Parse six hex octets separated by ':' or '-', e.g. aa:bb:cc:dd:ee:ff
+    ///
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut mac = [0u8; MAC_LENGTH];
+        let mut octets = s.split(|c| c == ':' || c == '-');
+
+        for octet in mac.iter_mut() {
+            *octet = octets.next()
+                .filter(|o| o.len() == 2)
+                .and_then(|o| u8::from_str_radix(o, 16).ok())
+                .ok_or_else(|| errors::Error::from(format!("Invalid mac address {}", s)))?;
+        }
+
+        if octets.next().is_some() {
+            return Err(errors::Error::from(format!("Invalid mac address {}", s)));
+        }
+
+        Ok(MacAddress(mac))
+    }
+}
+
+///
+/// Append `value` to `bytes` in byte order `endianness`, for writing captures
+///
+pub(crate) fn put_u16(bytes: &mut std::vec::Vec<u8>, value: u16, endianness: Endianness) {
+    match endianness {
+        Endianness::Big => bytes.extend_from_slice(&value.to_be_bytes()),
+        Endianness::Little => bytes.extend_from_slice(&value.to_le_bytes())
+    }
+}
+
+pub(crate) fn put_u32(bytes: &mut std::vec::Vec<u8>, value: u32, endianness: Endianness) {
+    match endianness {
+        Endianness::Big => bytes.extend_from_slice(&value.to_be_bytes()),
+        Endianness::Little => bytes.extend_from_slice(&value.to_le_bytes())
+    }
+}
+
+pub(crate) fn put_u64(bytes: &mut std::vec::Vec<u8>, value: u64, endianness: Endianness) {
+    match endianness {
+        Endianness::Big => bytes.extend_from_slice(&value.to_be_bytes()),
+        Endianness::Little => bytes.extend_from_slice(&value.to_le_bytes())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -32,4 +232,76 @@ mod tests {
 
         assert_eq!(format!("{}", mac), "00:01:02:03:04:05".to_string());
     }
+
+    #[test]
+    fn vlan_from_tci() {
+        let vlan = Vlan::from_tci(0x8100u16, 0xB12Cu16);
+
+        assert_eq!(vlan.id(), 300);
+        assert_eq!(vlan.pcp(), 5);
+        assert!(vlan.dei());
+        assert!(vlan.is_tagged());
+        assert_eq!(vlan.tci(), 0xB12Cu16);
+        assert_eq!(vlan, Vlan::new(300, 5, true, 0x8100u16));
+        assert_eq!(format!("{}", vlan), "300".to_string());
+
+        assert!(!Vlan::untagged().is_tagged());
+        assert_eq!(format!("{}", Vlan::untagged()), "0".to_string());
+    }
+
+    #[test]
+    fn classify_delivery() {
+        let unicast = MacAddress([0x00u8, 0x1Bu8, 0x21u8, 0x00u8, 0x00u8, 0x01u8]);
+        let ip = |s: &str| s.parse::<std::net::IpAddr>().expect("Could not parse ip address");
+
+        assert_eq!(Delivery::classify(&unicast, Some(&ip("10.0.0.1"))), Delivery::Unicast);
+        assert_eq!(Delivery::classify(&unicast, None), Delivery::Unicast);
+        assert_eq!(Delivery::classify(&MacAddress([0xFFu8; MAC_LENGTH]), None), Delivery::Broadcast);
+        assert_eq!(Delivery::classify(&unicast, Some(&ip("255.255.255.255"))), Delivery::Broadcast);
+        assert_eq!(Delivery::classify(&MacAddress([0x01u8, 0x00u8, 0x5Eu8, 0x00u8, 0x00u8, 0xFBu8]), Some(&ip("224.0.0.251"))), Delivery::Multicast);
+        assert_eq!(Delivery::classify(&unicast, Some(&ip("ff02::1"))), Delivery::Multicast);
+    }
+
+    #[test]
+    fn parse_mac_address() {
+        let mac = "aa:BB:cc:dd:ee:0f".parse::<MacAddress>().expect("Could not parse");
+
+        assert_eq!(mac, MacAddress([0xAAu8, 0xBBu8, 0xCCu8, 0xDDu8, 0xEEu8, 0x0Fu8]));
+        assert_eq!(format!("{}", mac), "aa:bb:cc:dd:ee:0f".to_string());
+        assert_eq!("aa-bb-cc-dd-ee-0f".parse::<MacAddress>().ok(), Some(mac));
+
+        assert!("aa:bb:cc:dd:ee".parse::<MacAddress>().is_err());
+        assert!("aa:bb:cc:dd:ee:ff:00".parse::<MacAddress>().is_err());
+        assert!("aa:bb:cc:dd:ee:f".parse::<MacAddress>().is_err());
+        assert!("aa:bb:cc:dd:ee:gg".parse::<MacAddress>().is_err());
+    }
+
+    #[test]
+    fn classify_mac_address() {
+        let broadcast = MacAddress([0xFFu8; MAC_LENGTH]);
+        assert!(broadcast.is_broadcast());
+        assert!(broadcast.is_multicast());
+
+        let multicast = MacAddress([0x01u8, 0x00u8, 0x5Eu8, 0x00u8, 0x00u8, 0x01u8]);
+        assert!(!multicast.is_broadcast());
+        assert!(multicast.is_multicast());
+        assert!(!multicast.is_locally_administered());
+
+        let local = MacAddress([0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x01u8]);
+        assert!(!local.is_multicast());
+        assert!(local.is_locally_administered());
+    }
+
+    #[test]
+    fn mac_address_vendor() {
+        let mac = "00:50:56:01:02:03".parse::<MacAddress>().expect("Could not parse");
+        assert_eq!(mac.oui(), 0x005056);
+
+        #[cfg(feature = "oui")]
+        {
+            assert_eq!(mac.vendor(), Some("VMware"));
+            assert_eq!(MacAddress([0x02u8, 0x50u8, 0x56u8, 0x01u8, 0x02u8, 0x03u8]).vendor(), None);
+            assert_eq!(MacAddress([0xFFu8; MAC_LENGTH]).vendor(), None);
+        }
+    }
 }
\ No newline at end of file
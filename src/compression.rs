@@ -0,0 +1,347 @@
+#[cfg(feature = "compression")]
+use super::flate2::read::MultiGzDecoder;
+#[cfg(feature = "lz4")]
+use super::lz4_flex::frame::FrameDecoder as Lz4Decoder;
+#[cfg(feature = "zstd")]
+use super::ruzstd::decoding::{BlockDecodingStrategy, FrameDecoder};
+#[cfg(feature = "zstd")]
+use super::ruzstd::decoding::errors::{FrameDecoderError, ReadFrameHeaderError};
+
+use std;
+use std::io::Read;
+
+///
+/// First bytes of a gzip stream, e.g. a `.pcap.gz` or `.pcapng.gz` archive
+///
+pub const GZIP_MAGIC: [u8; 2] = [0x1Fu8, 0x8Bu8];
+///
+/// First bytes of a zstd frame, e.g. a `.pcap.zst` archive
+///
+pub const ZSTD_MAGIC: [u8; 4] = [0x28u8, 0xB5u8, 0x2Fu8, 0xFDu8];
+///
+/// First bytes of an lz4 frame, e.g. a `.pcap.lz4` archive
+///
+pub const LZ4_MAGIC: [u8; 4] = [0x04u8, 0x22u8, 0x4Du8, 0x18u8];
+
+///
+/// Whether `input` starts with the gzip magic
+///
+pub fn is_gzip(input: &[u8]) -> bool {
+    input.starts_with(&GZIP_MAGIC)
+}
+
+///
+/// Whether `input` starts with the zstd frame magic
+///
+pub fn is_zstd(input: &[u8]) -> bool {
+    input.starts_with(&ZSTD_MAGIC)
+}
+
+///
+/// Whether `input` starts with the lz4 frame magic
+///
+pub fn is_lz4(input: &[u8]) -> bool {
+    input.starts_with(&LZ4_MAGIC)
+}
+
+fn invalid_data<E: std::fmt::Display>(e: E) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}", e))
+}
+
+///
+/// Error for a stream compressed with `format`, which needs `feature` to decompress
+///
+#[allow(dead_code)]
+fn unsupported(format: &str, feature: &str) -> std::io::Error {
+    invalid_data(format!("Capture is {} compressed, which needs the `{}` feature", format, feature))
+}
+
+///
+/// Reader decompressing a stream of zstd frames, skipping skippable frames between them
+///
+#[cfg(feature = "zstd")]
+pub struct ZstdDecoder<R> {
+    source: R,
+    frame: FrameDecoder
+}
+
+#[cfg(feature = "zstd")]
+impl<R: Read> ZstdDecoder<R> {
+    pub fn new(source: R) -> ZstdDecoder<R> {
+        ZstdDecoder {
+            source: source,
+            frame: FrameDecoder::new()
+        }
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl<R: Read> Read for ZstdDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            if self.frame.can_collect() > 0 {
+                return self.frame.read(buf)
+            }
+            if !self.frame.is_finished() {
+                self.frame.decode_blocks(&mut self.source, BlockDecodingStrategy::UptoBlocks(1)).map_err(invalid_data)?;
+                continue
+            }
+            match self.frame.reset(&mut self.source) {
+                Ok(()) => trace!("Decoding zstd frame"),
+                Err(FrameDecoderError::ReadFrameHeaderError(ReadFrameHeaderError::MagicNumberReadError(ref e)))
+                    if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(0),
+                Err(FrameDecoderError::ReadFrameHeaderError(ReadFrameHeaderError::SkipFrame { length, .. })) => {
+                    trace!("Skipping {} bytes of skippable frame", length);
+                    let skipped = std::io::copy(&mut (&mut self.source).take(length as u64), &mut std::io::sink())?;
+                    if skipped < length as u64 {
+                        return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "stream ended within skippable frame"))
+                    }
+                }
+                Err(e) => return Err(invalid_data(e))
+            }
+        }
+    }
+}
+
+///
+/// Stream with the bytes read to detect compression put back in front of it
+///
+pub type Peeked<R> = std::io::Chain<std::io::Cursor<std::vec::Vec<u8>>, R>;
+
+///
+/// Reader that decompresses, with the `compression` feature, a gzip stream, or with the `zstd` and
+/// `lz4` features a zstd or lz4 frame stream, and passes any other stream through unchanged. A
+/// stream compressed in a format whose feature is disabled is an error rather than passed through.
+/// Streams of several concatenated gzip members or frames, as written by e.g. appending to a `.gz`
+/// file, are read to the end of the last.
+///
+pub enum Decoder<R: Read> {
+    Plain(Peeked<R>),
+    #[cfg(feature = "compression")]
+    Gzip(MultiGzDecoder<Peeked<R>>),
+    #[cfg(feature = "zstd")]
+    Zstd(Box<ZstdDecoder<Peeked<R>>>),
+    #[cfg(feature = "lz4")]
+    Lz4(Lz4Decoder<Peeked<R>>)
+}
+
+impl<R: Read> Decoder<R> {
+    ///
+    /// Decoder of `reader`, chosen by reading its first bytes
+    ///
+    pub fn new(mut reader: R) -> std::io::Result<Decoder<R>> {
+        let mut magic = [0u8; 4];
+        let mut filled = 0;
+        while filled < magic.len() {
+            match reader.read(&mut magic[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e)
+            }
+        }
+
+        let peeked = std::io::Cursor::new(magic[..filled].to_vec()).chain(reader);
+        let magic = &magic[..filled];
+        if is_gzip(magic) {
+            #[cfg(feature = "compression")]
+            {
+                debug!("Decompressing gzip stream");
+                return Ok(Decoder::Gzip(MultiGzDecoder::new(peeked)))
+            }
+            #[cfg(not(feature = "compression"))]
+            return Err(unsupported("gzip", "compression"))
+        }
+        if is_zstd(magic) {
+            #[cfg(feature = "zstd")]
+            {
+                debug!("Decompressing zstd stream");
+                return Ok(Decoder::Zstd(Box::new(ZstdDecoder::new(peeked))))
+            }
+            #[cfg(not(feature = "zstd"))]
+            return Err(unsupported("zstd", "zstd"))
+        }
+        if is_lz4(magic) {
+            #[cfg(feature = "lz4")]
+            {
+                debug!("Decompressing lz4 stream");
+                return Ok(Decoder::Lz4(Lz4Decoder::new(peeked)))
+            }
+            #[cfg(not(feature = "lz4"))]
+            return Err(unsupported("lz4", "lz4"))
+        }
+        Ok(Decoder::Plain(peeked))
+    }
+
+    ///
+    /// Whether the stream is compressed
+    ///
+    pub fn is_compressed(&self) -> bool {
+        !matches!(*self, Decoder::Plain(_))
+    }
+}
+
+impl<R: Read> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match *self {
+            Decoder::Plain(ref mut reader) => reader.read(buf),
+            #[cfg(feature = "compression")]
+            Decoder::Gzip(ref mut decoder) => decoder.read(buf),
+            #[cfg(feature = "zstd")]
+            Decoder::Zstd(ref mut decoder) => decoder.read(buf),
+            #[cfg(feature = "lz4")]
+            Decoder::Lz4(ref mut decoder) => {
+                //a frame ends with a zero length read, after which the next frame, if any, is read
+                match decoder.read(buf)? {
+                    0 if !buf.is_empty() => decoder.read(buf),
+                    n => Ok(n)
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+    #[cfg(feature = "compression")]
+    use super::super::flate2::Compression;
+    #[cfg(feature = "compression")]
+    use super::super::flate2::write::GzEncoder;
+    #[cfg(feature = "compression")]
+    use std::io::Write;
+
+    #[cfg(feature = "compression")]
+    fn gzip(data: &[u8]) -> std::vec::Vec<u8> {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(data).expect("Could not compress");
+        encoder.finish().expect("Could not compress")
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn decode_gzip() {
+        let _ = env_logger::try_init();
+
+        let data = (0..1000u32).map(|i| i as u8).collect::<std::vec::Vec<_>>();
+
+        let mut compressed = gzip(&data[..600]);
+        compressed.extend_from_slice(&gzip(&data[600..]));
+        assert!(is_gzip(&compressed));
+
+        let mut decoder = Decoder::new(&compressed[..]).expect("Could not detect compression");
+        assert!(decoder.is_compressed());
+        let mut decoded = vec![];
+        decoder.read_to_end(&mut decoded).expect("Could not decompress");
+        assert_eq!(decoded, data);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn decode_zstd() {
+        use super::super::ruzstd::encoding::{compress_to_vec, CompressionLevel};
+
+        let _ = env_logger::try_init();
+
+        let data = (0..1000u32).map(|i| i as u8).collect::<std::vec::Vec<_>>();
+
+        let mut compressed = compress_to_vec(&data[..600], CompressionLevel::Fastest);
+        assert!(is_zstd(&compressed));
+        compressed.extend_from_slice(&[
+            0x50u8, 0x2Au8, 0x4Du8, 0x18u8, //skippable frame magic
+            0x03u8, 0x00u8, 0x00u8, 0x00u8, //length, 3
+            0x01u8, 0x02u8, 0x03u8
+        ]);
+        compressed.extend_from_slice(&compress_to_vec(&data[600..], CompressionLevel::Fastest));
+
+        let mut decoder = Decoder::new(&compressed[..]).expect("Could not detect compression");
+        assert!(decoder.is_compressed());
+        let mut decoded = vec![];
+        decoder.read_to_end(&mut decoded).expect("Could not decompress");
+        assert_eq!(decoded, data);
+
+        let length = compressed.len();
+        let mut decoder = Decoder::new(&compressed[..length - 4]).expect("Could not detect compression");
+        assert!(decoder.read_to_end(&mut vec![]).is_err());
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn decode_lz4() {
+        use super::super::lz4_flex::frame::FrameEncoder;
+
+        let _ = env_logger::try_init();
+
+        let data = (0..1000u32).map(|i| i as u8).collect::<std::vec::Vec<_>>();
+
+        let mut compressed = vec![];
+        for part in &[&data[..600], &data[600..]] {
+            let mut encoder = FrameEncoder::new(vec![]);
+            encoder.write_all(part).expect("Could not compress");
+            compressed.extend_from_slice(&encoder.finish().expect("Could not compress"));
+        }
+        assert!(is_lz4(&compressed));
+
+        let mut decoder = Decoder::new(&compressed[..]).expect("Could not detect compression");
+        assert!(decoder.is_compressed());
+        let mut decoded = vec![];
+        decoder.read_to_end(&mut decoded).expect("Could not decompress");
+        assert_eq!(decoded, data);
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn decode_corrupt_gzip() {
+        let _ = env_logger::try_init();
+
+        let mut compressed = gzip(&[0x01u8, 0x02u8, 0x03u8, 0x04u8]);
+        let length = compressed.len();
+        compressed.truncate(length - 6);
+
+        let mut decoder = Decoder::new(&compressed[..]).expect("Could not detect compression");
+        let mut decoded = vec![];
+        assert!(decoder.read_to_end(&mut decoded).is_err());
+    }
+
+    #[test]
+    fn decode_plain() {
+        let _ = env_logger::try_init();
+
+        let data = (0..1000u32).map(|i| i as u8).collect::<std::vec::Vec<_>>();
+
+        let mut decoder = Decoder::new(&data[..]).expect("Could not detect compression");
+        assert!(!decoder.is_compressed());
+        let mut decoded = vec![];
+        decoder.read_to_end(&mut decoded).expect("Could not read");
+        assert_eq!(decoded, data);
+
+        let mut decoder = Decoder::new(&data[..1]).expect("Could not detect compression");
+        let mut decoded = vec![];
+        decoder.read_to_end(&mut decoded).expect("Could not read");
+        assert_eq!(decoded, vec![0u8]);
+    }
+
+    #[cfg(not(feature = "zstd"))]
+    #[test]
+    fn decode_zstd_disabled() {
+        let _ = env_logger::try_init();
+
+        let compressed = [0x28u8, 0xB5u8, 0x2Fu8, 0xFDu8, 0x00u8, 0x00u8];
+        assert!(is_zstd(&compressed));
+        let err = Decoder::new(&compressed[..]).err().expect("Passed a zstd stream through");
+        assert_eq!(format!("{}", err), "Capture is zstd compressed, which needs the `zstd` feature");
+    }
+
+    #[cfg(not(feature = "lz4"))]
+    #[test]
+    fn decode_lz4_disabled() {
+        let _ = env_logger::try_init();
+
+        let compressed = [0x04u8, 0x22u8, 0x4Du8, 0x18u8, 0x60u8, 0x40u8];
+        assert!(is_lz4(&compressed));
+        let err = Decoder::new(&compressed[..]).err().expect("Passed an lz4 stream through");
+        assert_eq!(format!("{}", err), "Capture is lz4 compressed, which needs the `lz4` feature");
+    }
+}
@@ -0,0 +1,618 @@
+use super::prelude::*;
+#[cfg(feature = "flows")]
+use super::flow::{Flow, FlowKey};
+use super::parsed::ParsedRecord;
+use super::record::PcapRecord;
+
+use std;
+
+const IPV4_BITS: u8 = 32;
+const IPV6_BITS: u8 = 128;
+const OUI_LENGTH: usize = 3;
+
+///
+/// Ip network in prefix notation, e.g. 10.0.0.0/8. A bare address is a network of just that host.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Cidr {
+    network: std::net::IpAddr,
+    prefix: u8
+}
+
+impl Cidr {
+    ///
+    /// Network of `prefix` bits of `ip`, with any host bits cleared
+    ///
+    pub fn new(ip: std::net::IpAddr, prefix: u8) -> errors::Result<Cidr> {
+        let cidr = match ip {
+            std::net::IpAddr::V4(v4) if prefix <= IPV4_BITS => {
+                let (first, _) = v4_range(u32::from(v4), prefix);
+                Cidr { network: std::net::IpAddr::V4(first.into()), prefix: prefix }
+            }
+            std::net::IpAddr::V6(v6) if prefix <= IPV6_BITS => {
+                let (first, _) = v6_range(u128::from(v6), prefix);
+                Cidr { network: std::net::IpAddr::V6(first.into()), prefix: prefix }
+            }
+            _ => return Err(errors::Error::from(format!("Invalid prefix length {} for {}", prefix, ip)))
+        };
+        Ok(cidr)
+    }
+
+    pub fn network(&self) -> &std::net::IpAddr { &self.network }
+    pub fn prefix(&self) -> u8 { self.prefix }
+
+    pub fn contains(&self, ip: &std::net::IpAddr) -> bool {
+        match (self.network, *ip) {
+            (std::net::IpAddr::V4(network), std::net::IpAddr::V4(ip)) => {
+                let (first, last) = v4_range(u32::from(network), self.prefix);
+                first <= u32::from(ip) && u32::from(ip) <= last
+            }
+            (std::net::IpAddr::V6(network), std::net::IpAddr::V6(ip)) => {
+                let (first, last) = v6_range(u128::from(network), self.prefix);
+                first <= u128::from(ip) && u128::from(ip) <= last
+            }
+            _ => false
+        }
+    }
+}
+
+fn v4_range(ip: u32, prefix: u8) -> (u32, u32) {
+    let host_mask = u32::max_value().checked_shr(prefix as u32).unwrap_or(0);
+    (ip & !host_mask, ip | host_mask)
+}
+
+fn v6_range(ip: u128, prefix: u8) -> (u128, u128) {
+    let host_mask = u128::max_value().checked_shr(prefix as u32).unwrap_or(0);
+    (ip & !host_mask, ip | host_mask)
+}
+
+impl std::str::FromStr for Cidr {
+    type Err = errors::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, '/');
+        let ip = parts.next().unwrap_or("").parse::<std::net::IpAddr>()
+            .map_err(|_| errors::Error::from(format!("Invalid network {}", s)))?;
+        let prefix = match parts.next() {
+            Some(prefix) => prefix.parse::<u8>().map_err(|_| errors::Error::from(format!("Invalid network {}", s)))?,
+            None if ip.is_ipv4() => IPV4_BITS,
+            None => IPV6_BITS
+        };
+        Cidr::new(ip, prefix)
+    }
+}
+
+impl std::fmt::Display for Cidr {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix)
+    }
+}
+
+///
+/// Sorted, non overlapping address ranges, merged from the networks given
+///
+fn merge<T: Ord + Copy>(mut ranges: std::vec::Vec<(T, T)>, next: fn(T) -> Option<T>) -> std::vec::Vec<(T, T)> {
+    ranges.sort();
+    let mut merged: std::vec::Vec<(T, T)> = vec![];
+    for (first, last) in ranges {
+        if let Some(previous) = merged.last_mut() {
+            let adjacent = next(previous.1).map(|n| first <= n).unwrap_or(true);
+            if adjacent {
+                previous.1 = std::cmp::max(previous.1, last);
+                continue;
+            }
+        }
+        merged.push( (first, last) );
+    }
+    merged
+}
+
+fn in_ranges<T: Ord + Copy>(ranges: &[(T, T)], ip: T) -> bool {
+    match ranges.binary_search_by(|&(first, _)| first.cmp(&ip)) {
+        Ok(_) => true,
+        Err(0) => false,
+        Err(idx) => ip <= ranges[idx - 1].1
+    }
+}
+
+///
+/// Set of networks matched by binary search over their merged address ranges, so large lists of
+/// prefixes cost a logarithmic lookup per address
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CidrSet {
+    v4: std::vec::Vec<(u32, u32)>,
+    v6: std::vec::Vec<(u128, u128)>
+}
+
+impl CidrSet {
+    pub fn contains(&self, ip: &std::net::IpAddr) -> bool {
+        match *ip {
+            std::net::IpAddr::V4(v4) => in_ranges(&self.v4, u32::from(v4)),
+            std::net::IpAddr::V6(v6) => in_ranges(&self.v6, u128::from(v6))
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.v4.is_empty() && self.v6.is_empty()
+    }
+
+    ///
+    /// Parse a set from networks in prefix notation
+    ///
+    pub fn parse<'a, I: IntoIterator<Item = &'a str>>(cidrs: I) -> errors::Result<CidrSet> {
+        cidrs.into_iter()
+            .map(|s| s.parse::<Cidr>())
+            .collect::<errors::Result<std::vec::Vec<_>>>()
+            .map(|cidrs| cidrs.into_iter().collect())
+    }
+}
+
+impl std::iter::FromIterator<Cidr> for CidrSet {
+    fn from_iter<I: IntoIterator<Item = Cidr>>(iter: I) -> Self {
+        let mut v4 = vec![];
+        let mut v6 = vec![];
+        for cidr in iter {
+            match cidr.network {
+                std::net::IpAddr::V4(ip) => v4.push(v4_range(u32::from(ip), cidr.prefix)),
+                std::net::IpAddr::V6(ip) => v6.push(v6_range(u128::from(ip), cidr.prefix))
+            }
+        }
+        CidrSet {
+            v4: merge(v4, |ip: u32| ip.checked_add(1)),
+            v6: merge(v6, |ip: u128| ip.checked_add(1))
+        }
+    }
+}
+
+///
+/// Mac address, or the organizationally unique identifier (first three octets) of one
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MacPattern {
+    Exact(MacAddress),
+    Oui([u8; OUI_LENGTH])
+}
+
+impl MacPattern {
+    pub fn matches(&self, mac: &MacAddress) -> bool {
+        match *self {
+            MacPattern::Exact(ref exact) => exact == mac,
+            MacPattern::Oui(ref oui) => mac.0[..OUI_LENGTH] == oui[..]
+        }
+    }
+}
+
+impl std::str::FromStr for MacPattern {
+    type Err = errors::Error;
+
+    ///
+    /// Six octets for an exact address, or three for an oui, e.g. aa:bb:cc
+    ///
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.split(|c| c == ':' || c == '-').count() != OUI_LENGTH {
+            return s.parse::<MacAddress>().map(MacPattern::Exact)
+        }
+        let mut oui = [0u8; OUI_LENGTH];
+        for (octet, part) in oui.iter_mut().zip(s.split(|c| c == ':' || c == '-')) {
+            *octet = Some(part)
+                .filter(|p| p.len() == 2)
+                .and_then(|p| u8::from_str_radix(p, 16).ok())
+                .ok_or_else(|| errors::Error::from(format!("Invalid mac address prefix {}", s)))?;
+        }
+        Ok(MacPattern::Oui(oui))
+    }
+}
+
+///
+/// Items filters match against: records or flows, with their mac and ip addresses and capture time
+/// where they have them
+///
+pub trait Filterable {
+    fn addresses(&self) -> Option<(std::net::IpAddr, std::net::IpAddr)> { None }
+
+    ///
+    /// Source and destination mac
+    ///
+    fn macs(&self) -> Option<(MacAddress, MacAddress)> { None }
+
+    fn timestamp(&self) -> Option<std::time::SystemTime> { None }
+}
+
+///
+/// Unparsed records match on the macs read straight from the ethernet header and on their capture
+/// time, so mac and time filters can discard records before they're parsed. Ip filters never
+/// match them.
+///
+impl Filterable for PcapRecord {
+    fn macs(&self) -> Option<(MacAddress, MacAddress)> {
+        let payload = self.payload();
+        if payload.len() < 2 * MAC_LENGTH {
+            return None
+        }
+        let dst = MacAddress(array_ref![payload, 0, MAC_LENGTH].clone());
+        let src = MacAddress(array_ref![payload, MAC_LENGTH, MAC_LENGTH].clone());
+        Some( (src, dst) )
+    }
+
+    fn timestamp(&self) -> Option<std::time::SystemTime> { Some(*self.timestamp()) }
+}
+
+impl Filterable for (std::net::IpAddr, std::net::IpAddr) {
+    fn addresses(&self) -> Option<(std::net::IpAddr, std::net::IpAddr)> { Some(*self) }
+}
+
+impl Filterable for ParsedRecord {
+    fn addresses(&self) -> Option<(std::net::IpAddr, std::net::IpAddr)> {
+        match (self.src_ip(), self.dst_ip()) {
+            (Some(src), Some(dst)) => Some( (*src, *dst) ),
+            _ => None
+        }
+    }
+
+    fn macs(&self) -> Option<(MacAddress, MacAddress)> {
+        match (self.layer2().src_mac(), self.layer2().dst_mac()) {
+            (Some(src), Some(dst)) => Some( (src, dst) ),
+            _ => None
+        }
+    }
+
+    fn timestamp(&self) -> Option<std::time::SystemTime> { Some(*self.record().timestamp()) }
+}
+
+#[cfg(feature = "flows")]
+impl Filterable for Flow {
+    fn addresses(&self) -> Option<(std::net::IpAddr, std::net::IpAddr)> {
+        Some( (self.source.ip, self.destination.ip) )
+    }
+
+    fn macs(&self) -> Option<(MacAddress, MacAddress)> {
+        match (self.source.mac, self.destination.mac) {
+            (Some(src), Some(dst)) => Some( (src, dst) ),
+            _ => None
+        }
+    }
+
+    fn timestamp(&self) -> Option<std::time::SystemTime> { Some(*self.record.timestamp()) }
+}
+
+#[cfg(feature = "flows")]
+impl Filterable for FlowKey {
+    fn addresses(&self) -> Option<(std::net::IpAddr, std::net::IpAddr)> {
+        Some( (self.src_ip, self.dst_ip) )
+    }
+}
+
+impl<'a, T: Filterable> Filterable for &'a T {
+    fn addresses(&self) -> Option<(std::net::IpAddr, std::net::IpAddr)> { (*self).addresses() }
+
+    fn macs(&self) -> Option<(MacAddress, MacAddress)> { (*self).macs() }
+
+    fn timestamp(&self) -> Option<std::time::SystemTime> { (*self).timestamp() }
+}
+
+///
+/// Predicate on the addresses or capture time of records or flows, for subsetting with
+/// `Iterator::filter` or `FilterIteratorExt::matching`. Items without ip addresses never match ip
+/// filters, nor items without macs mac filters or without a timestamp time filters, except through
+/// `Not`.
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Filter {
+    ///
+    /// Either address is in the set
+    ///
+    IpIn(CidrSet),
+    SrcIn(CidrSet),
+    DstIn(CidrSet),
+    ///
+    /// Traffic between the two hosts, in either direction
+    ///
+    BetweenHosts(std::net::IpAddr, std::net::IpAddr),
+    ///
+    /// Either mac matches
+    ///
+    Mac(MacPattern),
+    SrcMac(MacPattern),
+    DstMac(MacPattern),
+    ///
+    /// Captured from the first time up to and including the second
+    ///
+    Within(std::time::SystemTime, std::time::SystemTime),
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>)
+}
+
+impl Filter {
+    ///
+    /// Either address in the network, e.g. `Filter::ip_in("10.0.0.0/8")`
+    ///
+    pub fn ip_in(cidr: &str) -> errors::Result<Filter> {
+        CidrSet::parse(std::iter::once(cidr)).map(Filter::IpIn)
+    }
+
+    ///
+    /// Either address in any of the networks
+    ///
+    pub fn ip_in_any<'a, I: IntoIterator<Item = &'a str>>(cidrs: I) -> errors::Result<Filter> {
+        CidrSet::parse(cidrs).map(Filter::IpIn)
+    }
+
+    pub fn src_in(cidr: &str) -> errors::Result<Filter> {
+        CidrSet::parse(std::iter::once(cidr)).map(Filter::SrcIn)
+    }
+
+    pub fn dst_in(cidr: &str) -> errors::Result<Filter> {
+        CidrSet::parse(std::iter::once(cidr)).map(Filter::DstIn)
+    }
+
+    pub fn between_hosts(a: std::net::IpAddr, b: std::net::IpAddr) -> Filter {
+        Filter::BetweenHosts(a, b)
+    }
+
+    ///
+    /// Either mac matches an address or oui, e.g. `Filter::mac("00:1b:21")`, selecting the traffic
+    /// of an endpoint or a vendor's devices
+    ///
+    pub fn mac(pattern: &str) -> errors::Result<Filter> {
+        pattern.parse().map(Filter::Mac)
+    }
+
+    pub fn src_mac(pattern: &str) -> errors::Result<Filter> {
+        pattern.parse().map(Filter::SrcMac)
+    }
+
+    pub fn dst_mac(pattern: &str) -> errors::Result<Filter> {
+        pattern.parse().map(Filter::DstMac)
+    }
+
+    pub fn between_times(start: std::time::SystemTime, end: std::time::SystemTime) -> Filter {
+        Filter::Within(start, end)
+    }
+
+    ///
+    /// Captured within `window` either side of `reference`, e.g. the timestamp of a packet of interest
+    ///
+    pub fn around(reference: std::time::SystemTime, window: std::time::Duration) -> Filter {
+        let start = reference.checked_sub(window).unwrap_or(std::time::UNIX_EPOCH);
+        let end = reference.checked_add(window).unwrap_or(reference);
+        Filter::Within(start, end)
+    }
+
+    pub fn and(self, other: Filter) -> Filter {
+        Filter::And(Box::new(self), Box::new(other))
+    }
+
+    pub fn or(self, other: Filter) -> Filter {
+        Filter::Or(Box::new(self), Box::new(other))
+    }
+
+    pub fn matches<T: Filterable>(&self, item: &T) -> bool {
+        match *self {
+            Filter::And(ref a, ref b) => a.matches(item) && b.matches(item),
+            Filter::Or(ref a, ref b) => a.matches(item) || b.matches(item),
+            Filter::Not(ref f) => !f.matches(item),
+            Filter::Within(ref start, ref end) => item.timestamp().map(|t| *start <= t && t <= *end).unwrap_or(false),
+            Filter::Mac(ref p) => item.macs().map(|(src, dst)| p.matches(&src) || p.matches(&dst)).unwrap_or(false),
+            Filter::SrcMac(ref p) => item.macs().map(|(src, _)| p.matches(&src)).unwrap_or(false),
+            Filter::DstMac(ref p) => item.macs().map(|(_, dst)| p.matches(&dst)).unwrap_or(false),
+            _ => item.addresses().map(|(src, dst)| self.matches_ips(&src, &dst)).unwrap_or(false)
+        }
+    }
+
+    fn matches_ips(&self, src: &std::net::IpAddr, dst: &std::net::IpAddr) -> bool {
+        match *self {
+            Filter::IpIn(ref set) => set.contains(src) || set.contains(dst),
+            Filter::SrcIn(ref set) => set.contains(src),
+            Filter::DstIn(ref set) => set.contains(dst),
+            Filter::BetweenHosts(ref a, ref b) => (src == a && dst == b) || (src == b && dst == a),
+            _ => false
+        }
+    }
+}
+
+impl std::ops::Not for Filter {
+    type Output = Filter;
+
+    fn not(self) -> Filter {
+        Filter::Not(Box::new(self))
+    }
+}
+
+///
+/// Iterator adaptor yielding only the items a filter matches
+///
+pub struct Matching<I> {
+    iter: I,
+    filter: Filter
+}
+
+impl<I> Iterator for Matching<I> where I: Iterator, I::Item: Filterable {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let filter = &self.filter;
+        self.iter.find(|item| filter.matches(item))
+    }
+}
+
+pub trait FilterIteratorExt: Iterator + Sized {
+    ///
+    /// Items matching `filter`, chaining with further adaptors, e.g.
+    /// `records.matching(Filter::around(t, window)).matching(Filter::ip_in("10.0.0.0/8")?)`
+    ///
+    fn matching(self, filter: Filter) -> Matching<Self> {
+        Matching {
+            iter: self,
+            filter: filter
+        }
+    }
+}
+
+impl<I: Iterator> FilterIteratorExt for I where I::Item: Filterable {}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+    use super::super::record::PcapRecord;
+
+    use std::convert::TryFrom;
+
+    const RAW_DATA: &'static [u8] = &[
+        0x5Bu8, 0x11u8, 0x6Du8, 0xE3u8, //seconds, 1527868899
+        0x00u8, 0x02u8, 0x51u8, 0xF5u8, //microseconds, 152053
+        0x00u8, 0x00u8, 0x00u8, 0x36u8, //actual length, 54: 14 (ethernet) + 20 (ipv4 header) + 20 (tcp header)
+        0x00u8, 0x00u8, 0x00u8, 0x36u8, //original length, 54
+        //ethernet
+        0x01u8, 0x02u8, 0x03u8, 0x04u8, 0x05u8, 0x06u8, //dst mac 01:02:03:04:05:06
+        0xFFu8, 0xFEu8, 0xFDu8, 0xFCu8, 0xFBu8, 0xFAu8, //src mac FF:FE:FD:FC:FB:FA
+        0x08u8, 0x00u8, //ipv4
+        //ipv4
+        0x45u8, //version and header length
+        0x00u8, //tos
+        0x00u8, 0x28u8, //length, 20 bytes for header, 20 bytes for tcp
+        0x00u8, 0x00u8, //id
+        0x00u8, 0x00u8, //flags
+        0x64u8, //ttl
+        0x06u8, //protocol, tcp
+        0x00u8, 0x00u8, //checksum
+        0x0Au8, 0x01u8, 0x02u8, 0x03u8, //src ip 10.1.2.3
+        0xC0u8, 0xA8u8, 0x00u8, 0x01u8, //dst ip 192.168.0.1
+        //tcp
+        0xC6u8, 0xB7u8, //src port, 50871
+        0x00u8, 0x50u8, //dst port, 80
+        0x00u8, 0x00u8, 0x00u8, 0x01u8, //sequence number, 1
+        0x00u8, 0x00u8, 0x00u8, 0x02u8, //acknowledgement number, 2
+        0x50u8, 0x00u8, //header and flags, 0
+        0x00u8, 0x00u8, //window
+        0x00u8, 0x00u8, //check
+        0x00u8, 0x00u8 //urgent
+    ];
+
+    fn ip(s: &str) -> std::net::IpAddr {
+        s.parse().expect("Could not parse ip address")
+    }
+
+    #[test]
+    fn parse_cidr() {
+        let _ = env_logger::try_init();
+
+        let cidr = "10.1.2.3/8".parse::<Cidr>().expect("Could not parse");
+        assert_eq!(cidr.network(), &ip("10.0.0.0"));
+        assert_eq!(cidr.prefix(), 8);
+        assert_eq!(format!("{}", cidr), "10.0.0.0/8".to_string());
+        assert!(cidr.contains(&ip("10.255.255.255")));
+        assert!(!cidr.contains(&ip("11.0.0.0")));
+
+        assert_eq!("fe80::1".parse::<Cidr>().expect("Could not parse").prefix(), 128);
+        assert!("0.0.0.0/0".parse::<Cidr>().expect("Could not parse").contains(&ip("255.255.255.255")));
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        assert!("10.0.0/8".parse::<Cidr>().is_err());
+    }
+
+    #[test]
+    fn cidr_set() {
+        let _ = env_logger::try_init();
+
+        let networks = (0..=255u32).filter(|n| n % 2 == 0).map(|n| format!("172.{}.0.0/16", n)).collect::<std::vec::Vec<_>>();
+        let mut set = networks.iter().map(|s| s.as_str()).collect::<std::vec::Vec<_>>();
+        set.push("10.0.0.0/9");
+        set.push("10.128.0.0/9");
+        set.push("2001:db8::/32");
+        let set = CidrSet::parse(set).expect("Could not parse");
+
+        assert_eq!(set.v4.len(), 128 + 1);
+        assert!(set.contains(&ip("10.200.1.1")));
+        assert!(set.contains(&ip("172.4.255.255")));
+        assert!(!set.contains(&ip("172.5.0.0")));
+        assert!(!set.contains(&ip("9.255.255.255")));
+        assert!(set.contains(&ip("2001:db8:ffff::1")));
+        assert!(!set.contains(&ip("2001:db9::1")));
+        assert!(!CidrSet::default().contains(&ip("10.0.0.1")));
+    }
+
+    #[test]
+    fn filter_records() {
+        let _ = env_logger::try_init();
+
+        let (_, record) = PcapRecord::parse(RAW_DATA, nom::Endianness::Big).expect("Could not parse");
+        let parsed = ParsedRecord::try_from(record).expect("Could not parse layers");
+
+        assert!(Filter::ip_in("10.0.0.0/8").expect("Could not parse").matches(&parsed));
+        assert!(Filter::ip_in("192.168.0.0/16").expect("Could not parse").matches(&parsed));
+        assert!(!Filter::src_in("192.168.0.0/16").expect("Could not parse").matches(&parsed));
+        assert!(Filter::between_hosts(ip("192.168.0.1"), ip("10.1.2.3")).matches(&parsed));
+        assert!(!Filter::between_hosts(ip("192.168.0.1"), ip("10.1.2.4")).matches(&parsed));
+
+        let filter = Filter::dst_in("192.168.0.0/24").expect("Could not parse")
+            .and(Filter::ip_in_any(vec!["172.16.0.0/12", "10.1.0.0/16"]).expect("Could not parse"));
+        assert!(filter.matches(&parsed));
+        assert!(!(!filter.clone()).matches(&parsed));
+        assert!(!filter.matches(&(ip("192.168.0.1"), ip("8.8.8.8"))));
+        assert!(filter.or(Filter::ip_in("8.8.8.8").expect("Could not parse")).matches(&(ip("192.168.0.1"), ip("8.8.8.8"))));
+
+        let records = vec![(ip("10.0.0.1"), ip("10.0.0.2")), (ip("8.8.8.8"), ip("10.0.0.2"))];
+        let filter = Filter::src_in("10.0.0.0/8").expect("Could not parse");
+        assert_eq!(records.iter().filter(|r| filter.matches(*r)).count(), 1);
+    }
+
+    #[test]
+    fn filter_time_window() {
+        let _ = env_logger::try_init();
+
+        let (_, record) = PcapRecord::parse(RAW_DATA, nom::Endianness::Big).expect("Could not parse");
+        let records = (0..10u64).map(|n| {
+            let mut record = record.clone();
+            record.set_timestamp(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1527868899 + n));
+            ParsedRecord::try_from(record).expect("Could not parse layers")
+        }).collect::<std::vec::Vec<_>>();
+        let reference = *records[5].record().timestamp();
+
+        assert_eq!(records.iter().matching(Filter::around(reference, std::time::Duration::from_secs(2))).count(), 5);
+        assert_eq!(records.iter().matching(Filter::between_times(reference, reference + std::time::Duration::from_secs(60))).count(), 5);
+
+        let filtered = records.iter()
+            .matching(Filter::around(reference, std::time::Duration::from_secs(1)))
+            .matching(Filter::src_in("10.0.0.0/8").expect("Could not parse"))
+            .collect::<std::vec::Vec<_>>();
+        assert_eq!(filtered.len(), 3);
+        assert_eq!(filtered[0].record().timestamp(), records[4].record().timestamp());
+
+        assert!(!Filter::around(reference, std::time::Duration::from_secs(1)).matches(&(ip("10.0.0.1"), ip("10.0.0.2"))));
+        assert!((!Filter::around(reference, std::time::Duration::from_secs(1))).matches(&(ip("10.0.0.1"), ip("10.0.0.2"))));
+    }
+
+    #[test]
+    fn filter_macs() {
+        let _ = env_logger::try_init();
+
+        let (_, record) = PcapRecord::parse(RAW_DATA, nom::Endianness::Big).expect("Could not parse");
+        let parsed = ParsedRecord::try_from(record.clone()).expect("Could not parse layers");
+
+        assert_eq!("ff:fe:fd".parse::<MacPattern>().expect("Could not parse"), MacPattern::Oui([0xFFu8, 0xFEu8, 0xFDu8]));
+        assert!("ff:fe".parse::<MacPattern>().is_err());
+        assert!("ff:fe:fg".parse::<MacPattern>().is_err());
+
+        let src = Filter::src_mac("ff:fe:fd:fc:fb:fa").expect("Could not parse");
+        assert!(src.matches(&record));
+        assert!(src.matches(&parsed));
+        assert!(Filter::dst_mac("01:02:03").expect("Could not parse").matches(&record));
+        assert!(!Filter::src_mac("01:02:03").expect("Could not parse").matches(&record));
+        assert!(Filter::mac("01-02-03-04-05-06").expect("Could not parse").matches(&record));
+        assert!(!Filter::ip_in("10.0.0.0/8").expect("Could not parse").matches(&record));
+
+        let mut payload = record.payload().clone();
+        payload[6] = 0x00u8;
+        let other = PcapRecord::new(*record.timestamp(), record.actual_length(), record.original_length(), payload);
+        let records = vec![record, other];
+        let parsed = records.into_iter()
+            .matching(Filter::src_mac("ff:fe:fd").expect("Could not parse"))
+            .map(ParsedRecord::try_from)
+            .collect::<errors::Result<std::vec::Vec<_>>>()
+            .expect("Could not parse layers");
+        assert_eq!(parsed.len(), 1);
+    }
+}
@@ -1,13 +1,20 @@
 use super::prelude::*;
+use super::layer2::ethernet::VlanTypeId;
+use super::layer3::InternetProtocolId;
+use super::layer3::arp::ArpOperation;
 use super::record::PcapRecord;
+#[cfg(feature = "tunnels")]
+use super::tunnel::TunnelInfo;
 
 use std;
 
 ///
-/// Representation of a device on the network, with the mac, ip, and port involved in a connection
+/// Representation of a device on the network, with the mac, ip, and port involved in a connection.
+/// The mac is absent for packets captured without it, e.g. raw ip.
 ///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Device {
-    pub mac: MacAddress,
+    pub mac: Option<MacAddress>,
     pub ip: std::net::IpAddr,
     pub port: u16
 }
@@ -15,25 +22,247 @@ pub struct Device {
 ///
 /// Representation of a connection or flow between two devices
 ///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Flow {
     pub record: PcapRecord,
     pub source: Device,
     pub destination: Device,
-    pub vlan: Vlan
+    ///
+    /// Ip protocol of the record, tcp or udp, absent for ARP records
+    ///
+    pub protocol: Option<InternetProtocolId>,
+    pub vlan: Vlan,
+    ///
+    /// Tags inside `vlan`, outermost first, e.g. the customer tag of an 802.1ad frame
+    ///
+    pub inner_vlans: std::vec::Vec<Vlan>,
+    ///
+    /// Whether the record was unicast, broadcast or multicast
+    ///
+    pub delivery: Delivery,
+    ///
+    /// Whether the record was reassembled from ip fragments
+    ///
+    pub reassembled: bool,
+    ///
+    /// Tunnel the record's payload was encapsulated in, with its type and id (VNI, TEID or GRE key),
+    /// when the record was decapsulated
+    ///
+    #[cfg(feature = "tunnels")]
+    pub tunnel: Option<TunnelInfo>,
+    ///
+    /// Operation of an ARP record, whose source and destination are the sender and target with port 0
+    ///
+    pub arp: Option<ArpOperation>
+}
+
+///
+/// Vlan tags that distinguish flows, so the same addresses on different vlans (e.g. tenants on a
+/// trunk) can be kept apart or collapsed together
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum VlanKeying {
+    ///
+    /// Ignore vlan tags, traffic on every vlan shares one flow space
+    ///
+    None,
+    ///
+    /// Outermost tag only
+    ///
+    Outer,
+    ///
+    /// Every tag, outermost first, e.g. both tags of a QinQ frame
+    ///
+    Stack,
+    ///
+    /// Service tag then customer tag, chosen by tag type rather than position, so frames with
+    /// further tags or with only a customer tag key on the same fields as 802.1ad frames
+    ///
+    Typed
+}
+
+impl Default for VlanKeying {
+    fn default() -> VlanKeying {
+        VlanKeying::Outer
+    }
+}
+
+///
+/// Headers of tunnelled traffic that flows are keyed on. Without the `tunnels` feature nothing is
+/// decapsulated, so both key on the outer headers.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TunnelKeying {
+    ///
+    /// Addresses of the tunnel endpoints, so all traffic in a tunnel is one flow
+    ///
+    Outer,
+    ///
+    /// Addresses of the innermost decapsulated packet
+    ///
+    Inner
+}
+
+impl Default for TunnelKeying {
+    fn default() -> TunnelKeying {
+        TunnelKeying::Inner
+    }
+}
+
+///
+/// Which vlan tags and tunnel headers distinguish flows
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct FlowKeying {
+    pub vlans: VlanKeying,
+    pub tunnels: TunnelKeying
+}
+
+///
+/// Addressing that identifies the flow a packet belongs to, as seen from the packet's source
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FlowKey {
+    pub src_ip: std::net::IpAddr,
+    pub src_port: u16,
+    pub dst_ip: std::net::IpAddr,
+    pub dst_port: u16,
+    ///
+    /// Ip protocol, so e.g. tcp and udp between the same ports are separate flows, absent for ARP
+    ///
+    pub protocol: Option<InternetProtocolId>,
+    ///
+    /// Vlan keyed on, with only its id, and its tag type when keying on tag types
+    ///
+    pub vlan: Vlan,
+    ///
+    /// Tags inside `vlan`, outermost first, when keying on the full vlan stack
+    ///
+    pub inner_vlans: std::vec::Vec<Vlan>
+}
+
+impl FlowKey {
+    ///
+    /// Key for packets travelling in the opposite direction of the same flow
+    ///
+    pub fn reversed(&self) -> FlowKey {
+        FlowKey {
+            src_ip: self.dst_ip,
+            src_port: self.dst_port,
+            dst_ip: self.src_ip,
+            dst_port: self.src_port,
+            protocol: self.protocol.clone(),
+            vlan: self.vlan,
+            inner_vlans: self.inner_vlans.clone()
+        }
+    }
+
+    ///
+    /// Service (802.1ad) tag keyed on, if any. Only keys on tag types have one.
+    ///
+    pub fn s_vlan(&self) -> Option<Vlan> {
+        typed_vlan(&self.vlan, &self.inner_vlans, VlanTypeId::ProviderBridging)
+    }
+
+    ///
+    /// Customer (802.1Q) tag keyed on, if any. Only keys on tag types have one.
+    ///
+    pub fn c_vlan(&self) -> Option<Vlan> {
+        typed_vlan(&self.vlan, &self.inner_vlans, VlanTypeId::VlanTagId)
+    }
+
+    ///
+    /// Vlan ids keyed on, outermost first
+    ///
+    pub fn vlan_ids(&self) -> std::vec::Vec<u16> {
+        std::iter::once(&self.vlan).chain(self.inner_vlans.iter()).map(|v| v.id()).collect()
+    }
+}
+
+///
+/// First of the tags `vlan` and `inner_vlans` of type `vlan_type`
+///
+fn typed_vlan(vlan: &Vlan, inner_vlans: &[Vlan], vlan_type: VlanTypeId) -> Option<Vlan> {
+    std::iter::once(vlan).chain(inner_vlans.iter())
+        .find(|v| v.tpid() == vlan_type.tpid())
+        .cloned()
+}
+
+///
+/// Vlan and inner vlans a flow key is taken from, of a tag stack starting with `vlan`, according
+/// to `keying`. Only the vlan id of each tag is kept, with its tag type when keying on it, so
+/// priority or drop eligibility changing within a flow doesn't split it.
+///
+pub(crate) fn keyed_vlans(vlan: Vlan, inner_vlans: &[Vlan], keying: VlanKeying) -> (Vlan, std::vec::Vec<Vlan>) {
+    let id_only = |v: &Vlan| Vlan::new(v.id(), 0, false, 0);
+    match keying {
+        VlanKeying::None => (Vlan::untagged(), vec![]),
+        VlanKeying::Outer => (id_only(&vlan), vec![]),
+        VlanKeying::Stack => (id_only(&vlan), inner_vlans.iter().map(id_only).collect()),
+        VlanKeying::Typed => {
+            let typed = |v: Vlan| Vlan::new(v.id(), 0, false, v.tpid());
+            let s_vlan = typed_vlan(&vlan, inner_vlans, VlanTypeId::ProviderBridging).map(typed);
+            let c_vlan = typed_vlan(&vlan, inner_vlans, VlanTypeId::VlanTagId).map(typed);
+            match (s_vlan, c_vlan) {
+                (Some(s_vlan), c_vlan) => (s_vlan, c_vlan.into_iter().collect()),
+                (None, c_vlan) => (c_vlan.unwrap_or(Vlan::untagged()), vec![])
+            }
+        }
+    }
 }
 
 impl Flow {
+    ///
+    /// Key of the flow, on its outermost vlan
+    ///
+    pub fn key(&self) -> FlowKey {
+        self.key_with(VlanKeying::Outer)
+    }
+
+    ///
+    /// Key of the flow, choosing the vlans keyed on according to `keying`
+    ///
+    pub fn key_with(&self, keying: VlanKeying) -> FlowKey {
+        let (vlan, inner_vlans) = keyed_vlans(self.vlan, &self.inner_vlans, keying);
+        FlowKey {
+            src_ip: self.source.ip,
+            src_port: self.source.port,
+            dst_ip: self.destination.ip,
+            dst_port: self.destination.port,
+            protocol: self.protocol.clone(),
+            vlan: vlan,
+            inner_vlans: inner_vlans
+        }
+    }
     pub fn source(&self) -> &Device { &self.source }
     pub fn destination(&self) -> &Device { &self.destination }
     pub fn vlan(&self) -> Vlan { self.vlan }
+
+    ///
+    /// Service (802.1ad) tag of the flow's record, if any
+    ///
+    pub fn s_vlan(&self) -> Option<Vlan> {
+        typed_vlan(&self.vlan, &self.inner_vlans, VlanTypeId::ProviderBridging)
+    }
+
+    ///
+    /// Customer (802.1Q) tag of the flow's record, if any
+    ///
+    pub fn c_vlan(&self) -> Option<Vlan> {
+        typed_vlan(&self.vlan, &self.inner_vlans, VlanTypeId::VlanTagId)
+    }
+
     pub fn record(&self) -> &PcapRecord { &self.record }
     pub unsafe fn packet_data(&mut self) -> *mut u8 { self.record.packet_data() }
 }
 
 impl std::fmt::Display for Device {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "Mac={}   Ip={}   Port={}",
-            self.mac,
+        match self.mac {
+            Some(ref mac) => write!(f, "Mac={}   ", mac)?,
+            None => write!(f, "Mac=-   ")?
+        }
+        write!(f, "Ip={}   Port={}",
             self.ip,
             self.port
         )
@@ -67,11 +296,17 @@ mod tests {
     fn format_device() {
         let dev = Device {
             ip: std::net::IpAddr::V4(std::net::Ipv4Addr::new(0, 1, 2, 3)),
-            mac: MacAddress([0u8, 1u8, 2u8, 3u8, 4u8, 5u8]),
+            mac: Some(MacAddress([0u8, 1u8, 2u8, 3u8, 4u8, 5u8])),
             port: 80
         };
 
         assert_eq!(format!("{}", dev), "Mac=00:01:02:03:04:05   Ip=0.1.2.3   Port=80".to_string());
+
+        let raw = Device {
+            mac: None,
+            ..dev
+        };
+        assert_eq!(format!("{}", raw), "Mac=-   Ip=0.1.2.3   Port=80".to_string());
     }
 
     #[test]
@@ -87,15 +322,22 @@ mod tests {
             record: record,
             source: Device {
                 ip: std::net::IpAddr::V4(std::net::Ipv4Addr::new(0, 1, 2, 3)),
-                mac: MacAddress([0u8, 1u8, 2u8, 3u8, 4u8, 5u8]),
+                mac: Some(MacAddress([0u8, 1u8, 2u8, 3u8, 4u8, 5u8])),
                 port: 80
             },
             destination: Device {
                 ip: std::net::IpAddr::V4(std::net::Ipv4Addr::new(100, 99, 98, 97)),
-                mac: MacAddress([11u8, 10u8, 9u8, 8u8, 7u8, 6u8]),
+                mac: Some(MacAddress([11u8, 10u8, 9u8, 8u8, 7u8, 6u8])),
                 port: 52436
             },
-            vlan: 0
+            protocol: Some(InternetProtocolId::Tcp),
+            vlan: Vlan::untagged(),
+            inner_vlans: vec![],
+            delivery: Delivery::Unicast,
+            reassembled: false,
+            #[cfg(feature = "tunnels")]
+            tunnel: None,
+            arp: None
         };
 
         assert_eq!(format!("{}", flow), "Source=[Mac=00:01:02:03:04:05   Ip=0.1.2.3   Port=80]   Destination=[Mac=0b:0a:09:08:07:06   Ip=100.99.98.97   Port=52436]   Vlan=0   Timestamp=00")
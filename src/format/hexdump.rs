@@ -0,0 +1,264 @@
+use super::prelude::*;
+use super::super::layer2::Layer2;
+use super::super::layer2::ethernet::{EthernetTypeId, VlanTypeId};
+use super::super::layer3::Layer3;
+use super::super::layer4::Layer4;
+use super::super::parsed::{ParseConfig, ParsedRecord};
+use super::super::record::PcapRecord;
+use super::text::link_layer_name;
+
+use std;
+use std::convert::TryFrom;
+use std::fmt::Write;
+
+const BYTES_PER_LINE: usize = 16;
+const MAC_LENGTH: usize = 6;
+const VLAN_TAG_LENGTH: usize = 4;
+const IPV4_HEADER_LENGTH: usize = 20;
+const IPV6_HEADER_LENGTH: usize = 40;
+const TCP_HEADER_LENGTH: usize = 20;
+
+///
+/// Named byte range of a record payload, e.g. the ipv4 ttl
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Field {
+    pub layer: &'static str,
+    pub name: &'static str,
+    pub range: std::ops::Range<usize>
+}
+
+///
+/// Fields laid out one after another from `start`, each with its length
+///
+fn sequence(fields: &mut std::vec::Vec<Field>, layer: &'static str, start: usize, layout: &[(&'static str, usize)]) -> usize {
+    layout.iter().fold(start, |offset, &(name, length)| {
+        fields.push(Field { layer: layer, name: name, range: offset..offset + length });
+        offset + length
+    })
+}
+
+///
+/// Field of the bytes between `start` and `end`, if there are any
+///
+fn remainder(fields: &mut std::vec::Vec<Field>, layer: &'static str, name: &'static str, start: usize, end: usize) {
+    if end > start {
+        fields.push(Field { layer: layer, name: name, range: start..end });
+    }
+}
+
+///
+/// Header fields of each parsed layer, then the payload and any bytes trailing the ip datagram,
+/// located using the record's layer offsets
+///
+pub fn fields(record: &ParsedRecord) -> std::vec::Vec<Field> {
+    let mut fields = vec![];
+    let offsets = record.offsets();
+
+    match *record.layer2() {
+        Layer2::Ethernet(ref ethernet) => {
+            let mut offset = sequence(&mut fields, "ethernet", 0, &[("dst mac", MAC_LENGTH), ("src mac", MAC_LENGTH)]);
+            for tag in ethernet.vlans() {
+                let name = match *tag.vlan_type() {
+                    VlanTypeId::VlanTagId => "802.1q tag",
+                    VlanTypeId::ProviderBridging => "802.1ad tag"
+                };
+                offset = sequence(&mut fields, "ethernet", offset, &[(name, VLAN_TAG_LENGTH)]);
+            }
+            let name = match *ethernet.ether_type() {
+                EthernetTypeId::PayloadLength(_) => "length",
+                _ => "type"
+            };
+            sequence(&mut fields, "ethernet", offset, &[(name, 2)]);
+        }
+        ref other => remainder(&mut fields, link_layer_name(other), "header", 0, other.header_length())
+    }
+
+    match (record.layer3(), offsets.layer3) {
+        (Some(&Layer3::IPv4(ref ipv4)), Some(start)) => {
+            let offset = sequence(&mut fields, "ipv4", start, &[
+                ("version and header length", 1),
+                ("tos", 1),
+                ("total length", 2),
+                ("id", 2),
+                ("flags and fragment offset", 2),
+                ("ttl", 1),
+                ("protocol", 1),
+                ("checksum", 2),
+                ("src ip", 4),
+                ("dst ip", 4)
+            ]);
+            remainder(&mut fields, "ipv4", "options", offset, start + std::cmp::max(ipv4.header_length(), IPV4_HEADER_LENGTH));
+        }
+        #[cfg(feature = "ipv6")]
+        (Some(&Layer3::IPv6(ref ipv6)), Some(start)) => {
+            let offset = sequence(&mut fields, "ipv6", start, &[
+                ("version, traffic class and flow label", 4),
+                ("payload length", 2),
+                ("next header", 1),
+                ("hop limit", 1),
+                ("src ip", 16),
+                ("dst ip", 16)
+            ]);
+            remainder(&mut fields, "ipv6", "extension headers", offset, start + std::cmp::max(ipv6.header_length(), IPV6_HEADER_LENGTH));
+        }
+        _ => {}
+    }
+
+    match (record.layer4(), offsets.layer4) {
+        (Some(&Layer4::Tcp(_)), Some(start)) => {
+            let offset = sequence(&mut fields, "tcp", start, &[
+                ("src port", 2),
+                ("dst port", 2),
+                ("sequence number", 4),
+                ("acknowledgement number", 4),
+                ("data offset and flags", 2),
+                ("window", 2),
+                ("checksum", 2),
+                ("urgent pointer", 2)
+            ]);
+            remainder(&mut fields, "tcp", "options", offset, std::cmp::max(offsets.payload, start + TCP_HEADER_LENGTH));
+        }
+        (Some(&Layer4::Udp(_)), Some(start)) => {
+            sequence(&mut fields, "udp", start, &[("src port", 2), ("dst port", 2), ("length", 2), ("checksum", 2)]);
+        }
+        _ => {}
+    }
+
+    remainder(&mut fields, "payload", "data", offsets.payload, offsets.end);
+    remainder(&mut fields, "trailer", "padding or trailing bytes", offsets.end, record.record().payload().len());
+    fields
+}
+
+///
+/// Bytes of each field as hex, sixteen to a line, annotated with the field on its first line
+///
+pub fn write_fields<W: Write>(out: &mut W, bytes: &[u8], fields: &[Field]) -> std::fmt::Result {
+    for field in fields {
+        let end = std::cmp::min(field.range.end, bytes.len());
+        let start = std::cmp::min(field.range.start, end);
+        for (idx, line) in bytes[start..end].chunks(BYTES_PER_LINE).enumerate() {
+            let hex = line.iter().map(|b| format!("{:02x}", b)).collect::<std::vec::Vec<_>>().join(" ");
+            if idx == 0 {
+                writeln!(out, "{:04x}  {:<47}  {} {}", start, hex, field.layer, field.name)?;
+            } else {
+                writeln!(out, "{:04x}  {}", start + idx * BYTES_PER_LINE, hex)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+///
+/// Annotated hexdump of a record's payload, one line per header field, like Wireshark's bytes
+/// pane. A record that fails to parse is dumped up to and from the offset it failed at, after the
+/// error.
+///
+pub fn write_hexdump<W: Write>(out: &mut W, record: &PcapRecord) -> std::fmt::Result {
+    write_hexdump_with(out, record, &ParseConfig::default())
+}
+
+///
+/// Annotated hexdump of a record parsed according to `config`, e.g. with the link type of its
+/// capture
+///
+pub fn write_hexdump_with<W: Write>(out: &mut W, record: &PcapRecord, config: &ParseConfig) -> std::fmt::Result {
+    let bytes = &record.payload()[..];
+    match ParsedRecord::parse_with(record.clone(), config) {
+        Ok(parsed) => write_fields(out, bytes, &fields(&parsed)),
+        Err(e) => {
+            writeln!(out, "error: {}", e)?;
+            let offset = match *e.kind() {
+                errors::ErrorKind::Parse(ref context) => std::cmp::min(context.offset, bytes.len()),
+                _ => 0
+            };
+            let mut fields = vec![];
+            remainder(&mut fields, "unparsed", "before error", 0, offset);
+            remainder(&mut fields, "unparsed", "from error", offset, bytes.len());
+            write_fields(out, bytes, &fields)
+        }
+    }
+}
+
+pub fn hexdump(record: &PcapRecord) -> String {
+    let mut out = String::new();
+    write_hexdump(&mut out, record).expect("Formatting to a string failed");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    const RAW_DATA: &'static [u8] = &[
+        0x5Bu8, 0x11u8, 0x6Du8, 0xE3u8, //seconds, 1527868899
+        0x00u8, 0x02u8, 0x51u8, 0xF5u8, //microseconds, 152053
+        0x00u8, 0x00u8, 0x00u8, 0x3Cu8, //actual length, 60: 14 (ethernet) + 20 (ipv4 header) + 8 (udp header) + 14 (udp payload) + 4 (padding)
+        0x00u8, 0x00u8, 0x00u8, 0x3Cu8, //original length, 60
+        //ethernet
+        0x01u8, 0x02u8, 0x03u8, 0x04u8, 0x05u8, 0x06u8, //dst mac 01:02:03:04:05:06
+        0xFFu8, 0xFEu8, 0xFDu8, 0xFCu8, 0xFBu8, 0xFAu8, //src mac FF:FE:FD:FC:FB:FA
+        0x08u8, 0x00u8, //ipv4
+        //ipv4
+        0x45u8, //version and header length
+        0x00u8, //tos
+        0x00u8, 0x2Au8, //length, 20 bytes for header, 22 bytes for udp
+        0x00u8, 0x00u8, //id
+        0x00u8, 0x00u8, //flags
+        0x40u8, //ttl
+        0x11u8, //protocol, udp
+        0x00u8, 0x00u8, //checksum
+        0x01u8, 0x02u8, 0x03u8, 0x04u8, //src ip 1.2.3.4
+        0x0Au8, 0x0Bu8, 0x0Cu8, 0x0Du8, //dst ip 10.11.12.13
+        //udp
+        0xC6u8, 0xB7u8, //src port, 50871
+        0x00u8, 0x35u8, //dst port, 53
+        0x00u8, 0x16u8, //length, 22
+        0x00u8, 0x00u8, //checksum
+        //payload
+        0x00u8, 0x01u8, 0x02u8, 0x03u8, 0x04u8, 0x05u8, 0x06u8, 0x07u8, 0x08u8, 0x09u8, 0x0Au8, 0x0Bu8, 0x0Cu8, 0x0Du8,
+        //padding
+        0x00u8, 0x00u8, 0x00u8, 0x00u8
+    ];
+
+    #[test]
+    fn hexdump_fields() {
+        let _ = env_logger::try_init();
+
+        let (_, record) = PcapRecord::parse(RAW_DATA, nom::Endianness::Big).expect("Could not parse");
+        let parsed = ParsedRecord::try_from(record.clone()).expect("Could not parse layers");
+        let fields = fields(&parsed);
+
+        assert_eq!(fields.len(), 3 + 10 + 4 + 2);
+        assert_eq!(fields[0], Field { layer: "ethernet", name: "dst mac", range: 0..6 });
+        assert_eq!(fields[8], Field { layer: "ipv4", name: "ttl", range: 22..23 });
+        assert_eq!(fields[13], Field { layer: "udp", name: "src port", range: 34..36 });
+        assert_eq!(fields[17], Field { layer: "payload", name: "data", range: 42..56 });
+        assert_eq!(fields[18], Field { layer: "trailer", name: "padding or trailing bytes", range: 56..60 });
+
+        let dump = hexdump(&record);
+        let lines = dump.lines().collect::<std::vec::Vec<_>>();
+        assert_eq!(lines.len(), fields.len());
+        assert_eq!(lines[0], "0000  01 02 03 04 05 06                                ethernet dst mac");
+        assert_eq!(lines[8], "0016  40                                               ipv4 ttl");
+    }
+
+    #[test]
+    fn hexdump_malformed() {
+        let _ = env_logger::try_init();
+
+        let mut data = RAW_DATA.to_vec();
+        data[30] = 0x75u8; //ipv4 version 7
+
+        let (_, record) = PcapRecord::parse(&data, nom::Endianness::Big).expect("Could not parse");
+        let dump = hexdump(&record);
+        let lines = dump.lines().collect::<std::vec::Vec<_>>();
+
+        assert!(lines[0].starts_with("error: "));
+        assert_eq!(lines[1], "0000  01 02 03 04 05 06 ff fe fd fc fb fa 08 00        unparsed before error");
+        assert_eq!(lines[2], "000e  75 00 00 2a 00 00 00 00 40 11 00 00 01 02 03 04  unparsed from error");
+        assert_eq!(lines.len(), 5);
+    }
+}
@@ -0,0 +1,329 @@
+use super::prelude::*;
+use super::super::layer3::Layer3;
+use super::super::layer4::Layer4;
+use super::super::layer7::detect;
+use super::super::global_header::GlobalHeader;
+use super::super::parsed::{ParseConfig, ParsedRecord};
+use super::super::reader::CaptureReader;
+use super::super::record::PcapRecord;
+use super::text::{ether_type_name, tcp_flags};
+
+use std;
+use std::convert::TryFrom;
+use std::fmt::Write;
+
+///
+/// Quoted json string, escaping quotes, backslashes and control characters
+///
+fn write_string<W: Write>(out: &mut W, value: &str) -> std::fmt::Result {
+    out.write_char('"')?;
+    for c in value.chars() {
+        match c {
+            '"' => out.write_str("\\\"")?,
+            '\\' => out.write_str("\\\\")?,
+            '\n' => out.write_str("\\n")?,
+            '\r' => out.write_str("\\r")?,
+            '\t' => out.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32)?,
+            c => out.write_char(c)?
+        }
+    }
+    out.write_char('"')
+}
+
+///
+/// Field whose value is written with its Display implementation as a json string, or null
+///
+fn write_display<W: Write, T: std::fmt::Display>(out: &mut W, name: &str, value: Option<T>) -> std::fmt::Result {
+    write!(out, ",\"{}\":", name)?;
+    match value {
+        Some(value) => write_string(out, &value.to_string()),
+        None => out.write_str("null")
+    }
+}
+
+///
+/// Field whose value is a json number, or null
+///
+fn write_number<W: Write, T: std::fmt::Display>(out: &mut W, name: &str, value: Option<T>) -> std::fmt::Result {
+    match value {
+        Some(value) => write!(out, ",\"{}\":{}", name, value),
+        None => write!(out, ",\"{}\":null", name)
+    }
+}
+
+///
+/// Timestamp and lengths of a record, which begin every line
+///
+fn write_record_fields<W: Write>(out: &mut W, record: &PcapRecord) -> std::fmt::Result {
+    let since_epoch = record.timestamp().duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+    write!(out, "{{\"timestamp\":{}.{:06},\"captured_length\":{},\"original_length\":{}",
+           since_epoch.as_secs(),
+           since_epoch.subsec_micros(),
+           record.actual_length(),
+           record.original_length()
+    )
+}
+
+///
+/// Metadata of a parsed record as a single line json object: timestamp, lengths, macs, vlan ids,
+/// ethernet type, ip addresses and protocol, ports, tcp flags and the application protocol detected
+/// in tcp payloads. Fields of layers the record doesn't have are null, so every line has the same
+/// fields.
+///
+pub fn write_json<W: Write>(out: &mut W, record: &ParsedRecord) -> std::fmt::Result {
+    write_record_fields(out, record.record())?;
+
+    write_display(out, "src_mac", record.layer2().src_mac())?;
+    write_display(out, "dst_mac", record.layer2().dst_mac())?;
+    let vlans = record.ethernet()
+        .map(|e| e.vlans().iter().map(|t| t.vlan().id().to_string()).collect::<std::vec::Vec<_>>())
+        .unwrap_or_default();
+    write!(out, ",\"vlans\":[{}]", vlans.join(","))?;
+    write_display(out, "ether_type", record.layer2().ether_type().map(|t| ether_type_name(&t).0))?;
+
+    let protocol = match record.layer3() {
+        Some(&Layer3::IPv4(ref ipv4)) => Some(ipv4.protocol().value()),
+        #[cfg(feature = "ipv6")]
+        Some(&Layer3::IPv6(ref ipv6)) => Some(ipv6.protocol().value()),
+        None => None
+    };
+    write_display(out, "src_ip", record.src_ip())?;
+    write_display(out, "dst_ip", record.dst_ip())?;
+    write_number(out, "ip_protocol", protocol)?;
+    write_number(out, "src_port", record.src_port())?;
+    write_number(out, "dst_port", record.dst_port())?;
+
+    let tcp = match record.layer4() {
+        Some(&Layer4::Tcp(ref tcp)) => Some(tcp),
+        _ => None
+    };
+    write_display(out, "tcp_flags", tcp.map(tcp_flags))?;
+    write_display(out, "application", tcp.and_then(|t| detect::detect(t.payload())))?;
+    write_number(out, "anomalies", Some(record.anomalies().len()))?;
+    out.write_char('}')
+}
+
+pub fn to_json(record: &ParsedRecord) -> String {
+    let mut out = String::new();
+    write_json(&mut out, record).expect("Formatting to a string failed");
+    out
+}
+
+///
+/// Writes one json object per line (ndjson) for each record as it is given, so captures can be
+/// streamed into tools like jq without being held in memory. Records that fail to parse are written
+/// with their timestamp, lengths and the error. Writes are unbuffered; wrap the writer in a
+/// `BufWriter` when writing to a file or socket.
+///
+pub struct JsonExporter<W> {
+    writer: W,
+    config: ParseConfig,
+    records: usize,
+    errors: usize
+}
+
+impl<W: std::io::Write> JsonExporter<W> {
+    ///
+    /// Exporter of records of ethernet captures
+    ///
+    pub fn new(writer: W) -> JsonExporter<W> {
+        JsonExporter::with_config(writer, ParseConfig::default())
+    }
+
+    ///
+    /// Exporter of records of the capture with global header `header`, parsed with the layer 2
+    /// parser of its link type
+    ///
+    pub fn for_header(writer: W, header: &GlobalHeader) -> JsonExporter<W> {
+        JsonExporter::with_config(writer, ParseConfig::for_header(header))
+    }
+
+    pub fn with_config(writer: W, config: ParseConfig) -> JsonExporter<W> {
+        JsonExporter {
+            writer: writer,
+            config: config,
+            records: 0,
+            errors: 0
+        }
+    }
+
+    ///
+    /// Lines written so far, and how many of them were for records that failed to parse
+    ///
+    pub fn records(&self) -> usize { self.records }
+    pub fn errors(&self) -> usize { self.errors }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_line(&mut self, line: &str) -> errors::Result<()> {
+        self.writer.write_all(line.as_bytes())?;
+        self.writer.write_all(b"\n")?;
+        self.records += 1;
+        Ok(())
+    }
+
+    pub fn write_parsed(&mut self, record: &ParsedRecord) -> errors::Result<()> {
+        let line = to_json(record);
+        self.write_line(&line)
+    }
+
+    pub fn write_record(&mut self, record: &PcapRecord) -> errors::Result<()> {
+        match ParsedRecord::parse_with(record.clone(), &self.config) {
+            Ok(parsed) => self.write_parsed(&parsed),
+            Err(e) => {
+                let mut line = String::new();
+                write_record_fields(&mut line, record)
+                    .and_then(|_| write_display(&mut line, "error", Some(e)))
+                    .and_then(|_| line.write_char('}'))
+                    .expect("Formatting to a string failed");
+                self.errors += 1;
+                self.write_line(&line)
+            }
+        }
+    }
+
+    ///
+    /// Write a line for each record read from `reader` until the capture ends, returning the
+    /// number of lines written. Records are parsed with the layer 2 parser of the reader's link
+    /// type, which later records written are parsed with too.
+    ///
+    pub fn export<R: std::io::Read>(&mut self, reader: CaptureReader<R>) -> errors::Result<usize> {
+        self.config.link_type = reader.header().link_type();
+        self.config.endianness = reader.header().endianness();
+        let start = self.records;
+        for record in reader {
+            self.write_record(&record?)?;
+        }
+        Ok(self.records - start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    const RAW_DATA: &'static [u8] = &[
+        0x5Bu8, 0x11u8, 0x6Du8, 0xE3u8, //seconds, 1527868899
+        0x00u8, 0x02u8, 0x51u8, 0xF5u8, //microseconds, 152053
+        0x00u8, 0x00u8, 0x00u8, 0x3Eu8, //actual length, 62: 14 (ethernet) + 4 (vlan) + 20 (ipv4 header) + 20 (tcp header) + 4 (tcp payload)
+        0x00u8, 0x00u8, 0x00u8, 0x3Eu8, //original length, 62
+        //ethernet
+        0x01u8, 0x02u8, 0x03u8, 0x04u8, 0x05u8, 0x06u8, //dst mac 01:02:03:04:05:06
+        0xFFu8, 0xFEu8, 0xFDu8, 0xFCu8, 0xFBu8, 0xFAu8, //src mac FF:FE:FD:FC:FB:FA
+        0x81u8, 0x00u8, //802.1q
+        0x20u8, 0x64u8, //priority 1, vlan 100
+        0x08u8, 0x00u8, //ipv4
+        //ipv4
+        0x45u8, //version and header length
+        0x00u8, //tos
+        0x00u8, 0x2Cu8, //length, 20 bytes for header, 24 bytes for tcp
+        0x00u8, 0x07u8, //id
+        0x40u8, 0x00u8, //flags, don't fragment
+        0x40u8, //ttl
+        0x06u8, //protocol, tcp
+        0x00u8, 0x00u8, //checksum
+        0x01u8, 0x02u8, 0x03u8, 0x04u8, //src ip 1.2.3.4
+        0x0Au8, 0x0Bu8, 0x0Cu8, 0x0Du8, //dst ip 10.11.12.13
+        //tcp
+        0xC6u8, 0xB7u8, //src port, 50871
+        0x00u8, 0x50u8, //dst port, 80
+        0x00u8, 0x00u8, 0x00u8, 0x01u8, //sequence number, 1
+        0x00u8, 0x00u8, 0x00u8, 0x02u8, //acknowledgement number, 2
+        0x50u8, 0x18u8, //header and flags, psh ack
+        0x00u8, 0x00u8, //window
+        0x00u8, 0x00u8, //check
+        0x00u8, 0x00u8, //urgent
+        //no options
+        //payload
+        b'G', b'E', b'T', b' '
+    ];
+
+    #[test]
+    fn json_record() {
+        let _ = env_logger::try_init();
+
+        let (_, record) = PcapRecord::parse(RAW_DATA, nom::Endianness::Big).expect("Could not parse");
+        let parsed = ParsedRecord::try_from(record).expect("Could not parse layers");
+
+        assert_eq!(to_json(&parsed), concat!(
+            "{\"timestamp\":1527868899.152053,\"captured_length\":62,\"original_length\":62,",
+            "\"src_mac\":\"ff:fe:fd:fc:fb:fa\",\"dst_mac\":\"01:02:03:04:05:06\",\"vlans\":[100],\"ether_type\":\"IPv4\",",
+            "\"src_ip\":\"1.2.3.4\",\"dst_ip\":\"10.11.12.13\",\"ip_protocol\":6,\"src_port\":50871,\"dst_port\":80,",
+            "\"tcp_flags\":\"P.\",\"application\":\"HTTP\",\"anomalies\":0}"
+        ));
+    }
+
+    #[test]
+    fn json_export() {
+        let _ = env_logger::try_init();
+
+        let mut capture = vec![
+            0xA1u8, 0xB2u8, 0xC3u8, 0xD4u8, //magic number
+            0x00u8, 0x02u8, //version major, 2
+            0x00u8, 0x04u8, //version minor, 4
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, //zone, 0
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, //sig figs, 0
+            0x00u8, 0x00u8, 0x05u8, 0xDCu8, //snap length, 1500
+            0x00u8, 0x00u8, 0x00u8, 0x01u8 //network, ethernet
+        ];
+        capture.extend_from_slice(RAW_DATA);
+        let mut malformed = RAW_DATA.to_vec();
+        malformed[16 + 18] = 0x75u8; //ipv4 version 7
+        capture.extend_from_slice(&malformed);
+
+        let reader = CaptureReader::new(&capture[..]).expect("Could not read header");
+        let mut exporter = JsonExporter::new(vec![]);
+        assert_eq!(exporter.export(reader).expect("Could not export"), 2);
+        assert_eq!(exporter.errors(), 1);
+
+        let output = String::from_utf8(exporter.into_inner()).expect("Not utf8");
+        let lines = output.lines().collect::<std::vec::Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with("\"anomalies\":0}"));
+        assert!(lines[1].starts_with("{\"timestamp\":1527868899.152053,\"captured_length\":62,\"original_length\":62,\"error\":\""));
+    }
+
+    #[test]
+    fn json_export_raw_ip() {
+        let _ = env_logger::try_init();
+
+        let mut capture = vec![
+            0xA1u8, 0xB2u8, 0xC3u8, 0xD4u8, //magic number
+            0x00u8, 0x02u8, //version major, 2
+            0x00u8, 0x04u8, //version minor, 4
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, //zone, 0
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, //sig figs, 0
+            0x00u8, 0x00u8, 0x05u8, 0xDCu8, //snap length, 1500
+            0x00u8, 0x00u8, 0x00u8, 0x65u8 //network, raw ip
+        ];
+        capture.extend_from_slice(&RAW_DATA[0..8]); //timestamp
+        capture.extend_from_slice(&[0x00u8, 0x00u8, 0x00u8, 0x2Cu8]); //actual length, 44
+        capture.extend_from_slice(&[0x00u8, 0x00u8, 0x00u8, 0x2Cu8]); //original length, 44
+        capture.extend_from_slice(&RAW_DATA[34..]); //ipv4 datagram
+
+        let reader = CaptureReader::new(&capture[..]).expect("Could not read header");
+        let mut exporter = JsonExporter::new(vec![]);
+        assert_eq!(exporter.export(reader).expect("Could not export"), 1);
+        assert_eq!(exporter.errors(), 0);
+
+        let output = String::from_utf8(exporter.into_inner()).expect("Not utf8");
+        assert_eq!(output, concat!(
+            "{\"timestamp\":1527868899.152053,\"captured_length\":44,\"original_length\":44,",
+            "\"src_mac\":null,\"dst_mac\":null,\"vlans\":[],\"ether_type\":\"IPv4\",",
+            "\"src_ip\":\"1.2.3.4\",\"dst_ip\":\"10.11.12.13\",\"ip_protocol\":6,\"src_port\":50871,\"dst_port\":80,",
+            "\"tcp_flags\":\"P.\",\"application\":\"HTTP\",\"anomalies\":0}\n"
+        ));
+    }
+
+    #[test]
+    fn json_escape() {
+        let mut out = String::new();
+        write_string(&mut out, "a \"b\"\\\n\u{1}").expect("Could not write");
+        assert_eq!(out, "\"a \\\"b\\\"\\\\\\n\\u0001\"");
+    }
+}
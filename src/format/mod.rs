@@ -0,0 +1,7 @@
+pub mod prelude {
+    pub use super::super::prelude::*;
+}
+
+pub mod hexdump;
+pub mod json;
+pub mod text;
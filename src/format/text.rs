@@ -0,0 +1,430 @@
+use super::prelude::*;
+use super::super::layer2::Layer2;
+use super::super::layer2::ethernet::{EthernetTypeId, Layer3Id, VlanTypeId};
+use super::super::layer3::{InternetProtocolId, Layer3};
+use super::super::layer3::arp::{Arp, ArpOperation};
+use super::super::layer3::ipv4::IPv4;
+#[cfg(feature = "ipv6")]
+use super::super::layer3::ipv6::IPv6;
+use super::super::layer4::Layer4;
+use super::super::layer4::tcp::{self, Tcp};
+use super::super::layer7::detect;
+use super::super::parsed::{Anomaly, ParsedRecord};
+
+use std;
+use std::fmt::Write;
+
+///
+/// Tcp flags in the order tcpdump prints them, with acknowledgement as '.'
+///
+const TCP_FLAGS: &'static [(u16, char)] = &[
+    (tcp::FLAG_FIN, 'F'),
+    (tcp::FLAG_SYN, 'S'),
+    (tcp::FLAG_RST, 'R'),
+    (tcp::FLAG_PSH, 'P'),
+    (tcp::FLAG_ACK, '.'),
+    (tcp::FLAG_URG, 'U'),
+    (tcp::FLAG_ECE, 'E'),
+    (tcp::FLAG_CWR, 'W')
+];
+
+const SECONDS_PER_DAY: u64 = 86_400;
+const CONTINUATION_INDENT: &'static str = "    ";
+
+///
+/// Detail levels matching tcpdump's default, `-v` and `-vv` output
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Verbosity {
+    Normal,
+    ///
+    /// Ip header fields, e.g. ttl, id and fragmentation, on the line before the addresses
+    ///
+    Verbose,
+    ///
+    /// Additionally, the application protocol detected in tcp payloads and any anomalies found
+    /// while parsing, each on a line of its own
+    ///
+    VeryVerbose
+}
+
+impl Default for Verbosity {
+    fn default() -> Self { Verbosity::Normal }
+}
+
+///
+/// Formats parsed records as tcpdump style text, one record per line (or several, at higher
+/// verbosity). Timestamps are time of day in UTC.
+///
+#[derive(Clone, Debug, Default)]
+pub struct TextFormatter {
+    verbosity: Verbosity,
+    link_level: bool
+}
+
+impl TextFormatter {
+    pub fn with_verbosity(verbosity: Verbosity) -> TextFormatter {
+        TextFormatter {
+            verbosity: verbosity,
+            link_level: false
+        }
+    }
+
+    ///
+    /// Include mac addresses, vlan tags and ethernet types, as tcpdump's `-e`
+    ///
+    pub fn link_level(mut self, link_level: bool) -> TextFormatter {
+        self.link_level = link_level;
+        self
+    }
+
+    pub fn verbosity(&self) -> Verbosity { self.verbosity }
+
+    pub fn format(&self, record: &ParsedRecord) -> String {
+        let mut out = String::new();
+        self.write(&mut out, record).expect("Formatting to a string failed");
+        out
+    }
+
+    pub fn write<W: Write>(&self, out: &mut W, record: &ParsedRecord) -> std::fmt::Result {
+        write_timestamp(out, record.record().timestamp())?;
+        out.write_char(' ')?;
+
+        let length = record.record().original_length();
+        if self.link_level {
+            write_link_level(out, record.layer2(), length)?;
+        }
+
+        match record.layer3() {
+            Some(&Layer3::IPv4(ref ipv4)) => {
+                if !self.link_level {
+                    out.write_str("IP ")?;
+                }
+                if self.verbosity >= Verbosity::Verbose {
+                    write_ipv4_detail(out, ipv4)?;
+                }
+                write_ip_body(out, ipv4.src_ip(), ipv4.dst_ip(), ipv4.protocol(), ipv4.payload().len(), record.layer4())?;
+            }
+            #[cfg(feature = "ipv6")]
+            Some(&Layer3::IPv6(ref ipv6)) => {
+                if !self.link_level {
+                    out.write_str("IP6 ")?;
+                }
+                if self.verbosity >= Verbosity::Verbose {
+                    write_ipv6_detail(out, ipv6)?;
+                }
+                write_ip_body(out, ipv6.src_ip(), ipv6.dst_ip(), ipv6.protocol(), ipv6.payload().len(), record.layer4())?;
+            }
+            None => write_non_ip(out, record.layer2(), self.link_level)?
+        }
+
+        if self.verbosity >= Verbosity::VeryVerbose {
+            if let Some(&Layer4::Tcp(ref tcp)) = record.layer4() {
+                if let Some(protocol) = detect::detect(tcp.payload()) {
+                    write!(out, "\n{}{}", CONTINUATION_INDENT, protocol)?;
+                }
+            }
+            for anomaly in record.anomalies() {
+                write!(out, "\n{}", CONTINUATION_INDENT)?;
+                write_anomaly(out, anomaly)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn write_timestamp<W: Write>(out: &mut W, timestamp: &std::time::SystemTime) -> std::fmt::Result {
+    let since_epoch = timestamp.duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs() % SECONDS_PER_DAY;
+    write!(out, "{:02}:{:02}:{:02}.{:06}", seconds / 3600, seconds / 60 % 60, seconds % 60, since_epoch.subsec_micros())
+}
+
+pub(super) fn ether_type_name(ether_type: &EthernetTypeId) -> (&'static str, u16) {
+    match *ether_type {
+        EthernetTypeId::L3(Layer3Id::IPv4) => ("IPv4", 0x0800u16),
+        EthernetTypeId::L3(Layer3Id::IPv6) => ("IPv6", 0x86ddu16),
+        EthernetTypeId::L3(Layer3Id::Arp) => ("ARP", 0x0806u16),
+        EthernetTypeId::L3(Layer3Id::Lldp) => ("LLDP", 0x88ccu16),
+        EthernetTypeId::L3(Layer3Id::PppoeDiscovery) => ("PPPoE D", 0x8863u16),
+        EthernetTypeId::L3(Layer3Id::PppoeSession) => ("PPPoE S", 0x8864u16),
+        EthernetTypeId::L3(Layer3Id::WakeOnLan) => ("WOL", 0x0842u16),
+        EthernetTypeId::Vlan(VlanTypeId::VlanTagId) => ("802.1Q", 0x8100u16),
+        EthernetTypeId::Vlan(VlanTypeId::ProviderBridging) => ("802.1Q-QinQ", 0x88a8u16),
+        EthernetTypeId::PayloadLength(length) => ("802.3", length),
+        EthernetTypeId::JumboLlc => ("Jumbo LLC", 0x8870u16)
+    }
+}
+
+///
+/// Name of a link layer, for records without an ethernet type to name them by
+///
+pub(super) fn link_layer_name(layer2: &Layer2) -> &'static str {
+    match *layer2 {
+        Layer2::Ethernet(_) => "ethernet",
+        Layer2::Sll(_) => "sll",
+        Layer2::Sll2(_) => "sll2",
+        Layer2::Null(_) => "null",
+        Layer2::Ppp(_) => "ppp",
+        Layer2::PppEther(_, _) => "pppoe",
+        Layer2::Ieee80211(_) => "802.11",
+        Layer2::Raw(_) => "raw"
+    }
+}
+
+///
+/// Mac addresses and ethernet type, then each vlan tag with the ethernet type it encapsulates.
+/// Link layers other than ethernet have only the addresses they carry, e.g. the source of linux
+/// cooked packets.
+///
+fn write_link_level<W: Write>(out: &mut W, layer2: &Layer2, length: u32) -> std::fmt::Result {
+    let ethernet = match *layer2 {
+        Layer2::Ethernet(ref ethernet) => ethernet,
+        ref other => {
+            match (other.src_mac(), other.dst_mac()) {
+                (Some(src), Some(dst)) => write!(out, "{} > {}, ", src, dst)?,
+                (Some(src), None) => write!(out, "{}, ", src)?,
+                _ => {}
+            }
+            return match other.ether_type() {
+                Some(ether_type) => {
+                    let (name, value) = ether_type_name(&ether_type);
+                    write!(out, "ethertype {} (0x{:04x}), length {}: ", name, value, length)
+                }
+                None => write!(out, "{}, length {}: ", link_layer_name(other), length)
+            }
+        }
+    };
+    write!(out, "{} > {}, ", ethernet.src_mac(), ethernet.dst_mac())?;
+
+    let mut ether_types = ethernet.vlans().iter().map(|t| EthernetTypeId::Vlan(t.vlan_type().clone()))
+        .chain(std::iter::once(ethernet.ether_type().clone()));
+    let (name, value) = ether_type_name(&ether_types.next().expect("Ethernet type missing"));
+    write!(out, "ethertype {} (0x{:04x}), length {}: ", name, value, length)?;
+
+    for (tag, ether_type) in ethernet.vlans().iter().zip(ether_types) {
+        let (name, value) = ether_type_name(&ether_type);
+        write!(out, "vlan {}, p {}, ethertype {} (0x{:04x}), ", tag.vlan().id(), tag.vlan().pcp(), name, value)?;
+    }
+    Ok(())
+}
+
+fn protocol_name(protocol: &InternetProtocolId) -> &'static str {
+    match *protocol {
+        InternetProtocolId::Tcp => "TCP",
+        InternetProtocolId::Udp => "UDP",
+        InternetProtocolId::Gre => "GRE",
+        InternetProtocolId::Icmp => "ICMP",
+        InternetProtocolId::Icmpv6 => "ICMPv6",
+        InternetProtocolId::AuthenticationHeader => "AH",
+        InternetProtocolId::EncapsulatingSecurityPayload => "ESP",
+        InternetProtocolId::HopByHop => "Options",
+        InternetProtocolId::IPv6Route => "Routing",
+        InternetProtocolId::IPv6Fragment => "Fragment",
+        InternetProtocolId::IPv6NoNext => "NoNext",
+        InternetProtocolId::IPv6Options => "DstOptions"
+    }
+}
+
+fn write_ipv4_detail<W: Write>(out: &mut W, ipv4: &IPv4) -> std::fmt::Result {
+    let flags = match (ipv4.dont_fragment(), ipv4.more_fragments()) {
+        (true, true) => "DF+",
+        (true, false) => "DF",
+        (false, true) => "+",
+        (false, false) => "none"
+    };
+    write!(out, "(tos 0x{:x}, ttl {}, id {}, offset {}, flags [{}], proto {} ({}), length {})\n{}",
+           ipv4.tos(),
+           ipv4.ttl(),
+           ipv4.id(),
+           ipv4.fragment_offset(),
+           flags,
+           protocol_name(ipv4.protocol()),
+           ipv4.protocol().value(),
+           ipv4.header_length() + ipv4.payload().len(),
+           CONTINUATION_INDENT
+    )
+}
+
+#[cfg(feature = "ipv6")]
+fn write_ipv6_detail<W: Write>(out: &mut W, ipv6: &IPv6) -> std::fmt::Result {
+    write!(out, "(next-header {} ({}) payload length: {})\n{}",
+           protocol_name(ipv6.protocol()),
+           ipv6.protocol().value(),
+           ipv6.payload().len(),
+           CONTINUATION_INDENT
+    )
+}
+
+///
+/// Addresses, with ports for tcp and udp, then the layer 4 summary
+///
+fn write_ip_body<W: Write>(
+    out: &mut W,
+    src_ip: &std::net::IpAddr,
+    dst_ip: &std::net::IpAddr,
+    protocol: &InternetProtocolId,
+    length: usize,
+    layer4: Option<&Layer4>
+) -> std::fmt::Result {
+    match layer4 {
+        Some(&Layer4::Tcp(ref tcp)) => {
+            write!(out, "{}.{} > {}.{}: ", src_ip, tcp.src_port(), dst_ip, tcp.dst_port())?;
+            write_tcp(out, tcp)
+        }
+        Some(&Layer4::Udp(ref udp)) => {
+            write!(out, "{}.{} > {}.{}: UDP, length {}", src_ip, udp.src_port(), dst_ip, udp.dst_port(), udp.payload().len())
+        }
+        None => write!(out, "{} > {}: ip-proto-{} {}", src_ip, dst_ip, protocol.value(), length)
+    }
+}
+
+///
+/// Flags set on a tcp segment as tcpdump prints them, e.g. "S." for a syn ack
+///
+pub(super) fn tcp_flags(tcp: &Tcp) -> String {
+    TCP_FLAGS.iter().filter(|&&(f, _)| tcp.has_flags(f)).map(|&(_, c)| c).collect()
+}
+
+fn write_tcp<W: Write>(out: &mut W, tcp: &Tcp) -> std::fmt::Result {
+    let flags = tcp_flags(tcp);
+    write!(out, "Flags [{}]", if flags.is_empty() { "none" } else { &flags })?;
+
+    let length = tcp.payload().len();
+    if length > 0 {
+        write!(out, ", seq {}:{}", tcp.sequence_number(), tcp.sequence_number().wrapping_add(length as u32))?;
+    } else if tcp.flags() & (tcp::FLAG_SYN | tcp::FLAG_FIN | tcp::FLAG_RST) != 0 {
+        write!(out, ", seq {}", tcp.sequence_number())?;
+    }
+    if tcp.has_flags(tcp::FLAG_ACK) {
+        write!(out, ", ack {}", tcp.acknowledgement_number())?;
+    }
+    write!(out, ", length {}", length)
+}
+
+///
+/// Arp is parsed from the ethernet payload here, as records don't carry it as a layer
+///
+fn write_non_ip<W: Write>(out: &mut W, layer2: &Layer2, link_level: bool) -> std::fmt::Result {
+    let length = layer2.payload().len();
+    let ether_type = match layer2.ether_type() {
+        Some(ether_type) => ether_type,
+        None => return write!(out, "{}, length {}", link_layer_name(layer2), length)
+    };
+    match ether_type {
+        EthernetTypeId::L3(Layer3Id::Arp) => {
+            match Arp::parse(layer2.payload()) {
+                Ok( (_, arp) ) => {
+                    match *arp.operation() {
+                        ArpOperation::Request => write!(out, "ARP, Request who-has {} tell {}", arp.target_ip(), arp.sender_ip())?,
+                        ArpOperation::Reply => write!(out, "ARP, Reply {} is-at {}", arp.sender_ip(), arp.sender_mac())?,
+                        ArpOperation::Other(op) => write!(out, "ARP, Unknown operation {}", op)?
+                    }
+                    write!(out, ", length {}", length)
+                }
+                Err(_) => write!(out, "[|ARP]")
+            }
+        }
+        ref ether_type if link_level => {
+            let (name, _) = ether_type_name(ether_type);
+            write!(out, "{}, length {}", name, length)
+        }
+        ref ether_type => {
+            let (name, value) = ether_type_name(ether_type);
+            write!(out, "ethertype {} (0x{:04x}), length {}", name, value, length)
+        }
+    }
+}
+
+fn write_anomaly<W: Write>(out: &mut W, anomaly: &Anomaly) -> std::fmt::Result {
+    match *anomaly {
+        Anomaly::IpTotalLength(n) => write!(out, "anomaly: {} bytes beyond ip total length", n),
+        Anomaly::EthernetPadding(n) => write!(out, "anomaly: {} bytes of ethernet padding", n),
+        Anomaly::TcpDataOffset(offset) => write!(out, "anomaly: tcp data offset {} below minimum", offset),
+        Anomaly::VlanCount(n) => write!(out, "anomaly: {} vlan tags", n),
+        Anomaly::Truncated(n) => write!(out, "anomaly: {} bytes truncated", n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+    use super::super::super::record::PcapRecord;
+
+    use std::convert::TryFrom;
+
+    const RAW_DATA: &'static [u8] = &[
+        0x5Bu8, 0x11u8, 0x6Du8, 0xE3u8, //seconds, 1527868899
+        0x00u8, 0x02u8, 0x51u8, 0xF5u8, //microseconds, 152053
+        0x00u8, 0x00u8, 0x00u8, 0x3Eu8, //actual length, 62: 14 (ethernet) + 4 (vlan) + 20 (ipv4 header) + 20 (tcp header) + 4 (tcp payload)
+        0x00u8, 0x00u8, 0x00u8, 0x3Eu8, //original length, 62
+        //ethernet
+        0x01u8, 0x02u8, 0x03u8, 0x04u8, 0x05u8, 0x06u8, //dst mac 01:02:03:04:05:06
+        0xFFu8, 0xFEu8, 0xFDu8, 0xFCu8, 0xFBu8, 0xFAu8, //src mac FF:FE:FD:FC:FB:FA
+        0x81u8, 0x00u8, //802.1q
+        0x20u8, 0x64u8, //priority 1, vlan 100
+        0x08u8, 0x00u8, //ipv4
+        //ipv4
+        0x45u8, //version and header length
+        0x00u8, //tos
+        0x00u8, 0x2Cu8, //length, 20 bytes for header, 24 bytes for tcp
+        0x00u8, 0x07u8, //id
+        0x40u8, 0x00u8, //flags, don't fragment
+        0x40u8, //ttl
+        0x06u8, //protocol, tcp
+        0x00u8, 0x00u8, //checksum
+        0x01u8, 0x02u8, 0x03u8, 0x04u8, //src ip 1.2.3.4
+        0x0Au8, 0x0Bu8, 0x0Cu8, 0x0Du8, //dst ip 10.11.12.13
+        //tcp
+        0xC6u8, 0xB7u8, //src port, 50871
+        0x00u8, 0x50u8, //dst port, 80
+        0x00u8, 0x00u8, 0x00u8, 0x01u8, //sequence number, 1
+        0x00u8, 0x00u8, 0x00u8, 0x02u8, //acknowledgement number, 2
+        0x50u8, 0x18u8, //header and flags, psh ack
+        0x00u8, 0x00u8, //window
+        0x00u8, 0x00u8, //check
+        0x00u8, 0x00u8, //urgent
+        //no options
+        //payload
+        b'G', b'E', b'T', b' '
+    ];
+
+    fn parsed() -> ParsedRecord {
+        let (_, record) = PcapRecord::parse(RAW_DATA, nom::Endianness::Big).expect("Could not parse");
+        ParsedRecord::try_from(record).expect("Could not parse layers")
+    }
+
+    #[test]
+    fn format_tcp() {
+        let _ = env_logger::try_init();
+
+        let record = parsed();
+
+        assert_eq!(
+            TextFormatter::default().format(&record),
+            "16:01:39.152053 IP 1.2.3.4.50871 > 10.11.12.13.80: Flags [P.], seq 1:5, ack 2, length 4"
+        );
+        assert_eq!(
+            TextFormatter::with_verbosity(Verbosity::Verbose).format(&record),
+            "16:01:39.152053 IP (tos 0x0, ttl 64, id 7, offset 0, flags [DF], proto TCP (6), length 44)\n    1.2.3.4.50871 > 10.11.12.13.80: Flags [P.], seq 1:5, ack 2, length 4"
+        );
+        assert_eq!(
+            TextFormatter::with_verbosity(Verbosity::VeryVerbose).format(&record),
+            "16:01:39.152053 IP (tos 0x0, ttl 64, id 7, offset 0, flags [DF], proto TCP (6), length 44)\n    1.2.3.4.50871 > 10.11.12.13.80: Flags [P.], seq 1:5, ack 2, length 4\n    HTTP"
+        );
+    }
+
+    #[test]
+    fn format_link_level() {
+        let _ = env_logger::try_init();
+
+        let record = parsed();
+
+        assert_eq!(
+            TextFormatter::default().link_level(true).format(&record),
+            "16:01:39.152053 ff:fe:fd:fc:fb:fa > 01:02:03:04:05:06, ethertype 802.1Q (0x8100), length 62: vlan 100, p 1, ethertype IPv4 (0x0800), 1.2.3.4.50871 > 10.11.12.13.80: Flags [P.], seq 1:5, ack 2, length 4"
+        );
+    }
+}
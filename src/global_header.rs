@@ -1,29 +1,188 @@
 use super::prelude::*;
+use super::common::{put_u16, put_u32};
+use super::link_type::LinkType;
 
 use self::nom::*;
 
+use std;
+
 const MAGIC_NUMBER: u32 = 0xA1B2C3D4u32;
+const NANOSECOND_MAGIC_NUMBER: u32 = 0xA1B23C4Du32;
+const MODIFIED_MAGIC_NUMBER: u32 = 0xA1B2CD34u32;
+const VERSION_MAJOR: u16 = 2;
+const VERSION_MINOR: u16 = 4;
+pub const GLOBAL_HEADER_LENGTH: usize = 24;
 #[cfg(target_endian = "little")]
 pub const NATIVE_ENDIAN: Endianness = Endianness::Little;
 #[cfg(target_endian = "big")]
 pub const NATIVE_ENDIAN: Endianness = Endianness::Big;
 
+///
+/// Resolution of the sub-second part of record timestamps, set by the magic number of a capture
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TimestampPrecision {
+    Microsecond,
+    ///
+    /// Captures written by e.g. `tcpdump --time-stamp-precision=nano`
+    ///
+    Nanosecond
+}
+
+impl Default for TimestampPrecision {
+    fn default() -> TimestampPrecision { TimestampPrecision::Microsecond }
+}
+
+impl TimestampPrecision {
+    fn magic_number(&self) -> u32 {
+        match *self {
+            TimestampPrecision::Microsecond => MAGIC_NUMBER,
+            TimestampPrecision::Nanosecond => NANOSECOND_MAGIC_NUMBER
+        }
+    }
+
+    ///
+    /// Sub-second fractions in a second, which a valid timestamp fraction is below
+    ///
+    pub fn fractions_per_second(&self) -> u32 {
+        match *self {
+            TimestampPrecision::Microsecond => 1_000_000,
+            TimestampPrecision::Nanosecond => 1_000_000_000
+        }
+    }
+
+    ///
+    /// Timestamp of `seconds` and `fraction` since the epoch, with `fraction` in this precision
+    ///
+    pub fn to_timestamp(&self, seconds: u32, fraction: u32) -> std::time::SystemTime {
+        let fraction = match *self {
+            TimestampPrecision::Microsecond => std::time::Duration::from_micros(fraction as u64),
+            TimestampPrecision::Nanosecond => std::time::Duration::from_nanos(fraction as u64)
+        };
+        std::time::UNIX_EPOCH + std::time::Duration::from_secs(seconds as u64) + fraction
+    }
+
+    ///
+    /// Seconds and sub-second fraction in this precision since the epoch, the inverse of
+    /// `to_timestamp`. Timestamps before the epoch are the epoch.
+    ///
+    pub fn from_timestamp(&self, timestamp: &std::time::SystemTime) -> (u32, u32) {
+        let since_epoch = timestamp.duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+        let fraction = match *self {
+            TimestampPrecision::Microsecond => since_epoch.subsec_micros(),
+            TimestampPrecision::Nanosecond => since_epoch.subsec_nanos()
+        };
+        (since_epoch.as_secs() as u32, fraction)
+    }
+}
+
+///
+/// Layout of the record headers of a capture, set by its magic number
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PcapVariant {
+    Standard,
+    ///
+    /// Captures written by Alexey Kuznetzov's patched libpcap on older Linux systems, whose record
+    /// headers carry the interface index, protocol and packet type after the standard fields
+    ///
+    Modified
+}
+
+impl Default for PcapVariant {
+    fn default() -> PcapVariant { PcapVariant::Standard }
+}
+
+impl PcapVariant {
+    ///
+    /// Length of a record header, standard fields included
+    ///
+    pub fn record_header_length(&self) -> usize {
+        match *self {
+            PcapVariant::Standard => RECORD_HEADER_LENGTH,
+            PcapVariant::Modified => RECORD_HEADER_LENGTH + 8
+        }
+    }
+}
+
 ///
 /// Global header associated with libpcap capture files
 ///
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct GlobalHeader {
     endianness: Endianness,
+    precision: TimestampPrecision,
+    variant: PcapVariant,
     version_major: u16,
     version_minor: u16,
     zone: i32,
     sig_figs: i32,
     snap_length: u32,
-    network: u32
+    network: LinkType
 }
 
 impl GlobalHeader {
+    ///
+    /// Header of a version 2.4 capture in native byte order, with microsecond timestamps in UTC
+    ///
+    pub fn new(link_type: LinkType, snap_length: u32) -> GlobalHeader {
+        GlobalHeader {
+            endianness: NATIVE_ENDIAN,
+            precision: TimestampPrecision::Microsecond,
+            variant: PcapVariant::Standard,
+            version_major: VERSION_MAJOR,
+            version_minor: VERSION_MINOR,
+            zone: 0,
+            sig_figs: 0,
+            snap_length: snap_length,
+            network: link_type
+        }
+    }
+
+    ///
+    /// Header written in byte order `endianness`, which the records following it must be written in
+    ///
+    pub fn with_endianness(mut self, endianness: Endianness) -> GlobalHeader {
+        self.endianness = endianness;
+        self
+    }
+
+    ///
+    /// Header of a capture whose record timestamps are written in `precision`
+    ///
+    pub fn with_precision(mut self, precision: TimestampPrecision) -> GlobalHeader {
+        self.precision = precision;
+        self
+    }
+
+    ///
+    /// Header of a capture whose record headers are laid out as `variant`. Modified captures have
+    /// microsecond timestamps.
+    ///
+    pub fn with_variant(mut self, variant: PcapVariant) -> GlobalHeader {
+        self.variant = variant;
+        self
+    }
+
+    ///
+    /// Header of a capture whose records are truncated to `snap_length` bytes
+    ///
+    pub fn with_snap_length(mut self, snap_length: u32) -> GlobalHeader {
+        self.snap_length = snap_length;
+        self
+    }
+
     pub fn endianness(&self) -> Endianness { self.endianness }
 
+    pub fn precision(&self) -> TimestampPrecision { self.precision }
+
+    pub fn variant(&self) -> PcapVariant { self.variant }
+
+    ///
+    /// Length of each record header in the capture
+    ///
+    pub fn record_header_length(&self) -> usize { self.variant.record_header_length() }
+
     pub fn version_major(&self) -> u16 { self.version_major }
 
     pub fn version_minor(&self) -> u16 { self.version_minor }
@@ -32,28 +191,64 @@ impl GlobalHeader {
         self.snap_length
     }
 
+    ///
+    /// Link layer header type of every record in the capture
+    ///
+    pub fn link_type(&self) -> LinkType { self.network }
+
+    ///
+    /// Header as it starts a libpcap capture, the inverse of parsing
+    ///
+    pub fn to_bytes(&self) -> std::vec::Vec<u8> {
+        let mut bytes = std::vec::Vec::with_capacity(GLOBAL_HEADER_LENGTH);
+        let magic_number = match self.variant {
+            PcapVariant::Standard => self.precision.magic_number(),
+            PcapVariant::Modified => MODIFIED_MAGIC_NUMBER
+        };
+        put_u32(&mut bytes, magic_number, self.endianness);
+        put_u16(&mut bytes, self.version_major, self.endianness);
+        put_u16(&mut bytes, self.version_minor, self.endianness);
+        put_u32(&mut bytes, self.zone as u32, self.endianness);
+        put_u32(&mut bytes, self.sig_figs as u32, self.endianness);
+        put_u32(&mut bytes, self.snap_length, self.endianness);
+        put_u32(&mut bytes, self.network.value(), self.endianness);
+        bytes
+    }
+
+    pub fn write_to<W: std::io::Write>(&self, writer: &mut W) -> errors::Result<()> {
+        writer.write_all(&self.to_bytes())?;
+        Ok(())
+    }
+
     pub(crate) fn parse<'a>(input: &'a [u8]) -> IResult<&'a [u8], GlobalHeader> {
         do_parse!(input,
 
-            endianness: map!(u32!(NATIVE_ENDIAN), |e| {
+            magic: map!(u32!(NATIVE_ENDIAN), |e| {
+                let other = if NATIVE_ENDIAN == Endianness::Little { Endianness::Big } else { Endianness::Little };
                 let res = match e {
-                    MAGIC_NUMBER => NATIVE_ENDIAN,
-                    _ if NATIVE_ENDIAN == Endianness::Little => Endianness::Big,
-                    _ => Endianness::Little
+                    MAGIC_NUMBER => (NATIVE_ENDIAN, TimestampPrecision::Microsecond, PcapVariant::Standard),
+                    NANOSECOND_MAGIC_NUMBER => (NATIVE_ENDIAN, TimestampPrecision::Nanosecond, PcapVariant::Standard),
+                    MODIFIED_MAGIC_NUMBER => (NATIVE_ENDIAN, TimestampPrecision::Microsecond, PcapVariant::Modified),
+                    _ if e.swap_bytes() == NANOSECOND_MAGIC_NUMBER => (other, TimestampPrecision::Nanosecond, PcapVariant::Standard),
+                    _ if e.swap_bytes() == MODIFIED_MAGIC_NUMBER => (other, TimestampPrecision::Microsecond, PcapVariant::Modified),
+                    _ => (other, TimestampPrecision::Microsecond, PcapVariant::Standard)
                 };
-                debug!("Read {:02x} compared to magic number {:02x}, setting endianness to {:?}", e, MAGIC_NUMBER, res);
+                debug!("Read {:02x} compared to magic number {:02x}, setting endianness, precision and variant to {:?}", e, MAGIC_NUMBER, res);
                 res
             }) >>
+            endianness: value!(magic.0) >>
             version_major: u16!(endianness) >>
             version_minor: u16!(endianness) >>
             zone: i32!(endianness) >>
             sig_figs: i32!(endianness) >>
             snap_length: u32!(endianness) >>
-            network: u32!(endianness) >>
+            network: map!(u32!(endianness), LinkType::new) >>
 
             (
                 GlobalHeader {
                     endianness: endianness,
+                    precision: magic.1,
+                    variant: magic.2,
                     version_major: version_major,
                     version_minor: version_minor,
                     zone: zone,
@@ -80,7 +275,7 @@ mod tests {
         0x00u8, 0x00u8, 0x00u8, 0x00u8, //zone, 0
         0x04u8, 0x00u8, 0x00u8, 0x00u8, //sig figs, 4
         0x13u8, 0x06u8, 0x00u8, 0x00u8, //snap length, 1555
-        0x02u8, 0x00u8, 0x00u8, 0x00u8, //network, 2
+        0x02u8, 0x00u8, 0x00u8, 0x00u8, //network, 2, unknown link type
     ];
     #[cfg(target_endian = "little")]
     const RAW_DATA_REVERSED: &'static [u8] = &[
@@ -90,7 +285,7 @@ mod tests {
         0x00u8, 0x00u8, 0x00u8, 0x00u8, //zone, 0
         0x00u8, 0x00u8, 0x00u8, 0x04u8, //sig figs, 4
         0x00u8, 0x00u8, 0x06u8, 0x13u8, //snap length, 1555
-        0x00u8, 0x00u8, 0x00u8, 0x02u8, //network, 2
+        0x00u8, 0x00u8, 0x00u8, 0x02u8, //network, 2, unknown link type
     ];
     #[cfg(target_endian = "big")]
     const RAW_DATA: &'static [u8] = &[
@@ -100,7 +295,7 @@ mod tests {
         0x00u8, 0x00u8, 0x00u8, 0x00u8, //zone, 0
         0x00u8, 0x00u8, 0x00u8, 0x04u8, //sig figs, 4
         0x00u8, 0x00u8, 0x06u8, 0x13u8, //snap length, 1555
-        0x00u8, 0x00u8, 0x00u8, 0x02u8, //network, 2
+        0x00u8, 0x00u8, 0x00u8, 0x02u8, //network, 2, unknown link type
     ];
     #[cfg(target_endian = "big")]
     const RAW_DATA_REVERSED: &'static [u8] = &[
@@ -110,7 +305,7 @@ mod tests {
         0x00u8, 0x00u8, 0x00u8, 0x00u8, //zone, 0
         0x04u8, 0x00u8, 0x00u8, 0x00u8, //sig figs, 4
         0x13u8, 0x06u8, 0x00u8, 0x00u8, //snap length, 1555
-        0x02u8, 0x00u8, 0x00u8, 0x00u8, //network, 2
+        0x02u8, 0x00u8, 0x00u8, 0x00u8, //network, 2, unknown link type
     ];
 
     #[test]
@@ -124,6 +319,114 @@ mod tests {
         assert_eq!(gh.version_minor(), 2);
         assert_eq!(gh.endianness(), NATIVE_ENDIAN);
         assert_eq!(gh.snap_length(), 1555);
+        assert_eq!(gh.link_type(), LinkType::Unknown(2));
+    }
+
+    #[test]
+    fn global_header_to_bytes() {
+        let _ = env_logger::try_init();
+
+        let (_, gh) = GlobalHeader::parse(RAW_DATA).expect("Failed to parse header");
+        assert_eq!(gh.to_bytes(), RAW_DATA.to_vec());
+        //fixture magic number is only recognized by not being native, the rest round trips
+        let (_, gh) = GlobalHeader::parse(RAW_DATA_REVERSED).expect("Failed to parse header");
+        assert_eq!(&gh.to_bytes()[4..], &RAW_DATA_REVERSED[4..]);
+
+        let gh = GlobalHeader::new(LinkType::Ethernet, 65535).with_endianness(Endianness::Big);
+        let mut bytes = vec![];
+        gh.write_to(&mut bytes).expect("Could not write header");
+        assert_eq!(bytes, vec![
+            0xA1u8, 0xB2u8, 0xC3u8, 0xD4u8, //magic number
+            0x00u8, 0x02u8, //version major, 2
+            0x00u8, 0x04u8, //version minor, 4
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, //zone, 0
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, //sig figs, 0
+            0x00u8, 0x00u8, 0xFFu8, 0xFFu8, //snap length, 65535
+            0x00u8, 0x00u8, 0x00u8, 0x01u8 //network, ethernet
+        ]);
+        assert_eq!(GlobalHeader::parse(&bytes).expect("Failed to parse header").1, gh);
+    }
+
+    #[test]
+    fn global_header_nanosecond_precision() {
+        let _ = env_logger::try_init();
+
+        let big = [
+            0xA1u8, 0xB2u8, 0x3Cu8, 0x4Du8, //magic number, nanosecond
+            0x00u8, 0x02u8, //version major, 2
+            0x00u8, 0x04u8, //version minor, 4
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, //zone, 0
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, //sig figs, 0
+            0x00u8, 0x04u8, 0x00u8, 0x00u8, //snap length, 262144
+            0x00u8, 0x00u8, 0x00u8, 0x01u8 //network, ethernet
+        ];
+        let (_, gh) = GlobalHeader::parse(&big).expect("Failed to parse header");
+        assert_eq!(gh.endianness(), Endianness::Big);
+        assert_eq!(gh.precision(), TimestampPrecision::Nanosecond);
+        assert_eq!(gh.snap_length(), 262144);
+        assert_eq!(gh.to_bytes(), big.to_vec());
+
+        let little = [
+            0x4Du8, 0x3Cu8, 0xB2u8, 0xA1u8, //magic number, nanosecond
+            0x02u8, 0x00u8, //version major, 2
+            0x04u8, 0x00u8, //version minor, 4
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, //zone, 0
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, //sig figs, 0
+            0x00u8, 0x00u8, 0x04u8, 0x00u8, //snap length, 262144
+            0x01u8, 0x00u8, 0x00u8, 0x00u8 //network, ethernet
+        ];
+        let (_, gh) = GlobalHeader::parse(&little).expect("Failed to parse header");
+        assert_eq!(gh.endianness(), Endianness::Little);
+        assert_eq!(gh.precision(), TimestampPrecision::Nanosecond);
+        assert_eq!(gh.snap_length(), 262144);
+
+        let (_, gh) = GlobalHeader::parse(RAW_DATA).expect("Failed to parse header");
+        assert_eq!(gh.precision(), TimestampPrecision::Microsecond);
+    }
+
+    #[test]
+    fn global_header_modified_variant() {
+        let _ = env_logger::try_init();
+
+        let big = [
+            0xA1u8, 0xB2u8, 0xCDu8, 0x34u8, //magic number, modified
+            0x00u8, 0x02u8, //version major, 2
+            0x00u8, 0x04u8, //version minor, 4
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, //zone, 0
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, //sig figs, 0
+            0x00u8, 0x00u8, 0xFFu8, 0xFFu8, //snap length, 65535
+            0x00u8, 0x00u8, 0x00u8, 0x01u8 //network, ethernet
+        ];
+        let (_, gh) = GlobalHeader::parse(&big).expect("Failed to parse header");
+        assert_eq!(gh.endianness(), Endianness::Big);
+        assert_eq!(gh.precision(), TimestampPrecision::Microsecond);
+        assert_eq!(gh.variant(), PcapVariant::Modified);
+        assert_eq!(gh.record_header_length(), 24);
+        assert_eq!(gh.to_bytes(), big.to_vec());
+
+        let mut little = big.to_vec();
+        little[..4].reverse();
+        little[4..6].reverse();
+        little[6..8].reverse();
+        little[16..20].reverse();
+        little[20..24].reverse();
+        let (_, gh) = GlobalHeader::parse(&little).expect("Failed to parse header");
+        assert_eq!(gh.endianness(), Endianness::Little);
+        assert_eq!(gh.variant(), PcapVariant::Modified);
+        assert_eq!(gh.snap_length(), 65535);
+
+        let (_, gh) = GlobalHeader::parse(RAW_DATA).expect("Failed to parse header");
+        assert_eq!(gh.variant(), PcapVariant::Standard);
+        assert_eq!(gh.record_header_length(), 16);
+    }
+
+    #[test]
+    fn timestamp_precision() {
+        let timestamp = TimestampPrecision::Nanosecond.to_timestamp(1527868899, 152053123);
+        assert_eq!(timestamp, std::time::UNIX_EPOCH + std::time::Duration::new(1527868899, 152053123));
+        assert_eq!(TimestampPrecision::Nanosecond.from_timestamp(&timestamp), (1527868899, 152053123));
+        assert_eq!(TimestampPrecision::Microsecond.from_timestamp(&timestamp), (1527868899, 152053));
+        assert_eq!(TimestampPrecision::Microsecond.to_timestamp(1527868899, 152053), std::time::UNIX_EPOCH + std::time::Duration::new(1527868899, 152053000));
     }
 
     #[test]
@@ -140,5 +443,6 @@ mod tests {
         assert_eq!(gh.version_minor(), 2);
         assert_eq!(gh.endianness(), expected_endianness);
         assert_eq!(gh.snap_length(), 1555);
+        assert_eq!(gh.link_type(), LinkType::Unknown(2));
     }
 }
\ No newline at end of file
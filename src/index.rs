@@ -0,0 +1,259 @@
+use super::prelude::*;
+use super::global_header::{GlobalHeader, GLOBAL_HEADER_LENGTH};
+use super::record::RecordHeader;
+use super::scan;
+
+use std;
+use std::io::{Read, Seek, SeekFrom};
+
+///
+/// Location and header fields of a single record in a capture
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IndexEntry {
+    offset: u64,
+    payload_offset: u64,
+    timestamp: std::time::SystemTime,
+    actual_length: u32,
+    original_length: u32
+}
+
+impl IndexEntry {
+    ///
+    /// Offset of the record header, from the start of the indexed input or stream
+    ///
+    pub fn offset(&self) -> u64 { self.offset }
+    pub fn timestamp(&self) -> &std::time::SystemTime { &self.timestamp }
+    pub fn actual_length(&self) -> u32 { self.actual_length }
+    pub fn original_length(&self) -> u32 { self.original_length }
+
+    fn with_header(offset: u64, header_length: usize, record_header: &RecordHeader) -> IndexEntry {
+        IndexEntry {
+            offset: offset,
+            payload_offset: offset + header_length as u64,
+            timestamp: *record_header.timestamp(),
+            actual_length: record_header.actual_length(),
+            original_length: record_header.original_length()
+        }
+    }
+
+    ///
+    /// Seek to and read the payload of this record from the indexed stream
+    ///
+    pub fn read_record<R: Read + Seek>(&self, reader: &mut R) -> errors::Result<PcapRecord> {
+        reader.seek(SeekFrom::Start(self.payload_offset))?;
+        let mut payload = vec![0u8; self.actual_length as usize];
+        reader.read_exact(&mut payload)?;
+        Ok(PcapRecord::new(self.timestamp, self.actual_length, self.original_length, payload))
+    }
+}
+
+///
+/// Offsets, timestamps and lengths of the records in a libpcap capture, built in one pass over the
+/// record headers. A later pass can then seek straight to a record number or time range, rather
+/// than parsing from the start of the capture.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct CaptureIndex {
+    header: GlobalHeader,
+    entries: std::vec::Vec<IndexEntry>
+}
+
+impl CaptureIndex {
+    ///
+    /// Index a libpcap capture in memory (including global header). Indexing stops at the first
+    /// truncated record.
+    ///
+    pub fn build(input: &[u8]) -> errors::Result<CaptureIndex> {
+        let (_, header) = GlobalHeader::parse(input)
+            .map_err(|e| errors::Error::from_nom(e, errors::Layer::GlobalHeader, input))?;
+        let header_length = header.record_header_length();
+        let mut entries = vec![];
+        let mut offset = GLOBAL_HEADER_LENGTH as u64;
+
+        scan::walk_headers(input, |_, record_header| {
+            entries.push(IndexEntry::with_header(offset, header_length, record_header));
+            offset += (header_length as u64) + record_header.actual_length() as u64;
+        })?;
+        debug!("Indexed {} records", entries.len());
+
+        Ok(CaptureIndex {
+            header: header,
+            entries: entries
+        })
+    }
+
+    ///
+    /// Index a libpcap capture starting at the current position of `reader`, seeking past record
+    /// payloads rather than reading them. Offsets are from the start of the stream, and indexing
+    /// stops at the first truncated record.
+    ///
+    pub fn from_reader<R: Read + Seek>(reader: &mut R) -> errors::Result<CaptureIndex> {
+        let start = reader.stream_position()?;
+        let length = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(start))?;
+
+        let mut header_bytes = [0u8; GLOBAL_HEADER_LENGTH];
+        reader.read_exact(&mut header_bytes)?;
+        let (_, header) = GlobalHeader::parse(&header_bytes)
+            .map_err(|e| errors::Error::from_nom(e, errors::Layer::GlobalHeader, &header_bytes))?;
+
+        let mut entries = vec![];
+        let mut offset = start + GLOBAL_HEADER_LENGTH as u64;
+        let header_length = header.record_header_length();
+        let mut record_header_bytes = vec![0u8; header_length];
+
+        while offset + (header_length as u64) <= length {
+            reader.read_exact(&mut record_header_bytes)?;
+            let (_, record_header) = RecordHeader::parse_for(&record_header_bytes, &header)
+                .map_err(|e| errors::Error::from_nom(e, errors::Layer::Record, &record_header_bytes))?;
+
+            let next = offset + (header_length as u64) + record_header.actual_length() as u64;
+            if next > length {
+                debug!("Capture ended within record payload");
+                break
+            }
+            entries.push(IndexEntry::with_header(offset, header_length, &record_header));
+            reader.seek(SeekFrom::Current(record_header.actual_length() as i64))?;
+            offset = next;
+        }
+        debug!("Indexed {} records", entries.len());
+
+        Ok(CaptureIndex {
+            header: header,
+            entries: entries
+        })
+    }
+
+    pub fn header(&self) -> &GlobalHeader { &self.header }
+    pub fn entries(&self) -> &std::vec::Vec<IndexEntry> { &self.entries }
+    pub fn len(&self) -> usize { self.entries.len() }
+    pub fn is_empty(&self) -> bool { self.entries.is_empty() }
+
+    pub fn entry(&self, index: usize) -> Option<&IndexEntry> { self.entries.get(index) }
+
+    ///
+    /// Record numbers whose timestamps fall in `[start, end]`, found by binary search over the index
+    /// rather than by scanning the capture. Timestamps are expected to be monotonic.
+    ///
+    pub fn time_range(&self, start: std::time::SystemTime, end: std::time::SystemTime) -> std::ops::Range<usize> {
+        let first = self.entries.partition_point(|e| e.timestamp < start);
+        let last = self.entries.partition_point(|e| e.timestamp <= end);
+        first..std::cmp::max(first, last)
+    }
+
+    ///
+    /// Read record number `index` from the indexed stream, if the capture has that many records
+    ///
+    pub fn read_record<R: Read + Seek>(&self, reader: &mut R, index: usize) -> errors::Result<Option<PcapRecord>> {
+        match self.entries.get(index) {
+            Some(entry) => entry.read_record(reader).map(|record| Some(record.with_header(&self.header))),
+            None => Ok(None)
+        }
+    }
+
+    ///
+    /// Read the records numbered in `range` from the indexed stream, clamped to the records indexed
+    ///
+    pub fn read_records<R: Read + Seek>(&self, reader: &mut R, range: std::ops::Range<usize>) -> errors::Result<std::vec::Vec<PcapRecord>> {
+        let end = std::cmp::min(range.end, self.entries.len());
+        let start = std::cmp::min(range.start, end);
+        self.entries[start..end].iter()
+            .map(|entry| entry.read_record(reader).map(|record| record.with_header(&self.header)))
+            .collect()
+    }
+
+    ///
+    /// Read the records whose timestamps fall in `[start, end]` from the indexed stream
+    ///
+    pub fn read_time_range<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+        start: std::time::SystemTime,
+        end: std::time::SystemTime
+    ) -> errors::Result<std::vec::Vec<PcapRecord>> {
+        let range = self.time_range(start, end);
+        self.read_records(reader, range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+    use super::super::CaptureParser;
+    use super::super::link_type::LinkType;
+    use super::super::nom::Endianness;
+
+    fn capture(endianness: Endianness) -> std::vec::Vec<u8> {
+        let header = GlobalHeader::new(LinkType::Ethernet, 65535).with_endianness(endianness);
+        let mut bytes = header.to_bytes();
+        for idx in 0..10u32 {
+            let payload = vec![idx as u8; idx as usize + 1];
+            let record = PcapRecord::new(PcapRecord::convert_packet_time(1527868899 + idx, 0), payload.len() as u32, payload.len() as u32, payload);
+            bytes.extend_from_slice(&record.to_bytes(endianness));
+        }
+        bytes
+    }
+
+    #[test]
+    fn index_capture() {
+        let _ = env_logger::try_init();
+
+        for endianness in &[Endianness::Big, Endianness::Little] {
+            let mut bytes = capture(*endianness);
+            let (_, (_, records)) = CaptureParser::parse_file(&bytes).expect("Could not parse");
+
+            let index = CaptureIndex::build(&bytes).expect("Could not index");
+            assert_eq!(index.header().endianness(), *endianness);
+            assert_eq!(index.len(), 10);
+            assert_eq!(index.entry(0).expect("No entry").offset(), 24);
+            assert_eq!(index.entry(1).expect("No entry").offset(), 24 + 16 + 1);
+            assert_eq!(index.entry(3).expect("No entry").actual_length(), 4);
+
+            bytes.extend_from_slice(&[0x00u8; 10]);
+            let mut cursor = std::io::Cursor::new(bytes);
+            let streamed = CaptureIndex::from_reader(&mut cursor).expect("Could not index");
+            assert_eq!(streamed, index);
+
+            assert_eq!(index.read_record(&mut cursor, 7).expect("Could not read"), Some(records[7].clone()));
+            assert_eq!(index.read_record(&mut cursor, 2).expect("Could not read"), Some(records[2].clone()));
+            assert!(index.read_record(&mut cursor, 10).expect("Could not read").is_none());
+            assert_eq!(index.read_records(&mut cursor, 8..20).expect("Could not read"), records[8..].to_vec());
+        }
+    }
+
+    #[test]
+    fn index_time_range() {
+        let _ = env_logger::try_init();
+
+        let bytes = capture(Endianness::Little);
+        let (_, (_, records)) = CaptureParser::parse_file(&bytes).expect("Could not parse");
+        let index = CaptureIndex::build(&bytes).expect("Could not index");
+
+        let start = PcapRecord::convert_packet_time(1527868901, 500);
+        let end = PcapRecord::convert_packet_time(1527868904, 0);
+        assert_eq!(index.time_range(start, end), 3..6);
+
+        let mut cursor = std::io::Cursor::new(&bytes);
+        assert_eq!(index.read_time_range(&mut cursor, start, end).expect("Could not read"), records[3..6].to_vec());
+        assert!(index.time_range(end, start).is_empty());
+        assert!(index.time_range(PcapRecord::convert_packet_time(0, 0), PcapRecord::convert_packet_time(1, 0)).is_empty());
+    }
+
+    #[test]
+    fn index_truncated() {
+        let _ = env_logger::try_init();
+
+        let bytes = capture(Endianness::Little);
+        let length = bytes.len();
+
+        let index = CaptureIndex::build(&bytes[..length - 2]).expect("Could not index");
+        assert_eq!(index.len(), 9);
+
+        let mut cursor = std::io::Cursor::new(&bytes[..length - 2]);
+        let streamed = CaptureIndex::from_reader(&mut cursor).expect("Could not index");
+        assert_eq!(streamed, index);
+    }
+}
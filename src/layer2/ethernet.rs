@@ -10,36 +10,64 @@ use self::layer3::{
 use std;
 use std::convert::TryFrom;
 use super::Layer2FlowInfo;
+use super::llc::Llc;
 
 const ETHERNET_PAYLOAD: u16 = 1500u16;
 const VLAN_LENGTH: usize = 4;
+const FCS_LENGTH: usize = 4;
+const CRC32_POLYNOMIAL: u32 = 0xEDB88320;
 
 ///
 /// List of valid ethernet types that aren't payload or vlan. https://en.wikipedia.org/wiki/EtherType
 ///
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Layer3Id {
     Lldp,
     IPv4,
     IPv6,
-    Arp
+    Arp,
+    PppoeDiscovery,
+    PppoeSession,
+    ///
+    /// Wake-on-LAN magic packet
+    ///
+    WakeOnLan
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum VlanTypeId {
     VlanTagId,
     ProviderBridging,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+impl VlanTypeId {
+    ///
+    /// Tag protocol identifier (ethernet type) announcing this kind of vlan tag
+    ///
+    pub fn tpid(&self) -> u16 {
+        match *self {
+            VlanTypeId::VlanTagId => 0x8100u16,
+            VlanTypeId::ProviderBridging => 0x88a8u16
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum EthernetTypeId {
     PayloadLength(u16),
+    ///
+    /// LLC frame too long for its length to fit the type field, e.g. in jumbo frames
+    ///
+    JumboLlc,
     Vlan(VlanTypeId),
     L3(Layer3Id)
 }
 
 impl EthernetTypeId {
-    fn new(vlan: u16) -> Option<EthernetTypeId> {
+    ///
+    /// Ethernet type of a 16 bit type or length field, `None` for types that aren't known
+    ///
+    pub fn new(vlan: u16) -> Option<EthernetTypeId> {
         match vlan {
             0x8100u16 => Some(EthernetTypeId::Vlan(VlanTypeId::VlanTagId)),
             0x88a8u16 => Some(EthernetTypeId::Vlan(VlanTypeId::ProviderBridging)),
@@ -47,6 +75,10 @@ impl EthernetTypeId {
             0x0800u16 => Some(EthernetTypeId::L3(Layer3Id::IPv4)),
             0x86ddu16 => Some(EthernetTypeId::L3(Layer3Id::IPv6)),
             0x0806u16 => Some(EthernetTypeId::L3(Layer3Id::Arp)),
+            0x8863u16 => Some(EthernetTypeId::L3(Layer3Id::PppoeDiscovery)),
+            0x8864u16 => Some(EthernetTypeId::L3(Layer3Id::PppoeSession)),
+            0x8870u16 => Some(EthernetTypeId::JumboLlc),
+            0x0842u16 => Some(EthernetTypeId::L3(Layer3Id::WakeOnLan)),
             x if x <= ETHERNET_PAYLOAD => Some(EthernetTypeId::PayloadLength(x)),
             x => {
                 //TODO: change to warn once list is more complete
@@ -55,25 +87,184 @@ impl EthernetTypeId {
             }
         }
     }
+
+    ///
+    /// 16 bit type or length field of the ethernet type, the inverse of `new`
+    ///
+    pub fn value(&self) -> u16 {
+        match *self {
+            EthernetTypeId::PayloadLength(length) => length,
+            EthernetTypeId::JumboLlc => 0x8870u16,
+            EthernetTypeId::Vlan(ref vlan_type) => vlan_type.tpid(),
+            EthernetTypeId::L3(Layer3Id::Lldp) => 0x88ccu16,
+            EthernetTypeId::L3(Layer3Id::IPv4) => 0x0800u16,
+            EthernetTypeId::L3(Layer3Id::IPv6) => 0x86ddu16,
+            EthernetTypeId::L3(Layer3Id::Arp) => 0x0806u16,
+            EthernetTypeId::L3(Layer3Id::PppoeDiscovery) => 0x8863u16,
+            EthernetTypeId::L3(Layer3Id::PppoeSession) => 0x8864u16,
+            EthernetTypeId::L3(Layer3Id::WakeOnLan) => 0x0842u16
+        }
+    }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct VlanTag {
     vlan_type: VlanTypeId,
-    value: [u8; 4]
+    vlan: Vlan
 }
 
 impl VlanTag {
-    pub fn vlan(&self) -> u16 {
-        unsafe { std::mem::transmute::<[u8; 2], u16>(array_ref!(self.value, 2, 2).clone()) }
+    pub fn vlan_type(&self) -> &VlanTypeId {
+        &self.vlan_type
+    }
+
+    pub fn vlan(&self) -> Vlan {
+        self.vlan
+    }
+
+    ///
+    /// Priority code point, the top 3 bits of the tag control information
+    ///
+    pub fn pcp(&self) -> u8 {
+        self.vlan.pcp()
+    }
+
+    ///
+    /// Drop eligible indicator, the bit following the priority code point
+    ///
+    pub fn dei(&self) -> bool {
+        self.vlan.dei()
+    }
+
+    ///
+    /// Vlan identifier, the low 12 bits of the tag control information
+    ///
+    pub fn vid(&self) -> u16 {
+        self.vlan.id()
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Ethernet {
     dst_mac: MacAddress,
     src_mac: MacAddress,
     ether_type: EthernetTypeId,
     vlans: std::vec::Vec<VlanTag>,
-    payload: std::vec::Vec<u8>
+    payload: std::vec::Vec<u8>,
+    fcs: Option<u32>,
+    fcs_valid: Option<bool>
+}
+
+///
+/// IEEE 802.3 CRC-32 of `data`, as carried in the frame check sequence
+///
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, b| {
+        (0..8).fold(crc ^ u32::from(*b), |c, _| {
+            if c & 1 == 1 { c >> 1 ^ CRC32_POLYNOMIAL } else { c >> 1 }
+        })
+    })
+}
+
+///
+/// Builder of ethernet frames, e.g. for test fixtures or synthesized captures. Frames are built
+/// with zero mac addresses and an 802.3 payload length type unless given otherwise. Payloads longer
+/// than 1500 bytes have no payload length type, so need an ether type given.
+///
+#[derive(Clone, Debug, Default)]
+pub struct EthernetBuilder {
+    dst_mac: Option<MacAddress>,
+    src_mac: Option<MacAddress>,
+    ether_type: Option<EthernetTypeId>,
+    vlans: std::vec::Vec<VlanTag>,
+    payload: std::vec::Vec<u8>,
+    fcs: bool
+}
+
+impl EthernetBuilder {
+    pub fn dst_mac(mut self, dst_mac: MacAddress) -> EthernetBuilder {
+        self.dst_mac = Some(dst_mac);
+        self
+    }
+
+    pub fn src_mac(mut self, src_mac: MacAddress) -> EthernetBuilder {
+        self.src_mac = Some(src_mac);
+        self
+    }
+
+    ///
+    /// Add a vlan tag inside any added before it. Tags with the 0x88a8 tag protocol identifier are
+    /// provider bridging (802.1ad service) tags, others 802.1Q tags.
+    ///
+    pub fn vlan(mut self, vlan: Vlan) -> EthernetBuilder {
+        let vlan_type = if vlan.tpid() == VlanTypeId::ProviderBridging.tpid() {
+            VlanTypeId::ProviderBridging
+        } else {
+            VlanTypeId::VlanTagId
+        };
+        self.vlans.push(VlanTag {
+            vlan: Vlan::from_tci(vlan_type.tpid(), vlan.tci()),
+            vlan_type: vlan_type
+        });
+        self
+    }
+
+    pub fn ether_type(mut self, ether_type: EthernetTypeId) -> EthernetBuilder {
+        self.ether_type = Some(ether_type);
+        self
+    }
+
+    pub fn payload(mut self, payload: std::vec::Vec<u8>) -> EthernetBuilder {
+        self.payload = payload;
+        self
+    }
+
+    ///
+    /// Compute the frame check sequence, so it is serialized after the payload
+    ///
+    pub fn fcs(mut self, fcs: bool) -> EthernetBuilder {
+        self.fcs = fcs;
+        self
+    }
+
+    ///
+    /// Frame of the fields set, with an 802.3 payload length when no ether type was set. An 802.3
+    /// payload length set explicitly must be the length of the payload.
+    ///
+    pub fn build(self) -> errors::Result<Ethernet> {
+        let ether_type = match self.ether_type {
+            Some(EthernetTypeId::PayloadLength(length)) if length as usize != self.payload.len() => {
+                return Err(format!("Payload length {} does not match the {} bytes of the payload", length, self.payload.len()).into())
+            }
+            Some(ether_type) => ether_type,
+            None if self.payload.len() <= ETHERNET_PAYLOAD as usize => EthernetTypeId::PayloadLength(self.payload.len() as u16),
+            None => return Err(format!("Payload of {} bytes is too long for an 802.3 payload length, an ether type is required", self.payload.len()).into())
+        };
+        let ethernet = Ethernet::new(
+            self.dst_mac.unwrap_or(MacAddress([0u8; MAC_LENGTH])),
+            self.src_mac.unwrap_or(MacAddress([0u8; MAC_LENGTH])),
+            ether_type,
+            self.vlans,
+            self.payload
+        );
+        if self.fcs {
+            let fcs = crc32(&ethernet.to_bytes());
+            Ok(Ethernet {
+                fcs: Some(fcs),
+                fcs_valid: Some(true),
+                ..ethernet
+            })
+        } else {
+            Ok(ethernet)
+        }
+    }
+
+    ///
+    /// Bytes of the built frame
+    ///
+    pub fn to_bytes(self) -> errors::Result<std::vec::Vec<u8>> {
+        self.build().map(|ethernet| ethernet.to_bytes())
+    }
 }
 
 fn to_mac_address(i: &[u8]) -> MacAddress {
@@ -95,23 +286,117 @@ impl Ethernet {
         &self.ether_type
     }
 
-    pub fn vlans(&self) -> &std::vec::Vec<VlanTag> {
+    pub fn vlans(&self) -> &[VlanTag] {
         &self.vlans
     }
 
-    pub fn vlans_to_vlan(vlans: &std::vec::Vec<VlanTag>) -> Vlan {
+    pub fn builder() -> EthernetBuilder {
+        EthernetBuilder::default()
+    }
+
+    ///
+    /// Frame as it appears on the wire, the inverse of parsing, followed by its frame check
+    /// sequence when it has one
+    ///
+    pub fn to_bytes(&self) -> std::vec::Vec<u8> {
+        let mut bytes = std::vec::Vec::with_capacity(Ethernet::header_length(&self.vlans) + self.payload.len() + FCS_LENGTH);
+        bytes.extend_from_slice(&self.dst_mac.0);
+        bytes.extend_from_slice(&self.src_mac.0);
+        for tag in self.vlans.iter() {
+            bytes.extend_from_slice(&tag.vlan_type.tpid().to_be_bytes());
+            bytes.extend_from_slice(&tag.vlan.tci().to_be_bytes());
+        }
+        bytes.extend_from_slice(&self.ether_type.value().to_be_bytes());
+        bytes.extend_from_slice(&self.payload);
+        if let Some(fcs) = self.fcs {
+            bytes.extend_from_slice(&fcs.to_le_bytes());
+        }
+        bytes
+    }
+
+    pub fn write_to<W: std::io::Write>(&self, writer: &mut W) -> errors::Result<()> {
+        writer.write_all(&self.to_bytes())?;
+        Ok(())
+    }
+
+    ///
+    /// Length of the ethernet header, including any vlan tags, preceding the layer 3 payload
+    ///
+    pub fn header_length(vlans: &[VlanTag]) -> usize {
+        2 * MAC_LENGTH + vlans.len() * VLAN_LENGTH + std::mem::size_of::<u16>()
+    }
+
+    pub fn vlans_to_vlan(vlans: &[VlanTag]) -> Vlan {
         let opt_vlan = vlans.first().map(|v| v.vlan());
-        opt_vlan.unwrap_or(0)
+        opt_vlan.unwrap_or(Vlan::untagged())
+    }
+
+    ///
+    /// Vlans of the tags inside the outermost, outermost first
+    ///
+    pub fn vlans_to_inner_vlans(vlans: &[VlanTag]) -> std::vec::Vec<Vlan> {
+        vlans.iter().skip(1).map(|v| v.vlan()).collect()
     }
 
     pub fn vlan(&self) -> Vlan {
         Ethernet::vlans_to_vlan(&self.vlans)
     }
 
+    fn typed_vlan(&self, vlan_type: VlanTypeId) -> Option<Vlan> {
+        self.vlans.iter().find(|t| t.vlan_type == vlan_type).map(|t| t.vlan)
+    }
+
+    ///
+    /// Service (provider) tag of an 802.1ad frame, the outermost 0x88a8 tag
+    ///
+    pub fn s_vlan(&self) -> Option<Vlan> {
+        self.typed_vlan(VlanTypeId::ProviderBridging)
+    }
+
+    ///
+    /// Customer tag, the outermost 0x8100 tag, whether or not the frame also has a service tag
+    ///
+    pub fn c_vlan(&self) -> Option<Vlan> {
+        self.typed_vlan(VlanTypeId::VlanTagId)
+    }
+
     pub fn payload(&self) -> &std::vec::Vec<u8> {
         &self.payload
     }
 
+    ///
+    /// Whether the payload is longer than the 1500 bytes of a standard frame, as in jumbo frames
+    ///
+    pub fn is_jumbo(&self) -> bool {
+        self.payload.len() > ETHERNET_PAYLOAD as usize
+    }
+
+    ///
+    /// Frame check sequence trailing the frame, for frames parsed with `parse_with_fcs`
+    ///
+    pub fn fcs(&self) -> Option<u32> {
+        self.fcs
+    }
+
+    ///
+    /// Whether the frame check sequence matches the CRC-32 of the frame, `None` for frames parsed
+    /// without one
+    ///
+    pub fn fcs_valid(&self) -> Option<bool> {
+        self.fcs_valid
+    }
+
+    ///
+    /// LLC header of an 802.3 frame, whose ethernet type is the length of its payload, or of a
+    /// jumbo LLC frame
+    ///
+    pub fn llc(&self) -> Result<Llc, errors::Error> {
+        match llc_length(&self.ether_type, &self.payload) {
+            Some(length) => parse_llc(length, &self.payload),
+            None => Err(errors::Error::from_kind(errors::ErrorKind::EthernetType(self.ether_type.clone())))
+        }
+    }
+
     fn parse_with_existing_vlan_tag<'b>(
         input: &'b [u8],
         dst_mac: MacAddress,
@@ -119,12 +404,13 @@ impl Ethernet {
         vlan_type: VlanTypeId,
         agg: std::vec::Vec<VlanTag>
     ) -> nom::IResult<&'b [u8], Ethernet> {
-        take!(input, VLAN_LENGTH).and_then(|r| {
-            let (rem, vlan) = r;
+        be_u16(input).and_then(|r| {
+            let (rem, tci) = r;
+            let vlan = Vlan::from_tci(vlan_type.tpid(), tci);
             let mut agg_mut = agg;
             agg_mut.push(VlanTag {
                 vlan_type: vlan_type,
-                value: array_ref!(vlan, 0, VLAN_LENGTH).clone()
+                vlan: vlan
             });
             Ethernet::parse_vlan_tag(rem, dst_mac, src_mac, agg_mut)
         })
@@ -160,7 +446,9 @@ impl Ethernet {
                                 src_mac: src_mac,
                                 ether_type: not_vlan,
                                 vlans: agg,
-                                payload: payload.into()
+                                payload: payload.into(),
+                                fcs: None,
+                                fcs_valid: None
                             }
                         )
                     )
@@ -177,16 +465,18 @@ impl Ethernet {
         payload: std::vec::Vec<u8>
     ) -> Ethernet {
         Ethernet {
-            dst_mac,
-            src_mac,
-            ether_type,
-            vlans,
-            payload
+            dst_mac: dst_mac,
+            src_mac: src_mac,
+            ether_type: ether_type,
+            vlans: vlans,
+            payload: payload,
+            fcs: None,
+            fcs_valid: None
         }
     }
 
     pub fn parse(input: &[u8]) -> nom::IResult<&[u8], Ethernet> {
-        trace!("Available={}", input.len());
+        layer_span!("ethernet", length = input.len());
 
         let r = do_parse!(input,
 
@@ -201,6 +491,103 @@ impl Ethernet {
             Ethernet::parse_vlan_tag(rem, dst_mac, src_mac, vec![])
         })
     }
+
+    ///
+    /// Parse a frame whose last 4 bytes are its frame check sequence, as kept by some captures,
+    /// validating it rather than leaving it in the payload
+    ///
+    pub fn parse_with_fcs(input: &[u8]) -> nom::IResult<&[u8], Ethernet> {
+        if input.len() < FCS_LENGTH {
+            return Err(nom::Err::Incomplete(nom::Needed::Size(FCS_LENGTH)))
+        }
+        let (frame, trailer) = input.split_at(input.len() - FCS_LENGTH);
+        let fcs = u32::from(trailer[0]) | u32::from(trailer[1]) << 8 | u32::from(trailer[2]) << 16 | u32::from(trailer[3]) << 24;
+
+        Ethernet::parse(frame).map(|(rem, ethernet)| {
+            (rem, Ethernet {
+                fcs: Some(fcs),
+                fcs_valid: Some(crc32(frame) == fcs),
+                ..ethernet
+            })
+        })
+    }
+}
+
+///
+/// Addresses and ports of the ip datagram in `payload`, of ethernet type `ether_type`
+///
+pub(crate) fn layer3_flow_info(ether_type: EthernetTypeId, payload: &[u8]) -> Result<Layer3FlowInfo, errors::Error> {
+    if let EthernetTypeId::L3(l3_id) = ether_type.clone() {
+        match l3_id {
+            Layer3Id::IPv4 => {
+                layer3::ipv4::IPv4::parse(payload)
+                    .map_err(|e| errors::Error::from_nom(e, errors::Layer::IPv4, payload)).and_then(|r| {
+                    let (rem, l3) = r;
+                    if rem.is_empty() {
+                        Layer3FlowInfo::try_from(l3)
+                    } else {
+                        Err(errors::Error::from_kind(errors::ErrorKind::IncompleteParse(rem.len())))
+                    }
+                })
+            }
+            #[cfg(feature = "ipv6")]
+            Layer3Id::IPv6 => {
+                layer3::ipv6::IPv6::parse(payload)
+                    .map_err(|e| errors::Error::from_nom(e, errors::Layer::IPv6, payload)).and_then(|r| {
+                    let (rem, l3) = r;
+                    if rem.is_empty() {
+                        Layer3FlowInfo::try_from(l3)
+                    } else {
+                        Err(errors::Error::from_kind(errors::ErrorKind::IncompleteParse(rem.len())))
+                    }
+                })
+            }
+            Layer3Id::Arp => {
+                layer3::arp::Arp::parse(payload)
+                    .map_err(|e| errors::Error::from_nom(e, errors::Layer::Arp, payload))
+                    .and_then(|(_, arp)| Layer3FlowInfo::try_from(arp))
+            }
+            Layer3Id::PppoeSession => {
+                super::pppoe::Pppoe::parse(payload)
+                    .map_err(|e| errors::Error::from_nom(e, errors::Layer::Pppoe, payload))
+                    .and_then(|(_, pppoe)| pppoe.ppp())
+                    .and_then(|ppp| ppp.layer3_flow_info().map_err(|e| e.offset_by(super::pppoe::HEADER_LENGTH)))
+            }
+            _ => {
+                Err(errors::Error::from_kind(errors::ErrorKind::EthernetType(ether_type)))
+            }
+        }
+    } else if let Some(length) = llc_length(&ether_type, payload) {
+        let llc = parse_llc(length, payload)?;
+        let inner = llc.ether_type()
+            .ok_or_else(|| errors::Error::from(format!("Unsupported LLC protocol {:?}", llc.protocol())))?;
+        layer3_flow_info(inner, llc.payload())
+            .map_err(|e| e.offset_by(llc.header_length()))
+    } else {
+        Err(errors::Error::from_kind(errors::ErrorKind::EthernetType(ether_type)))
+    }
+}
+
+///
+/// Length of the LLC frame in `payload`, from the length field of 802.3 frames and the whole
+/// payload of jumbo LLC frames, `None` for other ethernet types
+///
+fn llc_length(ether_type: &EthernetTypeId, payload: &[u8]) -> Option<usize> {
+    match *ether_type {
+        EthernetTypeId::PayloadLength(length) => Some(length as usize),
+        EthernetTypeId::JumboLlc => Some(payload.len()),
+        _ => None
+    }
+}
+
+///
+/// LLC header of an 802.3 payload, which is `length` bytes before any padding
+///
+fn parse_llc(length: usize, payload: &[u8]) -> Result<Llc, errors::Error> {
+    let payload = &payload[..std::cmp::min(length, payload.len())];
+    Llc::parse(payload)
+        .map(|(_, llc)| llc)
+        .map_err(|e| errors::Error::from_nom(e, errors::Layer::Llc, payload))
 }
 
 impl TryFrom<Ethernet> for Layer2FlowInfo {
@@ -208,49 +595,18 @@ impl TryFrom<Ethernet> for Layer2FlowInfo {
 
     fn try_from(value: Ethernet) -> Result<Self, Self::Error> {
         let ether_type = value.ether_type;
+        let header_length = Ethernet::header_length(&value.vlans);
+        layer_span!("ethernet_flow_info", header_length = header_length, vlans = value.vlans.len());
         debug!("Creating from layer 3 type {:?} using payload of {}B", ether_type, value.payload.len());
-        let l3 = if let EthernetTypeId::L3(l3_id) = ether_type.clone() {
-            match l3_id {
-                Layer3Id::IPv4 => {
-                    layer3::ipv4::IPv4::parse(&value.payload)
-                        .map_err(|e| {
-                            let err: Self::Error = e.into();
-                            err.chain_err(|| errors::Error::from_kind(errors::ErrorKind::FlowParse))
-                        }).and_then(|r| {
-                        let (rem, l3) = r;
-                        if rem.is_empty() {
-                            Layer3FlowInfo::try_from(l3)
-                        } else {
-                            Err(errors::Error::from_kind(errors::ErrorKind::IncompleteParse(rem.len())))
-                        }
-                    })
-                }
-                Layer3Id::IPv6 => {
-                    layer3::ipv6::IPv6::parse(&value.payload)
-                        .map_err(|e| {
-                            let err: Self::Error = e.into();
-                            err.chain_err(|| errors::Error::from_kind(errors::ErrorKind::FlowParse))
-                        }).and_then(|r| {
-                        let (rem, l3) = r;
-                        if rem.is_empty() {
-                            Layer3FlowInfo::try_from(l3)
-                        } else {
-                            Err(errors::Error::from_kind(errors::ErrorKind::IncompleteParse(rem.len())))
-                        }
-                    })
-                }
-                _ => {
-                    Err(errors::Error::from_kind(errors::ErrorKind::EthernetType(ether_type)))
-                }
-            }
-        } else {
-            Err(errors::Error::from_kind(errors::ErrorKind::EthernetType(ether_type)))
-        }?;
+        let l3 = layer3_flow_info(ether_type, &value.payload)
+            .map_err(|e| e.offset_by(header_length))?;
 
         Ok(Layer2FlowInfo {
-            src_mac: value.src_mac,
-            dst_mac: value.dst_mac,
+            src_mac: Some(value.src_mac),
+            dst_mac: Some(value.dst_mac),
             vlan: Ethernet::vlans_to_vlan(&value.vlans),
+            inner_vlans: Ethernet::vlans_to_inner_vlans(&value.vlans),
+            delivery: Delivery::classify(&value.dst_mac, Some(&l3.dst_ip)),
             layer3: l3
         })
     }
@@ -362,13 +718,258 @@ mod tests {
         assert_eq!(info.layer3.layer4.dst_port, 80);
     }
 
+    #[test]
+    fn convert_ethernet_invalid_ipv4() {
+        let _ = env_logger::try_init();
+
+        let mut data = TCP_RAW_DATA.to_vec();
+        data[14] = 0x43u8;
+
+        let (_, l2) = Ethernet::parse(&data).expect("Could not parse");
+
+        let err = Layer2FlowInfo::try_from(l2).err().expect("Converted invalid ipv4 header");
+
+        assert_eq!(format!("{}", err), "IPv4 header at offset 14: expected header length (IHL) of at least 5 words");
+    }
+
+    #[test]
+    fn convert_ethernet_llc_snap() {
+        let _ = env_logger::try_init();
+
+        let mut data = TCP_RAW_DATA[..12].to_vec();
+        data.extend_from_slice(&[
+            0x00u8, 0x50u8, //payload length, 8 bytes for llc/snap, 72 bytes for ipv4
+            0xAAu8, 0xAAu8, 0x03u8, //llc, snap unnumbered information
+            0x00u8, 0x00u8, 0x00u8, 0x08u8, 0x00u8 //snap, rfc 1042 ipv4
+        ]);
+        data.extend_from_slice(&TCP_RAW_DATA[14..]);
+        data.extend_from_slice(&[0x00u8, 0x00u8]); //padding
+
+        let (_, l2) = Ethernet::parse(&data).expect("Could not parse");
+        assert_eq!(l2.ether_type(), &EthernetTypeId::PayloadLength(80));
+
+        let llc = l2.llc().expect("No llc header");
+        assert_eq!(llc.ether_type(), Some(EthernetTypeId::L3(Layer3Id::IPv4)));
+        assert_eq!(llc.payload().len(), 72);
+
+        let info = Layer2FlowInfo::try_from(l2).expect("Could not convert to layer 2 flow info");
+
+        assert_eq!(info.layer3.layer4.src_port, 50871);
+        assert_eq!(info.layer3.layer4.dst_port, 80);
+
+        let (_, l2) = Ethernet::parse(PAYLOAD_RAW_DATA).expect("Could not parse");
+        assert!(Layer2FlowInfo::try_from(l2).is_err());
+
+        let (_, l2) = Ethernet::parse(TCP_RAW_DATA).expect("Could not parse");
+        assert!(l2.llc().is_err());
+    }
+
+    #[test]
+    fn convert_ethernet_pppoe_session() {
+        let _ = env_logger::try_init();
+
+        let mut data = TCP_RAW_DATA[..12].to_vec();
+        data.extend_from_slice(&[
+            0x88u8, 0x64u8, //pppoe session
+            0x11u8, //version and type
+            0x00u8, //code, session data
+            0x00u8, 0x01u8, //session id
+            0x00u8, 0x4Au8, //length, 2 bytes for ppp, 72 bytes for ipv4
+            0x00u8, 0x21u8 //ppp protocol, ipv4
+        ]);
+        data.extend_from_slice(&TCP_RAW_DATA[14..]);
+
+        let (_, l2) = Ethernet::parse(&data).expect("Could not parse");
+        assert_eq!(l2.ether_type(), &EthernetTypeId::L3(Layer3Id::PppoeSession));
+
+        let info = Layer2FlowInfo::try_from(l2).expect("Could not convert to layer 2 flow info");
+
+        assert_eq!(info.src_mac, Some(MacAddress([0xFFu8, 0xFEu8, 0xFDu8, 0xFCu8, 0xFBu8, 0xFAu8])));
+        assert_eq!(info.layer3.layer4.src_port, 50871);
+        assert_eq!(info.layer3.layer4.dst_port, 80);
+
+        data[14 + 8] = 0x43u8;
+        let (_, l2) = Ethernet::parse(&data).expect("Could not parse");
+        let err = Layer2FlowInfo::try_from(l2).expect_err("Converted invalid ipv4 header");
+
+        assert_eq!(format!("{}", err), "IPv4 header at offset 22: expected header length (IHL) of at least 5 words");
+    }
+
+    const VLAN_RAW_DATA: &'static [u8] = &[
+        0x01u8, 0x02u8, 0x03u8, 0x04u8, 0x05u8, 0x06u8, //dst mac 01:02:03:04:05:06
+        0xFFu8, 0xFEu8, 0xFDu8, 0xFCu8, 0xFBu8, 0xFAu8, //src mac FF:FE:FD:FC:FB:FA
+        0x88u8, 0xA8u8, //provider bridging
+        0x20u8, 0x64u8, //pcp 1, vlan 100
+        0x81u8, 0x00u8, //vlan tag
+        0xE1u8, 0x2Cu8, //pcp 7, vlan 300
+        0x00u8, 0x04u8, //payload ethernet
+        //payload
+        0x01u8, 0x02u8, 0x03u8, 0x04u8
+    ];
+
     #[test]
     fn test_single_vlan() {
-        //TODO
+        let _ = env_logger::try_init();
+
+        let mut data = VLAN_RAW_DATA[..12].to_vec();
+        data.extend_from_slice(&VLAN_RAW_DATA[16..]);
+
+        let (rem, l2) = Ethernet::parse(&data).expect("Could not parse");
+
+        assert!(rem.is_empty());
+        assert_eq!(l2.vlans().len(), 1);
+        assert_eq!(l2.vlans()[0].vlan_type(), &VlanTypeId::VlanTagId);
+        assert_eq!(l2.vlan(), Vlan::new(300, 7, false, 0x8100u16));
+        assert_eq!(l2.s_vlan(), None);
+        assert_eq!(l2.c_vlan(), Some(l2.vlan()));
+        assert_eq!(l2.payload(), &vec![0x01u8, 0x02u8, 0x03u8, 0x04u8]);
+        assert_eq!(Ethernet::header_length(l2.vlans()), data.len() - l2.payload().len());
     }
 
     #[test]
     fn test_multiple_vlans() {
-        //TODO
+        let _ = env_logger::try_init();
+
+        let (rem, l2) = Ethernet::parse(VLAN_RAW_DATA).expect("Could not parse");
+
+        assert!(rem.is_empty());
+        assert_eq!(l2.vlans().iter().map(|v| v.vlan().id()).collect::<std::vec::Vec<_>>(), vec![100, 300]);
+        assert_eq!(l2.vlans()[0].vlan_type(), &VlanTypeId::ProviderBridging);
+        assert_eq!(l2.vlan(), Vlan::new(100, 1, false, 0x88a8u16));
+        assert_eq!(l2.s_vlan(), Some(Vlan::new(100, 1, false, 0x88a8u16)));
+        assert_eq!(l2.c_vlan(), Some(Vlan::new(300, 7, false, 0x8100u16)));
+        assert_eq!(l2.payload(), &vec![0x01u8, 0x02u8, 0x03u8, 0x04u8]);
+        assert_eq!(Ethernet::header_length(l2.vlans()), VLAN_RAW_DATA.len() - l2.payload().len());
+    }
+
+    #[test]
+    fn test_vlan_tag_fields() {
+        let _ = env_logger::try_init();
+
+        let mut data = VLAN_RAW_DATA.to_vec();
+        data[14] = 0xBFu8; //pcp 5, dei, vlan 4095
+        data[15] = 0xFFu8;
+
+        let (_, l2) = Ethernet::parse(&data).expect("Could not parse");
+
+        let outer = &l2.vlans()[0];
+        assert_eq!(outer.pcp(), 5);
+        assert!(outer.dei());
+        assert_eq!(outer.vid(), 4095);
+        assert_eq!(outer.vlan().tci(), 0xBFFFu16);
+
+        let inner = &l2.vlans()[1];
+        assert_eq!(inner.pcp(), 7);
+        assert!(!inner.dei());
+        assert_eq!(inner.vid(), 300);
+    }
+
+    #[test]
+    fn test_builder() {
+        let _ = env_logger::try_init();
+
+        let built = Ethernet::builder()
+            .dst_mac(MacAddress([0x01u8, 0x02u8, 0x03u8, 0x04u8, 0x05u8, 0x06u8]))
+            .src_mac(MacAddress([0xFFu8, 0xFEu8, 0xFDu8, 0xFCu8, 0xFBu8, 0xFAu8]))
+            .vlan(Vlan::new(100, 1, false, 0x88a8u16))
+            .vlan(Vlan::new(300, 7, false, 0x8100u16))
+            .payload(vec![0x01u8, 0x02u8, 0x03u8, 0x04u8])
+            .to_bytes()
+            .expect("Could not build");
+
+        assert_eq!(built, VLAN_RAW_DATA.to_vec());
+
+        let (_, l2) = Ethernet::parse(TCP_RAW_DATA).expect("Could not parse");
+        assert_eq!(l2.to_bytes(), TCP_RAW_DATA.to_vec());
+
+        let rebuilt = Ethernet::builder()
+            .dst_mac(*l2.dst_mac())
+            .src_mac(*l2.src_mac())
+            .ether_type(l2.ether_type().clone())
+            .payload(l2.payload().clone())
+            .build()
+            .expect("Could not build");
+        assert_eq!(rebuilt, l2);
+
+        let with_fcs = Ethernet::builder().payload(vec![0x01u8]).fcs(true).to_bytes().expect("Could not build");
+        assert_eq!(with_fcs.len(), 14 + 1 + 4);
+        let (_, l2) = Ethernet::parse_with_fcs(&with_fcs).expect("Could not parse");
+        assert_eq!(l2.fcs_valid(), Some(true));
+        assert_eq!(l2.ether_type(), &EthernetTypeId::PayloadLength(1));
+        assert_eq!(l2.to_bytes(), with_fcs);
+
+        //an 802.3 payload length must match the payload
+        assert!(Ethernet::builder().ether_type(EthernetTypeId::PayloadLength(2)).payload(vec![0x01u8]).build().is_err());
+        let built = Ethernet::builder().ether_type(EthernetTypeId::PayloadLength(1)).payload(vec![0x01u8]).build().expect("Could not build");
+        assert_eq!(built.ether_type(), &EthernetTypeId::PayloadLength(1));
+
+        //a payload too long for an 802.3 payload length needs an ether type
+        let payload = vec![0x5Au8; 1501];
+        assert!(Ethernet::builder().payload(payload.clone()).build().is_err());
+        let built = Ethernet::builder()
+            .ether_type(EthernetTypeId::L3(Layer3Id::Lldp))
+            .payload(payload.clone())
+            .to_bytes()
+            .expect("Could not build");
+        assert_eq!(built.len(), 14 + 1501);
+        let (_, l2) = Ethernet::parse(&built).expect("Could not parse");
+        assert_eq!(l2.ether_type(), &EthernetTypeId::L3(Layer3Id::Lldp));
+        assert_eq!(l2.payload(), &payload);
+        assert_eq!(l2.to_bytes(), built);
+    }
+
+    #[test]
+    fn test_jumbo() {
+        let _ = env_logger::try_init();
+
+        let ipv4 = &TCP_RAW_DATA[14..];
+        let mut payload = vec![
+            0xAAu8, 0xAAu8, 0x03u8, //llc, snap
+            0x00u8, 0x00u8, 0x00u8, 0x08u8, 0x00u8 //snap, rfc 1042 ipv4
+        ];
+        payload.extend_from_slice(ipv4);
+        payload.resize(9000, 0u8);
+        let data = Ethernet::builder()
+            .ether_type(EthernetTypeId::JumboLlc)
+            .payload(payload)
+            .to_bytes()
+            .expect("Could not build");
+
+        let (_, l2) = Ethernet::parse(&data).expect("Could not parse");
+
+        assert_eq!(l2.ether_type(), &EthernetTypeId::JumboLlc);
+        assert!(l2.is_jumbo());
+        assert_eq!(l2.llc().expect("Could not parse llc").ether_type(), Some(EthernetTypeId::L3(Layer3Id::IPv4)));
+
+        let (_, l2) = Ethernet::parse(TCP_RAW_DATA).expect("Could not parse");
+        assert!(!l2.is_jumbo());
+        assert!(l2.llc().is_err());
+    }
+
+    #[test]
+    fn test_fcs() {
+        let _ = env_logger::try_init();
+
+        assert_eq!(crc32(b"123456789"), 0xCBF43926u32);
+
+        let mut data = PAYLOAD_RAW_DATA.to_vec();
+        data.extend_from_slice(&[0x78u8, 0x6Cu8, 0xACu8, 0xA3u8]); //fcs
+
+        let (rem, l2) = Ethernet::parse_with_fcs(&data).expect("Could not parse");
+
+        assert!(rem.is_empty());
+        assert_eq!(l2.fcs(), Some(0xA3AC6C78u32));
+        assert_eq!(l2.fcs_valid(), Some(true));
+        assert_eq!(l2.payload(), &vec![0x01u8, 0x02u8, 0x03u8, 0x04u8]);
+
+        data[15] = 0xFFu8;
+        let (_, l2) = Ethernet::parse_with_fcs(&data).expect("Could not parse");
+        assert_eq!(l2.fcs_valid(), Some(false));
+
+        let (_, l2) = Ethernet::parse(PAYLOAD_RAW_DATA).expect("Could not parse");
+        assert_eq!(l2.fcs(), None);
+        assert_eq!(l2.fcs_valid(), None);
+
+        assert!(Ethernet::parse_with_fcs(&data[..3]).is_err());
     }
 }
\ No newline at end of file
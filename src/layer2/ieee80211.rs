@@ -0,0 +1,428 @@
+use super::prelude::*;
+
+use self::nom::*;
+use super::ethernet::{self, EthernetTypeId};
+use super::llc::Llc;
+
+use std;
+use std::convert::TryFrom;
+use super::Layer2FlowInfo;
+
+const TO_DS_FLAG: u8 = 0x01;
+const FROM_DS_FLAG: u8 = 0x02;
+const MORE_FRAGMENTS_FLAG: u8 = 0x04;
+const RETRY_FLAG: u8 = 0x08;
+const PROTECTED_FLAG: u8 = 0x40;
+const ORDER_FLAG: u8 = 0x80;
+
+const QOS_SUBTYPE_FLAG: u8 = 0x08;
+const CTS_SUBTYPE: u8 = 12;
+const ACK_SUBTYPE: u8 = 13;
+
+const HT_CONTROL_LENGTH: usize = 4;
+
+///
+/// Type of an 802.11 frame, from the frame control field
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FrameType {
+    Management,
+    Control,
+    Data,
+    Extension
+}
+
+impl FrameType {
+    pub fn new(value: u8) -> FrameType {
+        match value & 0x03 {
+            0 => FrameType::Management,
+            1 => FrameType::Control,
+            2 => FrameType::Data,
+            _ => FrameType::Extension
+        }
+    }
+
+    pub fn value(&self) -> u8 {
+        match *self {
+            FrameType::Management => 0,
+            FrameType::Control => 1,
+            FrameType::Data => 2,
+            FrameType::Extension => 3
+        }
+    }
+}
+
+///
+/// Frame control field of an 802.11 frame, which determines the layout of the rest of the header
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FrameControl {
+    version: u8,
+    frame_type: FrameType,
+    subtype: u8,
+    flags: u8
+}
+
+impl FrameControl {
+    ///
+    /// Frame control from the little endian field `value`
+    ///
+    pub fn new(value: u16) -> FrameControl {
+        FrameControl {
+            version: (value & 0x03) as u8,
+            frame_type: FrameType::new((value >> 2) as u8),
+            subtype: ((value >> 4) & 0x0F) as u8,
+            flags: (value >> 8) as u8
+        }
+    }
+
+    pub fn version(&self) -> u8 { self.version }
+    pub fn frame_type(&self) -> FrameType { self.frame_type }
+    pub fn subtype(&self) -> u8 { self.subtype }
+    pub fn flags(&self) -> u8 { self.flags }
+
+    pub fn to_ds(&self) -> bool { self.flags & TO_DS_FLAG != 0 }
+    pub fn from_ds(&self) -> bool { self.flags & FROM_DS_FLAG != 0 }
+    pub fn more_fragments(&self) -> bool { self.flags & MORE_FRAGMENTS_FLAG != 0 }
+    pub fn retry(&self) -> bool { self.flags & RETRY_FLAG != 0 }
+    pub fn protected(&self) -> bool { self.flags & PROTECTED_FLAG != 0 }
+    pub fn order(&self) -> bool { self.flags & ORDER_FLAG != 0 }
+
+    ///
+    /// Whether the frame is a QoS data frame, which carries a QoS control field
+    ///
+    pub fn is_qos(&self) -> bool {
+        self.frame_type == FrameType::Data && self.subtype & QOS_SUBTYPE_FLAG != 0
+    }
+
+    fn has_address2(&self) -> bool {
+        match self.frame_type {
+            FrameType::Control => self.subtype != CTS_SUBTYPE && self.subtype != ACK_SUBTYPE,
+            FrameType::Extension => false,
+            _ => true
+        }
+    }
+
+    fn has_sequence(&self) -> bool {
+        self.frame_type == FrameType::Management || self.frame_type == FrameType::Data
+    }
+
+    fn has_address4(&self) -> bool {
+        self.frame_type == FrameType::Data && self.to_ds() && self.from_ds()
+    }
+
+    fn has_ht_control(&self) -> bool {
+        self.order() && (self.is_qos() || self.frame_type == FrameType::Management)
+    }
+}
+
+named!(mac_address<&[u8], MacAddress>, map!(take!(MAC_LENGTH), |i| MacAddress(*array_ref![i, 0, MAC_LENGTH])));
+
+///
+/// IEEE 802.11 frame, with the ethernet type of unprotected data frames taken from their LLC/SNAP
+/// header. https://www.tcpdump.org/linktypes/LINKTYPE_IEEE802_11.html
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Ieee80211 {
+    frame_control: FrameControl,
+    duration: u16,
+    addresses: std::vec::Vec<MacAddress>,
+    sequence_control: Option<u16>,
+    qos_control: Option<u16>,
+    ether_type: Option<EthernetTypeId>,
+    header_length: usize,
+    payload: std::vec::Vec<u8>
+}
+
+impl Ieee80211 {
+    pub fn frame_control(&self) -> &FrameControl { &self.frame_control }
+    pub fn duration(&self) -> u16 { self.duration }
+
+    ///
+    /// Addresses in the order they appear in the header, from one for acknowledgements to four
+    /// for data frames between distribution systems
+    ///
+    pub fn addresses(&self) -> &std::vec::Vec<MacAddress> { &self.addresses }
+
+    pub fn sequence_control(&self) -> Option<u16> { self.sequence_control }
+    pub fn sequence_number(&self) -> Option<u16> { self.sequence_control.map(|s| s >> 4) }
+    pub fn fragment_number(&self) -> Option<u8> { self.sequence_control.map(|s| (s & 0x0F) as u8) }
+
+    pub fn qos_control(&self) -> Option<u16> { self.qos_control }
+
+    ///
+    /// Traffic identifier of QoS data frames
+    ///
+    pub fn tid(&self) -> Option<u8> { self.qos_control.map(|q| (q & 0x0F) as u8) }
+
+    ///
+    /// Destination of the frame, which depends on the distribution system flags for data frames
+    ///
+    pub fn dst_mac(&self) -> Option<MacAddress> {
+        let index = if self.frame_control.frame_type == FrameType::Data && self.frame_control.to_ds() { 2 } else { 0 };
+        self.addresses.get(index).cloned()
+    }
+
+    ///
+    /// Source of the frame, which depends on the distribution system flags for data frames
+    ///
+    pub fn src_mac(&self) -> Option<MacAddress> {
+        let index = match (self.frame_control.frame_type, self.frame_control.to_ds(), self.frame_control.from_ds()) {
+            (FrameType::Data, true, true) => 3,
+            (FrameType::Data, false, true) => 2,
+            _ => 1
+        };
+        self.addresses.get(index).cloned()
+    }
+
+    ///
+    /// Basic service set of management frames and data frames within a single distribution system
+    ///
+    pub fn bssid(&self) -> Option<MacAddress> {
+        let index = match (self.frame_control.frame_type, self.frame_control.to_ds(), self.frame_control.from_ds()) {
+            (FrameType::Management, _, _) | (FrameType::Data, false, false) => 2,
+            (FrameType::Data, true, false) => 0,
+            (FrameType::Data, false, true) => 1,
+            _ => return None
+        };
+        self.addresses.get(index).cloned()
+    }
+
+    ///
+    /// Ethernet type of unprotected data frames with an LLC/SNAP header
+    ///
+    pub fn ether_type(&self) -> Option<EthernetTypeId> { self.ether_type.clone() }
+
+    ///
+    /// Length of the 802.11 header, and LLC/SNAP header when present, preceding the payload
+    ///
+    pub fn header_length(&self) -> usize { self.header_length }
+
+    ///
+    /// Frame body, following the LLC/SNAP header when the frame has one
+    ///
+    pub fn payload(&self) -> &std::vec::Vec<u8> { &self.payload }
+
+    pub fn parse(input: &[u8]) -> nom::IResult<&[u8], Ieee80211> {
+        layer_span!("ieee80211", length = input.len());
+
+        let (rem, (frame_control, duration, address1, address2, address3, sequence_control, address4, qos_control)) = do_parse!(input,
+
+            frame_control: map!(le_u16, FrameControl::new) >>
+            duration: le_u16 >>
+            address1: mac_address >>
+            address2: cond!(frame_control.has_address2(), mac_address) >>
+            address3: cond!(frame_control.has_sequence(), mac_address) >>
+            sequence_control: cond!(frame_control.has_sequence(), le_u16) >>
+            address4: cond!(frame_control.has_address4(), mac_address) >>
+            qos_control: cond!(frame_control.is_qos(), le_u16) >>
+            _ht_control: cond!(frame_control.has_ht_control(), take!(HT_CONTROL_LENGTH)) >>
+
+            ( (frame_control, duration, address1, address2, address3, sequence_control, address4, qos_control) )
+        )?;
+
+        let mut addresses = vec![address1];
+        addresses.extend(address2.into_iter().chain(address3).chain(address4));
+
+        let llc = if frame_control.frame_type == FrameType::Data && !frame_control.protected() {
+            Llc::parse(rem).ok().and_then(|(_, llc)| llc.ether_type().map(|ether_type| (ether_type, llc.header_length())))
+        } else {
+            None
+        };
+        let (ether_type, body) = match llc {
+            Some((ether_type, header_length)) => (Some(ether_type), &rem[header_length..]),
+            None => (None, rem)
+        };
+
+        Ok((
+            &[],
+            Ieee80211 {
+                frame_control: frame_control,
+                duration: duration,
+                addresses: addresses,
+                sequence_control: sequence_control,
+                qos_control: qos_control,
+                ether_type: ether_type,
+                header_length: input.len() - body.len(),
+                payload: body.into()
+            }
+        ))
+    }
+}
+
+impl TryFrom<Ieee80211> for Layer2FlowInfo {
+    type Error = errors::Error;
+
+    ///
+    /// Flow information of an unprotected data frame carrying an ip datagram
+    ///
+    fn try_from(value: Ieee80211) -> Result<Self, Self::Error> {
+        layer_span!("ieee80211_flow_info", subtype = value.frame_control.subtype);
+        let ether_type = value.ether_type()
+            .ok_or_else(|| errors::Error::from(format!(
+                "No ethernet type in 802.11 {:?} frame of subtype {}",
+                value.frame_control.frame_type,
+                value.frame_control.subtype
+            )))?;
+        let l3 = ethernet::layer3_flow_info(ether_type, &value.payload)
+            .map_err(|e| e.offset_by(value.header_length))?;
+
+        let dst_mac = value.dst_mac();
+        let delivery = match dst_mac {
+            Some(ref mac) => Delivery::classify(mac, Some(&l3.dst_ip)),
+            None => Delivery::classify_ip(&l3.dst_ip)
+        };
+
+        Ok(Layer2FlowInfo {
+            src_mac: value.src_mac(),
+            dst_mac: dst_mac,
+            vlan: Vlan::untagged(),
+            inner_vlans: vec![],
+            delivery: delivery,
+            layer3: l3
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+    use super::super::ethernet::Layer3Id;
+
+    const RAW_DATA: &'static [u8] = &[
+        0x88u8, 0x01u8, //frame control, qos data to ds
+        0x2Cu8, 0x00u8, //duration
+        0x00u8, 0x11u8, 0x22u8, 0x33u8, 0x44u8, 0x55u8, //address 1, bssid
+        0x66u8, 0x77u8, 0x88u8, 0x99u8, 0xAAu8, 0xBBu8, //address 2, source
+        0x02u8, 0x02u8, 0x03u8, 0x04u8, 0x05u8, 0x06u8, //address 3, destination
+        0x50u8, 0x01u8, //sequence control, sequence 21 fragment 0
+        0x05u8, 0x00u8, //qos control, tid 5
+        //llc/snap
+        0xAAu8, 0xAAu8, 0x03u8, 0x00u8, 0x00u8, 0x00u8, 0x08u8, 0x00u8, //rfc 1042, ipv4
+        //ipv4
+        0x45u8, //version and header length
+        0x00u8, //tos
+        0x00u8, 0x2Cu8, //length, 20 bytes for header, 24 bytes for tcp
+        0x00u8, 0x00u8, //id
+        0x00u8, 0x00u8, //flags
+        0x40u8, //ttl
+        0x06u8, //protocol, tcp
+        0x00u8, 0x00u8, //checksum
+        0xC0u8, 0xA8u8, 0x01u8, 0x02u8, //src ip 192.168.1.2
+        0x0Au8, 0x00u8, 0x00u8, 0x01u8, //dst ip 10.0.0.1
+        //tcp
+        0xC6u8, 0xB7u8, //src port, 50871
+        0x00u8, 0x50u8, //dst port, 80
+        0x00u8, 0x00u8, 0x00u8, 0x01u8, //sequence number, 1
+        0x00u8, 0x00u8, 0x00u8, 0x02u8, //acknowledgement number, 2
+        0x50u8, 0x00u8, //header and flags, 0
+        0x00u8, 0x00u8, //window
+        0x00u8, 0x00u8, //check
+        0x00u8, 0x00u8, //urgent
+        //no options
+        //payload
+        0xfcu8, 0xfdu8, 0xfeu8, 0xffu8
+    ];
+
+    #[test]
+    fn parse_data_frame() {
+        let _ = env_logger::try_init();
+
+        let (rem, frame) = Ieee80211::parse(RAW_DATA).expect("Unable to parse");
+
+        assert!(rem.is_empty());
+        assert_eq!(frame.frame_control().frame_type(), FrameType::Data);
+        assert_eq!(frame.frame_control().subtype(), 8);
+        assert!(frame.frame_control().to_ds());
+        assert!(!frame.frame_control().from_ds());
+        assert!(frame.frame_control().is_qos());
+        assert_eq!(frame.duration(), 44);
+        assert_eq!(frame.addresses().len(), 3);
+        assert_eq!(frame.bssid(), Some(MacAddress([0x00u8, 0x11u8, 0x22u8, 0x33u8, 0x44u8, 0x55u8])));
+        assert_eq!(frame.src_mac(), Some(MacAddress([0x66u8, 0x77u8, 0x88u8, 0x99u8, 0xAAu8, 0xBBu8])));
+        assert_eq!(frame.dst_mac(), Some(MacAddress([0x02u8, 0x02u8, 0x03u8, 0x04u8, 0x05u8, 0x06u8])));
+        assert_eq!(frame.sequence_number(), Some(21));
+        assert_eq!(frame.fragment_number(), Some(0));
+        assert_eq!(frame.tid(), Some(5));
+        assert_eq!(frame.ether_type(), Some(EthernetTypeId::L3(Layer3Id::IPv4)));
+        assert_eq!(frame.header_length(), 34);
+        assert_eq!(frame.payload().len(), 44);
+    }
+
+    #[test]
+    fn parse_four_address_frame() {
+        let _ = env_logger::try_init();
+
+        let mut data = RAW_DATA[..24].to_vec();
+        data[1] = TO_DS_FLAG | FROM_DS_FLAG;
+        data.extend_from_slice(&[0x0Au8, 0x0Bu8, 0x0Cu8, 0x0Du8, 0x0Eu8, 0x0Fu8]); //address 4, source
+        data.extend_from_slice(&RAW_DATA[24..]);
+
+        let (_, frame) = Ieee80211::parse(&data).expect("Unable to parse");
+
+        assert_eq!(frame.addresses().len(), 4);
+        assert_eq!(frame.src_mac(), Some(MacAddress([0x0Au8, 0x0Bu8, 0x0Cu8, 0x0Du8, 0x0Eu8, 0x0Fu8])));
+        assert_eq!(frame.dst_mac(), Some(MacAddress([0x02u8, 0x02u8, 0x03u8, 0x04u8, 0x05u8, 0x06u8])));
+        assert_eq!(frame.bssid(), None);
+        assert_eq!(frame.ether_type(), Some(EthernetTypeId::L3(Layer3Id::IPv4)));
+    }
+
+    #[test]
+    fn parse_management_and_control_frames() {
+        let _ = env_logger::try_init();
+
+        let beacon = [
+            0x80u8, 0x00u8, //frame control, beacon
+            0x00u8, 0x00u8, //duration
+            0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8, //destination, broadcast
+            0x00u8, 0x11u8, 0x22u8, 0x33u8, 0x44u8, 0x55u8, //source
+            0x00u8, 0x11u8, 0x22u8, 0x33u8, 0x44u8, 0x55u8, //bssid
+            0x10u8, 0x00u8, //sequence control, sequence 1
+            0x01u8, 0x02u8, 0x03u8 //body
+        ];
+        let (_, frame) = Ieee80211::parse(&beacon).expect("Unable to parse");
+        assert_eq!(frame.frame_control().frame_type(), FrameType::Management);
+        assert_eq!(frame.bssid(), Some(MacAddress([0x00u8, 0x11u8, 0x22u8, 0x33u8, 0x44u8, 0x55u8])));
+        assert_eq!(frame.sequence_number(), Some(1));
+        assert_eq!(frame.ether_type(), None);
+        assert_eq!(frame.payload().len(), 3);
+        assert!(Layer2FlowInfo::try_from(frame).is_err());
+
+        let ack = [
+            0xD4u8, 0x00u8, //frame control, ack
+            0x00u8, 0x00u8, //duration
+            0x00u8, 0x11u8, 0x22u8, 0x33u8, 0x44u8, 0x55u8 //receiver
+        ];
+        let (_, frame) = Ieee80211::parse(&ack).expect("Unable to parse");
+        assert_eq!(frame.frame_control().frame_type(), FrameType::Control);
+        assert_eq!(frame.addresses().len(), 1);
+        assert_eq!(frame.sequence_control(), None);
+        assert_eq!(frame.src_mac(), None);
+        assert!(frame.payload().is_empty());
+
+        assert!(Ieee80211::parse(&ack[..8]).is_err());
+    }
+
+    #[test]
+    fn convert_ieee80211() {
+        let _ = env_logger::try_init();
+
+        let (_, frame) = Ieee80211::parse(RAW_DATA).expect("Unable to parse");
+        let info = Layer2FlowInfo::try_from(frame).expect("Could not convert to layer 2 flow info");
+
+        assert_eq!(info.src_mac, Some(MacAddress([0x66u8, 0x77u8, 0x88u8, 0x99u8, 0xAAu8, 0xBBu8])));
+        assert_eq!(info.dst_mac, Some(MacAddress([0x02u8, 0x02u8, 0x03u8, 0x04u8, 0x05u8, 0x06u8])));
+        assert_eq!(info.delivery, Delivery::Unicast);
+        assert_eq!(info.layer3.dst_ip, "10.0.0.1".parse::<std::net::IpAddr>().expect("Could not parse ip address"));
+        assert_eq!(info.layer3.layer4.src_port, 50871);
+        assert_eq!(info.layer3.layer4.dst_port, 80);
+
+        let mut protected = RAW_DATA.to_vec();
+        protected[1] |= PROTECTED_FLAG;
+        let (_, frame) = Ieee80211::parse(&protected).expect("Unable to parse");
+        assert_eq!(frame.ether_type(), None);
+        assert!(Layer2FlowInfo::try_from(frame).is_err());
+    }
+}
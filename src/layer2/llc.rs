@@ -0,0 +1,241 @@
+use super::prelude::*;
+
+use self::nom::*;
+use super::ethernet::{EthernetTypeId, Layer3Id};
+use super::stp::Bpdu;
+
+use std;
+
+const SNAP_SAP: u8 = 0xAA;
+const IP_SAP: u8 = 0x06;
+const STP_SAP: u8 = 0x42;
+const UNNUMBERED_FORMAT: u8 = 0x03;
+
+///
+/// Organizations whose SNAP protocols are ethernet types: RFC 1042 encapsulation and 802.1H bridge
+/// tunnels
+///
+const RFC1042_OUI: u32 = 0x000000;
+const BRIDGE_TUNNEL_OUI: u32 = 0x0000F8;
+const CISCO_OUI: u32 = 0x00000C;
+const CDP_PROTOCOL: u16 = 0x2000;
+
+///
+/// Protocol an LLC frame carries, from its service access points or SNAP header
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    ///
+    /// Protocol identified by an ethernet type, from a SNAP header or the ip service access point
+    ///
+    EtherType(EthernetTypeId),
+    ///
+    /// Spanning tree bridge protocol data unit
+    ///
+    Stp,
+    ///
+    /// Cisco discovery protocol
+    ///
+    Cdp,
+    Unknown
+}
+
+///
+/// Subnetwork access protocol header, extending LLC frames to the SNAP service access point with
+/// an organization and protocol
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Snap {
+    oui: u32,
+    protocol: u16
+}
+
+impl Snap {
+    ///
+    /// Organizationally unique identifier qualifying the protocol, 0 for ethernet types
+    ///
+    pub fn oui(&self) -> u32 { self.oui }
+    pub fn protocol(&self) -> u16 { self.protocol }
+}
+
+///
+/// IEEE 802.2 logical link control header of an 802.3 frame, whose ethernet type field is the
+/// length of its payload
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Llc {
+    dsap: u8,
+    ssap: u8,
+    control: u16,
+    snap: Option<Snap>,
+    header_length: usize,
+    payload: std::vec::Vec<u8>
+}
+
+impl Llc {
+    ///
+    /// Destination service access point
+    ///
+    pub fn dsap(&self) -> u8 { self.dsap }
+
+    ///
+    /// Source service access point, with the low bit set for responses
+    ///
+    pub fn ssap(&self) -> u8 { self.ssap }
+
+    ///
+    /// Control field, one byte for unnumbered frames and two for information and supervisory frames
+    ///
+    pub fn control(&self) -> u16 { self.control }
+
+    pub fn snap(&self) -> Option<&Snap> { self.snap.as_ref() }
+
+    ///
+    /// Length of the LLC header, and SNAP header when present, preceding the payload
+    ///
+    pub fn header_length(&self) -> usize { self.header_length }
+
+    pub fn protocol(&self) -> Protocol {
+        match self.snap {
+            Some(Snap { oui: RFC1042_OUI, protocol }) | Some(Snap { oui: BRIDGE_TUNNEL_OUI, protocol }) => {
+                EthernetTypeId::new(protocol).map(Protocol::EtherType).unwrap_or(Protocol::Unknown)
+            }
+            Some(Snap { oui: CISCO_OUI, protocol: CDP_PROTOCOL }) => Protocol::Cdp,
+            Some(_) => Protocol::Unknown,
+            None if self.dsap == IP_SAP => Protocol::EtherType(EthernetTypeId::L3(Layer3Id::IPv4)),
+            None if self.dsap == STP_SAP => Protocol::Stp,
+            None => Protocol::Unknown
+        }
+    }
+
+    ///
+    /// Ethernet type of the payload, for SNAP frames of ethernet types and frames to the ip service
+    /// access point
+    ///
+    pub fn ether_type(&self) -> Option<EthernetTypeId> {
+        match self.protocol() {
+            Protocol::EtherType(ether_type) => Some(ether_type),
+            _ => None
+        }
+    }
+
+    pub fn payload(&self) -> &std::vec::Vec<u8> { &self.payload }
+
+    ///
+    /// Spanning tree BPDU of a frame to the spanning tree service access point
+    ///
+    pub fn bpdu(&self) -> Result<Bpdu, errors::Error> {
+        match self.protocol() {
+            Protocol::Stp => Bpdu::parse(&self.payload)
+                .map(|(_, bpdu)| bpdu)
+                .map_err(|e| errors::Error::from_nom(e, errors::Layer::Stp, &self.payload).offset_by(self.header_length)),
+            other => Err(format!("LLC protocol {:?} is not spanning tree", other).into())
+        }
+    }
+
+    ///
+    /// Parse the LLC header of an 802.3 payload, which should exclude any ethernet padding
+    ///
+    pub fn parse(input: &[u8]) -> nom::IResult<&[u8], Llc> {
+        layer_span!("llc", length = input.len());
+
+        do_parse!(input,
+
+            dsap: be_u8 >>
+            ssap: be_u8 >>
+            first: be_u8 >>
+            second: cond!(first & UNNUMBERED_FORMAT != UNNUMBERED_FORMAT, be_u8) >>
+            snap: cond!(dsap == SNAP_SAP && ssap & 0xFE == SNAP_SAP, do_parse!(
+                oui: be_u24 >>
+                protocol: be_u16 >>
+                ( Snap { oui: oui, protocol: protocol } )
+            )) >>
+            payload: rest >>
+
+            (
+                Llc {
+                    dsap: dsap,
+                    ssap: ssap,
+                    control: second.map(|s| u16::from(first) | u16::from(s) << 8).unwrap_or_else(|| u16::from(first)),
+                    snap: snap,
+                    header_length: input.len() - payload.len(),
+                    payload: payload.into()
+                }
+            )
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    const SNAP_RAW_DATA: &'static [u8] = &[
+        0xAAu8, //dsap, snap
+        0xAAu8, //ssap, snap
+        0x03u8, //control, unnumbered information
+        0x00u8, 0x00u8, 0x00u8, //oui, rfc 1042
+        0x08u8, 0x00u8, //protocol, ipv4
+        //payload
+        0x45u8, 0x00u8
+    ];
+
+    #[test]
+    fn parse_snap() {
+        let _ = env_logger::try_init();
+
+        let (rem, llc) = Llc::parse(SNAP_RAW_DATA).expect("Unable to parse");
+
+        assert!(rem.is_empty());
+        assert_eq!(llc.dsap(), 0xAA);
+        assert_eq!(llc.control(), 0x03);
+        assert_eq!(llc.snap(), Some(&Snap { oui: 0, protocol: 0x0800 }));
+        assert_eq!(llc.header_length(), 8);
+        assert_eq!(llc.protocol(), Protocol::EtherType(EthernetTypeId::L3(Layer3Id::IPv4)));
+        assert_eq!(llc.ether_type(), Some(EthernetTypeId::L3(Layer3Id::IPv4)));
+        assert_eq!(llc.payload(), &vec![0x45u8, 0x00u8]);
+
+        let mut cdp = SNAP_RAW_DATA.to_vec();
+        cdp[5] = 0x0C;
+        cdp[6] = 0x20;
+        let (_, llc) = Llc::parse(&cdp).expect("Unable to parse");
+        assert_eq!(llc.protocol(), Protocol::Cdp);
+        assert_eq!(llc.ether_type(), None);
+
+        assert!(Llc::parse(&SNAP_RAW_DATA[..5]).is_err());
+    }
+
+    #[test]
+    fn parse_llc() {
+        let _ = env_logger::try_init();
+
+        let stp = [
+            0x42u8, //dsap, stp
+            0x42u8, //ssap, stp
+            0x03u8, //control, unnumbered information
+            0x00u8, 0x00u8, 0x00u8, //bpdu protocol and version
+            0x80u8 //bpdu type, topology change notification
+        ];
+        let (_, llc) = Llc::parse(&stp).expect("Unable to parse");
+        assert_eq!(llc.protocol(), Protocol::Stp);
+        assert_eq!(llc.snap(), None);
+        assert_eq!(llc.header_length(), 3);
+        assert_eq!(llc.payload().len(), 4);
+        let bpdu = llc.bpdu().expect("Unable to parse bpdu");
+        assert_eq!(bpdu.bpdu_type(), ::layer2::stp::BpduType::TopologyChangeNotification);
+
+        let information = [
+            0xF0u8, //dsap, netbios
+            0xF0u8, //ssap, netbios
+            0x0Au8, 0x01u8, //control, information frame
+            0x01u8
+        ];
+        let (_, llc) = Llc::parse(&information).expect("Unable to parse");
+        assert_eq!(llc.control(), 0x010A);
+        assert_eq!(llc.protocol(), Protocol::Unknown);
+        assert_eq!(llc.header_length(), 4);
+        assert!(llc.bpdu().is_err());
+    }
+}
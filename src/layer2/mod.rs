@@ -4,23 +4,184 @@ pub mod prelude {
 }
 
 pub mod ethernet;
+pub mod ieee80211;
+pub mod llc;
+pub mod null;
+pub mod ppp;
+pub mod pppoe;
+pub mod raw;
+pub mod sll;
+pub mod sll2;
+pub mod stp;
 
 use super::common::*;
+use super::errors;
 use super::layer3::Layer3FlowInfo;
 
 ///
 /// Layer2 types that can be parsed
 ///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Layer2 {
-    Ethernet(ethernet::Ethernet)
+    Ethernet(ethernet::Ethernet),
+    Sll(sll::Sll),
+    Sll2(sll2::Sll2),
+    ///
+    /// Loopback header of NULL and LOOP captures
+    ///
+    Null(null::Null),
+    Ppp(ppp::Ppp),
+    ///
+    /// PPPoE header captured without an ethernet header (LINKTYPE_PPP_ETHER), with the PPP frame of
+    /// session data
+    ///
+    PppEther(pppoe::Pppoe, Option<ppp::Ppp>),
+    Ieee80211(ieee80211::Ieee80211),
+    ///
+    /// Raw ip packet, which has no link layer header
+    ///
+    Raw(std::vec::Vec<u8>)
+}
+
+impl Layer2 {
+    ///
+    /// Ethernet type of the payload, `None` when the link layer doesn't carry one, e.g. for 802.11
+    /// management frames, PPP control frames or PPPoE discovery
+    ///
+    pub fn ether_type(&self) -> Option<ethernet::EthernetTypeId> {
+        match *self {
+            Layer2::Ethernet(ref ethernet) => Some(ethernet.ether_type().clone()),
+            Layer2::Sll(ref sll) => sll.ether_type(),
+            Layer2::Sll2(ref sll2) => sll2.ether_type(),
+            Layer2::Null(ref null) => null.ether_type(),
+            Layer2::Ppp(ref ppp) => ppp.ether_type(),
+            Layer2::PppEther(_, ref ppp) => ppp.as_ref().and_then(|p| p.ether_type()),
+            Layer2::Ieee80211(ref frame) => frame.ether_type(),
+            Layer2::Raw(ref payload) => raw::ether_type(payload)
+        }
+    }
+
+    ///
+    /// Length of the link layer headers preceding the payload
+    ///
+    pub fn header_length(&self) -> usize {
+        match *self {
+            Layer2::Ethernet(ref ethernet) => ethernet::Ethernet::header_length(ethernet.vlans()),
+            Layer2::Sll(_) => sll::HEADER_LENGTH,
+            Layer2::Sll2(_) => sll2::HEADER_LENGTH,
+            Layer2::Null(_) => null::HEADER_LENGTH,
+            Layer2::Ppp(ref ppp) => ppp.header_length(),
+            Layer2::PppEther(_, ref ppp) => pppoe::HEADER_LENGTH + ppp.as_ref().map(|p| p.header_length()).unwrap_or(0),
+            Layer2::Ieee80211(ref frame) => frame.header_length(),
+            Layer2::Raw(_) => 0
+        }
+    }
+
+    ///
+    /// Bytes following the link layer headers, e.g. an ip datagram
+    ///
+    pub fn payload(&self) -> &[u8] {
+        match *self {
+            Layer2::Ethernet(ref ethernet) => ethernet.payload(),
+            Layer2::Sll(ref sll) => sll.payload(),
+            Layer2::Sll2(ref sll2) => sll2.payload(),
+            Layer2::Null(ref null) => null.payload(),
+            Layer2::Ppp(ref ppp) => ppp.payload(),
+            Layer2::PppEther(_, Some(ref ppp)) => ppp.payload(),
+            Layer2::PppEther(ref pppoe, None) => pppoe.payload(),
+            Layer2::Ieee80211(ref frame) => frame.payload(),
+            Layer2::Raw(ref payload) => payload
+        }
+    }
+
+    ///
+    /// Ethernet header of ethernet records
+    ///
+    pub fn ethernet(&self) -> Option<&ethernet::Ethernet> {
+        match *self {
+            Layer2::Ethernet(ref ethernet) => Some(ethernet),
+            _ => None
+        }
+    }
+
+    ///
+    /// Source mac, absent for link types without one, e.g. raw ip, or for linux cooked packets
+    /// captured on other than ethernet devices
+    ///
+    pub fn src_mac(&self) -> Option<MacAddress> {
+        match *self {
+            Layer2::Ethernet(ref ethernet) => Some(*ethernet.src_mac()),
+            Layer2::Sll(ref sll) => sll.src_mac(),
+            Layer2::Sll2(ref sll2) => sll2.src_mac(),
+            Layer2::Ieee80211(ref frame) => frame.src_mac(),
+            _ => None
+        }
+    }
+
+    ///
+    /// Destination mac, absent for link types without one, e.g. raw ip or linux cooked captures
+    ///
+    pub fn dst_mac(&self) -> Option<MacAddress> {
+        match *self {
+            Layer2::Ethernet(ref ethernet) => Some(*ethernet.dst_mac()),
+            Layer2::Ieee80211(ref frame) => frame.dst_mac(),
+            _ => None
+        }
+    }
+
+    ///
+    /// Delivery from the destination mac, or the packet type of linux cooked packets, or otherwise
+    /// from `dst_ip`
+    ///
+    pub fn delivery(&self, dst_ip: Option<&std::net::IpAddr>) -> Delivery {
+        let packet_type = match *self {
+            Layer2::Sll(ref sll) => Some(sll.packet_type()),
+            Layer2::Sll2(ref sll2) => Some(sll2.packet_type()),
+            _ => None
+        };
+        match (packet_type, self.dst_mac(), dst_ip) {
+            (Some(sll::PacketType::Broadcast), _, _) => Delivery::Broadcast,
+            (Some(sll::PacketType::Multicast), _, _) => Delivery::Multicast,
+            (_, Some(ref mac), ip) => Delivery::classify(mac, ip),
+            (_, None, Some(ip)) => Delivery::classify_ip(ip),
+            (_, None, None) => Delivery::Unicast
+        }
+    }
+
+    ///
+    /// Error for a payload without an ip datagram or ARP packet to take flow information from
+    ///
+    pub(crate) fn unsupported(&self) -> errors::Error {
+        match *self {
+            Layer2::Ethernet(ref ethernet) => errors::Error::from_kind(errors::ErrorKind::EthernetType(ethernet.ether_type().clone())),
+            Layer2::Sll(ref sll) => format!("Unsupported cooked capture protocol {:04x}", sll.protocol()).into(),
+            Layer2::Sll2(ref sll2) => format!("Unsupported cooked capture protocol {:04x}", sll2.protocol()).into(),
+            Layer2::Null(ref null) => format!("Unsupported loopback address family {}", null.family()).into(),
+            Layer2::Ppp(ref ppp) | Layer2::PppEther(_, Some(ref ppp)) => format!("Unsupported PPP protocol {:04x}", ppp.protocol().value()).into(),
+            Layer2::PppEther(ref pppoe, None) => format!("PPPoE discovery packet {:?} has no PPP frame", pppoe.code()).into(),
+            Layer2::Ieee80211(ref frame) => format!(
+                "No ethernet type in 802.11 {:?} frame of subtype {}",
+                frame.frame_control().frame_type(),
+                frame.frame_control().subtype()
+            ).into(),
+            Layer2::Raw(ref payload) => format!("Unsupported raw ip version {:?}", payload.first().map(|b| b >> 4)).into()
+        }
+    }
 }
 
 ///
-/// Information from Layer 2 protocols used in flow determination
+/// Information from Layer 2 protocols used in flow determination. Mac addresses are absent for
+/// link types without them, e.g. raw ip, or without a destination, e.g. linux cooked captures.
 ///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Layer2FlowInfo {
-    pub src_mac: MacAddress,
-    pub dst_mac: MacAddress,
+    pub src_mac: Option<MacAddress>,
+    pub dst_mac: Option<MacAddress>,
     pub vlan: Vlan,
+    ///
+    /// Tags inside `vlan`, outermost first, e.g. the customer tag of an 802.1ad frame
+    ///
+    pub inner_vlans: std::vec::Vec<Vlan>,
+    pub delivery: Delivery,
     pub layer3: Layer3FlowInfo
 }
@@ -0,0 +1,168 @@
+use super::prelude::*;
+
+use self::nom::*;
+use super::ethernet::{self, EthernetTypeId, Layer3Id};
+
+use std;
+use std::convert::TryFrom;
+use super::Layer2FlowInfo;
+
+pub const HEADER_LENGTH: usize = 4;
+
+///
+/// Address families of ip packets, which differ between operating systems for ipv6
+///
+const AF_INET: u32 = 2;
+const AF_INET6_LINUX: u32 = 10;
+const AF_INET6_BSD: u32 = 24;
+const AF_INET6_FREEBSD: u32 = 28;
+const AF_INET6_DARWIN: u32 = 30;
+
+///
+/// Null (BSD loopback) header, the address family of the packet in the byte order of the host that
+/// captured it. https://www.tcpdump.org/linktypes/LINKTYPE_NULL.html
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Null {
+    family: u32,
+    payload: std::vec::Vec<u8>
+}
+
+impl Null {
+    ///
+    /// Address family of the payload, e.g. 2 for AF_INET
+    ///
+    pub fn family(&self) -> u32 { self.family }
+
+    ///
+    /// Ethernet type of the payload, for ipv4 and the ipv6 families of BSD, FreeBSD, macOS and Linux
+    ///
+    pub fn ether_type(&self) -> Option<EthernetTypeId> {
+        match self.family {
+            AF_INET => Some(EthernetTypeId::L3(Layer3Id::IPv4)),
+            AF_INET6_LINUX | AF_INET6_BSD | AF_INET6_FREEBSD | AF_INET6_DARWIN => Some(EthernetTypeId::L3(Layer3Id::IPv6)),
+            _ => None
+        }
+    }
+
+    pub fn payload(&self) -> &std::vec::Vec<u8> { &self.payload }
+
+    ///
+    /// Parse a header whose family is written in byte order `endianness`, the byte order of the
+    /// capture for null captures, or big endian for OpenBSD loopback (LINKTYPE_LOOP) captures
+    ///
+    pub fn parse(input: &[u8], endianness: Endianness) -> nom::IResult<&[u8], Null> {
+        layer_span!("null", length = input.len());
+
+        do_parse!(input,
+
+            family: u32!(endianness) >>
+            payload: rest >>
+
+            (
+                Null {
+                    family: family,
+                    payload: payload.into()
+                }
+            )
+        )
+    }
+}
+
+impl TryFrom<Null> for Layer2FlowInfo {
+    type Error = errors::Error;
+
+    ///
+    /// Flow information of a loopback packet, which has no mac addresses or vlan
+    ///
+    fn try_from(value: Null) -> Result<Self, Self::Error> {
+        layer_span!("null_flow_info", family = value.family);
+        let ether_type = value.ether_type()
+            .ok_or_else(|| errors::Error::from(format!("Unsupported loopback address family {}", value.family)))?;
+        let l3 = ethernet::layer3_flow_info(ether_type, &value.payload)
+            .map_err(|e| e.offset_by(HEADER_LENGTH))?;
+
+        Ok(Layer2FlowInfo {
+            src_mac: None,
+            dst_mac: None,
+            vlan: Vlan::untagged(),
+            inner_vlans: vec![],
+            delivery: Delivery::classify_ip(&l3.dst_ip),
+            layer3: l3
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    const RAW_DATA: &'static [u8] = &[
+        0x02u8, 0x00u8, 0x00u8, 0x00u8, //family, AF_INET little endian
+        //ipv4
+        0x45u8, //version and header length
+        0x00u8, //tos
+        0x00u8, 0x2Cu8, //length, 20 bytes for header, 24 bytes for tcp
+        0x00u8, 0x00u8, //id
+        0x00u8, 0x00u8, //flags
+        0x40u8, //ttl
+        0x06u8, //protocol, tcp
+        0x00u8, 0x00u8, //checksum
+        0x7Fu8, 0x00u8, 0x00u8, 0x01u8, //src ip 127.0.0.1
+        0x7Fu8, 0x00u8, 0x00u8, 0x01u8, //dst ip 127.0.0.1
+        //tcp
+        0xC6u8, 0xB7u8, //src port, 50871
+        0x1Fu8, 0x90u8, //dst port, 8080
+        0x00u8, 0x00u8, 0x00u8, 0x01u8, //sequence number, 1
+        0x00u8, 0x00u8, 0x00u8, 0x02u8, //acknowledgement number, 2
+        0x50u8, 0x00u8, //header and flags, 0
+        0x00u8, 0x00u8, //window
+        0x00u8, 0x00u8, //check
+        0x00u8, 0x00u8, //urgent
+        //no options
+        //payload
+        0xfcu8, 0xfdu8, 0xfeu8, 0xffu8
+    ];
+
+    #[test]
+    fn parse_null() {
+        let _ = env_logger::try_init();
+
+        let (rem, null) = Null::parse(RAW_DATA, Endianness::Little).expect("Unable to parse");
+
+        assert!(rem.is_empty());
+        assert_eq!(null.family(), 2);
+        assert_eq!(null.ether_type(), Some(EthernetTypeId::L3(Layer3Id::IPv4)));
+        assert_eq!(null.payload().len(), 44);
+
+        let (_, swapped) = Null::parse(RAW_DATA, Endianness::Big).expect("Unable to parse");
+        assert_eq!(swapped.family(), 0x02000000);
+        assert_eq!(swapped.ether_type(), None);
+
+        for family in &[10u32, 24, 28, 30] {
+            let mut data = RAW_DATA.to_vec();
+            data[0] = *family as u8;
+            let (_, null) = Null::parse(&data, Endianness::Little).expect("Unable to parse");
+            assert_eq!(null.ether_type(), Some(EthernetTypeId::L3(Layer3Id::IPv6)));
+        }
+    }
+
+    #[test]
+    fn convert_null() {
+        let _ = env_logger::try_init();
+
+        let (_, null) = Null::parse(RAW_DATA, Endianness::Little).expect("Unable to parse");
+        let info = Layer2FlowInfo::try_from(null).expect("Could not convert to layer 2 flow info");
+
+        assert_eq!(info.src_mac, None);
+        assert_eq!(info.delivery, Delivery::Unicast);
+        assert_eq!(info.layer3.dst_ip, "127.0.0.1".parse::<std::net::IpAddr>().expect("Could not parse ip address"));
+        assert_eq!(info.layer3.layer4.src_port, 50871);
+        assert_eq!(info.layer3.layer4.dst_port, 8080);
+
+        let (_, swapped) = Null::parse(RAW_DATA, Endianness::Big).expect("Unable to parse");
+        assert!(Layer2FlowInfo::try_from(swapped).is_err());
+    }
+}
@@ -0,0 +1,397 @@
+use super::prelude::*;
+
+use self::nom::*;
+use self::layer3::Layer3FlowInfo;
+use super::ethernet::{self, EthernetTypeId, Layer3Id};
+
+use std;
+use std::convert::TryFrom;
+use super::Layer2FlowInfo;
+
+///
+/// Address and control fields of PPP in HDLC-like framing, omitted when negotiated away and in
+/// PPPoE sessions
+///
+const HDLC_HEADER: &'static [u8] = &[0xFFu8, 0x03u8];
+const CONTROL_HEADER_LENGTH: usize = 4;
+const OPTION_HEADER_LENGTH: usize = 2;
+
+const CONFIGURE_REJECT: u8 = 4;
+const IPCP_IP_ADDRESS: u8 = 3;
+const IPV6CP_INTERFACE_IDENTIFIER: u8 = 1;
+
+///
+/// Protocol of a PPP payload. https://www.iana.org/assignments/ppp-numbers
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    IPv4,
+    IPv6,
+    ///
+    /// IP control protocol, negotiating ipv4 addresses
+    ///
+    Ipcp,
+    ///
+    /// IPv6 control protocol, negotiating interface identifiers
+    ///
+    Ipv6cp,
+    ///
+    /// Link control protocol
+    ///
+    Lcp,
+    Pap,
+    Chap,
+    Unknown(u16)
+}
+
+impl Protocol {
+    pub fn new(value: u16) -> Protocol {
+        match value {
+            0x0021 => Protocol::IPv4,
+            0x0057 => Protocol::IPv6,
+            0x8021 => Protocol::Ipcp,
+            0x8057 => Protocol::Ipv6cp,
+            0xC021 => Protocol::Lcp,
+            0xC023 => Protocol::Pap,
+            0xC223 => Protocol::Chap,
+            other => Protocol::Unknown(other)
+        }
+    }
+
+    pub fn value(&self) -> u16 {
+        match *self {
+            Protocol::IPv4 => 0x0021,
+            Protocol::IPv6 => 0x0057,
+            Protocol::Ipcp => 0x8021,
+            Protocol::Ipv6cp => 0x8057,
+            Protocol::Lcp => 0xC021,
+            Protocol::Pap => 0xC023,
+            Protocol::Chap => 0xC223,
+            Protocol::Unknown(value) => value
+        }
+    }
+
+    ///
+    /// Whether payloads of the protocol are control packets in the LCP format
+    ///
+    pub fn is_control(&self) -> bool {
+        matches!(*self, Protocol::Ipcp | Protocol::Ipv6cp | Protocol::Lcp)
+    }
+}
+
+///
+/// Configuration option of a control packet
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ControlOption {
+    option_type: u8,
+    data: std::vec::Vec<u8>
+}
+
+impl ControlOption {
+    pub fn option_type(&self) -> u8 { self.option_type }
+    pub fn data(&self) -> &std::vec::Vec<u8> { &self.data }
+
+    fn parse(input: &[u8]) -> nom::IResult<&[u8], ControlOption> {
+        do_parse!(input,
+
+            option_type: be_u8 >>
+            length: verify!(be_u8, |l| l as usize >= OPTION_HEADER_LENGTH) >>
+            data: take!(length as usize - OPTION_HEADER_LENGTH) >>
+
+            (
+                ControlOption {
+                    option_type: option_type,
+                    data: data.into()
+                }
+            )
+        )
+    }
+}
+
+///
+/// Control packet of LCP, IPCP or IPv6CP, with the options of configure requests, acks, naks and
+/// rejects. https://tools.ietf.org/html/rfc1661#section-5
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ControlPacket {
+    code: u8,
+    identifier: u8,
+    options: std::vec::Vec<ControlOption>,
+    data: std::vec::Vec<u8>
+}
+
+impl ControlPacket {
+    pub fn code(&self) -> u8 { self.code }
+    pub fn identifier(&self) -> u8 { self.identifier }
+
+    ///
+    /// Options of configure packets, empty for other codes
+    ///
+    pub fn options(&self) -> &std::vec::Vec<ControlOption> { &self.options }
+
+    ///
+    /// Data following the packet header, the encoded options of configure packets
+    ///
+    pub fn data(&self) -> &std::vec::Vec<u8> { &self.data }
+
+    fn option(&self, option_type: u8, length: usize) -> Option<&[u8]> {
+        self.options.iter()
+            .find(|o| o.option_type == option_type && o.data.len() == length)
+            .map(|o| &o.data[..])
+    }
+
+    ///
+    /// Ipv4 address of an IPCP configure packet
+    ///
+    pub fn ip_address(&self) -> Option<std::net::Ipv4Addr> {
+        self.option(IPCP_IP_ADDRESS, 4).map(|a| std::net::Ipv4Addr::from(*array_ref![a, 0, 4]))
+    }
+
+    ///
+    /// Interface identifier of an IPv6CP configure packet
+    ///
+    pub fn interface_identifier(&self) -> Option<u64> {
+        self.option(IPV6CP_INTERFACE_IDENTIFIER, 8).map(|i| {
+            i.iter().fold(0u64, |acc, b| (acc << 8) | u64::from(*b))
+        })
+    }
+
+    ///
+    /// Link local address the interface identifier of an IPv6CP configure packet forms
+    ///
+    pub fn link_local_address(&self) -> Option<std::net::Ipv6Addr> {
+        self.interface_identifier().map(|id| {
+            std::net::Ipv6Addr::from((0xfe80u128 << 112) | u128::from(id))
+        })
+    }
+
+    pub fn parse(input: &[u8]) -> nom::IResult<&[u8], ControlPacket> {
+        let (rem, (code, identifier, data)) = do_parse!(input,
+
+            code: be_u8 >>
+            identifier: be_u8 >>
+            length: verify!(be_u16, |l| l as usize >= CONTROL_HEADER_LENGTH) >>
+            data: take!(length as usize - CONTROL_HEADER_LENGTH) >>
+
+            ( (code, identifier, data) )
+        )?;
+
+        let options = if (1..=CONFIGURE_REJECT).contains(&code) {
+            let (_, options) = many0!(data, complete!(ControlOption::parse))?;
+            options
+        } else {
+            vec![]
+        };
+
+        Ok((
+            rem,
+            ControlPacket {
+                code: code,
+                identifier: identifier,
+                options: options,
+                data: data.into()
+            }
+        ))
+    }
+}
+
+///
+/// Point to point protocol frame, with or without HDLC-like address and control fields and with a
+/// compressed or full protocol field. https://www.tcpdump.org/linktypes/LINKTYPE_PPP.html
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Ppp {
+    protocol: Protocol,
+    control: Option<ControlPacket>,
+    header_length: usize,
+    payload: std::vec::Vec<u8>
+}
+
+impl Ppp {
+    pub fn protocol(&self) -> Protocol { self.protocol }
+
+    ///
+    /// Control packet of LCP, IPCP and IPv6CP frames
+    ///
+    pub fn control(&self) -> Option<&ControlPacket> { self.control.as_ref() }
+
+    ///
+    /// Length of the address, control and protocol fields preceding the payload
+    ///
+    pub fn header_length(&self) -> usize { self.header_length }
+
+    ///
+    /// Ethernet type of ipv4 and ipv6 payloads
+    ///
+    pub fn ether_type(&self) -> Option<EthernetTypeId> {
+        match self.protocol {
+            Protocol::IPv4 => Some(EthernetTypeId::L3(Layer3Id::IPv4)),
+            Protocol::IPv6 => Some(EthernetTypeId::L3(Layer3Id::IPv6)),
+            _ => None
+        }
+    }
+
+    pub fn payload(&self) -> &std::vec::Vec<u8> { &self.payload }
+
+    pub fn parse(input: &[u8]) -> nom::IResult<&[u8], Ppp> {
+        layer_span!("ppp", length = input.len());
+
+        let framed = if input.starts_with(HDLC_HEADER) { &input[HDLC_HEADER.len()..] } else { input };
+
+        let (rem, protocol) = do_parse!(framed,
+
+            first: be_u8 >>
+            second: cond!(first & 0x01 == 0, be_u8) >>
+
+            ( Protocol::new(second.map(|s| u16::from(first) << 8 | u16::from(s)).unwrap_or_else(|| u16::from(first))) )
+        )?;
+
+        let control = if protocol.is_control() {
+            let (_, control) = ControlPacket::parse(rem)?;
+            Some(control)
+        } else {
+            None
+        };
+
+        Ok((
+            &[],
+            Ppp {
+                protocol: protocol,
+                control: control,
+                header_length: input.len() - rem.len(),
+                payload: rem.into()
+            }
+        ))
+    }
+
+    ///
+    /// Addresses and ports of the ip datagram a frame carries, with error offsets relative to the frame
+    ///
+    pub(crate) fn layer3_flow_info(&self) -> Result<Layer3FlowInfo, errors::Error> {
+        let ether_type = self.ether_type()
+            .ok_or_else(|| errors::Error::from(format!("Unsupported PPP protocol {:04x}", self.protocol.value())))?;
+        ethernet::layer3_flow_info(ether_type, &self.payload)
+            .map_err(|e| e.offset_by(self.header_length))
+    }
+}
+
+impl TryFrom<Ppp> for Layer2FlowInfo {
+    type Error = errors::Error;
+
+    ///
+    /// Flow information of a PPP frame carrying an ip datagram, which has no mac addresses or vlan
+    ///
+    fn try_from(value: Ppp) -> Result<Self, Self::Error> {
+        layer_span!("ppp_flow_info", protocol = value.protocol.value());
+        let l3 = value.layer3_flow_info()?;
+
+        Ok(Layer2FlowInfo {
+            src_mac: None,
+            dst_mac: None,
+            vlan: Vlan::untagged(),
+            inner_vlans: vec![],
+            delivery: Delivery::classify_ip(&l3.dst_ip),
+            layer3: l3
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    const RAW_DATA: &'static [u8] = &[
+        0xFFu8, 0x03u8, //address and control
+        0x00u8, 0x21u8, //protocol, ipv4
+        //ipv4
+        0x45u8, //version and header length
+        0x00u8, //tos
+        0x00u8, 0x2Cu8, //length, 20 bytes for header, 24 bytes for tcp
+        0x00u8, 0x00u8, //id
+        0x00u8, 0x00u8, //flags
+        0x40u8, //ttl
+        0x06u8, //protocol, tcp
+        0x00u8, 0x00u8, //checksum
+        0x0Au8, 0x00u8, 0x00u8, 0x02u8, //src ip 10.0.0.2
+        0x0Au8, 0x00u8, 0x00u8, 0x01u8, //dst ip 10.0.0.1
+        //tcp
+        0xC6u8, 0xB7u8, //src port, 50871
+        0x00u8, 0x50u8, //dst port, 80
+        0x00u8, 0x00u8, 0x00u8, 0x01u8, //sequence number, 1
+        0x00u8, 0x00u8, 0x00u8, 0x02u8, //acknowledgement number, 2
+        0x50u8, 0x00u8, //header and flags, 0
+        0x00u8, 0x00u8, //window
+        0x00u8, 0x00u8, //check
+        0x00u8, 0x00u8, //urgent
+        //no options
+        //payload
+        0xfcu8, 0xfdu8, 0xfeu8, 0xffu8
+    ];
+
+    const IPV6CP_RAW_DATA: &'static [u8] = &[
+        0x80u8, 0x57u8, //protocol, ipv6cp
+        0x01u8, //code, configure request
+        0x07u8, //identifier
+        0x00u8, 0x0Eu8, //length
+        0x01u8, 0x0Au8, //option, interface identifier
+        0x02u8, 0x11u8, 0x22u8, 0xFFu8, 0xFEu8, 0x33u8, 0x44u8, 0x55u8 //interface identifier
+    ];
+
+    #[test]
+    fn parse_ppp() {
+        let _ = env_logger::try_init();
+
+        let (rem, ppp) = Ppp::parse(RAW_DATA).expect("Unable to parse");
+
+        assert!(rem.is_empty());
+        assert_eq!(ppp.protocol(), Protocol::IPv4);
+        assert_eq!(ppp.header_length(), 4);
+        assert_eq!(ppp.ether_type(), Some(EthernetTypeId::L3(Layer3Id::IPv4)));
+        assert_eq!(ppp.control(), None);
+        assert_eq!(ppp.payload().len(), 44);
+
+        let (_, compressed) = Ppp::parse(&RAW_DATA[3..]).expect("Unable to parse");
+        assert_eq!(compressed.protocol(), Protocol::IPv4);
+        assert_eq!(compressed.header_length(), 1);
+        assert_eq!(compressed.payload(), ppp.payload());
+    }
+
+    #[test]
+    fn parse_ipv6cp() {
+        let _ = env_logger::try_init();
+
+        let (_, ppp) = Ppp::parse(IPV6CP_RAW_DATA).expect("Unable to parse");
+
+        assert_eq!(ppp.protocol(), Protocol::Ipv6cp);
+        assert_eq!(ppp.ether_type(), None);
+
+        let control = ppp.control().expect("No control packet");
+        assert_eq!(control.code(), 1);
+        assert_eq!(control.identifier(), 7);
+        assert_eq!(control.options().len(), 1);
+        assert_eq!(control.interface_identifier(), Some(0x021122FFFE334455u64));
+        assert_eq!(control.link_local_address(), Some("fe80::211:22ff:fe33:4455".parse().expect("Could not parse ip address")));
+        assert_eq!(control.ip_address(), None);
+
+        assert!(Ppp::parse(&IPV6CP_RAW_DATA[..5]).is_err());
+    }
+
+    #[test]
+    fn convert_ppp() {
+        let _ = env_logger::try_init();
+
+        let (_, ppp) = Ppp::parse(RAW_DATA).expect("Unable to parse");
+        let info = Layer2FlowInfo::try_from(ppp).expect("Could not convert to layer 2 flow info");
+
+        assert_eq!(info.src_mac, None);
+        assert_eq!(info.layer3.dst_ip, "10.0.0.1".parse::<std::net::IpAddr>().expect("Could not parse ip address"));
+        assert_eq!(info.layer3.layer4.src_port, 50871);
+        assert_eq!(info.layer3.layer4.dst_port, 80);
+
+        let (_, ppp) = Ppp::parse(IPV6CP_RAW_DATA).expect("Unable to parse");
+        assert!(Layer2FlowInfo::try_from(ppp).is_err());
+    }
+}
@@ -0,0 +1,374 @@
+use super::prelude::*;
+
+use self::nom::*;
+use super::ppp::Ppp;
+
+use std;
+use std::convert::TryFrom;
+use super::Layer2FlowInfo;
+
+pub const HEADER_LENGTH: usize = 6;
+
+///
+/// Code of a PPPoE packet, the discovery stage packet or session data
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Code {
+    Session,
+    ///
+    /// Active discovery initiation, broadcast by a host looking for access concentrators
+    ///
+    Padi,
+    ///
+    /// Active discovery offer from an access concentrator
+    ///
+    Pado,
+    ///
+    /// Active discovery request for an offered service
+    ///
+    Padr,
+    ///
+    /// Active discovery session confirmation, assigning the session id
+    ///
+    Pads,
+    ///
+    /// Active discovery termination of a session
+    ///
+    Padt,
+    Unknown(u8)
+}
+
+impl Code {
+    pub fn new(value: u8) -> Code {
+        match value {
+            0x00 => Code::Session,
+            0x09 => Code::Padi,
+            0x07 => Code::Pado,
+            0x19 => Code::Padr,
+            0x65 => Code::Pads,
+            0xA7 => Code::Padt,
+            other => Code::Unknown(other)
+        }
+    }
+
+    pub fn value(&self) -> u8 {
+        match *self {
+            Code::Session => 0x00,
+            Code::Padi => 0x09,
+            Code::Pado => 0x07,
+            Code::Padr => 0x19,
+            Code::Pads => 0x65,
+            Code::Padt => 0xA7,
+            Code::Unknown(value) => value
+        }
+    }
+}
+
+///
+/// Type of a discovery tag. https://tools.ietf.org/html/rfc2516#appendix-A
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TagType {
+    EndOfList,
+    ServiceName,
+    AcName,
+    HostUniq,
+    AcCookie,
+    VendorSpecific,
+    RelaySessionId,
+    ServiceNameError,
+    AcSystemError,
+    GenericError,
+    Unknown(u16)
+}
+
+impl TagType {
+    pub fn new(value: u16) -> TagType {
+        match value {
+            0x0000 => TagType::EndOfList,
+            0x0101 => TagType::ServiceName,
+            0x0102 => TagType::AcName,
+            0x0103 => TagType::HostUniq,
+            0x0104 => TagType::AcCookie,
+            0x0105 => TagType::VendorSpecific,
+            0x0110 => TagType::RelaySessionId,
+            0x0201 => TagType::ServiceNameError,
+            0x0202 => TagType::AcSystemError,
+            0x0203 => TagType::GenericError,
+            other => TagType::Unknown(other)
+        }
+    }
+
+    pub fn value(&self) -> u16 {
+        match *self {
+            TagType::EndOfList => 0x0000,
+            TagType::ServiceName => 0x0101,
+            TagType::AcName => 0x0102,
+            TagType::HostUniq => 0x0103,
+            TagType::AcCookie => 0x0104,
+            TagType::VendorSpecific => 0x0105,
+            TagType::RelaySessionId => 0x0110,
+            TagType::ServiceNameError => 0x0201,
+            TagType::AcSystemError => 0x0202,
+            TagType::GenericError => 0x0203,
+            TagType::Unknown(value) => value
+        }
+    }
+}
+
+///
+/// Tag of a discovery packet
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Tag {
+    tag_type: TagType,
+    value: std::vec::Vec<u8>
+}
+
+impl Tag {
+    pub fn tag_type(&self) -> TagType { self.tag_type }
+    pub fn value(&self) -> &std::vec::Vec<u8> { &self.value }
+
+    fn parse(input: &[u8]) -> nom::IResult<&[u8], Tag> {
+        do_parse!(input,
+
+            tag_type: map!(be_u16, TagType::new) >>
+            value: length_bytes!(be_u16) >>
+
+            (
+                Tag {
+                    tag_type: tag_type,
+                    value: value.into()
+                }
+            )
+        )
+    }
+}
+
+///
+/// PPPoE header, of discovery packets (ethernet type 0x8863) with their tags or of session data
+/// (ethernet type 0x8864) carrying a PPP frame. https://tools.ietf.org/html/rfc2516
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Pppoe {
+    version: u8,
+    pppoe_type: u8,
+    code: Code,
+    session_id: u16,
+    tags: std::vec::Vec<Tag>,
+    payload: std::vec::Vec<u8>
+}
+
+impl Pppoe {
+    pub fn version(&self) -> u8 { self.version }
+    pub fn pppoe_type(&self) -> u8 { self.pppoe_type }
+    pub fn code(&self) -> Code { self.code }
+    pub fn session_id(&self) -> u16 { self.session_id }
+
+    pub fn is_discovery(&self) -> bool { self.code != Code::Session }
+
+    ///
+    /// Tags of discovery packets, up to an end of list tag
+    ///
+    pub fn tags(&self) -> &std::vec::Vec<Tag> { &self.tags }
+
+    fn tag(&self, tag_type: TagType) -> Option<&Tag> {
+        self.tags.iter().find(|t| t.tag_type == tag_type)
+    }
+
+    ///
+    /// Service requested or offered, empty for any service
+    ///
+    pub fn service_name(&self) -> Option<std::string::String> {
+        self.tag(TagType::ServiceName).map(|t| std::string::String::from_utf8_lossy(&t.value).into_owned())
+    }
+
+    ///
+    /// Name of the access concentrator making an offer
+    ///
+    pub fn ac_name(&self) -> Option<std::string::String> {
+        self.tag(TagType::AcName).map(|t| std::string::String::from_utf8_lossy(&t.value).into_owned())
+    }
+
+    ///
+    /// Packet payload, as long as the header's length field excluding any ethernet padding
+    ///
+    pub fn payload(&self) -> &std::vec::Vec<u8> { &self.payload }
+
+    ///
+    /// PPP frame of session data
+    ///
+    pub fn ppp(&self) -> Result<Ppp, errors::Error> {
+        if self.is_discovery() {
+            return Err(format!("PPPoE discovery packet {:?} has no PPP frame", self.code).into())
+        }
+        Ppp::parse(&self.payload)
+            .map(|(_, ppp)| ppp)
+            .map_err(|e| errors::Error::from_nom(e, errors::Layer::Ppp, &self.payload).offset_by(HEADER_LENGTH))
+    }
+
+    ///
+    /// Parse a PPPoE header, returning any ethernet padding following the payload as remaining
+    ///
+    pub fn parse(input: &[u8]) -> nom::IResult<&[u8], Pppoe> {
+        layer_span!("pppoe", length = input.len());
+
+        let (rem, (version_type, code, session_id, payload)) = do_parse!(input,
+
+            version_type: be_u8 >>
+            code: map!(be_u8, Code::new) >>
+            session_id: be_u16 >>
+            payload: length_bytes!(be_u16) >>
+
+            ( (version_type, code, session_id, payload) )
+        )?;
+
+        let tags = if code == Code::Session {
+            vec![]
+        } else {
+            let (_, tags) = many0!(payload, complete!(Tag::parse))?;
+            tags.into_iter().take_while(|t| t.tag_type != TagType::EndOfList).collect()
+        };
+
+        Ok((
+            rem,
+            Pppoe {
+                version: version_type >> 4,
+                pppoe_type: version_type & 0x0F,
+                code: code,
+                session_id: session_id,
+                tags: tags,
+                payload: payload.into()
+            }
+        ))
+    }
+}
+
+impl TryFrom<Pppoe> for Layer2FlowInfo {
+    type Error = errors::Error;
+
+    ///
+    /// Flow information of PPPoE session data carrying an ip datagram, as captured without an
+    /// ethernet header (LINKTYPE_PPP_ETHER)
+    ///
+    fn try_from(value: Pppoe) -> Result<Self, Self::Error> {
+        layer_span!("pppoe_flow_info", session_id = value.session_id);
+        let l3 = value.ppp()?
+            .layer3_flow_info()
+            .map_err(|e| e.offset_by(HEADER_LENGTH))?;
+
+        Ok(Layer2FlowInfo {
+            src_mac: None,
+            dst_mac: None,
+            vlan: Vlan::untagged(),
+            inner_vlans: vec![],
+            delivery: Delivery::classify_ip(&l3.dst_ip),
+            layer3: l3
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+    use super::super::ppp::Protocol;
+
+    const RAW_DATA: &'static [u8] = &[
+        0x11u8, //version and type
+        0x00u8, //code, session data
+        0x12u8, 0x34u8, //session id
+        0x00u8, 0x2Eu8, //length, 2 bytes for ppp, 44 bytes for ipv4
+        //ppp
+        0x00u8, 0x21u8, //protocol, ipv4
+        //ipv4
+        0x45u8, //version and header length
+        0x00u8, //tos
+        0x00u8, 0x2Cu8, //length, 20 bytes for header, 24 bytes for tcp
+        0x00u8, 0x00u8, //id
+        0x00u8, 0x00u8, //flags
+        0x40u8, //ttl
+        0x06u8, //protocol, tcp
+        0x00u8, 0x00u8, //checksum
+        0x0Au8, 0x00u8, 0x00u8, 0x02u8, //src ip 10.0.0.2
+        0x0Au8, 0x00u8, 0x00u8, 0x01u8, //dst ip 10.0.0.1
+        //tcp
+        0xC6u8, 0xB7u8, //src port, 50871
+        0x00u8, 0x50u8, //dst port, 80
+        0x00u8, 0x00u8, 0x00u8, 0x01u8, //sequence number, 1
+        0x00u8, 0x00u8, 0x00u8, 0x02u8, //acknowledgement number, 2
+        0x50u8, 0x00u8, //header and flags, 0
+        0x00u8, 0x00u8, //window
+        0x00u8, 0x00u8, //check
+        0x00u8, 0x00u8, //urgent
+        //no options
+        //payload
+        0xfcu8, 0xfdu8, 0xfeu8, 0xffu8
+    ];
+
+    const PADO_RAW_DATA: &'static [u8] = &[
+        0x11u8, //version and type
+        0x07u8, //code, offer
+        0x00u8, 0x00u8, //session id
+        0x00u8, 0x14u8, //length
+        0x01u8, 0x01u8, 0x00u8, 0x00u8, //service name, any
+        0x01u8, 0x02u8, 0x00u8, 0x04u8, 0x42u8, 0x52u8, 0x41u8, 0x53u8, //ac name, BRAS
+        0x00u8, 0x00u8, 0x00u8, 0x00u8, //end of list
+        0x01u8, 0x03u8, 0x00u8, 0x00u8, //host uniq, after end of list
+        0x00u8, 0x00u8, //ethernet padding
+    ];
+
+    #[test]
+    fn parse_session() {
+        let _ = env_logger::try_init();
+
+        let (rem, pppoe) = Pppoe::parse(RAW_DATA).expect("Unable to parse");
+
+        assert!(rem.is_empty());
+        assert_eq!(pppoe.version(), 1);
+        assert_eq!(pppoe.pppoe_type(), 1);
+        assert_eq!(pppoe.code(), Code::Session);
+        assert_eq!(pppoe.session_id(), 0x1234);
+        assert!(!pppoe.is_discovery());
+        assert!(pppoe.tags().is_empty());
+        assert_eq!(pppoe.payload().len(), 46);
+
+        let ppp = pppoe.ppp().expect("No PPP frame");
+        assert_eq!(ppp.protocol(), Protocol::IPv4);
+        assert_eq!(ppp.payload().len(), 44);
+
+        assert!(Pppoe::parse(&RAW_DATA[..20]).is_err());
+    }
+
+    #[test]
+    fn parse_discovery() {
+        let _ = env_logger::try_init();
+
+        let (rem, pppoe) = Pppoe::parse(PADO_RAW_DATA).expect("Unable to parse");
+
+        assert_eq!(rem.len(), 2);
+        assert_eq!(pppoe.code(), Code::Pado);
+        assert!(pppoe.is_discovery());
+        assert_eq!(pppoe.tags().len(), 2);
+        assert_eq!(pppoe.tags()[0].tag_type(), TagType::ServiceName);
+        assert_eq!(pppoe.service_name(), Some("".to_string()));
+        assert_eq!(pppoe.ac_name(), Some("BRAS".to_string()));
+        assert!(pppoe.ppp().is_err());
+    }
+
+    #[test]
+    fn convert_pppoe() {
+        let _ = env_logger::try_init();
+
+        let (_, pppoe) = Pppoe::parse(RAW_DATA).expect("Unable to parse");
+        let info = Layer2FlowInfo::try_from(pppoe).expect("Could not convert to layer 2 flow info");
+
+        assert_eq!(info.src_mac, None);
+        assert_eq!(info.layer3.dst_ip, "10.0.0.1".parse::<std::net::IpAddr>().expect("Could not parse ip address"));
+        assert_eq!(info.layer3.layer4.dst_port, 80);
+
+        let (_, pppoe) = Pppoe::parse(PADO_RAW_DATA).expect("Unable to parse");
+        assert!(Layer2FlowInfo::try_from(pppoe).is_err());
+    }
+}
@@ -0,0 +1,98 @@
+use super::prelude::*;
+
+use super::ethernet::{self, EthernetTypeId, Layer3Id};
+
+use super::Layer2FlowInfo;
+
+const IP_VERSION_4: u8 = 4;
+const IP_VERSION_6: u8 = 6;
+
+///
+/// Ethernet type equivalent to a raw ip packet, e.g. from a capture on a tun interface, chosen
+/// from the version in the first nibble of the packet
+///
+pub fn ether_type(payload: &[u8]) -> Option<EthernetTypeId> {
+    match payload.first().map(|b| b >> 4) {
+        Some(IP_VERSION_4) => Some(EthernetTypeId::L3(Layer3Id::IPv4)),
+        Some(IP_VERSION_6) => Some(EthernetTypeId::L3(Layer3Id::IPv6)),
+        _ => None
+    }
+}
+
+///
+/// Flow information of a raw ip packet, which has no link layer header, so no mac addresses or vlan
+///
+pub fn flow_info(payload: &[u8]) -> Result<Layer2FlowInfo, errors::Error> {
+    layer_span!("raw_flow_info", length = payload.len());
+    let ether_type = ether_type(payload)
+        .ok_or_else(|| errors::Error::from(format!("Unsupported raw ip version {:?}", payload.first().map(|b| b >> 4))))?;
+    let l3 = ethernet::layer3_flow_info(ether_type, payload)?;
+
+    Ok(Layer2FlowInfo {
+        src_mac: None,
+        dst_mac: None,
+        vlan: Vlan::untagged(),
+        inner_vlans: vec![],
+        delivery: Delivery::classify_ip(&l3.dst_ip),
+        layer3: l3
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    const RAW_DATA: &'static [u8] = &[
+        //ipv4
+        0x45u8, //version and header length
+        0x00u8, //tos
+        0x00u8, 0x2Cu8, //length, 20 bytes for header, 24 bytes for tcp
+        0x00u8, 0x00u8, //id
+        0x00u8, 0x00u8, //flags
+        0x64u8, //ttl
+        0x06u8, //protocol, tcp
+        0x00u8, 0x00u8, //checksum
+        0x01u8, 0x02u8, 0x03u8, 0x04u8, //src ip 1.2.3.4
+        0xE0u8, 0x00u8, 0x00u8, 0xFBu8, //dst ip 224.0.0.251
+        //tcp
+        0xC6u8, 0xB7u8, //src port, 50871
+        0x00u8, 0x50u8, //dst port, 80
+        0x00u8, 0x00u8, 0x00u8, 0x01u8, //sequence number, 1
+        0x00u8, 0x00u8, 0x00u8, 0x02u8, //acknowledgement number, 2
+        0x50u8, 0x00u8, //header and flags, 0
+        0x00u8, 0x00u8, //window
+        0x00u8, 0x00u8, //check
+        0x00u8, 0x00u8, //urgent
+        //no options
+        //payload
+        0xfcu8, 0xfdu8, 0xfeu8, 0xffu8
+    ];
+
+    #[test]
+    fn raw_ether_type() {
+        let _ = env_logger::try_init();
+
+        assert_eq!(ether_type(RAW_DATA), Some(EthernetTypeId::L3(Layer3Id::IPv4)));
+        assert_eq!(ether_type(&[0x60u8, 0x00u8]), Some(EthernetTypeId::L3(Layer3Id::IPv6)));
+        assert_eq!(ether_type(&[0x10u8]), None);
+        assert_eq!(ether_type(&[]), None);
+    }
+
+    #[test]
+    fn convert_raw() {
+        let _ = env_logger::try_init();
+
+        let info = flow_info(RAW_DATA).expect("Could not convert to layer 2 flow info");
+
+        assert_eq!(info.src_mac, None);
+        assert_eq!(info.dst_mac, None);
+        assert_eq!(info.delivery, Delivery::Multicast);
+        assert_eq!(info.layer3.src_ip, "1.2.3.4".parse::<std::net::IpAddr>().expect("Could not parse ip address"));
+        assert_eq!(info.layer3.layer4.src_port, 50871);
+        assert_eq!(info.layer3.layer4.dst_port, 80);
+
+        assert!(flow_info(&[0x10u8, 0x00u8]).is_err());
+    }
+}
@@ -0,0 +1,253 @@
+use super::prelude::*;
+
+use self::nom::*;
+use super::ethernet::{self, EthernetTypeId};
+
+use std;
+use std::convert::TryFrom;
+use super::Layer2FlowInfo;
+
+pub const HEADER_LENGTH: usize = 16;
+const ADDRESS_LENGTH: usize = 8;
+pub const ARPHRD_ETHER: u16 = 1;
+
+///
+/// Direction of a packet relative to the capturing host, as linux classifies it
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PacketType {
+    ///
+    /// Sent to the capturing host
+    ///
+    Host,
+    Broadcast,
+    Multicast,
+    ///
+    /// Sent to another host, seen in promiscuous mode
+    ///
+    OtherHost,
+    ///
+    /// Sent by the capturing host
+    ///
+    Outgoing,
+    Unknown(u16)
+}
+
+impl PacketType {
+    pub fn new(value: u16) -> PacketType {
+        match value {
+            0 => PacketType::Host,
+            1 => PacketType::Broadcast,
+            2 => PacketType::Multicast,
+            3 => PacketType::OtherHost,
+            4 => PacketType::Outgoing,
+            other => PacketType::Unknown(other)
+        }
+    }
+
+    pub fn value(&self) -> u16 {
+        match *self {
+            PacketType::Host => 0,
+            PacketType::Broadcast => 1,
+            PacketType::Multicast => 2,
+            PacketType::OtherHost => 3,
+            PacketType::Outgoing => 4,
+            PacketType::Unknown(value) => value
+        }
+    }
+}
+
+///
+/// Linux cooked capture (SLL) pseudo-header, recorded in place of the link layer header by captures
+/// on the `any` interface, e.g. `tcpdump -i any`. https://www.tcpdump.org/linktypes/LINKTYPE_LINUX_SLL.html
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Sll {
+    packet_type: PacketType,
+    arphrd_type: u16,
+    address: std::vec::Vec<u8>,
+    protocol: u16,
+    payload: std::vec::Vec<u8>
+}
+
+impl Sll {
+    pub fn packet_type(&self) -> PacketType { self.packet_type }
+
+    ///
+    /// Linux ARPHRD_ type of the device the packet was captured on, e.g. 1 for ethernet
+    ///
+    pub fn arphrd_type(&self) -> u16 { self.arphrd_type }
+
+    ///
+    /// Link layer address of the packet's sender, as long as the device's addresses
+    ///
+    pub fn address(&self) -> &std::vec::Vec<u8> { &self.address }
+
+    ///
+    /// Protocol of the payload, an ethernet type for most devices
+    ///
+    pub fn protocol(&self) -> u16 { self.protocol }
+
+    ///
+    /// Ethernet type of the payload, `None` for protocols that aren't ethernet types, e.g. 4 for
+    /// 802.2 LLC frames
+    ///
+    pub fn ether_type(&self) -> Option<EthernetTypeId> {
+        match EthernetTypeId::new(self.protocol) {
+            Some(EthernetTypeId::PayloadLength(_)) => None,
+            ether_type => ether_type
+        }
+    }
+
+    ///
+    /// Sender's address as a mac address, for packets captured on ethernet devices
+    ///
+    pub fn src_mac(&self) -> Option<MacAddress> {
+        if self.arphrd_type == ARPHRD_ETHER && self.address.len() == MAC_LENGTH {
+            Some(MacAddress(*array_ref![self.address, 0, MAC_LENGTH]))
+        } else {
+            None
+        }
+    }
+
+    pub fn payload(&self) -> &std::vec::Vec<u8> { &self.payload }
+
+    pub fn parse(input: &[u8]) -> nom::IResult<&[u8], Sll> {
+        layer_span!("sll", length = input.len());
+
+        do_parse!(input,
+
+            packet_type: map!(be_u16, PacketType::new) >>
+            arphrd_type: be_u16 >>
+            address_length: map!(be_u16, |l| std::cmp::min(l as usize, ADDRESS_LENGTH)) >>
+            address: take!(ADDRESS_LENGTH) >>
+            protocol: be_u16 >>
+            payload: rest >>
+
+            (
+                Sll {
+                    packet_type: packet_type,
+                    arphrd_type: arphrd_type,
+                    address: address[..address_length].into(),
+                    protocol: protocol,
+                    payload: payload.into()
+                }
+            )
+        )
+    }
+}
+
+impl TryFrom<Sll> for Layer2FlowInfo {
+    type Error = errors::Error;
+
+    ///
+    /// Flow information of a cooked packet. The pseudo-header has no destination address, so the
+    /// destination mac is absent and delivery is taken from the packet type.
+    ///
+    fn try_from(value: Sll) -> Result<Self, Self::Error> {
+        layer_span!("sll_flow_info", protocol = value.protocol);
+        let ether_type = value.ether_type()
+            .ok_or_else(|| errors::Error::from(format!("Unsupported cooked capture protocol {:04x}", value.protocol)))?;
+        let l3 = ethernet::layer3_flow_info(ether_type, &value.payload)
+            .map_err(|e| e.offset_by(HEADER_LENGTH))?;
+
+        let delivery = match value.packet_type {
+            PacketType::Broadcast => Delivery::Broadcast,
+            PacketType::Multicast => Delivery::Multicast,
+            _ => Delivery::classify_ip(&l3.dst_ip)
+        };
+
+        Ok(Layer2FlowInfo {
+            src_mac: value.src_mac(),
+            dst_mac: None,
+            vlan: Vlan::untagged(),
+            inner_vlans: vec![],
+            delivery: delivery,
+            layer3: l3
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    const RAW_DATA: &'static [u8] = &[
+        0x00u8, 0x04u8, //packet type, outgoing
+        0x00u8, 0x01u8, //arphrd type, ethernet
+        0x00u8, 0x06u8, //address length, 6
+        0xFFu8, 0xFEu8, 0xFDu8, 0xFCu8, 0xFBu8, 0xFAu8, 0x00u8, 0x00u8, //address FF:FE:FD:FC:FB:FA
+        0x08u8, 0x00u8, //protocol, ipv4
+        //ipv4
+        0x45u8, //version and header length
+        0x00u8, //tos
+        0x00u8, 0x2Cu8, //length, 20 bytes for header, 24 bytes for tcp
+        0x00u8, 0x00u8, //id
+        0x00u8, 0x00u8, //flags
+        0x64u8, //ttl
+        0x06u8, //protocol, tcp
+        0x00u8, 0x00u8, //checksum
+        0x01u8, 0x02u8, 0x03u8, 0x04u8, //src ip 1.2.3.4
+        0x0Au8, 0x0Bu8, 0x0Cu8, 0x0Du8, //dst ip 10.11.12.13
+        //tcp
+        0xC6u8, 0xB7u8, //src port, 50871
+        0x00u8, 0x50u8, //dst port, 80
+        0x00u8, 0x00u8, 0x00u8, 0x01u8, //sequence number, 1
+        0x00u8, 0x00u8, 0x00u8, 0x02u8, //acknowledgement number, 2
+        0x50u8, 0x00u8, //header and flags, 0
+        0x00u8, 0x00u8, //window
+        0x00u8, 0x00u8, //check
+        0x00u8, 0x00u8, //urgent
+        //no options
+        //payload
+        0xfcu8, 0xfdu8, 0xfeu8, 0xffu8
+    ];
+
+    #[test]
+    fn parse_sll() {
+        let _ = env_logger::try_init();
+
+        let (rem, sll) = Sll::parse(RAW_DATA).expect("Unable to parse");
+
+        assert!(rem.is_empty());
+        assert_eq!(sll.packet_type(), PacketType::Outgoing);
+        assert_eq!(sll.arphrd_type(), 1);
+        assert_eq!(sll.address(), &vec![0xFFu8, 0xFEu8, 0xFDu8, 0xFCu8, 0xFBu8, 0xFAu8]);
+        assert_eq!(sll.src_mac(), Some(MacAddress([0xFFu8, 0xFEu8, 0xFDu8, 0xFCu8, 0xFBu8, 0xFAu8])));
+        assert_eq!(sll.protocol(), 0x0800);
+        assert_eq!(sll.ether_type(), Some(EthernetTypeId::L3(ethernet::Layer3Id::IPv4)));
+        assert_eq!(sll.payload().len(), 44);
+    }
+
+    #[test]
+    fn convert_sll() {
+        let _ = env_logger::try_init();
+
+        let (_, sll) = Sll::parse(RAW_DATA).expect("Unable to parse");
+        let info = Layer2FlowInfo::try_from(sll).expect("Could not convert to layer 2 flow info");
+
+        assert_eq!(info.src_mac, Some(MacAddress([0xFFu8, 0xFEu8, 0xFDu8, 0xFCu8, 0xFBu8, 0xFAu8])));
+        assert_eq!(info.dst_mac, None);
+        assert_eq!(info.vlan, Vlan::untagged());
+        assert_eq!(info.delivery, Delivery::Unicast);
+        assert_eq!(info.layer3.src_ip, "1.2.3.4".parse::<std::net::IpAddr>().expect("Could not parse ip address"));
+        assert_eq!(info.layer3.layer4.src_port, 50871);
+        assert_eq!(info.layer3.layer4.dst_port, 80);
+
+        let mut broadcast = RAW_DATA.to_vec();
+        broadcast[1] = 0x01u8; //packet type, broadcast
+        let (_, sll) = Sll::parse(&broadcast).expect("Unable to parse");
+        assert_eq!(Layer2FlowInfo::try_from(sll).expect("Could not convert to layer 2 flow info").delivery, Delivery::Broadcast);
+
+        let mut netlink = RAW_DATA.to_vec();
+        netlink[3] = 0x38u8; //arphrd type, 824 netlink
+        netlink[2] = 0x03u8;
+        netlink[14] = 0x00u8; //protocol, route
+        netlink[15] = 0x00u8;
+        let (_, sll) = Sll::parse(&netlink).expect("Unable to parse");
+        assert_eq!(sll.src_mac(), None);
+        assert!(Layer2FlowInfo::try_from(sll).is_err());
+    }
+}
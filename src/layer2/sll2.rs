@@ -0,0 +1,213 @@
+use super::prelude::*;
+
+use self::nom::*;
+use super::ethernet::{self, EthernetTypeId};
+use super::sll::{ARPHRD_ETHER, PacketType};
+
+use std;
+use std::convert::TryFrom;
+use super::Layer2FlowInfo;
+
+pub const HEADER_LENGTH: usize = 20;
+const ADDRESS_LENGTH: usize = 8;
+
+///
+/// Linux cooked capture v2 (SLL2) pseudo-header, which newer libpcap records in place of SLL for
+/// captures on the `any` interface. Adds the index of the interface each packet was captured on.
+/// https://www.tcpdump.org/linktypes/LINKTYPE_LINUX_SLL2.html
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Sll2 {
+    protocol: u16,
+    interface_index: u32,
+    arphrd_type: u16,
+    packet_type: PacketType,
+    address: std::vec::Vec<u8>,
+    payload: std::vec::Vec<u8>
+}
+
+impl Sll2 {
+    ///
+    /// Protocol of the payload, an ethernet type for most devices
+    ///
+    pub fn protocol(&self) -> u16 { self.protocol }
+
+    ///
+    /// Ethernet type of the payload, `None` for protocols that aren't ethernet types
+    ///
+    pub fn ether_type(&self) -> Option<EthernetTypeId> {
+        match EthernetTypeId::new(self.protocol) {
+            Some(EthernetTypeId::PayloadLength(_)) => None,
+            ether_type => ether_type
+        }
+    }
+
+    ///
+    /// Linux index of the interface the packet was captured on
+    ///
+    pub fn interface_index(&self) -> u32 { self.interface_index }
+
+    ///
+    /// Linux ARPHRD_ type of the device the packet was captured on, e.g. 1 for ethernet
+    ///
+    pub fn arphrd_type(&self) -> u16 { self.arphrd_type }
+
+    ///
+    /// Direction of the packet relative to the capturing host
+    ///
+    pub fn packet_type(&self) -> PacketType { self.packet_type }
+
+    ///
+    /// Link layer address of the packet's sender, as long as the device's addresses
+    ///
+    pub fn address(&self) -> &std::vec::Vec<u8> { &self.address }
+
+    ///
+    /// Sender's address as a mac address, for packets captured on ethernet devices
+    ///
+    pub fn src_mac(&self) -> Option<MacAddress> {
+        if self.arphrd_type == ARPHRD_ETHER && self.address.len() == MAC_LENGTH {
+            Some(MacAddress(*array_ref![self.address, 0, MAC_LENGTH]))
+        } else {
+            None
+        }
+    }
+
+    pub fn payload(&self) -> &std::vec::Vec<u8> { &self.payload }
+
+    pub fn parse(input: &[u8]) -> nom::IResult<&[u8], Sll2> {
+        layer_span!("sll2", length = input.len());
+
+        do_parse!(input,
+
+            protocol: be_u16 >>
+            _reserved: be_u16 >>
+            interface_index: be_u32 >>
+            arphrd_type: be_u16 >>
+            packet_type: map!(be_u8, |t| PacketType::new(t as u16)) >>
+            address_length: map!(be_u8, |l| std::cmp::min(l as usize, ADDRESS_LENGTH)) >>
+            address: take!(ADDRESS_LENGTH) >>
+            payload: rest >>
+
+            (
+                Sll2 {
+                    protocol: protocol,
+                    interface_index: interface_index,
+                    arphrd_type: arphrd_type,
+                    packet_type: packet_type,
+                    address: address[..address_length].into(),
+                    payload: payload.into()
+                }
+            )
+        )
+    }
+}
+
+impl TryFrom<Sll2> for Layer2FlowInfo {
+    type Error = errors::Error;
+
+    ///
+    /// Flow information of a cooked packet, as for SLL: the destination mac is absent and delivery
+    /// is taken from the packet type
+    ///
+    fn try_from(value: Sll2) -> Result<Self, Self::Error> {
+        layer_span!("sll2_flow_info", protocol = value.protocol);
+        let ether_type = value.ether_type()
+            .ok_or_else(|| errors::Error::from(format!("Unsupported cooked capture protocol {:04x}", value.protocol)))?;
+        let l3 = ethernet::layer3_flow_info(ether_type, &value.payload)
+            .map_err(|e| e.offset_by(HEADER_LENGTH))?;
+
+        let delivery = match value.packet_type {
+            PacketType::Broadcast => Delivery::Broadcast,
+            PacketType::Multicast => Delivery::Multicast,
+            _ => Delivery::classify_ip(&l3.dst_ip)
+        };
+
+        Ok(Layer2FlowInfo {
+            src_mac: value.src_mac(),
+            dst_mac: None,
+            vlan: Vlan::untagged(),
+            inner_vlans: vec![],
+            delivery: delivery,
+            layer3: l3
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    const RAW_DATA: &'static [u8] = &[
+        0x08u8, 0x00u8, //protocol, ipv4
+        0x00u8, 0x00u8, //reserved
+        0x00u8, 0x00u8, 0x00u8, 0x03u8, //interface index, 3
+        0x00u8, 0x01u8, //arphrd type, ethernet
+        0x04u8, //packet type, outgoing
+        0x06u8, //address length, 6
+        0xFFu8, 0xFEu8, 0xFDu8, 0xFCu8, 0xFBu8, 0xFAu8, 0x00u8, 0x00u8, //address FF:FE:FD:FC:FB:FA
+        //ipv4
+        0x45u8, //version and header length
+        0x00u8, //tos
+        0x00u8, 0x2Cu8, //length, 20 bytes for header, 24 bytes for tcp
+        0x00u8, 0x00u8, //id
+        0x00u8, 0x00u8, //flags
+        0x64u8, //ttl
+        0x06u8, //protocol, tcp
+        0x00u8, 0x00u8, //checksum
+        0x01u8, 0x02u8, 0x03u8, 0x04u8, //src ip 1.2.3.4
+        0x0Au8, 0x0Bu8, 0x0Cu8, 0x0Du8, //dst ip 10.11.12.13
+        //tcp
+        0xC6u8, 0xB7u8, //src port, 50871
+        0x00u8, 0x50u8, //dst port, 80
+        0x00u8, 0x00u8, 0x00u8, 0x01u8, //sequence number, 1
+        0x00u8, 0x00u8, 0x00u8, 0x02u8, //acknowledgement number, 2
+        0x50u8, 0x00u8, //header and flags, 0
+        0x00u8, 0x00u8, //window
+        0x00u8, 0x00u8, //check
+        0x00u8, 0x00u8, //urgent
+        //no options
+        //payload
+        0xfcu8, 0xfdu8, 0xfeu8, 0xffu8
+    ];
+
+    #[test]
+    fn parse_sll2() {
+        let _ = env_logger::try_init();
+
+        let (rem, sll2) = Sll2::parse(RAW_DATA).expect("Unable to parse");
+
+        assert!(rem.is_empty());
+        assert_eq!(sll2.protocol(), 0x0800);
+        assert_eq!(sll2.interface_index(), 3);
+        assert_eq!(sll2.arphrd_type(), 1);
+        assert_eq!(sll2.packet_type(), PacketType::Outgoing);
+        assert_eq!(sll2.src_mac(), Some(MacAddress([0xFFu8, 0xFEu8, 0xFDu8, 0xFCu8, 0xFBu8, 0xFAu8])));
+        assert_eq!(sll2.payload().len(), 44);
+    }
+
+    #[test]
+    fn convert_sll2() {
+        let _ = env_logger::try_init();
+
+        let (_, sll2) = Sll2::parse(RAW_DATA).expect("Unable to parse");
+        let info = Layer2FlowInfo::try_from(sll2).expect("Could not convert to layer 2 flow info");
+
+        assert_eq!(info.src_mac, Some(MacAddress([0xFFu8, 0xFEu8, 0xFDu8, 0xFCu8, 0xFBu8, 0xFAu8])));
+        assert_eq!(info.dst_mac, None);
+        assert_eq!(info.delivery, Delivery::Unicast);
+        assert_eq!(info.layer3.dst_ip, "10.11.12.13".parse::<std::net::IpAddr>().expect("Could not parse ip address"));
+        assert_eq!(info.layer3.layer4.src_port, 50871);
+        assert_eq!(info.layer3.layer4.dst_port, 80);
+
+        let mut multicast = RAW_DATA.to_vec();
+        multicast[10] = 0x02u8; //packet type, multicast
+        let (_, sll2) = Sll2::parse(&multicast).expect("Unable to parse");
+        assert_eq!(Layer2FlowInfo::try_from(sll2).expect("Could not convert to layer 2 flow info").delivery, Delivery::Multicast);
+
+        let err = Sll2::parse(&RAW_DATA[..12]).expect_err("Parsed truncated header");
+        assert!(matches!(err, nom::Err::Incomplete(_)));
+    }
+}
@@ -0,0 +1,336 @@
+use super::prelude::*;
+
+use self::nom::*;
+
+use std;
+
+const TOPOLOGY_CHANGE: u8 = 0x01;
+const PROPOSAL: u8 = 0x02;
+const PORT_ROLE_SHIFT: u8 = 2;
+const LEARNING: u8 = 0x10;
+const FORWARDING: u8 = 0x20;
+const AGREEMENT: u8 = 0x40;
+const TOPOLOGY_CHANGE_ACK: u8 = 0x80;
+
+///
+/// Nanoseconds in the 1/256 second units of BPDU timers
+///
+const TIMER_UNIT_NANOS: u64 = 1_000_000_000 / 256;
+
+///
+/// Type of a bridge protocol data unit
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BpduType {
+    ///
+    /// Configuration BPDU of the original spanning tree protocol
+    ///
+    Configuration,
+    ///
+    /// Topology change notification, sent toward the root bridge and carrying no configuration
+    ///
+    TopologyChangeNotification,
+    ///
+    /// Rapid spanning tree BPDU, also sent by MSTP bridges
+    ///
+    Rapid,
+    Unknown(u8)
+}
+
+impl BpduType {
+    pub fn new(value: u8) -> BpduType {
+        match value {
+            0x00 => BpduType::Configuration,
+            0x80 => BpduType::TopologyChangeNotification,
+            0x02 => BpduType::Rapid,
+            other => BpduType::Unknown(other)
+        }
+    }
+
+    pub fn value(&self) -> u8 {
+        match *self {
+            BpduType::Configuration => 0x00,
+            BpduType::TopologyChangeNotification => 0x80,
+            BpduType::Rapid => 0x02,
+            BpduType::Unknown(value) => value
+        }
+    }
+}
+
+///
+/// Role of the sending port, carried in the flags of rapid spanning tree BPDUs
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PortRole {
+    Unknown,
+    AlternateOrBackup,
+    Root,
+    Designated
+}
+
+///
+/// Bridge identifier, the bridge priority (including any system id extension) and mac address.
+/// Lower identifiers are preferred when electing the root bridge.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BridgeId {
+    pub priority: u16,
+    pub mac: MacAddress
+}
+
+impl BridgeId {
+    ///
+    /// Priority without the system id extension, a multiple of 4096
+    ///
+    pub fn base_priority(&self) -> u16 {
+        self.priority & 0xF000
+    }
+
+    ///
+    /// System id extension, the vlan of per-vlan spanning tree instances
+    ///
+    pub fn system_id(&self) -> u16 {
+        self.priority & 0x0FFF
+    }
+}
+
+impl std::fmt::Display for BridgeId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}/{}", self.priority, self.mac)
+    }
+}
+
+///
+/// Flags of configuration and rapid spanning tree BPDUs
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Flags(pub u8);
+
+impl Flags {
+    pub fn topology_change(&self) -> bool { self.0 & TOPOLOGY_CHANGE != 0 }
+    pub fn topology_change_ack(&self) -> bool { self.0 & TOPOLOGY_CHANGE_ACK != 0 }
+    pub fn proposal(&self) -> bool { self.0 & PROPOSAL != 0 }
+    pub fn learning(&self) -> bool { self.0 & LEARNING != 0 }
+    pub fn forwarding(&self) -> bool { self.0 & FORWARDING != 0 }
+    pub fn agreement(&self) -> bool { self.0 & AGREEMENT != 0 }
+
+    pub fn port_role(&self) -> PortRole {
+        match (self.0 >> PORT_ROLE_SHIFT) & 0x03 {
+            1 => PortRole::AlternateOrBackup,
+            2 => PortRole::Root,
+            3 => PortRole::Designated,
+            _ => PortRole::Unknown
+        }
+    }
+}
+
+///
+/// Spanning tree information of configuration and rapid spanning tree BPDUs, as seen by the
+/// sending bridge
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Configuration {
+    pub flags: Flags,
+    pub root_id: BridgeId,
+    ///
+    /// Cost of the sending bridge's path to the root bridge
+    ///
+    pub root_path_cost: u32,
+    pub bridge_id: BridgeId,
+    ///
+    /// Port the BPDU was sent from, its priority in the top 4 bits and number in the rest
+    ///
+    pub port_id: u16,
+    ///
+    /// Age of the information since it left the root bridge
+    ///
+    pub message_age: std::time::Duration,
+    pub max_age: std::time::Duration,
+    pub hello_time: std::time::Duration,
+    pub forward_delay: std::time::Duration
+}
+
+///
+/// Spanning tree bridge protocol data unit, from an LLC frame to the spanning tree service access
+/// point. https://standards.ieee.org/ieee/802.1D/3387/
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Bpdu {
+    protocol_id: u16,
+    version: u8,
+    bpdu_type: BpduType,
+    configuration: Option<Configuration>
+}
+
+fn to_duration(value: u16) -> std::time::Duration {
+    std::time::Duration::from_nanos(u64::from(value) * TIMER_UNIT_NANOS)
+}
+
+named!(bridge_id<&[u8], BridgeId>, do_parse!(
+    priority: be_u16 >>
+    mac: take!(MAC_LENGTH) >>
+
+    ( BridgeId { priority: priority, mac: MacAddress(*array_ref![mac, 0, MAC_LENGTH]) } )
+));
+
+named!(timer<&[u8], std::time::Duration>, map!(be_u16, to_duration));
+
+named!(configuration<&[u8], Configuration>, do_parse!(
+    flags: be_u8 >>
+    root_id: bridge_id >>
+    root_path_cost: be_u32 >>
+    bridge_id: bridge_id >>
+    port_id: be_u16 >>
+    message_age: timer >>
+    max_age: timer >>
+    hello_time: timer >>
+    forward_delay: timer >>
+
+    (
+        Configuration {
+            flags: Flags(flags),
+            root_id: root_id,
+            root_path_cost: root_path_cost,
+            bridge_id: bridge_id,
+            port_id: port_id,
+            message_age: message_age,
+            max_age: max_age,
+            hello_time: hello_time,
+            forward_delay: forward_delay
+        }
+    )
+));
+
+impl Bpdu {
+    pub fn protocol_id(&self) -> u16 { self.protocol_id }
+
+    ///
+    /// Protocol version, 0 for STP, 2 for RSTP and 3 for MSTP
+    ///
+    pub fn version(&self) -> u8 { self.version }
+
+    pub fn bpdu_type(&self) -> BpduType { self.bpdu_type }
+
+    ///
+    /// Spanning tree information, absent from topology change notifications
+    ///
+    pub fn configuration(&self) -> Option<&Configuration> { self.configuration.as_ref() }
+
+    pub fn root_id(&self) -> Option<BridgeId> {
+        self.configuration.as_ref().map(|c| c.root_id)
+    }
+
+    pub fn bridge_id(&self) -> Option<BridgeId> {
+        self.configuration.as_ref().map(|c| c.bridge_id)
+    }
+
+    ///
+    /// Whether the BPDU announces or notifies of a topology change
+    ///
+    pub fn is_topology_change(&self) -> bool {
+        self.bpdu_type == BpduType::TopologyChangeNotification ||
+            self.configuration.as_ref().map(|c| c.flags.topology_change()).unwrap_or(false)
+    }
+
+    ///
+    /// Parse a BPDU from the payload of an LLC frame. Bytes following the configuration, e.g. the
+    /// version 1 length of rapid BPDUs and MSTP configuration, are left unparsed.
+    ///
+    pub fn parse(input: &[u8]) -> nom::IResult<&[u8], Bpdu> {
+        layer_span!("stp", length = input.len());
+
+        do_parse!(input,
+
+            protocol_id: be_u16 >>
+            version: be_u8 >>
+            bpdu_type: map!(be_u8, BpduType::new) >>
+            configuration: cond!(bpdu_type != BpduType::TopologyChangeNotification, configuration) >>
+
+            (
+                Bpdu {
+                    protocol_id: protocol_id,
+                    version: version,
+                    bpdu_type: bpdu_type,
+                    configuration: configuration
+                }
+            )
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    const RSTP_RAW_DATA: &'static [u8] = &[
+        0x00u8, 0x00u8, //protocol id
+        0x02u8, //version, rstp
+        0x02u8, //type, rapid
+        0x3Du8, //flags, topology change, designated, learning and forwarding
+        0x80u8, 0x01u8, //root priority 32768, system id 1
+        0x00u8, 0x1Cu8, 0x0Eu8, 0x87u8, 0x78u8, 0x00u8, //root mac 00:1C:0E:87:78:00
+        0x00u8, 0x00u8, 0x00u8, 0x04u8, //root path cost, 4
+        0x80u8, 0x01u8, //bridge priority 32768, system id 1
+        0x00u8, 0x1Cu8, 0x0Eu8, 0x87u8, 0x85u8, 0x00u8, //bridge mac 00:1C:0E:87:85:00
+        0x80u8, 0x04u8, //port id, priority 128, port 4
+        0x01u8, 0x00u8, //message age, 1s
+        0x14u8, 0x00u8, //max age, 20s
+        0x02u8, 0x00u8, //hello time, 2s
+        0x0Fu8, 0x00u8, //forward delay, 15s
+        0x00u8 //version 1 length
+    ];
+
+    #[test]
+    fn parse_rstp() {
+        let _ = env_logger::try_init();
+
+        let (rem, bpdu) = Bpdu::parse(RSTP_RAW_DATA).expect("Unable to parse");
+
+        assert_eq!(rem, &[0x00u8][..]);
+        assert_eq!(bpdu.version(), 2);
+        assert_eq!(bpdu.bpdu_type(), BpduType::Rapid);
+        assert!(bpdu.is_topology_change());
+
+        let root_id = bpdu.root_id().expect("No root id");
+        assert_eq!(root_id.base_priority(), 32768);
+        assert_eq!(root_id.system_id(), 1);
+        assert_eq!(format!("{}", root_id), "32769/00:1c:0e:87:78:00");
+        assert!(root_id < bpdu.bridge_id().expect("No bridge id"));
+
+        let configuration = bpdu.configuration().expect("No configuration");
+        assert_eq!(configuration.root_path_cost, 4);
+        assert_eq!(configuration.port_id, 0x8004);
+        assert_eq!(configuration.flags.port_role(), PortRole::Designated);
+        assert!(configuration.flags.learning());
+        assert!(configuration.flags.forwarding());
+        assert!(!configuration.flags.proposal());
+        assert!(!configuration.flags.topology_change_ack());
+        assert_eq!(configuration.message_age, std::time::Duration::from_secs(1));
+        assert_eq!(configuration.max_age, std::time::Duration::from_secs(20));
+        assert_eq!(configuration.hello_time, std::time::Duration::from_secs(2));
+        assert_eq!(configuration.forward_delay, std::time::Duration::from_secs(15));
+
+        assert!(Bpdu::parse(&RSTP_RAW_DATA[..20]).is_err());
+    }
+
+    #[test]
+    fn parse_topology_change_notification() {
+        let _ = env_logger::try_init();
+
+        let tcn = [
+            0x00u8, 0x00u8, //protocol id
+            0x00u8, //version, stp
+            0x80u8 //type, topology change notification
+        ];
+
+        let (rem, bpdu) = Bpdu::parse(&tcn).expect("Unable to parse");
+
+        assert!(rem.is_empty());
+        assert_eq!(bpdu.bpdu_type(), BpduType::TopologyChangeNotification);
+        assert_eq!(bpdu.configuration(), None);
+        assert_eq!(bpdu.root_id(), None);
+        assert!(bpdu.is_topology_change());
+    }
+}
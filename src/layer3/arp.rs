@@ -0,0 +1,212 @@
+use super::prelude::*;
+
+use self::nom::*;
+use self::layer4::Layer4FlowInfo;
+use super::Layer3FlowInfo;
+
+use std;
+use std::convert::TryFrom;
+
+const HARDWARE_ADDRESS_LENGTH: u8 = 6;
+const PROTOCOL_ADDRESS_LENGTH: u8 = 4;
+
+///
+/// ARP operation codes https://www.iana.org/assignments/arp-parameters/arp-parameters.xhtml
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ArpOperation {
+    Request,
+    Reply,
+    Other(u16)
+}
+
+impl ArpOperation {
+    pub fn new(value: u16) -> ArpOperation {
+        match value {
+            1 => ArpOperation::Request,
+            2 => ArpOperation::Reply,
+            x => ArpOperation::Other(x)
+        }
+    }
+}
+
+///
+/// ARP packet resolving an IPv4 address to an ethernet address
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Arp {
+    hardware_type: u16,
+    protocol_type: u16,
+    operation: ArpOperation,
+    sender_mac: MacAddress,
+    sender_ip: std::net::Ipv4Addr,
+    target_mac: MacAddress,
+    target_ip: std::net::Ipv4Addr
+}
+
+fn to_mac_address(i: &[u8]) -> MacAddress {
+    MacAddress(array_ref![i, 0, MAC_LENGTH].clone())
+}
+
+fn to_ip_address(i: &[u8]) -> std::net::Ipv4Addr {
+    std::net::Ipv4Addr::from(array_ref![i, 0, PROTOCOL_ADDRESS_LENGTH as usize].clone())
+}
+
+named!(mac_address<&[u8], MacAddress>, map!(take!(MAC_LENGTH), to_mac_address));
+named!(ip_address<&[u8], std::net::Ipv4Addr>, map!(take!(PROTOCOL_ADDRESS_LENGTH), to_ip_address));
+
+impl Arp {
+    pub fn hardware_type(&self) -> u16 {
+        self.hardware_type
+    }
+    pub fn protocol_type(&self) -> u16 {
+        self.protocol_type
+    }
+    pub fn operation(&self) -> &ArpOperation {
+        &self.operation
+    }
+    pub fn sender_mac(&self) -> &MacAddress {
+        &self.sender_mac
+    }
+    pub fn sender_ip(&self) -> &std::net::Ipv4Addr {
+        &self.sender_ip
+    }
+    pub fn target_mac(&self) -> &MacAddress {
+        &self.target_mac
+    }
+    pub fn target_ip(&self) -> &std::net::Ipv4Addr {
+        &self.target_ip
+    }
+
+    ///
+    /// Announcement of the sender's own address, i.e. sender and target ip are the same
+    ///
+    pub fn is_gratuitous(&self) -> bool {
+        self.sender_ip == self.target_ip
+    }
+
+    fn parse_arp(input: &[u8], hardware_type: u16, protocol_type: u16) -> IResult<&[u8], Arp> {
+        do_parse!(input,
+
+            operation: be_u16 >>
+            sender_mac: mac_address >>
+            sender_ip: ip_address >>
+            target_mac: mac_address >>
+            target_ip: ip_address >>
+
+            (
+                Arp {
+                    hardware_type: hardware_type,
+                    protocol_type: protocol_type,
+                    operation: ArpOperation::new(operation),
+                    sender_mac: sender_mac,
+                    sender_ip: sender_ip,
+                    target_mac: target_mac,
+                    target_ip: target_ip
+                }
+            )
+        )
+    }
+
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Arp> {
+        layer_span!("arp", length = input.len());
+
+        let (rem, (hardware_type, protocol_type, hardware_length, protocol_length)) = do_parse!(input,
+
+            htype: be_u16 >>
+            ptype: be_u16 >>
+            hlen: be_u8 >>
+            plen: be_u8 >>
+
+            ( (htype, ptype, hlen, plen) )
+        )?;
+
+        if hardware_length != HARDWARE_ADDRESS_LENGTH || protocol_length != PROTOCOL_ADDRESS_LENGTH {
+            Err(Err::Error(error_position!(input, ErrorKind::Custom(errors::ParseCode::ArpAddressLength as u32))))
+        } else {
+            Arp::parse_arp(rem, hardware_type, protocol_type)
+        }
+    }
+}
+
+impl TryFrom<Arp> for Layer3FlowInfo {
+    type Error = errors::Error;
+
+    ///
+    /// Flow information of an ARP packet, from its sender to its target
+    ///
+    fn try_from(value: Arp) -> Result<Self, Self::Error> {
+        Ok(Layer3FlowInfo {
+            src_ip: std::net::IpAddr::V4(value.sender_ip),
+            dst_ip: std::net::IpAddr::V4(value.target_ip),
+            protocol: None,
+            layer4: Layer4FlowInfo {
+                src_port: 0,
+                dst_port: 0
+            },
+            arp: Some(value.operation)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    const RAW_DATA: &'static [u8] = &[
+        0x00u8, 0x01u8, //hardware type, ethernet
+        0x08u8, 0x00u8, //protocol type, ipv4
+        0x06u8, //hardware length
+        0x04u8, //protocol length
+        0x00u8, 0x02u8, //operation, reply
+        0x01u8, 0x02u8, 0x03u8, 0x04u8, 0x05u8, 0x06u8, //sender mac 01:02:03:04:05:06
+        0x0Au8, 0x00u8, 0x00u8, 0x01u8, //sender ip 10.0.0.1
+        0xFFu8, 0xFEu8, 0xFDu8, 0xFCu8, 0xFBu8, 0xFAu8, //target mac FF:FE:FD:FC:FB:FA
+        0x0Au8, 0x00u8, 0x00u8, 0x02u8 //target ip 10.0.0.2
+    ];
+
+    #[test]
+    fn parse_arp() {
+        let _ = env_logger::try_init();
+
+        let (rem, arp) = Arp::parse(RAW_DATA).expect("Unable to parse");
+
+        assert!(rem.is_empty());
+        assert_eq!(arp.hardware_type(), 1);
+        assert_eq!(arp.protocol_type(), 0x0800);
+        assert_eq!(arp.operation(), &ArpOperation::Reply);
+        assert_eq!(arp.sender_mac(), &MacAddress([0x01u8, 0x02u8, 0x03u8, 0x04u8, 0x05u8, 0x06u8]));
+        assert_eq!(arp.sender_ip(), &std::net::Ipv4Addr::new(10, 0, 0, 1));
+        assert_eq!(arp.target_mac(), &MacAddress([0xFFu8, 0xFEu8, 0xFDu8, 0xFCu8, 0xFBu8, 0xFAu8]));
+        assert_eq!(arp.target_ip(), &std::net::Ipv4Addr::new(10, 0, 0, 2));
+        assert!(!arp.is_gratuitous());
+    }
+
+    #[test]
+    fn parse_arp_invalid_address_length() {
+        let _ = env_logger::try_init();
+
+        let mut data = RAW_DATA.to_vec();
+        data[5] = 0x10u8;
+
+        let err = Arp::parse(&data).err().map(|e| errors::Error::from_nom(e, errors::Layer::Arp, &data)).expect("Parsed invalid address length");
+
+        assert_eq!(format!("{}", err), "ARP header at offset 0: expected ethernet (6 byte) and ipv4 (4 byte) addresses");
+    }
+
+    #[test]
+    fn convert_arp() {
+        let _ = env_logger::try_init();
+
+        let (_, arp) = Arp::parse(RAW_DATA).expect("Unable to parse");
+        let info = Layer3FlowInfo::try_from(arp).expect("Could not convert to layer 3 flow info");
+
+        assert_eq!(info.src_ip, std::net::IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 1)));
+        assert_eq!(info.dst_ip, std::net::IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 2)));
+        assert_eq!(info.layer4.src_port, 0);
+        assert_eq!(info.layer4.dst_port, 0);
+        assert_eq!(info.arp, Some(ArpOperation::Reply));
+    }
+}
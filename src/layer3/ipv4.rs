@@ -1,5 +1,5 @@
 use super::prelude::*;
-use super::{InternetProtocolId, Layer3FlowInfo};
+use super::{Ecn, InternetProtocolId, Layer3FlowInfo};
 
 use self::nom::*;
 use self::layer4::{
@@ -12,12 +12,20 @@ use std::convert::TryFrom;
 
 const ADDRESS_LENGTH: usize = 4;
 const HEADER_LENGTH: usize = 4 * std::mem::size_of::<u16>();
+const MINIMUM_HEADER_LENGTH: usize = 20;
+const DONT_FRAGMENT_FLAG: u16 = 0x4000;
+const MORE_FRAGMENTS_FLAG: u16 = 0x2000;
+const FRAGMENT_OFFSET_MASK: u16 = 0x1FFF;
 
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct IPv4 {
     dst_ip: std::net::IpAddr,
     src_ip: std::net::IpAddr,
+    header_length: usize,
+    id: u16,
     flags: u16,
     ttl: u8,
+    tos: u8,
     protocol: InternetProtocolId,
     payload: std::vec::Vec<u8>
 }
@@ -42,6 +50,42 @@ impl IPv4 {
     pub fn protocol(&self) -> &InternetProtocolId {
         &self.protocol
     }
+    pub fn header_length(&self) -> usize {
+        self.header_length
+    }
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+    pub fn ttl(&self) -> u8 {
+        self.ttl
+    }
+    ///
+    /// Type of service byte, holding the dscp and ecn fields
+    ///
+    pub fn tos(&self) -> u8 {
+        self.tos
+    }
+    pub fn dscp(&self) -> u8 {
+        self.tos >> 2
+    }
+    pub fn ecn(&self) -> Ecn {
+        Ecn::new(self.tos)
+    }
+    pub fn dont_fragment(&self) -> bool {
+        self.flags & DONT_FRAGMENT_FLAG != 0
+    }
+    pub fn more_fragments(&self) -> bool {
+        self.flags & MORE_FRAGMENTS_FLAG != 0
+    }
+    ///
+    /// Offset of this fragment's payload within the original datagram, in bytes
+    ///
+    pub fn fragment_offset(&self) -> usize {
+        (self.flags & FRAGMENT_OFFSET_MASK) as usize * 8
+    }
+    pub fn is_fragment(&self) -> bool {
+        self.more_fragments() || self.fragment_offset() != 0
+    }
     pub fn payload(&self) -> &std::vec::Vec<u8> { &self.payload }
 
     fn parse_ipv4(input: &[u8], version_and_length: u8) -> IResult<&[u8], IPv4> {
@@ -52,11 +96,11 @@ impl IPv4 {
         do_parse!(input,
 
             tos: be_u8 >>
-            length: map!(be_u16, |s| {
-                let l = s - (header_length as u16);
-                trace!("Payload Length={}", l);
+            length: add_return_error!(ErrorKind::Custom(errors::ParseCode::IPv4TotalLength as u32), map_opt!(be_u16, |s: u16| {
+                let l = s.checked_sub(header_length as u16);
+                trace!("Payload Length={:?}", l);
                 l
-            }) >>
+            })) >>
             id: be_u16 >>
             flags: be_u16 >>
             ttl: be_u8 >>
@@ -64,14 +108,18 @@ impl IPv4 {
             checksum: be_u16 >>
             src_ip: ipv4_address >>
             dst_ip: ipv4_address >>
+            _options: take!(header_length as usize - MINIMUM_HEADER_LENGTH) >>
             payload: take!(length) >>
 
             (
                 IPv4 {
                     dst_ip: dst_ip,
                     src_ip: src_ip,
+                    header_length: header_length as usize,
+                    id: id,
                     flags: flags,
                     ttl: ttl,
+                    tos: tos,
                     protocol: proto,
                     payload: payload.into()
                 }
@@ -90,23 +138,28 @@ impl IPv4 {
         IPv4 {
             dst_ip: std::net::IpAddr::V4(dst_ip),
             src_ip: std::net::IpAddr::V4(src_ip),
+            header_length: 20,
+            id: 0,
             flags: flags,
             ttl: ttl,
+            tos: 0,
             protocol: protocol,
             payload: payload
         }
     }
 
     pub fn parse(input: &[u8]) -> IResult<&[u8], IPv4> {
-        trace!("Available={}", input.len());
+        layer_span!("ipv4", length = input.len());
 
         be_u8(input).and_then(|r| {
             let (rem, version_and_length) = r;
             let version = version_and_length >> 4;
-            if version == 4 {
-                IPv4::parse_ipv4(rem, version_and_length)
+            if version != 4 {
+                Err(Err::Error(error_position!(input, ErrorKind::Custom(errors::ParseCode::IPv4Version as u32))))
+            } else if (version_and_length & 0x0F) < 5 {
+                Err(Err::Error(error_position!(input, ErrorKind::Custom(errors::ParseCode::IPv4HeaderLength as u32))))
             } else {
-                Err(Err::convert(Err::Error(error_position!(input, ErrorKind::CondReduce::<u32>))))
+                IPv4::parse_ipv4(rem, version_and_length)
             }
         })
     }
@@ -116,14 +169,12 @@ impl TryFrom<IPv4> for Layer3FlowInfo {
     type Error = errors::Error;
 
     fn try_from(value: IPv4) -> Result<Self, Self::Error> {
+        layer_span!("ipv4_flow_info", header_length = value.header_length, length = value.payload.len());
         debug!("Creating flow info from {:?}", value.protocol);
         let l4 = match value.protocol.clone() {
             InternetProtocolId::Tcp => {
                 layer4::tcp::Tcp::parse(value.payload())
-                    .map_err(|e| {
-                        let err: Self::Error = e.into();
-                        err.chain_err(|| errors::Error::from_kind(errors::ErrorKind::FlowParse))
-                    }).and_then(|r| {
+                    .map_err(|e| errors::Error::from_nom(e, errors::Layer::Tcp, value.payload())).and_then(|r| {
                     let (rem, l4) = r;
                     if rem.is_empty() {
                         Layer4FlowInfo::try_from(l4)
@@ -134,10 +185,7 @@ impl TryFrom<IPv4> for Layer3FlowInfo {
             }
             InternetProtocolId::Udp => {
                 layer4::udp::Udp::parse(value.payload())
-                    .map_err(|e| {
-                        let err: Self::Error = e.into();
-                        err.chain_err(|| errors::Error::from_kind(errors::ErrorKind::FlowParse))
-                    }).and_then(|r| {
+                    .map_err(|e| errors::Error::from_nom(e, errors::Layer::Udp, value.payload())).and_then(|r| {
                     let (rem, l4) = r;
                     if rem.is_empty() {
                         Layer4FlowInfo::try_from(l4)
@@ -147,14 +195,16 @@ impl TryFrom<IPv4> for Layer3FlowInfo {
                 })
             }
             _ => {
-                Err(errors::Error::from_kind(errors::ErrorKind::IPv4Type(value.protocol)))
+                Err(errors::Error::from_kind(errors::ErrorKind::IPv4Type(value.protocol.clone())))
             }
-        }?;
+        }.map_err(|e| e.offset_by(value.header_length))?;
 
         Ok(Layer3FlowInfo {
             src_ip: value.src_ip,
             dst_ip: value.dst_ip,
-            layer4: l4
+            protocol: Some(value.protocol),
+            layer4: l4,
+            arp: None
         })
     }
 }
@@ -216,7 +266,62 @@ mod tests {
         };
 
         assert!(is_tcp);
+        assert!(!l3.is_fragment());
+        assert_eq!(l3.ecn(), Ecn::NotEct);
+
+        let mut data = RAW_DATA.to_vec();
+        data[1] = 0xBBu8; //expedited forwarding, congestion experienced
+        let (_, l3) = IPv4::parse(&data).expect("Unable to parse");
+        assert_eq!(l3.dscp(), 46);
+        assert_eq!(l3.ecn(), Ecn::Ce);
     }
+
+    #[test]
+    fn parse_ipv4_fragment() {
+        let _ = env_logger::try_init();
+
+        let mut data = RAW_DATA.to_vec();
+        data[4] = 0x12u8; //id
+        data[5] = 0x34u8;
+        data[6] = 0x20u8; //more fragments, offset 3 (24 bytes)
+        data[7] = 0x03u8;
+
+        let (_, l3) = IPv4::parse(&data).expect("Unable to parse");
+
+        assert_eq!(l3.id(), 0x1234);
+        assert!(l3.more_fragments());
+        assert_eq!(l3.fragment_offset(), 24);
+        assert!(l3.is_fragment());
+    }
+    #[test]
+    fn parse_ipv4_options() {
+        let _ = env_logger::try_init();
+
+        let mut data = RAW_DATA[..20].to_vec();
+        data[0] = 0x46u8; //version and header length, 6 words
+        data[3] = 0x4Cu8; //length, 24 bytes for header, 52 bytes for tcp
+        data.extend_from_slice(&[0x01u8, 0x01u8, 0x01u8, 0x00u8]); //options, 3 no-ops and end of list
+        data.extend_from_slice(&RAW_DATA[20..]);
+
+        let (rem, l3) = IPv4::parse(&data).expect("Unable to parse");
+
+        assert!(rem.is_empty());
+        assert_eq!(l3.header_length(), 24);
+        assert_eq!(&l3.payload()[..], &RAW_DATA[20..]);
+    }
+
+    #[test]
+    fn parse_ipv4_invalid_header_length() {
+        let _ = env_logger::try_init();
+
+        let mut data = RAW_DATA.to_vec();
+        data[0] = 0x43u8;
+
+        let err = IPv4::parse(&data).err().map(|e| errors::Error::from_nom(e, errors::Layer::IPv4, &data)).expect("Parsed invalid header length");
+
+        assert_eq!(format!("{}", err), "IPv4 header at offset 0: expected header length (IHL) of at least 5 words");
+    }
+
     #[test]
     fn convert_ipv4() {
         let _ = env_logger::try_init();
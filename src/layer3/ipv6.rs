@@ -1,5 +1,5 @@
 use super::prelude::*;
-use super::{InternetProtocolId, Layer3FlowInfo};
+use super::{Ecn, InternetProtocolId, Layer3FlowInfo};
 
 use self::nom::*;
 use self::layer4::{
@@ -13,9 +13,12 @@ use std::convert::TryFrom;
 const ADDRESS_LENGTH: usize = 16;
 const HEADER_LENGTH: usize = 4 * std::mem::size_of::<u16>();
 
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct IPv6 {
     dst_ip: std::net::IpAddr,
     src_ip: std::net::IpAddr,
+    header_length: usize,
+    traffic_class: u8,
     protocol: InternetProtocolId,
     payload: std::vec::Vec<u8>
 }
@@ -40,6 +43,21 @@ impl IPv6 {
     pub fn protocol(&self) -> &InternetProtocolId {
         &self.protocol
     }
+    pub fn header_length(&self) -> usize {
+        self.header_length
+    }
+    ///
+    /// Traffic class byte, holding the dscp and ecn fields
+    ///
+    pub fn traffic_class(&self) -> u8 {
+        self.traffic_class
+    }
+    pub fn dscp(&self) -> u8 {
+        self.traffic_class >> 2
+    }
+    pub fn ecn(&self) -> Ecn {
+        Ecn::new(self.traffic_class)
+    }
     pub fn payload(&self) -> &std::vec::Vec<u8> { &self.payload }
 
     fn parse_next_header(
@@ -68,6 +86,8 @@ impl IPv6 {
                     IPv6 {
                         dst_ip: dst,
                         src_ip: src,
+                        header_length: 0,
+                        traffic_class: 0,
                         protocol: next_header,
                         payload: payload.into()
                     }
@@ -100,21 +120,28 @@ impl IPv6 {
         IPv6 {
             dst_ip: std::net::IpAddr::V6(dst_ip),
             src_ip: std::net::IpAddr::V6(src_ip),
+            header_length: 40,
+            traffic_class: 0,
             protocol: protocol,
             payload: payload
         }
     }
 
     pub fn parse(input: &[u8]) -> IResult<&[u8], IPv6> {
-        trace!("Available={}", input.len());
+        layer_span!("ipv6", length = input.len());
 
         be_u8(input).and_then(|r| {
             let (rem, length_check) = r;
             let version = length_check >> 4;
             if version == 6 {
-                IPv6::parse_ipv6(rem)
+                IPv6::parse_ipv6(rem).map(|r| {
+                    let (rem, mut ipv6) = r;
+                    ipv6.header_length = input.len() - rem.len() - ipv6.payload.len();
+                    ipv6.traffic_class = (length_check & 0x0F) << 4 | input[1] >> 4;
+                    (rem, ipv6)
+                })
             } else {
-                Err(Err::convert(Err::Error(error_position!(input, ErrorKind::CondReduce::<u32>))))
+                Err(Err::Error(error_position!(input, ErrorKind::Custom(errors::ParseCode::IPv6Version as u32))))
             }
         })
     }
@@ -124,14 +151,12 @@ impl TryFrom<IPv6> for Layer3FlowInfo {
     type Error = errors::Error;
 
     fn try_from(value: IPv6) -> Result<Self, Self::Error> {
+        layer_span!("ipv6_flow_info", header_length = value.header_length, length = value.payload.len());
         debug!("Creating flow info from {:?}", value.protocol);
         let l4 = match value.protocol.clone() {
             InternetProtocolId::Tcp => {
                 layer4::tcp::Tcp::parse(value.payload())
-                    .map_err(|e| {
-                        let err: Self::Error = e.into();
-                        err.chain_err(|| errors::Error::from_kind(errors::ErrorKind::FlowParse))
-                    }).and_then(|r| {
+                    .map_err(|e| errors::Error::from_nom(e, errors::Layer::Tcp, value.payload())).and_then(|r| {
                     let (rem, l4) = r;
                     if rem.is_empty() {
                         Layer4FlowInfo::try_from(l4)
@@ -142,10 +167,7 @@ impl TryFrom<IPv6> for Layer3FlowInfo {
             }
             InternetProtocolId::Udp => {
                 layer4::udp::Udp::parse(value.payload())
-                    .map_err(|e| {
-                        let err: Self::Error = e.into();
-                        err.chain_err(|| errors::Error::from_kind(errors::ErrorKind::FlowParse))
-                    }).and_then(|r| {
+                    .map_err(|e| errors::Error::from_nom(e, errors::Layer::Udp, value.payload())).and_then(|r| {
                     let (rem, l4) = r;
                     if rem.is_empty() {
                         Layer4FlowInfo::try_from(l4)
@@ -155,14 +177,16 @@ impl TryFrom<IPv6> for Layer3FlowInfo {
                 })
             }
             _ => {
-                Err(errors::Error::from_kind(errors::ErrorKind::IPv4Type(value.protocol)))
+                Err(errors::Error::from_kind(errors::ErrorKind::IPv6Type(value.protocol.clone())))
             }
-        }?;
+        }.map_err(|e| e.offset_by(value.header_length))?;
 
         Ok(Layer3FlowInfo {
             src_ip: value.src_ip,
             dst_ip: value.dst_ip,
-            layer4: l4
+            protocol: Some(value.protocol),
+            layer4: l4,
+            arp: None
         })
     }
 }
@@ -220,6 +244,9 @@ mod tests {
         };
 
         assert!(is_tcp);
+        assert_eq!(l3.traffic_class(), 0x50u8);
+        assert_eq!(l3.dscp(), 20);
+        assert_eq!(l3.ecn(), Ecn::NotEct);
 
         assert!(rem.is_empty());
     }
@@ -0,0 +1,383 @@
+use super::prelude::*;
+
+use self::nom::*;
+use std;
+
+const TLV_END: u8 = 0;
+const TLV_CHASSIS_ID: u8 = 1;
+const TLV_PORT_ID: u8 = 2;
+const TLV_TTL: u8 = 3;
+const TLV_PORT_DESCRIPTION: u8 = 4;
+const TLV_SYSTEM_NAME: u8 = 5;
+const TLV_SYSTEM_DESCRIPTION: u8 = 6;
+const TLV_SYSTEM_CAPABILITIES: u8 = 7;
+const TLV_MANAGEMENT_ADDRESS: u8 = 8;
+const TLV_ORGANIZATIONAL: u8 = 127;
+
+///
+/// IANA address families of network addresses
+///
+const FAMILY_IPV4: u8 = 1;
+const FAMILY_IPV6: u8 = 2;
+
+fn to_string(value: &[u8]) -> std::string::String {
+    std::string::String::from_utf8_lossy(value).into_owned()
+}
+
+///
+/// Network address prefixed by its IANA address family, `None` for families other than ipv4 and
+/// ipv6 or addresses of the wrong length
+///
+fn to_network_address(value: &[u8]) -> Option<std::net::IpAddr> {
+    match (value.first(), value.len()) {
+        (Some(&FAMILY_IPV4), 5) => Some(std::net::IpAddr::V4(std::net::Ipv4Addr::from(*array_ref![value, 1, 4]))),
+        (Some(&FAMILY_IPV6), 17) => Some(std::net::IpAddr::V6(std::net::Ipv6Addr::from(*array_ref![value, 1, 16]))),
+        _ => None
+    }
+}
+
+fn to_mac_address(value: &[u8]) -> Option<MacAddress> {
+    if value.len() == MAC_LENGTH {
+        Some(MacAddress(*array_ref![value, 0, MAC_LENGTH]))
+    } else {
+        None
+    }
+}
+
+///
+/// Chassis of the sending device, by its chassis id subtype
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ChassisId {
+    ChassisComponent(std::string::String),
+    InterfaceAlias(std::string::String),
+    PortComponent(std::string::String),
+    MacAddress(MacAddress),
+    NetworkAddress(std::net::IpAddr),
+    InterfaceName(std::string::String),
+    Local(std::string::String),
+    Other(u8, std::vec::Vec<u8>)
+}
+
+impl ChassisId {
+    fn new(subtype: u8, value: &[u8]) -> ChassisId {
+        match subtype {
+            1 => ChassisId::ChassisComponent(to_string(value)),
+            2 => ChassisId::InterfaceAlias(to_string(value)),
+            3 => ChassisId::PortComponent(to_string(value)),
+            4 => to_mac_address(value).map(ChassisId::MacAddress).unwrap_or_else(|| ChassisId::Other(subtype, value.into())),
+            5 => to_network_address(value).map(ChassisId::NetworkAddress).unwrap_or_else(|| ChassisId::Other(subtype, value.into())),
+            6 => ChassisId::InterfaceName(to_string(value)),
+            7 => ChassisId::Local(to_string(value)),
+            _ => ChassisId::Other(subtype, value.into())
+        }
+    }
+}
+
+///
+/// Port of the sending device the frame was sent from, by its port id subtype
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PortId {
+    InterfaceAlias(std::string::String),
+    PortComponent(std::string::String),
+    MacAddress(MacAddress),
+    NetworkAddress(std::net::IpAddr),
+    InterfaceName(std::string::String),
+    AgentCircuitId(std::vec::Vec<u8>),
+    Local(std::string::String),
+    Other(u8, std::vec::Vec<u8>)
+}
+
+impl PortId {
+    fn new(subtype: u8, value: &[u8]) -> PortId {
+        match subtype {
+            1 => PortId::InterfaceAlias(to_string(value)),
+            2 => PortId::PortComponent(to_string(value)),
+            3 => to_mac_address(value).map(PortId::MacAddress).unwrap_or_else(|| PortId::Other(subtype, value.into())),
+            4 => to_network_address(value).map(PortId::NetworkAddress).unwrap_or_else(|| PortId::Other(subtype, value.into())),
+            5 => PortId::InterfaceName(to_string(value)),
+            6 => PortId::AgentCircuitId(value.into()),
+            7 => PortId::Local(to_string(value)),
+            _ => PortId::Other(subtype, value.into())
+        }
+    }
+}
+
+///
+/// System capabilities, and those enabled, as bitmaps, e.g. 0x0004 for a bridge and 0x0010 for a
+/// router
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Capabilities {
+    pub capabilities: u16,
+    pub enabled: u16
+}
+
+///
+/// Address at which the sending device can be managed, with the interface it is reached through
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ManagementAddress {
+    pub address: Option<std::net::IpAddr>,
+    pub interface_subtype: u8,
+    pub interface_number: u32,
+    pub oid: std::vec::Vec<u8>
+}
+
+///
+/// Organizationally specific TLV, e.g. the IEEE 802.1 port vlan id (OUI 00-80-C2, subtype 1)
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Organizational {
+    pub oui: u32,
+    pub subtype: u8,
+    pub info: std::vec::Vec<u8>
+}
+
+///
+/// LLDP TLV, with the basic management TLVs decoded. https://standards.ieee.org/ieee/802.1AB/6047/
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Tlv {
+    ChassisId(ChassisId),
+    PortId(PortId),
+    ///
+    /// Seconds the information in the frame remains valid, 0 when the port is shutting down
+    ///
+    Ttl(u16),
+    PortDescription(std::string::String),
+    SystemName(std::string::String),
+    SystemDescription(std::string::String),
+    SystemCapabilities(Capabilities),
+    ManagementAddress(ManagementAddress),
+    Organizational(Organizational),
+    Unknown(u8, std::vec::Vec<u8>)
+}
+
+named!(management_address<&[u8], ManagementAddress>, do_parse!(
+    address: length_bytes!(be_u8) >>
+    interface_subtype: be_u8 >>
+    interface_number: be_u32 >>
+    oid: length_bytes!(be_u8) >>
+
+    (
+        ManagementAddress {
+            address: to_network_address(address),
+            interface_subtype: interface_subtype,
+            interface_number: interface_number,
+            oid: oid.into()
+        }
+    )
+));
+
+named!(organizational<&[u8], Organizational>, do_parse!(
+    oui: be_u24 >>
+    subtype: be_u8 >>
+    info: rest >>
+
+    (
+        Organizational {
+            oui: oui,
+            subtype: subtype,
+            info: info.into()
+        }
+    )
+));
+
+impl Tlv {
+    fn new(tlv_type: u8, value: &[u8]) -> Tlv {
+        let decoded = match tlv_type {
+            TLV_CHASSIS_ID if !value.is_empty() => Some(Tlv::ChassisId(ChassisId::new(value[0], &value[1..]))),
+            TLV_PORT_ID if !value.is_empty() => Some(Tlv::PortId(PortId::new(value[0], &value[1..]))),
+            TLV_TTL if value.len() == 2 => Some(Tlv::Ttl(u16::from(value[0]) << 8 | u16::from(value[1]))),
+            TLV_PORT_DESCRIPTION => Some(Tlv::PortDescription(to_string(value))),
+            TLV_SYSTEM_NAME => Some(Tlv::SystemName(to_string(value))),
+            TLV_SYSTEM_DESCRIPTION => Some(Tlv::SystemDescription(to_string(value))),
+            TLV_SYSTEM_CAPABILITIES if value.len() == 4 => Some(Tlv::SystemCapabilities(Capabilities {
+                capabilities: u16::from(value[0]) << 8 | u16::from(value[1]),
+                enabled: u16::from(value[2]) << 8 | u16::from(value[3])
+            })),
+            TLV_MANAGEMENT_ADDRESS => management_address(value).ok().map(|(_, a)| Tlv::ManagementAddress(a)),
+            TLV_ORGANIZATIONAL => organizational(value).ok().map(|(_, o)| Tlv::Organizational(o)),
+            _ => None
+        };
+        decoded.unwrap_or_else(|| Tlv::Unknown(tlv_type, value.into()))
+    }
+
+    ///
+    /// Type and value of a TLV, whose 16 bit header is a 7 bit type and 9 bit length
+    ///
+    fn parse(input: &[u8]) -> IResult<&[u8], (u8, &[u8])> {
+        do_parse!(input,
+
+            header: be_u16 >>
+            value: take!(header & 0x01FF) >>
+
+            ( ((header >> 9) as u8, value) )
+        )
+    }
+}
+
+///
+/// Link layer discovery protocol frame (ethernet type 0x88cc), a device announcing its identity and
+/// capabilities to its neighbours
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Lldp {
+    tlvs: std::vec::Vec<Tlv>
+}
+
+impl Lldp {
+    ///
+    /// TLVs in the order sent, excluding the end of LLDPDU TLV. Values that don't decode as their
+    /// type are kept as unknown TLVs.
+    ///
+    pub fn tlvs(&self) -> &std::vec::Vec<Tlv> {
+        &self.tlvs
+    }
+
+    pub fn chassis_id(&self) -> Option<&ChassisId> {
+        self.tlvs.iter().filter_map(|t| if let Tlv::ChassisId(ref id) = *t { Some(id) } else { None }).next()
+    }
+
+    pub fn port_id(&self) -> Option<&PortId> {
+        self.tlvs.iter().filter_map(|t| if let Tlv::PortId(ref id) = *t { Some(id) } else { None }).next()
+    }
+
+    pub fn ttl(&self) -> Option<u16> {
+        self.tlvs.iter().filter_map(|t| if let Tlv::Ttl(ttl) = *t { Some(ttl) } else { None }).next()
+    }
+
+    pub fn port_description(&self) -> Option<&str> {
+        self.tlvs.iter().filter_map(|t| if let Tlv::PortDescription(ref d) = *t { Some(d.as_str()) } else { None }).next()
+    }
+
+    pub fn system_name(&self) -> Option<&str> {
+        self.tlvs.iter().filter_map(|t| if let Tlv::SystemName(ref n) = *t { Some(n.as_str()) } else { None }).next()
+    }
+
+    pub fn system_description(&self) -> Option<&str> {
+        self.tlvs.iter().filter_map(|t| if let Tlv::SystemDescription(ref d) = *t { Some(d.as_str()) } else { None }).next()
+    }
+
+    pub fn capabilities(&self) -> Option<Capabilities> {
+        self.tlvs.iter().filter_map(|t| if let Tlv::SystemCapabilities(c) = *t { Some(c) } else { None }).next()
+    }
+
+    pub fn management_addresses(&self) -> std::vec::Vec<&ManagementAddress> {
+        self.tlvs.iter().filter_map(|t| if let Tlv::ManagementAddress(ref a) = *t { Some(a) } else { None }).collect()
+    }
+
+    pub fn organizational(&self) -> std::vec::Vec<&Organizational> {
+        self.tlvs.iter().filter_map(|t| if let Tlv::Organizational(ref o) = *t { Some(o) } else { None }).collect()
+    }
+
+    ///
+    /// Parse the TLVs of an LLDPDU up to its end TLV, returning the ethernet padding that follows
+    ///
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Lldp> {
+        layer_span!("lldp", length = input.len());
+
+        let mut tlvs = vec![];
+        let mut current = input;
+        loop {
+            let (rem, (tlv_type, value)) = Tlv::parse(current)?;
+            current = rem;
+            if tlv_type == TLV_END {
+                break;
+            }
+            tlvs.push(Tlv::new(tlv_type, value));
+            if current.is_empty() {
+                break;
+            }
+        }
+
+        Ok((current, Lldp { tlvs: tlvs }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    const RAW_DATA: &'static [u8] = &[
+        0x02u8, 0x07u8, //chassis id, length 7
+        0x04u8, //subtype, mac address
+        0x00u8, 0x11u8, 0x22u8, 0x33u8, 0x44u8, 0x55u8, //mac 00:11:22:33:44:55
+        0x04u8, 0x05u8, //port id, length 5
+        0x05u8, //subtype, interface name
+        0x47u8, 0x69u8, 0x30u8, 0x31u8, //Gi01
+        0x06u8, 0x02u8, //ttl, length 2
+        0x00u8, 0x78u8, //120 seconds
+        0x0Au8, 0x04u8, //system name, length 4
+        0x63u8, 0x6Fu8, 0x72u8, 0x65u8, //core
+        0x0Cu8, 0x03u8, //system description, length 3
+        0x69u8, 0x6Fu8, 0x73u8, //ios
+        0x0Eu8, 0x04u8, //system capabilities, length 4
+        0x00u8, 0x14u8, //bridge and router
+        0x00u8, 0x10u8, //router
+        0x10u8, 0x0Cu8, //management address, length 12
+        0x05u8, //address string length
+        0x01u8, 0x0Au8, 0x00u8, 0x00u8, 0x01u8, //ipv4 10.0.0.1
+        0x02u8, //interface subtype, if index
+        0x00u8, 0x00u8, 0x00u8, 0x03u8, //interface number 3
+        0x00u8, //oid length
+        0xFEu8, 0x06u8, //organizational, length 6
+        0x00u8, 0x80u8, 0xC2u8, //oui, ieee 802.1
+        0x01u8, //subtype, port vlan id
+        0x00u8, 0x64u8, //vlan 100
+        0x00u8, 0x00u8, //end
+        0x00u8, 0x00u8 //ethernet padding
+    ];
+
+    #[test]
+    fn parse_lldp() {
+        let _ = env_logger::try_init();
+
+        let (rem, lldp) = Lldp::parse(RAW_DATA).expect("Unable to parse");
+
+        assert_eq!(rem.len(), 2);
+        assert_eq!(lldp.tlvs().len(), 8);
+        assert_eq!(lldp.chassis_id(), Some(&ChassisId::MacAddress(MacAddress([0x00u8, 0x11u8, 0x22u8, 0x33u8, 0x44u8, 0x55u8]))));
+        assert_eq!(lldp.port_id(), Some(&PortId::InterfaceName("Gi01".to_string())));
+        assert_eq!(lldp.ttl(), Some(120));
+        assert_eq!(lldp.system_name(), Some("core"));
+        assert_eq!(lldp.system_description(), Some("ios"));
+        assert_eq!(lldp.port_description(), None);
+        assert_eq!(lldp.capabilities(), Some(Capabilities { capabilities: 0x14, enabled: 0x10 }));
+        assert_eq!(lldp.management_addresses(), vec![&ManagementAddress {
+            address: Some("10.0.0.1".parse().expect("Could not parse ip address")),
+            interface_subtype: 2,
+            interface_number: 3,
+            oid: vec![]
+        }]);
+        assert_eq!(lldp.organizational(), vec![&Organizational {
+            oui: 0x0080C2,
+            subtype: 1,
+            info: vec![0x00u8, 0x64u8]
+        }]);
+    }
+
+    #[test]
+    fn parse_lldp_malformed() {
+        let _ = env_logger::try_init();
+
+        let mut data = RAW_DATA.to_vec();
+        data[1] = 0x03; //chassis id too short for a mac address
+        data.drain(5..9);
+        let (_, lldp) = Lldp::parse(&data).expect("Unable to parse");
+        assert_eq!(lldp.chassis_id(), Some(&ChassisId::Other(4, vec![0x00u8, 0x11u8])));
+
+        let mut data = RAW_DATA.to_vec();
+        data[17] = 0x03; //ttl too long
+        data.insert(20, 0x00);
+        let (_, lldp) = Lldp::parse(&data).expect("Unable to parse");
+        assert_eq!(lldp.ttl(), None);
+        assert_eq!(lldp.tlvs()[2], Tlv::Unknown(3, vec![0x00u8, 0x78u8, 0x00u8]));
+
+        assert!(Lldp::parse(&RAW_DATA[..19]).is_err());
+    }
+}
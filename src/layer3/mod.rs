@@ -5,6 +5,7 @@ pub mod prelude {
 
 pub mod arp;
 pub mod ipv4;
+#[cfg(feature = "ipv6")]
 pub mod ipv6;
 pub mod lldp;
 
@@ -13,31 +14,87 @@ use std;
 ///
 /// Available layer 3 representations
 ///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Layer3 {
     //Arp(apr::Arp),
     IPv4(ipv4::IPv4),
+    #[cfg(feature = "ipv6")]
     IPv6(ipv6::IPv6),
     //Lldp(lldp::Lldp)
 }
 
+impl Layer3 {
+    pub fn ecn(&self) -> Ecn {
+        match *self {
+            Layer3::IPv4(ref ipv4) => ipv4.ecn(),
+            #[cfg(feature = "ipv6")]
+            Layer3::IPv6(ref ipv6) => ipv6.ecn()
+        }
+    }
+}
+
+///
+/// Explicit congestion notification codepoint, the low two bits of the ipv4 type of service or
+/// ipv6 traffic class https://tools.ietf.org/html/rfc3168
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Ecn {
+    NotEct,
+    Ect1,
+    Ect0,
+    ///
+    /// Congestion experienced, set by a router instead of dropping the packet
+    ///
+    Ce
+}
+
+impl Ecn {
+    pub fn new(value: u8) -> Ecn {
+        match value & 0x03 {
+            0 => Ecn::NotEct,
+            1 => Ecn::Ect1,
+            2 => Ecn::Ect0,
+            _ => Ecn::Ce
+        }
+    }
+
+    ///
+    /// Sent by an ecn capable transport, whether or not congestion was since marked
+    ///
+    pub fn is_capable(&self) -> bool {
+        *self != Ecn::NotEct
+    }
+}
+
 ///
 /// Information from Layer 3 protocols used in flow determination
 ///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Layer3FlowInfo {
     pub dst_ip: std::net::IpAddr,
     pub src_ip: std::net::IpAddr,
-    pub layer4: prelude::layer4::Layer4FlowInfo
+    ///
+    /// Ip protocol of the packet, absent for ARP packets
+    ///
+    pub protocol: Option<InternetProtocolId>,
+    pub layer4: prelude::layer4::Layer4FlowInfo,
+    ///
+    /// Operation of an ARP packet, whose addresses are the sender's and target's, without ports
+    ///
+    pub arp: Option<arp::ArpOperation>
 }
 
 ///
 /// IP Protocol numbers https://en.wikipedia.org/wiki/List_of_IP_protocol_numbers
 ///
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum InternetProtocolId {
     AuthenticationHeader,
     HopByHop,
     EncapsulatingSecurityPayload,
-    //ICMP,
+    Gre,
+    Icmp,
+    Icmpv6,
     IPv6Route,
     IPv6Fragment,
     IPv6NoNext,
@@ -50,13 +107,15 @@ impl InternetProtocolId {
     pub fn new(value: u8) -> Option<InternetProtocolId> {
         match value {
             0 => Some(InternetProtocolId::HopByHop),
-            //1 -> Some(InternetProtocolId::ICMP)
+            1 => Some(InternetProtocolId::Icmp),
             6 => Some(InternetProtocolId::Tcp),
             17 => Some(InternetProtocolId::Udp),
             43 => Some(InternetProtocolId::IPv6Route),
             44 => Some(InternetProtocolId::IPv6Fragment),
+            47 => Some(InternetProtocolId::Gre),
             50 => Some(InternetProtocolId::AuthenticationHeader),
             51 => Some(InternetProtocolId::EncapsulatingSecurityPayload),
+            58 => Some(InternetProtocolId::Icmpv6),
             59 => Some(InternetProtocolId::IPv6NoNext),
             60 => Some(InternetProtocolId::IPv6Options),
             _ => {
@@ -67,6 +126,26 @@ impl InternetProtocolId {
         }
     }
 
+    ///
+    /// Protocol number, the inverse of `new`
+    ///
+    pub fn value(&self) -> u8 {
+        match *self {
+            InternetProtocolId::HopByHop => 0,
+            InternetProtocolId::Icmp => 1,
+            InternetProtocolId::Tcp => 6,
+            InternetProtocolId::Udp => 17,
+            InternetProtocolId::IPv6Route => 43,
+            InternetProtocolId::IPv6Fragment => 44,
+            InternetProtocolId::Gre => 47,
+            InternetProtocolId::AuthenticationHeader => 50,
+            InternetProtocolId::EncapsulatingSecurityPayload => 51,
+            InternetProtocolId::Icmpv6 => 58,
+            InternetProtocolId::IPv6NoNext => 59,
+            InternetProtocolId::IPv6Options => 60
+        }
+    }
+
     pub fn has_next_option(v: InternetProtocolId) -> bool {
         match v {
             InternetProtocolId::AuthenticationHeader => true,
@@ -0,0 +1,225 @@
+use super::prelude::*;
+use super::super::layer3::InternetProtocolId;
+
+use self::nom::*;
+use std;
+
+const DESTINATION_UNREACHABLE: u8 = 3;
+const TIME_EXCEEDED: u8 = 11;
+const FRAGMENTATION_NEEDED: u8 = 4;
+const ICMPV6_DESTINATION_UNREACHABLE: u8 = 1;
+const ICMPV6_PACKET_TOO_BIG: u8 = 2;
+const ICMPV6_TIME_EXCEEDED: u8 = 3;
+const IPV4_HEADER_LENGTH: usize = 20;
+const IPV6_HEADER_LENGTH: usize = 40;
+
+///
+/// Icmp or icmpv6 message, which share a header layout: type, code, checksum and four bytes whose
+/// meaning depends on the type
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Icmp {
+    message_type: u8,
+    code: u8,
+    rest_of_header: u32,
+    payload: std::vec::Vec<u8>
+}
+
+///
+/// Error reported by a router or host about a datagram it could not deliver
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum IcmpError {
+    ///
+    /// Network, host, protocol or port unreachable, or administratively prohibited, with the code
+    /// of the message
+    ///
+    Unreachable(u8),
+    ///
+    /// Datagram too big to forward without fragmenting, with the mtu of the next hop, or zero if the
+    /// router did not report one https://tools.ietf.org/html/rfc1191#section-4
+    ///
+    PacketTooBig(u32),
+    ///
+    /// Hop limit reached in transit (code 0), or fragment reassembly timed out (code 1)
+    ///
+    TimeExceeded(u8)
+}
+
+///
+/// Addressing of the datagram an icmp error was reported about, from the headers quoted in the
+/// error. Ports are only available for tcp and udp.
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct OriginalDatagram {
+    pub src_ip: std::net::IpAddr,
+    pub dst_ip: std::net::IpAddr,
+    pub protocol: Option<InternetProtocolId>,
+    pub src_port: Option<u16>,
+    pub dst_port: Option<u16>
+}
+
+impl Icmp {
+    pub fn message_type(&self) -> u8 {
+        self.message_type
+    }
+    pub fn code(&self) -> u8 {
+        self.code
+    }
+    pub fn rest_of_header(&self) -> u32 {
+        self.rest_of_header
+    }
+    pub fn payload(&self) -> &std::vec::Vec<u8> {
+        &self.payload
+    }
+
+    ///
+    /// Error this icmp message reports, if it is an error
+    ///
+    pub fn error(&self) -> Option<IcmpError> {
+        match (self.message_type, self.code) {
+            (DESTINATION_UNREACHABLE, FRAGMENTATION_NEEDED) => Some(IcmpError::PacketTooBig(self.rest_of_header & 0xFFFF)),
+            (DESTINATION_UNREACHABLE, code) => Some(IcmpError::Unreachable(code)),
+            (TIME_EXCEEDED, code) => Some(IcmpError::TimeExceeded(code)),
+            _ => None
+        }
+    }
+
+    ///
+    /// Error this icmpv6 message reports, if it is an error
+    ///
+    pub fn error_v6(&self) -> Option<IcmpError> {
+        match self.message_type {
+            ICMPV6_DESTINATION_UNREACHABLE => Some(IcmpError::Unreachable(self.code)),
+            ICMPV6_PACKET_TOO_BIG => Some(IcmpError::PacketTooBig(self.rest_of_header)),
+            ICMPV6_TIME_EXCEEDED => Some(IcmpError::TimeExceeded(self.code)),
+            _ => None
+        }
+    }
+
+    ///
+    /// Datagram quoted by an error message, from its ip header and the first bytes of its payload.
+    /// Quoted datagrams are truncated, so are read directly rather than with the ip parsers, and
+    /// extension headers of quoted ipv6 datagrams are not followed.
+    ///
+    pub fn original(&self) -> Option<OriginalDatagram> {
+        let quoted = &self.payload[..];
+        let (src_ip, dst_ip, protocol, header_length) = match quoted.first()? >> 4 {
+            4 if quoted.len() >= IPV4_HEADER_LENGTH => (
+                std::net::IpAddr::V4(std::net::Ipv4Addr::from(array_ref![quoted, 12, 4].clone())),
+                std::net::IpAddr::V4(std::net::Ipv4Addr::from(array_ref![quoted, 16, 4].clone())),
+                quoted[9],
+                (quoted[0] & 0x0F) as usize * 4
+            ),
+            6 if quoted.len() >= IPV6_HEADER_LENGTH => (
+                std::net::IpAddr::V6(std::net::Ipv6Addr::from(array_ref![quoted, 8, 16].clone())),
+                std::net::IpAddr::V6(std::net::Ipv6Addr::from(array_ref![quoted, 24, 16].clone())),
+                quoted[6],
+                IPV6_HEADER_LENGTH
+            ),
+            _ => return None
+        };
+        let protocol = InternetProtocolId::new(protocol);
+        let ports = quoted.get(header_length..header_length + 4)
+            .map(|p| ((p[0] as u16) << 8 | p[1] as u16, (p[2] as u16) << 8 | p[3] as u16));
+        let (src_port, dst_port) = match (&protocol, ports) {
+            (&Some(InternetProtocolId::Tcp), Some((first, second))) => (Some(first), Some(second)),
+            //in the order the udp parser reads them, so keys built from them match udp flows
+            (&Some(InternetProtocolId::Udp), Some((first, second))) => (Some(second), Some(first)),
+            _ => (None, None)
+        };
+
+        Some(OriginalDatagram {
+            src_ip: src_ip,
+            dst_ip: dst_ip,
+            protocol: protocol,
+            src_port: src_port,
+            dst_port: dst_port
+        })
+    }
+
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Icmp> {
+        layer_span!("icmp", length = input.len());
+
+        do_parse!(input,
+
+            message_type: be_u8 >>
+            code: be_u8 >>
+            checksum: be_u16 >>
+            rest_of_header: be_u32 >>
+            payload: rest >>
+
+            (
+                Icmp {
+                    message_type: message_type,
+                    code: code,
+                    rest_of_header: rest_of_header,
+                    payload: payload.into()
+                }
+            )
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    const RAW_DATA: &'static [u8] = &[
+        0x03u8, //type, destination unreachable
+        0x04u8, //code, fragmentation needed
+        0x00u8, 0x00u8, //checksum
+        0x00u8, 0x00u8, 0x05u8, 0xDCu8, //unused, next hop mtu 1500
+        //quoted ipv4
+        0x45u8, //version and header length
+        0x00u8, //tos
+        0x05u8, 0xFCu8, //length, 1532
+        0x00u8, 0x00u8, //id
+        0x40u8, 0x00u8, //flags, don't fragment
+        0x40u8, //ttl
+        0x06u8, //protocol, tcp
+        0x00u8, 0x00u8, //checksum
+        0x01u8, 0x02u8, 0x03u8, 0x04u8, //src ip 1.2.3.4
+        0x0Au8, 0x0Bu8, 0x0Cu8, 0x0Du8, //dst ip 10.11.12.13
+        //quoted tcp
+        0xC6u8, 0xB7u8, //src port, 50871
+        0x00u8, 0x50u8, //dst port, 80
+        0x00u8, 0x00u8, 0x00u8, 0x01u8 //sequence number, 1
+    ];
+
+    #[test]
+    fn parse_icmp_error() {
+        let _ = env_logger::try_init();
+
+        let (rem, icmp) = Icmp::parse(RAW_DATA).expect("Unable to parse");
+
+        assert!(rem.is_empty());
+        assert_eq!(icmp.message_type(), 3);
+        assert_eq!(icmp.code(), 4);
+        assert_eq!(icmp.error(), Some(IcmpError::PacketTooBig(1500)));
+        assert_eq!(icmp.error_v6(), Some(IcmpError::TimeExceeded(4)));
+
+        let original = icmp.original().expect("No quoted datagram");
+        assert_eq!(original.src_ip, "1.2.3.4".parse::<std::net::IpAddr>().expect("Could not parse ip address"));
+        assert_eq!(original.dst_ip, "10.11.12.13".parse::<std::net::IpAddr>().expect("Could not parse ip address"));
+        assert_eq!(original.protocol, Some(InternetProtocolId::Tcp));
+        assert_eq!(original.src_port, Some(50871));
+        assert_eq!(original.dst_port, Some(80));
+    }
+
+    #[test]
+    fn parse_icmp_truncated_quote() {
+        let _ = env_logger::try_init();
+
+        let (_, icmp) = Icmp::parse(&RAW_DATA[..8 + 22]).expect("Unable to parse");
+        let original = icmp.original().expect("No quoted datagram");
+        assert_eq!(original.src_port, None);
+
+        let (_, icmp) = Icmp::parse(&RAW_DATA[..8 + 12]).expect("Unable to parse");
+        assert_eq!(icmp.original(), None);
+
+        assert!(Icmp::parse(&RAW_DATA[..4]).is_err());
+    }
+}
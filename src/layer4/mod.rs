@@ -2,12 +2,15 @@ pub mod prelude {
     pub use super::super::prelude::*;
 }
 
+#[cfg(feature = "icmp")]
+pub mod icmp;
 pub mod tcp;
 pub mod udp;
 
 ///
 /// Available Layer 4 representations
 ///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Layer4 {
     Tcp(tcp::Tcp),
     Udp(udp::Udp)
@@ -16,6 +19,7 @@ pub enum Layer4 {
 ///
 /// Information from Layer 4 protocols used in flow determination
 ///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Layer4FlowInfo {
     pub dst_port: u16,
     pub src_port: u16
@@ -1,5 +1,4 @@
 use super::prelude::*;
-use super::super::flow;
 use super::Layer4FlowInfo;
 
 use self::nom::*;
@@ -10,12 +9,29 @@ use std::convert::TryFrom;
 const MINIMUM_HEADER_BYTES: usize = 20; //5 32bit words
 const MAXIMUM_HEADER_BYTES: usize = 60; //15 32bit words
 
+pub const FLAG_FIN: u16 = 0x001;
+pub const FLAG_SYN: u16 = 0x002;
+pub const FLAG_RST: u16 = 0x004;
+pub const FLAG_PSH: u16 = 0x008;
+pub const FLAG_ACK: u16 = 0x010;
+pub const FLAG_URG: u16 = 0x020;
+pub const FLAG_ECE: u16 = 0x040;
+pub const FLAG_CWR: u16 = 0x080;
+
+const OPTION_END: u8 = 0;
+const OPTION_NOP: u8 = 1;
+const OPTION_WINDOW_SCALE: u8 = 3;
+const MAXIMUM_WINDOW_SCALE: u8 = 14;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Tcp {
     dst_port: u16,
     src_port: u16,
     sequence_number: u32,
     acknowledgement_number: u32,
     flags: u16,
+    window: u16,
+    options: std::vec::Vec<u8>,
     payload: std::vec::Vec<u8>
 }
 
@@ -26,10 +42,69 @@ impl Tcp {
     pub fn src_port(&self) -> u16 {
         self.src_port
     }
+    pub fn sequence_number(&self) -> u32 {
+        self.sequence_number
+    }
+    pub fn acknowledgement_number(&self) -> u32 {
+        self.acknowledgement_number
+    }
+    ///
+    /// Control bits, the lower 9 bits of the header length and flags field
+    ///
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+    ///
+    /// Advertised receive window, before any scaling
+    ///
+    pub fn window(&self) -> u16 {
+        self.window
+    }
+    pub fn options(&self) -> &std::vec::Vec<u8> {
+        &self.options
+    }
     pub fn payload(&self) -> &std::vec::Vec<u8> {
         &self.payload
     }
 
+    ///
+    /// Data of the first option of `kind`, stopping at the end of the option list or a malformed option
+    ///
+    fn option(&self, kind: u8) -> Option<&[u8]> {
+        let mut options = &self.options[..];
+        while let Some(&k) = options.first() {
+            match k {
+                OPTION_END => return None,
+                OPTION_NOP => options = &options[1..],
+                _ => {
+                    let length = *options.get(1)? as usize;
+                    if length < 2 || length > options.len() {
+                        return None
+                    }
+                    if k == kind {
+                        return Some(&options[2..length])
+                    }
+                    options = &options[length..];
+                }
+            }
+        }
+        None
+    }
+
+    ///
+    /// Shift count from the window scale option, which is only sent on SYN segments. Shifts beyond
+    /// 14 are treated as 14 https://tools.ietf.org/html/rfc7323#section-2.3
+    ///
+    pub fn window_scale(&self) -> Option<u8> {
+        self.option(OPTION_WINDOW_SCALE)
+            .and_then(|o| o.first())
+            .map(|s| std::cmp::min(*s, MAXIMUM_WINDOW_SCALE))
+    }
+
+    pub fn has_flags(&self, flags: u16) -> bool {
+        self.flags & flags == flags
+    }
+
     pub fn extract_length(value: u16) -> usize {
         let words = value >> 12;
         (words * 4) as usize
@@ -49,12 +124,14 @@ impl Tcp {
             sequence_number,
             acknowledgement_number,
             flags,
+            window: 0,
+            options: vec![],
             payload
         }
     }
 
     pub fn parse(input: &[u8]) -> IResult<&[u8], Tcp> {
-        trace!("Available={}", input.len());
+        layer_span!("tcp", length = input.len());
 
         do_parse!(input,
 
@@ -62,16 +139,16 @@ impl Tcp {
             dst_port: be_u16 >>
             sequence_number: be_u32 >>
             acknowledgement_number: be_u32 >>
-            header_length_and_flags: map_res!(be_u16, |v| {
+            header_length_and_flags: add_return_error!(ErrorKind::Custom(errors::ParseCode::TcpHeaderLength as u32), map_opt!(be_u16, |v| {
                 let hl = Tcp::extract_length(v);
                 trace!("Header Length={}", hl);
                 if hl >= MINIMUM_HEADER_BYTES && hl <= MAXIMUM_HEADER_BYTES {
                     let flags = v & 0x01FF; //take lower 9 bits
-                    Ok( (hl, flags) ) as Result<(usize, u16), nom::Context<&[u8]>>
+                    Some( (hl, flags) )
                 } else {
-                    Err(error_position!(input, ErrorKind::CondReduce::<u32>))
+                    None
                 }
-            }) >>
+            })) >>
             window: be_u16 >>
             check: be_u16 >>
             urgent: be_u16 >>
@@ -84,6 +161,8 @@ impl Tcp {
                     sequence_number: sequence_number,
                     acknowledgement_number: acknowledgement_number,
                     flags: header_length_and_flags.1,
+                    window: window,
+                    options: options.into(),
                     payload: payload.into()
                 }
             )
@@ -147,6 +226,9 @@ mod tests {
 
         assert_eq!(l4.dst_port(), 80);
         assert_eq!(l4.src_port(), 50871);
+        assert_eq!(l4.sequence_number(), 1);
+        assert_eq!(l4.acknowledgement_number(), 2);
+        assert!(!l4.has_flags(FLAG_SYN));
         assert_eq!(l4.payload().as_slice(), [0x01u8, 0x02u8, 0x03u8, 0x04u8,
             0x00u8, 0x00u8, 0x00u8, 0x00u8,
             0x00u8, 0x00u8, 0x00u8, 0x00u8,
@@ -157,6 +239,36 @@ mod tests {
             0xfcu8, 0xfdu8, 0xfeu8, 0xffu8], "Payload Mismatch: {:x}", l4.payload().as_hex());
     }
 
+    #[test]
+    fn parse_tcp_options() {
+        let _ = env_logger::try_init();
+
+        let data = [
+            0xC6u8, 0xB7u8, //src port, 50871
+            0x00u8, 0x50u8, //dst port, 80
+            0x00u8, 0x00u8, 0x00u8, 0x01u8, //sequence number, 1
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, //acknowledgement number, 0
+            0x80u8, 0x02u8, //header length 8 words, syn
+            0xFAu8, 0xF0u8, //window, 64240
+            0x00u8, 0x00u8, //check
+            0x00u8, 0x00u8, //urgent
+            0x02u8, 0x04u8, 0x05u8, 0xB4u8, //mss, 1460
+            0x01u8, //nop
+            0x03u8, 0x03u8, 0x07u8, //window scale, 7
+            0x04u8, 0x02u8, //sack permitted
+            0x00u8, 0x00u8 //end of options
+        ];
+
+        let (_, l4) = Tcp::parse(&data).expect("Unable to parse");
+
+        assert_eq!(l4.window(), 64240);
+        assert_eq!(l4.options().len(), 12);
+        assert_eq!(l4.window_scale(), Some(7));
+
+        let (_, l4) = Tcp::parse(RAW_DATA).expect("Unable to parse");
+        assert_eq!(l4.window_scale(), None);
+    }
+
     #[test]
     fn convert_tcp() {
         let _ = env_logger::try_init();
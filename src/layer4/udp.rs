@@ -7,6 +7,7 @@ use std::convert::TryFrom;
 
 const HEADER_LENGTH: usize = 4 * std::mem::size_of::<u16>();
 
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Udp {
     dst_port: u16,
     src_port: u16,
@@ -37,15 +38,15 @@ impl Udp {
     }
 
     pub fn parse(input: &[u8]) -> IResult<&[u8], Udp> {
-        trace!("Available={}", input.len());
+        layer_span!("udp", length = input.len());
 
         do_parse!(input,
 
             dst_port: be_u16 >>
             src_port: be_u16 >>
-            length: map!(be_u16, |s| {
-                (s as usize) - HEADER_LENGTH
-            }) >>
+            length: add_return_error!(ErrorKind::Custom(errors::ParseCode::UdpLength as u32), map_opt!(be_u16, |s| {
+                (s as usize).checked_sub(HEADER_LENGTH)
+            })) >>
             checksum: be_u16 >>
             payload: take!(length) >>
 
@@ -0,0 +1,157 @@
+use std;
+
+const TLS_MAJOR_VERSION: u8 = 3;
+const TPKT_VERSION: u8 = 3;
+const X224_CONNECTION_REQUEST: u8 = 0xE0;
+const X224_CONNECTION_CONFIRM: u8 = 0xD0;
+const DNS_HEADER_LENGTH: usize = 12;
+
+const HTTP_PREFIXES: &'static [&'static [u8]] = &[
+    b"GET ", b"POST ", b"HEAD ", b"PUT ", b"DELETE ", b"OPTIONS ", b"CONNECT ", b"PATCH ", b"TRACE ", b"HTTP/1."
+];
+
+///
+/// Application protocols recognized by the signature of their payload rather than by port
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    Tls,
+    Ssh,
+    Http,
+    DnsOverTcp,
+    Rdp
+}
+
+impl Protocol {
+    ///
+    /// Protocol conventionally served on `port`
+    ///
+    pub fn from_port(port: u16) -> Option<Protocol> {
+        match port {
+            443 => Some(Protocol::Tls),
+            22 => Some(Protocol::Ssh),
+            80 | 8080 => Some(Protocol::Http),
+            53 => Some(Protocol::DnsOverTcp),
+            3389 => Some(Protocol::Rdp),
+            _ => None
+        }
+    }
+}
+
+impl std::fmt::Display for Protocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match *self {
+            Protocol::Tls => "TLS",
+            Protocol::Ssh => "SSH",
+            Protocol::Http => "HTTP",
+            Protocol::DnsOverTcp => "DNS over TCP",
+            Protocol::Rdp => "RDP"
+        };
+        write!(f, "{}", name)
+    }
+}
+
+fn be_u16(input: &[u8], offset: usize) -> usize {
+    (input[offset] as usize) << 8 | input[offset + 1] as usize
+}
+
+///
+/// Record header with a known content type and a SSL 3.0 to TLS 1.3 version
+///
+fn is_tls(payload: &[u8]) -> bool {
+    payload.len() >= 5 && payload[0] >= 20 && payload[0] <= 23 && payload[1] == TLS_MAJOR_VERSION && payload[2] <= 4
+}
+
+///
+/// TPKT header covering the whole segment, carrying an X.224 connection request or confirm
+///
+fn is_rdp(payload: &[u8]) -> bool {
+    payload.len() >= 7 &&
+        payload[0] == TPKT_VERSION &&
+        payload[1] == 0 &&
+        be_u16(payload, 2) == payload.len() &&
+        payload[4] as usize == payload.len() - 5 &&
+        (payload[5] == X224_CONNECTION_REQUEST || payload[5] == X224_CONNECTION_CONFIRM)
+}
+
+///
+/// Length prefixed message with a standard query or response header asking a single question
+///
+fn is_dns_over_tcp(payload: &[u8]) -> bool {
+    if payload.len() < 2 + DNS_HEADER_LENGTH {
+        return false
+    }
+    let length = be_u16(payload, 0);
+    let opcode = (payload[4] >> 3) & 0x0F;
+    length >= DNS_HEADER_LENGTH && length <= payload.len() - 2 && opcode == 0 && be_u16(payload, 6) == 1
+}
+
+///
+/// Protocol of a tcp payload identified by its signature, regardless of port. Signatures match the
+/// start of a stream, so the first payload in each direction is the one to inspect.
+///
+pub fn detect(payload: &[u8]) -> Option<Protocol> {
+    if payload.starts_with(b"SSH-") {
+        Some(Protocol::Ssh)
+    } else if HTTP_PREFIXES.iter().any(|p| payload.starts_with(p)) {
+        Some(Protocol::Http)
+    } else if is_tls(payload) {
+        Some(Protocol::Tls)
+    } else if is_rdp(payload) {
+        Some(Protocol::Rdp)
+    } else if is_dns_over_tcp(payload) {
+        Some(Protocol::DnsOverTcp)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    #[test]
+    fn detect_protocols() {
+        let _ = env_logger::try_init();
+
+        assert_eq!(detect(b"SSH-2.0-OpenSSH_8.9\r\n"), Some(Protocol::Ssh));
+        assert_eq!(detect(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n"), Some(Protocol::Http));
+        assert_eq!(detect(b"HTTP/1.1 200 OK\r\n"), Some(Protocol::Http));
+
+        let client_hello = [
+            0x16u8, //handshake
+            0x03u8, 0x01u8, //tls 1.0 record version
+            0x00u8, 0x04u8, //length
+            0x01u8, 0x00u8, 0x00u8, 0x00u8 //client hello, truncated
+        ];
+        assert_eq!(detect(&client_hello), Some(Protocol::Tls));
+
+        let connection_request = [
+            0x03u8, 0x00u8, //tpkt version 3
+            0x00u8, 0x0Bu8, //length, 11
+            0x06u8, //x.224 length indicator
+            0xE0u8, //connection request
+            0x00u8, 0x00u8, //dst reference
+            0x00u8, 0x00u8, //src reference
+            0x00u8 //class
+        ];
+        assert_eq!(detect(&connection_request), Some(Protocol::Rdp));
+
+        let query = [
+            0x00u8, 0x1Du8, //length, 29
+            0x12u8, 0x34u8, //id
+            0x01u8, 0x00u8, //standard query, recursion desired
+            0x00u8, 0x01u8, //1 question
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, //no answers, authorities or additional records
+            0x07u8, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 0x03u8, b'c', b'o', b'm', 0x00u8, //example.com
+            0x00u8, 0x01u8, //a
+            0x00u8, 0x01u8 //in
+        ];
+        assert_eq!(detect(&query), Some(Protocol::DnsOverTcp));
+
+        assert_eq!(detect(&[0x00u8, 0x01u8, 0x02u8, 0x03u8]), None);
+        assert_eq!(detect(&[]), None);
+    }
+}
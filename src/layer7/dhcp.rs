@@ -0,0 +1,353 @@
+use super::prelude::*;
+use self::layer4::udp::Udp;
+
+use self::nom::*;
+use std;
+
+pub const SERVER_PORT: u16 = 67;
+pub const CLIENT_PORT: u16 = 68;
+
+const MAGIC_COOKIE: u32 = 0x63825363;
+const CLIENT_HARDWARE_ADDRESS_LENGTH: usize = 16;
+const SERVER_NAME_LENGTH: usize = 64;
+const BOOT_FILE_LENGTH: usize = 128;
+
+const OPTION_PAD: u8 = 0;
+const OPTION_HOSTNAME: u8 = 12;
+const OPTION_REQUESTED_IP: u8 = 50;
+const OPTION_LEASE_TIME: u8 = 51;
+const OPTION_MESSAGE_TYPE: u8 = 53;
+const OPTION_SERVER_IDENTIFIER: u8 = 54;
+const OPTION_END: u8 = 255;
+
+///
+/// DHCP message types, carried in option 53 https://tools.ietf.org/html/rfc2132#section-9.6
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DhcpMessageType {
+    Discover,
+    Offer,
+    Request,
+    Decline,
+    Ack,
+    Nak,
+    Release,
+    Inform,
+    Other(u8)
+}
+
+impl DhcpMessageType {
+    pub fn new(value: u8) -> DhcpMessageType {
+        match value {
+            1 => DhcpMessageType::Discover,
+            2 => DhcpMessageType::Offer,
+            3 => DhcpMessageType::Request,
+            4 => DhcpMessageType::Decline,
+            5 => DhcpMessageType::Ack,
+            6 => DhcpMessageType::Nak,
+            7 => DhcpMessageType::Release,
+            8 => DhcpMessageType::Inform,
+            x => DhcpMessageType::Other(x)
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DhcpOption {
+    code: u8,
+    value: std::vec::Vec<u8>
+}
+
+impl DhcpOption {
+    pub fn code(&self) -> u8 {
+        self.code
+    }
+    pub fn value(&self) -> &std::vec::Vec<u8> {
+        &self.value
+    }
+}
+
+///
+/// DHCP (BOOTP) message https://tools.ietf.org/html/rfc2131
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Dhcp {
+    op: u8,
+    xid: u32,
+    client_ip: std::net::Ipv4Addr,
+    your_ip: std::net::Ipv4Addr,
+    server_ip: std::net::Ipv4Addr,
+    relay_ip: std::net::Ipv4Addr,
+    client_mac: MacAddress,
+    options: std::vec::Vec<DhcpOption>,
+    truncated: bool
+}
+
+fn to_ip_address(i: &[u8]) -> std::net::Ipv4Addr {
+    std::net::Ipv4Addr::from(array_ref![i, 0, 4].clone())
+}
+
+fn to_mac_address(i: &[u8]) -> MacAddress {
+    MacAddress(array_ref![i, 0, MAC_LENGTH].clone())
+}
+
+named!(ipv4_address<&[u8], std::net::Ipv4Addr>, map!(take!(4), to_ip_address));
+
+///
+/// Options up to the end option. When parsing a `truncated` message, stops at an option cut short
+/// by the capture, setting the flag.
+///
+fn options(input: &[u8], truncated: bool) -> IResult<&[u8], (std::vec::Vec<DhcpOption>, bool)> {
+    let mut options = vec![];
+    let mut current = input;
+
+    loop {
+        match current.split_first() {
+            None => break,
+            Some((&OPTION_END, rem)) => {
+                current = rem;
+                break
+            }
+            Some((&OPTION_PAD, rem)) => {
+                current = rem;
+            }
+            Some((&code, rem)) => {
+                let (rem, value) = match do_parse!(rem,
+                    length: be_u8 >>
+                    value: take!(length) >>
+                    (value)
+                ) {
+                    Err(Err::Incomplete(_)) if truncated => return Ok( (&rem[rem.len()..], (options, true)) ),
+                    r => r?
+                };
+                options.push(DhcpOption {
+                    code: code,
+                    value: value.into()
+                });
+                current = rem;
+            }
+        }
+    }
+
+    Ok( (current, (options, false)) )
+}
+
+impl Dhcp {
+    ///
+    /// 1 for client requests, 2 for server replies
+    ///
+    pub fn op(&self) -> u8 {
+        self.op
+    }
+    ///
+    /// Transaction id chosen by the client, shared by all messages of an exchange
+    ///
+    pub fn xid(&self) -> u32 {
+        self.xid
+    }
+    pub fn client_ip(&self) -> &std::net::Ipv4Addr {
+        &self.client_ip
+    }
+    ///
+    /// Address offered or assigned to the client
+    ///
+    pub fn your_ip(&self) -> &std::net::Ipv4Addr {
+        &self.your_ip
+    }
+    pub fn server_ip(&self) -> &std::net::Ipv4Addr {
+        &self.server_ip
+    }
+    pub fn relay_ip(&self) -> &std::net::Ipv4Addr {
+        &self.relay_ip
+    }
+    pub fn client_mac(&self) -> &MacAddress {
+        &self.client_mac
+    }
+    pub fn options(&self) -> &std::vec::Vec<DhcpOption> {
+        &self.options
+    }
+
+    pub fn option(&self, code: u8) -> Option<&DhcpOption> {
+        self.options.iter().find(|o| o.code == code)
+    }
+
+    fn option_ip(&self, code: u8) -> Option<std::net::Ipv4Addr> {
+        self.option(code).filter(|o| o.value.len() == 4).map(|o| to_ip_address(&o.value))
+    }
+
+    pub fn message_type(&self) -> Option<DhcpMessageType> {
+        self.option(OPTION_MESSAGE_TYPE).and_then(|o| o.value.first()).map(|t| DhcpMessageType::new(*t))
+    }
+
+    pub fn hostname(&self) -> Option<String> {
+        self.option(OPTION_HOSTNAME).map(|o| String::from_utf8_lossy(&o.value).into_owned())
+    }
+
+    pub fn requested_ip(&self) -> Option<std::net::Ipv4Addr> {
+        self.option_ip(OPTION_REQUESTED_IP)
+    }
+
+    pub fn server_identifier(&self) -> Option<std::net::Ipv4Addr> {
+        self.option_ip(OPTION_SERVER_IDENTIFIER)
+    }
+
+    pub fn lease_time(&self) -> Option<std::time::Duration> {
+        self.option(OPTION_LEASE_TIME).filter(|o| o.value.len() == 4).map(|o| {
+            let v = &o.value;
+            std::time::Duration::from_secs(((v[0] as u64) << 24) | ((v[1] as u64) << 16) | ((v[2] as u64) << 8) | v[3] as u64)
+        })
+    }
+
+    ///
+    /// Whether a datagram was sent to or from a DHCP port
+    ///
+    pub fn is_dhcp_port(udp: &Udp) -> bool {
+        [udp.src_port(), udp.dst_port()].iter().any(|p| *p == SERVER_PORT || *p == CLIENT_PORT)
+    }
+
+    ///
+    /// Whether the message was cut short by the capture, when parsed with `parse_truncated`, leaving
+    /// out the options that weren't captured whole
+    ///
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Dhcp> {
+        Dhcp::parse_message(input, false)
+    }
+
+    ///
+    /// Parse a message that may have been cut short by the capture's snap length in its options,
+    /// keeping the options captured whole
+    ///
+    pub fn parse_truncated(input: &[u8]) -> IResult<&[u8], Dhcp> {
+        Dhcp::parse_message(input, true)
+    }
+
+    fn parse_message(input: &[u8], truncated: bool) -> IResult<&[u8], Dhcp> {
+        layer_span!("dhcp", length = input.len());
+
+        do_parse!(input,
+
+            op: be_u8 >>
+            _htype: be_u8 >>
+            _hlen: be_u8 >>
+            _hops: be_u8 >>
+            xid: be_u32 >>
+            _secs: be_u16 >>
+            _flags: be_u16 >>
+            client_ip: ipv4_address >>
+            your_ip: ipv4_address >>
+            server_ip: ipv4_address >>
+            relay_ip: ipv4_address >>
+            client_mac: map!(take!(CLIENT_HARDWARE_ADDRESS_LENGTH), to_mac_address) >>
+            _sname: take!(SERVER_NAME_LENGTH) >>
+            _file: take!(BOOT_FILE_LENGTH) >>
+            _cookie: add_return_error!(ErrorKind::Custom(errors::ParseCode::DhcpMagicCookie as u32), verify!(be_u32, |c: u32| c == MAGIC_COOKIE)) >>
+            options: apply!(options, truncated) >>
+
+            (
+                Dhcp {
+                    op: op,
+                    xid: xid,
+                    client_ip: client_ip,
+                    your_ip: your_ip,
+                    server_ip: server_ip,
+                    relay_ip: relay_ip,
+                    client_mac: client_mac,
+                    options: options.0,
+                    truncated: options.1
+                }
+            )
+        )
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    ///
+    /// DHCP message from client 02:00:00:00:00:01 with the given type, transaction, assigned address and options
+    ///
+    pub fn message(op: u8, xid: u32, your_ip: [u8; 4], options: &[u8]) -> std::vec::Vec<u8> {
+        let mut data = vec![
+            op, //op
+            0x01u8, //htype, ethernet
+            0x06u8, //hlen
+            0x00u8, //hops
+            (xid >> 24) as u8, (xid >> 16) as u8, (xid >> 8) as u8, xid as u8, //xid
+            0x00u8, 0x00u8, //secs
+            0x00u8, 0x00u8, //flags
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, //ciaddr
+            your_ip[0], your_ip[1], your_ip[2], your_ip[3], //yiaddr
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, //siaddr
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, //giaddr
+            0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x01u8 //chaddr
+        ];
+        data.extend_from_slice(&[0u8; CLIENT_HARDWARE_ADDRESS_LENGTH - MAC_LENGTH + SERVER_NAME_LENGTH + BOOT_FILE_LENGTH]);
+        data.extend_from_slice(&[0x63u8, 0x82u8, 0x53u8, 0x63u8]); //magic cookie
+        data.extend_from_slice(options);
+        data.push(OPTION_END);
+        data
+    }
+
+    #[test]
+    fn parse_dhcp_ack() {
+        let _ = env_logger::try_init();
+
+        let data = message(2, 0x3903F326, [192, 168, 1, 100], &[
+            0x35u8, 0x01u8, 0x05u8, //message type, ack
+            0x36u8, 0x04u8, 0xC0u8, 0xA8u8, 0x01u8, 0x01u8, //server identifier 192.168.1.1
+            0x33u8, 0x04u8, 0x00u8, 0x01u8, 0x51u8, 0x80u8, //lease time, 86400
+            0x00u8, //pad
+            0x0Cu8, 0x04u8, b'h', b'o', b's', b't' //hostname
+        ]);
+
+        let (rem, dhcp) = Dhcp::parse(&data).expect("Could not parse");
+
+        assert!(rem.is_empty());
+        assert_eq!(dhcp.op(), 2);
+        assert_eq!(dhcp.xid(), 0x3903F326);
+        assert_eq!(dhcp.client_mac(), &MacAddress([0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x01u8]));
+        assert_eq!(dhcp.your_ip(), &std::net::Ipv4Addr::new(192, 168, 1, 100));
+        assert_eq!(dhcp.message_type(), Some(DhcpMessageType::Ack));
+        assert_eq!(dhcp.server_identifier(), Some(std::net::Ipv4Addr::new(192, 168, 1, 1)));
+        assert_eq!(dhcp.lease_time(), Some(std::time::Duration::from_secs(86400)));
+        assert_eq!(dhcp.hostname(), Some("host".to_string()));
+        assert_eq!(dhcp.requested_ip(), None);
+    }
+
+    #[test]
+    fn parse_dhcp_invalid_cookie() {
+        let _ = env_logger::try_init();
+
+        let mut data = message(1, 1, [0, 0, 0, 0], &[0x35u8, 0x01u8, 0x01u8]);
+        data[236] = 0x00u8;
+
+        let err = Dhcp::parse(&data).err().map(|e| errors::Error::from_nom(e, errors::Layer::Dhcp, &data)).expect("Parsed invalid cookie");
+
+        assert_eq!(format!("{}", err), "DHCP header at offset 236: expected magic cookie 0x63825363");
+    }
+
+    #[test]
+    fn parse_truncated_dhcp() {
+        let _ = env_logger::try_init();
+
+        let data = message(1, 1, [0, 0, 0, 0], &[
+            0x35u8, 0x01u8, 0x03u8, //message type, request
+            0x0Cu8, 0x04u8, b'h', b'o', b's', b't' //hostname
+        ]);
+        let data = &data[..240 + 3 + 4]; //cut in the hostname
+        assert!(Dhcp::parse(data).is_err());
+
+        let (rem, dhcp) = Dhcp::parse_truncated(data).expect("Could not parse");
+        assert!(rem.is_empty());
+        assert!(dhcp.is_truncated());
+        assert_eq!(dhcp.message_type(), Some(DhcpMessageType::Request));
+        assert_eq!(dhcp.hostname(), None);
+    }
+}
@@ -0,0 +1,453 @@
+use super::prelude::*;
+
+use self::nom::*;
+use std;
+
+pub const PORT: u16 = 53;
+
+const MAX_POINTERS: usize = 16;
+
+///
+/// Resource record types https://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#dns-parameters-4
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RecordType {
+    A,
+    Ns,
+    Cname,
+    Soa,
+    Ptr,
+    Mx,
+    Txt,
+    Aaaa,
+    Srv,
+    Other(u16)
+}
+
+impl RecordType {
+    pub fn new(value: u16) -> RecordType {
+        match value {
+            1 => RecordType::A,
+            2 => RecordType::Ns,
+            5 => RecordType::Cname,
+            6 => RecordType::Soa,
+            12 => RecordType::Ptr,
+            15 => RecordType::Mx,
+            16 => RecordType::Txt,
+            28 => RecordType::Aaaa,
+            33 => RecordType::Srv,
+            x => RecordType::Other(x)
+        }
+    }
+}
+
+impl std::fmt::Display for RecordType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            RecordType::A => write!(f, "A"),
+            RecordType::Ns => write!(f, "NS"),
+            RecordType::Cname => write!(f, "CNAME"),
+            RecordType::Soa => write!(f, "SOA"),
+            RecordType::Ptr => write!(f, "PTR"),
+            RecordType::Mx => write!(f, "MX"),
+            RecordType::Txt => write!(f, "TXT"),
+            RecordType::Aaaa => write!(f, "AAAA"),
+            RecordType::Srv => write!(f, "SRV"),
+            RecordType::Other(x) => write!(f, "TYPE{}", x)
+        }
+    }
+}
+
+///
+/// Decoded resource record data, with compressed names expanded
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RecordData {
+    A(std::net::Ipv4Addr),
+    Aaaa(std::net::Ipv6Addr),
+    ///
+    /// Target of an NS, CNAME or PTR record
+    ///
+    Name(String),
+    Mx {
+        preference: u16,
+        exchange: String
+    },
+    Txt(std::vec::Vec<String>),
+    Other(std::vec::Vec<u8>)
+}
+
+impl std::fmt::Display for RecordData {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            RecordData::A(ref ip) => write!(f, "{}", ip),
+            RecordData::Aaaa(ref ip) => write!(f, "{}", ip),
+            RecordData::Name(ref name) => write!(f, "{}", name),
+            RecordData::Mx { preference, ref exchange } => write!(f, "{} {}", preference, exchange),
+            RecordData::Txt(ref strings) => write!(f, "{}", strings.join(" ")),
+            RecordData::Other(ref data) => {
+                for b in data {
+                    write!(f, "{:02x}", b)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Question {
+    name: String,
+    record_type: RecordType,
+    class: u16
+}
+
+impl Question {
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+    pub fn record_type(&self) -> RecordType {
+        self.record_type
+    }
+    pub fn class(&self) -> u16 {
+        self.class
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ResourceRecord {
+    name: String,
+    record_type: RecordType,
+    class: u16,
+    ttl: u32,
+    data: RecordData
+}
+
+impl ResourceRecord {
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+    pub fn record_type(&self) -> RecordType {
+        self.record_type
+    }
+    pub fn class(&self) -> u16 {
+        self.class
+    }
+    pub fn ttl(&self) -> u32 {
+        self.ttl
+    }
+    pub fn data(&self) -> &RecordData {
+        &self.data
+    }
+}
+
+///
+/// DNS message https://tools.ietf.org/html/rfc1035#section-4
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Dns {
+    id: u16,
+    flags: u16,
+    questions: std::vec::Vec<Question>,
+    answers: std::vec::Vec<ResourceRecord>,
+    authorities: std::vec::Vec<ResourceRecord>,
+    additionals: std::vec::Vec<ResourceRecord>,
+    truncated: bool
+}
+
+fn name_error<'a, T>(input: &'a [u8]) -> IResult<&'a [u8], T> {
+    Err(Err::Error(error_position!(input, ErrorKind::Custom(errors::ParseCode::DnsName as u32))))
+}
+
+///
+/// Name starting at `input`, following compression pointers into `message`
+///
+fn name<'a>(message: &'a [u8], input: &'a [u8]) -> IResult<&'a [u8], String> {
+    let mut labels: std::vec::Vec<String> = vec![];
+    let mut current = input;
+    let mut after_pointer = None;
+    let mut pointers = 0;
+
+    loop {
+        let (rem, length) = be_u8(current)?;
+        if length == 0 {
+            return Ok( (after_pointer.unwrap_or(rem), labels.join(".")) )
+        } else if length & 0xC0 == 0xC0 {
+            let (rem, low) = be_u8(rem)?;
+            let offset = (((length & 0x3F) as usize) << 8) | low as usize;
+            pointers += 1;
+            if pointers > MAX_POINTERS || offset >= message.len() {
+                return name_error(current)
+            }
+            after_pointer = after_pointer.or(Some(rem));
+            current = &message[offset..];
+        } else if length & 0xC0 != 0 {
+            return name_error(current)
+        } else {
+            let (rem, label) = take!(rem, length)?;
+            labels.push(String::from_utf8_lossy(label).into_owned());
+            current = rem;
+        }
+    }
+}
+
+fn character_strings(mut input: &[u8]) -> IResult<&[u8], std::vec::Vec<String>> {
+    let mut strings = vec![];
+    while !input.is_empty() {
+        let (rem, s) = length_bytes!(input, be_u8)?;
+        strings.push(String::from_utf8_lossy(s).into_owned());
+        input = rem;
+    }
+    Ok( (input, strings) )
+}
+
+fn record_data<'a>(message: &'a [u8], record_type: RecordType, data: &'a [u8]) -> IResult<&'a [u8], RecordData> {
+    match record_type {
+        RecordType::A if data.len() == 4 => {
+            Ok( (&data[4..], RecordData::A(std::net::Ipv4Addr::from(array_ref![data, 0, 4].clone()))) )
+        }
+        RecordType::Aaaa if data.len() == 16 => {
+            Ok( (&data[16..], RecordData::Aaaa(std::net::Ipv6Addr::from(array_ref![data, 0, 16].clone()))) )
+        }
+        RecordType::Ns | RecordType::Cname | RecordType::Ptr => {
+            name(message, data).map(|(rem, n)| (rem, RecordData::Name(n)))
+        }
+        RecordType::Mx => {
+            let (rem, preference) = be_u16(data)?;
+            name(message, rem).map(|(rem, exchange)| (rem, RecordData::Mx { preference: preference, exchange: exchange }))
+        }
+        RecordType::Txt => {
+            character_strings(data).map(|(rem, strings)| (rem, RecordData::Txt(strings)))
+        }
+        _ => Ok( (&data[data.len()..], RecordData::Other(data.into())) )
+    }
+}
+
+fn question<'a>(message: &'a [u8], input: &'a [u8]) -> IResult<&'a [u8], Question> {
+    let (rem, name) = name(message, input)?;
+    do_parse!(rem,
+
+        record_type: be_u16 >>
+        class: be_u16 >>
+
+        (
+            Question {
+                name: name,
+                record_type: RecordType::new(record_type),
+                class: class
+            }
+        )
+    )
+}
+
+fn resource_record<'a>(message: &'a [u8], input: &'a [u8]) -> IResult<&'a [u8], ResourceRecord> {
+    let (rem, name) = name(message, input)?;
+    let (rem, (record_type, class, ttl, data)) = do_parse!(rem,
+
+        record_type: be_u16 >>
+        class: be_u16 >>
+        ttl: be_u32 >>
+        data: length_bytes!(be_u16) >>
+
+        ( (RecordType::new(record_type), class, ttl, data) )
+    )?;
+    let (_, data) = record_data(message, record_type, data)?;
+
+    Ok( (rem, ResourceRecord {
+        name: name,
+        record_type: record_type,
+        class: class,
+        ttl: ttl,
+        data: data
+    }) )
+}
+
+///
+/// `count` items parsed by `f`. When parsing a `truncated` message, stops at an item cut short by
+/// the capture, setting the flag.
+///
+fn section<'a, T, F>(mut input: &'a [u8], count: u16, truncated: bool, f: F) -> IResult<&'a [u8], (std::vec::Vec<T>, bool)> where F: Fn(&'a [u8]) -> IResult<&'a [u8], T> {
+    let mut items = vec![];
+    for _ in 0..count {
+        match f(input) {
+            Ok( (rem, item) ) => {
+                items.push(item);
+                input = rem;
+            }
+            Err(Err::Incomplete(_)) if truncated => return Ok( (&input[input.len()..], (items, true)) ),
+            Err(e) => return Err(e)
+        }
+    }
+    Ok( (input, (items, false)) )
+}
+
+impl Dns {
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+    pub fn questions(&self) -> &std::vec::Vec<Question> {
+        &self.questions
+    }
+    pub fn answers(&self) -> &std::vec::Vec<ResourceRecord> {
+        &self.answers
+    }
+    pub fn authorities(&self) -> &std::vec::Vec<ResourceRecord> {
+        &self.authorities
+    }
+    pub fn additionals(&self) -> &std::vec::Vec<ResourceRecord> {
+        &self.additionals
+    }
+
+    pub fn is_response(&self) -> bool {
+        self.flags & 0x8000 != 0
+    }
+
+    pub fn response_code(&self) -> u8 {
+        (self.flags & 0x000F) as u8
+    }
+
+    ///
+    /// Whether the message was cut short by the capture, when parsed with `parse_truncated`, leaving
+    /// out the records that weren't captured whole. Unrelated to the truncation (TC) flag.
+    ///
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Dns> {
+        Dns::parse_message(input, false)
+    }
+
+    ///
+    /// Parse a message that may have been cut short by the capture's snap length, keeping the
+    /// questions and records captured whole
+    ///
+    pub fn parse_truncated(input: &[u8]) -> IResult<&[u8], Dns> {
+        Dns::parse_message(input, true)
+    }
+
+    fn parse_message(input: &[u8], truncated: bool) -> IResult<&[u8], Dns> {
+        layer_span!("dns", length = input.len());
+
+        let (rem, (id, flags, question_count, answer_count, authority_count, additional_count)) = do_parse!(input,
+
+            id: be_u16 >>
+            flags: be_u16 >>
+            qdcount: be_u16 >>
+            ancount: be_u16 >>
+            nscount: be_u16 >>
+            arcount: be_u16 >>
+
+            ( (id, flags, qdcount, ancount, nscount, arcount) )
+        )?;
+
+        let (rem, (questions, questions_cut)) = section(rem, question_count, truncated, |i| question(input, i))?;
+        let (rem, (answers, answers_cut)) = section(rem, answer_count, truncated, |i| resource_record(input, i))?;
+        let (rem, (authorities, authorities_cut)) = section(rem, authority_count, truncated, |i| resource_record(input, i))?;
+        let (rem, (additionals, additionals_cut)) = section(rem, additional_count, truncated, |i| resource_record(input, i))?;
+
+        Ok( (rem, Dns {
+            id: id,
+            flags: flags,
+            questions: questions,
+            answers: answers,
+            authorities: authorities,
+            additionals: additionals,
+            truncated: questions_cut || answers_cut || authorities_cut || additionals_cut
+        }) )
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    pub const RESPONSE_DATA: &'static [u8] = &[
+        0x12u8, 0x34u8, //id
+        0x81u8, 0x80u8, //flags, response, recursion desired and available
+        0x00u8, 0x01u8, //questions
+        0x00u8, 0x02u8, //answers
+        0x00u8, 0x00u8, //authorities
+        0x00u8, 0x00u8, //additionals
+        //question
+        0x03u8, b'w', b'w', b'w', 0x07u8, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 0x03u8, b'c', b'o', b'm', 0x00u8, //www.example.com
+        0x00u8, 0x01u8, //type A
+        0x00u8, 0x01u8, //class IN
+        //answer
+        0xC0u8, 0x0Cu8, //pointer to www.example.com
+        0x00u8, 0x05u8, //type CNAME
+        0x00u8, 0x01u8, //class IN
+        0x00u8, 0x00u8, 0x0Eu8, 0x10u8, //ttl, 3600
+        0x00u8, 0x06u8, //length
+        0x03u8, b'w', b'e', b'b', 0xC0u8, 0x10u8, //web.example.com
+        //answer
+        0xC0u8, 0x2Du8, //pointer to web.example.com
+        0x00u8, 0x01u8, //type A
+        0x00u8, 0x01u8, //class IN
+        0x00u8, 0x00u8, 0x00u8, 0x3Cu8, //ttl, 60
+        0x00u8, 0x04u8, //length
+        0x5Du8, 0xB8u8, 0xD8u8, 0x22u8 //93.184.216.34
+    ];
+
+    #[test]
+    fn parse_dns_response() {
+        let _ = env_logger::try_init();
+
+        let (rem, dns) = Dns::parse(RESPONSE_DATA).expect("Could not parse");
+
+        assert!(rem.is_empty());
+        assert_eq!(dns.id(), 0x1234);
+        assert!(dns.is_response());
+        assert_eq!(dns.response_code(), 0);
+        assert_eq!(dns.questions().len(), 1);
+        assert_eq!(dns.questions()[0].name(), "www.example.com");
+        assert_eq!(dns.questions()[0].record_type(), RecordType::A);
+
+        let answers = dns.answers();
+        assert_eq!(answers.len(), 2);
+        assert_eq!(answers[0].name(), "www.example.com");
+        assert_eq!(answers[0].record_type(), RecordType::Cname);
+        assert_eq!(answers[0].ttl(), 3600);
+        assert_eq!(answers[0].data(), &RecordData::Name("web.example.com".to_string()));
+        assert_eq!(answers[1].name(), "web.example.com");
+        assert_eq!(answers[1].data(), &RecordData::A(std::net::Ipv4Addr::new(93, 184, 216, 34)));
+        assert_eq!(format!("{} {}", answers[1].record_type(), answers[1].data()), "A 93.184.216.34");
+    }
+
+    #[test]
+    fn parse_dns_pointer_loop() {
+        let _ = env_logger::try_init();
+
+        let mut data = RESPONSE_DATA.to_vec();
+        data[45] = 0xC0u8; //web.example.com now points at itself
+        data[46] = 0x2Du8;
+
+        let err = Dns::parse(&data).err().map(|e| errors::Error::from_nom(e, errors::Layer::Dns, &data)).expect("Parsed pointer loop");
+
+        assert_eq!(format!("{}", err), "DNS header at offset 45: expected name labels and at most 16 compression pointers within the message");
+    }
+
+    #[test]
+    fn parse_truncated_dns_response() {
+        let _ = env_logger::try_init();
+
+        let data = &RESPONSE_DATA[..RESPONSE_DATA.len() - 2]; //cut in the last address
+        assert!(Dns::parse(data).is_err());
+
+        let (rem, dns) = Dns::parse_truncated(data).expect("Could not parse");
+        assert!(rem.is_empty());
+        assert!(dns.is_truncated());
+        assert_eq!(dns.questions()[0].name(), "www.example.com");
+        assert_eq!(dns.answers().len(), 1);
+        assert_eq!(dns.answers()[0].data(), &RecordData::Name("web.example.com".to_string()));
+
+        let (_, dns) = Dns::parse_truncated(RESPONSE_DATA).expect("Could not parse");
+        assert!(!dns.is_truncated());
+        assert!(Dns::parse_truncated(&RESPONSE_DATA[..6]).is_err());
+    }
+}
@@ -0,0 +1,523 @@
+use super::prelude::*;
+use super::{captured_bytes, captured_list};
+use self::layer4::udp::Udp;
+
+use self::nom::*;
+use std;
+
+///
+/// UDP port GTPv2-C signaling is exchanged on
+///
+pub const GTP_C_PORT: u16 = 2123;
+
+const VERSION: u8 = 2;
+const PIGGYBACK_FLAG: u8 = 0x10;
+const TEID_FLAG: u8 = 0x08;
+const FIXED_HEADER_LENGTH: usize = 4;
+const SEQUENCE_LENGTH: usize = 4; //3 byte sequence number and a spare byte
+
+///
+/// GTPv2-C message types https://www.etsi.org/deliver/etsi_ts/129200_129299/129274/
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum GtpV2MessageType {
+    EchoRequest,
+    EchoResponse,
+    VersionNotSupported,
+    CreateSessionRequest,
+    CreateSessionResponse,
+    ModifyBearerRequest,
+    ModifyBearerResponse,
+    DeleteSessionRequest,
+    DeleteSessionResponse,
+    CreateBearerRequest,
+    CreateBearerResponse,
+    UpdateBearerRequest,
+    UpdateBearerResponse,
+    DeleteBearerRequest,
+    DeleteBearerResponse,
+    ReleaseAccessBearersRequest,
+    ReleaseAccessBearersResponse,
+    DownlinkDataNotification,
+    DownlinkDataNotificationAcknowledge,
+    Other(u8)
+}
+
+impl GtpV2MessageType {
+    pub fn new(value: u8) -> GtpV2MessageType {
+        match value {
+            1 => GtpV2MessageType::EchoRequest,
+            2 => GtpV2MessageType::EchoResponse,
+            3 => GtpV2MessageType::VersionNotSupported,
+            32 => GtpV2MessageType::CreateSessionRequest,
+            33 => GtpV2MessageType::CreateSessionResponse,
+            34 => GtpV2MessageType::ModifyBearerRequest,
+            35 => GtpV2MessageType::ModifyBearerResponse,
+            36 => GtpV2MessageType::DeleteSessionRequest,
+            37 => GtpV2MessageType::DeleteSessionResponse,
+            95 => GtpV2MessageType::CreateBearerRequest,
+            96 => GtpV2MessageType::CreateBearerResponse,
+            97 => GtpV2MessageType::UpdateBearerRequest,
+            98 => GtpV2MessageType::UpdateBearerResponse,
+            99 => GtpV2MessageType::DeleteBearerRequest,
+            100 => GtpV2MessageType::DeleteBearerResponse,
+            170 => GtpV2MessageType::ReleaseAccessBearersRequest,
+            171 => GtpV2MessageType::ReleaseAccessBearersResponse,
+            176 => GtpV2MessageType::DownlinkDataNotification,
+            177 => GtpV2MessageType::DownlinkDataNotificationAcknowledge,
+            x => {
+                debug!("Encountered {} when parsing GTPv2 message type", x);
+                GtpV2MessageType::Other(x)
+            }
+        }
+    }
+}
+
+///
+/// Information element types decoded by this crate, other types are kept as raw values
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum InformationElementType {
+    Imsi,
+    Cause,
+    Recovery,
+    Apn,
+    Msisdn,
+    FTeid,
+    Other(u8)
+}
+
+impl InformationElementType {
+    pub fn new(value: u8) -> InformationElementType {
+        match value {
+            1 => InformationElementType::Imsi,
+            2 => InformationElementType::Cause,
+            3 => InformationElementType::Recovery,
+            71 => InformationElementType::Apn,
+            76 => InformationElementType::Msisdn,
+            87 => InformationElementType::FTeid,
+            x => InformationElementType::Other(x)
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct InformationElement {
+    ie_type: InformationElementType,
+    instance: u8,
+    value: std::vec::Vec<u8>
+}
+
+impl InformationElement {
+    pub fn ie_type(&self) -> &InformationElementType {
+        &self.ie_type
+    }
+    pub fn instance(&self) -> u8 {
+        self.instance
+    }
+    pub fn value(&self) -> &std::vec::Vec<u8> {
+        &self.value
+    }
+}
+
+///
+/// Fully qualified tunnel endpoint identifier, as carried in an F-TEID information element
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FTeid {
+    pub instance: u8,
+    pub interface_type: u8,
+    pub teid: u32,
+    pub ipv4: Option<std::net::Ipv4Addr>,
+    pub ipv6: Option<std::net::Ipv6Addr>
+}
+
+///
+/// Decode telephony binary coded decimal digits (IMSI, MSISDN), low nibble first, stopping at filler
+///
+fn decode_tbcd(value: &[u8]) -> String {
+    let mut digits = String::with_capacity(value.len() * 2);
+    for b in value {
+        for nibble in [b & 0x0F, b >> 4].iter() {
+            if *nibble > 9 {
+                return digits
+            }
+            digits.push((b'0' + nibble) as char);
+        }
+    }
+    digits
+}
+
+///
+/// Decode an access point name from its length prefixed labels
+///
+fn decode_apn(value: &[u8]) -> Option<String> {
+    let mut labels = vec![];
+    let mut current = value;
+    while let Some((length, rem)) = current.split_first() {
+        let length = *length as usize;
+        if rem.len() < length {
+            return None
+        }
+        labels.push(std::str::from_utf8(&rem[..length]).ok()?);
+        current = &rem[length..];
+    }
+    Some(labels.join("."))
+}
+
+fn decode_fteid(ie: &InformationElement) -> Option<FTeid> {
+    let value = &ie.value;
+    if value.len() < 5 {
+        return None
+    }
+    let flags = value[0];
+    let teid = (value[1] as u32) << 24 | (value[2] as u32) << 16 | (value[3] as u32) << 8 | value[4] as u32;
+    let mut current = &value[5..];
+
+    let ipv4 = if flags & 0x80 != 0 {
+        if current.len() < 4 {
+            return None
+        }
+        let ip = std::net::Ipv4Addr::from(array_ref![current, 0, 4].clone());
+        current = &current[4..];
+        Some(ip)
+    } else {
+        None
+    };
+    let ipv6 = if flags & 0x40 != 0 {
+        if current.len() < 16 {
+            return None
+        }
+        Some(std::net::Ipv6Addr::from(array_ref![current, 0, 16].clone()))
+    } else {
+        None
+    };
+
+    Some(FTeid {
+        instance: ie.instance,
+        interface_type: flags & 0x3F,
+        teid: teid,
+        ipv4: ipv4,
+        ipv6: ipv6
+    })
+}
+
+named!(information_element<&[u8], InformationElement>, do_parse!(
+    ie_type: be_u8 >>
+    length: be_u16 >>
+    instance: be_u8 >>
+    value: take!(length) >>
+
+    (
+        InformationElement {
+            ie_type: InformationElementType::new(ie_type),
+            instance: instance & 0x0F,
+            value: value.into()
+        }
+    )
+));
+
+fn information_elements(input: &[u8]) -> IResult<&[u8], std::vec::Vec<InformationElement>> {
+    let mut ies = vec![];
+    let mut current = input;
+
+    while !current.is_empty() {
+        match information_element(current) {
+            Ok( (rem, ie) ) => {
+                current = rem;
+                ies.push(ie);
+            }
+            Err(Err::Incomplete(_)) => {
+                return Err(Err::Error(error_position!(current, ErrorKind::Custom(errors::ParseCode::GtpInformationElementLength as u32))))
+            }
+            Err(e) => return Err(e)
+        }
+    }
+
+    Ok( (current, ies) )
+}
+
+///
+/// Information elements in the `length` byte message body. When parsing a `truncated` message cut
+/// short by the capture, keeps the elements captured whole, setting the flag.
+///
+fn message_body(input: &[u8], length: usize, truncated: bool) -> IResult<&[u8], (std::vec::Vec<InformationElement>, bool)> {
+    let (rem, body, cut) = captured_bytes(input, length);
+    if !(cut && truncated) {
+        return flat_map!(input, take!(length), information_elements).map(|(rem, ies)| (rem, (ies, false)))
+    }
+    let (_, (ies, _)) = captured_list(body, information_element)?;
+    Ok( (rem, (ies, true)) )
+}
+
+///
+/// GTPv2-C signaling message
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GtpV2 {
+    message_type: GtpV2MessageType,
+    piggybacked: bool,
+    teid: Option<u32>,
+    sequence: u32,
+    information_elements: std::vec::Vec<InformationElement>,
+    truncated: bool
+}
+
+impl GtpV2 {
+    pub fn message_type(&self) -> &GtpV2MessageType {
+        &self.message_type
+    }
+    ///
+    /// Whether another message follows this one in the same datagram
+    ///
+    pub fn piggybacked(&self) -> bool {
+        self.piggybacked
+    }
+    pub fn teid(&self) -> Option<u32> {
+        self.teid
+    }
+    pub fn sequence(&self) -> u32 {
+        self.sequence
+    }
+    pub fn information_elements(&self) -> &std::vec::Vec<InformationElement> {
+        &self.information_elements
+    }
+    ///
+    /// Whether the message was cut short by the capture, when parsed with `parse_truncated`, leaving
+    /// out the information elements that weren't captured whole
+    ///
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    ///
+    /// First information element of the given type
+    ///
+    pub fn information_element(&self, ie_type: InformationElementType) -> Option<&InformationElement> {
+        self.information_elements.iter().find(|ie| ie.ie_type == ie_type)
+    }
+
+    pub fn imsi(&self) -> Option<String> {
+        self.information_element(InformationElementType::Imsi).map(|ie| decode_tbcd(&ie.value))
+    }
+
+    pub fn msisdn(&self) -> Option<String> {
+        self.information_element(InformationElementType::Msisdn).map(|ie| decode_tbcd(&ie.value))
+    }
+
+    pub fn apn(&self) -> Option<String> {
+        self.information_element(InformationElementType::Apn).and_then(|ie| decode_apn(&ie.value))
+    }
+
+    pub fn cause(&self) -> Option<u8> {
+        self.information_element(InformationElementType::Cause).and_then(|ie| ie.value.first().cloned())
+    }
+
+    pub fn fteids(&self) -> std::vec::Vec<FTeid> {
+        self.information_elements.iter()
+            .filter(|ie| ie.ie_type == InformationElementType::FTeid)
+            .filter_map(decode_fteid)
+            .collect()
+    }
+
+    ///
+    /// Whether a datagram was sent to or from the GTPv2-C port
+    ///
+    pub fn is_control_port(udp: &Udp) -> bool {
+        udp.src_port() == GTP_C_PORT || udp.dst_port() == GTP_C_PORT
+    }
+
+    fn parse_gtpv2(input: &[u8], flags: u8, truncated: bool) -> IResult<&[u8], GtpV2> {
+        let has_teid = flags & TEID_FLAG != 0;
+        let header_length = SEQUENCE_LENGTH + if has_teid { std::mem::size_of::<u32>() } else { 0 };
+
+        do_parse!(input,
+
+            message_type: be_u8 >>
+            length: add_return_error!(ErrorKind::Custom(errors::ParseCode::GtpLength as u32), map_opt!(be_u16, |l: u16| {
+                (l as usize).checked_sub(header_length)
+            })) >>
+            teid: cond!(has_teid, be_u32) >>
+            sequence: take!(3) >>
+            spare: be_u8 >>
+            ies: apply!(message_body, length, truncated) >>
+
+            (
+                GtpV2 {
+                    message_type: GtpV2MessageType::new(message_type),
+                    piggybacked: flags & PIGGYBACK_FLAG != 0,
+                    teid: teid,
+                    sequence: (sequence[0] as u32) << 16 | (sequence[1] as u32) << 8 | sequence[2] as u32,
+                    information_elements: ies.0,
+                    truncated: ies.1
+                }
+            )
+        )
+    }
+
+    ///
+    /// Parse a single message. A piggybacked message, if any, is left in the remainder.
+    ///
+    pub fn parse(input: &[u8]) -> IResult<&[u8], GtpV2> {
+        GtpV2::parse_message(input, false)
+    }
+
+    ///
+    /// Parse a single message that may have been cut short by the capture's snap length, keeping
+    /// the information elements captured whole
+    ///
+    pub fn parse_truncated(input: &[u8]) -> IResult<&[u8], GtpV2> {
+        GtpV2::parse_message(input, true)
+    }
+
+    fn parse_message(input: &[u8], truncated: bool) -> IResult<&[u8], GtpV2> {
+        layer_span!("gtpv2", length = input.len());
+
+        be_u8(input).and_then(|r| {
+            let (rem, flags) = r;
+            if flags >> 5 != VERSION {
+                Err(Err::Error(error_position!(input, ErrorKind::Custom(errors::ParseCode::GtpVersion as u32))))
+            } else {
+                GtpV2::parse_gtpv2(rem, flags, truncated)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    const RAW_DATA: &'static [u8] = &[
+        //udp
+        0x08u8, 0x4Bu8, //dst port, 2123
+        0x84u8, 0x12u8, //src port, 33810
+        0x00u8, 0x4Du8, //length, 8 bytes for header, 69 bytes for gtp
+        0x00u8, 0x00u8, //checksum
+        //gtpv2-c
+        0x48u8, //version 2, teid present
+        0x20u8, //create session request
+        0x00u8, 0x41u8, //length, 65
+        0x00u8, 0x00u8, 0x00u8, 0x00u8, //teid, 0
+        0x00u8, 0x12u8, 0x34u8, //sequence, 4660
+        0x00u8, //spare
+        //imsi
+        0x01u8, //type
+        0x00u8, 0x08u8, //length
+        0x00u8, //instance
+        0x21u8, 0x43u8, 0x65u8, 0x87u8, 0x09u8, 0x21u8, 0x43u8, 0xF5u8, //123456789012345
+        //msisdn
+        0x4Cu8, //type
+        0x00u8, 0x06u8, //length
+        0x00u8, //instance
+        0x94u8, 0x71u8, 0x52u8, 0x76u8, 0x04u8, 0xF1u8, //49172567401
+        //apn
+        0x47u8, //type
+        0x00u8, 0x0Du8, //length
+        0x00u8, //instance
+        0x08u8, b'i', b'n', b't', b'e', b'r', b'n', b'e', b't', //internet
+        0x03u8, b'c', b'o', b'm', //com
+        //f-teid
+        0x57u8, //type
+        0x00u8, 0x09u8, //length
+        0x01u8, //instance
+        0x86u8, //ipv4 present, interface type 6 (S11 MME)
+        0x0Au8, 0x0Bu8, 0x0Cu8, 0x0Du8, //teid
+        0x0Au8, 0x01u8, 0x02u8, 0x03u8, //ip 10.1.2.3
+        //recovery
+        0x03u8, //type
+        0x00u8, 0x01u8, //length
+        0x00u8, //instance
+        0x07u8
+    ];
+
+    #[test]
+    fn parse_create_session_request() {
+        let _ = env_logger::try_init();
+
+        let (_, udp) = Udp::parse(RAW_DATA).expect("Could not parse udp");
+
+        assert!(GtpV2::is_control_port(&udp));
+
+        let (rem, gtp) = GtpV2::parse(udp.payload()).expect("Could not parse gtp");
+
+        assert!(rem.is_empty());
+        assert_eq!(gtp.message_type(), &GtpV2MessageType::CreateSessionRequest);
+        assert!(!gtp.piggybacked());
+        assert_eq!(gtp.teid(), Some(0));
+        assert_eq!(gtp.sequence(), 0x1234);
+        assert_eq!(gtp.information_elements().len(), 5);
+        assert_eq!(gtp.imsi(), Some("123456789012345".to_string()));
+        assert_eq!(gtp.msisdn(), Some("49172567401".to_string()));
+        assert_eq!(gtp.apn(), Some("internet.com".to_string()));
+        assert_eq!(gtp.cause(), None);
+        assert_eq!(gtp.fteids(), vec![
+            FTeid {
+                instance: 1,
+                interface_type: 6,
+                teid: 0x0A0B0C0D,
+                ipv4: Some(std::net::Ipv4Addr::new(10, 1, 2, 3)),
+                ipv6: None
+            }
+        ]);
+    }
+
+    #[test]
+    fn parse_echo_request() {
+        let _ = env_logger::try_init();
+
+        let data = [
+            0x40u8, //version 2, no teid
+            0x01u8, //echo request
+            0x00u8, 0x09u8, //length, 9
+            0x00u8, 0x00u8, 0x01u8, //sequence, 1
+            0x00u8, //spare
+            0x03u8, 0x00u8, 0x01u8, 0x00u8, 0x02u8 //recovery
+        ];
+
+        let (rem, gtp) = GtpV2::parse(&data).expect("Could not parse gtp");
+
+        assert!(rem.is_empty());
+        assert_eq!(gtp.message_type(), &GtpV2MessageType::EchoRequest);
+        assert_eq!(gtp.teid(), None);
+        assert_eq!(gtp.sequence(), 1);
+        assert_eq!(gtp.information_element(InformationElementType::Recovery).map(|ie| ie.value().clone()), Some(vec![0x02u8]));
+    }
+
+    #[test]
+    fn parse_invalid_gtp() {
+        let _ = env_logger::try_init();
+
+        let data = [0x20u8, 0x01u8, 0x00u8, 0x04u8, 0x00u8, 0x00u8, 0x01u8, 0x00u8];
+
+        let err = GtpV2::parse(&data).err().map(|e| errors::Error::from_nom(e, errors::Layer::Gtp, &data)).expect("Parsed gtpv1");
+        assert_eq!(format!("{}", err), "GTP header at offset 0: expected version 2");
+
+        let data = [
+            0x40u8, 0x01u8, 0x00u8, 0x09u8, 0x00u8, 0x00u8, 0x01u8, 0x00u8,
+            0x03u8, 0x00u8, 0x04u8, 0x00u8, 0x02u8 //recovery claiming 4 bytes
+        ];
+
+        let err = GtpV2::parse(&data).err().map(|e| errors::Error::from_nom(e, errors::Layer::Gtp, &data)).expect("Parsed overlong information element");
+        assert_eq!(format!("{}", err), "GTP header at offset 8: expected information elements within the message length");
+    }
+
+    #[test]
+    fn parse_truncated_gtp() {
+        let _ = env_logger::try_init();
+
+        let (_, udp) = Udp::parse(RAW_DATA).expect("Could not parse udp");
+        let data = &udp.payload()[..udp.payload().len() - 10]; //cut in the f-teid
+        assert!(GtpV2::parse(data).is_err());
+
+        let (rem, gtp) = GtpV2::parse_truncated(data).expect("Could not parse gtp");
+        assert!(rem.is_empty());
+        assert!(gtp.is_truncated());
+        assert_eq!(gtp.information_elements().len(), 3);
+        assert_eq!(gtp.imsi(), Some("123456789012345".to_string()));
+        assert_eq!(gtp.apn(), Some("internet.com".to_string()));
+        assert!(gtp.fteids().is_empty());
+    }
+}
@@ -0,0 +1,476 @@
+use super::prelude::*;
+use super::captured_bytes;
+
+use self::nom::*;
+use std;
+
+const HEAD_END: &'static [u8] = b"\r\n\r\n";
+const LINE_END: &'static [u8] = b"\r\n";
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Header {
+    name: String,
+    value: String
+}
+
+impl Header {
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+    pub fn value(&self) -> &String {
+        &self.value
+    }
+}
+
+///
+/// How the length of a message body is determined https://tools.ietf.org/html/rfc7230#section-3.3.3
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum BodyLength {
+    Empty,
+    Length(usize),
+    Chunked,
+    UntilClose
+}
+
+fn find(input: &[u8], pattern: &[u8]) -> Option<usize> {
+    input.windows(pattern.len()).position(|w| w == pattern)
+}
+
+fn header_value<'a>(headers: &'a [Header], name: &str) -> Option<&'a str> {
+    headers.iter().find(|h| h.name.eq_ignore_ascii_case(name)).map(|h| h.value.as_str())
+}
+
+fn content_length(headers: &[Header]) -> Option<usize> {
+    header_value(headers, "content-length").and_then(|v| v.trim().parse().ok())
+}
+
+fn is_chunked(headers: &[Header]) -> bool {
+    header_value(headers, "transfer-encoding").map(|v| v.to_ascii_lowercase().contains("chunked")).unwrap_or(false)
+}
+
+fn error<'a, T>(input: &'a [u8], code: errors::ParseCode) -> IResult<&'a [u8], T> {
+    Err(Err::Error(error_position!(input, ErrorKind::Custom(code as u32))))
+}
+
+fn start_line_and_headers(text: &str) -> (std::vec::Vec<String>, std::vec::Vec<Header>) {
+    let mut lines = text.split("\r\n");
+
+    let start_line = lines.next().unwrap_or_default().splitn(3, ' ').map(|s| s.to_string()).collect::<std::vec::Vec<_>>();
+    let headers = lines.filter_map(|line| {
+        line.find(':').map(|idx| Header {
+            name: line[..idx].trim().to_string(),
+            value: line[idx + 1..].trim().to_string()
+        })
+    }).collect();
+
+    (start_line, headers)
+}
+
+///
+/// Start line and headers, up to and including the blank line ending them
+///
+fn head(input: &[u8]) -> IResult<&[u8], (std::vec::Vec<String>, std::vec::Vec<Header>)> {
+    let end = match find(input, HEAD_END) {
+        Some(end) => end,
+        None => return Err(Err::Incomplete(Needed::Unknown))
+    };
+    let (start_line, headers) = start_line_and_headers(&String::from_utf8_lossy(&input[..end]));
+    if start_line.len() < 3 && !(start_line.len() == 2 && start_line[0].starts_with("HTTP/")) {
+        return error(input, errors::ParseCode::HttpStartLine)
+    }
+
+    Ok( (&input[end + HEAD_END.len()..], (start_line, headers)) )
+}
+
+///
+/// Start line and headers of a head cut short by the capture, without the line that was cut unless
+/// it is the start line
+///
+fn truncated_head(input: &[u8]) -> (std::vec::Vec<String>, std::vec::Vec<Header>) {
+    let text = String::from_utf8_lossy(input);
+    let complete = match text.rfind("\r\n") {
+        Some(end) => &text[..end],
+        None => &text[..]
+    };
+    start_line_and_headers(complete)
+}
+
+///
+/// Chunked body, extended into `body` chunk by chunk so that a body cut short keeps what was captured
+///
+fn chunks<'a>(input: &'a [u8], body: &mut std::vec::Vec<u8>) -> IResult<&'a [u8], ()> {
+    let mut current = input;
+    loop {
+        let line_end = match find(current, LINE_END) {
+            Some(end) => end,
+            None => return Err(Err::Incomplete(Needed::Unknown))
+        };
+        let size = std::str::from_utf8(&current[..line_end]).ok()
+            .and_then(|line| line.split(';').next())
+            .and_then(|size| usize::from_str_radix(size.trim(), 16).ok());
+        let size = match size {
+            Some(size) => size,
+            None => return error(current, errors::ParseCode::HttpChunkSize)
+        };
+        let rem = &current[line_end + LINE_END.len()..];
+
+        if size == 0 {
+            //trailers, ending with a blank line
+            return match (find(rem, LINE_END), find(rem, HEAD_END)) {
+                (Some(0), _) => Ok( (&rem[LINE_END.len()..], ()) ),
+                (_, Some(end)) => Ok( (&rem[end + HEAD_END.len()..], ()) ),
+                _ => Err(Err::Incomplete(Needed::Unknown))
+            }
+        }
+
+        let (rem, chunk, cut) = captured_bytes(rem, size);
+        body.extend_from_slice(chunk);
+        if cut {
+            return Err(Err::Incomplete(Needed::Size(size - chunk.len())))
+        }
+        let (rem, _) = tag!(rem, LINE_END)?;
+        current = rem;
+    }
+}
+
+fn chunked_body(input: &[u8]) -> IResult<&[u8], std::vec::Vec<u8>> {
+    let mut body = vec![];
+    let (rem, _) = chunks(input, &mut body)?;
+    Ok( (rem, body) )
+}
+
+fn body(input: &[u8], length: BodyLength) -> IResult<&[u8], std::vec::Vec<u8>> {
+    match length {
+        BodyLength::Empty => Ok( (input, vec![]) ),
+        BodyLength::Length(length) => take!(input, length).map(|(rem, b)| (rem, b.into())),
+        BodyLength::Chunked => chunked_body(input),
+        BodyLength::UntilClose => Ok( (&input[input.len()..], input.into()) )
+    }
+}
+
+///
+/// As much of a body as was captured
+///
+fn truncated_body(input: &[u8], length: BodyLength) -> std::vec::Vec<u8> {
+    match length {
+        BodyLength::Length(length) => captured_bytes(input, length).1.into(),
+        BodyLength::Chunked => {
+            let mut body = vec![];
+            let _ = chunks(input, &mut body);
+            body
+        }
+        _ => body(input, length).map(|(_, b)| b).unwrap_or_default()
+    }
+}
+
+fn request_body_length(headers: &[Header]) -> BodyLength {
+    if is_chunked(headers) {
+        BodyLength::Chunked
+    } else {
+        content_length(headers).map(BodyLength::Length).unwrap_or(BodyLength::Empty)
+    }
+}
+
+fn response_body_length(headers: &[Header], status: u16, method: &str) -> BodyLength {
+    if method.eq_ignore_ascii_case("HEAD") || (status >= 100 && status < 200) || status == 204 || status == 304 {
+        BodyLength::Empty
+    } else if is_chunked(headers) {
+        BodyLength::Chunked
+    } else {
+        content_length(headers).map(BodyLength::Length).unwrap_or(BodyLength::UntilClose)
+    }
+}
+
+///
+/// HTTP/1.x request https://tools.ietf.org/html/rfc7230#section-3
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Request {
+    method: String,
+    uri: String,
+    version: String,
+    headers: std::vec::Vec<Header>,
+    body: std::vec::Vec<u8>,
+    truncated: bool
+}
+
+impl Request {
+    pub fn method(&self) -> &String {
+        &self.method
+    }
+    pub fn uri(&self) -> &String {
+        &self.uri
+    }
+    pub fn version(&self) -> &String {
+        &self.version
+    }
+    pub fn headers(&self) -> &std::vec::Vec<Header> {
+        &self.headers
+    }
+    ///
+    /// Body with any chunked transfer coding removed
+    ///
+    pub fn body(&self) -> &std::vec::Vec<u8> {
+        &self.body
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        header_value(&self.headers, name)
+    }
+    ///
+    /// Whether the message was cut short by the capture, when parsed with `parse_truncated`. The
+    /// start line, the last header or the body may then be incomplete.
+    ///
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Request> {
+        layer_span!("http_request", length = input.len());
+
+        let (rem, (start_line, headers)) = head(input)?;
+        if start_line.len() != 3 || !start_line[2].starts_with("HTTP/") {
+            return error(input, errors::ParseCode::HttpStartLine)
+        }
+        let length = request_body_length(&headers);
+        let (rem, body) = body(rem, length)?;
+
+        Ok( (rem, Request::new(start_line, headers, body, false)) )
+    }
+
+    ///
+    /// Parse a request that may have been cut short by the capture's snap length, keeping as much of
+    /// it as was captured from the method and uri on
+    ///
+    pub fn parse_truncated(input: &[u8]) -> IResult<&[u8], Request> {
+        match Request::parse(input) {
+            Err(Err::Incomplete(_)) => (),
+            r => return r
+        }
+        let (start_line, headers, body) = match head(input) {
+            Ok( (rem, (start_line, headers)) ) => {
+                let body = truncated_body(rem, request_body_length(&headers));
+                (start_line, headers, body)
+            }
+            _ => {
+                let (start_line, headers) = truncated_head(input);
+                (start_line, headers, vec![])
+            }
+        };
+        if start_line.len() < 2 {
+            return error(input, errors::ParseCode::HttpStartLine)
+        }
+
+        Ok( (&input[input.len()..], Request::new(start_line, headers, body, true)) )
+    }
+
+    fn new(start_line: std::vec::Vec<String>, headers: std::vec::Vec<Header>, body: std::vec::Vec<u8>, truncated: bool) -> Request {
+        let mut start_line = start_line.into_iter();
+        Request {
+            method: start_line.next().unwrap_or_default(),
+            uri: start_line.next().unwrap_or_default(),
+            version: start_line.next().unwrap_or_default(),
+            headers: headers,
+            body: body,
+            truncated: truncated
+        }
+    }
+}
+
+///
+/// HTTP/1.x response https://tools.ietf.org/html/rfc7230#section-3
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Response {
+    version: String,
+    status: u16,
+    reason: String,
+    headers: std::vec::Vec<Header>,
+    body: std::vec::Vec<u8>,
+    truncated: bool
+}
+
+impl Response {
+    pub fn version(&self) -> &String {
+        &self.version
+    }
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+    pub fn reason(&self) -> &String {
+        &self.reason
+    }
+    pub fn headers(&self) -> &std::vec::Vec<Header> {
+        &self.headers
+    }
+    ///
+    /// Body with any chunked transfer coding removed
+    ///
+    pub fn body(&self) -> &std::vec::Vec<u8> {
+        &self.body
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        header_value(&self.headers, name)
+    }
+    ///
+    /// Whether the message was cut short by the capture, when parsed with `parse_truncated`. The
+    /// start line, the last header or the body may then be incomplete.
+    ///
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    ///
+    /// Interim (1xx) response, sent ahead of the final response to the same request
+    ///
+    pub fn is_informational(&self) -> bool {
+        self.status >= 100 && self.status < 200
+    }
+
+    ///
+    /// Parse a response to a GET or other request whose response may carry a body
+    ///
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Response> {
+        Response::parse_for(input, "GET")
+    }
+
+    ///
+    /// Parse a response to a request with `method`, as responses to HEAD never carry a body
+    ///
+    pub fn parse_for<'a>(input: &'a [u8], method: &str) -> IResult<&'a [u8], Response> {
+        layer_span!("http_response", length = input.len());
+
+        let (rem, (start_line, headers)) = head(input)?;
+        let status = match start_line.get(1).and_then(|s| s.parse::<u16>().ok()) {
+            Some(status) if start_line[0].starts_with("HTTP/") => status,
+            _ => return error(input, errors::ParseCode::HttpStartLine)
+        };
+        let length = response_body_length(&headers, status, method);
+        let (rem, body) = body(rem, length)?;
+
+        Ok( (rem, Response::new(start_line, status, headers, body, false)) )
+    }
+
+    ///
+    /// Parse a response to a GET that may have been cut short by the capture's snap length
+    ///
+    pub fn parse_truncated(input: &[u8]) -> IResult<&[u8], Response> {
+        Response::parse_truncated_for(input, "GET")
+    }
+
+    ///
+    /// Parse a response to a request with `method` that may have been cut short by the capture's
+    /// snap length, keeping as much of it as was captured from the status on
+    ///
+    pub fn parse_truncated_for<'a>(input: &'a [u8], method: &str) -> IResult<&'a [u8], Response> {
+        match Response::parse_for(input, method) {
+            Err(Err::Incomplete(_)) => (),
+            r => return r
+        }
+        let (start_line, headers, rem) = match head(input) {
+            Ok( (rem, (start_line, headers)) ) => (start_line, headers, Some(rem)),
+            _ => {
+                let (start_line, headers) = truncated_head(input);
+                (start_line, headers, None)
+            }
+        };
+        let status = match start_line.get(1).and_then(|s| s.parse::<u16>().ok()) {
+            Some(status) if start_line[0].starts_with("HTTP/") => status,
+            _ => return error(input, errors::ParseCode::HttpStartLine)
+        };
+        let body = rem.map(|rem| truncated_body(rem, response_body_length(&headers, status, method))).unwrap_or_default();
+
+        Ok( (&input[input.len()..], Response::new(start_line, status, headers, body, true)) )
+    }
+
+    fn new(start_line: std::vec::Vec<String>, status: u16, headers: std::vec::Vec<Header>, body: std::vec::Vec<u8>, truncated: bool) -> Response {
+        let mut start_line = start_line.into_iter();
+        Response {
+            version: start_line.next().unwrap_or_default(),
+            status: status,
+            reason: start_line.nth(1).unwrap_or_default(),
+            headers: headers,
+            body: body,
+            truncated: truncated
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    #[test]
+    fn parse_request() {
+        let _ = env_logger::try_init();
+
+        let data = b"POST /submit HTTP/1.1\r\nHost: example.com\r\nContent-Length: 5\r\n\r\nhelloGET / HTTP/1.1\r\n\r\n";
+        let (rem, request) = Request::parse(data).expect("Could not parse");
+
+        assert_eq!(rem, b"GET / HTTP/1.1\r\n\r\n");
+        assert_eq!(request.method(), "POST");
+        assert_eq!(request.uri(), "/submit");
+        assert_eq!(request.version(), "HTTP/1.1");
+        assert_eq!(request.header("host"), Some("example.com"));
+        assert_eq!(request.body(), &b"hello".to_vec());
+    }
+
+    #[test]
+    fn parse_chunked_response() {
+        let _ = env_logger::try_init();
+
+        let data = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n6;ext=1\r\n world\r\n0\r\n\r\n";
+        let (rem, response) = Response::parse(data).expect("Could not parse");
+
+        assert!(rem.is_empty());
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.reason(), "OK");
+        assert_eq!(response.body(), &b"hello world".to_vec());
+
+        let (rem, response) = Response::parse_for(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n", "HEAD").expect("Could not parse");
+        assert!(rem.is_empty());
+        assert!(response.body().is_empty());
+    }
+
+    #[test]
+    fn parse_invalid_start_line() {
+        let _ = env_logger::try_init();
+
+        let data = &b"\x16\x03\x01 \r\n\r\n"[..];
+        let err = Response::parse(data).err().map(|e| errors::Error::from_nom(e, errors::Layer::Http, data)).expect("Parsed tls as http");
+
+        assert_eq!(format!("{}", err), "HTTP header at offset 0: expected request or status line");
+        assert!(Request::parse(b"GET / HTTP/1.1\r\nHost: example.com\r\n").is_err());
+    }
+
+    #[test]
+    fn parse_truncated() {
+        let _ = env_logger::try_init();
+
+        let (rem, request) = Request::parse_truncated(b"GET /index.html HTTP/1.1\r\nHost: example.com\r\nUser-Ag").expect("Could not parse");
+        assert!(rem.is_empty());
+        assert!(request.is_truncated());
+        assert_eq!(request.method(), "GET");
+        assert_eq!(request.uri(), "/index.html");
+        assert_eq!(request.header("host"), Some("example.com"));
+        assert_eq!(request.headers().len(), 1);
+
+        let (_, request) = Request::parse_truncated(b"GET /very/long/pa").expect("Could not parse");
+        assert_eq!(request.uri(), "/very/long/pa");
+        assert_eq!(request.version(), "");
+
+        let data = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n6\r\n wo";
+        let (_, response) = Response::parse_truncated(data).expect("Could not parse");
+        assert!(response.is_truncated());
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.body(), &b"hello wo".to_vec());
+
+        let (_, response) = Response::parse_truncated(b"HTTP/1.1 204 No Content\r\n\r\n").expect("Could not parse");
+        assert!(!response.is_truncated());
+        assert!(Request::parse_truncated(b"GET").is_err());
+    }
+}
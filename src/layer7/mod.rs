@@ -0,0 +1,53 @@
+pub mod prelude {
+    pub use super::super::prelude::*;
+    pub use super::super::layer4;
+}
+
+pub mod detect;
+#[cfg(feature = "l7-dhcp")]
+pub mod dhcp;
+#[cfg(feature = "l7-dns")]
+pub mod dns;
+#[cfg(feature = "l7-gtp")]
+pub mod gtp;
+#[cfg(feature = "l7-http")]
+pub mod http;
+#[cfg(feature = "l7-tls")]
+pub mod tls;
+pub mod wol;
+
+use self::prelude::*;
+use self::nom::{Err, IResult};
+
+use std;
+
+///
+/// Items parsed by `f` until `input` is used up, stopping at an item cut short by the capture's snap
+/// length. The flag is set when an item was cut short.
+///
+pub fn captured_list<'a, T, F>(mut input: &'a [u8], f: F) -> IResult<&'a [u8], (std::vec::Vec<T>, bool)> where F: Fn(&'a [u8]) -> IResult<&'a [u8], T> {
+    let mut items = vec![];
+    while !input.is_empty() {
+        match f(input) {
+            Ok( (rem, item) ) => {
+                items.push(item);
+                input = rem;
+            }
+            Err(Err::Incomplete(_)) => return Ok( (&input[input.len()..], (items, true)) ),
+            Err(e) => return Err(e)
+        }
+    }
+    Ok( (input, (items, false)) )
+}
+
+///
+/// Remainder and the first `length` bytes of `input`, or all of `input` if the capture cut it short
+/// of `length`. The flag is set when it was cut short.
+///
+pub fn captured_bytes(input: &[u8], length: usize) -> (&[u8], &[u8], bool) {
+    if input.len() >= length {
+        (&input[length..], &input[..length], false)
+    } else {
+        (&input[input.len()..], input, true)
+    }
+}
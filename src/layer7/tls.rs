@@ -0,0 +1,667 @@
+use super::prelude::*;
+use super::{captured_bytes, captured_list};
+
+use self::nom::*;
+use std;
+
+const EXTENSION_SERVER_NAME: u16 = 0;
+const EXTENSION_ALPN: u16 = 16;
+const EXTENSION_SESSION_TICKET: u16 = 35;
+const EXTENSION_PRE_SHARED_KEY: u16 = 41;
+const EXTENSION_SUPPORTED_VERSIONS: u16 = 43;
+
+const SERVER_NAME_HOST: u8 = 0;
+
+///
+/// Record content types https://tools.ietf.org/html/rfc8446#appendix-B.1
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ContentType {
+    ChangeCipherSpec,
+    Alert,
+    Handshake,
+    ApplicationData
+}
+
+impl ContentType {
+    pub fn new(value: u8) -> Option<ContentType> {
+        match value {
+            20 => Some(ContentType::ChangeCipherSpec),
+            21 => Some(ContentType::Alert),
+            22 => Some(ContentType::Handshake),
+            23 => Some(ContentType::ApplicationData),
+            _ => None
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TlsVersion {
+    Ssl30,
+    Tls10,
+    Tls11,
+    Tls12,
+    Tls13,
+    Other(u16)
+}
+
+impl TlsVersion {
+    pub fn new(value: u16) -> TlsVersion {
+        match value {
+            0x0300 => TlsVersion::Ssl30,
+            0x0301 => TlsVersion::Tls10,
+            0x0302 => TlsVersion::Tls11,
+            0x0303 => TlsVersion::Tls12,
+            0x0304 => TlsVersion::Tls13,
+            x => TlsVersion::Other(x)
+        }
+    }
+}
+
+impl std::fmt::Display for TlsVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            TlsVersion::Ssl30 => write!(f, "SSL 3.0"),
+            TlsVersion::Tls10 => write!(f, "TLS 1.0"),
+            TlsVersion::Tls11 => write!(f, "TLS 1.1"),
+            TlsVersion::Tls12 => write!(f, "TLS 1.2"),
+            TlsVersion::Tls13 => write!(f, "TLS 1.3"),
+            TlsVersion::Other(x) => write!(f, "0x{:04x}", x)
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Extension {
+    extension_type: u16,
+    data: std::vec::Vec<u8>
+}
+
+impl Extension {
+    pub fn extension_type(&self) -> u16 {
+        self.extension_type
+    }
+    pub fn data(&self) -> &std::vec::Vec<u8> {
+        &self.data
+    }
+}
+
+fn find_extension(extensions: &[Extension], extension_type: u16) -> Option<&Extension> {
+    extensions.iter().find(|e| e.extension_type == extension_type)
+}
+
+///
+/// Parse `input` entirely as a sequence of `f`
+///
+fn list<'a, T, F>(mut input: &'a [u8], f: F) -> IResult<&'a [u8], std::vec::Vec<T>> where F: Fn(&'a [u8]) -> IResult<&'a [u8], T> {
+    let mut items = vec![];
+    while !input.is_empty() {
+        let (rem, item) = f(input)?;
+        items.push(item);
+        input = rem;
+    }
+    Ok( (input, items) )
+}
+
+named!(extension<&[u8], Extension>, do_parse!(
+    extension_type: be_u16 >>
+    data: length_bytes!(be_u16) >>
+    (
+        Extension {
+            extension_type: extension_type,
+            data: data.into()
+        }
+    )
+));
+
+///
+/// Extensions trailing a hello, which may be omitted entirely
+///
+fn extensions(input: &[u8]) -> IResult<&[u8], std::vec::Vec<Extension>> {
+    if input.is_empty() {
+        Ok( (input, vec![]) )
+    } else {
+        let (rem, data) = length_bytes!(input, be_u16)?;
+        let (_, extensions) = list(data, extension)?;
+        Ok( (rem, extensions) )
+    }
+}
+
+///
+/// Complete extensions of a hello cut short by the capture
+///
+fn captured_extensions(input: &[u8]) -> std::vec::Vec<Extension> {
+    be_u16(input).ok()
+        .map(|(rem, length)| captured_bytes(rem, length as usize).1)
+        .and_then(|data| captured_list(data, extension).ok())
+        .map(|(_, (extensions, _))| extensions)
+        .unwrap_or_default()
+}
+
+named!(server_name<&[u8], (u8, &[u8])>, do_parse!(
+    name_type: be_u8 >>
+    name: length_bytes!(be_u16) >>
+    ( (name_type, name) )
+));
+
+named!(protocol_name<&[u8], &[u8]>, length_bytes!(be_u8));
+
+fn alpn_protocols(extensions: &[Extension]) -> std::vec::Vec<String> {
+    find_extension(extensions, EXTENSION_ALPN)
+        .and_then(|e| length_bytes!(&e.data[..], be_u16).ok())
+        .and_then(|(_, data)| list(data, protocol_name).ok())
+        .map(|(_, names)| names.into_iter().map(|n| String::from_utf8_lossy(n).into_owned()).collect())
+        .unwrap_or_default()
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ClientHello {
+    version: TlsVersion,
+    session_id: std::vec::Vec<u8>,
+    cipher_suites: std::vec::Vec<u16>,
+    extensions: std::vec::Vec<Extension>,
+    truncated: bool
+}
+
+impl ClientHello {
+    ///
+    /// Legacy version field, clients offering TLS 1.3 list it in `supported_versions` instead
+    ///
+    pub fn version(&self) -> TlsVersion {
+        self.version
+    }
+    pub fn session_id(&self) -> &std::vec::Vec<u8> {
+        &self.session_id
+    }
+    pub fn cipher_suites(&self) -> &std::vec::Vec<u16> {
+        &self.cipher_suites
+    }
+    pub fn extensions(&self) -> &std::vec::Vec<Extension> {
+        &self.extensions
+    }
+    ///
+    /// Whether the hello was cut short by the capture, in which case the last cipher suites or
+    /// extensions may be missing
+    ///
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    ///
+    /// Host name from the server name indication extension
+    ///
+    pub fn server_name(&self) -> Option<String> {
+        find_extension(&self.extensions, EXTENSION_SERVER_NAME)
+            .and_then(|e| length_bytes!(&e.data[..], be_u16).ok())
+            .and_then(|(_, data)| list(data, server_name).ok())
+            .and_then(|(_, names)| names.into_iter().find(|n| n.0 == SERVER_NAME_HOST))
+            .map(|(_, name)| String::from_utf8_lossy(name).into_owned())
+    }
+
+    pub fn alpn_protocols(&self) -> std::vec::Vec<String> {
+        alpn_protocols(&self.extensions)
+    }
+
+    pub fn supported_versions(&self) -> std::vec::Vec<TlsVersion> {
+        find_extension(&self.extensions, EXTENSION_SUPPORTED_VERSIONS)
+            .and_then(|e| length_bytes!(&e.data[..], be_u8).ok())
+            .and_then(|(_, data)| list(data, be_u16).ok())
+            .map(|(_, versions)| versions.into_iter().map(TlsVersion::new).collect())
+            .unwrap_or_default()
+    }
+
+    ///
+    /// Whether the client presented a session id, session ticket or pre shared key that could resume
+    /// a session. TLS 1.3 clients may send a session id only for middlebox compatibility.
+    ///
+    pub fn offers_resumption(&self) -> bool {
+        !self.session_id.is_empty() ||
+            find_extension(&self.extensions, EXTENSION_SESSION_TICKET).map(|e| !e.data.is_empty()).unwrap_or(false) ||
+            find_extension(&self.extensions, EXTENSION_PRE_SHARED_KEY).is_some()
+    }
+
+    fn parse(input: &[u8]) -> IResult<&[u8], ClientHello> {
+        do_parse!(input,
+
+            version: be_u16 >>
+            _random: take!(32) >>
+            session_id: length_bytes!(be_u8) >>
+            cipher_suites: flat_map!(length_bytes!(be_u16), apply!(list, be_u16)) >>
+            _compression_methods: length_bytes!(be_u8) >>
+            extensions: extensions >>
+
+            (
+                ClientHello {
+                    version: TlsVersion::new(version),
+                    session_id: session_id.into(),
+                    cipher_suites: cipher_suites,
+                    extensions: extensions,
+                    truncated: false
+                }
+            )
+        )
+    }
+
+    ///
+    /// Parse a hello cut short by the capture, keeping the cipher suites and extensions captured
+    /// whole after the session id
+    ///
+    fn parse_truncated(input: &[u8]) -> IResult<&[u8], ClientHello> {
+        let (rem, (version, session_id)) = do_parse!(input,
+
+            version: be_u16 >>
+            _random: take!(32) >>
+            session_id: length_bytes!(be_u8) >>
+
+            ( (version, session_id) )
+        )?;
+        let (suites, rem) = match be_u16(rem) {
+            Ok( (rem, length) ) => {
+                let (rem, suites, _) = captured_bytes(rem, length as usize);
+                (suites, rem)
+            }
+            Err(_) => (&rem[rem.len()..], rem)
+        };
+        let extensions = length_bytes!(rem, be_u8).map(|(rem, _)| captured_extensions(rem)).unwrap_or_default();
+
+        Ok( (&input[input.len()..], ClientHello {
+            version: TlsVersion::new(version),
+            session_id: session_id.into(),
+            cipher_suites: captured_list(suites, be_u16).map(|(_, (suites, _))| suites).unwrap_or_default(),
+            extensions: extensions,
+            truncated: true
+        }) )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ServerHello {
+    version: TlsVersion,
+    session_id: std::vec::Vec<u8>,
+    cipher_suite: u16,
+    extensions: std::vec::Vec<Extension>,
+    truncated: bool
+}
+
+impl ServerHello {
+    pub fn version(&self) -> TlsVersion {
+        self.version
+    }
+    pub fn session_id(&self) -> &std::vec::Vec<u8> {
+        &self.session_id
+    }
+    pub fn cipher_suite(&self) -> u16 {
+        self.cipher_suite
+    }
+    pub fn extensions(&self) -> &std::vec::Vec<Extension> {
+        &self.extensions
+    }
+    ///
+    /// Whether the hello was cut short by the capture, in which case the last cipher suites or
+    /// extensions may be missing
+    ///
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    ///
+    /// Version in use for the session, from `supported_versions` when the server negotiated TLS 1.3
+    ///
+    pub fn selected_version(&self) -> TlsVersion {
+        find_extension(&self.extensions, EXTENSION_SUPPORTED_VERSIONS)
+            .and_then(|e| be_u16(&e.data).ok())
+            .map(|(_, v)| TlsVersion::new(v))
+            .unwrap_or(self.version)
+    }
+
+    pub fn alpn_protocol(&self) -> Option<String> {
+        alpn_protocols(&self.extensions).into_iter().next()
+    }
+
+    ///
+    /// Whether the server accepted a pre shared key, i.e. resumed a TLS 1.3 session
+    ///
+    pub fn accepted_pre_shared_key(&self) -> bool {
+        find_extension(&self.extensions, EXTENSION_PRE_SHARED_KEY).is_some()
+    }
+
+    fn parse(input: &[u8]) -> IResult<&[u8], ServerHello> {
+        do_parse!(input,
+
+            version: be_u16 >>
+            _random: take!(32) >>
+            session_id: length_bytes!(be_u8) >>
+            cipher_suite: be_u16 >>
+            _compression_method: be_u8 >>
+            extensions: extensions >>
+
+            (
+                ServerHello {
+                    version: TlsVersion::new(version),
+                    session_id: session_id.into(),
+                    cipher_suite: cipher_suite,
+                    extensions: extensions,
+                    truncated: false
+                }
+            )
+        )
+    }
+
+    ///
+    /// Parse a hello cut short by the capture, keeping the extensions captured whole after the
+    /// cipher suite
+    ///
+    fn parse_truncated(input: &[u8]) -> IResult<&[u8], ServerHello> {
+        do_parse!(input,
+
+            version: be_u16 >>
+            _random: take!(32) >>
+            session_id: length_bytes!(be_u8) >>
+            cipher_suite: be_u16 >>
+            _compression_method: be_u8 >>
+            extensions: map!(rest, captured_extensions) >>
+
+            (
+                ServerHello {
+                    version: TlsVersion::new(version),
+                    session_id: session_id.into(),
+                    cipher_suite: cipher_suite,
+                    extensions: extensions,
+                    truncated: true
+                }
+            )
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Handshake {
+    ClientHello(ClientHello),
+    ServerHello(ServerHello),
+    Other(u8)
+}
+
+fn to_length(i: &[u8]) -> usize {
+    (i[0] as usize) << 16 | (i[1] as usize) << 8 | i[2] as usize
+}
+
+impl Handshake {
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Handshake> {
+        let (rem, (message_type, body)) = do_parse!(input,
+
+            message_type: be_u8 >>
+            length: map!(take!(3), to_length) >>
+            body: take!(length) >>
+
+            ( (message_type, body) )
+        )?;
+
+        let handshake = match message_type {
+            1 => ClientHello::parse(body).map(|(_, h)| Handshake::ClientHello(h))?,
+            2 => ServerHello::parse(body).map(|(_, h)| Handshake::ServerHello(h))?,
+            x => Handshake::Other(x)
+        };
+        Ok( (rem, handshake) )
+    }
+
+    ///
+    /// Parse a handshake message that may have been cut short by the capture, keeping as much of a
+    /// hello as was captured
+    ///
+    pub fn parse_truncated(input: &[u8]) -> IResult<&[u8], Handshake> {
+        let (rem, (message_type, length)) = do_parse!(input,
+
+            message_type: be_u8 >>
+            length: map!(take!(3), to_length) >>
+
+            ( (message_type, length) )
+        )?;
+        let (rem, body, cut) = captured_bytes(rem, length);
+        if !cut {
+            return Handshake::parse(input)
+        }
+
+        let handshake = match message_type {
+            1 => ClientHello::parse_truncated(body).map(|(_, h)| Handshake::ClientHello(h))?,
+            2 => ServerHello::parse_truncated(body).map(|(_, h)| Handshake::ServerHello(h))?,
+            x => Handshake::Other(x)
+        };
+        Ok( (rem, handshake) )
+    }
+}
+
+///
+/// TLS record https://tools.ietf.org/html/rfc8446#section-5.1
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TlsRecord {
+    content_type: ContentType,
+    version: TlsVersion,
+    fragment: std::vec::Vec<u8>,
+    truncated: bool
+}
+
+impl TlsRecord {
+    pub fn content_type(&self) -> ContentType {
+        self.content_type
+    }
+    pub fn version(&self) -> TlsVersion {
+        self.version
+    }
+    pub fn fragment(&self) -> &std::vec::Vec<u8> {
+        &self.fragment
+    }
+    ///
+    /// Whether the record was cut short by the capture, when parsed with `parse_truncated`, leaving
+    /// only the start of its fragment
+    ///
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    ///
+    /// Handshake messages wholly contained in this record, followed for a truncated record by as
+    /// much of the message it was cut short in as was captured
+    ///
+    pub fn handshakes(&self) -> std::vec::Vec<Handshake> {
+        let mut handshakes = vec![];
+        if self.content_type == ContentType::Handshake {
+            let mut current = &self.fragment[..];
+            while let Ok( (rem, handshake) ) = Handshake::parse(current) {
+                handshakes.push(handshake);
+                current = rem;
+            }
+            if self.truncated {
+                handshakes.extend(Handshake::parse_truncated(current).ok().map(|(_, h)| h));
+            }
+        }
+        handshakes
+    }
+
+    pub fn parse(input: &[u8]) -> IResult<&[u8], TlsRecord> {
+        layer_span!("tls", length = input.len());
+
+        do_parse!(input,
+
+            content_type: add_return_error!(ErrorKind::Custom(errors::ParseCode::TlsContentType as u32), map_opt!(be_u8, ContentType::new)) >>
+            version: be_u16 >>
+            fragment: length_bytes!(be_u16) >>
+
+            (
+                TlsRecord {
+                    content_type: content_type,
+                    version: TlsVersion::new(version),
+                    fragment: fragment.into(),
+                    truncated: false
+                }
+            )
+        )
+    }
+
+    ///
+    /// Parse a record that may have been cut short by the capture's snap length, keeping as much of
+    /// its fragment as was captured
+    ///
+    pub fn parse_truncated(input: &[u8]) -> IResult<&[u8], TlsRecord> {
+        layer_span!("tls", length = input.len());
+
+        let (rem, (content_type, version, length)) = do_parse!(input,
+
+            content_type: add_return_error!(ErrorKind::Custom(errors::ParseCode::TlsContentType as u32), map_opt!(be_u8, ContentType::new)) >>
+            version: be_u16 >>
+            length: be_u16 >>
+
+            ( (content_type, version, length) )
+        )?;
+        let (rem, fragment, truncated) = captured_bytes(rem, length as usize);
+
+        Ok( (rem, TlsRecord {
+            content_type: content_type,
+            version: TlsVersion::new(version),
+            fragment: fragment.into(),
+            truncated: truncated
+        }) )
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    pub const CLIENT_HELLO_DATA: &'static [u8] = &[
+        0x16u8, //handshake
+        0x03u8, 0x01u8, //version, TLS 1.0
+        0x00u8, 0x55u8, //length, 85
+        //handshake
+        0x01u8, //client hello
+        0x00u8, 0x00u8, 0x51u8, //length, 81
+        0x03u8, 0x03u8, //version, TLS 1.2
+        0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8,
+        0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8,
+        0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8,
+        0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, //random
+        0x00u8, //session id length
+        0x00u8, 0x04u8, //cipher suites length
+        0x13u8, 0x01u8, //TLS_AES_128_GCM_SHA256
+        0xC0u8, 0x2Fu8, //TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256
+        0x01u8, 0x00u8, //compression methods, null
+        0x00u8, 0x24u8, //extensions length, 36
+        0x00u8, 0x00u8, 0x00u8, 0x10u8, //server name, 16
+        0x00u8, 0x0Eu8, //list length
+        0x00u8, 0x00u8, 0x0Bu8, b'e', b'x', b'a', b'm', b'p', b'l', b'e', b'.', b'c', b'o', b'm', //host example.com
+        0x00u8, 0x10u8, 0x00u8, 0x05u8, //alpn, 5
+        0x00u8, 0x03u8, //list length
+        0x02u8, b'h', b'2',
+        0x00u8, 0x2Bu8, 0x00u8, 0x03u8, //supported versions, 3
+        0x02u8, 0x03u8, 0x04u8 //TLS 1.3
+    ];
+
+    pub const SERVER_HELLO_DATA: &'static [u8] = &[
+        0x16u8, //handshake
+        0x03u8, 0x03u8, //version, TLS 1.2
+        0x00u8, 0x38u8, //length, 56
+        //handshake
+        0x02u8, //server hello
+        0x00u8, 0x00u8, 0x34u8, //length, 52
+        0x03u8, 0x03u8, //version, TLS 1.2
+        0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8,
+        0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8,
+        0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8,
+        0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, //random
+        0x00u8, //session id length
+        0x13u8, 0x01u8, //TLS_AES_128_GCM_SHA256
+        0x00u8, //compression method, null
+        0x00u8, 0x0Cu8, //extensions length, 12
+        0x00u8, 0x2Bu8, 0x00u8, 0x02u8, //supported versions, 2
+        0x03u8, 0x04u8, //TLS 1.3
+        0x00u8, 0x29u8, 0x00u8, 0x02u8, //pre shared key, 2
+        0x00u8, 0x00u8 //selected identity
+    ];
+
+    #[test]
+    fn parse_client_hello() {
+        let _ = env_logger::try_init();
+
+        let (rem, record) = TlsRecord::parse(CLIENT_HELLO_DATA).expect("Could not parse");
+
+        assert!(rem.is_empty());
+        assert_eq!(record.content_type(), ContentType::Handshake);
+        assert_eq!(record.version(), TlsVersion::Tls10);
+
+        let handshakes = record.handshakes();
+        assert_eq!(handshakes.len(), 1);
+        match handshakes[0] {
+            Handshake::ClientHello(ref hello) => {
+                assert_eq!(hello.version(), TlsVersion::Tls12);
+                assert_eq!(hello.cipher_suites(), &vec![0x1301, 0xC02F]);
+                assert_eq!(hello.server_name(), Some("example.com".to_string()));
+                assert_eq!(hello.alpn_protocols(), vec!["h2".to_string()]);
+                assert_eq!(hello.supported_versions(), vec![TlsVersion::Tls13]);
+                assert!(!hello.offers_resumption());
+            }
+            ref other => panic!("Expected client hello, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn parse_server_hello() {
+        let _ = env_logger::try_init();
+
+        let (_, record) = TlsRecord::parse(SERVER_HELLO_DATA).expect("Could not parse");
+
+        match record.handshakes()[0] {
+            Handshake::ServerHello(ref hello) => {
+                assert_eq!(hello.version(), TlsVersion::Tls12);
+                assert_eq!(hello.selected_version(), TlsVersion::Tls13);
+                assert_eq!(format!("{}", hello.selected_version()), "TLS 1.3");
+                assert_eq!(hello.cipher_suite(), 0x1301);
+                assert_eq!(hello.alpn_protocol(), None);
+                assert!(hello.accepted_pre_shared_key());
+            }
+            ref other => panic!("Expected server hello, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn parse_invalid_content_type() {
+        let _ = env_logger::try_init();
+
+        let data = &b"GET / HTTP/1.1\r\n"[..];
+        let err = TlsRecord::parse(data).err().map(|e| errors::Error::from_nom(e, errors::Layer::Tls, data)).expect("Parsed http as tls");
+
+        assert_eq!(format!("{}", err), "TLS header at offset 0: expected change cipher spec, alert, handshake or application data content type");
+    }
+
+    #[test]
+    fn parse_truncated_client_hello() {
+        let _ = env_logger::try_init();
+
+        let data = &CLIENT_HELLO_DATA[..CLIENT_HELLO_DATA.len() - 12]; //cut in the alpn extension
+        assert!(TlsRecord::parse(data).is_err());
+
+        let (rem, record) = TlsRecord::parse_truncated(data).expect("Could not parse");
+        assert!(rem.is_empty());
+        assert!(record.is_truncated());
+
+        match record.handshakes()[0] {
+            Handshake::ClientHello(ref hello) => {
+                assert!(hello.is_truncated());
+                assert_eq!(hello.cipher_suites(), &vec![0x1301, 0xC02F]);
+                assert_eq!(hello.server_name(), Some("example.com".to_string()));
+                assert!(hello.alpn_protocols().is_empty());
+            }
+            ref other => panic!("Expected client hello, got {:?}", other)
+        }
+
+        let (_, record) = TlsRecord::parse_truncated(&CLIENT_HELLO_DATA[..5 + 4 + 2 + 32 + 1 + 2 + 3]).expect("Could not parse");
+        match record.handshakes()[0] {
+            Handshake::ClientHello(ref hello) => {
+                assert_eq!(hello.cipher_suites(), &vec![0x1301]);
+                assert!(hello.extensions().is_empty());
+            }
+            ref other => panic!("Expected client hello, got {:?}", other)
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use super::prelude::*;
+
+use self::nom::*;
+
+use std;
+
+const SYNC_STREAM: &'static [u8] = &[0xFFu8; MAC_LENGTH];
+const REPETITIONS: usize = 16;
+
+///
+/// Wake-on-LAN magic packet, six 0xFF bytes followed by sixteen repetitions of the mac address of
+/// the host to wake, sent in an ethernet frame of type 0x0842 or in any udp datagram
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MagicPacket {
+    target: MacAddress,
+    password: Option<std::vec::Vec<u8>>
+}
+
+impl MagicPacket {
+    ///
+    /// Mac address of the host being woken
+    ///
+    pub fn target(&self) -> &MacAddress { &self.target }
+
+    ///
+    /// SecureOn password, the 4 or 6 bytes following the repeated mac address, if present
+    ///
+    pub fn password(&self) -> Option<&std::vec::Vec<u8>> { self.password.as_ref() }
+
+    ///
+    /// Parse a magic packet starting at the start of `input`
+    ///
+    pub fn parse(input: &[u8]) -> IResult<&[u8], MagicPacket> {
+        layer_span!("wol", length = input.len());
+
+        do_parse!(input,
+
+            tag!(SYNC_STREAM) >>
+            target: take!(MAC_LENGTH) >>
+            repeated: verify!(take!(MAC_LENGTH * (REPETITIONS - 1)), |r: &[u8]| r.chunks(MAC_LENGTH).all(|c| c == target)) >>
+            password: rest >>
+
+            (
+                MagicPacket {
+                    target: MacAddress(*array_ref![target, 0, MAC_LENGTH]),
+                    password: match password.len() {
+                        4 | 6 => Some(password.into()),
+                        _ => None
+                    }
+                }
+            )
+        )
+    }
+
+    ///
+    /// Magic packet anywhere in `payload`, as receiving network interfaces match the pattern
+    /// wherever it appears
+    ///
+    pub fn find(payload: &[u8]) -> Option<MagicPacket> {
+        (0..payload.len())
+            .filter(|i| payload[*i..].starts_with(SYNC_STREAM))
+            .filter_map(|i| MagicPacket::parse(&payload[i..]).ok())
+            .map(|(_, magic)| magic)
+            .next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    const TARGET: [u8; MAC_LENGTH] = [0x00u8, 0x11u8, 0x22u8, 0x33u8, 0x44u8, 0x55u8];
+
+    fn magic_packet() -> std::vec::Vec<u8> {
+        let mut data = SYNC_STREAM.to_vec();
+        for _ in 0..REPETITIONS {
+            data.extend_from_slice(&TARGET);
+        }
+        data
+    }
+
+    #[test]
+    fn parse_magic_packet() {
+        let _ = env_logger::try_init();
+
+        let data = magic_packet();
+        let (rem, magic) = MagicPacket::parse(&data).expect("Could not parse");
+
+        assert!(rem.is_empty());
+        assert_eq!(magic.target(), &MacAddress(TARGET));
+        assert_eq!(magic.password(), None);
+
+        let mut secure_on = magic_packet();
+        secure_on.extend_from_slice(&[0x01u8, 0x02u8, 0x03u8, 0x04u8]); //password
+        let (_, magic) = MagicPacket::parse(&secure_on).expect("Could not parse");
+        assert_eq!(magic.password(), Some(&vec![0x01u8, 0x02u8, 0x03u8, 0x04u8]));
+
+        let mut mismatched = magic_packet();
+        mismatched[6 + 15 * MAC_LENGTH] = 0x01u8;
+        assert!(MagicPacket::parse(&mismatched).is_err());
+        assert!(MagicPacket::parse(&data[..50]).is_err());
+    }
+
+    #[test]
+    fn find_magic_packet() {
+        let _ = env_logger::try_init();
+
+        let mut payload = vec![0xFFu8, 0xFFu8, 0x00u8]; //leading bytes, including a partial sync stream
+        payload.extend_from_slice(&magic_packet());
+
+        assert_eq!(MagicPacket::find(&payload).map(|m| *m.target()), Some(MacAddress(TARGET)));
+        assert_eq!(MagicPacket::find(&payload[..40]), None);
+        assert_eq!(MagicPacket::find(&[]), None);
+    }
+}
@@ -7,9 +7,39 @@
 ///! associated records.
 ///!
 #[macro_use] pub extern crate arrayref;
-#[macro_use] pub extern crate error_chain;
+#[cfg(feature = "bytes")] pub extern crate bytes;
+#[cfg(feature = "compression")] pub extern crate flate2;
+#[cfg(feature = "futures-core")] pub extern crate futures_core;
+#[cfg(feature = "libc")] pub extern crate libc;
 #[macro_use(debug, info, error, log, trace, warn)] pub extern crate log;
+#[cfg(feature = "lz4")] pub extern crate lz4_flex;
 #[macro_use] pub extern crate nom;
+#[cfg(feature = "rayon")] pub extern crate rayon;
+#[cfg(feature = "zstd")] pub extern crate ruzstd;
+#[cfg(feature = "sha2")] pub extern crate sha2;
+#[cfg(feature = "tokio")] pub extern crate tokio;
+#[cfg(feature = "tracing")] pub extern crate tracing;
+#[cfg(feature = "twox-hash")] pub extern crate twox_hash;
+
+///
+/// Span covering the parsing of a record or layer, with fields such as record index, offset or
+/// length. With the `tracing` feature a trace level span is entered until the end of the enclosing
+/// block, otherwise the fields are logged at trace level.
+///
+#[cfg(feature = "tracing")]
+macro_rules! layer_span {
+    ($name:tt, $($key:ident = $value:expr),*) => {
+        let span = ::tracing::trace_span!($name, $($key = $value),*);
+        let _enter = span.enter();
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! layer_span {
+    ($name:tt, $($key:ident = $value:expr),*) => {
+        trace!(concat!($name $(, " ", stringify!($key), "={}")*), $($value),*);
+    }
+}
 
 pub mod prelude {
     pub use super::arrayref::*;
@@ -20,6 +50,7 @@ pub mod prelude {
 }
 
 pub mod convert {
+    #[cfg(feature = "flows")]
     pub use super::flow::Flow;
     pub use super::record::*;
     pub use std::convert::TryFrom;
@@ -29,75 +60,492 @@ pub mod errors {
     use std;
     use super::layer2;
     use super::layer3;
+    use super::link_type;
+    #[cfg(feature = "tunnels")]
+    use super::tunnel;
 
-    // Create the Error, ErrorKind, ResultExt, and Result types
-    error_chain! {
-        foreign_links {
-            Io(std::io::Error) #[doc = "Error during IO"];
-            Ffi(std::ffi::NulError) #[doc = "Error during FFI conversion"];
-            Utf8(std::str::Utf8Error) #[doc = "Error during UTF8 conversion"];
-        }
-        errors {
-            FlowParse {
-                display("Parsing failure when converting to flow")
-            }
-            NomIncomplete(needed: String) {
-                display("Not enough data to parse, needed {}", needed)
-            }
-            NomError(message: String) {
-                display("Error parsing: {}", message)
-            }
-            IncompleteParse(amt: usize) {
-                display("Incomplete parse of payload, {} bytes remain", amt)
-            }
-            EthernetType(value: layer2::ethernet::EthernetTypeId) {
-                display("Invalid ethernet type {:?}", value)
+    ///
+    /// Kinds of errors raised while parsing captures and converting them to flows
+    ///
+    #[derive(Debug)]
+    pub enum ErrorKind {
+        /// Free form error message
+        Msg(String),
+        /// Error during IO
+        Io(std::io::Error),
+        /// Error during FFI conversion
+        Ffi(std::ffi::NulError),
+        /// Error during UTF8 conversion
+        Utf8(std::str::Utf8Error),
+        FlowParse,
+        IncompleteParse(usize),
+        EthernetType(layer2::ethernet::EthernetTypeId),
+        /// Link type of a capture whose records have no layer 2 parser
+        LinkType(link_type::LinkType),
+        IPv4Length(u8),
+        IPv4Type(layer3::InternetProtocolId),
+        IPv6Type(layer3::InternetProtocolId),
+        FlowConversion(String),
+        Parse(ParseContext),
+        /// Error code and message sent by a remote capture server
+        Rpcap(u16, String),
+        /// Layers of a record truncated by the capture could not be parsed, with the bytes of the
+        /// packet missing from the record
+        Truncated(u32),
+        /// Frame longer than the configured maximum frame length, with its length and the maximum
+        FrameLength(usize, usize),
+        /// Tunnels nested deeper than the configured maximum depth
+        #[cfg(feature = "tunnels")]
+        TunnelDepth(usize),
+        /// Tunnel carrying itself, with the same encapsulation, id and endpoints as a tunnel outside it
+        #[cfg(feature = "tunnels")]
+        TunnelLoop(tunnel::TunnelInfo),
+        NotImplemented
+    }
+
+    impl std::fmt::Display for ErrorKind {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            match *self {
+                ErrorKind::Msg(ref message) => write!(f, "{}", message),
+                ErrorKind::Io(ref e) => write!(f, "{}", e),
+                ErrorKind::Ffi(ref e) => write!(f, "{}", e),
+                ErrorKind::Utf8(ref e) => write!(f, "{}", e),
+                ErrorKind::FlowParse => write!(f, "Parsing failure when converting to flow"),
+                ErrorKind::IncompleteParse(amt) => write!(f, "Incomplete parse of payload, {} bytes remain", amt),
+                ErrorKind::EthernetType(ref value) => write!(f, "Invalid ethernet type {:?}", value),
+                ErrorKind::LinkType(link_type) => write!(f, "Unsupported link type {}", link_type),
+                ErrorKind::IPv4Length(value) => write!(f, "Invalid IPv4 length {}", value),
+                ErrorKind::IPv4Type(ref value) => write!(f, "Invalid ipv4 type {:?}", value),
+                ErrorKind::IPv6Type(ref value) => write!(f, "Invalid ipv6 type {:?}", value),
+                ErrorKind::FlowConversion(ref why) => write!(f, "Could not convert to flow {}", why),
+                ErrorKind::Parse(ref context) => write!(f, "{}", context),
+                ErrorKind::Rpcap(code, ref message) => write!(f, "Remote capture error {}: {}", code, message),
+                ErrorKind::Truncated(missing) => write!(f, "Record truncated by the capture, {} bytes of the packet missing", missing),
+                ErrorKind::FrameLength(length, maximum) => write!(f, "Frame of {} bytes longer than the maximum of {}", length, maximum),
+                #[cfg(feature = "tunnels")]
+                ErrorKind::TunnelDepth(depth) => write!(f, "Tunnels nested deeper than {}", depth),
+                #[cfg(feature = "tunnels")]
+                ErrorKind::TunnelLoop(ref info) => write!(f, "Tunnel {:?} {:?} from {}:{} to {}:{} carries itself",
+                                                          info.tunnel_type, info.id, info.src_ip, info.src_port, info.dst_ip, info.dst_port),
+                ErrorKind::NotImplemented => write!(f, "Not implemented yet")
             }
-            IPv4Length(value: u8) {
-                display("Invalid IPv4 length {}", value)
+        }
+    }
+
+    ///
+    /// Error raised by this crate, optionally caused by another error. Errors are `Send + Sync`
+    /// so they can cross thread and task boundaries.
+    ///
+    #[derive(Debug)]
+    pub struct Error {
+        kind: ErrorKind,
+        cause: Option<Box<dyn std::error::Error + Send + Sync + 'static>>
+    }
+
+    pub type Result<T> = std::result::Result<T, Error>;
+
+    impl Error {
+        pub fn from_kind(kind: ErrorKind) -> Error {
+            Error {
+                kind: kind,
+                cause: None
             }
-            IPv4Type(value: layer3::InternetProtocolId) {
-                display("Invalid ipv4 type {:?}", value)
+        }
+
+        pub fn kind(&self) -> &ErrorKind { &self.kind }
+
+        ///
+        /// Wrap this error as the cause of a new error
+        ///
+        pub fn chain_err<F, K>(self, f: F) -> Error where F: FnOnce() -> K, K: Into<Error> {
+            let mut err: Error = f().into();
+            err.cause = Some(Box::new(self));
+            err
+        }
+    }
+
+    ///
+    /// Extension to wrap the error of a result as the cause of a new error
+    ///
+    pub trait ResultExt<T> {
+        fn chain_err<F, K>(self, f: F) -> Result<T> where F: FnOnce() -> K, K: Into<Error>;
+    }
+
+    impl<T, E> ResultExt<T> for std::result::Result<T, E> where E: std::error::Error + Send + Sync + 'static {
+        fn chain_err<F, K>(self, f: F) -> Result<T> where F: FnOnce() -> K, K: Into<Error> {
+            self.map_err(|e| {
+                let mut err: Error = f().into();
+                err.cause = Some(Box::new(e));
+                err
+            })
+        }
+    }
+
+    impl std::fmt::Display for Error {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "{}", self.kind)
+        }
+    }
+
+    impl std::error::Error for Error {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            match self.kind {
+                ErrorKind::Io(ref e) => Some(e),
+                ErrorKind::Ffi(ref e) => Some(e),
+                ErrorKind::Utf8(ref e) => Some(e),
+                _ => self.cause.as_ref().map(|c| {
+                    let c: &(dyn std::error::Error + 'static) = &**c;
+                    c
+                })
             }
-            IPv6Type(value: layer3::InternetProtocolId) {
-                display("Invalid ipv6 type {:?}", value)
+        }
+    }
+
+    impl From<ErrorKind> for Error {
+        fn from(kind: ErrorKind) -> Error {
+            Error::from_kind(kind)
+        }
+    }
+
+    impl<'a> From<&'a str> for Error {
+        fn from(message: &'a str) -> Error {
+            Error::from_kind(ErrorKind::Msg(message.to_string()))
+        }
+    }
+
+    impl From<String> for Error {
+        fn from(message: String) -> Error {
+            Error::from_kind(ErrorKind::Msg(message))
+        }
+    }
+
+    impl From<std::io::Error> for Error {
+        fn from(err: std::io::Error) -> Error {
+            Error::from_kind(ErrorKind::Io(err))
+        }
+    }
+
+    impl From<std::ffi::NulError> for Error {
+        fn from(err: std::ffi::NulError) -> Error {
+            Error::from_kind(ErrorKind::Ffi(err))
+        }
+    }
+
+    impl From<std::str::Utf8Error> for Error {
+        fn from(err: std::str::Utf8Error) -> Error {
+            Error::from_kind(ErrorKind::Utf8(err))
+        }
+    }
+
+    ///
+    /// Layer being parsed when an error occurred
+    ///
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub enum Layer {
+        GlobalHeader,
+        Record,
+        Rpcap,
+        Ethernet,
+        Sll,
+        Null,
+        Ieee80211,
+        Llc,
+        Ppp,
+        Pppoe,
+        Stp,
+        IPv4,
+        IPv6,
+        Tcp,
+        Udp,
+        Gtp,
+        Arp,
+        Dhcp,
+        Dns,
+        Tls,
+        Http,
+        Gre,
+        Vxlan,
+        Erspan,
+        Geneve,
+        GtpU,
+        Pcapng,
+        Snoop,
+        Netmon
+    }
+
+    impl std::fmt::Display for Layer {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            let name = match *self {
+                Layer::GlobalHeader => "Global",
+                Layer::Record => "Record",
+                Layer::Rpcap => "rpcap",
+                Layer::Ethernet => "Ethernet",
+                Layer::Sll => "Linux cooked",
+                Layer::Null => "Loopback",
+                Layer::Ieee80211 => "802.11",
+                Layer::Llc => "LLC",
+                Layer::Ppp => "PPP",
+                Layer::Pppoe => "PPPoE",
+                Layer::Stp => "STP",
+                Layer::IPv4 => "IPv4",
+                Layer::IPv6 => "IPv6",
+                Layer::Tcp => "TCP",
+                Layer::Udp => "UDP",
+                Layer::Gtp => "GTP",
+                Layer::Arp => "ARP",
+                Layer::Dhcp => "DHCP",
+                Layer::Dns => "DNS",
+                Layer::Tls => "TLS",
+                Layer::Http => "HTTP",
+                Layer::Gre => "GRE",
+                Layer::Vxlan => "VXLAN",
+                Layer::Erspan => "ERSPAN",
+                Layer::Geneve => "Geneve",
+                Layer::GtpU => "GTP-U",
+                Layer::Pcapng => "pcapng",
+                Layer::Snoop => "snoop",
+                Layer::Netmon => "NetMon"
+            };
+            write!(f, "{}", name)
+        }
+    }
+
+    ///
+    /// Custom nom error codes raised by layer parsers, carried as `nom::ErrorKind::Custom`
+    ///
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub enum ParseCode {
+        IPv4Version = 1,
+        IPv4HeaderLength = 2,
+        IPv4TotalLength = 3,
+        IPv6Version = 4,
+        TcpHeaderLength = 5,
+        UdpLength = 6,
+        GtpVersion = 7,
+        GtpLength = 8,
+        GtpInformationElementLength = 9,
+        ArpAddressLength = 10,
+        DhcpMagicCookie = 11,
+        DnsName = 12,
+        TlsContentType = 13,
+        HttpStartLine = 14,
+        HttpChunkSize = 15,
+        GreVersion = 16,
+        VxlanFlags = 17,
+        GeneveVersion = 18,
+        GtpUVersion = 19,
+        GtpUExtensionLength = 20,
+        PcapngBlockLength = 21,
+        PcapngByteOrder = 22,
+        PcapngInterface = 23,
+        SnoopVersion = 24,
+        SnoopRecordLength = 25,
+        NetmonVersion = 26,
+        NetmonFrameOffset = 27,
+        ErspanVersion = 28,
+        PcapngTimestamp = 29,
+        PcapngLinkType = 30
+    }
+
+    impl ParseCode {
+        pub fn from_code(code: u32) -> Option<ParseCode> {
+            match code {
+                1 => Some(ParseCode::IPv4Version),
+                2 => Some(ParseCode::IPv4HeaderLength),
+                3 => Some(ParseCode::IPv4TotalLength),
+                4 => Some(ParseCode::IPv6Version),
+                5 => Some(ParseCode::TcpHeaderLength),
+                6 => Some(ParseCode::UdpLength),
+                7 => Some(ParseCode::GtpVersion),
+                8 => Some(ParseCode::GtpLength),
+                9 => Some(ParseCode::GtpInformationElementLength),
+                10 => Some(ParseCode::ArpAddressLength),
+                11 => Some(ParseCode::DhcpMagicCookie),
+                12 => Some(ParseCode::DnsName),
+                13 => Some(ParseCode::TlsContentType),
+                14 => Some(ParseCode::HttpStartLine),
+                15 => Some(ParseCode::HttpChunkSize),
+                16 => Some(ParseCode::GreVersion),
+                17 => Some(ParseCode::VxlanFlags),
+                18 => Some(ParseCode::GeneveVersion),
+                19 => Some(ParseCode::GtpUVersion),
+                20 => Some(ParseCode::GtpUExtensionLength),
+                21 => Some(ParseCode::PcapngBlockLength),
+                22 => Some(ParseCode::PcapngByteOrder),
+                23 => Some(ParseCode::PcapngInterface),
+                24 => Some(ParseCode::SnoopVersion),
+                25 => Some(ParseCode::SnoopRecordLength),
+                26 => Some(ParseCode::NetmonVersion),
+                27 => Some(ParseCode::NetmonFrameOffset),
+                28 => Some(ParseCode::ErspanVersion),
+                29 => Some(ParseCode::PcapngTimestamp),
+                30 => Some(ParseCode::PcapngLinkType),
+                _ => None
             }
-            FlowConversion(why: String) {
-                display("Could not convert to flow {}", why)
+        }
+
+        pub fn expected(&self) -> &'static str {
+            match *self {
+                ParseCode::IPv4Version => "version 4",
+                ParseCode::IPv4HeaderLength => "header length (IHL) of at least 5 words",
+                ParseCode::IPv4TotalLength => "total length covering the header",
+                ParseCode::IPv6Version => "version 6",
+                ParseCode::TcpHeaderLength => "data offset between 5 and 15 words",
+                ParseCode::UdpLength => "length covering the 8 byte header",
+                ParseCode::GtpVersion => "version 2",
+                ParseCode::GtpLength => "message length covering the header",
+                ParseCode::GtpInformationElementLength => "information elements within the message length",
+                ParseCode::ArpAddressLength => "ethernet (6 byte) and ipv4 (4 byte) addresses",
+                ParseCode::DhcpMagicCookie => "magic cookie 0x63825363",
+                ParseCode::DnsName => "name labels and at most 16 compression pointers within the message",
+                ParseCode::TlsContentType => "change cipher spec, alert, handshake or application data content type",
+                ParseCode::HttpStartLine => "request or status line",
+                ParseCode::HttpChunkSize => "hexadecimal chunk size",
+                ParseCode::GreVersion => "version 0",
+                ParseCode::VxlanFlags => "VNI flag set",
+                ParseCode::GeneveVersion => "version 0",
+                ParseCode::GtpUVersion => "version 1 with the GTP protocol type",
+                ParseCode::GtpUExtensionLength => "extension header length of at least 1 word",
+                ParseCode::PcapngBlockLength => "block length of at least 12 bytes in words, repeated after the block body",
+                ParseCode::PcapngByteOrder => "byte order magic 0x1A2B3C4D",
+                ParseCode::PcapngInterface => "interface described earlier in the section",
+                ParseCode::SnoopVersion => "version 2",
+                ParseCode::SnoopRecordLength => "record length covering the 24 byte header and included data",
+                ParseCode::NetmonVersion => "version 2",
+                ParseCode::NetmonFrameOffset => "frame table and frame offsets within the capture",
+                ParseCode::ErspanVersion => "version 1 (type II) or 2 (type III)",
+                ParseCode::PcapngTimestamp => "timestamp and interface offset within the range of system time after the epoch",
+                ParseCode::PcapngLinkType => "interfaces of a single link type"
             }
-            NotImplemented {
-                display("Not implemented yet")
+        }
+    }
+
+    ///
+    /// Location and cause of a parse failure within a record
+    ///
+    #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+    pub struct ParseContext {
+        pub layer: Layer,
+        pub record: Option<usize>,
+        pub offset: usize,
+        pub expected: String
+    }
+
+    impl std::fmt::Display for ParseContext {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            match self.record {
+                Some(idx) => write!(f, "{} header at record {}, offset {}: expected {}", self.layer, idx, self.offset, self.expected),
+                None => write!(f, "{} header at offset {}: expected {}", self.layer, self.offset, self.expected)
             }
         }
     }
 
-    impl<I, E> From<super::nom::Err<I, E>> for Error {
-        fn from(err: super::nom::Err<I, E>) -> Error {
-            match err {
+    impl Error {
+        ///
+        /// Convert a nom error raised while parsing `input` as `layer`, recording where in `input`
+        /// the failure happened
+        ///
+        pub fn from_nom(err: super::nom::Err<&[u8]>, layer: Layer, input: &[u8]) -> Error {
+            let (offset, expected) = match err {
                 super::nom::Err::Incomplete(super::nom::Needed::Unknown) => {
-                    Error::from_kind(ErrorKind::NomIncomplete("Unknown".to_string()))
+                    (input.len(), "more data".to_string())
                 }
                 super::nom::Err::Incomplete(super::nom::Needed::Size(sz)) => {
-                    Error::from_kind(ErrorKind::NomIncomplete(format!("{}", sz)))
-                }
-                super::nom::Err::Error(super::nom::simple_errors::Context::Code(_, k)) => {
-                    Error::from_kind(ErrorKind::NomError(k.description().to_string()))
+                    (input.len(), format!("{} more bytes", sz))
                 }
-                super::nom::Err::Failure(super::nom::simple_errors::Context::Code(_, k)) => {
-                    Error::from_kind(ErrorKind::NomError(k.description().to_string()))
+                super::nom::Err::Error(super::nom::simple_errors::Context::Code(rem, k)) |
+                super::nom::Err::Failure(super::nom::simple_errors::Context::Code(rem, k)) => {
+                    let expected = match k {
+                        super::nom::ErrorKind::Custom(code) => {
+                            ParseCode::from_code(code).map(|c| c.expected().to_string())
+                                .unwrap_or_else(|| format!("custom code {}", code))
+                        }
+                        other => other.description().to_string()
+                    };
+                    (input.len().saturating_sub(rem.len()), expected)
                 }
+            };
+            Error::from_kind(ErrorKind::Parse(ParseContext {
+                layer: layer,
+                record: None,
+                offset: offset,
+                expected: expected
+            }))
+        }
+
+        ///
+        /// Shift the offset of a parse error by the length of the enclosing headers, leaving other
+        /// errors unchanged
+        ///
+        pub fn offset_by(self, amount: usize) -> Error {
+            if let ErrorKind::Parse(ref context) = *self.kind() {
+                let mut context = context.clone();
+                context.offset += amount;
+                return Error::from_kind(ErrorKind::Parse(context))
+            }
+            self
+        }
+
+        ///
+        /// Attach the index of the record being parsed to a parse error
+        ///
+        pub fn at_record(self, index: usize) -> Error {
+            if let ErrorKind::Parse(ref context) = *self.kind() {
+                let mut context = context.clone();
+                context.record = Some(index);
+                return Error::from_kind(ErrorKind::Parse(context))
             }
+            self
         }
     }
 }
 
+pub mod analysis;
+#[cfg(feature = "tokio")]
+pub mod async;
+#[cfg(any(all(feature = "afpacket", target_os = "linux"), feature = "libpcap"))]
+pub mod capture;
 pub mod common;
+pub mod compression;
+pub mod filter;
+#[cfg(feature = "flows")]
 pub mod flow;
+pub mod format;
 pub mod global_header;
+pub mod index;
 pub mod layer2;
 pub mod layer3;
 pub mod layer4;
+pub mod layer7;
+pub mod link_type;
+pub mod lint;
+pub mod merge;
+pub mod netmon;
+#[cfg(feature = "oui")]
+pub mod oui;
+#[cfg(feature = "rayon")]
+pub mod parallel;
+pub mod parsed;
+pub mod pcapng;
+#[cfg(feature = "writer")]
+pub mod pipeline;
+pub mod reader;
 pub mod record;
+pub mod recovery;
+#[cfg(feature = "remote")]
+pub mod remote;
+pub mod scan;
+#[cfg(feature = "bytes")]
+pub mod shared;
+pub mod snoop;
+#[cfg(feature = "writer")]
+pub mod split;
+#[cfg(feature = "writer")]
+pub mod transcode;
+#[cfg(feature = "tunnels")]
+pub mod tunnel;
+#[cfg(feature = "writer")]
+pub mod writer;
 
 use errors::*;
 use nom::*;
@@ -116,6 +564,9 @@ use nom::*;
 ///    let file_bytes = include_bytes!("capture.pcap");
 ///    let records = CaptureParser::parse_file(file_bytes).expect("Could not parse");
 ///
+///    //Read and parse a capture file from disk
+///    let (header, records) = CaptureParser::parse_path("capture.pcap").expect("Could not parse");
+///
 ///    //Parse a sequence of one or more packet records
 ///    let records = CaptureParser::parse_records(record_bytes).expect("Could not parse");
 ///
@@ -132,24 +583,104 @@ pub struct CaptureParser;
 
 impl CaptureParser {
     ///
-    /// Parse a slice of bytes that start with libpcap file format header (https://wiki.wireshark.org/Development/LibpcapFileFormat)
+    /// Parse a slice of bytes that start with libpcap file format header (https://wiki.wireshark.org/Development/LibpcapFileFormat).
+    /// Snoop, NetMon 2.x and pcapng captures, detected by their magic bytes, are parsed into the
+    /// equivalent libpcap header and records. Pcapng captures whose interfaces differ in link type
+    /// have no equivalent libpcap header and fail, `pcapng::Capture` parses them.
     ///
-    pub fn parse_file(input: &[u8]) -> IResult<&[u8], (global_header::GlobalHeader, std::vec::Vec<record::PcapRecord>)> {
-        let header_res = global_header::GlobalHeader::parse(input);
+    pub fn parse_file<'a, T: AsRef<[u8]> + ?Sized>(input: &'a T) -> Result<(&'a [u8], (global_header::GlobalHeader, std::vec::Vec<record::PcapRecord>))> {
+        let input = input.as_ref();
+        if pcapng::is_pcapng(input) {
+            debug!("Parsing pcapng capture");
+            return pcapng::parse_file(input)
+                .map_err(|e| errors::Error::from_nom(e, errors::Layer::Pcapng, input))
+        }
+        if snoop::is_snoop(input) {
+            debug!("Parsing snoop capture");
+            return snoop::parse_file(input)
+                .map_err(|e| errors::Error::from_nom(e, errors::Layer::Snoop, input))
+        }
+        if netmon::is_netmon(input) {
+            debug!("Parsing NetMon capture");
+            return netmon::parse_file(input)
+                .map_err(|e| errors::Error::from_nom(e, errors::Layer::Netmon, input))
+        }
 
-        header_res.and_then(|r| {
-            let (rem, header) = r;
+        let (rem, header) = global_header::GlobalHeader::parse(input)
+            .map_err(|e| errors::Error::from_nom(e, errors::Layer::GlobalHeader, input))?;
 
-            debug!("Global header version {}.{}, with endianness {:?}", header.version_major(), header.version_minor(), header.endianness());
+        debug!("Global header version {}.{}, with endianness {:?}", header.version_major(), header.version_minor(), header.endianness());
 
-            CaptureParser::parse_records(rem, header.endianness()).map(|records_res| {
-                let (records_rem, records) = records_res;
+        let (records_rem, records) = CaptureParser::parse_records_with_variant(rem, header.endianness(), header.precision(), header.variant())
+            .map_err(|e| e.offset_by(global_header::GLOBAL_HEADER_LENGTH))?;
+        let records = records.into_iter().map(|r| r.with_header(&header)).collect();
 
-                trace!("{} bytes left for record parsing", records_rem.len());
+        trace!("{} bytes left for record parsing", records_rem.len());
 
-                (records_rem, (header, records))
-            })
-        })
+        Ok( (records_rem, (header, records)) )
+    }
+
+    ///
+    /// Parse a capture in memory as `parse_file` does, then each record into its layers with the
+    /// layer 2 parser of the capture's link type. Records of link types without one fail with
+    /// `ErrorKind::LinkType`.
+    ///
+    pub fn parse_file_layers<T: AsRef<[u8]> + ?Sized>(input: &T) -> Result<(global_header::GlobalHeader, std::vec::Vec<Result<parsed::ParsedRecord>>)> {
+        let input = input.as_ref();
+        let (_, (header, records)) = CaptureParser::parse_file(input)?;
+        debug!("Parsing records of link type {}", header.link_type());
+        let config = parsed::ParseConfig::for_header(&header);
+        let layers = records.into_iter()
+            .enumerate()
+            .map(|(index, record)| parsed::ParsedRecord::parse_with(record, &config).map_err(|e| e.at_record(index)))
+            .collect();
+        Ok( (header, layers) )
+    }
+
+    ///
+    /// Parse a libpcap capture in memory across the rayon thread pool, after finding record
+    /// boundaries in one pass. Bytes of a trailing incomplete record are ignored.
+    ///
+    #[cfg(feature = "rayon")]
+    pub fn parse_file_parallel<T: AsRef<[u8]> + ?Sized>(input: &T) -> Result<(global_header::GlobalHeader, std::vec::Vec<record::PcapRecord>)> {
+        parallel::parse_file(input.as_ref())
+    }
+
+    ///
+    /// Parse a libpcap capture in memory that may be damaged, e.g. cut off by a crashed tcpdump,
+    /// resynchronizing on the next plausible record after damage and reporting the bytes skipped
+    /// and any truncation, where `parse_file` stops silently
+    ///
+    pub fn parse_file_recovering<T: AsRef<[u8]> + ?Sized>(input: &T) -> Result<recovery::RecoveredCapture> {
+        recovery::recover(input.as_ref(), recovery::RecoveryOptions::default())
+    }
+
+    ///
+    /// Summarize a libpcap capture in memory (record count, time span, byte totals and captured
+    /// length range) by walking only its record headers, without parsing or copying payloads
+    ///
+    pub fn scan<T: AsRef<[u8]> + ?Sized>(input: &T) -> Result<scan::ScanReport> {
+        scan::scan(input.as_ref())
+    }
+
+    ///
+    /// Parse the global header of a libpcap capture in memory, returning it with an iterator parsing
+    /// the records after it one at a time
+    ///
+    pub fn iter_file<'a, T: AsRef<[u8]> + ?Sized>(input: &'a T) -> Result<(global_header::GlobalHeader, record::RecordIterator<'a>)> {
+        let input = input.as_ref();
+        let (rem, header) = global_header::GlobalHeader::parse(input)
+            .map_err(|e| errors::Error::from_nom(e, errors::Layer::GlobalHeader, input))?;
+        let records = record::RecordIterator::for_header(rem, &header);
+        Ok( (header, records) )
+    }
+
+    ///
+    /// Iterator parsing a slice of records without libpcap file format header one at a time, e.g.
+    /// to stop at the first record of interest. Endianness of the byte slice must be known.
+    ///
+    pub fn iter_records<'a, T: AsRef<[u8]> + ?Sized>(input: &'a T, endianness: Endianness) -> record::RecordIterator<'a> {
+        record::RecordIterator::new(input.as_ref(), endianness)
     }
 
     ///
@@ -157,17 +688,37 @@ impl CaptureParser {
     /// header (https://wiki.wireshark.org/Development/LibpcapFileFormat). Endianness of the byte
     /// slice must be known.
     ///
-    pub fn parse_records(input: &[u8], endianness: Endianness) -> IResult<&[u8], std::vec::Vec<record::PcapRecord>> {
+    pub fn parse_records<'a, T: AsRef<[u8]> + ?Sized>(input: &'a T, endianness: Endianness) -> Result<(&'a [u8], std::vec::Vec<record::PcapRecord>)> {
+        CaptureParser::parse_records_with_precision(input, endianness, global_header::TimestampPrecision::Microsecond)
+    }
+
+    ///
+    /// Parse a slice of bytes that correspond to a set of records whose timestamps are in `precision`
+    ///
+    pub fn parse_records_with_precision<'a, T: AsRef<[u8]> + ?Sized>(
+        input: &'a T,
+        endianness: Endianness,
+        precision: global_header::TimestampPrecision
+    ) -> Result<(&'a [u8], std::vec::Vec<record::PcapRecord>)> {
+        CaptureParser::parse_records_with_variant(input, endianness, precision, global_header::PcapVariant::Standard)
+    }
+
+    fn parse_records_with_variant<'a, T: AsRef<[u8]> + ?Sized>(
+        input: &'a T,
+        endianness: Endianness,
+        precision: global_header::TimestampPrecision,
+        variant: global_header::PcapVariant
+    ) -> Result<(&'a [u8], std::vec::Vec<record::PcapRecord>)> {
+        let input = input.as_ref();
         let mut records: std::vec::Vec<record::PcapRecord> = vec![];
         let mut current = input;
 
-        trace!("{} bytes left for record parsing", current.len());
-
         loop {
-            match record::PcapRecord::parse(current, endianness) {
+            layer_span!("record", index = records.len(), offset = input.len() - current.len());
+
+            match record::PcapRecord::parse_with_variant(current, endianness, precision, variant) {
                 Ok( (rem, r) ) => {
                     current = rem;
-                    trace!("{} bytes left for record parsing", current.len());
                     records.push(r);
                 }
                 Err(nom::Err::Incomplete(nom::Needed::Size(s))) => {
@@ -178,7 +729,10 @@ impl CaptureParser {
                     debug!("Needed unknown number of bytes for parsing, only had {}", current.len());
                     break
                 }
-                Err(e) => return Err(e)
+                Err(e) => {
+                    let offset = input.len() - current.len();
+                    return Err(errors::Error::from_nom(e, errors::Layer::Record, current).offset_by(offset).at_record(records.len()))
+                }
             }
         };
 
@@ -188,8 +742,121 @@ impl CaptureParser {
     ///
     /// Parse a slice of bytes as a single record. Endianness must be known.
     ///
-    pub fn parse_record(input: &[u8], endianness: Endianness) -> IResult<&[u8], record::PcapRecord> {
+    pub fn parse_record<'a, T: AsRef<[u8]> + ?Sized>(input: &'a T, endianness: Endianness) -> Result<(&'a [u8], record::PcapRecord)> {
+        let input = input.as_ref();
         record::PcapRecord::parse(input, endianness)
+            .map_err(|e| errors::Error::from_nom(e, errors::Layer::Record, input))
+    }
+
+    ///
+    /// Read an entire libpcap, pcapng, snoop or NetMon capture from `reader` and parse it. Bytes of a trailing
+    /// incomplete record are ignored. Compressed captures are decompressed as `from_reader` describes.
+    ///
+    pub fn parse_reader<R: std::io::Read>(reader: R) -> Result<(global_header::GlobalHeader, std::vec::Vec<record::PcapRecord>)> {
+        use std::io::Read;
+
+        let mut reader = compression::Decoder::new(reader)?;
+
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes)?;
+
+        let (rem, (header, records)) = CaptureParser::parse_file(&bytes)?;
+        if !rem.is_empty() {
+            debug!("Ignoring {} bytes of incomplete record", rem.len());
+        }
+        Ok( (header, records) )
+    }
+
+    ///
+    /// Read and parse the libpcap, pcapng, snoop or NetMon capture at `path`
+    ///
+    pub fn parse_path<P: AsRef<std::path::Path>>(path: P) -> Result<(global_header::GlobalHeader, std::vec::Vec<record::PcapRecord>)> {
+        let file = std::fs::File::open(path)?;
+        CaptureParser::parse_reader(std::io::BufReader::new(file))
+    }
+
+    ///
+    /// Stream the records of a libpcap capture from `source` as they are read, holding a bounded
+    /// buffer rather than the whole capture as `parse_reader` does. A compressed capture, e.g. a
+    /// `.pcap.gz` archive, is decompressed as it is read with the `compression` feature for gzip and
+    /// the `zstd` and `lz4` features for zstd and lz4, and is an error without them.
+    ///
+    pub fn from_reader<R: std::io::Read>(source: R) -> Result<reader::CaptureReader<compression::Decoder<R>>> {
+        reader::CaptureReader::new(compression::Decoder::new(source)?)
+    }
+
+    ///
+    /// Stream the records of a libpcap capture piped to standard input, e.g. `tcpdump -w - | mytool`.
+    /// Each record blocks until the writer has produced all of it, and the capture ends when the
+    /// writer closes the pipe.
+    ///
+    pub fn from_stdin() -> Result<reader::CaptureReader<std::io::Stdin>> {
+        reader::CaptureReader::from_stdin()
+    }
+
+    ///
+    /// Read the records of a libpcap capture from an async `source`, e.g. a tokio file or socket,
+    /// without blocking. With the `stream` feature the reader is a `Stream` of records.
+    ///
+    #[cfg(feature = "tokio")]
+    pub fn from_async_reader<R: tokio::io::AsyncRead + Unpin>(source: R) -> async::AsyncCaptureReader<R> {
+        async::AsyncCaptureReader::new(source)
+    }
+
+    ///
+    /// Read the records of a libpcap capture whose timestamps fall in `[start, end]`, seeking past
+    /// record payloads outside the range rather than reading them. Timestamps are expected to be
+    /// monotonic, so reading stops at the first record after `end`. To query one capture
+    /// repeatedly, build an `index::CaptureIndex` once and use `CaptureIndex::read_time_range`,
+    /// which finds each range by binary search.
+    ///
+    pub fn parse_time_range<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        start: std::time::SystemTime,
+        end: std::time::SystemTime
+    ) -> Result<(global_header::GlobalHeader, std::vec::Vec<record::PcapRecord>)> {
+        use std::io::Read;
+
+        let mut header_bytes = [0u8; global_header::GLOBAL_HEADER_LENGTH];
+        reader.read_exact(&mut header_bytes)?;
+        let (_, header) = global_header::GlobalHeader::parse(&header_bytes)
+            .map_err(|e| errors::Error::from_nom(e, errors::Layer::GlobalHeader, &header_bytes))?;
+
+        let mut records = vec![];
+        let mut record_header_bytes = vec![0u8; header.record_header_length()];
+
+        loop {
+            match reader.read_exact(&mut record_header_bytes) {
+                Ok(_) => {}
+                Err(ref e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.into())
+            }
+            let (_, record_header) = record::RecordHeader::parse_for(&record_header_bytes, &header)
+                .map_err(|e| errors::Error::from_nom(e, errors::Layer::Record, &record_header_bytes))?;
+
+            if *record_header.timestamp() > end {
+                trace!("Record after end of range, stopping");
+                break
+            } else if *record_header.timestamp() < start {
+                reader.seek(std::io::SeekFrom::Current(record_header.actual_length() as i64))?;
+            } else {
+                //the length is untrusted, so the payload grows as it is read rather than up front
+                let mut payload = vec![];
+                (&mut *reader).take(record_header.actual_length() as u64).read_to_end(&mut payload)?;
+                if payload.len() < record_header.actual_length() as usize {
+                    debug!("Capture ended within record payload");
+                    break
+                }
+                records.push(record::PcapRecord::new(
+                    *record_header.timestamp(),
+                    record_header.actual_length(),
+                    record_header.original_length(),
+                    payload
+                ).with_header(&header));
+            }
+        }
+
+        Ok( (header, records) )
     }
 }
 
@@ -253,6 +920,75 @@ mod tests {
         0xfcu8, 0xfdu8, 0xfeu8, 0xffu8 //payload, 8 words
     ];
 
+    fn timed_capture(seconds: &[u32]) -> std::vec::Vec<u8> {
+        let mut bytes = RAW_DATA[0..global_header::GLOBAL_HEADER_LENGTH].to_vec();
+        for (idx, s) in seconds.iter().enumerate() {
+            bytes.extend_from_slice(&[(s >> 24) as u8, (s >> 16) as u8, (s >> 8) as u8, *s as u8]);
+            bytes.extend_from_slice(&[0x00u8, 0x00u8, 0x00u8, 0x00u8]); //microseconds
+            bytes.extend_from_slice(&[0x00u8, 0x00u8, 0x00u8, 0x02u8]); //actual length
+            bytes.extend_from_slice(&[0x00u8, 0x00u8, 0x00u8, 0x02u8]); //original length
+            bytes.extend_from_slice(&[idx as u8, 0xFFu8]); //payload
+        }
+        bytes
+    }
+
+    #[test]
+    fn time_range_parse() {
+        let _ = env_logger::try_init();
+
+        let bytes = timed_capture(&[1, 2, 3, 4, 5]);
+        let mut cursor = std::io::Cursor::new(bytes);
+
+        let (header, records) = CaptureParser::parse_time_range(
+            &mut cursor,
+            record::PcapRecord::convert_packet_time(2, 0),
+            record::PcapRecord::convert_packet_time(4, 0)
+        ).expect("Failed to parse");
+
+        assert_eq!(header.endianness(), Endianness::Big);
+        assert_eq!(records.iter().map(|r| r.payload()[0]).collect::<std::vec::Vec<_>>(), vec![1, 2, 3]);
+
+        //a capture cut off within its last record still has the records before it
+        let mut bytes = timed_capture(&[1, 2, 3, 4, 5]);
+        let length = bytes.len();
+        bytes.truncate(length - 1);
+        let (_, records) = CaptureParser::parse_time_range(
+            &mut std::io::Cursor::new(bytes),
+            record::PcapRecord::convert_packet_time(4, 0),
+            record::PcapRecord::convert_packet_time(9, 0)
+        ).expect("Failed to parse");
+        assert_eq!(records.iter().map(|r| r.payload()[0]).collect::<std::vec::Vec<_>>(), vec![3]);
+
+        //as does one whose last record claims more than the capture holds
+        let mut bytes = timed_capture(&[1, 2, 3]);
+        let length = bytes.len();
+        bytes[length - 10..length - 6].copy_from_slice(&[0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8]);
+        let (_, records) = CaptureParser::parse_time_range(
+            &mut std::io::Cursor::new(bytes),
+            record::PcapRecord::convert_packet_time(1, 0),
+            record::PcapRecord::convert_packet_time(9, 0)
+        ).expect("Failed to parse");
+        assert_eq!(records.iter().map(|r| r.payload()[0]).collect::<std::vec::Vec<_>>(), vec![0, 1]);
+    }
+
+    fn assert_thread_safe_error<E: std::error::Error + Send + Sync + 'static>(_: &E) {}
+
+    #[test]
+    fn error_thread_safe() {
+        let err = errors::Error::from_kind(errors::ErrorKind::IncompleteParse(4))
+            .chain_err(|| errors::ErrorKind::FlowParse);
+
+        assert_thread_safe_error(&err);
+        assert_eq!(format!("{}", err), "Parsing failure when converting to flow");
+        assert_eq!(
+            std::error::Error::source(&err).map(|e| format!("{}", e)),
+            Some("Incomplete parse of payload, 4 bytes remain".to_string())
+        );
+
+        let handle = std::thread::spawn(move || format!("{}", err));
+        assert_eq!(handle.join().expect("Thread failed"), "Parsing failure when converting to flow");
+    }
+
     #[test]
     fn file_bytes_parse() {
         let _ = env_logger::try_init();
@@ -265,6 +1001,210 @@ mod tests {
         assert_eq!(records.len(), 1);
     }
 
+    #[test]
+    fn file_bytes_parse_error() {
+        let _ = env_logger::try_init();
+
+        let err = CaptureParser::parse_file(&RAW_DATA[..10]).expect_err("Parsed truncated global header");
+        match *err.kind() {
+            errors::ErrorKind::Parse(ref context) => {
+                assert_eq!(context.layer, errors::Layer::GlobalHeader);
+                assert_eq!(context.offset, 10);
+            }
+            ref other => panic!("Unexpected error {:?}", other)
+        }
+    }
+
+    #[test]
+    fn file_bytes_parse_nanoseconds() {
+        let _ = env_logger::try_init();
+
+        let mut bytes = RAW_DATA.to_vec();
+        bytes[..4].copy_from_slice(&[0xA1u8, 0xB2u8, 0x3Cu8, 0x4Du8]); //nanosecond magic number
+
+        let (_, (header, records)) = CaptureParser::parse_file(&bytes).expect("Failed to parse");
+
+        assert_eq!(header.precision(), global_header::TimestampPrecision::Nanosecond);
+        assert_eq!(*records[0].timestamp(), std::time::UNIX_EPOCH + std::time::Duration::new(1527868899, 152053));
+    }
+
+    #[test]
+    fn file_bytes_parse_layers() {
+        let _ = env_logger::try_init();
+
+        let (header, records) = CaptureParser::parse_file_layers(RAW_DATA).expect("Failed to parse");
+        assert_eq!(header.link_type(), link_type::LinkType::Unknown(2));
+        let err = records.into_iter().next().expect("No record").expect_err("Parsed record of unknown link type");
+        match *err.kind() {
+            errors::ErrorKind::LinkType(link_type) => assert_eq!(link_type, link_type::LinkType::Unknown(2)),
+            ref other => panic!("Unexpected error {:?}", other)
+        }
+
+        let mut bytes = RAW_DATA.to_vec();
+        bytes[23] = 0x01u8; //network, ethernet
+
+        let (_, records) = CaptureParser::parse_file_layers(&bytes).expect("Failed to parse");
+        let parsed = records.into_iter().next().expect("No record").expect("Failed to parse layers");
+        assert_eq!(parsed.dst_port(), Some(80));
+    }
+
+    #[test]
+    fn file_bytes_parse_layers_sll() {
+        let _ = env_logger::try_init();
+
+        let mut bytes = RAW_DATA[0..global_header::GLOBAL_HEADER_LENGTH].to_vec();
+        bytes[23] = 0x71u8; //network, linux cooked
+        bytes.extend_from_slice(&RAW_DATA[24..32]); //timestamp
+        bytes.extend_from_slice(&[0x00u8, 0x00u8, 0x00u8, 0x58u8]); //actual length, 88: 16 (sll) + 72 (ipv4)
+        bytes.extend_from_slice(&[0x00u8, 0x00u8, 0x00u8, 0x58u8]); //original length, 88
+        bytes.extend_from_slice(&[
+            0x00u8, 0x01u8, //packet type, broadcast
+            0x00u8, 0x01u8, //arphrd type, ethernet
+            0x00u8, 0x06u8, //address length, 6
+            0xFFu8, 0xFEu8, 0xFDu8, 0xFCu8, 0xFBu8, 0xFAu8, 0x00u8, 0x00u8, //address FF:FE:FD:FC:FB:FA
+            0x08u8, 0x00u8 //protocol, ipv4
+        ]);
+        bytes.extend_from_slice(&RAW_DATA[54..]);
+
+        let (header, records) = CaptureParser::parse_file_layers(&bytes).expect("Failed to parse");
+        assert_eq!(header.link_type(), link_type::LinkType::LinuxSll);
+        let parsed = records.into_iter().next().expect("No record").expect("Failed to parse layers");
+
+        assert!(parsed.ethernet().is_none());
+        assert_eq!(parsed.layer2().src_mac(), Some(common::MacAddress([0xFFu8, 0xFEu8, 0xFDu8, 0xFCu8, 0xFBu8, 0xFAu8])));
+        assert_eq!(parsed.layer2().dst_mac(), None);
+        assert_eq!(parsed.src_ip(), Some(&"1.2.3.4".parse::<std::net::IpAddr>().expect("Could not parse ip address")));
+        assert_eq!(parsed.dst_port(), Some(80));
+        assert_eq!(parsed.delivery(), common::Delivery::Broadcast);
+        assert_eq!(parsed.offsets().layer3, Some(16));
+        assert_eq!(parsed.offsets().layer4, Some(36));
+        assert!(parsed.anomalies().is_empty());
+    }
+
+    #[cfg(feature = "writer")]
+    #[test]
+    fn file_bytes_parse_pcapng() {
+        let _ = env_logger::try_init();
+
+        let records = vec![
+            record::PcapRecord::new(std::time::UNIX_EPOCH + std::time::Duration::new(1527868899, 152053789), 4, 4, vec![0x01u8, 0x02u8, 0x03u8, 0x04u8]),
+            record::PcapRecord::new(std::time::UNIX_EPOCH + std::time::Duration::new(1527868900, 0), 2, 60, vec![0xFFu8; 2])
+        ];
+        let mut writer = pcapng::Writer::with_endianness(vec![], Endianness::Little).expect("Could not write section header");
+        let first = writer.add_interface(pcapng::Interface::new(link_type::LinkType::Raw, 1500)).expect("Could not add interface");
+        let mut nanos = pcapng::Interface::new(link_type::LinkType::Raw, 0);
+        nanos.set_timestamp_resolution(pcapng::TimestampResolution::Decimal(9));
+        let second = writer.add_interface(nanos).expect("Could not add interface");
+        writer.write_record(first, &records[0]).expect("Could not write record");
+        writer.write_record(second, &records[1]).expect("Could not write record");
+        let bytes = writer.into_inner();
+
+        let (rem, (header, parsed)) = CaptureParser::parse_file(&bytes).expect("Failed to parse");
+        assert!(rem.is_empty());
+        assert_eq!(header.link_type(), link_type::LinkType::Raw);
+        assert_eq!(header.endianness(), Endianness::Little);
+        assert_eq!(header.snap_length(), pcapng::UNLIMITED_SNAP_LENGTH);
+        assert_eq!(header.precision(), global_header::TimestampPrecision::Nanosecond);
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed.iter().map(|r| r.interface_id()).collect::<std::vec::Vec<_>>(), vec![0, 1]);
+        assert_eq!(parsed[1].timestamp(), records[1].timestamp());
+        assert_eq!(parsed[1].original_length(), 60);
+        assert_eq!(CaptureParser::parse_reader(&bytes[..]).expect("Failed to parse"), (header, parsed));
+
+        //interfaces of several link types have no libpcap header
+        let mut writer = pcapng::Writer::new(vec![]).expect("Could not write section header");
+        writer.add_interface(pcapng::Interface::new(link_type::LinkType::Raw, 0)).expect("Could not add interface");
+        writer.add_interface(pcapng::Interface::new(link_type::LinkType::Ethernet, 0)).expect("Could not add interface");
+        let bytes = writer.into_inner();
+        let err = CaptureParser::parse_reader(&bytes[..]).expect_err("Parsed interfaces of several link types");
+        assert_eq!(format!("{}", err), "pcapng header at offset 0: expected interfaces of a single link type");
+    }
+
+    #[test]
+    fn stream_parse() {
+        let _ = env_logger::try_init();
+
+        let mut bytes = RAW_DATA.to_vec();
+        bytes.extend_from_slice(&RAW_DATA[global_header::GLOBAL_HEADER_LENGTH..]);
+
+        let reader = CaptureParser::from_reader(&bytes[..]).expect("Failed to read header");
+        assert_eq!(reader.header().endianness(), Endianness::Big);
+
+        let records = reader.collect::<Result<std::vec::Vec<_>>>().expect("Failed to parse");
+        let (_, (_, expected)) = CaptureParser::parse_file(&bytes).expect("Failed to parse");
+        assert_eq!(records, expected);
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn compressed_parse() {
+        use flate2::Compression;
+        use flate2::write::GzEncoder;
+
+        let _ = env_logger::try_init();
+
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(RAW_DATA).expect("Failed to compress");
+        let compressed = encoder.finish().expect("Failed to compress");
+
+        let (header, records) = CaptureParser::parse_reader(&compressed[..]).expect("Failed to parse");
+        assert_eq!(header.endianness(), Endianness::Big);
+        assert_eq!(records.len(), 1);
+
+        let reader = CaptureParser::from_reader(&compressed[..]).expect("Failed to read header");
+        assert_eq!(reader.collect::<Result<std::vec::Vec<_>>>().expect("Failed to parse"), records);
+
+        let reader = CaptureParser::from_reader(RAW_DATA).expect("Failed to read header");
+        assert_eq!(reader.collect::<Result<std::vec::Vec<_>>>().expect("Failed to parse"), records);
+    }
+
+    #[cfg(not(feature = "compression"))]
+    #[test]
+    fn compressed_parse_disabled() {
+        let _ = env_logger::try_init();
+
+        let compressed = [0x1Fu8, 0x8Bu8, 0x08u8, 0x00u8];
+        assert!(CaptureParser::from_reader(&compressed[..]).is_err());
+        assert!(CaptureParser::parse_reader(&compressed[..]).is_err());
+    }
+
+    #[test]
+    fn iter_parse() {
+        let _ = env_logger::try_init();
+
+        let mut bytes = RAW_DATA.to_vec();
+        bytes.extend_from_slice(&RAW_DATA[global_header::GLOBAL_HEADER_LENGTH..]);
+
+        let (header, records) = CaptureParser::iter_file(&bytes).expect("Failed to parse header");
+        assert_eq!(header.endianness(), Endianness::Big);
+        let records = records.collect::<Result<std::vec::Vec<_>>>().expect("Failed to parse");
+        let (_, (_, expected)) = CaptureParser::parse_file(&bytes).expect("Failed to parse");
+        assert_eq!(records, expected);
+
+        let mut records = CaptureParser::iter_records(&bytes[global_header::GLOBAL_HEADER_LENGTH..], Endianness::Big);
+        assert!(records.next().is_some());
+        assert_eq!(records.offset(), RAW_DATA.len() - global_header::GLOBAL_HEADER_LENGTH);
+
+        assert!(CaptureParser::iter_file(&bytes[..10]).is_err());
+    }
+
+    #[test]
+    fn reader_parse() {
+        let _ = env_logger::try_init();
+
+        let mut bytes = RAW_DATA.to_vec();
+        bytes.extend_from_slice(&RAW_DATA[global_header::GLOBAL_HEADER_LENGTH..global_header::GLOBAL_HEADER_LENGTH + 4]);
+
+        let (header, records) = CaptureParser::parse_reader(std::io::Cursor::new(bytes)).expect("Failed to parse");
+
+        assert_eq!(header.endianness(), Endianness::Big);
+        assert_eq!(records.len(), 1);
+
+        let (_, (_, from_vec)) = CaptureParser::parse_file(&RAW_DATA.to_vec()).expect("Failed to parse");
+        assert_eq!(from_vec, records);
+    }
+
+    #[cfg(feature = "flows")]
     #[test]
     fn convert_packet() {
         let _ = env_logger::try_init();
@@ -273,7 +1213,11 @@ mod tests {
 
         assert!(rem.is_empty());
 
-        let record = records.pop().unwrap();
+        //the capture's link type, 2, isn't parsed, though its frame is ethernet
+        let mut record = records.pop().unwrap();
+        assert_eq!(record.link_type(), header.link_type());
+        assert!(Flow::try_from(record.clone()).is_err());
+        record.set_link_type(link_type::LinkType::Ethernet);
         let flow = Flow::try_from(record).expect("Failed to convert record");
 
         assert_eq!(flow.source.port, 50871);
@@ -286,34 +1230,29 @@ mod tests {
 
         let pcap_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources").join("4SICS-GeekLounge-151020.pcap");
 
-        let pcap_reader = std::fs::File::open(pcap_path.clone()).expect(&format!("Failed to open pcap path {:?}", pcap_path));
-
-        let bytes = pcap_reader.bytes().map(|b| b.unwrap()).collect::<std::vec::Vec<u8>>();
-
-        let (rem, (header, records)) = CaptureParser::parse_file(&bytes).expect("Failed to parse");
+        let (header, records) = CaptureParser::parse_path(pcap_path).expect("Failed to parse");
 
         assert_eq!(header.endianness(), Endianness::Little);
         assert_eq!(records.len(), 246137);
     }
 
+    #[cfg(feature = "flows")]
     #[test]
     fn file_convert() {
         let _ = env_logger::try_init();
 
         let pcap_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources").join("4SICS-GeekLounge-151020.pcap");
 
-        let pcap_reader = std::fs::File::open(pcap_path.clone()).expect(&format!("Failed to open pcap path {:?}", pcap_path));
-
-        let bytes = pcap_reader.bytes().map(|b| b.unwrap()).collect::<std::vec::Vec<u8>>();
-
-        let (rem, (header, mut records)) = CaptureParser::parse_file(&bytes).expect("Failed to parse");
+        let (header, records) = CaptureParser::parse_path(pcap_path).expect("Failed to parse");
 
         assert_eq!(header.endianness(), Endianness::Little);
         assert_eq!(records.len(), 246137);
 
         let flows = PcapRecord::convert_records(records, true).expect("Failed to convert to flows");
 
-        assert_eq!(flows.len(), 129643);
+        // includes a flow from sender to target for each ARP record
+        assert_eq!(flows.len(), 134032);
+        assert!(flows.iter().any(|f| f.arp.is_some()));
     }
 
     #[bench]
@@ -334,6 +1273,26 @@ mod tests {
         });
     }
 
+    #[cfg(feature = "rayon")]
+    #[bench]
+    fn bench_parse_parallel(b: &mut Bencher) {
+        let _ = env_logger::try_init();
+
+        let pcap_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources").join("4SICS-GeekLounge-151020.pcap");
+
+        let pcap_reader = std::fs::File::open(pcap_path.clone()).expect(&format!("Failed to open pcap path {:?}", pcap_path));
+
+        let bytes = pcap_reader.bytes().map(|b| b.unwrap()).collect::<std::vec::Vec<u8>>();
+
+        b.iter(|| {
+            let (header, records) = CaptureParser::parse_file_parallel(&bytes).expect("Failed to parse");
+
+            assert_eq!(header.endianness(), Endianness::Little);
+            assert_eq!(records.len(), 246137);
+        });
+    }
+
+    #[cfg(feature = "flows")]
     #[bench]
     fn bench_parse_convert(b: &mut Bencher) {
         let _ = env_logger::try_init();
@@ -352,7 +1311,30 @@ mod tests {
 
             let flows = PcapRecord::convert_records(records, true).expect("Failed to convert to flows");
 
-            assert_eq!(flows.len(), 129643);
+            assert_eq!(flows.len(), 134032);
+        });
+    }
+
+    #[cfg(all(feature = "flows", feature = "rayon"))]
+    #[bench]
+    fn bench_parse_convert_parallel(b: &mut Bencher) {
+        let _ = env_logger::try_init();
+
+        let pcap_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources").join("4SICS-GeekLounge-151020.pcap");
+
+        let pcap_reader = std::fs::File::open(pcap_path.clone()).expect(&format!("Failed to open pcap path {:?}", pcap_path));
+
+        let bytes = pcap_reader.bytes().map(|b| b.unwrap()).collect::<std::vec::Vec<u8>>();
+
+        b.iter(|| {
+            let (header, records) = CaptureParser::parse_file_parallel(&bytes).expect("Failed to parse");
+
+            assert_eq!(header.endianness(), Endianness::Little);
+            assert_eq!(records.len(), 246137);
+
+            let flows = parallel::convert_records(records, true).expect("Failed to convert to flows");
+
+            assert_eq!(flows.len(), 134032);
         });
     }
 }
\ No newline at end of file
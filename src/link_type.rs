@@ -0,0 +1,909 @@
+use super::prelude::*;
+
+use std;
+
+///
+/// First and last of the link types reserved for private use, LINKTYPE_USER0 through LINKTYPE_USER15
+///
+const USER_FIRST: u32 = 147;
+const USER_LAST: u32 = 162;
+
+///
+/// Link layer header type of a capture (the `network` field of a libpcap global header, or the
+/// link type of a pcapng interface), following the tcpdump LINKTYPE_ registry. Values the registry
+/// doesn't assign are kept as `Unknown` so they round trip.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LinkType {
+    Null,
+    Ethernet,
+    Ax25,
+    Ieee8025,
+    ArcnetBsd,
+    Slip,
+    Ppp,
+    Fddi,
+    PppHdlc,
+    PppEther,
+    AtmRfc1483,
+    Raw,
+    CHdlc,
+    Ieee80211,
+    Frelay,
+    Loop,
+    LinuxSll,
+    Ltalk,
+    Pflog,
+    Ieee80211Prism,
+    IpOverFc,
+    Sunatm,
+    Ieee80211Radiotap,
+    ArcnetLinux,
+    AppleIpOverIeee1394,
+    Mtp2WithPhdr,
+    Mtp2,
+    Mtp3,
+    Sccp,
+    Docsis,
+    LinuxIrda,
+    Ieee80211Avs,
+    BacnetMsTp,
+    PppPppd,
+    GprsLlc,
+    GpfT,
+    GpfF,
+    LinuxLapd,
+    Mfr,
+    BluetoothHciH4,
+    UsbLinux,
+    Ppi,
+    Ieee802154WithFcs,
+    Sita,
+    Erf,
+    BluetoothHciH4WithPhdr,
+    Ax25Kiss,
+    Lapd,
+    PppWithDir,
+    CHdlcWithDir,
+    FrelayWithDir,
+    LapbWithDir,
+    IpmbLinux,
+    Flexray,
+    Lin,
+    Ieee802154NonaskPhy,
+    UsbLinuxMmapped,
+    Fc2,
+    Fc2WithFrameDelims,
+    Ipnet,
+    CanSocketcan,
+    Ipv4,
+    Ipv6,
+    Ieee802154NoFcs,
+    Dbus,
+    DvbCi,
+    Mux27010,
+    Stanag5066DPdu,
+    Nflog,
+    Netanalyzer,
+    NetanalyzerTransparent,
+    Ipoib,
+    Mpeg2Ts,
+    Ng40,
+    NfcLlcp,
+    Infiniband,
+    Sctp,
+    Usbpcap,
+    RtacSerial,
+    BluetoothLeLl,
+    Netlink,
+    BluetoothLinuxMonitor,
+    BluetoothBredrBb,
+    BluetoothLeLlWithPhdr,
+    ProfibusDl,
+    Pktap,
+    Epon,
+    IpmiHpm2,
+    ZwaveR1R2,
+    ZwaveR3,
+    WattstopperDlm,
+    Iso14443,
+    Rds,
+    UsbDarwin,
+    Sdlc,
+    Loratap,
+    Vsock,
+    NordicBle,
+    Docsis31Xra31,
+    EthernetMpacket,
+    DisplayportAux,
+    LinuxSll2,
+    Openvizsla,
+    Ebhscr,
+    VppDispatch,
+    DsaTagBrcm,
+    DsaTagBrcmPrepend,
+    Ieee802154Tap,
+    DsaTagDsa,
+    DsaTagEdsa,
+    Elee,
+    ZWaveSerial,
+    Usb20,
+    AtscAlp,
+    Etw,
+    ZbossNcp,
+    Usb20LowSpeed,
+    Usb20FullSpeed,
+    Usb20HighSpeed,
+    AuerswaldLog,
+    ZwaveTap,
+    SilabsDebugChannel,
+    FiraUci,
+    Mdb,
+    DectNr,
+    ///
+    /// Private use link types, numbered 0 through 15
+    ///
+    User(u8),
+    Unknown(u32)
+}
+
+impl LinkType {
+    pub fn new(value: u32) -> LinkType {
+        match value {
+            0 => LinkType::Null,
+            1 => LinkType::Ethernet,
+            3 => LinkType::Ax25,
+            6 => LinkType::Ieee8025,
+            7 => LinkType::ArcnetBsd,
+            8 => LinkType::Slip,
+            9 => LinkType::Ppp,
+            10 => LinkType::Fddi,
+            50 => LinkType::PppHdlc,
+            51 => LinkType::PppEther,
+            100 => LinkType::AtmRfc1483,
+            101 => LinkType::Raw,
+            104 => LinkType::CHdlc,
+            105 => LinkType::Ieee80211,
+            107 => LinkType::Frelay,
+            108 => LinkType::Loop,
+            113 => LinkType::LinuxSll,
+            114 => LinkType::Ltalk,
+            117 => LinkType::Pflog,
+            119 => LinkType::Ieee80211Prism,
+            122 => LinkType::IpOverFc,
+            123 => LinkType::Sunatm,
+            127 => LinkType::Ieee80211Radiotap,
+            129 => LinkType::ArcnetLinux,
+            138 => LinkType::AppleIpOverIeee1394,
+            139 => LinkType::Mtp2WithPhdr,
+            140 => LinkType::Mtp2,
+            141 => LinkType::Mtp3,
+            142 => LinkType::Sccp,
+            143 => LinkType::Docsis,
+            144 => LinkType::LinuxIrda,
+            163 => LinkType::Ieee80211Avs,
+            165 => LinkType::BacnetMsTp,
+            166 => LinkType::PppPppd,
+            169 => LinkType::GprsLlc,
+            170 => LinkType::GpfT,
+            171 => LinkType::GpfF,
+            177 => LinkType::LinuxLapd,
+            182 => LinkType::Mfr,
+            187 => LinkType::BluetoothHciH4,
+            189 => LinkType::UsbLinux,
+            192 => LinkType::Ppi,
+            195 => LinkType::Ieee802154WithFcs,
+            196 => LinkType::Sita,
+            197 => LinkType::Erf,
+            201 => LinkType::BluetoothHciH4WithPhdr,
+            202 => LinkType::Ax25Kiss,
+            203 => LinkType::Lapd,
+            204 => LinkType::PppWithDir,
+            205 => LinkType::CHdlcWithDir,
+            206 => LinkType::FrelayWithDir,
+            207 => LinkType::LapbWithDir,
+            209 => LinkType::IpmbLinux,
+            210 => LinkType::Flexray,
+            212 => LinkType::Lin,
+            215 => LinkType::Ieee802154NonaskPhy,
+            220 => LinkType::UsbLinuxMmapped,
+            224 => LinkType::Fc2,
+            225 => LinkType::Fc2WithFrameDelims,
+            226 => LinkType::Ipnet,
+            227 => LinkType::CanSocketcan,
+            228 => LinkType::Ipv4,
+            229 => LinkType::Ipv6,
+            230 => LinkType::Ieee802154NoFcs,
+            231 => LinkType::Dbus,
+            235 => LinkType::DvbCi,
+            236 => LinkType::Mux27010,
+            237 => LinkType::Stanag5066DPdu,
+            239 => LinkType::Nflog,
+            240 => LinkType::Netanalyzer,
+            241 => LinkType::NetanalyzerTransparent,
+            242 => LinkType::Ipoib,
+            243 => LinkType::Mpeg2Ts,
+            244 => LinkType::Ng40,
+            245 => LinkType::NfcLlcp,
+            247 => LinkType::Infiniband,
+            248 => LinkType::Sctp,
+            249 => LinkType::Usbpcap,
+            250 => LinkType::RtacSerial,
+            251 => LinkType::BluetoothLeLl,
+            253 => LinkType::Netlink,
+            254 => LinkType::BluetoothLinuxMonitor,
+            255 => LinkType::BluetoothBredrBb,
+            256 => LinkType::BluetoothLeLlWithPhdr,
+            257 => LinkType::ProfibusDl,
+            258 => LinkType::Pktap,
+            259 => LinkType::Epon,
+            260 => LinkType::IpmiHpm2,
+            261 => LinkType::ZwaveR1R2,
+            262 => LinkType::ZwaveR3,
+            263 => LinkType::WattstopperDlm,
+            264 => LinkType::Iso14443,
+            265 => LinkType::Rds,
+            266 => LinkType::UsbDarwin,
+            268 => LinkType::Sdlc,
+            270 => LinkType::Loratap,
+            271 => LinkType::Vsock,
+            272 => LinkType::NordicBle,
+            273 => LinkType::Docsis31Xra31,
+            274 => LinkType::EthernetMpacket,
+            275 => LinkType::DisplayportAux,
+            276 => LinkType::LinuxSll2,
+            278 => LinkType::Openvizsla,
+            279 => LinkType::Ebhscr,
+            280 => LinkType::VppDispatch,
+            281 => LinkType::DsaTagBrcm,
+            282 => LinkType::DsaTagBrcmPrepend,
+            283 => LinkType::Ieee802154Tap,
+            284 => LinkType::DsaTagDsa,
+            285 => LinkType::DsaTagEdsa,
+            286 => LinkType::Elee,
+            287 => LinkType::ZWaveSerial,
+            288 => LinkType::Usb20,
+            289 => LinkType::AtscAlp,
+            290 => LinkType::Etw,
+            292 => LinkType::ZbossNcp,
+            293 => LinkType::Usb20LowSpeed,
+            294 => LinkType::Usb20FullSpeed,
+            295 => LinkType::Usb20HighSpeed,
+            296 => LinkType::AuerswaldLog,
+            297 => LinkType::ZwaveTap,
+            298 => LinkType::SilabsDebugChannel,
+            299 => LinkType::FiraUci,
+            300 => LinkType::Mdb,
+            301 => LinkType::DectNr,
+            USER_FIRST..=USER_LAST => LinkType::User((value - USER_FIRST) as u8),
+            _ => {
+                debug!("Encountered unknown link type {}", value);
+                LinkType::Unknown(value)
+            }
+        }
+    }
+
+    ///
+    /// LINKTYPE_ value, the inverse of `new`
+    ///
+    pub fn value(&self) -> u32 {
+        match *self {
+            LinkType::Null => 0,
+            LinkType::Ethernet => 1,
+            LinkType::Ax25 => 3,
+            LinkType::Ieee8025 => 6,
+            LinkType::ArcnetBsd => 7,
+            LinkType::Slip => 8,
+            LinkType::Ppp => 9,
+            LinkType::Fddi => 10,
+            LinkType::PppHdlc => 50,
+            LinkType::PppEther => 51,
+            LinkType::AtmRfc1483 => 100,
+            LinkType::Raw => 101,
+            LinkType::CHdlc => 104,
+            LinkType::Ieee80211 => 105,
+            LinkType::Frelay => 107,
+            LinkType::Loop => 108,
+            LinkType::LinuxSll => 113,
+            LinkType::Ltalk => 114,
+            LinkType::Pflog => 117,
+            LinkType::Ieee80211Prism => 119,
+            LinkType::IpOverFc => 122,
+            LinkType::Sunatm => 123,
+            LinkType::Ieee80211Radiotap => 127,
+            LinkType::ArcnetLinux => 129,
+            LinkType::AppleIpOverIeee1394 => 138,
+            LinkType::Mtp2WithPhdr => 139,
+            LinkType::Mtp2 => 140,
+            LinkType::Mtp3 => 141,
+            LinkType::Sccp => 142,
+            LinkType::Docsis => 143,
+            LinkType::LinuxIrda => 144,
+            LinkType::Ieee80211Avs => 163,
+            LinkType::BacnetMsTp => 165,
+            LinkType::PppPppd => 166,
+            LinkType::GprsLlc => 169,
+            LinkType::GpfT => 170,
+            LinkType::GpfF => 171,
+            LinkType::LinuxLapd => 177,
+            LinkType::Mfr => 182,
+            LinkType::BluetoothHciH4 => 187,
+            LinkType::UsbLinux => 189,
+            LinkType::Ppi => 192,
+            LinkType::Ieee802154WithFcs => 195,
+            LinkType::Sita => 196,
+            LinkType::Erf => 197,
+            LinkType::BluetoothHciH4WithPhdr => 201,
+            LinkType::Ax25Kiss => 202,
+            LinkType::Lapd => 203,
+            LinkType::PppWithDir => 204,
+            LinkType::CHdlcWithDir => 205,
+            LinkType::FrelayWithDir => 206,
+            LinkType::LapbWithDir => 207,
+            LinkType::IpmbLinux => 209,
+            LinkType::Flexray => 210,
+            LinkType::Lin => 212,
+            LinkType::Ieee802154NonaskPhy => 215,
+            LinkType::UsbLinuxMmapped => 220,
+            LinkType::Fc2 => 224,
+            LinkType::Fc2WithFrameDelims => 225,
+            LinkType::Ipnet => 226,
+            LinkType::CanSocketcan => 227,
+            LinkType::Ipv4 => 228,
+            LinkType::Ipv6 => 229,
+            LinkType::Ieee802154NoFcs => 230,
+            LinkType::Dbus => 231,
+            LinkType::DvbCi => 235,
+            LinkType::Mux27010 => 236,
+            LinkType::Stanag5066DPdu => 237,
+            LinkType::Nflog => 239,
+            LinkType::Netanalyzer => 240,
+            LinkType::NetanalyzerTransparent => 241,
+            LinkType::Ipoib => 242,
+            LinkType::Mpeg2Ts => 243,
+            LinkType::Ng40 => 244,
+            LinkType::NfcLlcp => 245,
+            LinkType::Infiniband => 247,
+            LinkType::Sctp => 248,
+            LinkType::Usbpcap => 249,
+            LinkType::RtacSerial => 250,
+            LinkType::BluetoothLeLl => 251,
+            LinkType::Netlink => 253,
+            LinkType::BluetoothLinuxMonitor => 254,
+            LinkType::BluetoothBredrBb => 255,
+            LinkType::BluetoothLeLlWithPhdr => 256,
+            LinkType::ProfibusDl => 257,
+            LinkType::Pktap => 258,
+            LinkType::Epon => 259,
+            LinkType::IpmiHpm2 => 260,
+            LinkType::ZwaveR1R2 => 261,
+            LinkType::ZwaveR3 => 262,
+            LinkType::WattstopperDlm => 263,
+            LinkType::Iso14443 => 264,
+            LinkType::Rds => 265,
+            LinkType::UsbDarwin => 266,
+            LinkType::Sdlc => 268,
+            LinkType::Loratap => 270,
+            LinkType::Vsock => 271,
+            LinkType::NordicBle => 272,
+            LinkType::Docsis31Xra31 => 273,
+            LinkType::EthernetMpacket => 274,
+            LinkType::DisplayportAux => 275,
+            LinkType::LinuxSll2 => 276,
+            LinkType::Openvizsla => 278,
+            LinkType::Ebhscr => 279,
+            LinkType::VppDispatch => 280,
+            LinkType::DsaTagBrcm => 281,
+            LinkType::DsaTagBrcmPrepend => 282,
+            LinkType::Ieee802154Tap => 283,
+            LinkType::DsaTagDsa => 284,
+            LinkType::DsaTagEdsa => 285,
+            LinkType::Elee => 286,
+            LinkType::ZWaveSerial => 287,
+            LinkType::Usb20 => 288,
+            LinkType::AtscAlp => 289,
+            LinkType::Etw => 290,
+            LinkType::ZbossNcp => 292,
+            LinkType::Usb20LowSpeed => 293,
+            LinkType::Usb20FullSpeed => 294,
+            LinkType::Usb20HighSpeed => 295,
+            LinkType::AuerswaldLog => 296,
+            LinkType::ZwaveTap => 297,
+            LinkType::SilabsDebugChannel => 298,
+            LinkType::FiraUci => 299,
+            LinkType::Mdb => 300,
+            LinkType::DectNr => 301,
+            LinkType::User(n) => USER_FIRST + n as u32,
+            LinkType::Unknown(value) => value
+        }
+    }
+
+    ///
+    /// Registry name without the LINKTYPE_ prefix, e.g. ETHERNET, or None for private use and
+    /// unknown link types
+    ///
+    pub fn name(&self) -> Option<&'static str> {
+        let name = match *self {
+            LinkType::Null => "NULL",
+            LinkType::Ethernet => "ETHERNET",
+            LinkType::Ax25 => "AX25",
+            LinkType::Ieee8025 => "IEEE802_5",
+            LinkType::ArcnetBsd => "ARCNET_BSD",
+            LinkType::Slip => "SLIP",
+            LinkType::Ppp => "PPP",
+            LinkType::Fddi => "FDDI",
+            LinkType::PppHdlc => "PPP_HDLC",
+            LinkType::PppEther => "PPP_ETHER",
+            LinkType::AtmRfc1483 => "ATM_RFC1483",
+            LinkType::Raw => "RAW",
+            LinkType::CHdlc => "C_HDLC",
+            LinkType::Ieee80211 => "IEEE802_11",
+            LinkType::Frelay => "FRELAY",
+            LinkType::Loop => "LOOP",
+            LinkType::LinuxSll => "LINUX_SLL",
+            LinkType::Ltalk => "LTALK",
+            LinkType::Pflog => "PFLOG",
+            LinkType::Ieee80211Prism => "IEEE802_11_PRISM",
+            LinkType::IpOverFc => "IP_OVER_FC",
+            LinkType::Sunatm => "SUNATM",
+            LinkType::Ieee80211Radiotap => "IEEE802_11_RADIOTAP",
+            LinkType::ArcnetLinux => "ARCNET_LINUX",
+            LinkType::AppleIpOverIeee1394 => "APPLE_IP_OVER_IEEE1394",
+            LinkType::Mtp2WithPhdr => "MTP2_WITH_PHDR",
+            LinkType::Mtp2 => "MTP2",
+            LinkType::Mtp3 => "MTP3",
+            LinkType::Sccp => "SCCP",
+            LinkType::Docsis => "DOCSIS",
+            LinkType::LinuxIrda => "LINUX_IRDA",
+            LinkType::Ieee80211Avs => "IEEE802_11_AVS",
+            LinkType::BacnetMsTp => "BACNET_MS_TP",
+            LinkType::PppPppd => "PPP_PPPD",
+            LinkType::GprsLlc => "GPRS_LLC",
+            LinkType::GpfT => "GPF_T",
+            LinkType::GpfF => "GPF_F",
+            LinkType::LinuxLapd => "LINUX_LAPD",
+            LinkType::Mfr => "MFR",
+            LinkType::BluetoothHciH4 => "BLUETOOTH_HCI_H4",
+            LinkType::UsbLinux => "USB_LINUX",
+            LinkType::Ppi => "PPI",
+            LinkType::Ieee802154WithFcs => "IEEE802_15_4_WITHFCS",
+            LinkType::Sita => "SITA",
+            LinkType::Erf => "ERF",
+            LinkType::BluetoothHciH4WithPhdr => "BLUETOOTH_HCI_H4_WITH_PHDR",
+            LinkType::Ax25Kiss => "AX25_KISS",
+            LinkType::Lapd => "LAPD",
+            LinkType::PppWithDir => "PPP_WITH_DIR",
+            LinkType::CHdlcWithDir => "C_HDLC_WITH_DIR",
+            LinkType::FrelayWithDir => "FRELAY_WITH_DIR",
+            LinkType::LapbWithDir => "LAPB_WITH_DIR",
+            LinkType::IpmbLinux => "IPMB_LINUX",
+            LinkType::Flexray => "FLEXRAY",
+            LinkType::Lin => "LIN",
+            LinkType::Ieee802154NonaskPhy => "IEEE802_15_4_NONASK_PHY",
+            LinkType::UsbLinuxMmapped => "USB_LINUX_MMAPPED",
+            LinkType::Fc2 => "FC_2",
+            LinkType::Fc2WithFrameDelims => "FC_2_WITH_FRAME_DELIMS",
+            LinkType::Ipnet => "IPNET",
+            LinkType::CanSocketcan => "CAN_SOCKETCAN",
+            LinkType::Ipv4 => "IPV4",
+            LinkType::Ipv6 => "IPV6",
+            LinkType::Ieee802154NoFcs => "IEEE802_15_4_NOFCS",
+            LinkType::Dbus => "DBUS",
+            LinkType::DvbCi => "DVB_CI",
+            LinkType::Mux27010 => "MUX27010",
+            LinkType::Stanag5066DPdu => "STANAG_5066_D_PDU",
+            LinkType::Nflog => "NFLOG",
+            LinkType::Netanalyzer => "NETANALYZER",
+            LinkType::NetanalyzerTransparent => "NETANALYZER_TRANSPARENT",
+            LinkType::Ipoib => "IPOIB",
+            LinkType::Mpeg2Ts => "MPEG_2_TS",
+            LinkType::Ng40 => "NG40",
+            LinkType::NfcLlcp => "NFC_LLCP",
+            LinkType::Infiniband => "INFINIBAND",
+            LinkType::Sctp => "SCTP",
+            LinkType::Usbpcap => "USBPCAP",
+            LinkType::RtacSerial => "RTAC_SERIAL",
+            LinkType::BluetoothLeLl => "BLUETOOTH_LE_LL",
+            LinkType::Netlink => "NETLINK",
+            LinkType::BluetoothLinuxMonitor => "BLUETOOTH_LINUX_MONITOR",
+            LinkType::BluetoothBredrBb => "BLUETOOTH_BREDR_BB",
+            LinkType::BluetoothLeLlWithPhdr => "BLUETOOTH_LE_LL_WITH_PHDR",
+            LinkType::ProfibusDl => "PROFIBUS_DL",
+            LinkType::Pktap => "PKTAP",
+            LinkType::Epon => "EPON",
+            LinkType::IpmiHpm2 => "IPMI_HPM_2",
+            LinkType::ZwaveR1R2 => "ZWAVE_R1_R2",
+            LinkType::ZwaveR3 => "ZWAVE_R3",
+            LinkType::WattstopperDlm => "WATTSTOPPER_DLM",
+            LinkType::Iso14443 => "ISO_14443",
+            LinkType::Rds => "RDS",
+            LinkType::UsbDarwin => "USB_DARWIN",
+            LinkType::Sdlc => "SDLC",
+            LinkType::Loratap => "LORATAP",
+            LinkType::Vsock => "VSOCK",
+            LinkType::NordicBle => "NORDIC_BLE",
+            LinkType::Docsis31Xra31 => "DOCSIS31_XRA31",
+            LinkType::EthernetMpacket => "ETHERNET_MPACKET",
+            LinkType::DisplayportAux => "DISPLAYPORT_AUX",
+            LinkType::LinuxSll2 => "LINUX_SLL2",
+            LinkType::Openvizsla => "OPENVIZSLA",
+            LinkType::Ebhscr => "EBHSCR",
+            LinkType::VppDispatch => "VPP_DISPATCH",
+            LinkType::DsaTagBrcm => "DSA_TAG_BRCM",
+            LinkType::DsaTagBrcmPrepend => "DSA_TAG_BRCM_PREPEND",
+            LinkType::Ieee802154Tap => "IEEE802_15_4_TAP",
+            LinkType::DsaTagDsa => "DSA_TAG_DSA",
+            LinkType::DsaTagEdsa => "DSA_TAG_EDSA",
+            LinkType::Elee => "ELEE",
+            LinkType::ZWaveSerial => "Z_WAVE_SERIAL",
+            LinkType::Usb20 => "USB_2_0",
+            LinkType::AtscAlp => "ATSC_ALP",
+            LinkType::Etw => "ETW",
+            LinkType::ZbossNcp => "ZBOSS_NCP",
+            LinkType::Usb20LowSpeed => "USB_2_0_LOW_SPEED",
+            LinkType::Usb20FullSpeed => "USB_2_0_FULL_SPEED",
+            LinkType::Usb20HighSpeed => "USB_2_0_HIGH_SPEED",
+            LinkType::AuerswaldLog => "AUERSWALD_LOG",
+            LinkType::ZwaveTap => "ZWAVE_TAP",
+            LinkType::SilabsDebugChannel => "SILABS_DEBUG_CHANNEL",
+            LinkType::FiraUci => "FIRA_UCI",
+            LinkType::Mdb => "MDB",
+            LinkType::DectNr => "DECT_NR",
+            LinkType::User(_) | LinkType::Unknown(_) => return None
+        };
+        Some(name)
+    }
+
+    ///
+    /// Short human readable description, e.g. Linux cooked
+    ///
+    pub fn description(&self) -> &'static str {
+        match *self {
+            LinkType::Null => "BSD loopback",
+            LinkType::Ethernet => "Ethernet",
+            LinkType::Ax25 => "AX.25",
+            LinkType::Ieee8025 => "Token Ring",
+            LinkType::ArcnetBsd => "ARCNET",
+            LinkType::Slip => "SLIP",
+            LinkType::Ppp => "PPP",
+            LinkType::Fddi => "FDDI",
+            LinkType::PppHdlc => "PPP in HDLC-like framing",
+            LinkType::PppEther => "PPPoE session",
+            LinkType::AtmRfc1483 => "RFC 1483 LLC/SNAP over ATM",
+            LinkType::Raw => "Raw IP",
+            LinkType::CHdlc => "Cisco HDLC",
+            LinkType::Ieee80211 => "802.11",
+            LinkType::Frelay => "Frame Relay",
+            LinkType::Loop => "OpenBSD loopback",
+            LinkType::LinuxSll => "Linux cooked",
+            LinkType::Ltalk => "LocalTalk",
+            LinkType::Pflog => "OpenBSD pflog",
+            LinkType::Ieee80211Prism => "802.11 with Prism header",
+            LinkType::IpOverFc => "IP over Fibre Channel",
+            LinkType::Sunatm => "SunATM",
+            LinkType::Ieee80211Radiotap => "802.11 with radiotap header",
+            LinkType::ArcnetLinux => "Linux ARCNET",
+            LinkType::AppleIpOverIeee1394 => "Apple IP over IEEE 1394",
+            LinkType::Mtp2WithPhdr => "SS7 MTP2 with pseudo header",
+            LinkType::Mtp2 => "SS7 MTP2",
+            LinkType::Mtp3 => "SS7 MTP3",
+            LinkType::Sccp => "SS7 SCCP",
+            LinkType::Docsis => "DOCSIS",
+            LinkType::LinuxIrda => "Linux IrDA",
+            LinkType::Ieee80211Avs => "802.11 with AVS header",
+            LinkType::BacnetMsTp => "BACnet MS/TP",
+            LinkType::PppPppd => "PPP with direction",
+            LinkType::GprsLlc => "GPRS LLC",
+            LinkType::GpfT => "GPF-T",
+            LinkType::GpfF => "GPF-F",
+            LinkType::LinuxLapd => "Linux LAPD",
+            LinkType::Mfr => "Multi Link Frame Relay",
+            LinkType::BluetoothHciH4 => "Bluetooth HCI UART",
+            LinkType::UsbLinux => "Linux USB",
+            LinkType::Ppi => "Per-Packet Information",
+            LinkType::Ieee802154WithFcs => "802.15.4 with FCS",
+            LinkType::Sita => "SITA",
+            LinkType::Erf => "Endace ERF",
+            LinkType::BluetoothHciH4WithPhdr => "Bluetooth HCI UART with direction",
+            LinkType::Ax25Kiss => "AX.25 with KISS header",
+            LinkType::Lapd => "LAPD",
+            LinkType::PppWithDir => "PPP with direction",
+            LinkType::CHdlcWithDir => "Cisco HDLC with direction",
+            LinkType::FrelayWithDir => "Frame Relay with direction",
+            LinkType::LapbWithDir => "LAPB with direction",
+            LinkType::IpmbLinux => "IPMB",
+            LinkType::Flexray => "FlexRay",
+            LinkType::Lin => "LIN",
+            LinkType::Ieee802154NonaskPhy => "802.15.4 with PHY header",
+            LinkType::UsbLinuxMmapped => "Linux USB, memory mapped",
+            LinkType::Fc2 => "Fibre Channel FC-2",
+            LinkType::Fc2WithFrameDelims => "Fibre Channel FC-2 with frame delimiters",
+            LinkType::Ipnet => "Solaris ipnet",
+            LinkType::CanSocketcan => "Linux SocketCAN",
+            LinkType::Ipv4 => "Raw IPv4",
+            LinkType::Ipv6 => "Raw IPv6",
+            LinkType::Ieee802154NoFcs => "802.15.4 without FCS",
+            LinkType::Dbus => "D-Bus",
+            LinkType::DvbCi => "DVB-CI",
+            LinkType::Mux27010 => "3GPP TS 27.010",
+            LinkType::Stanag5066DPdu => "STANAG 5066 D_PDU",
+            LinkType::Nflog => "Linux netfilter log",
+            LinkType::Netanalyzer => "Hilscher netANALYZER",
+            LinkType::NetanalyzerTransparent => "Hilscher netANALYZER, transparent",
+            LinkType::Ipoib => "IP over InfiniBand",
+            LinkType::Mpeg2Ts => "MPEG-2 transport stream",
+            LinkType::Ng40 => "ng40 protocol tester",
+            LinkType::NfcLlcp => "NFC LLCP",
+            LinkType::Infiniband => "InfiniBand",
+            LinkType::Sctp => "SCTP",
+            LinkType::Usbpcap => "USBPcap",
+            LinkType::RtacSerial => "RTAC serial",
+            LinkType::BluetoothLeLl => "Bluetooth LE link layer",
+            LinkType::Netlink => "Linux netlink",
+            LinkType::BluetoothLinuxMonitor => "Linux Bluetooth monitor",
+            LinkType::BluetoothBredrBb => "Bluetooth BR/EDR baseband",
+            LinkType::BluetoothLeLlWithPhdr => "Bluetooth LE link layer with pseudo header",
+            LinkType::ProfibusDl => "PROFIBUS data link",
+            LinkType::Pktap => "Apple PKTAP",
+            LinkType::Epon => "Ethernet passive optical network",
+            LinkType::IpmiHpm2 => "IPMI HPM.2 trace",
+            LinkType::ZwaveR1R2 => "Z-Wave R1 and R2",
+            LinkType::ZwaveR3 => "Z-Wave R3",
+            LinkType::WattstopperDlm => "WattStopper DLM",
+            LinkType::Iso14443 => "ISO 14443",
+            LinkType::Rds => "RDS",
+            LinkType::UsbDarwin => "Darwin USB",
+            LinkType::Sdlc => "SDLC",
+            LinkType::Loratap => "LoRaTap",
+            LinkType::Vsock => "Linux vsock",
+            LinkType::NordicBle => "Nordic Semiconductor BLE sniffer",
+            LinkType::Docsis31Xra31 => "DOCSIS 3.1 XRA31",
+            LinkType::EthernetMpacket => "Ethernet mPackets",
+            LinkType::DisplayportAux => "DisplayPort AUX channel",
+            LinkType::LinuxSll2 => "Linux cooked v2",
+            LinkType::Openvizsla => "OpenVizsla",
+            LinkType::Ebhscr => "Elektrobit high speed capture and replay",
+            LinkType::VppDispatch => "fd.io VPP dispatch trace",
+            LinkType::DsaTagBrcm => "Broadcom DSA tag",
+            LinkType::DsaTagBrcmPrepend => "Broadcom DSA tag, prepended",
+            LinkType::Ieee802154Tap => "802.15.4 with TAP header",
+            LinkType::DsaTagDsa => "Marvell DSA tag",
+            LinkType::DsaTagEdsa => "Marvell EDSA tag",
+            LinkType::Elee => "ELEE lawful intercept",
+            LinkType::ZWaveSerial => "Z-Wave serial",
+            LinkType::Usb20 => "USB 2.0",
+            LinkType::AtscAlp => "ATSC link layer protocol",
+            LinkType::Etw => "Event Tracing for Windows",
+            LinkType::ZbossNcp => "ZBOSS NCP",
+            LinkType::Usb20LowSpeed => "USB 2.0 low speed",
+            LinkType::Usb20FullSpeed => "USB 2.0 full speed",
+            LinkType::Usb20HighSpeed => "USB 2.0 high speed",
+            LinkType::AuerswaldLog => "Auerswald logger",
+            LinkType::ZwaveTap => "Z-Wave with TAP header",
+            LinkType::SilabsDebugChannel => "Silicon Labs debug channel",
+            LinkType::FiraUci => "FiRa UCI",
+            LinkType::Mdb => "MDB",
+            LinkType::DectNr => "DECT-2020 NR",
+            LinkType::User(_) => "Private use",
+            LinkType::Unknown(_) => "Unknown"
+        }
+    }
+
+    ///
+    /// Link type from its registry name, with or without the LINKTYPE_ prefix and in any case
+    ///
+    pub fn from_name(name: &str) -> Option<LinkType> {
+        let upper = name.to_uppercase();
+        let name = upper.trim_start_matches("LINKTYPE_");
+        if let Some(n) = name.strip_prefix("USER") {
+            return n.parse::<u8>().ok()
+                .filter(|n| (*n as u32) <= USER_LAST - USER_FIRST)
+                .map(LinkType::User);
+        }
+        match name {
+            "NULL" => Some(LinkType::Null),
+            "ETHERNET" => Some(LinkType::Ethernet),
+            "AX25" => Some(LinkType::Ax25),
+            "IEEE802_5" => Some(LinkType::Ieee8025),
+            "ARCNET_BSD" => Some(LinkType::ArcnetBsd),
+            "SLIP" => Some(LinkType::Slip),
+            "PPP" => Some(LinkType::Ppp),
+            "FDDI" => Some(LinkType::Fddi),
+            "PPP_HDLC" => Some(LinkType::PppHdlc),
+            "PPP_ETHER" => Some(LinkType::PppEther),
+            "ATM_RFC1483" => Some(LinkType::AtmRfc1483),
+            "RAW" => Some(LinkType::Raw),
+            "C_HDLC" => Some(LinkType::CHdlc),
+            "IEEE802_11" => Some(LinkType::Ieee80211),
+            "FRELAY" => Some(LinkType::Frelay),
+            "LOOP" => Some(LinkType::Loop),
+            "LINUX_SLL" => Some(LinkType::LinuxSll),
+            "LTALK" => Some(LinkType::Ltalk),
+            "PFLOG" => Some(LinkType::Pflog),
+            "IEEE802_11_PRISM" => Some(LinkType::Ieee80211Prism),
+            "IP_OVER_FC" => Some(LinkType::IpOverFc),
+            "SUNATM" => Some(LinkType::Sunatm),
+            "IEEE802_11_RADIOTAP" => Some(LinkType::Ieee80211Radiotap),
+            "ARCNET_LINUX" => Some(LinkType::ArcnetLinux),
+            "APPLE_IP_OVER_IEEE1394" => Some(LinkType::AppleIpOverIeee1394),
+            "MTP2_WITH_PHDR" => Some(LinkType::Mtp2WithPhdr),
+            "MTP2" => Some(LinkType::Mtp2),
+            "MTP3" => Some(LinkType::Mtp3),
+            "SCCP" => Some(LinkType::Sccp),
+            "DOCSIS" => Some(LinkType::Docsis),
+            "LINUX_IRDA" => Some(LinkType::LinuxIrda),
+            "IEEE802_11_AVS" => Some(LinkType::Ieee80211Avs),
+            "BACNET_MS_TP" => Some(LinkType::BacnetMsTp),
+            "PPP_PPPD" => Some(LinkType::PppPppd),
+            "GPRS_LLC" => Some(LinkType::GprsLlc),
+            "GPF_T" => Some(LinkType::GpfT),
+            "GPF_F" => Some(LinkType::GpfF),
+            "LINUX_LAPD" => Some(LinkType::LinuxLapd),
+            "MFR" => Some(LinkType::Mfr),
+            "BLUETOOTH_HCI_H4" => Some(LinkType::BluetoothHciH4),
+            "USB_LINUX" => Some(LinkType::UsbLinux),
+            "PPI" => Some(LinkType::Ppi),
+            "IEEE802_15_4_WITHFCS" => Some(LinkType::Ieee802154WithFcs),
+            "SITA" => Some(LinkType::Sita),
+            "ERF" => Some(LinkType::Erf),
+            "BLUETOOTH_HCI_H4_WITH_PHDR" => Some(LinkType::BluetoothHciH4WithPhdr),
+            "AX25_KISS" => Some(LinkType::Ax25Kiss),
+            "LAPD" => Some(LinkType::Lapd),
+            "PPP_WITH_DIR" => Some(LinkType::PppWithDir),
+            "C_HDLC_WITH_DIR" => Some(LinkType::CHdlcWithDir),
+            "FRELAY_WITH_DIR" => Some(LinkType::FrelayWithDir),
+            "LAPB_WITH_DIR" => Some(LinkType::LapbWithDir),
+            "IPMB_LINUX" => Some(LinkType::IpmbLinux),
+            "FLEXRAY" => Some(LinkType::Flexray),
+            "LIN" => Some(LinkType::Lin),
+            "IEEE802_15_4_NONASK_PHY" => Some(LinkType::Ieee802154NonaskPhy),
+            "USB_LINUX_MMAPPED" => Some(LinkType::UsbLinuxMmapped),
+            "FC_2" => Some(LinkType::Fc2),
+            "FC_2_WITH_FRAME_DELIMS" => Some(LinkType::Fc2WithFrameDelims),
+            "IPNET" => Some(LinkType::Ipnet),
+            "CAN_SOCKETCAN" => Some(LinkType::CanSocketcan),
+            "IPV4" => Some(LinkType::Ipv4),
+            "IPV6" => Some(LinkType::Ipv6),
+            "IEEE802_15_4_NOFCS" => Some(LinkType::Ieee802154NoFcs),
+            "DBUS" => Some(LinkType::Dbus),
+            "DVB_CI" => Some(LinkType::DvbCi),
+            "MUX27010" => Some(LinkType::Mux27010),
+            "STANAG_5066_D_PDU" => Some(LinkType::Stanag5066DPdu),
+            "NFLOG" => Some(LinkType::Nflog),
+            "NETANALYZER" => Some(LinkType::Netanalyzer),
+            "NETANALYZER_TRANSPARENT" => Some(LinkType::NetanalyzerTransparent),
+            "IPOIB" => Some(LinkType::Ipoib),
+            "MPEG_2_TS" => Some(LinkType::Mpeg2Ts),
+            "NG40" => Some(LinkType::Ng40),
+            "NFC_LLCP" => Some(LinkType::NfcLlcp),
+            "INFINIBAND" => Some(LinkType::Infiniband),
+            "SCTP" => Some(LinkType::Sctp),
+            "USBPCAP" => Some(LinkType::Usbpcap),
+            "RTAC_SERIAL" => Some(LinkType::RtacSerial),
+            "BLUETOOTH_LE_LL" => Some(LinkType::BluetoothLeLl),
+            "NETLINK" => Some(LinkType::Netlink),
+            "BLUETOOTH_LINUX_MONITOR" => Some(LinkType::BluetoothLinuxMonitor),
+            "BLUETOOTH_BREDR_BB" => Some(LinkType::BluetoothBredrBb),
+            "BLUETOOTH_LE_LL_WITH_PHDR" => Some(LinkType::BluetoothLeLlWithPhdr),
+            "PROFIBUS_DL" => Some(LinkType::ProfibusDl),
+            "PKTAP" => Some(LinkType::Pktap),
+            "EPON" => Some(LinkType::Epon),
+            "IPMI_HPM_2" => Some(LinkType::IpmiHpm2),
+            "ZWAVE_R1_R2" => Some(LinkType::ZwaveR1R2),
+            "ZWAVE_R3" => Some(LinkType::ZwaveR3),
+            "WATTSTOPPER_DLM" => Some(LinkType::WattstopperDlm),
+            "ISO_14443" => Some(LinkType::Iso14443),
+            "RDS" => Some(LinkType::Rds),
+            "USB_DARWIN" => Some(LinkType::UsbDarwin),
+            "SDLC" => Some(LinkType::Sdlc),
+            "LORATAP" => Some(LinkType::Loratap),
+            "VSOCK" => Some(LinkType::Vsock),
+            "NORDIC_BLE" => Some(LinkType::NordicBle),
+            "DOCSIS31_XRA31" => Some(LinkType::Docsis31Xra31),
+            "ETHERNET_MPACKET" => Some(LinkType::EthernetMpacket),
+            "DISPLAYPORT_AUX" => Some(LinkType::DisplayportAux),
+            "LINUX_SLL2" => Some(LinkType::LinuxSll2),
+            "OPENVIZSLA" => Some(LinkType::Openvizsla),
+            "EBHSCR" => Some(LinkType::Ebhscr),
+            "VPP_DISPATCH" => Some(LinkType::VppDispatch),
+            "DSA_TAG_BRCM" => Some(LinkType::DsaTagBrcm),
+            "DSA_TAG_BRCM_PREPEND" => Some(LinkType::DsaTagBrcmPrepend),
+            "IEEE802_15_4_TAP" => Some(LinkType::Ieee802154Tap),
+            "DSA_TAG_DSA" => Some(LinkType::DsaTagDsa),
+            "DSA_TAG_EDSA" => Some(LinkType::DsaTagEdsa),
+            "ELEE" => Some(LinkType::Elee),
+            "Z_WAVE_SERIAL" => Some(LinkType::ZWaveSerial),
+            "USB_2_0" => Some(LinkType::Usb20),
+            "ATSC_ALP" => Some(LinkType::AtscAlp),
+            "ETW" => Some(LinkType::Etw),
+            "ZBOSS_NCP" => Some(LinkType::ZbossNcp),
+            "USB_2_0_LOW_SPEED" => Some(LinkType::Usb20LowSpeed),
+            "USB_2_0_FULL_SPEED" => Some(LinkType::Usb20FullSpeed),
+            "USB_2_0_HIGH_SPEED" => Some(LinkType::Usb20HighSpeed),
+            "AUERSWALD_LOG" => Some(LinkType::AuerswaldLog),
+            "ZWAVE_TAP" => Some(LinkType::ZwaveTap),
+            "SILABS_DEBUG_CHANNEL" => Some(LinkType::SilabsDebugChannel),
+            "FIRA_UCI" => Some(LinkType::FiraUci),
+            "MDB" => Some(LinkType::Mdb),
+            "DECT_NR" => Some(LinkType::DectNr),
+            _ => None
+        }
+    }
+}
+
+impl From<u32> for LinkType {
+    fn from(value: u32) -> LinkType {
+        LinkType::new(value)
+    }
+}
+
+impl From<LinkType> for u32 {
+    fn from(link_type: LinkType) -> u32 {
+        link_type.value()
+    }
+}
+
+impl std::fmt::Display for LinkType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match (self.name(), *self) {
+            (Some(name), _) => write!(f, "{}", name),
+            (None, LinkType::User(n)) => write!(f, "USER{}", n),
+            (None, _) => write!(f, "UNKNOWN({})", self.value())
+        }
+    }
+}
+
+impl std::str::FromStr for LinkType {
+    type Err = errors::Error;
+
+    ///
+    /// Parse a registry name, e.g. ETHERNET or LINKTYPE_LINUX_SLL, or a LINKTYPE_ value
+    ///
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        LinkType::from_name(s)
+            .or_else(|| s.parse::<u32>().ok().map(LinkType::new))
+            .ok_or_else(|| errors::Error::from(format!("Invalid link type {}", s)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn link_type_values() {
+        assert_eq!(LinkType::new(1), LinkType::Ethernet);
+        assert_eq!(LinkType::new(113), LinkType::LinuxSll);
+        assert_eq!(LinkType::new(150), LinkType::User(3));
+        assert_eq!(LinkType::new(65000), LinkType::Unknown(65000));
+
+        for value in 0..400u32 {
+            assert_eq!(LinkType::new(value).value(), value);
+            assert_eq!(u32::from(LinkType::from(value)), value);
+        }
+    }
+
+    #[test]
+    fn link_type_names() {
+        assert_eq!(LinkType::Ethernet.to_string(), "ETHERNET");
+        assert_eq!(LinkType::Ieee80211Radiotap.to_string(), "IEEE802_11_RADIOTAP");
+        assert_eq!(LinkType::User(15).to_string(), "USER15");
+        assert_eq!(LinkType::Unknown(65000).to_string(), "UNKNOWN(65000)");
+        assert_eq!(LinkType::LinuxSll.description(), "Linux cooked");
+
+        assert_eq!("ETHERNET".parse::<LinkType>().expect("Could not parse"), LinkType::Ethernet);
+        assert_eq!("linktype_linux_sll2".parse::<LinkType>().expect("Could not parse"), LinkType::LinuxSll2);
+        assert_eq!("USER0".parse::<LinkType>().expect("Could not parse"), LinkType::User(0));
+        assert_eq!("228".parse::<LinkType>().expect("Could not parse"), LinkType::Ipv4);
+        assert!("USER16".parse::<LinkType>().is_err());
+        assert!("TOKEN_BUS".parse::<LinkType>().is_err());
+
+        for value in 0..400u32 {
+            let link_type = LinkType::new(value);
+            if let LinkType::Unknown(_) = link_type {
+                continue;
+            }
+            assert_eq!(LinkType::from_name(&link_type.to_string()), Some(link_type));
+        }
+    }
+}
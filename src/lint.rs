@@ -0,0 +1,231 @@
+use super::prelude::*;
+use super::scan::{self, LengthInconsistency};
+
+use std;
+
+///
+/// Gap between consecutive records beyond which the capture is flagged, unless configured otherwise
+///
+pub const DEFAULT_MAX_GAP: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+///
+/// Problem found in a capture, along with the index of the record it was found in
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Finding {
+    ///
+    /// Captured length larger than the original length, larger than the snap length, or running
+    /// past the end of the capture
+    ///
+    Length(LengthInconsistency),
+    ///
+    /// Record with no captured bytes
+    ///
+    ZeroLength { index: usize },
+    ///
+    /// Record timestamped before the record preceding it
+    ///
+    NonMonotonicTimestamp { index: usize, timestamp: std::time::SystemTime, previous: std::time::SystemTime },
+    ///
+    /// Record timestamped more than the maximum gap after the record preceding it
+    ///
+    TimeGap { index: usize, gap: std::time::Duration }
+}
+
+impl Finding {
+    pub fn index(&self) -> usize {
+        match *self {
+            Finding::Length(LengthInconsistency::ExceedsOriginalLength { index, .. }) => index,
+            Finding::Length(LengthInconsistency::ExceedsSnapLength { index, .. }) => index,
+            Finding::Length(LengthInconsistency::TruncatedRecord { index, .. }) => index,
+            Finding::ZeroLength { index } => index,
+            Finding::NonMonotonicTimestamp { index, .. } => index,
+            Finding::TimeGap { index, .. } => index
+        }
+    }
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            Finding::Length(LengthInconsistency::ExceedsOriginalLength { index, actual_length, original_length }) => {
+                write!(f, "record {}: captured length {} exceeds original length {}", index, actual_length, original_length)
+            }
+            Finding::Length(LengthInconsistency::ExceedsSnapLength { index, actual_length, snap_length }) => {
+                write!(f, "record {}: captured length {} exceeds snap length {}", index, actual_length, snap_length)
+            }
+            Finding::Length(LengthInconsistency::TruncatedRecord { index, available, needed }) => {
+                write!(f, "record {}: truncated, {} of {} bytes available", index, available, needed)
+            }
+            Finding::ZeroLength { index } => write!(f, "record {}: zero length", index),
+            Finding::NonMonotonicTimestamp { index, timestamp, previous } => {
+                let behind = previous.duration_since(timestamp).unwrap_or_default();
+                write!(f, "record {}: timestamp {:?} before the previous record", index, behind)
+            }
+            Finding::TimeGap { index, gap } => write!(f, "record {}: {:?} after the previous record", index, gap)
+        }
+    }
+}
+
+///
+/// Findings of linting a capture, in the order of the records they were found in
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LintReport {
+    records: usize,
+    findings: std::vec::Vec<Finding>
+}
+
+impl LintReport {
+    pub fn records(&self) -> usize { self.records }
+    pub fn findings(&self) -> &std::vec::Vec<Finding> { &self.findings }
+
+    pub fn is_clean(&self) -> bool {
+        self.findings.is_empty()
+    }
+}
+
+///
+/// Sanity checks over the record headers of a capture, so bad captures can be set aside with
+/// specific reasons before any records are parsed
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Linter {
+    max_gap: std::time::Duration
+}
+
+impl Default for Linter {
+    fn default() -> Linter {
+        Linter::with_max_gap(DEFAULT_MAX_GAP)
+    }
+}
+
+impl Linter {
+    pub fn with_max_gap(max_gap: std::time::Duration) -> Linter {
+        Linter {
+            max_gap: max_gap
+        }
+    }
+
+    ///
+    /// Lint a libpcap capture (including global header). Fails only when the global header or a
+    /// record header can't be parsed.
+    ///
+    pub fn lint(&self, input: &[u8]) -> Result<LintReport, errors::Error> {
+        let mut report = LintReport::default();
+        let mut previous: Option<std::time::SystemTime> = None;
+
+        let (_, inconsistencies) = scan::walk_headers(input, |index, record_header| {
+            let timestamp = *record_header.timestamp();
+            if record_header.actual_length() == 0 {
+                report.findings.push(Finding::ZeroLength { index: index });
+            }
+            match previous.map(|p| timestamp.duration_since(p)) {
+                Some(Err(_)) => report.findings.push(Finding::NonMonotonicTimestamp {
+                    index: index,
+                    timestamp: timestamp,
+                    previous: previous.unwrap_or(timestamp)
+                }),
+                Some(Ok(gap)) if gap > self.max_gap => report.findings.push(Finding::TimeGap {
+                    index: index,
+                    gap: gap
+                }),
+                _ => {}
+            }
+            previous = Some(timestamp);
+            report.records += 1;
+        })?;
+
+        report.findings.extend(inconsistencies.into_iter().map(Finding::Length));
+        report.findings.sort_by_key(|f| f.index());
+        Ok(report)
+    }
+}
+
+///
+/// Lint a libpcap capture with the default maximum gap between records
+///
+pub fn lint(input: &[u8]) -> Result<LintReport, errors::Error> {
+    Linter::default().lint(input)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    const RAW_DATA: &'static [u8] = &[
+        0x4du8, 0x3c, 0x2b, 0x1au8, //magic number
+        0x00u8, 0x04u8, //version major, 4
+        0x00u8, 0x02u8, //version minor, 2
+        0x00u8, 0x00u8, 0x00u8, 0x00u8, //zone, 0
+        0x00u8, 0x00u8, 0x00u8, 0x04u8, //sig figs, 4
+        0x00u8, 0x00u8, 0x00u8, 0x04u8, //snap length, 4
+        0x00u8, 0x00u8, 0x00u8, 0x01u8, //network, 1
+        //record
+        0x00u8, 0x00u8, 0x00u8, 0x03u8, //seconds, 3
+        0x00u8, 0x00u8, 0x00u8, 0x00u8, //microseconds, 0
+        0x00u8, 0x00u8, 0x00u8, 0x02u8, //actual length, 2
+        0x00u8, 0x00u8, 0x00u8, 0x04u8, //original length, 4
+        0x01u8, 0x02u8, //payload
+        //record
+        0x00u8, 0x00u8, 0x00u8, 0x01u8, //seconds, 1
+        0x00u8, 0x00u8, 0x00u8, 0x00u8, //microseconds, 0
+        0x00u8, 0x00u8, 0x00u8, 0x00u8, //actual length, 0
+        0x00u8, 0x00u8, 0x00u8, 0x00u8, //original length, 0
+        //record
+        0x00u8, 0x01u8, 0x00u8, 0x00u8, //seconds, 65536
+        0x00u8, 0x00u8, 0x00u8, 0x00u8, //microseconds, 0
+        0x00u8, 0x00u8, 0x00u8, 0x06u8, //actual length, 6
+        0x00u8, 0x00u8, 0x00u8, 0x05u8, //original length, 5
+        0x01u8, 0x02u8, 0x03u8, 0x04u8, 0x05u8, 0x06u8 //payload
+    ];
+
+    #[test]
+    fn lint_capture() {
+        let _ = env_logger::try_init();
+
+        let report = lint(RAW_DATA).expect("Failed to lint");
+
+        assert_eq!(report.records(), 3);
+        assert!(!report.is_clean());
+        assert_eq!(report.findings(), &vec![
+            Finding::ZeroLength { index: 1 },
+            Finding::NonMonotonicTimestamp {
+                index: 1,
+                timestamp: PcapRecord::convert_packet_time(1, 0),
+                previous: PcapRecord::convert_packet_time(3, 0)
+            },
+            Finding::TimeGap { index: 2, gap: std::time::Duration::from_secs(65535) },
+            Finding::Length(LengthInconsistency::ExceedsOriginalLength { index: 2, actual_length: 6, original_length: 5 }),
+            Finding::Length(LengthInconsistency::ExceedsSnapLength { index: 2, actual_length: 6, snap_length: 4 })
+        ]);
+        assert_eq!(format!("{}", report.findings()[1]), "record 1: timestamp 2s before the previous record");
+
+        let report = Linter::with_max_gap(std::time::Duration::from_secs(100000)).lint(RAW_DATA).expect("Failed to lint");
+        assert_eq!(report.findings().len(), 4);
+
+        //no snap length
+        let mut data = RAW_DATA.to_vec();
+        data[16..20].copy_from_slice(&[0x00u8, 0x00u8, 0x00u8, 0x00u8]);
+        let report = lint(&data).expect("Failed to lint");
+        assert!(report.findings().iter().all(|f| !matches!(*f, Finding::Length(LengthInconsistency::ExceedsSnapLength { .. }))));
+        assert_eq!(report.findings().len(), 4);
+    }
+
+    #[test]
+    fn lint_clean_capture() {
+        let _ = env_logger::try_init();
+
+        let report = lint(&RAW_DATA[..24 + 18]).expect("Failed to lint");
+
+        assert_eq!(report.records(), 1);
+        assert!(report.is_clean());
+
+        let report = lint(&RAW_DATA[..24 + 18 + 8]).expect("Failed to lint");
+        assert_eq!(report.findings(), &vec![
+            Finding::Length(LengthInconsistency::TruncatedRecord { index: 1, available: 8, needed: 16 })
+        ]);
+    }
+}
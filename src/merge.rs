@@ -0,0 +1,359 @@
+use super::prelude::*;
+use super::global_header::{GlobalHeader, TimestampPrecision};
+use super::reader::CaptureReader;
+
+use std;
+use std::collections::BinaryHeap;
+
+///
+/// Clock correction applied to every record timestamp of a merge input, used when captures were
+/// taken on hosts whose clocks disagree
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ClockOffset {
+    None,
+    Ahead(std::time::Duration),
+    Behind(std::time::Duration)
+}
+
+impl ClockOffset {
+    ///
+    /// Correct a timestamp taken on a clock with this offset. A clock that is ahead has the offset
+    /// removed, a clock that is behind has it added. Timestamps a capture can't hold, before the
+    /// epoch or past the range of system time once corrected, fail.
+    ///
+    pub fn correct(&self, timestamp: std::time::SystemTime) -> errors::Result<std::time::SystemTime> {
+        let corrected = match *self {
+            ClockOffset::None => return Ok(timestamp),
+            ClockOffset::Ahead(d) => timestamp.checked_sub(d).filter(|corrected| *corrected >= std::time::UNIX_EPOCH),
+            ClockOffset::Behind(d) => timestamp.checked_add(d)
+        };
+        corrected.ok_or_else(|| format!("Timestamp {:?} is out of range once corrected by {:?}", timestamp, self).into())
+    }
+}
+
+///
+/// A source of records taking part in a merge, along with the clock correction for that source
+///
+pub struct MergeInput<I> {
+    records: I,
+    offset: ClockOffset
+}
+
+impl<I> MergeInput<I> where I: Iterator {
+    pub fn new<T>(records: T) -> MergeInput<I> where T: IntoIterator<Item=I::Item, IntoIter=I> {
+        MergeInput::with_offset(records, ClockOffset::None)
+    }
+
+    pub fn with_offset<T>(records: T, offset: ClockOffset) -> MergeInput<I> where T: IntoIterator<Item=I::Item, IntoIter=I> {
+        MergeInput {
+            records: records.into_iter(),
+            offset: offset
+        }
+    }
+
+    pub fn offset(&self) -> ClockOffset { self.offset }
+}
+
+fn corrected(offset: ClockOffset, mut record: PcapRecord) -> errors::Result<PcapRecord> {
+    let ts = offset.correct(*record.timestamp())?;
+    record.set_timestamp(ts);
+    Ok(record)
+}
+
+///
+/// Next record available from an input, ordered so the earliest timestamp (and lowest input index
+/// on ties) is at the top of the heap
+///
+struct Pending {
+    input: usize,
+    record: PcapRecord
+}
+
+impl PartialEq for Pending {
+    fn eq(&self, other: &Pending) -> bool {
+        self.cmp(other) == std::cmp::Ordering::Equal
+    }
+}
+
+impl Eq for Pending {}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Pending) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Pending {
+    fn cmp(&self, other: &Pending) -> std::cmp::Ordering {
+        other.record.timestamp().cmp(self.record.timestamp())
+            .then_with(|| other.input.cmp(&self.input))
+    }
+}
+
+///
+/// Iterator yielding the records of several inputs as a single stream ordered by timestamp, as a
+/// `TryMerge` of inputs that can't fail to read. Each input is expected to already be in timestamp
+/// order, as a capture file normally is.
+///
+pub type Merge<I> = TryMerge<std::iter::Map<I, fn(PcapRecord) -> errors::Result<PcapRecord>>>;
+
+///
+/// Merge several record sources into a single stream ordered by (corrected) timestamp, using a
+/// k-way merge so only one record per input is held at a time. A record whose clock correction
+/// fails ends the merge with its error.
+///
+pub fn merge<I>(inputs: std::vec::Vec<MergeInput<I>>) -> Merge<I> where I: Iterator<Item=PcapRecord> {
+    try_merge(inputs.into_iter()
+        .map(|input| MergeInput {
+            records: input.records.map(Ok as fn(PcapRecord) -> errors::Result<PcapRecord>),
+            offset: input.offset
+        })
+        .collect())
+}
+
+///
+/// Iterator yielding the records of several fallible inputs, e.g. capture readers, as a single
+/// stream ordered by timestamp. The first error, read from any input or correcting the clock of
+/// a record, is yielded in place of the remaining records, ending the merge.
+///
+pub struct TryMerge<I> {
+    inputs: std::vec::Vec<MergeInput<I>>,
+    pending: BinaryHeap<Pending>,
+    error: Option<errors::Error>
+}
+
+impl<I> TryMerge<I> where I: Iterator<Item=errors::Result<PcapRecord>> {
+    fn fill(&mut self, input: usize) {
+        let next = {
+            let source = &mut self.inputs[input];
+            let offset = source.offset;
+            source.records.next().map(|r| r.and_then(|r| corrected(offset, r)))
+        };
+        match next {
+            Some(Ok(record)) => {
+                self.pending.push(Pending {
+                    input: input,
+                    record: record
+                });
+            }
+            Some(Err(e)) if self.error.is_none() => self.error = Some(e),
+            _ => {}
+        }
+    }
+}
+
+impl<I> Iterator for TryMerge<I> where I: Iterator<Item=errors::Result<PcapRecord>> {
+    type Item = errors::Result<PcapRecord>;
+
+    fn next(&mut self) -> Option<errors::Result<PcapRecord>> {
+        if let Some(e) = self.error.take() {
+            self.pending.clear();
+            return Some(Err(e))
+        }
+        self.pending.pop().map(|p| {
+            self.fill(p.input);
+            Ok(p.record)
+        })
+    }
+}
+
+///
+/// Merge several fallible record sources into a single stream ordered by (corrected) timestamp
+///
+pub fn try_merge<I>(inputs: std::vec::Vec<MergeInput<I>>) -> TryMerge<I> where I: Iterator<Item=errors::Result<PcapRecord>> {
+    let mut merge = TryMerge {
+        pending: BinaryHeap::with_capacity(inputs.len()),
+        inputs: inputs,
+        error: None
+    };
+
+    for input in 0..merge.inputs.len() {
+        merge.fill(input);
+    }
+
+    merge
+}
+
+///
+/// Global header for the merge of captures with `headers`, as mergecap writes it: native byte
+/// order, the largest snap length, and nanosecond timestamps if any capture has them. Records are
+/// decoded from each capture in its own byte order, so captures only need to agree on link type.
+///
+pub fn merged_header(headers: &[&GlobalHeader]) -> errors::Result<GlobalHeader> {
+    let first = match headers.first() {
+        Some(first) => first,
+        None => return Err("No captures to merge".into())
+    };
+
+    let mut snap_length = 0;
+    let mut precision = TimestampPrecision::Microsecond;
+    for header in headers {
+        if header.link_type() != first.link_type() {
+            return Err(format!("Cannot merge captures of link types {:?} and {:?}", first.link_type(), header.link_type()).into())
+        }
+        snap_length = std::cmp::max(snap_length, header.snap_length());
+        if header.precision() == TimestampPrecision::Nanosecond {
+            precision = TimestampPrecision::Nanosecond;
+        }
+    }
+
+    Ok(GlobalHeader::new(first.link_type(), snap_length).with_precision(precision))
+}
+
+///
+/// Merge parsed captures, e.g. from `CaptureParser::parse_path`, into the header of the merged
+/// capture and its records in timestamp order
+///
+pub fn merge_captures(
+    captures: std::vec::Vec<(GlobalHeader, std::vec::Vec<PcapRecord>)>
+) -> errors::Result<(GlobalHeader, Merge<std::vec::IntoIter<PcapRecord>>)> {
+    let header = merged_header(&captures.iter().map(|c| &c.0).collect::<std::vec::Vec<_>>())?;
+    let inputs = captures.into_iter()
+        .map(|(_, records)| MergeInput::new(records))
+        .collect();
+    Ok( (header, merge(inputs)) )
+}
+
+///
+/// Read the global header of each of `readers`, returning the header of the merged capture and
+/// the records of all of them in timestamp order, holding one record per reader at a time
+///
+pub fn merge_readers<R: std::io::Read>(readers: std::vec::Vec<R>) -> errors::Result<(GlobalHeader, TryMerge<CaptureReader<R>>)> {
+    let readers = readers.into_iter()
+        .map(CaptureReader::new)
+        .collect::<errors::Result<std::vec::Vec<_>>>()?;
+    let header = merged_header(&readers.iter().map(|r| r.header()).collect::<std::vec::Vec<_>>())?;
+    let inputs = readers.into_iter()
+        .map(MergeInput::new)
+        .collect();
+    Ok( (header, try_merge(inputs)) )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::link_type::LinkType;
+    use super::super::nom::Endianness;
+
+    fn record_at(seconds: u32, tag: u8) -> PcapRecord {
+        PcapRecord::new(
+            PcapRecord::convert_packet_time(seconds, 0),
+            1,
+            1,
+            vec![tag]
+        )
+    }
+
+    fn seconds(record: &PcapRecord) -> u64 {
+        record.timestamp().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs()
+    }
+
+    #[test]
+    fn merge_in_timestamp_order() {
+        let first = vec![record_at(1, 0), record_at(4, 0), record_at(5, 0)];
+        let second = vec![record_at(2, 1), record_at(3, 1), record_at(6, 1)];
+
+        let merged = merge(vec![MergeInput::new(first), MergeInput::new(second)]).collect::<errors::Result<std::vec::Vec<_>>>().expect("Could not merge");
+
+        assert_eq!(merged.iter().map(seconds).collect::<std::vec::Vec<_>>(), vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(merged.iter().map(|r| r.payload()[0]).collect::<std::vec::Vec<_>>(), vec![0, 1, 1, 0, 0, 1]);
+    }
+
+    #[test]
+    fn merge_ties_prefer_first_input() {
+        let first = vec![record_at(1, 0)];
+        let second = vec![record_at(1, 1)];
+
+        let merged = merge(vec![MergeInput::new(first), MergeInput::new(second)]).collect::<errors::Result<std::vec::Vec<_>>>().expect("Could not merge");
+
+        assert_eq!(merged.iter().map(|r| r.payload()[0]).collect::<std::vec::Vec<_>>(), vec![0, 1]);
+    }
+
+    #[test]
+    fn merge_with_clock_offset() {
+        let first = vec![record_at(10, 0), record_at(12, 0)];
+        let second = vec![record_at(1, 1), record_at(3, 1)];
+
+        let merged = merge(vec![
+            MergeInput::new(first),
+            MergeInput::with_offset(second, ClockOffset::Behind(std::time::Duration::from_secs(10)))
+        ]).collect::<errors::Result<std::vec::Vec<_>>>().expect("Could not merge");
+
+        assert_eq!(merged.iter().map(seconds).collect::<std::vec::Vec<_>>(), vec![10, 11, 12, 13]);
+        assert_eq!(merged.iter().map(|r| r.payload()[0]).collect::<std::vec::Vec<_>>(), vec![0, 1, 0, 1]);
+
+        let merged = merge(vec![
+            MergeInput::new(vec![record_at(1, 0), record_at(6, 0)]),
+            MergeInput::with_offset(vec![record_at(15, 1), record_at(17, 1)], ClockOffset::Ahead(std::time::Duration::from_secs(10)))
+        ]).collect::<errors::Result<std::vec::Vec<_>>>().expect("Could not merge");
+        assert_eq!(merged.iter().map(seconds).collect::<std::vec::Vec<_>>(), vec![1, 5, 6, 7]);
+
+        //a record before the epoch once corrected ends the merge
+        let merged = merge(vec![
+            MergeInput::with_offset(vec![record_at(20, 0), record_at(5, 0), record_at(30, 0)], ClockOffset::Ahead(std::time::Duration::from_secs(10)))
+        ]).collect::<std::vec::Vec<_>>();
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].as_ref().map(seconds).ok(), Some(10));
+        assert!(merged[1].is_err());
+
+        //as does one past the range of system time
+        let merged = merge(vec![
+            MergeInput::with_offset(vec![record_at(1, 0)], ClockOffset::Behind(std::time::Duration::new(u64::MAX, 0)))
+        ]).collect::<std::vec::Vec<_>>();
+        assert_eq!(merged.len(), 1);
+        assert!(merged[0].is_err());
+    }
+
+    fn capture(header: GlobalHeader, records: &[PcapRecord]) -> std::vec::Vec<u8> {
+        let mut bytes = header.to_bytes();
+        for record in records {
+            bytes.extend_from_slice(&record.to_bytes_with_precision(header.endianness(), header.precision()));
+        }
+        bytes
+    }
+
+    #[test]
+    fn merge_parsed_captures() {
+        let first = GlobalHeader::new(LinkType::Ethernet, 1500).with_endianness(Endianness::Big);
+        let second = GlobalHeader::new(LinkType::Ethernet, 65535).with_endianness(Endianness::Little)
+            .with_precision(TimestampPrecision::Nanosecond);
+
+        let (header, merged) = merge_captures(vec![
+            (first.clone(), vec![record_at(1, 0), record_at(3, 0)]),
+            (second.clone(), vec![record_at(2, 1)])
+        ]).expect("Could not merge");
+        assert_eq!(header.snap_length(), 65535);
+        assert_eq!(header.precision(), TimestampPrecision::Nanosecond);
+        assert_eq!(merged.map(|r| r.expect("Could not merge").payload()[0]).collect::<std::vec::Vec<_>>(), vec![0, 1, 0]);
+
+        let other = GlobalHeader::new(LinkType::Raw, 65535);
+        assert!(merge_captures(vec![(first, vec![]), (other, vec![])]).is_err());
+        assert!(merge_captures(vec![]).is_err());
+    }
+
+    #[test]
+    fn merge_capture_readers() {
+        let first = capture(
+            GlobalHeader::new(LinkType::Ethernet, 1500).with_endianness(Endianness::Big),
+            &[record_at(1, 0), record_at(4, 0)]
+        );
+        let second = capture(
+            GlobalHeader::new(LinkType::Ethernet, 65535).with_endianness(Endianness::Little),
+            &[record_at(2, 1), record_at(3, 1)]
+        );
+
+        let (header, merged) = merge_readers(vec![&first[..], &second[..]]).expect("Could not merge");
+        assert_eq!(header.snap_length(), 65535);
+        let merged = merged.collect::<errors::Result<std::vec::Vec<_>>>().expect("Could not read");
+        assert_eq!(merged.iter().map(seconds).collect::<std::vec::Vec<_>>(), vec![1, 2, 3, 4]);
+        assert_eq!(merged.iter().map(|r| r.payload()[0]).collect::<std::vec::Vec<_>>(), vec![0, 1, 1, 0]);
+
+        let failing = vec![Ok(record_at(1, 0)), Err("Could not read".into()), Ok(record_at(3, 0))];
+        let merged = try_merge(vec![MergeInput::new(failing), MergeInput::new(vec![Ok(record_at(2, 1))])])
+            .collect::<std::vec::Vec<_>>();
+        assert_eq!(merged.len(), 2);
+        assert!(merged[0].is_ok());
+        assert!(merged[1].is_err());
+    }
+}
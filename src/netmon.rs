@@ -0,0 +1,273 @@
+use super::prelude::*;
+use super::global_header::GlobalHeader;
+use super::link_type::LinkType;
+
+use self::nom::*;
+
+use std;
+
+///
+/// Identification pattern starting a NetMon 2.x capture
+///
+pub const NETMON_MAGIC: [u8; 4] = [0x47u8, 0x4Du8, 0x42u8, 0x55u8];
+pub const NETMON_HEADER_LENGTH: usize = 64;
+pub const NETMON_FRAME_HEADER_LENGTH: usize = 16;
+
+const VERSION_MAJOR: u8 = 2;
+///
+/// NetMon headers don't record a snap length, so captures are given tcpdump's maximum
+///
+const SNAP_LENGTH: u32 = 262144;
+
+const MEDIA_ETHERNET: u16 = 1;
+const MEDIA_TOKEN_RING: u16 = 2;
+const MEDIA_FDDI: u16 = 3;
+
+fn netmon_error<'a, T>(input: &'a [u8], code: errors::ParseCode) -> IResult<&'a [u8], T> {
+    Err(Err::Error(error_position!(input, ErrorKind::Custom(code as u32))))
+}
+
+///
+/// Whether `input` starts with a NetMon 2.x file header rather than a libpcap global header
+///
+pub fn is_netmon(input: &[u8]) -> bool {
+    input.starts_with(&NETMON_MAGIC)
+}
+
+///
+/// Days from 1970-01-01 to the date in the proleptic Gregorian calendar
+///
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = (if year >= 0 { year } else { year - 399 }) / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+///
+/// File header of a Microsoft Network Monitor 2.x capture, as written by NetMon and Message
+/// Analyzer. Every field of a NetMon capture is little endian, and frames are located through a
+/// table of their offsets rather than read in sequence.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NetmonHeader {
+    version_major: u8,
+    version_minor: u8,
+    network: u16,
+    start: std::time::SystemTime,
+    frame_table_offset: u32,
+    frame_table_length: u32
+}
+
+impl NetmonHeader {
+    pub fn version_major(&self) -> u8 { self.version_major }
+    pub fn version_minor(&self) -> u8 { self.version_minor }
+
+    ///
+    /// NetMon media type of the capture, which differs from the libpcap link types
+    ///
+    pub fn network(&self) -> u16 { self.network }
+
+    ///
+    /// Time the capture started, which frame timestamps are offsets from
+    ///
+    pub fn start(&self) -> &std::time::SystemTime { &self.start }
+    pub fn frame_table_offset(&self) -> u32 { self.frame_table_offset }
+
+    ///
+    /// Number of frames in the frame table
+    ///
+    pub fn frames(&self) -> usize { self.frame_table_length as usize / 4 }
+
+    ///
+    /// Libpcap link type of the media type. Media types without a libpcap equivalent are kept as
+    /// `Unknown`.
+    ///
+    pub fn link_type(&self) -> LinkType {
+        match self.network {
+            MEDIA_ETHERNET => LinkType::Ethernet,
+            MEDIA_TOKEN_RING => LinkType::Ieee8025,
+            MEDIA_FDDI => LinkType::Fddi,
+            other => LinkType::Unknown(other as u32)
+        }
+    }
+
+    ///
+    /// Libpcap global header equivalent to this header, little endian with microsecond timestamps
+    ///
+    pub fn to_global_header(&self) -> GlobalHeader {
+        GlobalHeader::new(self.link_type(), SNAP_LENGTH).with_endianness(Endianness::Little)
+    }
+
+    pub fn parse(input: &[u8]) -> IResult<&[u8], NetmonHeader> {
+        let (rem, (_, version_minor, version_major, network)) = tuple!(input, tag!(NETMON_MAGIC), le_u8, le_u8, le_u16)?;
+        if version_major != VERSION_MAJOR {
+            return netmon_error(input, errors::ParseCode::NetmonVersion)
+        }
+        let (rem, (year, month, _day_of_week, day, hour, minute, second, millisecond)) =
+            tuple!(rem, le_u16, le_u16, le_u16, le_u16, le_u16, le_u16, le_u16, le_u16)?;
+        let (rem, (frame_table_offset, frame_table_length, _)) =
+            tuple!(rem, le_u32, le_u32, take!(NETMON_HEADER_LENGTH - 32))?;
+
+        //start time is a windows SYSTEMTIME in utc
+        let days = days_from_civil(year as i64, month as i64, day as i64);
+        let seconds = days * 86400 + hour as i64 * 3600 + minute as i64 * 60 + second as i64;
+        let start = std::time::UNIX_EPOCH
+            + std::time::Duration::from_secs(std::cmp::max(seconds, 0) as u64)
+            + std::time::Duration::from_millis(millisecond as u64);
+        debug!("NetMon header version {}.{}, with media type {}", version_major, version_minor, network);
+
+        Ok( (rem, NetmonHeader {
+            version_major: version_major,
+            version_minor: version_minor,
+            network: network,
+            start: start,
+            frame_table_offset: frame_table_offset,
+            frame_table_length: frame_table_length
+        }) )
+    }
+}
+
+///
+/// Parse a NetMon 2.x frame, timestamped relative to `start`. The trailer NetMon 2.1 and later
+/// write after the frame data is left unparsed.
+///
+pub fn parse_frame(input: &[u8], start: std::time::SystemTime) -> IResult<&[u8], PcapRecord> {
+    let (rem, (delta, original_length, included_length)) = tuple!(input, le_u64, le_u32, le_u32)?;
+    let (rem, payload) = take!(rem, included_length)?;
+
+    Ok( (rem, PcapRecord::new(
+        start + std::time::Duration::from_micros(delta),
+        included_length,
+        original_length,
+        payload.to_vec()
+    )) )
+}
+
+///
+/// Parse a NetMon 2.x capture in memory into the libpcap global header and records equivalent to
+/// it, reading frames in the order of the frame table. As frames are located through the table at
+/// the end of the capture, a truncated capture fails to parse rather than yielding its first frames.
+///
+pub fn parse_file(input: &[u8]) -> IResult<&[u8], (GlobalHeader, std::vec::Vec<PcapRecord>)> {
+    let (_, header) = NetmonHeader::parse(input)?;
+    let global_header = header.to_global_header();
+
+    let table_offset = header.frame_table_offset as usize;
+    if table_offset > input.len() {
+        return netmon_error(input, errors::ParseCode::NetmonFrameOffset)
+    }
+    let (_, offsets) = count!(&input[table_offset..], le_u32, header.frames())?;
+
+    let mut records = std::vec::Vec::with_capacity(offsets.len());
+    for offset in offsets {
+        layer_span!("record", index = records.len(), offset = offset);
+
+        let offset = offset as usize;
+        if offset < NETMON_HEADER_LENGTH || offset > input.len() {
+            return netmon_error(input, errors::ParseCode::NetmonFrameOffset)
+        }
+        let (_, record) = parse_frame(&input[offset..], header.start)?;
+        records.push(record.with_header(&global_header));
+    }
+
+    Ok( (&input[input.len()..], (global_header, records)) )
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+    use super::super::CaptureParser;
+
+    const RAW_DATA: &'static [u8] = &[
+        0x47u8, 0x4Du8, 0x42u8, 0x55u8, //GMBU
+        0x01u8, 0x02u8, //version, 2.1
+        0x01u8, 0x00u8, //network, ethernet
+        0xE2u8, 0x07u8, 0x06u8, 0x00u8, //year 2018, month 6
+        0x05u8, 0x00u8, 0x01u8, 0x00u8, //friday, day 1
+        0x10u8, 0x00u8, 0x01u8, 0x00u8, //16:01
+        0x27u8, 0x00u8, 0x98u8, 0x00u8, //39 seconds, 152 milliseconds
+        0x6Au8, 0x00u8, 0x00u8, 0x00u8, //frame table offset, 106
+        0x08u8, 0x00u8, 0x00u8, 0x00u8, //frame table length, 8
+        0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, //user data
+        0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, //comment data
+        0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, //process info
+        0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, //network info
+        //frame at 64
+        0x35u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, //delta, 53 microseconds
+        0x06u8, 0x00u8, 0x00u8, 0x00u8, //original length, 6
+        0x04u8, 0x00u8, 0x00u8, 0x00u8, //included length, 4
+        0x01u8, 0x02u8, 0x03u8, 0x04u8, //data
+        0x01u8, 0x00u8, //trailer, network
+        //frame at 86
+        0x40u8, 0x42u8, 0x0Fu8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, //delta, 1 second
+        0x02u8, 0x00u8, 0x00u8, 0x00u8, //original length, 2
+        0x02u8, 0x00u8, 0x00u8, 0x00u8, //included length, 2
+        0x05u8, 0x06u8, //data
+        0x01u8, 0x00u8, //trailer, network
+        //frame table at 106
+        0x40u8, 0x00u8, 0x00u8, 0x00u8, //64
+        0x56u8, 0x00u8, 0x00u8, 0x00u8 //86
+    ];
+
+    #[test]
+    fn parse_netmon() {
+        let _ = env_logger::try_init();
+
+        assert!(is_netmon(RAW_DATA));
+
+        let (_, header) = NetmonHeader::parse(RAW_DATA).expect("Could not parse header");
+        assert_eq!(header.version_major(), 2);
+        assert_eq!(header.version_minor(), 1);
+        assert_eq!(header.frames(), 2);
+        assert_eq!(*header.start(), PcapRecord::convert_packet_time(1527868899, 152000));
+
+        let (_, (global_header, records)) = parse_file(RAW_DATA).expect("Could not parse");
+        assert_eq!(global_header.link_type(), LinkType::Ethernet);
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(*records[0].timestamp(), PcapRecord::convert_packet_time(1527868899, 152053));
+        assert_eq!(records[0].actual_length(), 4);
+        assert_eq!(records[0].original_length(), 6);
+        assert_eq!(records[0].payload(), &vec![0x01u8, 0x02u8, 0x03u8, 0x04u8]);
+        assert_eq!(*records[1].timestamp(), PcapRecord::convert_packet_time(1527868900, 152000));
+        assert_eq!(records[1].payload(), &vec![0x05u8, 0x06u8]);
+
+        //records take the link type of the media type
+        let mut token_ring = RAW_DATA.to_vec();
+        token_ring[6] = 0x02u8;
+        let (_, (_, records)) = parse_file(&token_ring).expect("Could not parse");
+        assert!(records.iter().all(|r| r.link_type() == LinkType::Ieee8025 && r.interface_id() == 0));
+    }
+
+    #[test]
+    fn detect_netmon() {
+        let _ = env_logger::try_init();
+
+        let (header, records) = CaptureParser::parse_reader(RAW_DATA).expect("Could not parse");
+        assert_eq!(header.link_type(), LinkType::Ethernet);
+        assert_eq!(records.len(), 2);
+    }
+
+    #[test]
+    fn parse_netmon_invalid() {
+        let _ = env_logger::try_init();
+
+        let mut bytes = RAW_DATA.to_vec();
+        bytes[5] = 0x01u8;
+        let e = CaptureParser::parse_reader(&bytes[..]).expect_err("Parsed version 1");
+        assert!(format!("{}", e).contains("version 2"));
+
+        let mut bytes = RAW_DATA.to_vec();
+        bytes[110] = 0xFFu8;
+        let e = CaptureParser::parse_reader(&bytes[..]).expect_err("Parsed frame outside capture");
+        assert!(format!("{}", e).contains("frame offset"));
+
+        let length = RAW_DATA.len();
+        assert!(CaptureParser::parse_reader(&RAW_DATA[..length - 2]).is_err());
+    }
+}
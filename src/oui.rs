@@ -0,0 +1,121 @@
+///
+/// Organizationally unique identifiers of common network equipment, server, virtualization and
+/// consumer device vendors, sorted by OUI. A subset of the IEEE registry,
+/// https://standards-oui.ieee.org/oui/oui.txt, rather than the full registry of tens of thousands
+/// of assignments.
+///
+const VENDORS: &'static [(u32, &'static str)] = &[
+    (0x00000C, "Cisco"),
+    (0x0002B3, "Intel"),
+    (0x000393, "Apple"),
+    (0x0003BA, "Oracle"),
+    (0x00044B, "NVIDIA"),
+    (0x00045A, "Linksys"),
+    (0x000569, "VMware"),
+    (0x000585, "Juniper Networks"),
+    (0x0007E9, "Intel"),
+    (0x00089B, "QNAP Systems"),
+    (0x00090F, "Fortinet"),
+    (0x000B86, "Aruba Networks"),
+    (0x000C29, "VMware"),
+    (0x000C42, "MikroTik"),
+    (0x000C6E, "ASUSTek Computer"),
+    (0x000CF1, "Intel"),
+    (0x000D3A, "Microsoft"),
+    (0x000D93, "Apple"),
+    (0x000E0C, "Intel"),
+    (0x000E7F, "Hewlett Packard"),
+    (0x000FB5, "Netgear"),
+    (0x001018, "Broadcom"),
+    (0x001132, "Synology"),
+    (0x001320, "Intel"),
+    (0x00144F, "Oracle"),
+    (0x00146C, "Netgear"),
+    (0x00155D, "Microsoft"),
+    (0x00156D, "Ubiquiti"),
+    (0x00163E, "XenSource"),
+    (0x0016B6, "Cisco-Linksys"),
+    (0x0016CB, "Apple"),
+    (0x00178B, "Philips Lighting"),
+    (0x0017F2, "Apple"),
+    (0x00180A, "Cisco Meraki"),
+    (0x00188B, "Dell"),
+    (0x0019B9, "Dell"),
+    (0x001A11, "Google"),
+    (0x001AA0, "Dell"),
+    (0x001B17, "Palo Alto Networks"),
+    (0x001B21, "Intel"),
+    (0x001B54, "Cisco"),
+    (0x001B63, "Apple"),
+    (0x001C14, "VMware"),
+    (0x001C42, "Parallels"),
+    (0x001C73, "Arista Networks"),
+    (0x001CB3, "Apple"),
+    (0x001D0F, "TP-Link"),
+    (0x001E0B, "Hewlett Packard"),
+    (0x001E4F, "Dell"),
+    (0x001E67, "Intel"),
+    (0x001EC2, "Apple"),
+    (0x001EC9, "Dell"),
+    (0x001F12, "Juniper Networks"),
+    (0x001F29, "Hewlett Packard"),
+    (0x001FC6, "ASUSTek Computer"),
+    (0x00215A, "Hewlett Packard"),
+    (0x00219B, "Dell"),
+    (0x002219, "Dell"),
+    (0x0023DF, "Apple"),
+    (0x0024D7, "Intel"),
+    (0x002500, "Apple"),
+    (0x002590, "Supermicro"),
+    (0x0025B3, "Hewlett Packard"),
+    (0x0025B5, "Cisco"),
+    (0x0026B9, "Dell"),
+    (0x0026BB, "Apple"),
+    (0x003048, "Supermicro"),
+    (0x005056, "VMware"),
+    (0x0050F2, "Microsoft"),
+    (0x00A0C9, "Intel"),
+    (0x00E04C, "Realtek"),
+    (0x080027, "Oracle VirtualBox"),
+    (0x0CC47A, "Supermicro"),
+    (0x180373, "Dell"),
+    (0x245EBE, "QNAP Systems"),
+    (0x24A43C, "Ubiquiti"),
+    (0x3CD92B, "Hewlett Packard"),
+    (0x3CFDFE, "Intel"),
+    (0x4C5E0C, "MikroTik"),
+    (0x50C7BF, "TP-Link"),
+    (0x7483C2, "Ubiquiti"),
+    (0xAC1F6B, "Supermicro"),
+    (0xB827EB, "Raspberry Pi Foundation"),
+    (0xDCA632, "Raspberry Pi Trading"),
+    (0xF48E38, "Dell"),
+    (0xF4F5D8, "Google")
+];
+
+///
+/// Vendor the IEEE assigned `oui` to, for vendors in the embedded table
+///
+pub fn vendor(oui: u32) -> Option<&'static str> {
+    VENDORS.binary_search_by_key(&oui, |&(o, _)| o)
+        .ok()
+        .map(|index| VENDORS[index].1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vendors_sorted() {
+        assert!(VENDORS.windows(2).all(|w| w[0].0 < w[1].0));
+    }
+
+    #[test]
+    fn vendor_lookup() {
+        assert_eq!(vendor(0x005056), Some("VMware"));
+        assert_eq!(vendor(0x00000C), Some("Cisco"));
+        assert_eq!(vendor(0xF4F5D8), Some("Google"));
+        assert_eq!(vendor(0x123456), None);
+    }
+}
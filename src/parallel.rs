@@ -0,0 +1,174 @@
+use super::prelude::*;
+#[cfg(feature = "flows")]
+use super::analysis::defrag::Defragmenter;
+#[cfg(feature = "flows")]
+use super::flow::Flow;
+use super::global_header::GlobalHeader;
+use super::rayon::prelude::*;
+use super::record::RecordHeader;
+
+use std;
+
+///
+/// Fewest records handed to a thread at a time, so scheduling costs little next to parsing
+///
+const MIN_RECORDS_PER_TASK: usize = 1024;
+
+///
+/// Slices of `input`, a sequence of records without global header, each holding one complete record
+/// header and payload. Scanning stops at the first incomplete record.
+///
+fn record_slices<'a>(input: &'a [u8], header: &GlobalHeader) -> errors::Result<std::vec::Vec<&'a [u8]>> {
+    let mut slices = vec![];
+    let mut current = input;
+
+    while current.len() >= header.record_header_length() {
+        let (_, record_header) = RecordHeader::parse_for(current, header)
+            .map_err(|e| errors::Error::from_nom(e, errors::Layer::Record, current).at_record(slices.len()))?;
+        let length = header.record_header_length() + record_header.actual_length() as usize;
+        if length > current.len() {
+            break
+        }
+        slices.push(&current[..length]);
+        current = &current[length..];
+    }
+
+    if !current.is_empty() {
+        debug!("Ignoring {} bytes of incomplete record", current.len());
+    }
+    Ok(slices)
+}
+
+///
+/// Parse a libpcap capture in memory, finding record boundaries in one pass over the record headers,
+/// then parsing records across the rayon thread pool. Records are returned in capture order, and
+/// bytes of a trailing incomplete record are ignored.
+///
+pub fn parse_file(input: &[u8]) -> errors::Result<(GlobalHeader, std::vec::Vec<PcapRecord>)> {
+    let (rem, header) = GlobalHeader::parse(input)
+        .map_err(|e| errors::Error::from_nom(e, errors::Layer::Record, input))?;
+    let slices = record_slices(rem, &header)?;
+    debug!("Parsing {} records in parallel", slices.len());
+
+    let records = slices.par_iter()
+        .with_min_len(MIN_RECORDS_PER_TASK)
+        .map(|slice| {
+            PcapRecord::parse_for(slice, &header)
+                .map(|(_, record)| record)
+                .map_err(|e| errors::Error::from_nom(e, errors::Layer::Record, slice))
+        })
+        .collect::<errors::Result<std::vec::Vec<_>>>()?;
+
+    Ok( (header, records) )
+}
+
+///
+/// Convert records to flows across the rayon thread pool, in capture order. Ipv4 fragments are
+/// reassembled first in a single pass, so each datagram converts to a single flow as with
+/// `PcapRecord::convert_records`.
+///
+#[cfg(feature = "flows")]
+pub fn convert_records(records: std::vec::Vec<PcapRecord>, ignore_error: bool) -> errors::Result<std::vec::Vec<Flow>> {
+    let mut defragmenter = Defragmenter::default();
+    let datagrams = records.into_iter()
+        .enumerate()
+        .filter_map(|(idx, record)| defragmenter.defragment(record).map(|d| (idx, d)))
+        .collect::<std::vec::Vec<_>>();
+
+    if defragmenter.pending() > 0 {
+        let e = errors::Error::from_kind(errors::ErrorKind::FlowConversion(format!("{} datagrams missing fragments", defragmenter.pending())));
+        if ignore_error {
+            debug!("Failed to extract flow: {}", e);
+        } else {
+            return Err(e)
+        }
+    }
+
+    let converted = datagrams.into_par_iter()
+        .with_min_len(MIN_RECORDS_PER_TASK)
+        .map(|(idx, datagram)| {
+            let reassembled = datagram.is_reassembled();
+            Flow::try_from(datagram.record)
+                .map(|mut f| {
+                    f.reassembled = reassembled;
+                    f
+                })
+                .map_err(|e| e.at_record(idx))
+        })
+        .collect::<std::vec::Vec<_>>();
+
+    let mut flows = std::vec::Vec::with_capacity(converted.len());
+    for result in converted {
+        match result {
+            Ok(f) => flows.push(f),
+            Err(e) => {
+                if ignore_error {
+                    debug!("Failed to extract flow: {}", e);
+                } else {
+                    return Err(e)
+                }
+            }
+        }
+    }
+    Ok(flows)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+    use super::super::CaptureParser;
+    use super::super::link_type::LinkType;
+
+    fn capture(count: usize) -> std::vec::Vec<u8> {
+        let header = GlobalHeader::new(LinkType::Ethernet, 65535);
+        let mut bytes = header.to_bytes();
+        for idx in 0..count {
+            let payload = vec![idx as u8; idx % 64];
+            let record = PcapRecord::new(PcapRecord::convert_packet_time(idx as u32, 0), payload.len() as u32, payload.len() as u32, payload);
+            bytes.extend_from_slice(&record.to_bytes(header.endianness()));
+        }
+        bytes
+    }
+
+    #[test]
+    fn parse_parallel() {
+        let _ = env_logger::try_init();
+
+        let mut bytes = capture(1000);
+        let (_, (expected_header, expected)) = CaptureParser::parse_file(&bytes).expect("Could not parse");
+
+        let (header, records) = parse_file(&bytes).expect("Could not parse");
+        assert_eq!(header, expected_header);
+        assert_eq!(records, expected);
+
+        bytes.extend_from_slice(&[0x00u8; 10]);
+        let (_, records) = parse_file(&bytes).expect("Could not parse");
+        assert_eq!(records.len(), 1000);
+
+        assert!(parse_file(&bytes[..10]).is_err());
+    }
+
+    #[cfg(feature = "flows")]
+    #[test]
+    fn convert_parallel() {
+        use super::super::analysis::defrag::tests::fragments;
+        use super::super::analysis::reassembly::tests::tcp_record;
+
+        let _ = env_logger::try_init();
+
+        let mut records = vec![
+            tcp_record(1, true, 1, 0x02, &[]),
+            tcp_record(2, false, 1, 0x12, &[])
+        ];
+        records.extend(fragments(3, 7, &[0x01u8; 64], 24));
+        records.push(tcp_record(4, true, 2, 0x10, &[]));
+
+        let flows = convert_records(records.clone(), false).expect("Could not convert");
+        assert_eq!(flows.len(), 4);
+        assert_eq!(flows[1].destination.port, 50000);
+        assert!(flows[2].reassembled);
+        assert_eq!(flows.len(), PcapRecord::convert_records(records, false).expect("Could not convert").len());
+    }
+}
@@ -0,0 +1,1289 @@
+use super::prelude::*;
+#[cfg(feature = "flows")]
+use super::flow;
+use super::global_header::GlobalHeader;
+use super::layer2::{Layer2, Layer2FlowInfo};
+use super::layer2::ethernet::{Ethernet, EthernetTypeId, Layer3Id};
+use super::layer2::ieee80211::Ieee80211;
+use super::layer2::null::Null;
+use super::layer2::ppp::Ppp;
+use super::layer2::pppoe::Pppoe;
+use super::layer2::sll::Sll;
+use super::layer2::sll2::Sll2;
+use super::layer3::{InternetProtocolId, Layer3, Layer3FlowInfo};
+use super::layer3::arp::Arp;
+use super::layer3::ipv4::IPv4;
+#[cfg(feature = "ipv6")]
+use super::layer3::ipv6::IPv6;
+use super::layer4::{Layer4, Layer4FlowInfo};
+use super::layer4::tcp::Tcp;
+use super::layer4::udp::Udp;
+use super::layer7::wol::MagicPacket;
+use super::link_type::LinkType;
+#[cfg(feature = "tunnels")]
+use super::tunnel::{self, InnerProtocol, TunnelInfo};
+
+use std;
+
+///
+/// Most tunnels decapsulated from a single record, unless configured otherwise
+///
+#[cfg(feature = "tunnels")]
+pub const DEFAULT_MAX_TUNNEL_DEPTH: usize = 4;
+
+///
+/// Shortest ethernet frame without its frame check sequence, shorter payloads are padded up to it
+///
+const MINIMUM_FRAME_LENGTH: usize = 60;
+const TCP_MINIMUM_DATA_OFFSET: u8 = 5; //words
+const TCP_DATA_OFFSET_INDEX: usize = 12;
+const IPV4_TOTAL_LENGTH_INDEX: usize = 2;
+#[cfg(feature = "ipv6")]
+const IPV6_PAYLOAD_LENGTH_INDEX: usize = 4;
+#[cfg(feature = "ipv6")]
+const IPV6_HEADER_LENGTH: usize = 40;
+const UDP_LENGTH_INDEX: usize = 4;
+const MAXIMUM_VLAN_TAGS: usize = 2;
+
+///
+/// Irregularity found while parsing a record that didn't stop it from being parsed
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Anomaly {
+    ///
+    /// Bytes following an ip datagram that its total (or payload) length doesn't account for, in a
+    /// frame too long for them to be ethernet padding
+    ///
+    IpTotalLength(usize),
+    ///
+    /// Bytes padding a short frame up to the ethernet minimum
+    ///
+    EthernetPadding(usize),
+    ///
+    /// Tcp data offset, in words, below the 5 word minimum header. The header is parsed as 5 words.
+    ///
+    TcpDataOffset(u8),
+    ///
+    /// Number of vlan tags, when more than an 802.1ad outer and inner tag
+    ///
+    VlanCount(usize),
+    ///
+    /// Bytes of an ip or udp datagram missing from a record truncated by the capture, by the
+    /// datagram's length. The datagram is parsed from the bytes captured.
+    ///
+    Truncated(usize)
+}
+
+///
+/// How records are parsed into layers
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseConfig {
+    ///
+    /// Most tunnels decapsulated from a single record, so nested encapsulation can't recurse
+    /// without bound. Records nested deeper fail with `ErrorKind::TunnelDepth`.
+    ///
+    #[cfg(feature = "tunnels")]
+    pub max_tunnel_depth: usize,
+    ///
+    /// Link type of the capture the records come from, selecting their layer 2 parser. Records of
+    /// link types without one fail with `ErrorKind::LinkType`.
+    ///
+    pub link_type: LinkType,
+    ///
+    /// Byte order of the capture the records come from, which loopback headers of NULL captures
+    /// are written in
+    ///
+    pub endianness: nom::Endianness,
+    ///
+    /// Parse datagrams cut short by the capture's snap length from the bytes captured, flagging
+    /// `Anomaly::Truncated`. Otherwise truncated records whose layers don't parse fail with
+    /// `ErrorKind::Truncated`.
+    ///
+    pub tolerate_truncation: bool,
+    ///
+    /// Treat the last 4 bytes of ethernet records as the frame check sequence, for captures that
+    /// keep it, validating it into `Ethernet::fcs_valid` instead of leaving it in the payload
+    ///
+    pub fcs: bool,
+    ///
+    /// Longest frame accepted, by the record's original length, e.g. 9216 to accept jumbo frames
+    /// but not the oversized frames of segmentation offload. Longer records fail with
+    /// `ErrorKind::FrameLength`. Frames of any length are accepted when unset.
+    ///
+    pub max_frame_length: Option<usize>
+}
+
+impl Default for ParseConfig {
+    fn default() -> ParseConfig {
+        ParseConfig {
+            #[cfg(feature = "tunnels")]
+            max_tunnel_depth: DEFAULT_MAX_TUNNEL_DEPTH,
+            link_type: LinkType::Ethernet,
+            endianness: nom::Endianness::Big,
+            tolerate_truncation: true,
+            fcs: false,
+            max_frame_length: None
+        }
+    }
+}
+
+impl ParseConfig {
+    ///
+    /// Default configuration for records of a capture of `link_type`
+    ///
+    pub fn for_link_type(link_type: LinkType) -> ParseConfig {
+        ParseConfig {
+            link_type: link_type,
+            ..ParseConfig::default()
+        }
+    }
+
+    ///
+    /// Default configuration for records of the capture with global header `header`
+    ///
+    pub fn for_header(header: &GlobalHeader) -> ParseConfig {
+        ParseConfig {
+            link_type: header.link_type(),
+            endianness: header.endianness(),
+            ..ParseConfig::default()
+        }
+    }
+}
+
+///
+/// Where each parsed layer starts within the record payload. Bytes from `end` on follow the ip
+/// datagram, e.g. ethernet padding, and for records without ip `end` is the payload length.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LayerOffsets {
+    pub layer3: Option<usize>,
+    pub layer4: Option<usize>,
+    pub payload: usize,
+    pub end: usize
+}
+
+///
+/// Record parsed once into each of its layers, which can then be queried repeatedly without
+/// reparsing or consuming the record. Layers above a protocol this crate doesn't parse are absent.
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ParsedRecord {
+    record: PcapRecord,
+    layer2: Layer2,
+    layer3: Option<Layer3>,
+    layer4: Option<Layer4>,
+    #[cfg(feature = "tunnels")]
+    tunnels: std::vec::Vec<Decapsulated>,
+    anomalies: std::vec::Vec<Anomaly>
+}
+
+///
+/// Packet carried in a tunnel, parsed into its layers. Packets tunnelled as ip (e.g. GTP-U) have
+/// no layer 2.
+///
+#[cfg(feature = "tunnels")]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Decapsulated {
+    tunnel: TunnelInfo,
+    layer2: Option<Ethernet>,
+    layer3: Option<Layer3>,
+    layer4: Option<Layer4>
+}
+
+#[cfg(feature = "tunnels")]
+impl Decapsulated {
+    pub fn tunnel(&self) -> &TunnelInfo { &self.tunnel }
+    pub fn layer2(&self) -> Option<&Ethernet> { self.layer2.as_ref() }
+    pub fn layer3(&self) -> Option<&Layer3> { self.layer3.as_ref() }
+    pub fn layer4(&self) -> Option<&Layer4> { self.layer4.as_ref() }
+
+    fn parse(tunnel: TunnelInfo, protocol: InnerProtocol, payload: &[u8], anomalies: &mut std::vec::Vec<Anomaly>) -> Result<Decapsulated, errors::Error> {
+        let (layer2, (layer3, layer4)) = match protocol {
+            InnerProtocol::Ethernet => {
+                let ethernet = parse_ethernet(payload, false, anomalies)?;
+                let l3 = parse_layer3(ethernet.ether_type(), ethernet.payload(), Some(Ethernet::header_length(ethernet.vlans())), false, anomalies)?;
+                (Some(ethernet), l3)
+            }
+            InnerProtocol::IPv4 => (None, parse_layer3(&EthernetTypeId::L3(Layer3Id::IPv4), payload, None, false, anomalies)?),
+            InnerProtocol::IPv6 => (None, parse_layer3(&EthernetTypeId::L3(Layer3Id::IPv6), payload, None, false, anomalies)?)
+        };
+
+        Ok(Decapsulated {
+            tunnel: tunnel,
+            layer2: layer2,
+            layer3: layer3,
+            layer4: layer4
+        })
+    }
+}
+
+///
+/// Tunnels carried by a packet with the given layers, outermost first. Tunnelled packets that fail
+/// to parse end decapsulation rather than failing the record. Anomalies in tunnelled packets are
+/// added to `anomalies`. Fails when tunnels are nested deeper than the configured depth, or when a
+/// tunnel carries itself (the same encapsulation, id and endpoints as a tunnel outside it), which
+/// would otherwise be decapsulated until the depth runs out.
+///
+#[cfg(feature = "tunnels")]
+fn decapsulate(layer3: Option<&Layer3>, layer4: Option<&Layer4>, config: &ParseConfig, anomalies: &mut std::vec::Vec<Anomaly>) -> Result<std::vec::Vec<Decapsulated>, errors::Error> {
+    let mut tunnels: std::vec::Vec<Decapsulated> = vec![];
+    loop {
+        let encapsulated = {
+            let (layer3, layer4) = match tunnels.last() {
+                Some(inner) => (inner.layer3(), inner.layer4()),
+                None => (layer3, layer4)
+            };
+            layer3.and_then(|l3| tunnel::encapsulated(l3, layer4))
+        };
+        let (info, protocol, payload) = match encapsulated {
+            Some(encapsulated) => encapsulated,
+            None => return Ok(tunnels)
+        };
+        if tunnels.iter().any(|t| t.tunnel() == &info) {
+            return Err(errors::Error::from_kind(errors::ErrorKind::TunnelLoop(info)));
+        }
+        if tunnels.len() >= config.max_tunnel_depth {
+            return Err(errors::Error::from_kind(errors::ErrorKind::TunnelDepth(config.max_tunnel_depth)));
+        }
+        let mut inner_anomalies = vec![];
+        match Decapsulated::parse(info, protocol, &payload, &mut inner_anomalies) {
+            Ok(decapsulated) => {
+                anomalies.extend(inner_anomalies);
+                tunnels.push(decapsulated);
+            }
+            Err(e) => {
+                debug!("Could not parse tunnelled packet: {:?}", e);
+                return Ok(tunnels)
+            }
+        }
+    }
+}
+
+fn complete<T>(r: (&[u8], T)) -> Result<T, errors::Error> {
+    let (rem, t) = r;
+    if rem.is_empty() {
+        Ok(t)
+    } else {
+        Err(errors::Error::from_kind(errors::ErrorKind::IncompleteParse(rem.len())))
+    }
+}
+
+///
+/// Remainder of a parsed ip datagram, which must be ethernet padding or is an anomaly. Packets
+/// without an ethernet header of length `l2_length` can't be padded.
+///
+fn trailing<T>(r: (&[u8], T), datagram_length: usize, l2_length: Option<usize>, anomalies: &mut std::vec::Vec<Anomaly>) -> T {
+    let (rem, t) = r;
+    if !rem.is_empty() {
+        let frame_length = l2_length.map(|l| l + datagram_length + rem.len());
+        if frame_length.map(|l| l <= MINIMUM_FRAME_LENGTH).unwrap_or(false) {
+            anomalies.push(Anomaly::EthernetPadding(rem.len()));
+        } else {
+            anomalies.push(Anomaly::IpTotalLength(rem.len()));
+        }
+    }
+    t
+}
+
+///
+/// Ethernet frame of a record, whose last 4 bytes are its frame check sequence when `fcs` is set
+///
+fn parse_ethernet(payload: &[u8], fcs: bool, anomalies: &mut std::vec::Vec<Anomaly>) -> Result<Ethernet, errors::Error> {
+    let parsed = if fcs { Ethernet::parse_with_fcs(payload) } else { Ethernet::parse(payload) };
+    let ethernet = parsed
+        .map_err(|e| errors::Error::from_nom(e, errors::Layer::Ethernet, payload))
+        .and_then(complete)?;
+    if ethernet.vlans().len() > MAXIMUM_VLAN_TAGS {
+        anomalies.push(Anomaly::VlanCount(ethernet.vlans().len()));
+    }
+    Ok(ethernet)
+}
+
+///
+/// Link layer of a record, with the parser of the capture's link type
+///
+pub(crate) fn parse_layer2(payload: &[u8], config: &ParseConfig, anomalies: &mut std::vec::Vec<Anomaly>) -> Result<Layer2, errors::Error> {
+    match config.link_type {
+        LinkType::Ethernet => parse_ethernet(payload, config.fcs, anomalies).map(Layer2::Ethernet),
+        LinkType::LinuxSll => {
+            Sll::parse(payload)
+                .map_err(|e| errors::Error::from_nom(e, errors::Layer::Sll, payload))
+                .map(|(_, sll)| Layer2::Sll(sll))
+        }
+        LinkType::LinuxSll2 => {
+            Sll2::parse(payload)
+                .map_err(|e| errors::Error::from_nom(e, errors::Layer::Sll, payload))
+                .map(|(_, sll2)| Layer2::Sll2(sll2))
+        }
+        link_type @ LinkType::Null | link_type @ LinkType::Loop => {
+            let endianness = if link_type == LinkType::Loop { nom::Endianness::Big } else { config.endianness };
+            Null::parse(payload, endianness)
+                .map_err(|e| errors::Error::from_nom(e, errors::Layer::Null, payload))
+                .map(|(_, null)| Layer2::Null(null))
+        }
+        LinkType::Ppp | LinkType::PppHdlc => {
+            Ppp::parse(payload)
+                .map_err(|e| errors::Error::from_nom(e, errors::Layer::Ppp, payload))
+                .map(|(_, ppp)| Layer2::Ppp(ppp))
+        }
+        LinkType::PppEther => {
+            let (_, pppoe) = Pppoe::parse(payload)
+                .map_err(|e| errors::Error::from_nom(e, errors::Layer::Pppoe, payload))?;
+            let ppp = if pppoe.is_discovery() { None } else { Some(pppoe.ppp()?) };
+            Ok(Layer2::PppEther(pppoe, ppp))
+        }
+        LinkType::Ieee80211 => {
+            Ieee80211::parse(payload)
+                .map_err(|e| errors::Error::from_nom(e, errors::Layer::Ieee80211, payload))
+                .map(|(_, frame)| Layer2::Ieee80211(frame))
+        }
+        LinkType::Raw | LinkType::Ipv4 | LinkType::Ipv6 => Ok(Layer2::Raw(payload.to_vec())),
+        other => Err(errors::Error::from_kind(errors::ErrorKind::LinkType(other)))
+    }
+}
+
+///
+/// Tcp segment, parsing a data offset below the minimum as the minimum header
+///
+fn parse_tcp(payload: &[u8], anomalies: &mut std::vec::Vec<Anomaly>) -> Result<Tcp, errors::Error> {
+    let data_offset = payload.get(TCP_DATA_OFFSET_INDEX).map(|b| b >> 4);
+    let patched = match data_offset {
+        Some(offset) if offset < TCP_MINIMUM_DATA_OFFSET && payload.len() >= TCP_MINIMUM_DATA_OFFSET as usize * 4 => {
+            anomalies.push(Anomaly::TcpDataOffset(offset));
+            let mut patched = payload.to_vec();
+            patched[TCP_DATA_OFFSET_INDEX] = TCP_MINIMUM_DATA_OFFSET << 4 | patched[TCP_DATA_OFFSET_INDEX] & 0x0F;
+            Some(patched)
+        }
+        _ => None
+    };
+    let input = patched.as_ref().map(|p| &p[..]).unwrap_or(payload);
+    Tcp::parse(input)
+        .map_err(|e| errors::Error::from_nom(e, errors::Layer::Tcp, input))
+        .and_then(complete)
+}
+
+///
+/// Copy of a datagram whose big endian length at `index` exceeds the `captured` bytes it covers,
+/// with the length lowered to them, flagging the missing bytes. Datagrams that were captured in
+/// full aren't copied.
+///
+fn clamp_length(payload: &[u8], index: usize, captured: usize, anomalies: &mut std::vec::Vec<Anomaly>) -> Option<std::vec::Vec<u8>> {
+    let declared = payload.get(index..index + 2).map(|l| (l[0] as usize) << 8 | l[1] as usize)?;
+    if declared <= captured {
+        return None
+    }
+    anomalies.push(Anomaly::Truncated(declared - captured));
+    let mut patched = payload.to_vec();
+    patched[index] = (captured >> 8) as u8;
+    patched[index + 1] = captured as u8;
+    Some(patched)
+}
+
+///
+/// Transport layer of an ip payload. Datagrams of a `truncated` record are parsed from the bytes
+/// captured.
+///
+fn parse_layer4(protocol: &InternetProtocolId, payload: &[u8], truncated: bool, anomalies: &mut std::vec::Vec<Anomaly>) -> Result<Option<Layer4>, errors::Error> {
+    match *protocol {
+        InternetProtocolId::Tcp => parse_tcp(payload, anomalies).map(|l4| Some(Layer4::Tcp(l4))),
+        InternetProtocolId::Udp => {
+            let patched = if truncated { clamp_length(payload, UDP_LENGTH_INDEX, payload.len(), anomalies) } else { None };
+            let input = patched.as_ref().map(|p| &p[..]).unwrap_or(payload);
+            Udp::parse(input)
+                .map_err(|e| errors::Error::from_nom(e, errors::Layer::Udp, input))
+                .and_then(complete)
+                .map(|l4| Some(Layer4::Udp(l4)))
+        }
+        _ => Ok(None)
+    }
+}
+
+///
+/// Ip and transport layers of a link layer payload, with error offsets relative to the payload.
+/// `l2_length` is the length of the ethernet header the payload followed, if any. Datagrams of a
+/// `truncated` record are parsed from the bytes captured.
+///
+fn parse_layer3(ether_type: &EthernetTypeId, payload: &[u8], l2_length: Option<usize>, truncated: bool, anomalies: &mut std::vec::Vec<Anomaly>) -> Result<(Option<Layer3>, Option<Layer4>), errors::Error> {
+    match *ether_type {
+        EthernetTypeId::L3(Layer3Id::IPv4) => {
+            let patched = if truncated { clamp_length(payload, IPV4_TOTAL_LENGTH_INDEX, payload.len(), anomalies) } else { None };
+            let input = patched.as_ref().map(|p| &p[..]).unwrap_or(payload);
+            let ipv4 = IPv4::parse(input)
+                .map_err(|e| errors::Error::from_nom(e, errors::Layer::IPv4, input))?;
+            let length = ipv4.1.header_length() + ipv4.1.payload().len();
+            let ipv4 = trailing(ipv4, length, l2_length, anomalies);
+            let l4 = parse_layer4(ipv4.protocol(), ipv4.payload(), truncated, anomalies)
+                .map_err(|e| e.offset_by(ipv4.header_length()))?;
+            Ok( (Some(Layer3::IPv4(ipv4)), l4) )
+        }
+        #[cfg(feature = "ipv6")]
+        EthernetTypeId::L3(Layer3Id::IPv6) => {
+            let patched = if truncated {
+                clamp_length(payload, IPV6_PAYLOAD_LENGTH_INDEX, payload.len().saturating_sub(IPV6_HEADER_LENGTH), anomalies)
+            } else {
+                None
+            };
+            let input = patched.as_ref().map(|p| &p[..]).unwrap_or(payload);
+            let ipv6 = IPv6::parse(input)
+                .map_err(|e| errors::Error::from_nom(e, errors::Layer::IPv6, input))?;
+            let length = input.len() - ipv6.0.len();
+            let ipv6 = trailing(ipv6, length, l2_length, anomalies);
+            let l4 = parse_layer4(ipv6.protocol(), ipv6.payload(), truncated, anomalies)
+                .map_err(|e| e.offset_by(ipv6.header_length()))?;
+            Ok( (Some(Layer3::IPv6(ipv6)), l4) )
+        }
+        _ => Ok( (None, None) )
+    }
+}
+
+///
+/// Link layer a flow key is taken from: the innermost tunnelled ethernet header, or the record's
+/// link layer
+///
+#[derive(Clone, Copy)]
+enum KeyedLink<'a> {
+    Ethernet(&'a Ethernet),
+    Record(&'a Layer2)
+}
+
+///
+/// Addresses and ports of the given layers, or addresses of an ARP packet following `link`,
+/// failing for other packets that aren't tcp or udp over ip
+///
+fn layer3_flow_info(link: KeyedLink, layer3: Option<&Layer3>, layer4: Option<&Layer4>) -> Result<Layer3FlowInfo, errors::Error> {
+    let (ether_type, payload, header_length) = match link {
+        KeyedLink::Ethernet(ethernet) => (Some(ethernet.ether_type().clone()), &ethernet.payload()[..], Ethernet::header_length(ethernet.vlans())),
+        KeyedLink::Record(layer2) => (layer2.ether_type(), layer2.payload(), layer2.header_length())
+    };
+    if ether_type == Some(EthernetTypeId::L3(Layer3Id::Arp)) {
+        return Arp::parse(payload)
+            .map_err(|e| errors::Error::from_nom(e, errors::Layer::Arp, payload))
+            .and_then(|(_, arp)| Layer3FlowInfo::try_from(arp))
+            .map_err(|e| e.offset_by(header_length))
+    }
+    let l3 = layer3
+        .ok_or_else(|| match link {
+            KeyedLink::Ethernet(ethernet) => errors::Error::from_kind(errors::ErrorKind::EthernetType(ethernet.ether_type().clone())),
+            KeyedLink::Record(layer2) => layer2.unsupported()
+        })?;
+    let (src_ip, dst_ip, protocol) = match *l3 {
+        Layer3::IPv4(ref ipv4) => (*ipv4.src_ip(), *ipv4.dst_ip(), ipv4.protocol().clone()),
+        #[cfg(feature = "ipv6")]
+        Layer3::IPv6(ref ipv6) => (*ipv6.src_ip(), *ipv6.dst_ip(), ipv6.protocol().clone())
+    };
+    let l4 = match (l3, layer4) {
+        (_, Some(&Layer4::Tcp(ref tcp))) => Layer4FlowInfo::try_from(tcp.clone())?,
+        (_, Some(&Layer4::Udp(ref udp))) => Layer4FlowInfo::try_from(udp.clone())?,
+        (&Layer3::IPv4(ref ipv4), None) => {
+            return Err(errors::Error::from_kind(errors::ErrorKind::IPv4Type(ipv4.protocol().clone())))
+        }
+        #[cfg(feature = "ipv6")]
+        (&Layer3::IPv6(ref ipv6), None) => {
+            return Err(errors::Error::from_kind(errors::ErrorKind::IPv6Type(ipv6.protocol().clone())))
+        }
+    };
+
+    Ok(Layer3FlowInfo {
+        src_ip: src_ip,
+        dst_ip: dst_ip,
+        protocol: Some(protocol),
+        layer4: l4,
+        arp: None
+    })
+}
+
+impl ParsedRecord {
+    ///
+    /// Parse a record into its layers according to `config`, with the layer 2 parser of its link
+    /// type. Records truncated by the capture whose
+    /// layers don't parse fail with `ErrorKind::Truncated`, caused by the parse error.
+    ///
+    pub fn parse_with(value: PcapRecord, config: &ParseConfig) -> Result<ParsedRecord, errors::Error> {
+        let truncation = |e: errors::Error| {
+            if value.is_truncated() {
+                e.chain_err(|| errors::ErrorKind::Truncated(value.truncated_length()))
+            } else {
+                e
+            }
+        };
+        let frame_length = value.original_length() as usize;
+        if let Some(maximum) = config.max_frame_length.filter(|m| frame_length > *m) {
+            return Err(errors::Error::from_kind(errors::ErrorKind::FrameLength(frame_length, maximum)))
+        }
+        let mut anomalies = vec![];
+        let layer2 = parse_layer2(value.payload(), config, &mut anomalies).map_err(|e| match *e.kind() {
+            errors::ErrorKind::LinkType(_) => e,
+            _ => truncation(e)
+        })?;
+        let l3_offset = layer2.header_length();
+        //only ethernet frames are padded
+        let l2_length = layer2.ethernet().map(|_| l3_offset);
+
+        let truncated = value.is_truncated() && config.tolerate_truncation;
+        let (layer3, layer4) = match layer2.ether_type() {
+            Some(ether_type) => parse_layer3(&ether_type, layer2.payload(), l2_length, truncated, &mut anomalies)
+                .map_err(|e| truncation(e.offset_by(l3_offset)))?,
+            None => (None, None)
+        };
+        #[cfg(feature = "tunnels")]
+        let tunnels = decapsulate(layer3.as_ref(), layer4.as_ref(), config, &mut anomalies)?;
+
+        Ok(ParsedRecord {
+            record: value,
+            layer2: layer2,
+            layer3: layer3,
+            layer4: layer4,
+            #[cfg(feature = "tunnels")]
+            tunnels: tunnels,
+            anomalies: anomalies
+        })
+    }
+
+    pub fn record(&self) -> &PcapRecord { &self.record }
+    pub fn into_record(self) -> PcapRecord { self.record }
+    pub fn layer2(&self) -> &Layer2 { &self.layer2 }
+    pub fn layer3(&self) -> Option<&Layer3> { self.layer3.as_ref() }
+    pub fn layer4(&self) -> Option<&Layer4> { self.layer4.as_ref() }
+
+    ///
+    /// Tunnels the record was carried in, outermost first, each with the packet it carried
+    ///
+    #[cfg(feature = "tunnels")]
+    pub fn tunnels(&self) -> &std::vec::Vec<Decapsulated> { &self.tunnels }
+
+    ///
+    /// Irregularities found while parsing the record and any packets tunnelled in it
+    ///
+    pub fn anomalies(&self) -> &std::vec::Vec<Anomaly> { &self.anomalies }
+
+    ///
+    /// Offsets of the layers within the record payload, from the header lengths of the parsed layers
+    ///
+    pub fn offsets(&self) -> LayerOffsets {
+        let l3_offset = self.layer2.header_length();
+        let (l3_header_length, l3_payload) = match self.layer3 {
+            Some(Layer3::IPv4(ref ipv4)) => (ipv4.header_length(), ipv4.payload()),
+            #[cfg(feature = "ipv6")]
+            Some(Layer3::IPv6(ref ipv6)) => (ipv6.header_length(), ipv6.payload()),
+            None => {
+                return LayerOffsets {
+                    layer3: None,
+                    layer4: None,
+                    payload: l3_offset,
+                    end: self.record.payload().len()
+                }
+            }
+        };
+        let l4_offset = l3_offset + l3_header_length;
+        let l4_payload_length = match self.layer4 {
+            Some(Layer4::Tcp(ref tcp)) => tcp.payload().len(),
+            Some(Layer4::Udp(ref udp)) => udp.payload().len(),
+            None => l3_payload.len()
+        };
+        LayerOffsets {
+            layer3: Some(l3_offset),
+            layer4: self.layer4.as_ref().map(|_| l4_offset),
+            payload: l4_offset + l3_payload.len() - l4_payload_length,
+            end: l4_offset + l3_payload.len()
+        }
+    }
+
+    ///
+    /// Ethernet header of records of ethernet captures
+    ///
+    pub fn ethernet(&self) -> Option<&Ethernet> {
+        self.layer2.ethernet()
+    }
+
+    ///
+    /// Outer vlan of ethernet records, untagged for other link types
+    ///
+    pub fn vlan(&self) -> Vlan {
+        self.ethernet().map(|e| e.vlan()).unwrap_or_else(Vlan::untagged)
+    }
+
+    ///
+    /// Whether the record's frame check sequence is valid, `None` unless an ethernet record parsed
+    /// with `fcs` set
+    ///
+    pub fn fcs_valid(&self) -> Option<bool> {
+        self.ethernet().and_then(|e| e.fcs_valid())
+    }
+
+    pub fn delivery(&self) -> Delivery {
+        self.layer2.delivery(self.dst_ip())
+    }
+
+    pub fn src_ip(&self) -> Option<&std::net::IpAddr> {
+        self.layer3.as_ref().map(|l3| match *l3 {
+            Layer3::IPv4(ref ipv4) => ipv4.src_ip(),
+            #[cfg(feature = "ipv6")]
+            Layer3::IPv6(ref ipv6) => ipv6.src_ip()
+        })
+    }
+
+    pub fn dst_ip(&self) -> Option<&std::net::IpAddr> {
+        self.layer3.as_ref().map(|l3| match *l3 {
+            Layer3::IPv4(ref ipv4) => ipv4.dst_ip(),
+            #[cfg(feature = "ipv6")]
+            Layer3::IPv6(ref ipv6) => ipv6.dst_ip()
+        })
+    }
+
+    pub fn src_port(&self) -> Option<u16> {
+        self.layer4.as_ref().map(|l4| match *l4 {
+            Layer4::Tcp(ref tcp) => tcp.src_port(),
+            Layer4::Udp(ref udp) => udp.src_port()
+        })
+    }
+
+    pub fn dst_port(&self) -> Option<u16> {
+        self.layer4.as_ref().map(|l4| match *l4 {
+            Layer4::Tcp(ref tcp) => tcp.dst_port(),
+            Layer4::Udp(ref udp) => udp.dst_port()
+        })
+    }
+
+    ///
+    /// Payload of the highest parsed layer, e.g. the tcp payload of a tcp segment
+    ///
+    pub fn payload(&self) -> &[u8] {
+        match (&self.layer3, &self.layer4) {
+            (_, &Some(Layer4::Tcp(ref tcp))) => tcp.payload(),
+            (_, &Some(Layer4::Udp(ref udp))) => udp.payload(),
+            (&Some(Layer3::IPv4(ref ipv4)), &None) => ipv4.payload(),
+            #[cfg(feature = "ipv6")]
+            (&Some(Layer3::IPv6(ref ipv6)), &None) => ipv6.payload(),
+            (&None, &None) => self.layer2.payload()
+        }
+    }
+
+    ///
+    /// Wake-on-LAN magic packet the record carries, in an ethernet frame of its type or anywhere
+    /// in a udp payload
+    ///
+    pub fn wake_on_lan(&self) -> Option<MagicPacket> {
+        match (self.layer2.ether_type(), &self.layer4) {
+            (Some(EthernetTypeId::L3(Layer3Id::WakeOnLan)), _) => MagicPacket::find(self.layer2.payload()),
+            (_, &Some(Layer4::Udp(ref udp))) => MagicPacket::find(udp.payload()),
+            _ => None
+        }
+    }
+
+    ///
+    /// Flow information from the cached layers, failing as flow conversion would for records that
+    /// aren't tcp or udp over ip
+    ///
+    pub fn flow_info(&self) -> Result<Layer2FlowInfo, errors::Error> {
+        let l3 = layer3_flow_info(KeyedLink::Record(&self.layer2), self.layer3(), self.layer4())?;
+
+        Ok(Layer2FlowInfo {
+            src_mac: self.layer2.src_mac(),
+            dst_mac: self.layer2.dst_mac(),
+            vlan: self.vlan(),
+            inner_vlans: self.ethernet().map(|e| Ethernet::vlans_to_inner_vlans(e.vlans())).unwrap_or_default(),
+            delivery: self.layer2.delivery(Some(&l3.dst_ip)),
+            layer3: l3
+        })
+    }
+
+    ///
+    /// Key of the flow this record belongs to, from the outermost headers as seen from the record's
+    /// source
+    ///
+    #[cfg(feature = "flows")]
+    pub fn flow_key(&self) -> Result<flow::FlowKey, errors::Error> {
+        self.flow_key_with(flow::FlowKeying {
+            vlans: flow::VlanKeying::Outer,
+            tunnels: flow::TunnelKeying::Outer
+        })
+    }
+
+    ///
+    /// Key of the flow this record belongs to, choosing the tunnel headers and vlans keyed on
+    /// according to `keying`
+    ///
+    #[cfg(feature = "flows")]
+    pub fn flow_key_with(&self, keying: flow::FlowKeying) -> Result<flow::FlowKey, errors::Error> {
+        let (ethernet, layer3, layer4) = self.keyed_layers(keying.tunnels);
+        let link = match ethernet {
+            Some(ethernet) => KeyedLink::Ethernet(ethernet),
+            None => KeyedLink::Record(&self.layer2)
+        };
+        let l3 = layer3_flow_info(link, layer3, layer4)?;
+        let (vlan, inner_vlans) = self.keyed_vlans(keying);
+
+        Ok(flow::FlowKey {
+            src_ip: l3.src_ip,
+            src_port: l3.layer4.src_port,
+            dst_ip: l3.dst_ip,
+            dst_port: l3.layer4.dst_port,
+            protocol: l3.protocol,
+            vlan: vlan,
+            inner_vlans: inner_vlans
+        })
+    }
+
+    ///
+    /// Vlan and inner vlans a flow key is taken from, according to `keying`
+    ///
+    #[cfg(feature = "flows")]
+    pub fn keyed_vlans(&self, keying: flow::FlowKeying) -> (Vlan, std::vec::Vec<Vlan>) {
+        match self.keyed_layers(keying.tunnels).0 {
+            Some(ethernet) => flow::keyed_vlans(ethernet.vlan(), &Ethernet::vlans_to_inner_vlans(ethernet.vlans()), keying.vlans),
+            None => (Vlan::untagged(), vec![])
+        }
+    }
+
+    ///
+    /// Layers a flow key is taken from: the innermost tunnelled packet when keying on inner headers,
+    /// with the innermost ethernet header for vlans, absent when neither the record nor its tunnels
+    /// have one
+    ///
+    #[cfg(feature = "flows")]
+    pub fn keyed_layers(&self, keying: flow::TunnelKeying) -> (Option<&Ethernet>, Option<&Layer3>, Option<&Layer4>) {
+        #[cfg(feature = "tunnels")]
+        {
+            if let (flow::TunnelKeying::Inner, Some(inner)) = (keying, self.tunnels.last()) {
+                let ethernet = self.tunnels.iter().rev().filter_map(|t| t.layer2()).next().or_else(|| self.ethernet());
+                return (ethernet, inner.layer3(), inner.layer4())
+            }
+        }
+        (self.ethernet(), self.layer3(), self.layer4())
+    }
+
+    ///
+    /// Tunnel adjacent to the headers a flow key is taken from: the outermost tunnel when keying on
+    /// outer headers, as that is what the keyed packet carries, or the innermost when keying on
+    /// inner headers, as that is what carried the keyed packet
+    ///
+    #[cfg(all(feature = "flows", feature = "tunnels"))]
+    pub fn keyed_tunnel(&self, keying: flow::TunnelKeying) -> Option<&TunnelInfo> {
+        match keying {
+            flow::TunnelKeying::Outer => self.tunnels.first(),
+            flow::TunnelKeying::Inner => self.tunnels.last()
+        }.map(|t| t.tunnel())
+    }
+
+    ///
+    /// Flow for this record, copying the record rather than consuming it
+    ///
+    #[cfg(feature = "flows")]
+    pub fn to_flow(&self) -> Result<flow::Flow, errors::Error> {
+        let l2 = self.flow_info()?;
+
+        Ok(flow::Flow {
+            source: flow::Device {
+                mac: l2.src_mac,
+                ip: l2.layer3.src_ip,
+                port: l2.layer3.layer4.src_port
+            },
+            destination: flow::Device {
+                mac: l2.dst_mac,
+                ip: l2.layer3.dst_ip,
+                port: l2.layer3.layer4.dst_port
+            },
+            protocol: l2.layer3.protocol,
+            record: self.record.clone(),
+            vlan: l2.vlan,
+            inner_vlans: l2.inner_vlans,
+            delivery: l2.delivery,
+            reassembled: false,
+            #[cfg(feature = "tunnels")]
+            tunnel: self.keyed_tunnel(flow::TunnelKeying::Outer).cloned(),
+            arp: l2.layer3.arp
+        })
+    }
+}
+
+impl TryFrom<PcapRecord> for ParsedRecord {
+    type Error = errors::Error;
+
+    fn try_from(value: PcapRecord) -> Result<Self, Self::Error> {
+        let config = ParseConfig::for_link_type(value.link_type());
+        ParsedRecord::parse_with(value, &config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    const RAW_DATA: &'static [u8] = &[
+        0x5Bu8, 0x11u8, 0x6Du8, 0xE3u8, //seconds, 1527868899
+        0x00u8, 0x02u8, 0x51u8, 0xF5u8, //microseconds, 152053
+        0x00u8, 0x00u8, 0x00u8, 0x3Au8, //actual length, 58: 14 (ethernet) + 20 (ipv4 header) + 20 (tcp header) + 4 (tcp payload)
+        0x00u8, 0x00u8, 0x00u8, 0x3Au8, //original length, 58
+        //ethernet
+        0x01u8, 0x02u8, 0x03u8, 0x04u8, 0x05u8, 0x06u8, //dst mac 01:02:03:04:05:06
+        0xFFu8, 0xFEu8, 0xFDu8, 0xFCu8, 0xFBu8, 0xFAu8, //src mac FF:FE:FD:FC:FB:FA
+        0x08u8, 0x00u8, //ipv4
+        //ipv4
+        0x45u8, //version and header length
+        0x00u8, //tos
+        0x00u8, 0x2Cu8, //length, 20 bytes for header, 24 bytes for tcp
+        0x00u8, 0x00u8, //id
+        0x00u8, 0x00u8, //flags
+        0x64u8, //ttl
+        0x06u8, //protocol, tcp
+        0x00u8, 0x00u8, //checksum
+        0x01u8, 0x02u8, 0x03u8, 0x04u8, //src ip 1.2.3.4
+        0x0Au8, 0x0Bu8, 0x0Cu8, 0x0Du8, //dst ip 10.11.12.13
+        //tcp
+        0xC6u8, 0xB7u8, //src port, 50871
+        0x00u8, 0x50u8, //dst port, 80
+        0x00u8, 0x00u8, 0x00u8, 0x01u8, //sequence number, 1
+        0x00u8, 0x00u8, 0x00u8, 0x02u8, //acknowledgement number, 2
+        0x50u8, 0x00u8, //header and flags, 0
+        0x00u8, 0x00u8, //window
+        0x00u8, 0x00u8, //check
+        0x00u8, 0x00u8, //urgent
+        //no options
+        //payload
+        0xfcu8, 0xfdu8, 0xfeu8, 0xffu8
+    ];
+
+    #[test]
+    fn parsed_record_layers() {
+        let _ = env_logger::try_init();
+
+        let (_, record) = PcapRecord::parse(RAW_DATA, nom::Endianness::Big).expect("Could not parse");
+        let parsed = ParsedRecord::try_from(record.clone()).expect("Could not parse layers");
+
+        assert_eq!(parsed.record(), &record);
+        assert_eq!(parsed.src_ip(), Some(&"1.2.3.4".parse::<std::net::IpAddr>().expect("Could not parse ip address")));
+        assert_eq!(parsed.dst_ip(), Some(&"10.11.12.13".parse::<std::net::IpAddr>().expect("Could not parse ip address")));
+        assert_eq!(parsed.src_port(), Some(50871));
+        assert_eq!(parsed.dst_port(), Some(80));
+        assert_eq!(parsed.payload(), &[0xfcu8, 0xfdu8, 0xfeu8, 0xffu8][..]);
+        assert_eq!(parsed.vlan(), Vlan::untagged());
+        assert!(parsed.anomalies().is_empty());
+        assert_eq!(parsed.offsets(), LayerOffsets { layer3: Some(14), layer4: Some(34), payload: 54, end: 58 });
+    }
+
+    #[cfg(feature = "flows")]
+    #[test]
+    fn parsed_record_flow() {
+        let _ = env_logger::try_init();
+
+        let (_, record) = PcapRecord::parse(RAW_DATA, nom::Endianness::Big).expect("Could not parse");
+        let parsed = ParsedRecord::try_from(record.clone()).expect("Could not parse layers");
+
+        let flow = parsed.to_flow().expect("Could not extract flow");
+
+        assert_eq!(flow, Flow::try_from(record).expect("Could not extract flow"));
+        assert_eq!(parsed.flow_key().expect("Could not extract flow key"), flow.key());
+        assert_eq!(parsed.to_flow().expect("Could not extract flow"), flow);
+    }
+
+    #[cfg(feature = "flows")]
+    #[test]
+    fn parsed_record_qinq_flow() {
+        let _ = env_logger::try_init();
+
+        let mut payload = RAW_DATA[16..28].to_vec();
+        payload.extend_from_slice(&[0x88u8, 0xA8u8, 0x00u8, 0x64u8]); //service tag, vlan 100
+        payload.extend_from_slice(&[0x81u8, 0x00u8, 0x00u8, 0xC8u8]); //customer tag, vlan 200
+        payload.extend_from_slice(&RAW_DATA[28..]);
+        let record = PcapRecord::new(std::time::UNIX_EPOCH, payload.len() as u32, payload.len() as u32, payload);
+        let parsed = ParsedRecord::try_from(record.clone()).expect("Could not parse layers");
+
+        let info = parsed.flow_info().expect("Could not extract flow info");
+        assert_eq!(info.vlan.id(), 100);
+        assert_eq!(info.inner_vlans.iter().map(|v| v.id()).collect::<std::vec::Vec<_>>(), vec![200]);
+
+        let flow = parsed.to_flow().expect("Could not extract flow");
+        assert_eq!(flow, Flow::try_from(record).expect("Could not extract flow"));
+        assert_eq!(flow.s_vlan().map(|v| v.id()), Some(100));
+        assert_eq!(flow.c_vlan().map(|v| v.id()), Some(200));
+        assert_eq!(flow.key(), parsed.flow_key().expect("Could not extract flow key"));
+
+        let keying = flow::FlowKeying { vlans: flow::VlanKeying::Typed, tunnels: flow::TunnelKeying::Outer };
+        let key = flow.key_with(keying.vlans);
+        assert_eq!(key, parsed.flow_key_with(keying).expect("Could not extract flow key"));
+        assert_eq!(key.vlan_ids(), vec![100, 200]);
+    }
+
+    #[cfg(feature = "flows")]
+    #[test]
+    fn parsed_record_arp_flow() {
+        use super::super::layer3::arp::ArpOperation;
+
+        let _ = env_logger::try_init();
+
+        let payload = vec![
+            0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8, //dst mac, broadcast
+            0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x01u8, //src mac
+            0x08u8, 0x06u8, //arp
+            //arp
+            0x00u8, 0x01u8, //hardware type, ethernet
+            0x08u8, 0x00u8, //protocol type, ipv4
+            0x06u8, //hardware length
+            0x04u8, //protocol length
+            0x00u8, 0x01u8, //operation, request
+            0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x01u8, //sender mac
+            0x0Au8, 0x00u8, 0x00u8, 0x01u8, //sender ip 10.0.0.1
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, //target mac
+            0x0Au8, 0x00u8, 0x00u8, 0xFEu8, //target ip 10.0.0.254
+            0x00u8, 0x00u8, 0x00u8, 0x00u8 //ethernet padding
+        ];
+        let record = PcapRecord::new(std::time::UNIX_EPOCH, payload.len() as u32, payload.len() as u32, payload);
+        let parsed = ParsedRecord::try_from(record.clone()).expect("Could not parse layers");
+
+        assert!(parsed.layer3().is_none());
+
+        let flow = parsed.to_flow().expect("Could not extract flow");
+
+        assert_eq!(flow.arp, Some(ArpOperation::Request));
+        assert_eq!(flow.source().ip, "10.0.0.1".parse::<std::net::IpAddr>().expect("Could not parse ip address"));
+        assert_eq!(flow.destination().ip, "10.0.0.254".parse::<std::net::IpAddr>().expect("Could not parse ip address"));
+        assert_eq!(flow.destination().port, 0);
+        assert_eq!(flow.delivery, Delivery::Broadcast);
+        assert_eq!(flow, Flow::try_from(record).expect("Could not extract flow"));
+        assert_eq!(parsed.flow_key().expect("Could not extract flow key"), flow.key());
+    }
+
+    #[test]
+    fn parsed_record_unknown_protocol() {
+        let _ = env_logger::try_init();
+
+        let mut data = RAW_DATA.to_vec();
+        data[16 + 23] = 0x3Bu8; //no next header
+
+        let (_, record) = PcapRecord::parse(&data, nom::Endianness::Big).expect("Could not parse");
+        let parsed = ParsedRecord::try_from(record).expect("Could not parse layers");
+
+        assert!(parsed.layer3().is_some());
+        assert!(parsed.layer4().is_none());
+        assert_eq!(parsed.src_port(), None);
+        assert_eq!(parsed.payload().len(), 24);
+        assert!(parsed.flow_info().is_err());
+    }
+
+    #[test]
+    fn parsed_record_wake_on_lan() {
+        let _ = env_logger::try_init();
+
+        let target = MacAddress([0x00u8, 0x11u8, 0x22u8, 0x33u8, 0x44u8, 0x55u8]);
+        let mut magic = vec![0xFFu8; 6];
+        for _ in 0..16 {
+            magic.extend_from_slice(&target.0);
+        }
+        let record = |payload: std::vec::Vec<u8>| {
+            PcapRecord::new(std::time::UNIX_EPOCH, payload.len() as u32, payload.len() as u32, payload)
+        };
+
+        let frame = Ethernet::builder()
+            .dst_mac(MacAddress([0xFFu8; 6]))
+            .ether_type(EthernetTypeId::L3(Layer3Id::WakeOnLan))
+            .payload(magic.clone())
+            .to_bytes()
+            .expect("Could not build");
+        let parsed = ParsedRecord::try_from(record(frame)).expect("Could not parse layers");
+        assert_eq!(parsed.wake_on_lan().map(|m| *m.target()), Some(target));
+
+        let mut datagram = vec![
+            0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8, //dst mac, broadcast
+            0xFFu8, 0xFEu8, 0xFDu8, 0xFCu8, 0xFBu8, 0xFAu8, //src mac
+            0x08u8, 0x00u8, //ipv4
+            //ipv4
+            0x45u8, //version and header length
+            0x00u8, //tos
+            0x00u8, 0x82u8, //length, 20 bytes for header, 110 bytes for udp
+            0x00u8, 0x00u8, //id
+            0x00u8, 0x00u8, //flags
+            0x40u8, //ttl
+            0x11u8, //protocol, udp
+            0x00u8, 0x00u8, //checksum
+            0xC0u8, 0x00u8, 0x02u8, 0x01u8, //src ip 192.0.2.1
+            0xC0u8, 0x00u8, 0x02u8, 0xFFu8, //dst ip 192.0.2.255
+            //udp
+            0xC6u8, 0xB7u8, //src port, 50871
+            0x00u8, 0x09u8, //dst port, 9
+            0x00u8, 0x6Eu8, //length, 8 bytes for header, 102 bytes for payload
+            0x00u8, 0x00u8 //checksum
+        ];
+        datagram.extend_from_slice(&magic);
+        let parsed = ParsedRecord::try_from(record(datagram)).expect("Could not parse layers");
+        assert_eq!(parsed.wake_on_lan().map(|m| *m.target()), Some(target));
+
+        let (_, record) = PcapRecord::parse(RAW_DATA, nom::Endianness::Big).expect("Could not parse");
+        let parsed = ParsedRecord::try_from(record).expect("Could not parse layers");
+        assert_eq!(parsed.wake_on_lan(), None);
+    }
+
+    #[test]
+    fn parsed_record_anomalies() {
+        let _ = env_logger::try_init();
+
+        let parse = |payload: std::vec::Vec<u8>| {
+            let record = PcapRecord::new(std::time::UNIX_EPOCH, payload.len() as u32, payload.len() as u32, payload);
+            ParsedRecord::try_from(record).expect("Could not parse layers")
+        };
+
+        let mut padded = RAW_DATA[16..].to_vec();
+        padded.extend_from_slice(&[0u8; 2]);
+        assert_eq!(parse(padded).anomalies(), &vec![Anomaly::EthernetPadding(2)]);
+
+        let mut trailing = RAW_DATA[16..].to_vec();
+        trailing.extend_from_slice(&[0u8; 10]);
+        assert_eq!(parse(trailing).anomalies(), &vec![Anomaly::IpTotalLength(10)]);
+
+        let mut data_offset = RAW_DATA[16..].to_vec();
+        data_offset[14 + 20 + 12] = 0x40u8; //4 words
+        let parsed = parse(data_offset);
+        assert_eq!(parsed.anomalies(), &vec![Anomaly::TcpDataOffset(4)]);
+        assert_eq!(parsed.dst_port(), Some(80));
+        assert_eq!(parsed.payload().len(), 4);
+
+        let mut stacked = RAW_DATA[16..28].to_vec();
+        for vlan in 1..4u8 {
+            stacked.extend_from_slice(&[0x81u8, 0x00u8, 0x00u8, vlan]);
+        }
+        stacked.extend_from_slice(&RAW_DATA[28..]);
+        let parsed = parse(stacked);
+        assert_eq!(parsed.anomalies(), &vec![Anomaly::VlanCount(3)]);
+        assert!(parsed.layer4().is_some());
+    }
+
+    #[test]
+    fn parsed_record_fcs() {
+        let _ = env_logger::try_init();
+
+        let mut payload = RAW_DATA[16..].to_vec();
+        payload.extend_from_slice(&[0x19u8, 0x8Eu8, 0x93u8, 0x4Au8]); //fcs
+        let record = |payload: &std::vec::Vec<u8>| {
+            PcapRecord::new(std::time::UNIX_EPOCH, payload.len() as u32, payload.len() as u32, payload.clone())
+        };
+        let config = ParseConfig { fcs: true, ..ParseConfig::default() };
+
+        let parsed = ParsedRecord::parse_with(record(&payload), &config).expect("Could not parse layers");
+        assert_eq!(parsed.fcs_valid(), Some(true));
+        assert!(parsed.anomalies().is_empty());
+        assert_eq!(parsed.payload(), &[0xfcu8, 0xfdu8, 0xfeu8, 0xffu8][..]);
+
+        let parsed = ParsedRecord::try_from(record(&payload)).expect("Could not parse layers");
+        assert_eq!(parsed.fcs_valid(), None);
+        assert_eq!(parsed.anomalies(), &vec![Anomaly::IpTotalLength(4)]);
+
+        payload[14 + 20 + 20] = 0x00u8;
+        let parsed = ParsedRecord::parse_with(record(&payload), &config).expect("Could not parse layers");
+        assert_eq!(parsed.fcs_valid(), Some(false));
+    }
+
+    #[test]
+    fn parsed_record_max_frame_length() {
+        let _ = env_logger::try_init();
+
+        let (_, record) = PcapRecord::parse(RAW_DATA, nom::Endianness::Big).expect("Could not parse");
+        let config = ParseConfig { max_frame_length: Some(58), ..ParseConfig::default() };
+        assert!(ParsedRecord::parse_with(record.clone(), &config).is_ok());
+
+        let config = ParseConfig { max_frame_length: Some(57), ..ParseConfig::default() };
+        let err = ParsedRecord::parse_with(record, &config).expect_err("Parsed frame longer than the maximum");
+        match *err.kind() {
+            errors::ErrorKind::FrameLength(length, maximum) => assert_eq!( (length, maximum), (58, 57) ),
+            ref other => panic!("Unexpected error {:?}", other)
+        }
+    }
+
+    #[test]
+    fn parsed_record_truncated() {
+        let _ = env_logger::try_init();
+
+        let truncated = |protocol: u8, original: u32| {
+            let mut payload = RAW_DATA[16..].to_vec();
+            payload[14 + 2] = 0x03u8; //length, 1000 as on the wire
+            payload[14 + 3] = 0xE8u8;
+            payload[14 + 9] = protocol;
+            payload[14 + 20 + 4] = 0x03u8; //udp length, 980 as on the wire
+            payload[14 + 20 + 5] = 0xD4u8;
+            PcapRecord::new(std::time::UNIX_EPOCH, payload.len() as u32, original, payload)
+        };
+
+        let parsed = ParsedRecord::try_from(truncated(0x06u8, 14 + 1000)).expect("Could not parse layers");
+        assert!(parsed.record().is_truncated());
+        assert_eq!(parsed.anomalies(), &vec![Anomaly::Truncated(956)]);
+        assert_eq!(parsed.dst_port(), Some(80));
+        assert_eq!(parsed.payload(), &[0xfcu8, 0xfdu8, 0xfeu8, 0xffu8][..]);
+
+        let parsed = ParsedRecord::try_from(truncated(0x11u8, 14 + 1000)).expect("Could not parse layers");
+        assert_eq!(parsed.anomalies(), &vec![Anomaly::Truncated(956), Anomaly::Truncated(956)]);
+        assert!(matches!(parsed.layer4(), Some(&Layer4::Udp(_))));
+        assert_eq!(parsed.payload().len(), 16);
+
+        let strict = ParseConfig { tolerate_truncation: false, ..ParseConfig::default() };
+        let err = ParsedRecord::parse_with(truncated(0x06u8, 14 + 1000), &strict).expect_err("Parsed truncated record");
+        match *err.kind() {
+            errors::ErrorKind::Truncated(missing) => assert_eq!(missing, 956),
+            ref other => panic!("Unexpected error {:?}", other)
+        }
+
+        let err = ParsedRecord::try_from(truncated(0x06u8, 58)).expect_err("Parsed datagram longer than the record");
+        match *err.kind() {
+            errors::ErrorKind::Parse(ref context) => assert_eq!(context.layer, errors::Layer::IPv4),
+            ref other => panic!("Unexpected error {:?}", other)
+        }
+    }
+
+    #[cfg(all(feature = "tunnels", feature = "flows"))]
+    #[test]
+    fn parsed_record_gre() {
+        let _ = env_logger::try_init();
+
+        let inner = &RAW_DATA[16 + 14..];
+        let ip_length = 20 + 8 + inner.len();
+        let mut payload = vec![
+            0x01u8, 0x02u8, 0x03u8, 0x04u8, 0x05u8, 0x06u8, //dst mac
+            0xFFu8, 0xFEu8, 0xFDu8, 0xFCu8, 0xFBu8, 0xFAu8, //src mac
+            0x08u8, 0x00u8, //ipv4
+            //ipv4
+            0x45u8, //version and header length
+            0x00u8, //tos
+            0x00u8, ip_length as u8, //length
+            0x00u8, 0x00u8, //id
+            0x00u8, 0x00u8, //flags
+            0x40u8, //ttl
+            0x2Fu8, //protocol, gre
+            0x00u8, 0x00u8, //checksum
+            0xC0u8, 0x00u8, 0x02u8, 0x01u8, //src ip 192.0.2.1
+            0xC0u8, 0x00u8, 0x02u8, 0x02u8, //dst ip 192.0.2.2
+            //gre
+            0x20u8, 0x00u8, //key present
+            0x08u8, 0x00u8, //ipv4
+            0x00u8, 0x00u8, 0x00u8, 0x2Au8 //key, 42
+        ];
+        payload.extend_from_slice(inner);
+        let record = PcapRecord::new(std::time::UNIX_EPOCH, payload.len() as u32, payload.len() as u32, payload);
+        let parsed = ParsedRecord::try_from(record).expect("Could not parse layers");
+
+        assert!(parsed.layer4().is_none());
+        assert_eq!(parsed.tunnels().len(), 1);
+        let tunnel = parsed.tunnels()[0].tunnel();
+        assert_eq!(tunnel.tunnel_type, tunnel::TunnelType::Gre);
+        assert_eq!(tunnel.id, Some(42));
+        assert_eq!(tunnel.dst_ip, "192.0.2.2".parse::<std::net::IpAddr>().expect("Could not parse ip address"));
+
+        let key = parsed.flow_key_with(flow::FlowKeying::default()).expect("Could not extract inner flow key");
+        assert_eq!(key.src_ip, "1.2.3.4".parse::<std::net::IpAddr>().expect("Could not parse ip address"));
+        assert_eq!(key.dst_port, 80);
+        assert!(parsed.flow_key().is_err());
+    }
+
+    #[cfg(all(feature = "tunnels", feature = "flows"))]
+    #[test]
+    fn parsed_record_erspan() {
+        let _ = env_logger::try_init();
+
+        let inner = &RAW_DATA[16..];
+        let ip_length = 20 + 8 + 8 + inner.len();
+        let mut payload = vec![
+            0x01u8, 0x02u8, 0x03u8, 0x04u8, 0x05u8, 0x06u8, //dst mac
+            0xFFu8, 0xFEu8, 0xFDu8, 0xFCu8, 0xFBu8, 0xFAu8, //src mac
+            0x08u8, 0x00u8, //ipv4
+            //ipv4
+            0x45u8, //version and header length
+            0x00u8, //tos
+            0x00u8, ip_length as u8, //length
+            0x00u8, 0x00u8, //id
+            0x00u8, 0x00u8, //flags
+            0x40u8, //ttl
+            0x2Fu8, //protocol, gre
+            0x00u8, 0x00u8, //checksum
+            0xC0u8, 0x00u8, 0x02u8, 0x01u8, //src ip 192.0.2.1
+            0xC0u8, 0x00u8, 0x02u8, 0x02u8, //dst ip 192.0.2.2
+            //gre
+            0x10u8, 0x00u8, //sequence number present
+            0x88u8, 0xBEu8, //erspan type ii
+            0x00u8, 0x00u8, 0x00u8, 0x01u8, //sequence number, 1
+            //erspan
+            0x10u8, 0x0Au8, //version 1, vlan 10
+            0x00u8, 0x07u8, //session 7
+            0x00u8, 0x00u8, 0x00u8, 0x03u8 //index, 3
+        ];
+        payload.extend_from_slice(inner);
+        let record = PcapRecord::new(std::time::UNIX_EPOCH, payload.len() as u32, payload.len() as u32, payload);
+        let parsed = ParsedRecord::try_from(record).expect("Could not parse layers");
+
+        assert_eq!(parsed.tunnels().len(), 1);
+        let decapsulated = &parsed.tunnels()[0];
+        assert_eq!(decapsulated.tunnel().tunnel_type, tunnel::TunnelType::Erspan);
+        assert_eq!(decapsulated.tunnel().id, Some(7));
+        assert_eq!(decapsulated.tunnel().erspan.map(|m| m.vlan), Some(10));
+        assert!(decapsulated.layer2().is_some());
+
+        let key = parsed.flow_key_with(flow::FlowKeying::default()).expect("Could not extract inner flow key");
+        assert_eq!(key.src_ip, "1.2.3.4".parse::<std::net::IpAddr>().expect("Could not parse ip address"));
+        assert_eq!(key.dst_port, 80);
+    }
+
+    #[cfg(feature = "tunnels")]
+    #[test]
+    fn parsed_record_nested_tunnels() {
+        let _ = env_logger::try_init();
+
+        let gre = |key: u8, inner: std::vec::Vec<u8>| {
+            let ip_length = 20 + 8 + inner.len();
+            let mut datagram = vec![
+                0x45u8, //version and header length
+                0x00u8, //tos
+                (ip_length >> 8) as u8, ip_length as u8, //length
+                0x00u8, 0x00u8, //id
+                0x00u8, 0x00u8, //flags
+                0x40u8, //ttl
+                0x2Fu8, //protocol, gre
+                0x00u8, 0x00u8, //checksum
+                0xC0u8, 0x00u8, 0x02u8, 0x01u8, //src ip 192.0.2.1
+                0xC0u8, 0x00u8, 0x02u8, 0x02u8, //dst ip 192.0.2.2
+                //gre
+                0x20u8, 0x00u8, //key present
+                0x08u8, 0x00u8, //ipv4
+                0x00u8, 0x00u8, 0x00u8, key //key
+            ];
+            datagram.extend_from_slice(&inner);
+            datagram
+        };
+        let parse = |datagram: std::vec::Vec<u8>, config: &ParseConfig| {
+            let mut payload = RAW_DATA[16..16 + 14].to_vec();
+            payload.extend_from_slice(&datagram);
+            let record = PcapRecord::new(std::time::UNIX_EPOCH, payload.len() as u32, payload.len() as u32, payload);
+            ParsedRecord::parse_with(record, config)
+        };
+        let nested = |keys: &[u8]| {
+            keys.iter().rev().fold(RAW_DATA[16 + 14..].to_vec(), |inner, key| gre(*key, inner))
+        };
+
+        let parsed = parse(nested(&[1, 2, 3, 4]), &ParseConfig::default()).expect("Could not parse layers");
+        assert_eq!(parsed.tunnels().iter().map(|t| t.tunnel().id).collect::<std::vec::Vec<_>>(), vec![Some(1), Some(2), Some(3), Some(4)]);
+        match parsed.tunnels()[3].layer4() {
+            Some(&Layer4::Tcp(ref tcp)) => assert_eq!(tcp.dst_port(), 80),
+            other => panic!("Unexpected innermost layer 4 {:?}", other)
+        }
+
+        let err = parse(nested(&[1, 2, 3, 4, 5]), &ParseConfig::default()).expect_err("Parsed beyond the maximum depth");
+        match *err.kind() {
+            errors::ErrorKind::TunnelDepth(depth) => assert_eq!(depth, DEFAULT_MAX_TUNNEL_DEPTH),
+            ref other => panic!("Unexpected error {:?}", other)
+        }
+
+        let shallow = ParseConfig { max_tunnel_depth: 1, ..ParseConfig::default() };
+        assert!(parse(nested(&[1]), &shallow).is_ok());
+        match *parse(nested(&[1, 2]), &shallow).expect_err("Parsed beyond the maximum depth").kind() {
+            errors::ErrorKind::TunnelDepth(depth) => assert_eq!(depth, 1),
+            ref other => panic!("Unexpected error {:?}", other)
+        }
+
+        //the same tunnel inside itself
+        let deep = ParseConfig { max_tunnel_depth: 64, ..ParseConfig::default() };
+        match *parse(nested(&[1, 2, 1]), &deep).expect_err("Parsed a tunnel loop").kind() {
+            errors::ErrorKind::TunnelLoop(ref info) => {
+                assert_eq!(info.tunnel_type, tunnel::TunnelType::Gre);
+                assert_eq!(info.id, Some(1));
+            }
+            ref other => panic!("Unexpected error {:?}", other)
+        }
+    }
+}
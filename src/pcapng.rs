@@ -0,0 +1,1709 @@
+use super::prelude::*;
+use super::common::{put_u16, put_u32, put_u64};
+use super::global_header::{GlobalHeader, NATIVE_ENDIAN, TimestampPrecision};
+use super::link_type::LinkType;
+
+use self::nom::*;
+
+use std;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+pub const SECTION_HEADER_BLOCK: u32 = 0x0A0D0D0Au32;
+pub const INTERFACE_DESCRIPTION_BLOCK: u32 = 0x00000001u32;
+pub const NAME_RESOLUTION_BLOCK: u32 = 0x00000004u32;
+pub const ENHANCED_PACKET_BLOCK: u32 = 0x00000006u32;
+pub const DECRYPTION_SECRETS_BLOCK: u32 = 0x0000000Au32;
+
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B3C4Du32;
+///
+/// Block type and total length before the body, total length again after it
+///
+const BLOCK_OVERHEAD: u32 = 12;
+const OPTION_END: u16 = 0;
+const OPTION_COMMENT: u16 = 1;
+const OPTION_SHB_HARDWARE: u16 = 2;
+const OPTION_SHB_OS: u16 = 3;
+const OPTION_SHB_USERAPPL: u16 = 4;
+const OPTION_IF_NAME: u16 = 2;
+const OPTION_IF_DESCRIPTION: u16 = 3;
+const OPTION_IF_TSRESOL: u16 = 9;
+const OPTION_IF_OS: u16 = 12;
+const OPTION_IF_TSOFFSET: u16 = 14;
+const OPTION_IF_HARDWARE: u16 = 15;
+const NANOS_PER_SECOND: u128 = 1_000_000_000;
+
+const NRB_RECORD_END: u16 = 0;
+const NRB_RECORD_IPV4: u16 = 1;
+const NRB_RECORD_IPV6: u16 = 2;
+
+const SECRETS_TLS_KEY_LOG: u32 = 0x544C534Bu32;
+const SECRETS_WIREGUARD_KEY_LOG: u32 = 0x57474B4Cu32;
+const SECRETS_ZIGBEE_NWK_KEY: u32 = 0x5A4E574Bu32;
+const SECRETS_ZIGBEE_APS_KEY: u32 = 0x5A415053u32;
+
+fn pcapng_error<'a, T>(input: &'a [u8], code: errors::ParseCode) -> IResult<&'a [u8], T> {
+    Err(Err::Error(error_position!(input, ErrorKind::Custom(code as u32))))
+}
+
+///
+/// Snap length of the libpcap header of captures whose interfaces don't limit it, as editcap
+/// writes it
+///
+pub const UNLIMITED_SNAP_LENGTH: u32 = 262144;
+
+///
+/// Whether `input` starts with a pcapng section header block rather than a libpcap global header
+///
+pub fn is_pcapng(input: &[u8]) -> bool {
+    be_u32(input).map(|(_, t)| t == SECTION_HEADER_BLOCK).unwrap_or(false)
+}
+
+///
+/// Units timestamps of an interface are counted in, a negative power of 10 or of 2 of a second
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TimestampResolution {
+    Decimal(u8),
+    Binary(u8)
+}
+
+impl Default for TimestampResolution {
+    ///
+    /// Microseconds, when an interface doesn't give its resolution
+    ///
+    fn default() -> TimestampResolution {
+        TimestampResolution::Decimal(6)
+    }
+}
+
+impl From<TimestampPrecision> for TimestampResolution {
+    fn from(precision: TimestampPrecision) -> TimestampResolution {
+        match precision {
+            TimestampPrecision::Microsecond => TimestampResolution::Decimal(6),
+            TimestampPrecision::Nanosecond => TimestampResolution::Decimal(9)
+        }
+    }
+}
+
+impl TimestampResolution {
+    ///
+    /// Resolution from the if_tsresol option, whose high bit selects powers of 2
+    ///
+    pub fn new(value: u8) -> TimestampResolution {
+        if value & 0x80 == 0 {
+            TimestampResolution::Decimal(value)
+        } else {
+            TimestampResolution::Binary(value & 0x7F)
+        }
+    }
+
+    ///
+    /// if_tsresol option value, the inverse of `new`
+    ///
+    pub fn value(&self) -> u8 {
+        match *self {
+            TimestampResolution::Decimal(exponent) => exponent & 0x7F,
+            TimestampResolution::Binary(exponent) => 0x80 | exponent
+        }
+    }
+
+    ///
+    /// Units in a second, saturating for resolutions finer than a `u128` can count
+    ///
+    pub fn units_per_second(&self) -> u128 {
+        match *self {
+            TimestampResolution::Decimal(exponent) => 10u128.checked_pow(exponent as u32).unwrap_or(u128::MAX),
+            TimestampResolution::Binary(exponent) => 1u128.checked_shl(exponent as u32).unwrap_or(u128::MAX)
+        }
+    }
+
+    ///
+    /// Time spanned by `units`, truncated to nanoseconds
+    ///
+    pub fn to_duration(&self, units: u64) -> std::time::Duration {
+        let per_second = self.units_per_second();
+        let units = units as u128;
+        let nanos = (units % per_second) * NANOS_PER_SECOND / per_second;
+        std::time::Duration::new((units / per_second) as u64, nanos as u32)
+    }
+
+    ///
+    /// Units in `duration`, the inverse of `to_duration` to within a unit. Durations of more units
+    /// than the 64 bits of a pcapng timestamp fail.
+    ///
+    pub fn to_units(&self, duration: std::time::Duration) -> errors::Result<u64> {
+        let per_second = self.units_per_second();
+        (duration.as_secs() as u128).checked_mul(per_second)
+            .and_then(|seconds| {
+                (duration.subsec_nanos() as u128).checked_mul(per_second)
+                    .and_then(|nanos| seconds.checked_add(nanos / NANOS_PER_SECOND))
+            })
+            .and_then(|units| u64::try_from(units).ok())
+            .ok_or_else(|| format!("Duration {:?} is more units of resolution {:?} than a timestamp holds", duration, self).into())
+    }
+}
+
+///
+/// Section header block, starting each section of a capture and giving the byte order of the
+/// blocks in it
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SectionHeader {
+    endianness: Endianness,
+    version_major: u16,
+    version_minor: u16,
+    section_length: Option<u64>,
+    hardware: Option<String>,
+    os: Option<String>,
+    user_application: Option<String>,
+    comments: std::vec::Vec<String>
+}
+
+impl SectionHeader {
+    ///
+    /// Header of a version 1.0 section in byte order `endianness`, without options, e.g. to start
+    /// a `Writer` with
+    ///
+    pub fn new(endianness: Endianness) -> SectionHeader {
+        SectionHeader {
+            endianness: endianness,
+            version_major: 1,
+            version_minor: 0,
+            section_length: None,
+            hardware: None,
+            os: None,
+            user_application: None,
+            comments: vec![]
+        }
+    }
+
+    pub fn set_hardware(&mut self, hardware: Option<String>) {
+        self.hardware = hardware
+    }
+    pub fn set_os(&mut self, os: Option<String>) {
+        self.os = os
+    }
+    pub fn set_user_application(&mut self, user_application: Option<String>) {
+        self.user_application = user_application
+    }
+    pub fn set_comments(&mut self, comments: std::vec::Vec<String>) {
+        self.comments = comments
+    }
+
+    pub fn endianness(&self) -> Endianness { self.endianness }
+    pub fn version_major(&self) -> u16 { self.version_major }
+    pub fn version_minor(&self) -> u16 { self.version_minor }
+
+    ///
+    /// Length of the section following this header, when the writer recorded it
+    ///
+    pub fn section_length(&self) -> Option<u64> { self.section_length }
+
+    ///
+    /// Hardware of the machine the section was written on, from the shb_hardware option
+    ///
+    pub fn hardware(&self) -> Option<&str> { self.hardware.as_deref() }
+
+    ///
+    /// Operating system the section was written on, from the shb_os option
+    ///
+    pub fn os(&self) -> Option<&str> { self.os.as_deref() }
+
+    ///
+    /// Application that wrote the section, e.g. "Dumpcap (Wireshark) 4.2.2", from the shb_userappl
+    /// option
+    ///
+    pub fn user_application(&self) -> Option<&str> { self.user_application.as_deref() }
+
+    ///
+    /// Comments from the section's opt_comment options
+    ///
+    pub fn comments(&self) -> &std::vec::Vec<String> { &self.comments }
+
+    ///
+    /// Parse a section header block, taking its byte order from the byte order magic
+    ///
+    pub fn parse<'a>(input: &'a [u8]) -> IResult<&'a [u8], SectionHeader> {
+        let (_, (block_type, _, magic)) = tuple!(input, be_u32, be_u32, be_u32)?;
+        if block_type != SECTION_HEADER_BLOCK {
+            return Err(Err::Error(error_position!(input, ErrorKind::Tag)))
+        }
+        let endianness = if magic == BYTE_ORDER_MAGIC {
+            Endianness::Big
+        } else if magic == BYTE_ORDER_MAGIC.swap_bytes() {
+            Endianness::Little
+        } else {
+            return pcapng_error(input, errors::ParseCode::PcapngByteOrder)
+        };
+        let (rem, (_, body)) = block(input, endianness)?;
+
+        let (options_input, mut header) = complete_body(body, do_parse!(body,
+            _magic: u32!(endianness) >>
+            version_major: u16!(endianness) >>
+            version_minor: u16!(endianness) >>
+            section_length: i64!(endianness) >>
+
+            (
+                SectionHeader {
+                    version_major: version_major,
+                    version_minor: version_minor,
+                    section_length: if section_length < 0 { None } else { Some(section_length as u64) },
+                    ..SectionHeader::new(endianness)
+                }
+            )
+        ))?;
+        for (code, value) in options(options_input, endianness) {
+            let value = std::str::from_utf8(value).ok().map(|v| v.to_string());
+            match code {
+                OPTION_COMMENT => header.comments.extend(value),
+                OPTION_SHB_HARDWARE => header.hardware = value,
+                OPTION_SHB_OS => header.os = value,
+                OPTION_SHB_USERAPPL => header.user_application = value,
+                _ => {}
+            }
+        }
+        debug!("Section header version {}.{}, with endianness {:?}", header.version_major, header.version_minor, header.endianness);
+
+        Ok( (rem, header) )
+    }
+}
+
+///
+/// Interface packets in a section were captured on, from an interface description block.
+/// Interfaces are numbered by the order they are described in within a section.
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Interface {
+    link_type: LinkType,
+    snap_length: u32,
+    name: Option<String>,
+    description: Option<String>,
+    os: Option<String>,
+    hardware: Option<String>,
+    comments: std::vec::Vec<String>,
+    timestamp_resolution: TimestampResolution,
+    timestamp_offset: i64
+}
+
+impl Interface {
+    ///
+    /// Interface with microsecond timestamps and no name, e.g. to describe to a `Writer`
+    ///
+    pub fn new(link_type: LinkType, snap_length: u32) -> Interface {
+        Interface {
+            link_type: link_type,
+            snap_length: snap_length,
+            name: None,
+            description: None,
+            os: None,
+            hardware: None,
+            comments: vec![],
+            timestamp_resolution: TimestampResolution::default(),
+            timestamp_offset: 0
+        }
+    }
+
+    pub fn set_name(&mut self, name: Option<String>) {
+        self.name = name
+    }
+    pub fn set_description(&mut self, description: Option<String>) {
+        self.description = description
+    }
+    pub fn set_os(&mut self, os: Option<String>) {
+        self.os = os
+    }
+    pub fn set_hardware(&mut self, hardware: Option<String>) {
+        self.hardware = hardware
+    }
+    pub fn set_comments(&mut self, comments: std::vec::Vec<String>) {
+        self.comments = comments
+    }
+    pub fn set_timestamp_resolution(&mut self, timestamp_resolution: TimestampResolution) {
+        self.timestamp_resolution = timestamp_resolution
+    }
+    pub fn set_timestamp_offset(&mut self, timestamp_offset: i64) {
+        self.timestamp_offset = timestamp_offset
+    }
+
+    pub fn link_type(&self) -> LinkType { self.link_type }
+
+    ///
+    /// Most bytes captured from each packet, 0 for no limit
+    ///
+    pub fn snap_length(&self) -> u32 { self.snap_length }
+    pub fn name(&self) -> Option<&str> { self.name.as_deref() }
+    pub fn description(&self) -> Option<&str> { self.description.as_deref() }
+
+    ///
+    /// Operating system of the machine the interface is on, e.g. "Linux 6.1"
+    ///
+    pub fn os(&self) -> Option<&str> { self.os.as_deref() }
+
+    ///
+    /// Hardware the interface is on, e.g. "Intel 82579LM", from the if_hardware option
+    ///
+    pub fn hardware(&self) -> Option<&str> { self.hardware.as_deref() }
+
+    ///
+    /// Comments from the interface's opt_comment options
+    ///
+    pub fn comments(&self) -> &std::vec::Vec<String> { &self.comments }
+    pub fn timestamp_resolution(&self) -> TimestampResolution { self.timestamp_resolution }
+
+    ///
+    /// Seconds added to packet timestamps, so timestamps can be stored relative to a capture start
+    ///
+    pub fn timestamp_offset(&self) -> i64 { self.timestamp_offset }
+
+    ///
+    /// Time of a packet timestamped `units` on this interface. Timestamps a negative offset puts
+    /// before the epoch, or that with the offset are past the times the system can represent, fail.
+    ///
+    pub fn timestamp(&self, units: u64) -> errors::Result<std::time::SystemTime> {
+        let since_offset = self.timestamp_resolution.to_duration(units);
+        let offset = std::time::Duration::from_secs(self.timestamp_offset.unsigned_abs());
+        let timestamp = if self.timestamp_offset < 0 {
+            since_offset.checked_sub(offset).and_then(|d| std::time::UNIX_EPOCH.checked_add(d))
+        } else {
+            std::time::UNIX_EPOCH.checked_add(offset).and_then(|start| start.checked_add(since_offset))
+        };
+        timestamp.ok_or_else(|| {
+            format!("Timestamp of {} units is outside the range of system time after the epoch with the interface offset of {} seconds", units, self.timestamp_offset).into()
+        })
+    }
+
+    ///
+    /// Units `timestamp` is after this interface's offset, the inverse of `timestamp`. Times before
+    /// the offset, or too far after it for a timestamp to hold, fail.
+    ///
+    pub fn units(&self, timestamp: std::time::SystemTime) -> errors::Result<u64> {
+        let offset = std::time::Duration::from_secs(self.timestamp_offset.unsigned_abs());
+        let since_offset = if self.timestamp_offset < 0 {
+            timestamp.duration_since(std::time::UNIX_EPOCH).ok().and_then(|d| d.checked_add(offset))
+        } else {
+            std::time::UNIX_EPOCH.checked_add(offset).and_then(|start| timestamp.duration_since(start).ok())
+        };
+        match since_offset {
+            Some(since_offset) => self.timestamp_resolution.to_units(since_offset),
+            None => Err(format!("Timestamp {:?} is before the interface offset of {} seconds", timestamp, self.timestamp_offset).into())
+        }
+    }
+
+    fn parse_body<'a>(body: &'a [u8], endianness: Endianness) -> IResult<&'a [u8], Interface> {
+        let (rem, (link_type, snap_length)) = do_parse!(body,
+            link_type: u16!(endianness) >>
+            _reserved: u16!(endianness) >>
+            snap_length: u32!(endianness) >>
+            ( (LinkType::new(link_type as u32), snap_length) )
+        )?;
+
+        let mut interface = Interface::new(link_type, snap_length);
+        for (code, value) in options(rem, endianness) {
+            match code {
+                OPTION_COMMENT => interface.comments.extend(std::str::from_utf8(value).ok().map(|c| c.to_string())),
+                OPTION_IF_NAME => interface.name = std::str::from_utf8(value).ok().map(|n| n.to_string()),
+                OPTION_IF_DESCRIPTION => interface.description = std::str::from_utf8(value).ok().map(|d| d.to_string()),
+                OPTION_IF_OS => interface.os = std::str::from_utf8(value).ok().map(|o| o.to_string()),
+                OPTION_IF_HARDWARE => interface.hardware = std::str::from_utf8(value).ok().map(|h| h.to_string()),
+                OPTION_IF_TSRESOL if value.len() == 1 => interface.timestamp_resolution = TimestampResolution::new(value[0]),
+                OPTION_IF_TSOFFSET => {
+                    if let Ok( (_, offset) ) = i64!(value, endianness) {
+                        interface.timestamp_offset = offset;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok( (&rem[rem.len()..], interface) )
+    }
+}
+
+///
+/// Packet from an enhanced packet block, with its timestamp in units of its interface's resolution
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct EnhancedPacket {
+    interface_id: u32,
+    timestamp: u64,
+    original_length: u32,
+    payload: std::vec::Vec<u8>,
+    comments: std::vec::Vec<String>
+}
+
+impl EnhancedPacket {
+    pub fn interface_id(&self) -> u32 { self.interface_id }
+    pub fn timestamp(&self) -> u64 { self.timestamp }
+    pub fn original_length(&self) -> u32 { self.original_length }
+    pub fn payload(&self) -> &std::vec::Vec<u8> { &self.payload }
+
+    ///
+    /// Comments from the packet's opt_comment options
+    ///
+    pub fn comments(&self) -> &std::vec::Vec<String> { &self.comments }
+
+    ///
+    /// Record of the packet, timestamped according to the interface it was captured on and with its
+    /// id, link type and timestamp resolution, failing if the interface can't timestamp it
+    ///
+    pub fn to_record(&self, interface: &Interface) -> errors::Result<PcapRecord> {
+        let mut record = PcapRecord::new(
+            interface.timestamp(self.timestamp)?,
+            self.payload.len() as u32,
+            self.original_length,
+            self.payload.clone()
+        );
+        record.set_comments(self.comments.clone());
+        record.set_interface_id(self.interface_id);
+        record.set_link_type(interface.link_type());
+        record.set_timestamp_resolution(interface.timestamp_resolution());
+        Ok(record)
+    }
+
+    fn parse_body<'a>(body: &'a [u8], endianness: Endianness) -> IResult<&'a [u8], EnhancedPacket> {
+        let (rem, mut packet) = do_parse!(body,
+            interface_id: u32!(endianness) >>
+            timestamp_high: u32!(endianness) >>
+            timestamp_low: u32!(endianness) >>
+            captured_length: u32!(endianness) >>
+            original_length: u32!(endianness) >>
+            payload: take!(captured_length) >>
+            _padding: take!(padding(captured_length as usize)) >>
+
+            (
+                EnhancedPacket {
+                    interface_id: interface_id,
+                    timestamp: (timestamp_high as u64) << 32 | timestamp_low as u64,
+                    original_length: original_length,
+                    payload: payload.to_vec(),
+                    comments: vec![]
+                }
+            )
+        )?;
+
+        packet.comments = options(rem, endianness).into_iter()
+            .filter(|&(code, _)| code == OPTION_COMMENT)
+            .filter_map(|(_, value)| std::str::from_utf8(value).ok().map(|c| c.to_string()))
+            .collect();
+
+        Ok( (&rem[rem.len()..], packet) )
+    }
+}
+
+///
+/// Names an address resolved to, from a record of a name resolution block
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct NameRecord {
+    address: std::net::IpAddr,
+    names: std::vec::Vec<String>
+}
+
+impl NameRecord {
+    pub fn new(address: std::net::IpAddr, names: std::vec::Vec<String>) -> NameRecord {
+        NameRecord {
+            address: address,
+            names: names
+        }
+    }
+
+    pub fn address(&self) -> std::net::IpAddr { self.address }
+    pub fn names(&self) -> &std::vec::Vec<String> { &self.names }
+
+    ///
+    /// Record from a value of `address_length` address bytes followed by zero terminated names.
+    /// Names that aren't UTF-8 are skipped.
+    ///
+    fn from_value(value: &[u8], address_length: usize) -> Option<NameRecord> {
+        if value.len() < address_length {
+            return None
+        }
+        let (address, names) = value.split_at(address_length);
+        let address = if address_length == 4 {
+            std::net::IpAddr::V4(std::net::Ipv4Addr::from(*array_ref![address, 0, 4]))
+        } else {
+            std::net::IpAddr::V6(std::net::Ipv6Addr::from(*array_ref![address, 0, 16]))
+        };
+        let names = names.split(|b| *b == 0)
+            .filter(|n| !n.is_empty())
+            .filter_map(|n| std::str::from_utf8(n).ok().map(|n| n.to_string()))
+            .collect();
+        Some(NameRecord::new(address, names))
+    }
+
+    fn value(&self) -> std::vec::Vec<u8> {
+        let mut value = match self.address {
+            std::net::IpAddr::V4(address) => address.octets().to_vec(),
+            std::net::IpAddr::V6(address) => address.octets().to_vec()
+        };
+        for name in self.names.iter() {
+            value.extend_from_slice(name.as_bytes());
+            value.push(0);
+        }
+        value
+    }
+
+    ///
+    /// Records of a name resolution block, up to the end of records record. Options after the
+    /// records (e.g. the dns server used) are skipped, as are records of unknown type.
+    ///
+    fn parse_block<'a>(mut body: &'a [u8], endianness: Endianness) -> IResult<&'a [u8], std::vec::Vec<NameRecord>> {
+        let mut records = vec![];
+        while !body.is_empty() {
+            let (rem, (record_type, value)) = do_parse!(body,
+                record_type: u16!(endianness) >>
+                length: u16!(endianness) >>
+                value: take!(length) >>
+                _padding: take!(padding(length as usize)) >>
+                ( (record_type, value) )
+            )?;
+            let record = match record_type {
+                NRB_RECORD_END => break,
+                NRB_RECORD_IPV4 => NameRecord::from_value(value, 4),
+                NRB_RECORD_IPV6 => NameRecord::from_value(value, 16),
+                other => {
+                    trace!("Skipping name resolution record type {}", other);
+                    None
+                }
+            };
+            records.extend(record);
+            body = rem;
+        }
+        Ok( (&body[body.len()..], records) )
+    }
+}
+
+///
+/// Kind of secrets a decryption secrets block holds
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SecretsType {
+    ///
+    /// NSS key log, as written through SSLKEYLOGFILE
+    ///
+    TlsKeyLog,
+    WireGuardKeyLog,
+    ZigbeeNwkKey,
+    ZigbeeApsKey,
+    Unknown(u32)
+}
+
+impl SecretsType {
+    pub fn new(value: u32) -> SecretsType {
+        match value {
+            SECRETS_TLS_KEY_LOG => SecretsType::TlsKeyLog,
+            SECRETS_WIREGUARD_KEY_LOG => SecretsType::WireGuardKeyLog,
+            SECRETS_ZIGBEE_NWK_KEY => SecretsType::ZigbeeNwkKey,
+            SECRETS_ZIGBEE_APS_KEY => SecretsType::ZigbeeApsKey,
+            other => SecretsType::Unknown(other)
+        }
+    }
+
+    pub fn value(&self) -> u32 {
+        match *self {
+            SecretsType::TlsKeyLog => SECRETS_TLS_KEY_LOG,
+            SecretsType::WireGuardKeyLog => SECRETS_WIREGUARD_KEY_LOG,
+            SecretsType::ZigbeeNwkKey => SECRETS_ZIGBEE_NWK_KEY,
+            SecretsType::ZigbeeApsKey => SECRETS_ZIGBEE_APS_KEY,
+            SecretsType::Unknown(value) => value
+        }
+    }
+}
+
+///
+/// Secrets embedded in a capture by a decryption secrets block, e.g. the TLS key log Wireshark
+/// embeds with `editcap --inject-secrets`, kept as the raw bytes of the block
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DecryptionSecrets {
+    secrets_type: SecretsType,
+    data: std::vec::Vec<u8>
+}
+
+impl DecryptionSecrets {
+    pub fn new(secrets_type: SecretsType, data: std::vec::Vec<u8>) -> DecryptionSecrets {
+        DecryptionSecrets {
+            secrets_type: secrets_type,
+            data: data
+        }
+    }
+
+    pub fn secrets_type(&self) -> SecretsType { self.secrets_type }
+    pub fn data(&self) -> &std::vec::Vec<u8> { &self.data }
+
+    ///
+    /// Secrets as text, for the text based key logs, or `None` if they aren't UTF-8
+    ///
+    pub fn as_text(&self) -> Option<&str> {
+        std::str::from_utf8(&self.data).ok()
+    }
+
+    fn parse_body<'a>(body: &'a [u8], endianness: Endianness) -> IResult<&'a [u8], DecryptionSecrets> {
+        do_parse!(body,
+            secrets_type: u32!(endianness) >>
+            length: u32!(endianness) >>
+            data: take!(length) >>
+            _padding: take!(padding(length as usize)) >>
+
+            ( DecryptionSecrets::new(SecretsType::new(secrets_type), data.to_vec()) )
+        )
+    }
+}
+
+///
+/// Blocks of a pcapng capture this crate parses. Other blocks (e.g. statistics) are skipped,
+/// keeping only their type.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Block {
+    SectionHeader(SectionHeader),
+    InterfaceDescription(Interface),
+    EnhancedPacket(EnhancedPacket),
+    NameResolution(std::vec::Vec<NameRecord>),
+    DecryptionSecrets(DecryptionSecrets),
+    Other(u32)
+}
+
+impl Block {
+    ///
+    /// Parse a block in a section with byte order `endianness`. A section header block is parsed in
+    /// the byte order it gives, which then applies to the blocks after it.
+    ///
+    pub fn parse<'a>(input: &'a [u8], endianness: Endianness) -> IResult<&'a [u8], Block> {
+        if is_pcapng(input) {
+            return SectionHeader::parse(input).map(|(rem, header)| (rem, Block::SectionHeader(header)))
+        }
+        let (rem, (block_type, body)) = block(input, endianness)?;
+        let block = match block_type {
+            INTERFACE_DESCRIPTION_BLOCK => Block::InterfaceDescription(complete_body(body, Interface::parse_body(body, endianness))?.1),
+            ENHANCED_PACKET_BLOCK => Block::EnhancedPacket(complete_body(body, EnhancedPacket::parse_body(body, endianness))?.1),
+            NAME_RESOLUTION_BLOCK => Block::NameResolution(complete_body(body, NameRecord::parse_block(body, endianness))?.1),
+            DECRYPTION_SECRETS_BLOCK => Block::DecryptionSecrets(complete_body(body, DecryptionSecrets::parse_body(body, endianness))?.1),
+            other => {
+                trace!("Skipping block type {:08x}", other);
+                Block::Other(other)
+            }
+        };
+        Ok( (rem, block) )
+    }
+}
+
+///
+/// Bytes padding `length` bytes to a 32 bit boundary
+///
+fn padding(length: usize) -> usize {
+    (4 - length % 4) % 4
+}
+
+///
+/// Type and body of a block, checking its total length is repeated after the body
+///
+fn block<'a>(input: &'a [u8], endianness: Endianness) -> IResult<&'a [u8], (u32, &'a [u8])> {
+    do_parse!(input,
+        block_type: u32!(endianness) >>
+        length: add_return_error!(ErrorKind::Custom(errors::ParseCode::PcapngBlockLength as u32), verify!(u32!(endianness), |l: u32| {
+            l >= BLOCK_OVERHEAD && l % 4 == 0
+        })) >>
+        body: take!(length - BLOCK_OVERHEAD) >>
+        _length: add_return_error!(ErrorKind::Custom(errors::ParseCode::PcapngBlockLength as u32), verify!(u32!(endianness), |l: u32| {
+            l == length
+        })) >>
+
+        ( (block_type, body) )
+    )
+}
+
+///
+/// Fields running past the end of a block body are a malformed block rather than a need for
+/// more input
+///
+fn complete_body<'a, T>(body: &'a [u8], result: IResult<&'a [u8], T>) -> IResult<&'a [u8], T> {
+    match result {
+        Err(Err::Incomplete(_)) => pcapng_error(body, errors::ParseCode::PcapngBlockLength),
+        result => result
+    }
+}
+
+///
+/// Code and value of each option in `input`, up to the end of options option. Malformed options
+/// end the list.
+///
+fn options<'a>(mut input: &'a [u8], endianness: Endianness) -> std::vec::Vec<(u16, &'a [u8])> {
+    let mut options = vec![];
+    while !input.is_empty() {
+        let parsed: IResult<&'a [u8], (u16, &'a [u8])> = do_parse!(input,
+            code: u16!(endianness) >>
+            length: u16!(endianness) >>
+            value: take!(length) >>
+            _padding: take!(padding(length as usize)) >>
+            ( (code, value) )
+        );
+        match parsed {
+            Ok( (_, (OPTION_END, _)) ) => break,
+            Ok( (rem, option) ) => {
+                options.push(option);
+                input = rem;
+            }
+            Err(e) => {
+                debug!("Ignoring malformed options: {:?}", e);
+                break
+            }
+        }
+    }
+    options
+}
+
+///
+/// Section of a capture, with the interfaces described in it
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Section {
+    header: SectionHeader,
+    interfaces: std::vec::Vec<Interface>
+}
+
+impl Section {
+    pub fn header(&self) -> &SectionHeader { &self.header }
+    pub fn interfaces(&self) -> &std::vec::Vec<Interface> { &self.interfaces }
+}
+
+///
+/// Interface of a capture, keyed by the section it was described in and its interface id there,
+/// as records reference it
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct InterfaceInfo {
+    section: usize,
+    id: u32,
+    interface: Interface
+}
+
+impl InterfaceInfo {
+    pub fn section(&self) -> usize { self.section }
+    pub fn id(&self) -> u32 { self.id }
+    pub fn interface(&self) -> &Interface { &self.interface }
+
+    pub fn link_type(&self) -> LinkType { self.interface.link_type() }
+    pub fn snap_length(&self) -> u32 { self.interface.snap_length() }
+    pub fn name(&self) -> Option<&str> { self.interface.name() }
+    pub fn description(&self) -> Option<&str> { self.interface.description() }
+    pub fn os(&self) -> Option<&str> { self.interface.os() }
+    pub fn hardware(&self) -> Option<&str> { self.interface.hardware() }
+    pub fn comments(&self) -> &std::vec::Vec<String> { self.interface.comments() }
+    pub fn timestamp_resolution(&self) -> TimestampResolution { self.interface.timestamp_resolution() }
+}
+
+///
+/// Pcapng capture (https://www.ietf.org/archive/id/draft-ietf-opsawg-pcapng-01.html) parsed into
+/// its sections and packet records, each record timestamped according to the interface it was
+/// captured on
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Capture {
+    sections: std::vec::Vec<Section>,
+    records: std::vec::Vec<PcapRecord>,
+    //section and interface id of each record
+    record_interfaces: std::vec::Vec<(usize, u32)>,
+    secrets: std::vec::Vec<DecryptionSecrets>,
+    names: HashMap<std::net::IpAddr, std::vec::Vec<String>>
+}
+
+impl Capture {
+    pub fn sections(&self) -> &std::vec::Vec<Section> { &self.sections }
+    pub fn records(&self) -> &std::vec::Vec<PcapRecord> { &self.records }
+    pub fn into_records(self) -> std::vec::Vec<PcapRecord> { self.records }
+
+    ///
+    /// Decryption secrets embedded in the capture, in the order their blocks appear
+    ///
+    pub fn secrets(&self) -> &std::vec::Vec<DecryptionSecrets> { &self.secrets }
+
+    ///
+    /// Names of each address resolved by the capture's name resolution blocks, in the order they
+    /// appear
+    ///
+    pub fn names(&self) -> &HashMap<std::net::IpAddr, std::vec::Vec<String>> { &self.names }
+
+    ///
+    /// First name `address` resolved to, the name Wireshark shows for it
+    ///
+    pub fn name(&self, address: &std::net::IpAddr) -> Option<&str> {
+        self.names.get(address).and_then(|names| names.first()).map(|name| name.as_str())
+    }
+
+    ///
+    /// Names of the source and destination of `flow`, where the capture resolved them
+    ///
+    #[cfg(feature = "flows")]
+    pub fn flow_names(&self, flow: &Flow) -> (Option<&str>, Option<&str>) {
+        (self.name(&flow.source().ip), self.name(&flow.destination().ip))
+    }
+
+    ///
+    /// Every TLS key log embedded in the capture, joined in order, e.g. to write out for a tool
+    /// reading SSLKEYLOGFILE. `None` if the capture has no TLS key log.
+    ///
+    pub fn tls_key_log(&self) -> Option<String> {
+        let logs = self.secrets.iter()
+            .filter(|s| s.secrets_type == SecretsType::TlsKeyLog)
+            .map(|s| String::from_utf8_lossy(&s.data).into_owned())
+            .collect::<std::vec::Vec<_>>();
+        if logs.is_empty() {
+            return None
+        }
+        Some(logs.iter().fold(String::new(), |mut log, next| {
+            log.push_str(next);
+            if !log.is_empty() && !log.ends_with('\n') {
+                log.push('\n');
+            }
+            log
+        }))
+    }
+
+    ///
+    /// Interface the record at `index` was captured on
+    ///
+    pub fn interface(&self, index: usize) -> Option<&Interface> {
+        let &(section, interface_id) = self.record_interfaces.get(index)?;
+        self.sections[section].interfaces.get(interface_id as usize)
+    }
+
+    ///
+    /// Section and interface id of the interface the record at `index` was captured on, the key
+    /// of its `InterfaceInfo`
+    ///
+    pub fn interface_key(&self, index: usize) -> Option<(usize, u32)> {
+        self.record_interfaces.get(index).cloned()
+    }
+
+    ///
+    /// Equivalent libpcap global header of the capture: the byte order of its first section, the
+    /// largest snap length of its interfaces, and nanosecond timestamps if any interface is finer
+    /// than microseconds. A libpcap capture has a single link type, so every interface must share
+    /// one.
+    ///
+    pub fn global_header(&self) -> errors::Result<GlobalHeader> {
+        let interfaces = self.interface_infos();
+        let first = match interfaces.first() {
+            Some(first) => first,
+            None => return Err("Capture describes no interfaces".into())
+        };
+
+        let mut snap_length = 0;
+        let mut precision = TimestampPrecision::Microsecond;
+        for interface in interfaces.iter() {
+            if interface.link_type() != first.link_type() {
+                return Err(format!("Cannot describe interfaces of link types {:?} and {:?} with one libpcap header", first.link_type(), interface.link_type()).into())
+            }
+            snap_length = std::cmp::max(snap_length, match interface.snap_length() {
+                0 => UNLIMITED_SNAP_LENGTH,
+                length => length
+            });
+            if interface.timestamp_resolution().units_per_second() > 1_000_000 {
+                precision = TimestampPrecision::Nanosecond;
+            }
+        }
+        let endianness = self.sections[0].header().endianness();
+
+        Ok(GlobalHeader::new(first.link_type(), snap_length).with_endianness(endianness).with_precision(precision))
+    }
+
+    ///
+    /// Every interface described in the capture, in order of section and interface id
+    ///
+    pub fn interface_infos(&self) -> std::vec::Vec<InterfaceInfo> {
+        self.sections.iter().enumerate().flat_map(|(section, s)| {
+            s.interfaces.iter().enumerate().map(move |(id, interface)| InterfaceInfo {
+                section: section,
+                id: id as u32,
+                interface: interface.clone()
+            })
+        }).collect()
+    }
+
+    ///
+    /// Interface the record at `index` was captured on, with its key
+    ///
+    pub fn interface_info(&self, index: usize) -> Option<InterfaceInfo> {
+        let (section, id) = self.interface_key(index)?;
+        self.interface(index).map(|interface| InterfaceInfo {
+            section: section,
+            id: id,
+            interface: interface.clone()
+        })
+    }
+
+    ///
+    /// Parse a slice of bytes starting with a section header block. Blocks after the last complete
+    /// block are left unparsed.
+    ///
+    pub fn parse<'a, T: AsRef<[u8]> + ?Sized>(input: &'a T) -> IResult<&'a [u8], Capture> {
+        let (mut current, header) = SectionHeader::parse(input.as_ref())?;
+        let mut capture = Capture {
+            sections: vec![Section { header: header, interfaces: vec![] }],
+            records: vec![],
+            record_interfaces: vec![],
+            secrets: vec![],
+            names: HashMap::new()
+        };
+
+        while !current.is_empty() {
+            let section_index = capture.sections.len() - 1;
+            let endianness = capture.sections[section_index].header.endianness;
+            let (rem, block) = match Block::parse(current, endianness) {
+                Ok(parsed) => parsed,
+                Err(Err::Incomplete(_)) => {
+                    debug!("Capture ended within a block, {} bytes left", current.len());
+                    break
+                }
+                Err(e) => return Err(e)
+            };
+            match block {
+                Block::SectionHeader(header) => capture.sections.push(Section { header: header, interfaces: vec![] }),
+                Block::InterfaceDescription(interface) => capture.sections[section_index].interfaces.push(interface),
+                Block::EnhancedPacket(packet) => {
+                    let record = match capture.sections[section_index].interfaces.get(packet.interface_id as usize) {
+                        Some(interface) => match packet.to_record(interface) {
+                            Ok(record) => record,
+                            Err(e) => {
+                                debug!("Could not timestamp packet: {}", e);
+                                return pcapng_error(current, errors::ParseCode::PcapngTimestamp)
+                            }
+                        },
+                        None => return pcapng_error(current, errors::ParseCode::PcapngInterface)
+                    };
+                    capture.records.push(record);
+                    capture.record_interfaces.push( (section_index, packet.interface_id) );
+                }
+                Block::NameResolution(records) => {
+                    for record in records {
+                        let names = capture.names.entry(record.address).or_insert_with(std::vec::Vec::new);
+                        for name in record.names {
+                            if !names.contains(&name) {
+                                names.push(name);
+                            }
+                        }
+                    }
+                }
+                Block::DecryptionSecrets(secrets) => capture.secrets.push(secrets),
+                Block::Other(_) => {}
+            }
+            current = rem;
+        }
+
+        Ok( (current, capture) )
+    }
+
+    ///
+    /// Read an entire pcapng capture from `reader` and parse it. With the `compression` feature, gzip
+    /// compressed captures are decompressed.
+    ///
+    pub fn parse_reader<R: std::io::Read>(reader: R) -> errors::Result<Capture> {
+        use std::io::Read;
+
+        #[cfg(feature = "compression")]
+        let mut reader = super::compression::Decoder::new(reader)?;
+        #[cfg(not(feature = "compression"))]
+        let mut reader = reader;
+
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes)?;
+
+        match Capture::parse(&bytes) {
+            Ok( (rem, capture) ) => {
+                if !rem.is_empty() {
+                    debug!("Ignoring {} bytes of incomplete block", rem.len());
+                }
+                Ok(capture)
+            }
+            Err(e) => Err(errors::Error::from_nom(e, errors::Layer::Pcapng, &bytes))
+        }
+    }
+
+    ///
+    /// Read and parse the pcapng capture at `path`
+    ///
+    pub fn parse_path<P: AsRef<std::path::Path>>(path: P) -> errors::Result<Capture> {
+        let file = std::fs::File::open(path)?;
+        Capture::parse_reader(std::io::BufReader::new(file))
+    }
+}
+
+///
+/// Parse a pcapng capture into its equivalent libpcap global header and its records, which keep
+/// the interface they were captured on. Captures without interfaces, or whose interfaces differ
+/// in link type, have no equivalent header and fail.
+///
+pub fn parse_file(input: &[u8]) -> IResult<&[u8], (GlobalHeader, std::vec::Vec<PcapRecord>)> {
+    let (rem, capture) = Capture::parse(input)?;
+    match capture.global_header() {
+        Ok(header) => Ok( (rem, (header, capture.into_records())) ),
+        Err(e) => {
+            debug!("{}", e);
+            pcapng_error(input, errors::ParseCode::PcapngLinkType)
+        }
+    }
+}
+
+///
+/// Append option `code` with `value`, which must fit the 16 bit option length
+///
+#[cfg(feature = "writer")]
+fn put_option(bytes: &mut std::vec::Vec<u8>, code: u16, value: &[u8], endianness: Endianness) -> errors::Result<()> {
+    if value.len() > u16::MAX as usize {
+        return Err(format!("Option {} of {} bytes is longer than the {} bytes an option can hold", code, value.len(), u16::MAX).into())
+    }
+    put_u16(bytes, code, endianness);
+    put_u16(bytes, value.len() as u16, endianness);
+    bytes.extend_from_slice(value);
+    bytes.extend_from_slice(&[0u8; 3][..padding(value.len())]);
+    Ok(())
+}
+
+///
+/// Writes a pcapng capture of a single section: the section header block when created, an
+/// interface description block per interface added, and an enhanced packet block per record,
+/// timestamped in the resolution of the interface it is written for. Writes are unbuffered; wrap
+/// the writer in a `BufWriter` when writing to a file.
+///
+#[cfg(feature = "writer")]
+pub struct Writer<W> {
+    writer: W,
+    endianness: Endianness,
+    interfaces: std::vec::Vec<Interface>,
+    records: usize
+}
+
+#[cfg(feature = "writer")]
+impl<W: std::io::Write> Writer<W> {
+    ///
+    /// Start a capture in native byte order
+    ///
+    pub fn new(writer: W) -> errors::Result<Writer<W>> {
+        Writer::with_endianness(writer, NATIVE_ENDIAN)
+    }
+
+    pub fn with_endianness(writer: W, endianness: Endianness) -> errors::Result<Writer<W>> {
+        Writer::with_section_header(writer, &SectionHeader::new(endianness))
+    }
+
+    ///
+    /// Start a capture in the byte order of `header`, writing its options, e.g. to carry over the
+    /// section header of a capture being rewritten. The section is written as version 1.0, without
+    /// its length.
+    ///
+    pub fn with_section_header(writer: W, header: &SectionHeader) -> errors::Result<Writer<W>> {
+        let endianness = header.endianness;
+        let mut writer = Writer {
+            writer: writer,
+            endianness: endianness,
+            interfaces: vec![],
+            records: 0
+        };
+        let mut body = vec![];
+        put_u32(&mut body, BYTE_ORDER_MAGIC, endianness);
+        put_u16(&mut body, 1, endianness); //version 1.0
+        put_u16(&mut body, 0, endianness);
+        put_u64(&mut body, u64::MAX, endianness); //section length not given
+
+        let options_start = body.len();
+        for comment in header.comments.iter() {
+            put_option(&mut body, OPTION_COMMENT, comment.as_bytes(), endianness)?;
+        }
+        if let Some(ref hardware) = header.hardware {
+            put_option(&mut body, OPTION_SHB_HARDWARE, hardware.as_bytes(), endianness)?;
+        }
+        if let Some(ref os) = header.os {
+            put_option(&mut body, OPTION_SHB_OS, os.as_bytes(), endianness)?;
+        }
+        if let Some(ref user_application) = header.user_application {
+            put_option(&mut body, OPTION_SHB_USERAPPL, user_application.as_bytes(), endianness)?;
+        }
+        if body.len() > options_start {
+            put_option(&mut body, OPTION_END, &[], endianness)?;
+        }
+
+        writer.write_block(SECTION_HEADER_BLOCK, &body)?;
+        Ok(writer)
+    }
+
+    pub fn interfaces(&self) -> &std::vec::Vec<Interface> { &self.interfaces }
+
+    ///
+    /// Records written so far
+    ///
+    pub fn records(&self) -> usize { self.records }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_block(&mut self, block_type: u32, body: &[u8]) -> errors::Result<()> {
+        let length = BLOCK_OVERHEAD + body.len() as u32;
+        let mut block = vec![];
+        put_u32(&mut block, block_type, self.endianness);
+        put_u32(&mut block, length, self.endianness);
+        block.extend_from_slice(body);
+        put_u32(&mut block, length, self.endianness);
+        self.writer.write_all(&block)?;
+        Ok(())
+    }
+
+    ///
+    /// Describe an interface records can then be written for, returning its interface id. Link
+    /// types are 16 bits in pcapng, so interfaces of larger link types fail.
+    ///
+    pub fn add_interface(&mut self, interface: Interface) -> errors::Result<u32> {
+        let link_type = interface.link_type.value();
+        if link_type > u16::MAX as u32 {
+            return Err(format!("Link type {} is larger than the {} an interface can hold", link_type, u16::MAX).into())
+        }
+        let endianness = self.endianness;
+        let mut body = vec![];
+        put_u16(&mut body, link_type as u16, endianness);
+        put_u16(&mut body, 0, endianness); //reserved
+        put_u32(&mut body, interface.snap_length, endianness);
+
+        let options_start = body.len();
+        for comment in interface.comments.iter() {
+            put_option(&mut body, OPTION_COMMENT, comment.as_bytes(), endianness)?;
+        }
+        if let Some(ref name) = interface.name {
+            put_option(&mut body, OPTION_IF_NAME, name.as_bytes(), endianness)?;
+        }
+        if let Some(ref description) = interface.description {
+            put_option(&mut body, OPTION_IF_DESCRIPTION, description.as_bytes(), endianness)?;
+        }
+        if interface.timestamp_resolution != TimestampResolution::default() {
+            put_option(&mut body, OPTION_IF_TSRESOL, &[interface.timestamp_resolution.value()], endianness)?;
+        }
+        if interface.timestamp_offset != 0 {
+            let mut offset = vec![];
+            put_u64(&mut offset, interface.timestamp_offset as u64, endianness);
+            put_option(&mut body, OPTION_IF_TSOFFSET, &offset, endianness)?;
+        }
+        if let Some(ref os) = interface.os {
+            put_option(&mut body, OPTION_IF_OS, os.as_bytes(), endianness)?;
+        }
+        if let Some(ref hardware) = interface.hardware {
+            put_option(&mut body, OPTION_IF_HARDWARE, hardware.as_bytes(), endianness)?;
+        }
+        if body.len() > options_start {
+            put_option(&mut body, OPTION_END, &[], endianness)?;
+        }
+
+        self.write_block(INTERFACE_DESCRIPTION_BLOCK, &body)?;
+        self.interfaces.push(interface);
+        Ok(self.interfaces.len() as u32 - 1)
+    }
+
+    ///
+    /// Write `record` as captured on interface `interface_id`, truncating its payload to the
+    /// interface's snap length. Comments on the record are written as opt_comment options, and are
+    /// rejected if longer than the 65535 bytes an option can hold. Records the interface can't
+    /// timestamp, before its offset or too far past it for its resolution, are rejected too.
+    ///
+    pub fn write_record(&mut self, interface_id: u32, record: &PcapRecord) -> errors::Result<()> {
+        let endianness = self.endianness;
+        let (units, snap_length) = match self.interfaces.get(interface_id as usize) {
+            Some(interface) => (interface.units(*record.timestamp())?, interface.snap_length),
+            None => return Err(errors::Error::from(format!("Interface {} has not been added", interface_id)))
+        };
+        let payload = match snap_length as usize {
+            0 => &record.payload()[..],
+            snap_length => &record.payload()[..std::cmp::min(snap_length, record.payload().len())]
+        };
+
+        let mut body = vec![];
+        put_u32(&mut body, interface_id, endianness);
+        put_u32(&mut body, (units >> 32) as u32, endianness);
+        put_u32(&mut body, units as u32, endianness);
+        put_u32(&mut body, payload.len() as u32, endianness);
+        put_u32(&mut body, record.original_length(), endianness);
+        body.extend_from_slice(payload);
+        body.extend_from_slice(&[0u8; 3][..padding(payload.len())]);
+        for comment in record.comments() {
+            put_option(&mut body, OPTION_COMMENT, comment.as_bytes(), endianness)?;
+        }
+        if !record.comments().is_empty() {
+            put_option(&mut body, OPTION_END, &[], endianness)?;
+        }
+
+        self.write_block(ENHANCED_PACKET_BLOCK, &body)?;
+        self.records += 1;
+        Ok(())
+    }
+
+    ///
+    /// Embed `secrets`, e.g. a TLS key log, so readers can decrypt the packets written after it
+    ///
+    pub fn write_secrets(&mut self, secrets: &DecryptionSecrets) -> errors::Result<()> {
+        let endianness = self.endianness;
+        let mut body = vec![];
+        put_u32(&mut body, secrets.secrets_type.value(), endianness);
+        put_u32(&mut body, secrets.data.len() as u32, endianness);
+        body.extend_from_slice(&secrets.data);
+        body.extend_from_slice(&[0u8; 3][..padding(secrets.data.len())]);
+        self.write_block(DECRYPTION_SECRETS_BLOCK, &body)
+    }
+
+    ///
+    /// Write a name resolution block resolving the address of each of `records` to its names
+    ///
+    pub fn write_names(&mut self, records: &[NameRecord]) -> errors::Result<()> {
+        let endianness = self.endianness;
+        let mut body = vec![];
+        for record in records {
+            let record_type = if record.address.is_ipv4() { NRB_RECORD_IPV4 } else { NRB_RECORD_IPV6 };
+            put_option(&mut body, record_type, &record.value(), endianness)?;
+        }
+        put_option(&mut body, NRB_RECORD_END, &[], endianness)?;
+        self.write_block(NAME_RESOLUTION_BLOCK, &body)
+    }
+
+    ///
+    /// Write each of `records` as captured on interface `interface_id`
+    ///
+    pub fn write_records<'a, I>(&mut self, interface_id: u32, records: I) -> errors::Result<()> where I: IntoIterator<Item=&'a PcapRecord> {
+        for record in records {
+            self.write_record(interface_id, record)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+    use layer3::InternetProtocolId;
+
+    const RAW_DATA: &'static [u8] = &[
+        //section header block
+        0x0Au8, 0x0Du8, 0x0Du8, 0x0Au8, //block type
+        0x1Cu8, 0x00u8, 0x00u8, 0x00u8, //block length, 28
+        0x4Du8, 0x3Cu8, 0x2Bu8, 0x1Au8, //byte order magic, little endian
+        0x01u8, 0x00u8, //version major, 1
+        0x00u8, 0x00u8, //version minor, 0
+        0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8, //section length, unknown
+        0x1Cu8, 0x00u8, 0x00u8, 0x00u8, //block length, 28
+        //interface description block
+        0x01u8, 0x00u8, 0x00u8, 0x00u8, //block type
+        0x28u8, 0x00u8, 0x00u8, 0x00u8, //block length, 40
+        0x01u8, 0x00u8, //link type, ethernet
+        0x00u8, 0x00u8, //reserved
+        0x00u8, 0x00u8, 0x04u8, 0x00u8, //snap length, 262144
+        0x02u8, 0x00u8, 0x04u8, 0x00u8, //if_name, 4 bytes
+        b'e', b't', b'h', b'0',
+        0x09u8, 0x00u8, 0x01u8, 0x00u8, //if_tsresol, 1 byte
+        0x09u8, 0x00u8, 0x00u8, 0x00u8, //nanoseconds, padded
+        0x00u8, 0x00u8, 0x00u8, 0x00u8, //end of options
+        0x28u8, 0x00u8, 0x00u8, 0x00u8, //block length, 40
+        //name resolution block, with no records
+        0x04u8, 0x00u8, 0x00u8, 0x00u8, //block type
+        0x10u8, 0x00u8, 0x00u8, 0x00u8, //block length, 16
+        0x00u8, 0x00u8, 0x00u8, 0x00u8, //end of records
+        0x10u8, 0x00u8, 0x00u8, 0x00u8, //block length, 16
+        //enhanced packet block
+        0x06u8, 0x00u8, 0x00u8, 0x00u8, //block type
+        0x28u8, 0x00u8, 0x00u8, 0x00u8, //block length, 40
+        0x00u8, 0x00u8, 0x00u8, 0x00u8, //interface id, 0
+        0xABu8, 0x14u8, 0x34u8, 0x15u8, //timestamp high
+        0x08u8, 0x43u8, 0x53u8, 0x35u8, //timestamp low, 1527868899152053000ns
+        0x05u8, 0x00u8, 0x00u8, 0x00u8, //captured length, 5
+        0x40u8, 0x00u8, 0x00u8, 0x00u8, //original length, 64
+        0x01u8, 0x02u8, 0x03u8, 0x04u8, 0x05u8, 0x00u8, 0x00u8, 0x00u8, //payload, padded
+        0x28u8, 0x00u8, 0x00u8, 0x00u8 //block length, 40
+    ];
+
+    #[test]
+    fn parse_pcapng() {
+        let _ = env_logger::try_init();
+
+        assert!(is_pcapng(RAW_DATA));
+        let (rem, capture) = Capture::parse(RAW_DATA).expect("Failed to parse capture");
+        assert!(rem.is_empty());
+
+        assert_eq!(capture.sections().len(), 1);
+        let section = &capture.sections()[0];
+        assert_eq!(section.header().endianness(), Endianness::Little);
+        assert_eq!(section.header().version_major(), 1);
+        assert_eq!(section.header().section_length(), None);
+
+        let interface = &section.interfaces()[0];
+        assert_eq!(interface.link_type(), LinkType::Ethernet);
+        assert_eq!(interface.snap_length(), 262144);
+        assert_eq!(interface.name(), Some("eth0"));
+        assert_eq!(interface.timestamp_resolution(), TimestampResolution::Decimal(9));
+
+        assert_eq!(capture.records().len(), 1);
+        let record = &capture.records()[0];
+        assert_eq!(*record.timestamp(), std::time::UNIX_EPOCH + std::time::Duration::new(1527868899, 152053000));
+        assert_eq!(record.actual_length(), 5);
+        assert_eq!(record.original_length(), 64);
+        assert_eq!(record.payload(), &vec![0x01u8, 0x02u8, 0x03u8, 0x04u8, 0x05u8]);
+        assert_eq!( (record.interface_id(), record.link_type()), (0, LinkType::Ethernet) );
+        assert_eq!(record.timestamp_resolution(), TimestampResolution::Decimal(9));
+        assert_eq!(capture.interface(0), Some(interface));
+
+        let info = capture.interface_info(0).expect("No interface");
+        assert_eq!( (info.section(), info.id()), (0, 0) );
+        assert_eq!(info.name(), Some("eth0"));
+        assert_eq!(info.snap_length(), 262144);
+        assert!(info.os().is_none());
+        assert_eq!(capture.interface_infos(), vec![info]);
+        assert!(capture.interface_info(1).is_none());
+
+        //a trailing partial block is left unparsed
+        let (rem, capture) = Capture::parse(&RAW_DATA[..RAW_DATA.len() - 4]).expect("Failed to parse capture");
+        assert_eq!(rem.len(), 36);
+        assert!(capture.records().is_empty());
+    }
+
+    #[test]
+    fn parse_interface_options() {
+        let _ = env_logger::try_init();
+
+        let mut data = RAW_DATA[..28].to_vec();
+        data.extend_from_slice(&[
+            //interface description block
+            0x01u8, 0x00u8, 0x00u8, 0x00u8, //block type
+            0x3Cu8, 0x00u8, 0x00u8, 0x00u8, //block length, 60
+            0x01u8, 0x00u8, //link type, ethernet
+            0x00u8, 0x00u8, //reserved
+            0xFFu8, 0xFFu8, 0x00u8, 0x00u8, //snap length, 65535
+            0x01u8, 0x00u8, 0x05u8, 0x00u8, //opt_comment, 5 bytes
+            b'f', b'i', b'r', b's', b't', 0x00u8, 0x00u8, 0x00u8,
+            0x01u8, 0x00u8, 0x06u8, 0x00u8, //opt_comment, 6 bytes
+            b's', b'e', b'c', b'o', b'n', b'd', 0x00u8, 0x00u8,
+            0x0Fu8, 0x00u8, 0x06u8, 0x00u8, //if_hardware, 6 bytes
+            b'x', b'8', b'6', b'_', b'6', b'4', 0x00u8, 0x00u8,
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, //end of options
+            0x3Cu8, 0x00u8, 0x00u8, 0x00u8 //block length, 60
+        ]);
+
+        let (rem, capture) = Capture::parse(&data).expect("Failed to parse capture");
+        assert!(rem.is_empty());
+        let interface = &capture.sections()[0].interfaces()[0];
+        assert_eq!(interface.hardware(), Some("x86_64"));
+        assert_eq!(interface.comments(), &vec!["first".to_string(), "second".to_string()]);
+        assert!(interface.name().is_none());
+    }
+
+    #[test]
+    fn parse_pcapng_errors() {
+        let _ = env_logger::try_init();
+
+        let mut wrong_magic = RAW_DATA.to_vec();
+        wrong_magic[8] = 0x00u8;
+        let err = Capture::parse(&wrong_magic).err().map(|e| errors::Error::from_nom(e, errors::Layer::Pcapng, &wrong_magic)).expect("Parsed");
+        assert_eq!(format!("{}", err), "pcapng header at offset 0: expected byte order magic 0x1A2B3C4D");
+
+        let mut mismatched_length = RAW_DATA.to_vec();
+        mismatched_length[28 + 36] = 0x2Cu8;
+        assert!(Capture::parse(&mismatched_length).is_err());
+
+        let mut unknown_interface = RAW_DATA.to_vec();
+        unknown_interface[28 + 40 + 16 + 8] = 0x01u8;
+        let err = Capture::parse(&unknown_interface).err().map(|e| errors::Error::from_nom(e, errors::Layer::Pcapng, &unknown_interface)).expect("Parsed");
+        assert_eq!(format!("{}", err), "pcapng header at offset 84: expected interface described earlier in the section");
+
+        assert!(Capture::parse(&RAW_DATA[28..]).is_err());
+        assert!(!is_pcapng(&RAW_DATA[28..]));
+    }
+
+    #[cfg(feature = "writer")]
+    #[test]
+    fn timestamp_overflow() {
+        let _ = env_logger::try_init();
+
+        let mut interface = Interface::new(LinkType::Ethernet, 0);
+        interface.set_timestamp_offset(i64::MAX);
+        let mut writer = Writer::with_endianness(vec![], Endianness::Little).expect("Could not write section header");
+        let interface_id = writer.add_interface(interface.clone()).expect("Could not add interface");
+        let other = writer.add_interface(Interface::new(LinkType::Ethernet, 0)).expect("Could not add interface");
+        //times before the offset can't be written
+        let record = PcapRecord::new(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1), 1, 1, vec![0x01u8]);
+        assert!(writer.write_record(interface_id, &record).is_err());
+        writer.write_record(other, &record).expect("Could not write record");
+        let mut bytes = writer.into_inner();
+        //move the packet to the interface with the offset, past the range of system time
+        bytes[28 + 36 + 20 + 8] = 0x00u8;
+
+        let err = Capture::parse(&bytes).err().map(|e| errors::Error::from_nom(e, errors::Layer::Pcapng, &bytes)).expect("Parsed");
+        assert_eq!(format!("{}", err), "pcapng header at offset 84: expected timestamp and interface offset within the range of system time after the epoch");
+
+        assert!(interface.timestamp(u64::MAX).is_err());
+        assert!(interface.units(std::time::UNIX_EPOCH).is_err());
+
+        //current times are past the 64 bits of a picosecond timestamp
+        let mut picos = Interface::new(LinkType::Ethernet, 0);
+        picos.set_timestamp_resolution(TimestampResolution::Decimal(12));
+        let mut writer = Writer::new(vec![]).expect("Could not write section header");
+        let interface_id = writer.add_interface(picos).expect("Could not add interface");
+        let record = PcapRecord::new(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000), 1, 1, vec![0x01u8]);
+        assert!(writer.write_record(interface_id, &record).is_err());
+        assert_eq!(writer.records(), 0);
+
+        //a negative offset putting a packet before the epoch
+        let mut behind = Interface::new(LinkType::Ethernet, 0);
+        behind.set_timestamp_offset(-10);
+        assert_eq!(behind.timestamp(11_000_000).expect("Could not timestamp"), std::time::UNIX_EPOCH + std::time::Duration::from_secs(1));
+        assert!(behind.timestamp(9_000_000).is_err());
+    }
+
+    #[test]
+    fn timestamp_resolution() {
+        let micros = TimestampResolution::default();
+        assert_eq!(micros.to_duration(1_500_000), std::time::Duration::from_millis(1500));
+        assert_eq!(micros.to_units(std::time::Duration::from_millis(1500)).expect("Could not convert"), 1_500_000);
+
+        //picoseconds since the epoch are past the 64 bits of a timestamp, and finer resolutions past a u128
+        let now = std::time::Duration::from_secs(1_700_000_000);
+        assert!(TimestampResolution::Decimal(12).to_units(now).is_err());
+        assert!(TimestampResolution::Decimal(30).to_units(now).is_err());
+        assert_eq!(TimestampResolution::Decimal(12).to_units(std::time::Duration::from_secs(1)).expect("Could not convert"), 1_000_000_000_000);
+
+        let binary = TimestampResolution::new(0x82);
+        assert_eq!(binary, TimestampResolution::Binary(2));
+        assert_eq!(binary.value(), 0x82);
+        assert_eq!(binary.to_duration(5), std::time::Duration::from_millis(1250));
+    }
+
+    #[cfg(feature = "writer")]
+    #[test]
+    fn write_pcapng() {
+        let _ = env_logger::try_init();
+
+        let records = vec![
+            PcapRecord::new(std::time::UNIX_EPOCH + std::time::Duration::new(1527868899, 152053789), 5, 5, vec![0x01u8, 0x02u8, 0x03u8, 0x04u8, 0x05u8]),
+            PcapRecord::new(std::time::UNIX_EPOCH + std::time::Duration::new(1527868900, 0), 8, 1500, vec![0xFFu8; 8])
+        ];
+
+        let mut writer = Writer::with_endianness(vec![], Endianness::Big).expect("Could not write section header");
+        let micros = writer.add_interface(Interface::new(LinkType::Ethernet, 4)).expect("Could not add interface");
+        let mut interface = Interface::new(LinkType::Raw, 0);
+        interface.set_name(Some("tun0".to_string()));
+        interface.set_description(Some("VPN tunnel".to_string()));
+        interface.set_os(Some("Linux 6.1".to_string()));
+        interface.set_hardware(Some("Intel 82579LM".to_string()));
+        interface.set_comments(vec!["uplink".to_string()]);
+        interface.set_timestamp_resolution(TimestampResolution::Decimal(9));
+        interface.set_timestamp_offset(1527868800);
+        let nanos = writer.add_interface(interface.clone()).expect("Could not add interface");
+        assert_eq!( (micros, nanos), (0, 1) );
+        //link types are 16 bits in pcapng
+        assert!(writer.add_interface(Interface::new(LinkType::Unknown(0x10000), 0)).is_err());
+
+        writer.write_records(micros, &records).expect("Could not write records");
+        writer.write_records(nanos, &records).expect("Could not write records");
+        assert!(writer.write_record(2, &records[0]).is_err());
+        assert_eq!(writer.records(), 4);
+
+        let bytes = writer.into_inner();
+        assert_eq!(&bytes[..12], &[0x0Au8, 0x0Du8, 0x0Du8, 0x0Au8, 0x00u8, 0x00u8, 0x00u8, 0x1Cu8, 0x1Au8, 0x2Bu8, 0x3Cu8, 0x4Du8][..]);
+        let (rem, capture) = Capture::parse(&bytes).expect("Failed to parse capture");
+        assert!(rem.is_empty());
+
+        assert_eq!(capture.sections()[0].header().endianness(), Endianness::Big);
+        assert_eq!(capture.sections()[0].interfaces()[1], interface);
+        let written = capture.records();
+        assert_eq!(written.len(), 4);
+        //microseconds, truncated to the snap length
+        assert_eq!(*written[0].timestamp(), std::time::UNIX_EPOCH + std::time::Duration::new(1527868899, 152053000));
+        assert_eq!(written[0].payload(), &vec![0x01u8, 0x02u8, 0x03u8, 0x04u8]);
+        assert_eq!(written[0].original_length(), 5);
+        assert_eq!(written[1].original_length(), 1500);
+        //nanoseconds, relative to the offset
+        assert!(written[2..].iter().zip(records.iter()).all(|(w, r)| w.timestamp() == r.timestamp() && w.payload() == r.payload()));
+        //each record carries the interface it was captured on
+        assert_eq!( (written[0].interface_id(), written[0].link_type()), (0, LinkType::Ethernet) );
+        assert_eq!(written[0].timestamp_resolution(), TimestampResolution::Decimal(6));
+        assert_eq!( (written[2].interface_id(), written[2].link_type()), (1, LinkType::Raw) );
+        assert_eq!(written[3].timestamp_resolution(), TimestampResolution::Decimal(9));
+        assert_eq!(capture.interface(3).and_then(|i| i.name()), Some("tun0"));
+
+        let info = capture.interface_info(3).expect("No interface");
+        assert_eq!(capture.interface_key(3), Some( (0, 1) ));
+        assert_eq!(info.os(), Some("Linux 6.1"));
+        assert_eq!(info.description(), Some("VPN tunnel"));
+        assert_eq!(info.hardware(), Some("Intel 82579LM"));
+        assert_eq!(info.comments(), &vec!["uplink".to_string()]);
+        assert_eq!(info.timestamp_resolution(), TimestampResolution::Decimal(9));
+        assert_eq!(capture.interface_infos().len(), 2);
+    }
+
+    #[cfg(feature = "writer")]
+    #[test]
+    fn section_header_options() {
+        let _ = env_logger::try_init();
+
+        let mut header = SectionHeader::new(Endianness::Little);
+        header.set_hardware(Some("x86_64".to_string()));
+        header.set_os(Some("Linux 6.1".to_string()));
+        header.set_user_application(Some("Dumpcap (Wireshark) 4.2.2".to_string()));
+        header.set_comments(vec!["office uplink".to_string(), "second hour".to_string()]);
+
+        let mut writer = Writer::with_section_header(vec![], &header).expect("Could not write section header");
+        writer.add_interface(Interface::new(LinkType::Ethernet, 0)).expect("Could not add interface");
+        let bytes = writer.into_inner();
+
+        let (rem, capture) = Capture::parse(&bytes).expect("Failed to parse capture");
+        assert!(rem.is_empty());
+        let parsed = capture.sections()[0].header();
+        assert_eq!(parsed, &header);
+        assert_eq!(parsed.hardware(), Some("x86_64"));
+        assert_eq!(parsed.os(), Some("Linux 6.1"));
+        assert_eq!(parsed.user_application(), Some("Dumpcap (Wireshark) 4.2.2"));
+        assert_eq!(parsed.comments().len(), 2);
+        assert_eq!(capture.sections()[0].interfaces().len(), 1);
+
+        let (_, capture) = Capture::parse(RAW_DATA).expect("Failed to parse capture");
+        assert_eq!(capture.sections()[0].header(), &SectionHeader::new(Endianness::Little));
+    }
+
+    #[cfg(feature = "writer")]
+    #[test]
+    fn packet_comments() {
+        let _ = env_logger::try_init();
+
+        let mut record = PcapRecord::new(std::time::UNIX_EPOCH + std::time::Duration::new(1527868899, 0), 3, 3, vec![0x01u8, 0x02u8, 0x03u8]);
+        record.set_comments(vec!["retransmission".to_string(), "seen twice".to_string()]);
+        let plain = PcapRecord::new(std::time::UNIX_EPOCH + std::time::Duration::new(1527868900, 0), 1, 1, vec![0x04u8]);
+
+        let mut writer = Writer::with_endianness(vec![], Endianness::Little).expect("Could not write section header");
+        let interface_id = writer.add_interface(Interface::new(LinkType::Ethernet, 0)).expect("Could not add interface");
+        writer.write_records(interface_id, vec![&record, &plain]).expect("Could not write records");
+        let bytes = writer.into_inner();
+
+        let (_, capture) = Capture::parse(&bytes).expect("Failed to parse capture");
+        assert_eq!(capture.records(), &vec![record.clone(), plain]);
+        assert_eq!(capture.records()[0].comments(), &vec!["retransmission".to_string(), "seen twice".to_string()]);
+        assert!(capture.records()[1].comments().is_empty());
+
+        //comments are dropped from libpcap captures
+        let (_, parsed) = PcapRecord::parse(&record.to_bytes(Endianness::Big), Endianness::Big).expect("Could not parse");
+        assert!(parsed.comments().is_empty());
+
+        //comments longer than an option can hold are rejected, without writing the record
+        let mut writer = Writer::with_endianness(vec![], Endianness::Little).expect("Could not write section header");
+        let interface_id = writer.add_interface(Interface::new(LinkType::Ethernet, 0)).expect("Could not add interface");
+        record.set_comments(vec!["a".repeat(65535)]);
+        writer.write_record(interface_id, &record).expect("Could not write record");
+        record.set_comments(vec!["a".repeat(65536)]);
+        assert!(writer.write_record(interface_id, &record).is_err());
+        assert_eq!(writer.records(), 1);
+        let (_, capture) = Capture::parse(&writer.into_inner()).expect("Failed to parse capture");
+        assert_eq!(capture.records()[0].comments()[0].len(), 65535);
+        assert_eq!(capture.records().len(), 1);
+    }
+
+    #[cfg(feature = "writer")]
+    #[test]
+    fn decryption_secrets() {
+        let _ = env_logger::try_init();
+
+        let key_log = "CLIENT_RANDOM 0102 0304\nCLIENT_RANDOM 0506 0708";
+        let mut writer = Writer::with_endianness(vec![], Endianness::Big).expect("Could not write section header");
+        writer.write_secrets(&DecryptionSecrets::new(SecretsType::TlsKeyLog, key_log.as_bytes().to_vec())).expect("Could not write secrets");
+        writer.write_secrets(&DecryptionSecrets::new(SecretsType::new(0x57474B4Cu32), vec![0x01u8, 0x02u8])).expect("Could not write secrets");
+        writer.write_secrets(&DecryptionSecrets::new(SecretsType::TlsKeyLog, b"CLIENT_RANDOM 090A 0B0C\n".to_vec())).expect("Could not write secrets");
+        let bytes = writer.into_inner();
+
+        let (_, block) = Block::parse(&bytes[28..], Endianness::Big).expect("Could not parse block");
+        match block {
+            Block::DecryptionSecrets(secrets) => {
+                assert_eq!(secrets.secrets_type(), SecretsType::TlsKeyLog);
+                assert_eq!(secrets.as_text(), Some(key_log));
+            }
+            other => panic!("Parsed {:?}", other)
+        }
+
+        let (rem, capture) = Capture::parse(&bytes).expect("Failed to parse capture");
+        assert!(rem.is_empty());
+        assert_eq!(capture.secrets().len(), 3);
+        assert_eq!(capture.secrets()[1].secrets_type(), SecretsType::WireGuardKeyLog);
+        assert_eq!(capture.secrets()[1].data(), &vec![0x01u8, 0x02u8]);
+        assert_eq!(capture.tls_key_log(), Some("CLIENT_RANDOM 0102 0304\nCLIENT_RANDOM 0506 0708\nCLIENT_RANDOM 090A 0B0C\n".to_string()));
+
+        let (_, capture) = Capture::parse(RAW_DATA).expect("Failed to parse capture");
+        assert!(capture.tls_key_log().is_none());
+    }
+
+    #[cfg(feature = "writer")]
+    #[test]
+    fn name_resolution() {
+        let _ = env_logger::try_init();
+
+        let server = std::net::IpAddr::V4(std::net::Ipv4Addr::new(192, 0, 2, 1));
+        let client = std::net::IpAddr::V6(std::net::Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2));
+        let mut writer = Writer::with_endianness(vec![], Endianness::Big).expect("Could not write section header");
+        writer.write_names(&[
+            NameRecord::new(server, vec!["www.example.com".to_string(), "example.com".to_string()]),
+            NameRecord::new(client, vec!["client.example.com".to_string()])
+        ]).expect("Could not write names");
+        writer.write_names(&[NameRecord::new(server, vec!["example.com".to_string(), "cdn.example.com".to_string()])]).expect("Could not write names");
+        let bytes = writer.into_inner();
+
+        let (_, block) = Block::parse(&bytes[28..], Endianness::Big).expect("Could not parse block");
+        match block {
+            Block::NameResolution(records) => {
+                assert_eq!(records.len(), 2);
+                assert_eq!(records[0].address(), server);
+                assert_eq!(records[0].names(), &vec!["www.example.com".to_string(), "example.com".to_string()]);
+                assert_eq!(records[1].address(), client);
+            }
+            other => panic!("Parsed {:?}", other)
+        }
+
+        let (rem, capture) = Capture::parse(&bytes).expect("Failed to parse capture");
+        assert!(rem.is_empty());
+        assert_eq!(capture.names().len(), 2);
+        assert_eq!(capture.names().get(&server), Some(&vec!["www.example.com".to_string(), "example.com".to_string(), "cdn.example.com".to_string()]));
+        assert_eq!(capture.name(&server), Some("www.example.com"));
+        assert_eq!(capture.name(&client), Some("client.example.com"));
+
+        let (_, capture) = Capture::parse(RAW_DATA).expect("Failed to parse capture");
+        assert!(capture.names().is_empty());
+    }
+
+    #[cfg(all(feature = "flows", feature = "writer"))]
+    #[test]
+    fn name_resolution_flow_names() {
+        use super::super::flow::Device;
+
+        let _ = env_logger::try_init();
+
+        let record = PcapRecord::new(std::time::UNIX_EPOCH, 0, 0, vec![]);
+        let flow = Flow {
+            record: record,
+            source: Device {
+                mac: None,
+                ip: std::net::IpAddr::V4(std::net::Ipv4Addr::new(192, 0, 2, 2)),
+                port: 50871
+            },
+            destination: Device {
+                mac: None,
+                ip: std::net::IpAddr::V4(std::net::Ipv4Addr::new(192, 0, 2, 1)),
+                port: 80
+            },
+            protocol: Some(InternetProtocolId::Tcp),
+            vlan: Vlan::untagged(),
+            inner_vlans: vec![],
+            delivery: Delivery::Unicast,
+            reassembled: false,
+            #[cfg(feature = "tunnels")]
+            tunnel: None,
+            arp: None
+        };
+
+        let mut writer = Writer::new(vec![]).expect("Could not write section header");
+        writer.write_names(&[NameRecord::new(flow.destination().ip, vec!["www.example.com".to_string()])]).expect("Could not write names");
+        let bytes = writer.into_inner();
+
+        let (_, capture) = Capture::parse(&bytes).expect("Failed to parse capture");
+        assert_eq!(capture.flow_names(&flow), (None, Some("www.example.com")));
+    }
+}
@@ -0,0 +1,592 @@
+use super::prelude::*;
+use super::filter::Filter;
+use super::layer2::Layer2;
+use super::layer2::ethernet::{EthernetTypeId, Layer3Id};
+use super::layer3::InternetProtocolId;
+use super::link_type::LinkType;
+use super::parsed::{self, ParseConfig, ParsedRecord};
+use super::pcapng;
+use super::writer::CaptureWriter;
+
+use std;
+use std::collections::HashMap;
+
+const ETHER_TYPE_OFFSET: usize = 2 * MAC_LENGTH;
+const SLL_ADDRESS_OFFSET: usize = 6;
+const SLL2_ADDRESS_OFFSET: usize = 12;
+const IEEE80211_ADDRESS_OFFSETS: [usize; 4] = [4, 10, 16, 24];
+const IPV4_HEADER_LENGTH: usize = 20;
+const IPV6_HEADER_LENGTH: usize = 40;
+
+///
+/// Destination of the records a pipeline keeps
+///
+pub trait RecordSink {
+    fn write_record(&mut self, record: &PcapRecord) -> errors::Result<()>;
+}
+
+impl<W: std::io::Write> RecordSink for CaptureWriter<W> {
+    fn write_record(&mut self, record: &PcapRecord) -> errors::Result<()> {
+        CaptureWriter::write_record(self, record)
+    }
+}
+
+///
+/// Records are written as captured on the interface of their interface id, which must have been
+/// added to the writer
+///
+impl<W: std::io::Write> RecordSink for pcapng::Writer<W> {
+    fn write_record(&mut self, record: &PcapRecord) -> errors::Result<()> {
+        pcapng::Writer::write_record(self, record.interface_id(), record)
+    }
+}
+
+impl RecordSink for std::vec::Vec<PcapRecord> {
+    fn write_record(&mut self, record: &PcapRecord) -> errors::Result<()> {
+        self.push(record.clone());
+        Ok(())
+    }
+}
+
+///
+/// Rewrite applied to each record a pipeline keeps, in the order added
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Transform {
+    ///
+    /// Keep at most this many bytes of each record, as `editcap -s` does. The original length is
+    /// kept.
+    ///
+    Truncate(usize),
+    ///
+    /// Replace addresses with those of an `Anonymizer`
+    ///
+    Anonymize
+}
+
+///
+/// Replaces the mac and ip addresses of records with stand-ins, the same address always getting
+/// the same stand-in, so traffic can be shared without identifying hosts. Macs are rewritten in
+/// ethernet, linux cooked and 802.11 headers, and ip addresses in the ipv4 and ipv6 packets after
+/// the link layer headers of any link type `ParsedRecord` parses, with the ipv4, tcp and udp
+/// checksums adjusted to match. Broadcast, multicast and unspecified addresses are kept, so
+/// delivery is unchanged.
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Anonymizer {
+    macs: HashMap<MacAddress, MacAddress>,
+    ipv4: HashMap<std::net::Ipv4Addr, std::net::Ipv4Addr>,
+    ipv6: HashMap<std::net::Ipv6Addr, std::net::Ipv6Addr>
+}
+
+impl Anonymizer {
+    ///
+    /// Stand-in for `mac`, locally administered addresses from 02:00:00:00:00:01
+    ///
+    pub fn mac(&mut self, mac: MacAddress) -> MacAddress {
+        if mac.0[0] & 0x01 != 0 {
+            return mac
+        }
+        let next = self.macs.len() as u32 + 1;
+        *self.macs.entry(mac).or_insert_with(|| {
+            MacAddress([0x02u8, 0x00u8, (next >> 24) as u8, (next >> 16) as u8, (next >> 8) as u8, next as u8])
+        })
+    }
+
+    ///
+    /// Stand-in for `ip`, addresses from 10.0.0.1 for ipv4 and from fd00::1 for ipv6
+    ///
+    pub fn ip(&mut self, ip: std::net::IpAddr) -> std::net::IpAddr {
+        match ip {
+            std::net::IpAddr::V4(v4) if v4.is_broadcast() || v4.is_multicast() || v4.is_unspecified() => ip,
+            std::net::IpAddr::V4(v4) => {
+                let next = self.ipv4.len() as u32 + 1;
+                std::net::IpAddr::V4(*self.ipv4.entry(v4).or_insert_with(|| std::net::Ipv4Addr::from(0x0A000000u32 | (next & 0x00FFFFFF))))
+            }
+            std::net::IpAddr::V6(v6) if v6.is_multicast() || v6.is_unspecified() => ip,
+            std::net::IpAddr::V6(v6) => {
+                let next = self.ipv6.len() as u128 + 1;
+                std::net::IpAddr::V6(*self.ipv6.entry(v6).or_insert_with(|| std::net::Ipv6Addr::from(0xFD00u128 << 112 | next)))
+            }
+        }
+    }
+
+    ///
+    /// `record` with the addresses of its frame replaced, parsed with the layer 2 parser of its link
+    /// type
+    ///
+    pub fn anonymize(&mut self, record: &PcapRecord) -> errors::Result<PcapRecord> {
+        self.anonymize_with(record, &ParseConfig::for_link_type(record.link_type()))
+    }
+
+    ///
+    /// `record` with the addresses of its frame replaced, parsed according to `config`. Records of
+    /// link types without a layer 2 parser fail with `ErrorKind::LinkType`, rather than being kept
+    /// with their addresses. Records too short for their link layer header are kept as is, though
+    /// the macs of ethernet frames long enough to hold them are replaced, as are the ip addresses
+    /// of packets cut short before them. The rest of the record, e.g. its interface and link type,
+    /// is kept.
+    ///
+    pub fn anonymize_with(&mut self, record: &PcapRecord, config: &ParseConfig) -> errors::Result<PcapRecord> {
+        let mut payload = record.payload().clone();
+        let (macs, packet) = match parsed::parse_layer2(&payload, config, &mut vec![]) {
+            Ok(layer2) => {
+                let packet = match layer2.ether_type() {
+                    Some(EthernetTypeId::L3(layer3)) => Some( (layer3, layer2.header_length()) ),
+                    _ => None
+                };
+                (mac_offsets(&layer2), packet)
+            }
+            Err(e) => {
+                if let errors::ErrorKind::LinkType(_) = *e.kind() {
+                    return Err(e)
+                }
+                debug!("Anonymizing at most the macs of a record that doesn't parse: {}", e);
+                let macs = if config.link_type == LinkType::Ethernet && payload.len() >= ETHER_TYPE_OFFSET + 2 {
+                    vec![0, MAC_LENGTH]
+                } else {
+                    vec![]
+                };
+                (macs, None)
+            }
+        };
+
+        for offset in macs {
+            let mac = self.mac(MacAddress(*array_ref![payload, offset, MAC_LENGTH]));
+            payload[offset..offset + MAC_LENGTH].copy_from_slice(&mac.0);
+        }
+        if let Some( (layer3, header_length) ) = packet {
+            self.anonymize_packet(layer3, &mut payload[header_length..]);
+        }
+
+        let mut anonymized = record.clone();
+        anonymized.set_payload(payload);
+        Ok(anonymized)
+    }
+
+    fn anonymize_packet(&mut self, layer3: Layer3Id, packet: &mut [u8]) {
+        match layer3 {
+            Layer3Id::IPv4 if packet.len() >= IPV4_HEADER_LENGTH => {
+                let header_length = (packet[0] & 0x0F) as usize * 4;
+                let fragment_offset = read_u16(packet, 6) & 0x1FFF;
+                let protocol = InternetProtocolId::new(packet[9]);
+                let (old, new) = self.replace_ips(packet, 12, 4);
+                let checksum = adjust_checksum(read_u16(packet, 10), &old, &new);
+                write_u16(packet, 10, checksum);
+                if fragment_offset == 0 && packet.len() >= header_length {
+                    adjust_segment_checksum(&mut packet[header_length..], protocol, &old, &new);
+                }
+            }
+            Layer3Id::IPv6 if packet.len() >= IPV6_HEADER_LENGTH => {
+                let protocol = InternetProtocolId::new(packet[6]);
+                let (old, new) = self.replace_ips(packet, 8, 16);
+                adjust_segment_checksum(&mut packet[IPV6_HEADER_LENGTH..], protocol, &old, &new);
+            }
+            _ => {}
+        }
+    }
+
+    ///
+    /// Replace the source and destination addresses of `length` bytes at `offset`, returning the
+    /// bytes of both before and after
+    ///
+    fn replace_ips(&mut self, packet: &mut [u8], offset: usize, length: usize) -> (std::vec::Vec<u8>, std::vec::Vec<u8>) {
+        let old = packet[offset..offset + 2 * length].to_vec();
+        for address in old.chunks(length).enumerate() {
+            let (index, octets) = address;
+            let ip = if length == 4 {
+                std::net::IpAddr::V4(std::net::Ipv4Addr::from(*array_ref![octets, 0, 4]))
+            } else {
+                std::net::IpAddr::V6(std::net::Ipv6Addr::from(*array_ref![octets, 0, 16]))
+            };
+            let replaced = match self.ip(ip) {
+                std::net::IpAddr::V4(v4) => v4.octets().to_vec(),
+                std::net::IpAddr::V6(v6) => v6.octets().to_vec()
+            };
+            let start = offset + index * length;
+            packet[start..start + length].copy_from_slice(&replaced);
+        }
+        let new = packet[offset..offset + 2 * length].to_vec();
+        (old, new)
+    }
+}
+
+///
+/// Offsets of the macs in the link layer headers of `layer2`
+///
+fn mac_offsets(layer2: &Layer2) -> std::vec::Vec<usize> {
+    match *layer2 {
+        Layer2::Ethernet(_) => vec![0, MAC_LENGTH],
+        Layer2::Sll(ref sll) if sll.src_mac().is_some() => vec![SLL_ADDRESS_OFFSET],
+        Layer2::Sll2(ref sll2) if sll2.src_mac().is_some() => vec![SLL2_ADDRESS_OFFSET],
+        Layer2::Ieee80211(ref frame) => IEEE80211_ADDRESS_OFFSETS[..frame.addresses().len()].to_vec(),
+        _ => vec![]
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    (data[offset] as u16) << 8 | data[offset + 1] as u16
+}
+
+fn write_u16(data: &mut [u8], offset: usize, value: u16) {
+    data[offset] = (value >> 8) as u8;
+    data[offset + 1] = value as u8;
+}
+
+///
+/// Checksum after the words `old` covered change to `new`, updated incrementally (RFC 1624) so
+/// segments cut short by the snap length are adjusted too
+///
+fn adjust_checksum(checksum: u16, old: &[u8], new: &[u8]) -> u16 {
+    let mut sum = !checksum as u32;
+    for (old, new) in old.chunks(2).zip(new.chunks(2)) {
+        sum += !read_u16(old, 0) as u32;
+        sum += read_u16(new, 0) as u32;
+    }
+    while sum > 0xFFFF {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+///
+/// Adjust the tcp or udp checksum of `segment` for its pseudo header addresses changing. A udp
+/// checksum of 0 means none was computed and is kept.
+///
+fn adjust_segment_checksum(segment: &mut [u8], protocol: Option<InternetProtocolId>, old: &[u8], new: &[u8]) {
+    let offset = match protocol {
+        Some(InternetProtocolId::Tcp) => 16,
+        Some(InternetProtocolId::Udp) => 6,
+        _ => return
+    };
+    if segment.len() < offset + 2 {
+        return
+    }
+    let checksum = read_u16(segment, offset);
+    let adjusted = match (protocol, checksum) {
+        (Some(InternetProtocolId::Udp), 0) => return,
+        (Some(InternetProtocolId::Udp), _) => match adjust_checksum(checksum, old, new) {
+            0 => 0xFFFF,
+            adjusted => adjusted
+        },
+        _ => adjust_checksum(checksum, old, new)
+    };
+    write_u16(segment, offset, adjusted);
+}
+
+type Predicate = Box<dyn FnMut(&PcapRecord) -> bool>;
+
+///
+/// Extracts a subset of a capture: each record is checked against an optional filter and
+/// predicate, rewritten by the transforms added, then written to a sink, e.g.
+/// `Pipeline::new().filter(Filter::ip_in("10.0.0.0/8")?).transform(Transform::Truncate(96))`.
+/// Records are filtered and anonymized with the layer 2 parser of their link type unless given a
+/// `parse_config`.
+///
+#[derive(Default)]
+pub struct Pipeline {
+    config: Option<ParseConfig>,
+    filter: Option<Filter>,
+    predicate: Option<Predicate>,
+    transforms: std::vec::Vec<Transform>,
+    anonymizer: Anonymizer
+}
+
+impl Pipeline {
+    pub fn new() -> Pipeline {
+        Pipeline::default()
+    }
+
+    ///
+    /// Keep only records `filter` matches. Ip filters match the addresses of the parsed record;
+    /// records that don't parse are matched on their macs and timestamp alone.
+    ///
+    pub fn filter(mut self, filter: Filter) -> Pipeline {
+        self.filter = Some(filter);
+        self
+    }
+
+    ///
+    /// Parse records according to `config` for the filter and anonymizer, e.g.
+    /// `ParseConfig::for_header` of the capture they come from
+    ///
+    pub fn parse_config(mut self, config: ParseConfig) -> Pipeline {
+        self.config = Some(config);
+        self
+    }
+
+    fn config(&self, record: &PcapRecord) -> ParseConfig {
+        self.config.unwrap_or_else(|| ParseConfig::for_link_type(record.link_type()))
+    }
+
+    ///
+    /// Keep only records `predicate` holds for, after any filter
+    ///
+    pub fn predicate<F>(mut self, predicate: F) -> Pipeline where F: FnMut(&PcapRecord) -> bool + 'static {
+        self.predicate = Some(Box::new(predicate));
+        self
+    }
+
+    ///
+    /// Apply `transform` to kept records, after the transforms added before it
+    ///
+    pub fn transform(mut self, transform: Transform) -> Pipeline {
+        self.transforms.push(transform);
+        self
+    }
+
+    pub fn transforms(&self) -> &std::vec::Vec<Transform> { &self.transforms }
+
+    ///
+    /// Anonymizer of the `Anonymize` transform, with the stand-ins given so far
+    ///
+    pub fn anonymizer(&self) -> &Anonymizer { &self.anonymizer }
+
+    fn matches(&mut self, record: &PcapRecord) -> bool {
+        if let Some(ref filter) = self.filter {
+            let matched = match ParsedRecord::parse_with(record.clone(), &self.config(record)) {
+                Ok(parsed) => filter.matches(&parsed),
+                Err(_) => filter.matches(record)
+            };
+            if !matched {
+                return false
+            }
+        }
+        self.predicate.as_mut().map(|predicate| predicate(record)).unwrap_or(true)
+    }
+
+    ///
+    /// `record` rewritten by the transforms, or `None` if it isn't kept. Records the transforms
+    /// can't rewrite, e.g. records of link types the anonymizer can't parse, fail.
+    ///
+    pub fn apply(&mut self, record: PcapRecord) -> errors::Result<Option<PcapRecord>> {
+        if !self.matches(&record) {
+            return Ok(None)
+        }
+        let config = self.config(&record);
+        let mut record = record;
+        for transform in self.transforms.iter() {
+            match *transform {
+                Transform::Truncate(length) if record.payload().len() > length => {
+                    let truncated = record.payload()[..length].to_vec();
+                    record.set_payload(truncated);
+                }
+                Transform::Truncate(_) => {}
+                Transform::Anonymize => record = self.anonymizer.anonymize_with(&record, &config)?
+            }
+        }
+        Ok(Some(record))
+    }
+
+    ///
+    /// Write the records kept from `records` to `sink`, returning how many were written
+    ///
+    pub fn run<I, S>(&mut self, records: I, sink: &mut S) -> errors::Result<usize>
+        where I: IntoIterator<Item=PcapRecord>, S: RecordSink
+    {
+        self.try_run(records.into_iter().map(Ok), sink)
+    }
+
+    ///
+    /// Write the records kept from `records` that may fail to read, e.g. a `CaptureReader`,
+    /// stopping at the first error
+    ///
+    pub fn try_run<I, S>(&mut self, records: I, sink: &mut S) -> errors::Result<usize>
+        where I: IntoIterator<Item=errors::Result<PcapRecord>>, S: RecordSink
+    {
+        let mut written = 0;
+        for record in records {
+            if let Some(record) = self.apply(record?)? {
+                sink.write_record(&record)?;
+                written += 1;
+            }
+        }
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+    use super::super::CaptureParser;
+    use super::super::global_header::GlobalHeader;
+    use super::super::link_type::LinkType;
+    use super::super::nom::Endianness;
+
+    const RAW_DATA: &'static [u8] = &[
+        0x00u8, 0x02u8, 0x03u8, 0x04u8, 0x05u8, 0x06u8, //dst mac 00:02:03:04:05:06
+        0xF0u8, 0xFEu8, 0xFDu8, 0xFCu8, 0xFBu8, 0xFAu8, //src mac F0:FE:FD:FC:FB:FA
+        0x08u8, 0x00u8, //ipv4
+        //ipv4
+        0x45u8, //version and header length
+        0x00u8, //tos
+        0x00u8, 0x1Cu8, //length, 20 bytes for header, 8 bytes for udp
+        0x00u8, 0x00u8, //id
+        0x00u8, 0x00u8, //flags
+        0x40u8, //ttl
+        0x11u8, //protocol, udp
+        0x00u8, 0x00u8, //checksum, filled in
+        0x01u8, 0x02u8, 0x03u8, 0x04u8, //src ip 1.2.3.4
+        0x0Au8, 0x0Bu8, 0x0Cu8, 0x0Du8, //dst ip 10.11.12.13
+        //udp
+        0xC6u8, 0xB7u8, //src port, 50871
+        0x00u8, 0x35u8, //dst port, 53
+        0x00u8, 0x08u8, //length, 8
+        0x00u8, 0x00u8 //checksum, filled in
+    ];
+
+    fn checksum(data: &[u8]) -> u16 {
+        let mut sum = 0u32;
+        for chunk in data.chunks(2) {
+            sum += (chunk[0] as u32) << 8 | chunk.get(1).cloned().unwrap_or(0) as u32;
+        }
+        while sum > 0xFFFF {
+            sum = (sum & 0xFFFF) + (sum >> 16);
+        }
+        !(sum as u16)
+    }
+
+    fn udp_checksum(packet: &[u8]) -> u16 {
+        let mut pseudo = packet[12..20].to_vec();
+        pseudo.extend_from_slice(&[0x00u8, 0x11u8, 0x00u8, 0x08u8]);
+        pseudo.extend_from_slice(&packet[20..28]);
+        checksum(&pseudo)
+    }
+
+    fn record(seconds: u64, src: u8) -> PcapRecord {
+        let mut frame = RAW_DATA.to_vec();
+        frame[29] = src;
+        let ip_checksum = checksum(&frame[14..34]);
+        write_u16(&mut frame[14..], 10, ip_checksum);
+        let udp_checksum = udp_checksum(&frame[14..]);
+        write_u16(&mut frame[14..], 26, udp_checksum);
+        PcapRecord::new(std::time::UNIX_EPOCH + std::time::Duration::from_secs(seconds), frame.len() as u32, frame.len() as u32, frame)
+    }
+
+    #[test]
+    fn anonymize_addresses() {
+        let _ = env_logger::try_init();
+
+        let mut anonymizer = Anonymizer::default();
+        let anonymized = anonymizer.anonymize(&record(1, 4)).expect("Could not anonymize");
+        let frame = anonymized.payload();
+        assert_eq!(&frame[..12], &[0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x01u8, 0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x02u8][..]);
+        assert_eq!(&frame[26..34], &[10u8, 0u8, 0u8, 1u8, 10u8, 0u8, 0u8, 2u8][..]);
+        assert_eq!(checksum(&frame[14..34]), 0);
+        assert_eq!(udp_checksum(&frame[14..]), 0);
+
+        //the same hosts get the same stand-ins
+        let again = anonymizer.anonymize(&record(2, 4)).expect("Could not anonymize");
+        assert_eq!(&again.payload()[..], &frame[..]);
+        let other = anonymizer.anonymize(&record(3, 5)).expect("Could not anonymize");
+        assert_eq!(&other.payload()[26..34], &[10u8, 0u8, 0u8, 3u8, 10u8, 0u8, 0u8, 2u8][..]);
+
+        let broadcast = "ff:ff:ff:ff:ff:ff".parse().expect("Invalid mac");
+        assert_eq!(anonymizer.mac(broadcast), broadcast);
+        let multicast = std::net::IpAddr::V4(std::net::Ipv4Addr::new(224, 0, 0, 251));
+        assert_eq!(anonymizer.ip(multicast), multicast);
+        let v6 = std::net::IpAddr::V6(std::net::Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1));
+        assert_eq!(anonymizer.ip(v6), std::net::IpAddr::V6(std::net::Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1)));
+    }
+
+    #[test]
+    fn anonymize_link_types() {
+        let _ = env_logger::try_init();
+
+        let frame = record(1, 4);
+        let mut raw = PcapRecord::new(*frame.timestamp(), 28, 200, frame.payload()[14..].to_vec());
+        raw.set_interface_id(3);
+        raw.set_link_type(LinkType::Raw);
+        raw.set_comments(vec!["tunnel".to_string()]);
+
+        let mut pipeline = Pipeline::new()
+            .parse_config(ParseConfig::for_link_type(LinkType::Raw))
+            .transform(Transform::Anonymize)
+            .transform(Transform::Truncate(24));
+        let mut kept = vec![];
+        assert_eq!(pipeline.run(vec![raw.clone()], &mut kept).expect("Could not run pipeline"), 1);
+        let packet = kept[0].payload();
+        assert_eq!(packet.len(), 24);
+        //flags, ttl and protocol are kept, the addresses replaced
+        assert_eq!(&packet[..10], &raw.payload()[..10]);
+        assert_eq!(&packet[12..20], &[10u8, 0u8, 0u8, 1u8, 10u8, 0u8, 0u8, 2u8][..]);
+        assert_eq!(checksum(&packet[..20]), 0);
+        assert_eq!( (kept[0].interface_id(), kept[0].link_type()), (3, LinkType::Raw) );
+        assert_eq!(kept[0].original_length(), 200);
+        assert_eq!(kept[0].comments(), raw.comments());
+
+        //records are parsed with their own link type without a parse config
+        let anonymized = Anonymizer::default().anonymize(&raw).expect("Could not anonymize");
+        assert_eq!(&anonymized.payload()[12..20], &[10u8, 0u8, 0u8, 1u8, 10u8, 0u8, 0u8, 2u8][..]);
+        assert_eq!(udp_checksum(anonymized.payload()), 0);
+
+        let mut cooked = vec![
+            0x00u8, 0x00u8, //packet type, host
+            0x00u8, 0x01u8, //arphrd type, ethernet
+            0x00u8, 0x06u8, //address length, 6
+            0xF0u8, 0xFEu8, 0xFDu8, 0xFCu8, 0xFBu8, 0xFAu8, 0x00u8, 0x00u8, //address F0:FE:FD:FC:FB:FA
+            0x08u8, 0x00u8 //protocol, ipv4
+        ];
+        cooked.extend_from_slice(&frame.payload()[14..]);
+        let mut cooked = PcapRecord::new(*frame.timestamp(), cooked.len() as u32, cooked.len() as u32, cooked);
+        cooked.set_link_type(LinkType::LinuxSll);
+        let anonymized = Anonymizer::default().anonymize(&cooked).expect("Could not anonymize");
+        assert_eq!(&anonymized.payload()[6..12], &[0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x01u8][..]);
+        assert_eq!(&anonymized.payload()[16 + 12..16 + 20], &[10u8, 0u8, 0u8, 1u8, 10u8, 0u8, 0u8, 2u8][..]);
+
+        //link types without a parser fail rather than leaking addresses
+        raw.set_link_type(LinkType::Ieee80211Radiotap);
+        let err = Anonymizer::default().anonymize(&raw).expect_err("Anonymized radiotap record");
+        match *err.kind() {
+            errors::ErrorKind::LinkType(link_type) => assert_eq!(link_type, LinkType::Ieee80211Radiotap),
+            ref other => panic!("Unexpected error {:?}", other)
+        }
+        assert!(Pipeline::new().transform(Transform::Anonymize).run(vec![raw], &mut vec![]).is_err());
+    }
+
+    #[test]
+    fn pipeline_to_pcapng() {
+        let _ = env_logger::try_init();
+
+        let mut writer = pcapng::Writer::new(vec![]).expect("Could not write section header");
+        writer.add_interface(pcapng::Interface::new(LinkType::Ethernet, 0)).expect("Could not add interface");
+        writer.add_interface(pcapng::Interface::new(LinkType::Ethernet, 0)).expect("Could not add interface");
+        let mut second = record(2, 4);
+        second.set_interface_id(1);
+        assert_eq!(Pipeline::new().run(vec![record(1, 4), second], &mut writer).expect("Could not run pipeline"), 2);
+
+        let (_, capture) = pcapng::Capture::parse(&writer.into_inner()).expect("Could not parse capture");
+        assert_eq!(capture.records().iter().map(|r| r.interface_id()).collect::<std::vec::Vec<_>>(), vec![0, 1]);
+    }
+
+    #[test]
+    fn pipeline_to_writer() {
+        let _ = env_logger::try_init();
+
+        let records = vec![record(1, 4), record(2, 5), record(3, 4)];
+        let header = GlobalHeader::new(LinkType::Ethernet, 65535).with_endianness(Endianness::Big);
+        let mut writer = CaptureWriter::new(vec![], header).expect("Could not write header");
+
+        let mut pipeline = Pipeline::new()
+            .filter(Filter::src_in("1.2.3.4").expect("Invalid cidr"))
+            .predicate(|r| r.timestamp().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs() > 1).unwrap_or(false))
+            .transform(Transform::Anonymize)
+            .transform(Transform::Truncate(30));
+        assert_eq!(pipeline.run(records.clone(), &mut writer).expect("Could not run pipeline"), 1);
+
+        let bytes = writer.into_inner().expect("Could not flush");
+        let (_, (_, written)) = CaptureParser::parse_file(&bytes).expect("Could not parse");
+        assert_eq!(written.len(), 1);
+        assert_eq!(written[0].timestamp(), records[2].timestamp());
+        assert_eq!(written[0].payload().len(), 30);
+        assert_eq!(written[0].original_length(), RAW_DATA.len() as u32);
+        assert_eq!(&written[0].payload()[26..30], &[10u8, 0u8, 0u8, 1u8][..]);
+
+        let mut kept = vec![];
+        let failing = vec![Ok(record(1, 4)), Err("Could not read".into()), Ok(record(3, 4))];
+        assert!(Pipeline::new().try_run(failing, &mut kept).is_err());
+        assert_eq!(kept.len(), 1);
+    }
+}
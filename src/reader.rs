@@ -0,0 +1,389 @@
+use super::prelude::*;
+use super::global_header::{GlobalHeader, GLOBAL_HEADER_LENGTH};
+use super::record::{PcapRecord, RecordHeader};
+use super::recovery::DEFAULT_MAX_RECORD_LENGTH;
+
+use std;
+use std::io::Read;
+
+///
+/// Bytes read ahead from the source at a time, and so the memory held by a reader unless a record is
+/// larger
+///
+pub const DEFAULT_BUFFER_SIZE: usize = 64 * 1024;
+
+///
+/// Blocking, pull based reader of a libpcap capture from a pipe or other stream, e.g. for
+/// `dumpcap -w - | mytool`, or a capture too large to hold in memory. Reads the global header up
+/// front, then blocks for each record, tolerating short reads. Reads ahead into a fixed buffer that
+/// is reused for every record, growing only to fit a record larger than it. A capture ending part
+/// way through a record ends cleanly, with the bytes of the partial record counted in `discarded`.
+/// A record longer than `max_record_length` is an error rather than a reason to grow the buffer. A
+/// record longer than the snap length is read, as libpcap and Wireshark do, and counted in
+/// `exceeding_snap_length`.
+///
+pub struct CaptureReader<R> {
+    reader: R,
+    header: GlobalHeader,
+    buffer: ReadBuffer,
+    max_record_length: u32,
+    exceeding_snap_length: usize,
+    discarded: usize,
+    ended: bool
+}
+
+///
+/// Bytes read from a source but not yet consumed, in `bytes[start..end]`
+///
+struct ReadBuffer {
+    bytes: std::vec::Vec<u8>,
+    start: usize,
+    end: usize
+}
+
+impl ReadBuffer {
+    fn available(&self) -> &[u8] { &self.bytes[self.start..self.end] }
+
+    fn consume(&mut self, length: usize) {
+        self.start += length;
+    }
+
+    fn clear(&mut self) {
+        self.start = self.end;
+    }
+
+    ///
+    /// Read from `reader` until at least `needed` bytes are available, returning fewer only at the
+    /// end of input. Available bytes are moved to the front first if `needed` would not fit after them.
+    ///
+    fn fill<R: Read>(&mut self, reader: &mut R, needed: usize) -> std::io::Result<usize> {
+        if self.end - self.start >= needed {
+            return Ok(needed)
+        }
+        if self.bytes.len() - self.start < needed {
+            self.bytes.copy_within(self.start..self.end, 0);
+            self.end -= self.start;
+            self.start = 0;
+            if self.bytes.len() < needed {
+                trace!("Growing read buffer to {} bytes", needed);
+                self.bytes.resize(needed, 0);
+            }
+        }
+        while self.end - self.start < needed {
+            match reader.read(&mut self.bytes[self.end..]) {
+                Ok(0) => break,
+                Ok(n) => self.end += n,
+                Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e)
+            }
+        }
+        Ok(self.end - self.start)
+    }
+}
+
+impl CaptureReader<std::io::Stdin> {
+    ///
+    /// Reader of a capture piped to standard input
+    ///
+    pub fn from_stdin() -> errors::Result<CaptureReader<std::io::Stdin>> {
+        CaptureReader::new(std::io::stdin())
+    }
+}
+
+impl<R: Read> CaptureReader<R> {
+    ///
+    /// Read the global header from `reader`, failing if the capture ends before it
+    ///
+    pub fn new(reader: R) -> errors::Result<CaptureReader<R>> {
+        CaptureReader::with_capacity(reader, DEFAULT_BUFFER_SIZE)
+    }
+
+    ///
+    /// Read the global header from `reader`, reading ahead `capacity` bytes at a time
+    ///
+    pub fn with_capacity(mut reader: R, capacity: usize) -> errors::Result<CaptureReader<R>> {
+        let mut buffer = ReadBuffer {
+            bytes: vec![0u8; std::cmp::max(capacity, GLOBAL_HEADER_LENGTH)],
+            start: 0,
+            end: 0
+        };
+        let filled = buffer.fill(&mut reader, GLOBAL_HEADER_LENGTH)?;
+        if filled < GLOBAL_HEADER_LENGTH {
+            return Err(errors::Error::from_kind(errors::ErrorKind::IncompleteParse(filled)))
+        }
+        let (_, header) = GlobalHeader::parse(buffer.available())
+            .map_err(|e| errors::Error::from_nom(e, errors::Layer::GlobalHeader, buffer.available()))?;
+        debug!("Global header version {}.{}, with endianness {:?}", header.version_major(), header.version_minor(), header.endianness());
+        buffer.consume(GLOBAL_HEADER_LENGTH);
+
+        Ok(CaptureReader {
+            reader: reader,
+            header: header,
+            buffer: buffer,
+            max_record_length: DEFAULT_MAX_RECORD_LENGTH,
+            exceeding_snap_length: 0,
+            discarded: 0,
+            ended: false
+        })
+    }
+
+    ///
+    /// Largest record length read regardless of the snap length, `DEFAULT_MAX_RECORD_LENGTH` unless set
+    ///
+    pub fn max_record_length(mut self, max_record_length: u32) -> CaptureReader<R> {
+        self.max_record_length = max_record_length;
+        self
+    }
+
+    pub fn header(&self) -> &GlobalHeader { &self.header }
+
+    ///
+    /// Bytes of a partial record the capture ended with, which were dropped
+    ///
+    pub fn discarded(&self) -> usize { self.discarded }
+
+    ///
+    /// Records read so far whose captured length exceeded a non-zero snap length
+    ///
+    pub fn exceeding_snap_length(&self) -> usize { self.exceeding_snap_length }
+
+    ///
+    /// Whether the capture has ended
+    ///
+    pub fn is_ended(&self) -> bool { self.ended }
+
+    ///
+    /// Block for the next record, returning `None` once the capture has ended
+    ///
+    pub fn next_record(&mut self) -> errors::Result<Option<PcapRecord>> {
+        if self.ended {
+            return Ok(None)
+        }
+
+        let header_length = self.header.record_header_length();
+        let filled = self.buffer.fill(&mut self.reader, header_length)?;
+        if filled < header_length {
+            return Ok(self.end(filled))
+        }
+        let (_, header) = RecordHeader::parse_for(self.buffer.available(), &self.header)
+            .map_err(|e| errors::Error::from_nom(e, errors::Layer::Record, self.buffer.available()))?;
+        if header.actual_length() > self.max_record_length {
+            return Err(format!(
+                "Record length {} exceeds the maximum record length {}",
+                header.actual_length(), self.max_record_length
+            ).into())
+        }
+        let snap_length = self.header.snap_length();
+        if snap_length > 0 && header.actual_length() > snap_length {
+            warn!("Record length {} exceeds the snap length {}", header.actual_length(), snap_length);
+            self.exceeding_snap_length += 1;
+        }
+
+        let length = header_length + header.actual_length() as usize;
+        let filled = self.buffer.fill(&mut self.reader, length)?;
+        if filled < length {
+            return Ok(self.end(filled))
+        }
+        let payload = self.buffer.available()[header_length..length].to_vec();
+        self.buffer.consume(length);
+
+        Ok(Some(PcapRecord::new(*header.timestamp(), header.actual_length(), header.original_length(), payload).with_header(&self.header)))
+    }
+
+    fn end(&mut self, discarded: usize) -> Option<PcapRecord> {
+        if discarded > 0 {
+            debug!("Capture ended with {} bytes of a partial record", discarded);
+        }
+        self.discarded = discarded;
+        self.ended = true;
+        self.buffer.clear();
+        None
+    }
+}
+
+impl<R: Read> Iterator for CaptureReader<R> {
+    type Item = errors::Result<PcapRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_record() {
+            Ok(record) => record.map(Ok),
+            Err(e) => {
+                self.ended = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    const RAW_DATA: &'static [u8] = &[
+        0xA1u8, 0xB2u8, 0xC3u8, 0xD4u8, //magic number
+        0x00u8, 0x04u8, 0x00u8, 0x02u8, //version major, 4; version minor, 2
+        0x00u8, 0x00u8, 0x00u8, 0x00u8, //zone
+        0x00u8, 0x00u8, 0x00u8, 0x04u8, //sig figs
+        0x00u8, 0x00u8, 0x06u8, 0x13u8, //snap length
+        0x00u8, 0x00u8, 0x00u8, 0x02u8, //network
+        //record
+        0x5Bu8, 0x11u8, 0x6Du8, 0xE3u8, //seconds
+        0x00u8, 0x02u8, 0x51u8, 0xF5u8, //microseconds
+        0x00u8, 0x00u8, 0x00u8, 0x04u8, //actual length
+        0x00u8, 0x00u8, 0x00u8, 0x04u8, //original length
+        0x01u8, 0x02u8, 0x03u8, 0x04u8, //payload
+        //record
+        0x5Bu8, 0x11u8, 0x6Du8, 0xE4u8, //seconds
+        0x00u8, 0x00u8, 0x00u8, 0x00u8, //microseconds
+        0x00u8, 0x00u8, 0x00u8, 0x04u8, //actual length
+        0x00u8, 0x00u8, 0x00u8, 0x04u8, //original length
+        0x05u8, 0x06u8, 0x07u8, 0x08u8 //payload
+    ];
+
+    ///
+    /// Reader handing out a byte at a time, as a pipe under load may
+    ///
+    struct Trickle<'a>(&'a [u8]);
+
+    impl<'a> Read for Trickle<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match (self.0.split_first(), buf.first_mut()) {
+                (Some((b, rem)), Some(out)) => {
+                    *out = *b;
+                    self.0 = rem;
+                    Ok(1)
+                }
+                _ => Ok(0)
+            }
+        }
+    }
+
+    #[test]
+    fn read_short_reads() {
+        let _ = env_logger::try_init();
+
+        let reader = CaptureReader::new(Trickle(RAW_DATA)).expect("Could not read header");
+        assert_eq!(reader.header().snap_length(), 0x0613);
+
+        let records = reader.collect::<errors::Result<std::vec::Vec<_>>>().expect("Could not read records");
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].payload(), &vec![0x05u8, 0x06u8, 0x07u8, 0x08u8]);
+    }
+
+    ///
+    /// Reader of the chunks a writer sends, blocking until one arrives, as a pipe does
+    ///
+    struct Pipe {
+        chunks: std::sync::mpsc::Receiver<std::vec::Vec<u8>>,
+        pending: std::vec::Vec<u8>
+    }
+
+    impl Read for Pipe {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.pending.is_empty() {
+                match self.chunks.recv() {
+                    Ok(chunk) => self.pending = chunk,
+                    Err(_) => return Ok(0)
+                }
+            }
+            let length = std::cmp::min(buf.len(), self.pending.len());
+            buf[..length].copy_from_slice(&self.pending[..length]);
+            self.pending.drain(..length);
+            Ok(length)
+        }
+    }
+
+    #[test]
+    fn read_pipeline() {
+        let _ = env_logger::try_init();
+
+        let (sender, chunks) = std::sync::mpsc::channel();
+        let (started, start) = std::sync::mpsc::channel();
+        let writer = std::thread::spawn(move || {
+            sender.send(RAW_DATA[..GLOBAL_HEADER_LENGTH].to_vec()).expect("Could not send");
+            //records only follow once the header was read without them
+            start.recv().expect("Reader went away");
+            sender.send(RAW_DATA[GLOBAL_HEADER_LENGTH..30].to_vec()).expect("Could not send");
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            sender.send(RAW_DATA[30..].to_vec()).expect("Could not send");
+        });
+
+        let mut reader = CaptureReader::new(Pipe { chunks: chunks, pending: vec![] }).expect("Could not read header");
+        assert_eq!(reader.header().snap_length(), 0x0613);
+        started.send(()).expect("Writer went away");
+
+        let record = reader.next_record().expect("Could not read record").expect("Capture ended");
+        assert_eq!(record.payload(), &vec![0x01u8, 0x02u8, 0x03u8, 0x04u8]);
+        assert!(reader.next_record().expect("Could not read record").is_some());
+        assert!(reader.next_record().expect("Could not read record").is_none());
+        assert_eq!(reader.discarded(), 0);
+        writer.join().expect("Writer panicked");
+    }
+
+    #[test]
+    fn read_small_buffer() {
+        let _ = env_logger::try_init();
+
+        //buffer smaller than a record, which grows to fit it
+        let mut data = RAW_DATA.to_vec();
+        for _ in 0..100 {
+            data.extend_from_slice(&RAW_DATA[GLOBAL_HEADER_LENGTH..]);
+        }
+        let reader = CaptureReader::with_capacity(Trickle(&data), 8).expect("Could not read header");
+        let records = reader.collect::<errors::Result<std::vec::Vec<_>>>().expect("Could not read records");
+        assert_eq!(records.len(), 202);
+        assert_eq!(records[201].payload(), &vec![0x05u8, 0x06u8, 0x07u8, 0x08u8]);
+
+        //buffer holding several records
+        let reader = CaptureReader::with_capacity(&data[..], 50).expect("Could not read header");
+        let records = reader.collect::<errors::Result<std::vec::Vec<_>>>().expect("Could not read records");
+        assert_eq!(records.len(), 202);
+        assert_eq!(records[200].payload(), &vec![0x01u8, 0x02u8, 0x03u8, 0x04u8]);
+    }
+
+    #[test]
+    fn read_ended_mid_record() {
+        let _ = env_logger::try_init();
+
+        let mut reader = CaptureReader::new(&RAW_DATA[..RAW_DATA.len() - 2]).expect("Could not read header");
+
+        assert!(reader.next_record().expect("Could not read record").is_some());
+        assert!(reader.next_record().expect("Capture ending mid record was an error").is_none());
+        assert!(reader.is_ended());
+        assert_eq!(reader.discarded(), 18);
+        assert!(reader.next().is_none());
+
+        assert!(CaptureReader::new(&RAW_DATA[..10]).is_err());
+    }
+
+    #[test]
+    fn read_oversized_record() {
+        let _ = env_logger::try_init();
+
+        //length above the maximum record length, which is not read ahead for
+        let mut data = RAW_DATA.to_vec();
+        data[GLOBAL_HEADER_LENGTH + 8..GLOBAL_HEADER_LENGTH + 16].copy_from_slice(&[0x7Fu8, 0xFFu8, 0xFFu8, 0xFFu8, 0x7Fu8, 0xFFu8, 0xFFu8, 0xFFu8]);
+        let mut reader = CaptureReader::new(&data[..]).expect("Could not read header");
+        assert!(reader.next_record().is_err());
+
+        //length within the snap length but above the maximum record length
+        let mut reader = CaptureReader::new(RAW_DATA).expect("Could not read header").max_record_length(2);
+        assert!(reader.next_record().is_err());
+
+        //lengths above a snap length of 2 are read and counted
+        let mut data = RAW_DATA.to_vec();
+        data[16..20].copy_from_slice(&[0x00u8, 0x00u8, 0x00u8, 0x02u8]);
+        let mut reader = CaptureReader::new(&data[..]).expect("Could not read header");
+        assert_eq!(reader.by_ref().count(), 2);
+        assert_eq!(reader.exceeding_snap_length(), 2);
+
+        //no snap length
+        let mut data = RAW_DATA.to_vec();
+        data[16..20].copy_from_slice(&[0x00u8, 0x00u8, 0x00u8, 0x00u8]);
+        let mut reader = CaptureReader::new(&data[..]).expect("Could not read header");
+        assert_eq!(reader.by_ref().count(), 2);
+        assert_eq!(reader.exceeding_snap_length(), 0);
+    }
+}
@@ -1,42 +1,196 @@
 use super::prelude::*;
 
+#[cfg(feature = "flows")]
 use super::{
+    analysis::defrag::Defragmenter,
     flow,
     layer2::{
-        Layer2,
         Layer2FlowInfo,
         ethernet::Ethernet
-    }
+    },
+    parsed::{ParseConfig, ParsedRecord}
 };
 
+use super::common::put_u32;
+use super::global_header::{GlobalHeader, PcapVariant, TimestampPrecision};
+use super::link_type::LinkType;
+use super::pcapng::TimestampResolution;
+
 use self::nom::*;
 
 use std;
 use std::convert::TryFrom;
 
+pub const RECORD_HEADER_LENGTH: usize = 16;
+
 ///
-/// Pcap record associated with a libpcap capture
+/// Fixed length header preceding each record payload in a libpcap capture
 ///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RecordHeader {
+    timestamp: std::time::SystemTime,
+    actual_length: u32,
+    original_length: u32
+}
+
+impl RecordHeader {
+    pub fn timestamp(&self) -> &std::time::SystemTime {
+        &self.timestamp
+    }
+    pub fn actual_length(&self) -> u32 {
+        self.actual_length
+    }
+    pub fn original_length(&self) -> u32 {
+        self.original_length
+    }
+
+    pub fn parse(input: &[u8], endianness: nom::Endianness) -> nom::IResult<&[u8], RecordHeader> {
+        RecordHeader::parse_with_precision(input, endianness, TimestampPrecision::Microsecond)
+    }
+
+    ///
+    /// Parse a record header of a capture whose timestamps are in `precision`
+    ///
+    pub fn parse_with_precision(input: &[u8], endianness: nom::Endianness, precision: TimestampPrecision) -> nom::IResult<&[u8], RecordHeader> {
+        RecordHeader::parse_with_variant(input, endianness, precision, PcapVariant::Standard)
+    }
+
+    ///
+    /// Parse a record header laid out as `variant`, consuming the fields of the modified variant
+    /// after the standard ones
+    ///
+    pub fn parse_with_variant(
+        input: &[u8],
+        endianness: nom::Endianness,
+        precision: TimestampPrecision,
+        variant: PcapVariant
+    ) -> nom::IResult<&[u8], RecordHeader> {
+        do_parse!(input,
+
+            ts_seconds: u32!(endianness) >>
+            ts_fraction: u32!(endianness) >>
+            actual_length: u32!(endianness) >>
+            original_length: u32!(endianness) >>
+            _extension: take!(variant.record_header_length() - RECORD_HEADER_LENGTH) >>
+
+            (
+                RecordHeader {
+                    timestamp: precision.to_timestamp(ts_seconds, ts_fraction),
+                    actual_length: actual_length,
+                    original_length: original_length
+                }
+            )
+        )
+    }
+
+    ///
+    /// Parse a record header of the capture with `header`
+    ///
+    pub fn parse_for<'a>(input: &'a [u8], header: &GlobalHeader) -> nom::IResult<&'a [u8], RecordHeader> {
+        RecordHeader::parse_with_variant(input, header.endianness(), header.precision(), header.variant())
+    }
+}
+
+///
+/// Pcap record associated with a libpcap capture, with the interface it was captured on. Records
+/// of libpcap, snoop and NetMon captures are all of interface 0, with the link type and timestamp
+/// resolution of their capture's header, while records of pcapng captures take them from the
+/// interface each was captured on.
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct PcapRecord{
     timestamp: std::time::SystemTime,
     actual_length: u32,
     original_length: u32,
-    payload: std::vec::Vec<u8>
+    payload: std::vec::Vec<u8>,
+    comments: std::vec::Vec<String>,
+    interface_id: u32,
+    link_type: LinkType,
+    timestamp_resolution: TimestampResolution
 }
 
 impl PcapRecord {
     pub fn timestamp(&self) -> &std::time::SystemTime {
         &self.timestamp
     }
+    pub fn set_timestamp(&mut self, timestamp: std::time::SystemTime) {
+        self.timestamp = timestamp
+    }
     pub fn actual_length(&self) -> u32 {
         self.actual_length
     }
     pub fn original_length(&self) -> u32 {
         self.original_length
     }
+
+    ///
+    /// Whether the capture kept fewer bytes of the packet than were on the wire, e.g. because of
+    /// its snap length
+    ///
+    pub fn is_truncated(&self) -> bool {
+        self.actual_length < self.original_length
+    }
+
+    ///
+    /// Bytes of the packet on the wire missing from the record
+    ///
+    pub fn truncated_length(&self) -> u32 {
+        self.original_length.saturating_sub(self.actual_length)
+    }
     pub fn payload(&self) -> &std::vec::Vec<u8> { &self.payload }
+
+    ///
+    /// Replace the bytes captured, e.g. with a rewritten or truncated frame, keeping the original
+    /// length and the rest of the record
+    ///
+    pub fn set_payload(&mut self, payload: std::vec::Vec<u8>) {
+        self.actual_length = payload.len() as u32;
+        self.payload = payload
+    }
+
+    ///
+    /// Comments annotating the record, kept in pcapng captures and dropped in libpcap captures
+    ///
+    pub fn comments(&self) -> &std::vec::Vec<String> { &self.comments }
+    pub fn set_comments(&mut self, comments: std::vec::Vec<String>) {
+        self.comments = comments
+    }
     pub unsafe fn packet_data(&mut self) -> *mut u8 { self.payload.as_mut_ptr() }
 
+    ///
+    /// Id of the interface the record was captured on, as numbered in its pcapng section
+    ///
+    pub fn interface_id(&self) -> u32 { self.interface_id }
+    pub fn set_interface_id(&mut self, interface_id: u32) {
+        self.interface_id = interface_id
+    }
+
+    ///
+    /// Link type of the interface the record was captured on, which its payload starts with
+    ///
+    pub fn link_type(&self) -> LinkType { self.link_type }
+    pub fn set_link_type(&mut self, link_type: LinkType) {
+        self.link_type = link_type
+    }
+
+    ///
+    /// Resolution of the timestamps of the interface the record was captured on, finer than
+    /// microseconds for e.g. nanosecond libpcap captures
+    ///
+    pub fn timestamp_resolution(&self) -> TimestampResolution { self.timestamp_resolution }
+    pub fn set_timestamp_resolution(&mut self, timestamp_resolution: TimestampResolution) {
+        self.timestamp_resolution = timestamp_resolution
+    }
+
+    ///
+    /// Record with the link type and timestamp resolution of the capture with `header`
+    ///
+    pub fn with_header(mut self, header: &GlobalHeader) -> PcapRecord {
+        self.link_type = header.link_type();
+        self.timestamp_resolution = header.precision().into();
+        self
+    }
+
     ///
     /// Convert a packet time (seconds and partial second microseconds) to a system time (offset from epoch)
     ///
@@ -46,18 +200,50 @@ impl PcapRecord {
     }
 
     ///
-    /// Utility function to convert a vector of records to flows, unless an error is encountered in flow conversion
+    /// Utility function to convert a vector of records to flows in capture order, unless an error is encountered in flow conversion.
+    /// Each record is parsed with the layer 2 parser of its own link type, so records of a pcapng capture's interfaces can be
+    /// mixed. Fragmented ipv4 datagrams are reassembled and converted once, when their last fragment is seen.
+    ///
+    #[cfg(feature = "flows")]
+    pub fn convert_records(records: std::vec::Vec<PcapRecord>, ignore_error: bool) -> Result<std::vec::Vec<flow::Flow>, errors::Error> {
+        PcapRecord::convert_defragmented(records, Defragmenter::default(), ignore_error, Flow::try_from)
+    }
+
+    ///
+    /// Convert records to flows with `convert` once `defragmenter` passes them on
     ///
-    pub fn convert_records(mut records: std::vec::Vec<PcapRecord>, ignore_error: bool) -> Result<std::vec::Vec<flow::Flow>, errors::Error> {
+    #[cfg(feature = "flows")]
+    fn convert_defragmented<F>(
+        records: std::vec::Vec<PcapRecord>,
+        mut defragmenter: Defragmenter,
+        ignore_error: bool,
+        convert: F
+    ) -> Result<std::vec::Vec<flow::Flow>, errors::Error> where F: Fn(PcapRecord) -> Result<flow::Flow, errors::Error> {
         let mut result = vec![];
         result.reserve_exact(records.len());
 
-        while let Some(record) = records.pop() {
-            match Flow::try_from(record) {
-                Ok(f) => {
+        for (index, record) in records.into_iter().enumerate() {
+            layer_span!("record", index = index);
+
+            let datagram = match defragmenter.defragment(record) {
+                Some(datagram) => datagram,
+                None => continue
+            };
+            let reassembled = datagram.is_reassembled();
+            match convert(datagram.record) {
+                Ok(mut f) => {
+                    f.reassembled = reassembled;
                     result.push(f)
                 },
                 Err(e) => {
+                    if let errors::ErrorKind::LinkType(link_type) = *e.kind() {
+                        if !ignore_error {
+                            return Err(e)
+                        }
+                        debug!("Failed to extract flow from record of link type {}", link_type);
+                        continue
+                    }
+                    let e = e.at_record(index);
                     if ignore_error {
                         debug!("Failed to extract flow: {}", e);
                     } else {
@@ -67,9 +253,37 @@ impl PcapRecord {
             }
         };
 
+        if defragmenter.pending() > 0 {
+            let e = errors::Error::from_kind(errors::ErrorKind::FlowConversion(format!("{} datagrams missing fragments", defragmenter.pending())));
+            if ignore_error {
+                debug!("Failed to extract flow: {}", e);
+            } else {
+                return Err(e)
+            }
+        }
+
         Ok(result)
     }
 
+    ///
+    /// Convert records of a capture with global header `header` to flows, with the layer 2 parser
+    /// of the capture's link type, as `convert_records` does for ethernet captures. Records of other
+    /// link types with a layer 2 parser, e.g. linux cooked or raw ip, are converted from the layers
+    /// `ParsedRecord` parses, reassembling fragments as `convert_records` does. Records of link
+    /// types without a layer 2 parser fail with `ErrorKind::LinkType`, or convert to no flows when
+    /// ignoring errors.
+    ///
+    #[cfg(feature = "flows")]
+    pub fn convert_records_with_header(records: std::vec::Vec<PcapRecord>, header: &GlobalHeader, ignore_error: bool) -> Result<std::vec::Vec<flow::Flow>, errors::Error> {
+        if header.link_type() == LinkType::Ethernet {
+            return PcapRecord::convert_records(records, ignore_error)
+        }
+        let config = ParseConfig::for_header(header);
+        PcapRecord::convert_defragmented(records, Defragmenter::for_header(header), ignore_error, |record| {
+            ParsedRecord::parse_with(record, &config).and_then(|p| p.to_flow())
+        })
+    }
+
     pub fn new(
         timestamp: std::time::SystemTime,
         actual_length: u32,
@@ -80,29 +294,176 @@ impl PcapRecord {
             timestamp,
             actual_length,
             original_length,
-            payload
+            payload,
+            comments: vec![],
+            interface_id: 0,
+            link_type: LinkType::Ethernet,
+            timestamp_resolution: TimestampResolution::default()
         }
     }
 
+    ///
+    /// Record header and payload as they appear in a libpcap capture written in byte order
+    /// `endianness`, the inverse of parsing. Timestamps before the epoch are written as the epoch.
+    ///
+    pub fn to_bytes(&self, endianness: nom::Endianness) -> std::vec::Vec<u8> {
+        self.to_bytes_with_precision(endianness, TimestampPrecision::Microsecond)
+    }
+
+    ///
+    /// Record as it appears in a capture whose timestamps are in `precision`
+    ///
+    pub fn to_bytes_with_precision(&self, endianness: nom::Endianness, precision: TimestampPrecision) -> std::vec::Vec<u8> {
+        let (seconds, fraction) = precision.from_timestamp(&self.timestamp);
+        let mut bytes = std::vec::Vec::with_capacity(RECORD_HEADER_LENGTH + self.payload.len());
+        put_u32(&mut bytes, seconds, endianness);
+        put_u32(&mut bytes, fraction, endianness);
+        put_u32(&mut bytes, self.payload.len() as u32, endianness);
+        put_u32(&mut bytes, self.original_length, endianness);
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+
+    pub fn write_to<W: std::io::Write>(&self, writer: &mut W, endianness: nom::Endianness) -> errors::Result<()> {
+        writer.write_all(&self.to_bytes(endianness))?;
+        Ok(())
+    }
+
     pub fn parse(input: &[u8], endianness: nom::Endianness) -> nom::IResult<&[u8], PcapRecord> {
+        PcapRecord::parse_with_precision(input, endianness, TimestampPrecision::Microsecond)
+    }
+
+    ///
+    /// Parse a record of a capture whose timestamps are in `precision`
+    ///
+    pub fn parse_with_precision(input: &[u8], endianness: nom::Endianness, precision: TimestampPrecision) -> nom::IResult<&[u8], PcapRecord> {
+        PcapRecord::parse_with_variant(input, endianness, precision, PcapVariant::Standard)
+    }
+
+    ///
+    /// Parse a record whose header is laid out as `variant`
+    ///
+    pub fn parse_with_variant(
+        input: &[u8],
+        endianness: nom::Endianness,
+        precision: TimestampPrecision,
+        variant: PcapVariant
+    ) -> nom::IResult<&[u8], PcapRecord> {
         do_parse!(input,
 
-            ts_seconds: u32!(endianness) >>
-            ts_microseconds: u32!(endianness) >>
-            actual_length: u32!(endianness) >>
-            original_length: u32!(endianness) >>
-            payload: take!(actual_length) >>
+            header: apply!(RecordHeader::parse_with_variant, endianness, precision, variant) >>
+            payload: take!(header.actual_length) >>
 
             (
                 PcapRecord {
-                    timestamp: PcapRecord::convert_packet_time(ts_seconds, ts_microseconds),
-                    actual_length: actual_length,
-                    original_length: original_length,
-                    payload: payload.into()
+                    timestamp: header.timestamp,
+                    actual_length: header.actual_length,
+                    original_length: header.original_length,
+                    payload: payload.into(),
+                    comments: vec![],
+                    interface_id: 0,
+                    link_type: LinkType::Ethernet,
+                    timestamp_resolution: precision.into()
                 }
             )
         )
     }
+
+    ///
+    /// Parse a record of the capture with `header`
+    ///
+    pub fn parse_for<'a>(input: &'a [u8], header: &GlobalHeader) -> nom::IResult<&'a [u8], PcapRecord> {
+        PcapRecord::parse_with_variant(input, header.endianness(), header.precision(), header.variant())
+            .map(|(rem, record)| (rem, record.with_header(header)))
+    }
+}
+
+///
+/// Iterator parsing one record per call to `next` from a slice of records without libpcap global
+/// header, so a caller stopping early does not pay for parsing the rest. Iteration ends at the first
+/// incomplete record, whose bytes are left in `remainder`, or after the first error.
+///
+pub struct RecordIterator<'a> {
+    input: &'a [u8],
+    current: &'a [u8],
+    endianness: nom::Endianness,
+    precision: TimestampPrecision,
+    variant: PcapVariant,
+    link_type: LinkType,
+    index: usize,
+    ended: bool
+}
+
+impl<'a> RecordIterator<'a> {
+    pub fn new(input: &'a [u8], endianness: nom::Endianness) -> RecordIterator<'a> {
+        RecordIterator::with_precision(input, endianness, TimestampPrecision::Microsecond)
+    }
+
+    ///
+    /// Iterator over records whose timestamps are in `precision`
+    ///
+    pub fn with_precision(input: &'a [u8], endianness: nom::Endianness, precision: TimestampPrecision) -> RecordIterator<'a> {
+        RecordIterator {
+            input: input,
+            current: input,
+            endianness: endianness,
+            precision: precision,
+            variant: PcapVariant::Standard,
+            link_type: LinkType::Ethernet,
+            index: 0,
+            ended: false
+        }
+    }
+
+    ///
+    /// Iterator over the records of the capture with `header`
+    ///
+    pub fn for_header(input: &'a [u8], header: &GlobalHeader) -> RecordIterator<'a> {
+        let mut iterator = RecordIterator::with_precision(input, header.endianness(), header.precision());
+        iterator.variant = header.variant();
+        iterator.link_type = header.link_type();
+        iterator
+    }
+
+    ///
+    /// Bytes not yet parsed, the bytes of an incomplete record once iteration has ended
+    ///
+    pub fn remainder(&self) -> &'a [u8] { self.current }
+
+    ///
+    /// Offset into the input of the next record
+    ///
+    pub fn offset(&self) -> usize { self.input.len() - self.current.len() }
+}
+
+impl<'a> Iterator for RecordIterator<'a> {
+    type Item = errors::Result<PcapRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.ended {
+            return None
+        }
+
+        layer_span!("record", index = self.index, offset = self.offset());
+
+        match PcapRecord::parse_with_variant(self.current, self.endianness, self.precision, self.variant) {
+            Ok( (rem, mut record) ) => {
+                self.current = rem;
+                self.index += 1;
+                record.set_link_type(self.link_type);
+                Some(Ok(record))
+            }
+            Err(nom::Err::Incomplete(_)) => {
+                debug!("Records ended with {} bytes of an incomplete record", self.current.len());
+                self.ended = true;
+                None
+            }
+            Err(e) => {
+                self.ended = true;
+                Some(Err(errors::Error::from_nom(e, errors::Layer::Record, self.current).at_record(self.index)))
+            }
+        }
+    }
 }
 
 impl std::fmt::Display for PcapRecord {
@@ -122,39 +483,62 @@ impl std::fmt::Display for PcapRecord {
     }
 }
 
+#[cfg(feature = "flows")]
 impl TryFrom<PcapRecord> for flow::Flow {
     type Error = errors::Error;
 
     fn try_from(value: PcapRecord) -> Result<Self, Self::Error> {
-        trace!("Creating flow from payload of {}B", value.payload().len());
+        layer_span!("flow", length = value.payload().len());
 
-        let l2 = Ethernet::parse(value.payload().as_slice())
-            .map_err(|e| {
-                let err: Self::Error = e.into();
-                err
-            }).and_then(|r| {
-            let (rem, l2) = r;
-            if rem.is_empty() {
-                Layer2FlowInfo::try_from(l2)
-            } else {
-                Err(errors::Error::from_kind(errors::ErrorKind::IncompleteParse(rem.len())))
-            }
-        })?;
-
-        Ok(Flow {
-            source: flow::Device {
-                mac: l2.src_mac,
-                ip: l2.layer3.src_ip,
-                port: l2.layer3.layer4.src_port
-            },
-            destination: flow::Device {
-                mac: l2.dst_mac,
-                ip: l2.layer3.dst_ip,
-                port: l2.layer3.layer4.dst_port
-            },
-            record: value,
-            vlan: l2.vlan
-        })
+        //truncated datagrams are parsed from the bytes captured, as ParsedRecord does, which also
+        //parses records of link types other than ethernet
+        let (l2, value) = if value.is_truncated() || value.link_type() != LinkType::Ethernet {
+            let parsed = ParsedRecord::try_from(value)?;
+            (parsed.flow_info()?, parsed.into_record())
+        } else {
+            let l2 = Ethernet::parse(value.payload().as_slice())
+                .map_err(|e| errors::Error::from_nom(e, errors::Layer::Ethernet, value.payload()))
+                .and_then(|r| {
+                let (rem, l2) = r;
+                if rem.is_empty() {
+                    Layer2FlowInfo::try_from(l2)
+                } else {
+                    Err(errors::Error::from_kind(errors::ErrorKind::IncompleteParse(rem.len())))
+                }
+            })?;
+            (l2, value)
+        };
+
+        Ok(to_flow(value, l2))
+    }
+}
+
+///
+/// Flow of `record` from its layer 2 flow information
+///
+#[cfg(feature = "flows")]
+fn to_flow(record: PcapRecord, l2: Layer2FlowInfo) -> Flow {
+    Flow {
+        source: flow::Device {
+            mac: l2.src_mac,
+            ip: l2.layer3.src_ip,
+            port: l2.layer3.layer4.src_port
+        },
+        destination: flow::Device {
+            mac: l2.dst_mac,
+            ip: l2.layer3.dst_ip,
+            port: l2.layer3.layer4.dst_port
+        },
+        protocol: l2.layer3.protocol,
+        record: record,
+        vlan: l2.vlan,
+        inner_vlans: l2.inner_vlans,
+        delivery: l2.delivery,
+        reassembled: false,
+        //tunnels are only decapsulated when parsing with ParsedRecord
+        #[cfg(feature = "tunnels")]
+        tunnel: None,
+        arp: l2.layer3.arp
     }
 }
 
@@ -238,6 +622,132 @@ mod tests {
         assert_eq!(record.original_length(), 1232);
     }
 
+    #[test]
+    fn record_to_bytes() {
+        let _ = env_logger::try_init();
+
+        let (_, record) = PcapRecord::parse(RAW_DATA, nom::Endianness::Big).expect("Could not parse");
+        assert_eq!(record.to_bytes(nom::Endianness::Big), RAW_DATA.to_vec());
+
+        let mut bytes = vec![];
+        record.write_to(&mut bytes, nom::Endianness::Little).expect("Could not write record");
+        assert_eq!(&bytes[..RECORD_HEADER_LENGTH], &[
+            0xE3u8, 0x6Du8, 0x11u8, 0x5Bu8, //seconds, 1527868899
+            0xF5u8, 0x51u8, 0x02u8, 0x00u8, //microseconds, 152053
+            0x56u8, 0x00u8, 0x00u8, 0x00u8, //actual length, 86
+            0xD0u8, 0x04u8, 0x00u8, 0x00u8 //original length, 1232
+        ]);
+        assert_eq!(PcapRecord::parse(&bytes, nom::Endianness::Little).expect("Could not parse").1, record);
+    }
+
+    #[test]
+    fn iterate_records() {
+        let _ = env_logger::try_init();
+
+        let mut data = RAW_DATA.to_vec();
+        data.extend_from_slice(RAW_DATA);
+        data.extend_from_slice(&RAW_DATA[..20]);
+
+        let mut iter = RecordIterator::new(&data, nom::Endianness::Big);
+        assert_eq!(iter.next().expect("No record").expect("Could not parse").actual_length(), 86);
+        assert_eq!(iter.offset(), RAW_DATA.len());
+
+        assert_eq!(iter.by_ref().count(), 1);
+        assert_eq!(iter.remainder(), &RAW_DATA[..20]);
+        assert!(iter.next().is_none());
+
+        let first = RecordIterator::new(&data, nom::Endianness::Big)
+            .filter_map(|r| r.ok())
+            .find(|r| r.original_length() == 1232);
+        assert_eq!(first.map(|r| r.actual_length()), Some(86));
+    }
+
+    #[test]
+    fn parse_record_header() {
+        let _ = env_logger::try_init();
+
+        let (rem, header) = RecordHeader::parse(RAW_DATA, nom::Endianness::Big).expect("Could not parse");
+
+        assert_eq!(rem.len(), RAW_DATA.len() - RECORD_HEADER_LENGTH);
+        assert_eq!(header.actual_length(), 86);
+        assert_eq!(header.original_length(), 1232);
+    }
+
+    #[test]
+    fn parse_modified_records() {
+        let _ = env_logger::try_init();
+
+        let mut data = vec![
+            0xA1u8, 0xB2u8, 0xCDu8, 0x34u8, //magic number, modified
+            0x00u8, 0x02u8, //version major, 2
+            0x00u8, 0x04u8, //version minor, 4
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, //zone, 0
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, //sig figs, 0
+            0x00u8, 0x00u8, 0xFFu8, 0xFFu8, //snap length, 65535
+            0x00u8, 0x00u8, 0x00u8, 0x01u8 //network, ethernet
+        ];
+        for _ in 0..2 {
+            data.extend_from_slice(&RAW_DATA[..RECORD_HEADER_LENGTH]);
+            data.extend_from_slice(&[
+                0x00u8, 0x00u8, 0x00u8, 0x02u8, //interface index, 2
+                0x00u8, 0x08u8, //protocol, ipv4
+                0x00u8, //packet type, outgoing
+                0x00u8 //padding
+            ]);
+            data.extend_from_slice(&RAW_DATA[RECORD_HEADER_LENGTH..]);
+        }
+
+        let (rem, (header, records)) = super::super::CaptureParser::parse_file(&data).expect("Could not parse");
+        assert!(rem.is_empty());
+        assert_eq!(header.variant(), PcapVariant::Modified);
+        assert_eq!(records.len(), 2);
+        assert!(records.iter().all(|r| r.actual_length() == 86 && r.payload()[..6] == RAW_DATA[16..22]));
+
+        let (_, record) = PcapRecord::parse_for(&data[24..], &header).expect("Could not parse");
+        assert_eq!(record, records[0]);
+        assert_eq!(RecordIterator::for_header(&data[24..], &header).count(), 2);
+    }
+
+    #[test]
+    fn parse_records_for_header() {
+        let _ = env_logger::try_init();
+
+        let (_, record) = PcapRecord::parse(RAW_DATA, nom::Endianness::Big).expect("Could not parse");
+        assert_eq!( (record.interface_id(), record.link_type()), (0, LinkType::Ethernet) );
+        assert_eq!(record.timestamp_resolution(), TimestampResolution::Decimal(6));
+
+        let header = GlobalHeader::new(LinkType::Raw, 65535)
+            .with_endianness(nom::Endianness::Big)
+            .with_precision(TimestampPrecision::Nanosecond);
+        let (_, record) = PcapRecord::parse_for(RAW_DATA, &header).expect("Could not parse");
+        assert_eq!(record.link_type(), LinkType::Raw);
+        assert_eq!(record.timestamp_resolution(), TimestampResolution::Decimal(9));
+        assert_eq!(*record.timestamp(), std::time::UNIX_EPOCH + std::time::Duration::new(1527868899, 152053));
+
+        let iterated = RecordIterator::for_header(RAW_DATA, &header).next().expect("No record").expect("Could not parse");
+        assert_eq!(iterated, record);
+    }
+
+    #[cfg(feature = "flows")]
+    #[test]
+    fn compare_records() {
+        let _ = env_logger::try_init();
+
+        let (_, record) = PcapRecord::parse(RAW_DATA, nom::Endianness::Big).expect("Could not parse");
+        let (_, other) = PcapRecord::parse(RAW_DATA, nom::Endianness::Big).expect("Could not parse");
+
+        assert_eq!(record, other);
+
+        let mut records = std::collections::HashSet::new();
+        records.insert(record.clone());
+        records.insert(other);
+        assert_eq!(records.len(), 1);
+
+        let flow = flow::Flow::try_from(record.clone()).expect("Could not extract flow");
+        assert_eq!(flow.clone(), flow::Flow::try_from(record).expect("Could not extract flow"));
+    }
+
+    #[cfg(feature = "flows")]
     #[test]
     fn convert_record() {
         let _ = env_logger::try_init();
@@ -251,4 +761,265 @@ mod tests {
         assert_eq!(info.source().port, 50871);
         assert_eq!(info.destination().port, 80);
     }
-}
\ No newline at end of file
+
+    #[cfg(feature = "flows")]
+    #[test]
+    fn convert_record_ipv4_options() {
+        let _ = env_logger::try_init();
+
+        let mut payload = RAW_DATA[16..50].to_vec();
+        payload[14] = 0x46u8; //version and header length, 6 words
+        payload[17] = 0x4Cu8; //length, 24 bytes for header, 52 bytes for tcp
+        payload.extend_from_slice(&[0x01u8, 0x01u8, 0x01u8, 0x00u8]); //options, 3 no-ops and end of list
+        payload.extend_from_slice(&RAW_DATA[50..]);
+        let record = PcapRecord::new(std::time::UNIX_EPOCH, payload.len() as u32, payload.len() as u32, payload);
+
+        let info = flow::Flow::try_from(record.clone()).expect("Could not extract flow");
+        assert_eq!(info.source().port, 50871);
+        assert_eq!(info.destination().port, 80);
+
+        let parsed = ParsedRecord::try_from(record).expect("Could not parse layers");
+        assert_eq!(parsed.offsets().layer4, Some(38));
+        assert_eq!(parsed.payload().len(), 32);
+    }
+
+    #[cfg(feature = "flows")]
+    #[test]
+    fn convert_records_link_type() {
+        let _ = env_logger::try_init();
+
+        let (_, record) = PcapRecord::parse(RAW_DATA, nom::Endianness::Big).expect("Could not parse");
+
+        let err = PcapRecord::convert_records_with_header(vec![record.clone()], &GlobalHeader::new(LinkType::Ieee80211Radiotap, 65535), false).expect_err("Converted radiotap record");
+        match *err.kind() {
+            errors::ErrorKind::LinkType(link_type) => assert_eq!(link_type, LinkType::Ieee80211Radiotap),
+            ref other => panic!("Unexpected error {:?}", other)
+        }
+        assert!(PcapRecord::convert_records_with_header(vec![record], &GlobalHeader::new(LinkType::Ieee80211Radiotap, 65535), true).expect("Could not convert records").is_empty());
+
+        let mut cooked = vec![
+            0x00u8, 0x00u8, //packet type, host
+            0x00u8, 0x01u8, //arphrd type, ethernet
+            0x00u8, 0x06u8, //address length, 6
+            0xFFu8, 0xFEu8, 0xFDu8, 0xFCu8, 0xFBu8, 0xFAu8, 0x00u8, 0x00u8, //address FF:FE:FD:FC:FB:FA
+            0x08u8, 0x00u8 //protocol, ipv4
+        ];
+        cooked.extend_from_slice(&RAW_DATA[16 + 14..]);
+        let record = PcapRecord::new(std::time::UNIX_EPOCH, cooked.len() as u32, cooked.len() as u32, cooked);
+
+        let flows = PcapRecord::convert_records_with_header(vec![record.clone()], &GlobalHeader::new(LinkType::LinuxSll, 65535), false).expect("Could not convert records");
+        assert_eq!(flows.len(), 1);
+        assert_eq!(flows[0].source().port, 50871);
+        assert_eq!(flows[0].destination().port, 80);
+        assert_eq!(flows[0].record(), &record);
+        assert!(PcapRecord::convert_records_with_header(vec![record], &GlobalHeader::new(LinkType::Ethernet, 65535), false).is_err());
+
+        let mut cooked = vec![
+            0x08u8, 0x00u8, //protocol, ipv4
+            0x00u8, 0x00u8, //reserved
+            0x00u8, 0x00u8, 0x00u8, 0x02u8, //interface index, 2
+            0x00u8, 0x01u8, //arphrd type, ethernet
+            0x00u8, //packet type, host
+            0x06u8, //address length, 6
+            0xFFu8, 0xFEu8, 0xFDu8, 0xFCu8, 0xFBu8, 0xFAu8, 0x00u8, 0x00u8 //address FF:FE:FD:FC:FB:FA
+        ];
+        cooked.extend_from_slice(&RAW_DATA[16 + 14..]);
+        let record = PcapRecord::new(std::time::UNIX_EPOCH, cooked.len() as u32, cooked.len() as u32, cooked);
+
+        let flows = PcapRecord::convert_records_with_header(vec![record], &GlobalHeader::new(LinkType::LinuxSll2, 65535), false).expect("Could not convert records");
+        assert_eq!(flows.len(), 1);
+        assert_eq!(flows[0].source().mac, Some(MacAddress([0xFFu8, 0xFEu8, 0xFDu8, 0xFCu8, 0xFBu8, 0xFAu8])));
+        assert_eq!(flows[0].destination().port, 80);
+
+        let raw = RAW_DATA[16 + 14..].to_vec();
+        let record = PcapRecord::new(std::time::UNIX_EPOCH, raw.len() as u32, raw.len() as u32, raw);
+
+        for link_type in &[LinkType::Raw, LinkType::Ipv4] {
+            let flows = PcapRecord::convert_records_with_header(vec![record.clone()], &GlobalHeader::new(*link_type, 65535), false).expect("Could not convert records");
+            assert_eq!(flows.len(), 1);
+            assert_eq!(flows[0].source().mac, None);
+            assert_eq!(flows[0].destination().mac, None);
+            assert_eq!(flows[0].source().port, 50871);
+            assert_eq!(flows[0].destination().port, 80);
+        }
+
+        for endianness in &[nom::Endianness::Big, nom::Endianness::Little] {
+            let mut looped = match *endianness {
+                nom::Endianness::Big => vec![0x00u8, 0x00u8, 0x00u8, 0x02u8], //family, AF_INET
+                nom::Endianness::Little => vec![0x02u8, 0x00u8, 0x00u8, 0x00u8]
+            };
+            looped.extend_from_slice(&RAW_DATA[16 + 14..]);
+            let record = PcapRecord::new(std::time::UNIX_EPOCH, looped.len() as u32, looped.len() as u32, looped);
+
+            let header = GlobalHeader::new(LinkType::Null, 65535).with_endianness(*endianness);
+            let flows = PcapRecord::convert_records_with_header(vec![record], &header, false).expect("Could not convert records");
+            assert_eq!(flows.len(), 1);
+            assert_eq!(flows[0].source().mac, None);
+            assert_eq!(flows[0].destination().port, 80);
+        }
+
+        let mut framed = vec![
+            0xFFu8, 0x03u8, //address and control
+            0x00u8, 0x21u8 //protocol, ipv4
+        ];
+        framed.extend_from_slice(&RAW_DATA[16 + 14..]);
+        let record = PcapRecord::new(std::time::UNIX_EPOCH, framed.len() as u32, framed.len() as u32, framed);
+
+        for link_type in &[LinkType::Ppp, LinkType::PppHdlc] {
+            let flows = PcapRecord::convert_records_with_header(vec![record.clone()], &GlobalHeader::new(*link_type, 65535), false).expect("Could not convert records");
+            assert_eq!(flows.len(), 1);
+            assert_eq!(flows[0].source().mac, None);
+            assert_eq!(flows[0].destination().port, 80);
+        }
+
+        let mut wireless = vec![
+            0x08u8, 0x02u8, //frame control, data from ds
+            0x00u8, 0x00u8, //duration
+            0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x01u8, //destination
+            0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x02u8, //bssid
+            0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x03u8, //source
+            0x00u8, 0x00u8, //sequence control
+            0xAAu8, 0xAAu8, 0x03u8, 0x00u8, 0x00u8, 0x00u8, 0x08u8, 0x00u8 //llc/snap, ipv4
+        ];
+        wireless.extend_from_slice(&RAW_DATA[16 + 14..]);
+        let record = PcapRecord::new(std::time::UNIX_EPOCH, wireless.len() as u32, wireless.len() as u32, wireless);
+
+        let flows = PcapRecord::convert_records_with_header(vec![record], &GlobalHeader::new(LinkType::Ieee80211, 65535), false).expect("Could not convert records");
+        assert_eq!(flows.len(), 1);
+        assert_eq!(flows[0].source().mac, Some(MacAddress([0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x03u8])));
+        assert_eq!(flows[0].destination().mac, Some(MacAddress([0x02u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x01u8])));
+        assert_eq!(flows[0].destination().port, 80);
+    }
+
+    #[cfg(all(feature = "flows", feature = "writer"))]
+    #[test]
+    fn convert_records_of_mixed_interfaces() {
+        use super::super::analysis::flow_stats::FlowTable;
+        use super::super::pcapng::{Capture, Interface, Writer};
+
+        let _ = env_logger::try_init();
+
+        let (_, ethernet) = PcapRecord::parse(RAW_DATA, nom::Endianness::Big).expect("Could not parse");
+        let mut cooked = vec![
+            0x00u8, 0x00u8, //packet type, host
+            0x00u8, 0x01u8, //arphrd type, ethernet
+            0x00u8, 0x06u8, //address length, 6
+            0xFFu8, 0xFEu8, 0xFDu8, 0xFCu8, 0xFBu8, 0xFAu8, 0x00u8, 0x00u8, //address FF:FE:FD:FC:FB:FA
+            0x08u8, 0x00u8 //protocol, ipv4
+        ];
+        cooked.extend_from_slice(&RAW_DATA[16 + 14..]);
+        let cooked = PcapRecord::new(std::time::UNIX_EPOCH, cooked.len() as u32, cooked.len() as u32, cooked);
+
+        let mut writer = Writer::new(vec![]).expect("Could not write section header");
+        let eth0 = writer.add_interface(Interface::new(LinkType::Ethernet, 0)).expect("Could not add interface");
+        let any = writer.add_interface(Interface::new(LinkType::LinuxSll, 0)).expect("Could not add interface");
+        writer.write_record(eth0, &ethernet).expect("Could not write record");
+        writer.write_record(any, &cooked).expect("Could not write record");
+        writer.write_record(eth0, &ethernet).expect("Could not write record");
+        let (_, capture) = Capture::parse(&writer.into_inner()).expect("Could not parse capture");
+        let records = capture.into_records();
+        assert_eq!(records.iter().map(|r| r.link_type()).collect::<std::vec::Vec<_>>(), vec![LinkType::Ethernet, LinkType::LinuxSll, LinkType::Ethernet]);
+
+        for record in &records {
+            let parsed = ParsedRecord::try_from(record.clone()).expect("Could not parse layers");
+            assert_eq!(parsed.dst_port(), Some(80));
+            let flow = flow::Flow::try_from(record.clone()).expect("Could not extract flow");
+            assert_eq!( (flow.source().port, flow.destination().port), (50871, 80) );
+        }
+        assert_eq!(flow::Flow::try_from(records[1].clone()).expect("Could not extract flow").destination().mac, None);
+
+        let flows = PcapRecord::convert_records(records.clone(), false).expect("Could not convert records");
+        assert_eq!(flows.len(), 3);
+        assert_eq!(flows[1].record(), &records[1]);
+        #[cfg(feature = "rayon")]
+        assert_eq!(super::super::parallel::convert_records(records.clone(), false).expect("Could not convert records"), flows);
+
+        let table = FlowTable::from_records(&records);
+        assert_eq!(table.flows().len(), 1);
+        assert_eq!(table.flows()[0].packets(), 3);
+    }
+
+    #[cfg(feature = "flows")]
+    #[test]
+    fn convert_records_skipping_link_type() {
+        let _ = env_logger::try_init();
+
+        let (_, record) = PcapRecord::parse(RAW_DATA, nom::Endianness::Big).expect("Could not parse");
+        let mut unsupported = record.clone();
+        unsupported.set_link_type(LinkType::Unknown(2));
+        let records = vec![record.clone(), unsupported, record.clone()];
+
+        let flows = PcapRecord::convert_records(records.clone(), true).expect("Could not convert records");
+        assert_eq!(flows.len(), 2);
+        assert_eq!(flows[1].record(), &record);
+
+        let err = PcapRecord::convert_records(records, false).err().expect("Converted record of unsupported link type");
+        match *err.kind() {
+            errors::ErrorKind::LinkType(link_type) => assert_eq!(link_type, LinkType::Unknown(2)),
+            ref e => panic!("Unexpected error {:?}", e)
+        }
+    }
+
+    #[cfg(feature = "flows")]
+    #[test]
+    fn convert_truncated_record() {
+        let _ = env_logger::try_init();
+
+        let mut data = RAW_DATA.to_vec();
+        data[16 + 16] = 0x04u8; //length, 1218 as on the wire
+        data[16 + 17] = 0xC2u8;
+
+        let (_, record) = PcapRecord::parse(&data, nom::Endianness::Big).expect("Could not parse");
+
+        assert!(record.is_truncated());
+        assert_eq!(record.truncated_length(), 1146);
+
+        let info = flow::Flow::try_from(record.clone()).expect("Could not extract flow");
+
+        assert_eq!(info.source().port, 50871);
+        assert_eq!(info.destination().port, 80);
+        assert_eq!(info.record(), &record);
+    }
+
+    #[cfg(feature = "flows")]
+    #[test]
+    fn convert_fragmented_records() {
+        let _ = env_logger::try_init();
+
+        let mut records = super::super::analysis::defrag::tests::fragments(1, 7, &[0u8; 40], 16);
+        let (_, record) = PcapRecord::parse(RAW_DATA, nom::Endianness::Big).expect("Could not parse");
+        records.insert(0, record);
+
+        let flows = PcapRecord::convert_records(records.clone(), false).expect("Could not convert records");
+
+        assert_eq!(flows.len(), 2);
+        assert_eq!(PcapRecord::convert_records_with_header(records.clone(), &GlobalHeader::new(LinkType::Ethernet, 65535), false).expect("Could not convert records"), flows);
+        assert_eq!(flows.iter().filter(|f| f.reassembled).count(), 1);
+        //in capture order
+        assert!(flows[1].reassembled);
+
+        records.remove(1);
+        assert!(PcapRecord::convert_records(records.clone(), false).is_err());
+        assert_eq!(PcapRecord::convert_records(records, true).expect("Could not convert records").len(), 1);
+
+        //fragments further apart than the timeout aren't reassembled
+        let mut records = super::super::analysis::defrag::tests::fragments(1, 7, &[0u8; 40], 16);
+        let last = records.len() - 1;
+        records[last].set_timestamp(PcapRecord::convert_packet_time(100, 0));
+        assert!(PcapRecord::convert_records(records.clone(), false).is_err());
+        assert!(PcapRecord::convert_records(records, true).expect("Could not convert records").is_empty());
+
+        //fragments of captures of other link types are reassembled too
+        let header = GlobalHeader::new(LinkType::Raw, 65535);
+        let framed = super::super::analysis::defrag::tests::fragments(1, 7, &[0u8; 40], 16);
+        let expected = PcapRecord::convert_records(framed.clone(), false).expect("Could not convert records");
+        let records = framed.into_iter().map(|record| {
+            let payload = record.payload()[14..].to_vec();
+            PcapRecord::new(*record.timestamp(), payload.len() as u32, payload.len() as u32, payload)
+        }).collect::<std::vec::Vec<_>>();
+        let flows = PcapRecord::convert_records_with_header(records, &header, false).expect("Could not convert records");
+        assert_eq!(flows.len(), 1);
+        assert!(flows[0].reassembled);
+        assert_eq!( (flows[0].source().port, flows[0].destination().port), (expected[0].source().port, expected[0].destination().port) );
+    }
+}
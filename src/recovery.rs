@@ -0,0 +1,344 @@
+use super::prelude::*;
+use super::global_header::{GlobalHeader, GLOBAL_HEADER_LENGTH};
+use super::record::RECORD_HEADER_LENGTH;
+
+use std;
+
+///
+/// Largest record length considered plausible unless configured otherwise, tcpdump's maximum snap
+/// length
+///
+pub const DEFAULT_MAX_RECORD_LENGTH: u32 = 262144;
+
+///
+/// How a damaged capture is recovered
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RecoveryOptions {
+    ///
+    /// Skip past a damaged region to the next plausible record header, rather than stopping at it
+    ///
+    pub resynchronize: bool,
+    ///
+    /// Largest captured or original length a plausible record header has
+    ///
+    pub max_record_length: u32
+}
+
+impl Default for RecoveryOptions {
+    fn default() -> RecoveryOptions {
+        RecoveryOptions {
+            resynchronize: true,
+            max_record_length: DEFAULT_MAX_RECORD_LENGTH
+        }
+    }
+}
+
+///
+/// Bytes at the end of a capture that don't hold a complete record, e.g. a record cut off when
+/// tcpdump crashed
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TruncatedCapture {
+    ///
+    /// Offset of the first byte not recovered, from the start of the capture
+    ///
+    pub offset: usize,
+    ///
+    /// Bytes from `offset` to the end of the capture
+    ///
+    pub length: usize,
+    ///
+    /// Records recovered before `offset`
+    ///
+    pub records: usize,
+    ///
+    /// Bytes missing to complete the record at `offset`, when its header was plausible
+    ///
+    pub missing: Option<usize>
+}
+
+impl std::fmt::Display for TruncatedCapture {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.missing {
+            Some(missing) => write!(f, "Capture truncated at offset {} after record {}, {} bytes short of a complete record",
+                                    self.offset, self.records, missing),
+            None => write!(f, "Capture truncated at offset {} after record {}, {} bytes of trailing garbage",
+                           self.offset, self.records, self.length)
+        }
+    }
+}
+
+///
+/// Damaged region skipped while resynchronizing on the next plausible record header
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SkippedBytes {
+    pub offset: usize,
+    pub length: usize,
+    ///
+    /// Records recovered before the region
+    ///
+    pub records: usize
+}
+
+///
+/// Records recovered from a possibly damaged capture, with the regions that couldn't be
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecoveredCapture {
+    header: GlobalHeader,
+    records: std::vec::Vec<PcapRecord>,
+    skipped: std::vec::Vec<SkippedBytes>,
+    truncated: Option<TruncatedCapture>
+}
+
+impl RecoveredCapture {
+    pub fn header(&self) -> &GlobalHeader { &self.header }
+    pub fn records(&self) -> &std::vec::Vec<PcapRecord> { &self.records }
+    pub fn into_records(self) -> std::vec::Vec<PcapRecord> { self.records }
+    pub fn skipped(&self) -> &std::vec::Vec<SkippedBytes> { &self.skipped }
+    pub fn truncated(&self) -> Option<&TruncatedCapture> { self.truncated.as_ref() }
+
+    ///
+    /// Bytes of the capture not recovered as records, skipped and trailing
+    ///
+    pub fn lost_bytes(&self) -> usize {
+        self.skipped.iter().map(|s| s.length).sum::<usize>() + self.truncated.as_ref().map(|t| t.length).unwrap_or(0)
+    }
+
+    ///
+    /// Whether every byte of the capture was recovered as records
+    ///
+    pub fn is_complete(&self) -> bool {
+        self.skipped.is_empty() && self.truncated.is_none()
+    }
+}
+
+///
+/// Record read at an offset, with its payload borrowed from the capture until it is accepted, so
+/// offsets tried while resynchronizing don't each copy a payload
+///
+struct Found<'a> {
+    timestamp: std::time::SystemTime,
+    original_length: u32,
+    payload: &'a [u8]
+}
+
+impl<'a> Found<'a> {
+    fn to_record(&self, header: &GlobalHeader) -> PcapRecord {
+        PcapRecord::new(self.timestamp, self.payload.len() as u32, self.original_length, self.payload.to_vec()).with_header(header)
+    }
+}
+
+///
+/// Outcome of reading a record at an offset
+///
+enum Candidate<'a> {
+    Complete(Found<'a>, usize),
+    ///
+    /// Plausible header, or part of one, running past the end of the capture by this many bytes
+    ///
+    Incomplete(usize),
+    Implausible
+}
+
+fn candidate<'a>(input: &'a [u8], offset: usize, header: &GlobalHeader, options: &RecoveryOptions) -> Candidate<'a> {
+    let current = &input[offset..];
+    let header_length = header.record_header_length();
+    if current.len() < header_length {
+        return Candidate::Incomplete(header_length - current.len())
+    }
+
+    let endianness = header.endianness();
+    let parsed: nom::IResult<&[u8], (u32, u32, u32, u32)> = do_parse!(current,
+        ts_seconds: u32!(endianness) >>
+        ts_fraction: u32!(endianness) >>
+        actual_length: u32!(endianness) >>
+        original_length: u32!(endianness) >>
+        _extension: take!(header_length - RECORD_HEADER_LENGTH) >>
+        ( (ts_seconds, ts_fraction, actual_length, original_length) )
+    );
+    let (rem, (ts_seconds, ts_fraction, actual_length, original_length)) = match parsed {
+        Ok(parsed) => parsed,
+        Err(_) => return Candidate::Implausible
+    };
+
+    if ts_fraction >= header.precision().fractions_per_second() ||
+        actual_length > original_length ||
+        original_length > options.max_record_length {
+        return Candidate::Implausible
+    }
+    if (actual_length as usize) > rem.len() {
+        return Candidate::Incomplete(actual_length as usize - rem.len())
+    }
+
+    let found = Found {
+        timestamp: header.precision().to_timestamp(ts_seconds, ts_fraction),
+        original_length: original_length,
+        payload: &rem[..actual_length as usize]
+    };
+    Candidate::Complete(found, offset + header_length + actual_length as usize)
+}
+
+///
+/// Offset after `from` of the next record that is plausible, complete, no earlier than `after`,
+/// and followed by either the end of the capture or another plausible header
+///
+fn resynchronize(
+    input: &[u8],
+    from: usize,
+    after: Option<std::time::SystemTime>,
+    header: &GlobalHeader,
+    options: &RecoveryOptions
+) -> Option<usize> {
+    (from..input.len()).find(|offset| {
+        match candidate(input, *offset, header, options) {
+            Candidate::Complete(found, next) => {
+                after.map(|ts| found.timestamp >= ts).unwrap_or(true) &&
+                    (next == input.len() || !matches!(candidate(input, next, header, options), Candidate::Implausible))
+            }
+            _ => false
+        }
+    })
+}
+
+///
+/// Parse a libpcap capture (including global header) that may be damaged, recovering the records
+/// it still holds rather than silently stopping at the first bad one. Record headers with
+/// out of range timestamps or lengths are treated as damage; with `resynchronize`, parsing skips to
+/// the next plausible record, otherwise the rest of the capture is reported as truncated.
+///
+pub fn recover(input: &[u8], options: RecoveryOptions) -> errors::Result<RecoveredCapture> {
+    let (_, header) = GlobalHeader::parse(input)
+        .map_err(|e| errors::Error::from_nom(e, errors::Layer::Record, input))?;
+
+    let mut records: std::vec::Vec<PcapRecord> = vec![];
+    let mut skipped = vec![];
+    let mut truncated = None;
+    let mut offset = GLOBAL_HEADER_LENGTH;
+
+    while offset < input.len() {
+        let missing = match candidate(input, offset, &header, &options) {
+            Candidate::Complete(found, next) => {
+                records.push(found.to_record(&header));
+                offset = next;
+                continue
+            }
+            Candidate::Incomplete(missing) => Some(missing),
+            Candidate::Implausible => None
+        };
+
+        let previous = records.last().map(|r| *r.timestamp());
+        let next = if options.resynchronize {
+            resynchronize(input, offset + 1, previous, &header, &options)
+        } else {
+            None
+        };
+        match next {
+            Some(next) => {
+                debug!("Skipping {} damaged bytes at offset {}", next - offset, offset);
+                skipped.push(SkippedBytes {
+                    offset: offset,
+                    length: next - offset,
+                    records: records.len()
+                });
+                offset = next;
+            }
+            None => {
+                let truncation = TruncatedCapture {
+                    offset: offset,
+                    length: input.len() - offset,
+                    records: records.len(),
+                    missing: missing
+                };
+                debug!("{}", truncation);
+                truncated = Some(truncation);
+                break
+            }
+        }
+    }
+
+    Ok(RecoveredCapture {
+        header: header,
+        records: records,
+        skipped: skipped,
+        truncated: truncated
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+    use super::super::link_type::LinkType;
+    use super::super::nom::Endianness;
+
+    fn record_at(seconds: u32, length: usize) -> PcapRecord {
+        PcapRecord::new(PcapRecord::convert_packet_time(seconds, 0), length as u32, length as u32, vec![0x01u8; length])
+    }
+
+    fn capture(records: &[PcapRecord]) -> std::vec::Vec<u8> {
+        let header = GlobalHeader::new(LinkType::Ethernet, 65535).with_endianness(Endianness::Big);
+        let mut bytes = header.to_bytes();
+        for record in records {
+            bytes.extend_from_slice(&record.to_bytes(Endianness::Big));
+        }
+        bytes
+    }
+
+    #[test]
+    fn recover_complete() {
+        let _ = env_logger::try_init();
+
+        let records = vec![record_at(1527868899, 10), record_at(1527868900, 20)];
+        let recovered = recover(&capture(&records), RecoveryOptions::default()).expect("Could not recover");
+        assert!(recovered.is_complete());
+        assert_eq!(recovered.records(), &records);
+    }
+
+    #[test]
+    fn recover_truncated() {
+        let _ = env_logger::try_init();
+
+        let records = vec![record_at(1527868899, 10), record_at(1527868900, 20)];
+        let bytes = capture(&records);
+        let length = bytes.len();
+
+        let recovered = recover(&bytes[..length - 5], RecoveryOptions::default()).expect("Could not recover");
+        assert_eq!(recovered.records(), &records[..1].to_vec());
+        assert_eq!(recovered.truncated(), Some(&TruncatedCapture {
+            offset: 24 + 26,
+            length: 31,
+            records: 1,
+            missing: Some(5)
+        }));
+        assert_eq!(recovered.lost_bytes(), 31);
+
+        let recovered = recover(&bytes[..24 + 26 + 7], RecoveryOptions::default()).expect("Could not recover");
+        assert_eq!(recovered.truncated().and_then(|t| t.missing), Some(9));
+    }
+
+    #[test]
+    fn recover_resynchronize() {
+        let _ = env_logger::try_init();
+
+        let records = vec![record_at(1527868899, 10), record_at(1527868900, 20), record_at(1527868901, 4)];
+        let mut bytes = capture(&records[..1]);
+        bytes.extend_from_slice(&[0xFFu8; 7]);
+        bytes.extend_from_slice(&capture(&records[1..])[24..]);
+
+        let recovered = recover(&bytes, RecoveryOptions::default()).expect("Could not recover");
+        assert_eq!(recovered.records(), &records);
+        assert_eq!(recovered.skipped(), &vec![SkippedBytes { offset: 24 + 26, length: 7, records: 1 }]);
+        assert!(recovered.truncated().is_none());
+
+        let options = RecoveryOptions { resynchronize: false, ..RecoveryOptions::default() };
+        let recovered = recover(&bytes, options).expect("Could not recover");
+        assert_eq!(recovered.records().len(), 1);
+        let truncated = recovered.truncated().expect("Not truncated");
+        assert_eq!(truncated.length, 7 + 36 + 20);
+        assert!(truncated.missing.is_none());
+    }
+}
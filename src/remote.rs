@@ -0,0 +1,625 @@
+use super::prelude::*;
+use super::global_header::{GlobalHeader, GLOBAL_HEADER_LENGTH};
+use super::link_type::LinkType;
+
+use self::nom::*;
+
+use std;
+use std::io::{Read, Write};
+
+///
+/// Port rpcapd listens on for control connections
+///
+pub const RPCAP_PORT: u16 = 2002;
+
+const RPCAP_VERSION: u8 = 0;
+const HEADER_LENGTH: usize = 8;
+const REPLY_FLAG: u8 = 0x80;
+const DEFAULT_SNAP_LENGTH: u32 = 65535;
+const DEFAULT_READ_TIMEOUT: u32 = 1000; //milliseconds
+
+const BPF_RET_K: u16 = 0x06;
+const FILTER_BPF: u16 = 0;
+const AUTH_NULL: u16 = 0;
+const AUTH_PASSWORD: u16 = 1;
+const START_CAPTURE_FLAG_PROMISCUOUS: u16 = 0x01;
+
+///
+/// Rpcap message types https://www.tcpdump.org/manpages/rpcapd.8.html, replies have the reply flag set
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MessageType {
+    Error,
+    OpenRequest,
+    StartCaptureRequest,
+    Close,
+    Packet,
+    AuthRequest,
+    EndCaptureRequest,
+    Other(u8)
+}
+
+impl MessageType {
+    pub fn new(value: u8) -> MessageType {
+        match value {
+            1 => MessageType::Error,
+            3 => MessageType::OpenRequest,
+            4 => MessageType::StartCaptureRequest,
+            6 => MessageType::Close,
+            7 => MessageType::Packet,
+            8 => MessageType::AuthRequest,
+            10 => MessageType::EndCaptureRequest,
+            x => MessageType::Other(x)
+        }
+    }
+
+    pub fn value(&self) -> u8 {
+        match *self {
+            MessageType::Error => 1,
+            MessageType::OpenRequest => 3,
+            MessageType::StartCaptureRequest => 4,
+            MessageType::Close => 6,
+            MessageType::Packet => 7,
+            MessageType::AuthRequest => 8,
+            MessageType::EndCaptureRequest => 10,
+            MessageType::Other(x) => x
+        }
+    }
+}
+
+///
+/// Header preceding every rpcap message
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct MessageHeader {
+    version: u8,
+    message_type: u8,
+    value: u16,
+    length: u32
+}
+
+impl MessageHeader {
+    fn new(message_type: MessageType, value: u16, length: usize) -> MessageHeader {
+        MessageHeader {
+            version: RPCAP_VERSION,
+            message_type: message_type.value(),
+            value: value,
+            length: length as u32
+        }
+    }
+
+    fn to_bytes(&self) -> [u8; HEADER_LENGTH] {
+        [
+            self.version,
+            self.message_type,
+            (self.value >> 8) as u8, self.value as u8,
+            (self.length >> 24) as u8, (self.length >> 16) as u8, (self.length >> 8) as u8, self.length as u8
+        ]
+    }
+
+    fn parse(input: &[u8]) -> IResult<&[u8], MessageHeader> {
+        do_parse!(input,
+
+            version: be_u8 >>
+            message_type: be_u8 >>
+            value: be_u16 >>
+            length: be_u32 >>
+
+            (
+                MessageHeader {
+                    version: version,
+                    message_type: message_type,
+                    value: value,
+                    length: length
+                }
+            )
+        )
+    }
+}
+
+fn send<W: Write>(writer: &mut W, message_type: MessageType, value: u16, body: &[u8]) -> errors::Result<()> {
+    writer.write_all(&MessageHeader::new(message_type, value, body.len()).to_bytes())?;
+    writer.write_all(body)?;
+    writer.flush()?;
+    Ok(())
+}
+
+///
+/// Read from `reader` until `bytes` is full or the input ends, returning the bytes read
+///
+fn read_full<R: Read>(reader: &mut R, bytes: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < bytes.len() {
+        match reader.read(&mut bytes[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e)
+        }
+    }
+    Ok(filled)
+}
+
+///
+/// Read `length` bytes the peer claims to send, growing the buffer only as they arrive rather than
+/// allocating `length` up front, failing with the bytes read if the input ends first
+///
+fn read_body<R: Read>(reader: &mut R, length: u32, read: usize) -> errors::Result<std::vec::Vec<u8>> {
+    let mut body = vec![];
+    reader.take(length as u64).read_to_end(&mut body)?;
+    if body.len() < length as usize {
+        return Err(errors::Error::from_kind(errors::ErrorKind::IncompleteParse(read + body.len())))
+    }
+    Ok(body)
+}
+
+///
+/// Next message from `reader`, failing with `UnexpectedEof` if the input ends before it begins
+///
+fn receive<R: Read>(reader: &mut R) -> errors::Result<(MessageHeader, std::vec::Vec<u8>)> {
+    let mut header_bytes = [0u8; HEADER_LENGTH];
+    match read_full(reader, &mut header_bytes)? {
+        0 => return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into()),
+        HEADER_LENGTH => {}
+        n => return Err(errors::Error::from_kind(errors::ErrorKind::IncompleteParse(n)))
+    }
+    let (_, header) = MessageHeader::parse(&header_bytes)
+        .map_err(|e| errors::Error::from_nom(e, errors::Layer::Rpcap, &header_bytes))?;
+    let body = read_body(reader, header.length, HEADER_LENGTH)?;
+    Ok( (header, body) )
+}
+
+///
+/// Body of the reply to a request of `request` type, failing with the server's message for an error
+///
+fn expect_reply<R: Read>(reader: &mut R, request: MessageType) -> errors::Result<std::vec::Vec<u8>> {
+    let (header, body) = receive(reader)?;
+    if header.message_type == MessageType::Error.value() {
+        Err(errors::Error::from_kind(errors::ErrorKind::Rpcap(header.value, String::from_utf8_lossy(&body).into_owned())))
+    } else if header.message_type != request.value() | REPLY_FLAG {
+        Err(format!("Expected reply to rpcap message {:?}, received message type {}", request, header.message_type).into())
+    } else {
+        Ok(body)
+    }
+}
+
+fn put_u16(bytes: &mut std::vec::Vec<u8>, value: u16) {
+    bytes.extend_from_slice(&[(value >> 8) as u8, value as u8]);
+}
+
+fn put_u32(bytes: &mut std::vec::Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&[(value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, value as u8]);
+}
+
+///
+/// Reads a libpcap capture streamed over a connection, e.g. from `tcpdump -w - | nc -l`, yielding
+/// one record at a time as it arrives
+///
+pub struct PcapStreamReader<R> {
+    reader: R,
+    header: GlobalHeader
+}
+
+impl PcapStreamReader<std::net::TcpStream> {
+    ///
+    /// Connect to a probe serving a pcap stream and read its global header
+    ///
+    pub fn connect<A: std::net::ToSocketAddrs>(addr: A) -> errors::Result<PcapStreamReader<std::net::TcpStream>> {
+        PcapStreamReader::new(std::net::TcpStream::connect(addr)?)
+    }
+}
+
+impl<R: Read> PcapStreamReader<R> {
+    pub fn new(mut reader: R) -> errors::Result<PcapStreamReader<R>> {
+        let mut header_bytes = [0u8; GLOBAL_HEADER_LENGTH];
+        reader.read_exact(&mut header_bytes)?;
+        let (_, header) = GlobalHeader::parse(&header_bytes)
+            .map_err(|e| errors::Error::from_nom(e, errors::Layer::GlobalHeader, &header_bytes))?;
+        debug!("Global header version {}.{}, with endianness {:?}", header.version_major(), header.version_minor(), header.endianness());
+
+        Ok(PcapStreamReader {
+            reader: reader,
+            header: header
+        })
+    }
+
+    pub fn header(&self) -> &GlobalHeader { &self.header }
+
+    ///
+    /// Next record, or `None` once the stream ends between records. A stream ending part way through
+    /// a record is an error with the bytes of the partial record.
+    ///
+    pub fn next_record(&mut self) -> errors::Result<Option<PcapRecord>> {
+        let header_length = self.header.record_header_length();
+        let mut header_bytes = vec![0u8; header_length];
+        match read_full(&mut self.reader, &mut header_bytes)? {
+            0 => return Ok(None),
+            n if n < header_length => return Err(errors::Error::from_kind(errors::ErrorKind::IncompleteParse(n))),
+            _ => {}
+        }
+        let (_, header) = RecordHeader::parse_for(&header_bytes, &self.header)
+            .map_err(|e| errors::Error::from_nom(e, errors::Layer::Record, &header_bytes))?;
+        let payload = read_body(&mut self.reader, header.actual_length(), header_length)?;
+
+        Ok(Some(PcapRecord::new(*header.timestamp(), header.actual_length(), header.original_length(), payload).with_header(&self.header)))
+    }
+}
+
+impl<R: Read> Iterator for PcapStreamReader<R> {
+    type Item = errors::Result<PcapRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_record() {
+            Ok(record) => record.map(Ok),
+            Err(e) => Some(Err(e))
+        }
+    }
+}
+
+///
+/// Credentials to authenticate to rpcapd with, which otherwise must allow null authentication
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String
+}
+
+///
+/// Options for a remote capture
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CaptureOptions {
+    pub snap_length: u32,
+    ///
+    /// How long the server waits to fill a buffer before sending what it has, in milliseconds
+    ///
+    pub read_timeout: u32,
+    pub promiscuous: bool
+}
+
+impl Default for CaptureOptions {
+    fn default() -> CaptureOptions {
+        CaptureOptions {
+            snap_length: DEFAULT_SNAP_LENGTH,
+            read_timeout: DEFAULT_READ_TIMEOUT,
+            promiscuous: false
+        }
+    }
+}
+
+///
+/// Control connection to an rpcapd remote capture daemon (active mode is not supported)
+///
+pub struct RpcapClient {
+    control: std::net::TcpStream
+}
+
+impl RpcapClient {
+    ///
+    /// Connect to rpcapd, authenticating with `credentials` or with null authentication
+    ///
+    pub fn connect<A: std::net::ToSocketAddrs>(addr: A, credentials: Option<&Credentials>) -> errors::Result<RpcapClient> {
+        let mut body = vec![];
+        match credentials {
+            Some(credentials) => {
+                if credentials.username.len() > std::u16::MAX as usize || credentials.password.len() > std::u16::MAX as usize {
+                    return Err(format!("Username and password must each be at most {} bytes", std::u16::MAX).into())
+                }
+                put_u16(&mut body, AUTH_PASSWORD);
+                put_u16(&mut body, 0);
+                put_u16(&mut body, credentials.username.len() as u16);
+                put_u16(&mut body, credentials.password.len() as u16);
+                body.extend_from_slice(credentials.username.as_bytes());
+                body.extend_from_slice(credentials.password.as_bytes());
+            }
+            None => {
+                put_u16(&mut body, AUTH_NULL);
+                put_u16(&mut body, 0);
+                put_u16(&mut body, 0);
+                put_u16(&mut body, 0);
+            }
+        }
+        let mut control = std::net::TcpStream::connect(addr)?;
+        send(&mut control, MessageType::AuthRequest, 0, &body)?;
+        expect_reply(&mut control, MessageType::AuthRequest)?;
+
+        Ok(RpcapClient {
+            control: control
+        })
+    }
+
+    ///
+    /// Open `interface` on the remote host and start capturing on it, receiving packets over a
+    /// separate data connection to the port the server chooses
+    ///
+    pub fn start_capture(mut self, interface: &str, options: &CaptureOptions) -> errors::Result<RemoteCapture> {
+        send(&mut self.control, MessageType::OpenRequest, 0, interface.as_bytes())?;
+        let reply = expect_reply(&mut self.control, MessageType::OpenRequest)?;
+        let (_, link_type) = map!(&reply[..], be_u32, LinkType::new)
+            .map_err(|e| errors::Error::from_nom(e, errors::Layer::Rpcap, &reply))?;
+
+        let mut body = vec![];
+        put_u32(&mut body, options.snap_length);
+        put_u32(&mut body, options.read_timeout);
+        put_u16(&mut body, if options.promiscuous { START_CAPTURE_FLAG_PROMISCUOUS } else { 0 });
+        put_u16(&mut body, 0); //let the server choose the data port
+        //accept everything, as a single bpf return instruction
+        put_u16(&mut body, FILTER_BPF);
+        put_u16(&mut body, 0);
+        put_u32(&mut body, 1);
+        put_u16(&mut body, BPF_RET_K);
+        body.extend_from_slice(&[0u8, 0u8]); //jt, jf
+        put_u32(&mut body, options.snap_length);
+        send(&mut self.control, MessageType::StartCaptureRequest, 0, &body)?;
+
+        let reply = expect_reply(&mut self.control, MessageType::StartCaptureRequest)?;
+        let (_, (_buffer_size, port)) = do_parse!(&reply[..],
+            buffer_size: be_u32 >>
+            port: be_u16 >>
+            ( (buffer_size, port) )
+        ).map_err(|e| errors::Error::from_nom(e, errors::Layer::Rpcap, &reply))?;
+        let data = std::net::TcpStream::connect( (self.control.peer_addr()?.ip(), port) )?;
+        debug!("Capturing on {} with link type {}, data on port {}", interface, link_type, port);
+
+        Ok(RemoteCapture {
+            control: self.control,
+            data: data,
+            link_type: link_type
+        })
+    }
+}
+
+///
+/// Capture running on an rpcapd host, yielding the packets it sends as records
+///
+pub struct RemoteCapture {
+    control: std::net::TcpStream,
+    data: std::net::TcpStream,
+    link_type: LinkType
+}
+
+impl RemoteCapture {
+    ///
+    /// Link layer header type of the captured interface
+    ///
+    pub fn link_type(&self) -> LinkType { self.link_type }
+
+    ///
+    /// Next packet sent by the server, or `None` once it closes the data connection
+    ///
+    pub fn next_record(&mut self) -> errors::Result<Option<PcapRecord>> {
+        let (header, body) = match receive(&mut self.data) {
+            Ok(message) => message,
+            Err(e) => {
+                return match *e.kind() {
+                    errors::ErrorKind::Io(ref io) if io.kind() == std::io::ErrorKind::UnexpectedEof => Ok(None),
+                    _ => Err(e)
+                }
+            }
+        };
+        if header.message_type != MessageType::Packet.value() {
+            return Err(format!("Expected rpcap packet, received message type {}", header.message_type).into())
+        }
+
+        let (payload, (seconds, microseconds, actual_length, original_length)) = do_parse!(&body[..],
+            seconds: be_u32 >>
+            microseconds: be_u32 >>
+            actual_length: be_u32 >>
+            original_length: be_u32 >>
+            _packet_number: be_u32 >>
+            ( (seconds, microseconds, actual_length, original_length) )
+        ).map_err(|e| errors::Error::from_nom(e, errors::Layer::Rpcap, &body))?;
+        let payload = payload.get(..actual_length as usize).ok_or_else(|| errors::Error::from_kind(errors::ErrorKind::IncompleteParse(payload.len())))?;
+
+        let mut record = PcapRecord::new(PcapRecord::convert_packet_time(seconds, microseconds), actual_length, original_length, payload.to_vec());
+        record.set_link_type(self.link_type);
+        Ok(Some(record))
+    }
+
+    ///
+    /// Stop the capture and close the connection to the server
+    ///
+    pub fn close(mut self) -> errors::Result<()> {
+        send(&mut self.control, MessageType::EndCaptureRequest, 0, &[])?;
+        expect_reply(&mut self.control, MessageType::EndCaptureRequest)?;
+        send(&mut self.control, MessageType::Close, 0, &[])
+    }
+}
+
+impl Iterator for RemoteCapture {
+    type Item = errors::Result<PcapRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_record() {
+            Ok(record) => record.map(Ok),
+            Err(e) => Some(Err(e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    const PAYLOAD: &'static [u8] = &[0x01u8, 0x02u8, 0x03u8, 0x04u8];
+
+    #[test]
+    fn pcap_stream() {
+        let _ = env_logger::try_init();
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("Could not bind");
+        let addr = listener.local_addr().expect("No address");
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("Could not accept");
+            stream.write_all(&[
+                0xD4u8, 0xC3u8, 0xB2u8, 0xA1u8, //magic number, little endian
+                0x02u8, 0x00u8, 0x04u8, 0x00u8, //version 2.4
+                0x00u8, 0x00u8, 0x00u8, 0x00u8, //zone
+                0x00u8, 0x00u8, 0x00u8, 0x00u8, //sig figs
+                0xFFu8, 0xFFu8, 0x00u8, 0x00u8, //snap length
+                0x01u8, 0x00u8, 0x00u8, 0x00u8, //ethernet
+                0x01u8, 0x00u8, 0x00u8, 0x00u8, //seconds
+                0x02u8, 0x00u8, 0x00u8, 0x00u8, //microseconds
+                0x04u8, 0x00u8, 0x00u8, 0x00u8, //actual length
+                0x04u8, 0x00u8, 0x00u8, 0x00u8 //original length
+            ]).expect("Could not write");
+            stream.write_all(PAYLOAD).expect("Could not write");
+        });
+
+        let mut reader = PcapStreamReader::connect(addr).expect("Could not connect");
+        assert_eq!(reader.header().snap_length(), 65535);
+
+        let records = reader.by_ref().collect::<errors::Result<std::vec::Vec<_>>>().expect("Could not read records");
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].timestamp(), &PcapRecord::convert_packet_time(1, 2));
+        assert_eq!(records[0].payload(), &PAYLOAD.to_vec());
+        server.join().expect("Server failed");
+    }
+
+    #[test]
+    fn rpcap_capture() {
+        let _ = env_logger::try_init();
+
+        let control_listener = std::net::TcpListener::bind("127.0.0.1:0").expect("Could not bind");
+        let data_listener = std::net::TcpListener::bind("127.0.0.1:0").expect("Could not bind");
+        let addr = control_listener.local_addr().expect("No address");
+        let data_port = data_listener.local_addr().expect("No address").port();
+
+        let server = std::thread::spawn(move || {
+            let (mut control, _) = control_listener.accept().expect("Could not accept");
+
+            let (header, body) = receive(&mut control).expect("No auth request");
+            assert_eq!(MessageType::new(header.message_type), MessageType::AuthRequest);
+            assert_eq!(body, vec![0u8; 8]);
+            send(&mut control, MessageType::Other(0x88), 0, &[]).expect("Could not reply");
+
+            let (header, body) = receive(&mut control).expect("No open request");
+            assert_eq!(MessageType::new(header.message_type), MessageType::OpenRequest);
+            assert_eq!(body, b"eth0".to_vec());
+            send(&mut control, MessageType::Other(0x83), 0, &[0, 0, 0, 1, 0, 0, 0, 0]).expect("Could not reply");
+
+            let (header, body) = receive(&mut control).expect("No start capture request");
+            assert_eq!(MessageType::new(header.message_type), MessageType::StartCaptureRequest);
+            assert_eq!(body.len(), 12 + 8 + 8);
+            send(&mut control, MessageType::Other(0x84), 0, &[0, 0, 0x80, 0, (data_port >> 8) as u8, data_port as u8, 0, 0]).expect("Could not reply");
+
+            let (mut data, _) = data_listener.accept().expect("Could not accept");
+            let mut packet = vec![
+                0x00u8, 0x00u8, 0x00u8, 0x01u8, //seconds
+                0x00u8, 0x00u8, 0x00u8, 0x02u8, //microseconds
+                0x00u8, 0x00u8, 0x00u8, 0x04u8, //actual length
+                0x00u8, 0x00u8, 0x00u8, 0x40u8, //original length
+                0x00u8, 0x00u8, 0x00u8, 0x01u8 //packet number
+            ];
+            packet.extend_from_slice(PAYLOAD);
+            send(&mut data, MessageType::Packet, 0, &packet).expect("Could not send packet");
+            drop(data);
+
+            let (header, _) = receive(&mut control).expect("No end capture request");
+            assert_eq!(MessageType::new(header.message_type), MessageType::EndCaptureRequest);
+            send(&mut control, MessageType::Other(0x8A), 0, &[]).expect("Could not reply");
+            let (header, _) = receive(&mut control).expect("No close");
+            assert_eq!(MessageType::new(header.message_type), MessageType::Close);
+        });
+
+        let client = RpcapClient::connect(addr, None).expect("Could not connect");
+        let mut capture = client.start_capture("eth0", &CaptureOptions::default()).expect("Could not start capture");
+        assert_eq!(capture.link_type(), LinkType::Ethernet);
+
+        let record = capture.next().expect("No packet").expect("Could not read packet");
+        assert_eq!(record.payload(), &PAYLOAD.to_vec());
+        assert_eq!(record.original_length(), 64);
+        assert!(capture.next().is_none());
+
+        capture.close().expect("Could not close");
+        server.join().expect("Server failed");
+    }
+
+    #[test]
+    fn rpcap_error() {
+        let _ = env_logger::try_init();
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("Could not bind");
+        let addr = listener.local_addr().expect("No address");
+        let server = std::thread::spawn(move || {
+            let (mut control, _) = listener.accept().expect("Could not accept");
+            receive(&mut control).expect("No auth request");
+            send(&mut control, MessageType::Error, 4, b"Authentication failed").expect("Could not reply");
+        });
+
+        let credentials = Credentials {
+            username: "user".to_string(),
+            password: "wrong".to_string()
+        };
+        let err = RpcapClient::connect(addr, Some(&credentials)).err().expect("Authenticated");
+        assert_eq!(format!("{}", err), "Remote capture error 4: Authentication failed");
+        server.join().expect("Server failed");
+    }
+
+    #[test]
+    fn rpcap_long_credentials() {
+        let _ = env_logger::try_init();
+
+        let credentials = Credentials {
+            username: "u".repeat(std::u16::MAX as usize + 1),
+            password: "password".to_string()
+        };
+        //rejected before connecting, so the address is never used
+        assert!(RpcapClient::connect("127.0.0.1:0", Some(&credentials)).is_err());
+    }
+
+    #[test]
+    fn rpcap_message_ended_early() {
+        let _ = env_logger::try_init();
+
+        //header claiming a 4 GiB body, which is not allocated up front
+        let message = [RPCAP_VERSION, MessageType::Packet.value(), 0x00u8, 0x00u8, 0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8, 0x01u8, 0x02u8];
+        let err = receive(&mut &message[..]).err().expect("Received a partial message");
+        match *err.kind() {
+            errors::ErrorKind::IncompleteParse(read) => assert_eq!(read, 10),
+            ref e => panic!("Unexpected error {:?}", e)
+        }
+
+        let err = receive(&mut &message[..4]).err().expect("Received a partial header");
+        match *err.kind() {
+            errors::ErrorKind::IncompleteParse(read) => assert_eq!(read, 4),
+            ref e => panic!("Unexpected error {:?}", e)
+        }
+    }
+
+    #[test]
+    fn pcap_stream_ended_early() {
+        let _ = env_logger::try_init();
+
+        let mut stream = vec![
+            0xD4u8, 0xC3u8, 0xB2u8, 0xA1u8, //magic number, little endian
+            0x02u8, 0x00u8, 0x04u8, 0x00u8, //version 2.4
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, //zone
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, //sig figs
+            0xFFu8, 0xFFu8, 0x00u8, 0x00u8, //snap length
+            0x01u8, 0x00u8, 0x00u8, 0x00u8, //ethernet
+            0x01u8, 0x00u8, 0x00u8, 0x00u8, //seconds
+            0x02u8, 0x00u8, 0x00u8, 0x00u8, //microseconds
+            0xFFu8, 0xFFu8, 0xFFu8, 0x7Fu8, //actual length, 2 GiB
+            0xFFu8, 0xFFu8, 0xFFu8, 0x7Fu8 //original length
+        ];
+        stream.extend_from_slice(PAYLOAD);
+
+        let mut reader = PcapStreamReader::new(&stream[..]).expect("Could not read header");
+        assert!(reader.next_record().is_err());
+
+        //partial record header
+        let mut reader = PcapStreamReader::new(&stream[..GLOBAL_HEADER_LENGTH + 6]).expect("Could not read header");
+        let err = reader.next_record().err().expect("Read a partial record header");
+        match *err.kind() {
+            errors::ErrorKind::IncompleteParse(read) => assert_eq!(read, 6),
+            ref e => panic!("Unexpected error {:?}", e)
+        }
+
+        let mut reader = PcapStreamReader::new(&stream[..GLOBAL_HEADER_LENGTH]).expect("Could not read header");
+        assert!(reader.next_record().expect("Could not read record").is_none());
+    }
+}
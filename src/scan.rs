@@ -0,0 +1,207 @@
+use super::prelude::*;
+use super::global_header::GlobalHeader;
+use super::record::RecordHeader;
+
+use std;
+
+///
+/// Record length problem found while scanning, along with the index of the record it was found in
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum LengthInconsistency {
+    ExceedsOriginalLength { index: usize, actual_length: u32, original_length: u32 },
+    ExceedsSnapLength { index: usize, actual_length: u32, snap_length: u32 },
+    TruncatedRecord { index: usize, available: usize, needed: usize }
+}
+
+///
+/// Summary of a capture produced by walking only the record headers
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ScanReport {
+    records: usize,
+    captured_bytes: u64,
+    original_bytes: u64,
+    first_timestamp: Option<std::time::SystemTime>,
+    last_timestamp: Option<std::time::SystemTime>,
+    min_captured_length: Option<u32>,
+    max_captured_length: Option<u32>,
+    inconsistencies: std::vec::Vec<LengthInconsistency>
+}
+
+impl ScanReport {
+    pub fn records(&self) -> usize { self.records }
+    pub fn captured_bytes(&self) -> u64 { self.captured_bytes }
+    pub fn original_bytes(&self) -> u64 { self.original_bytes }
+    pub fn first_timestamp(&self) -> Option<&std::time::SystemTime> { self.first_timestamp.as_ref() }
+    pub fn last_timestamp(&self) -> Option<&std::time::SystemTime> { self.last_timestamp.as_ref() }
+
+    ///
+    /// Shortest and longest captured length of a record, `None` without records
+    ///
+    pub fn min_captured_length(&self) -> Option<u32> { self.min_captured_length }
+    pub fn max_captured_length(&self) -> Option<u32> { self.max_captured_length }
+    pub fn inconsistencies(&self) -> &std::vec::Vec<LengthInconsistency> { &self.inconsistencies }
+
+    ///
+    /// Time between the first and last record, zero if there are fewer than two records or the
+    /// last record precedes the first
+    ///
+    pub fn time_span(&self) -> std::time::Duration {
+        match (self.first_timestamp, self.last_timestamp) {
+            (Some(first), Some(last)) => last.duration_since(first).unwrap_or(std::time::Duration::from_secs(0)),
+            _ => std::time::Duration::from_secs(0)
+        }
+    }
+}
+
+///
+/// Walk the record headers of a libpcap capture (including global header), calling `visit` with the
+/// index and header of each complete record, and returning the global header with any length
+/// inconsistencies found. Walking stops at the first truncated record.
+///
+pub(crate) fn walk_headers<F>(input: &[u8], mut visit: F) -> Result<(GlobalHeader, std::vec::Vec<LengthInconsistency>), errors::Error>
+    where F: FnMut(usize, &RecordHeader)
+{
+    let (mut current, header) = GlobalHeader::parse(input)
+        .map_err(|e| errors::Error::from_nom(e, errors::Layer::GlobalHeader, input))?;
+    let mut inconsistencies = vec![];
+    let mut index = 0;
+
+    while current.len() >= header.record_header_length() {
+        let (rem, record_header) = RecordHeader::parse_for(current, &header)
+            .map_err(|e| errors::Error::from_nom(e, errors::Layer::Record, current))?;
+        let actual_length = record_header.actual_length();
+
+        if actual_length > record_header.original_length() {
+            inconsistencies.push(LengthInconsistency::ExceedsOriginalLength {
+                index: index,
+                actual_length: actual_length,
+                original_length: record_header.original_length()
+            });
+        }
+        //a snap length of 0 is unlimited
+        if header.snap_length() > 0 && actual_length > header.snap_length() {
+            inconsistencies.push(LengthInconsistency::ExceedsSnapLength {
+                index: index,
+                actual_length: actual_length,
+                snap_length: header.snap_length()
+            });
+        }
+        if (actual_length as usize) > rem.len() {
+            inconsistencies.push(LengthInconsistency::TruncatedRecord {
+                index: index,
+                available: rem.len(),
+                needed: actual_length as usize
+            });
+            current = &rem[rem.len()..];
+            break
+        }
+
+        visit(index, &record_header);
+        index += 1;
+
+        current = &rem[actual_length as usize..];
+    }
+
+    if !current.is_empty() {
+        inconsistencies.push(LengthInconsistency::TruncatedRecord {
+            index: index,
+            available: current.len(),
+            needed: header.record_header_length()
+        });
+    }
+
+    Ok( (header, inconsistencies) )
+}
+
+///
+/// Walk the record headers of a libpcap capture (including global header) without parsing or
+/// copying record payloads.
+///
+pub fn scan(input: &[u8]) -> Result<ScanReport, errors::Error> {
+    let mut report = ScanReport::default();
+
+    let (_, inconsistencies) = walk_headers(input, |_, record_header| {
+        if report.first_timestamp.is_none() {
+            report.first_timestamp = Some(*record_header.timestamp());
+        }
+        report.last_timestamp = Some(*record_header.timestamp());
+        report.records += 1;
+        report.captured_bytes += record_header.actual_length() as u64;
+        report.min_captured_length = Some(report.min_captured_length.map_or(record_header.actual_length(), |l| std::cmp::min(l, record_header.actual_length())));
+        report.max_captured_length = Some(report.max_captured_length.map_or(record_header.actual_length(), |l| std::cmp::max(l, record_header.actual_length())));
+        report.original_bytes += record_header.original_length() as u64;
+    })?;
+    report.inconsistencies = inconsistencies;
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    const RAW_DATA: &'static [u8] = &[
+        0x4du8, 0x3c, 0x2b, 0x1au8, //magic number
+        0x00u8, 0x04u8, //version major, 4
+        0x00u8, 0x02u8, //version minor, 2
+        0x00u8, 0x00u8, 0x00u8, 0x00u8, //zone, 0
+        0x00u8, 0x00u8, 0x00u8, 0x04u8, //sig figs, 4
+        0x00u8, 0x00u8, 0x00u8, 0x04u8, //snap length, 4
+        0x00u8, 0x00u8, 0x00u8, 0x01u8, //network, 1
+        //record
+        0x00u8, 0x00u8, 0x00u8, 0x01u8, //seconds, 1
+        0x00u8, 0x00u8, 0x00u8, 0x00u8, //microseconds, 0
+        0x00u8, 0x00u8, 0x00u8, 0x02u8, //actual length, 2
+        0x00u8, 0x00u8, 0x00u8, 0x04u8, //original length, 4
+        0x01u8, 0x02u8, //payload
+        //record
+        0x00u8, 0x00u8, 0x00u8, 0x03u8, //seconds, 3
+        0x00u8, 0x00u8, 0x00u8, 0x00u8, //microseconds, 0
+        0x00u8, 0x00u8, 0x00u8, 0x06u8, //actual length, 6
+        0x00u8, 0x00u8, 0x00u8, 0x05u8, //original length, 5
+        0x01u8, 0x02u8, 0x03u8, 0x04u8, 0x05u8, 0x06u8, //payload
+        //record
+        0x00u8, 0x00u8, 0x00u8, 0x04u8, //seconds, 4
+        0x00u8, 0x00u8, 0x00u8, 0x00u8, //microseconds, 0
+        0x00u8, 0x00u8, 0x00u8, 0x04u8, //actual length, 4
+        0x00u8, 0x00u8, 0x00u8, 0x04u8, //original length, 4
+        0x01u8, 0x02u8 //payload, truncated
+    ];
+
+    #[test]
+    fn scan_capture() {
+        let _ = env_logger::try_init();
+
+        let report = scan(RAW_DATA).expect("Failed to scan");
+
+        assert_eq!(report.records(), 2);
+        assert_eq!(report.captured_bytes(), 8);
+        assert_eq!(report.original_bytes(), 9);
+        assert_eq!(report.time_span(), std::time::Duration::from_secs(2));
+        assert_eq!(report.min_captured_length(), Some(2));
+        assert_eq!(report.max_captured_length(), Some(6));
+        assert_eq!(report.inconsistencies(), &vec![
+            LengthInconsistency::ExceedsOriginalLength { index: 1, actual_length: 6, original_length: 5 },
+            LengthInconsistency::ExceedsSnapLength { index: 1, actual_length: 6, snap_length: 4 },
+            LengthInconsistency::TruncatedRecord { index: 2, available: 2, needed: 4 }
+        ]);
+
+        //no snap length
+        let mut data = RAW_DATA.to_vec();
+        data[16..20].copy_from_slice(&[0x00u8, 0x00u8, 0x00u8, 0x00u8]);
+        let report = scan(&data).expect("Failed to scan");
+        assert_eq!(report.inconsistencies(), &vec![
+            LengthInconsistency::ExceedsOriginalLength { index: 1, actual_length: 6, original_length: 5 },
+            LengthInconsistency::TruncatedRecord { index: 2, available: 2, needed: 4 }
+        ]);
+
+        let report = super::super::CaptureParser::scan(&RAW_DATA[..24]).expect("Failed to scan");
+        assert_eq!(report.records(), 0);
+        assert!(report.min_captured_length().is_none());
+        assert!(report.first_timestamp().is_none());
+    }
+}
@@ -0,0 +1,268 @@
+use super::prelude::*;
+use super::bytes::Bytes;
+use super::global_header::{GlobalHeader, PcapVariant, TimestampPrecision};
+use super::layer2::ethernet::{Ethernet, EthernetTypeId, Layer3Id};
+use super::layer3::InternetProtocolId;
+use super::layer3::ipv4::IPv4;
+#[cfg(feature = "ipv6")]
+use super::layer3::ipv6::IPv6;
+use super::layer4::tcp::Tcp;
+use super::layer4::udp::Udp;
+use super::record::RecordHeader;
+
+use self::nom::*;
+
+use std;
+
+///
+/// Pcap record whose payload references a shared capture buffer rather than owning a copy
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SharedRecord {
+    timestamp: std::time::SystemTime,
+    actual_length: u32,
+    original_length: u32,
+    payload: Bytes
+}
+
+///
+/// Payloads of each layer of a shared record, each referencing the same buffer as the record
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SharedLayers {
+    pub layer3: Bytes,
+    pub layer4: Option<Bytes>,
+    pub application: Option<Bytes>
+}
+
+impl SharedRecord {
+    pub fn timestamp(&self) -> &std::time::SystemTime {
+        &self.timestamp
+    }
+    pub fn actual_length(&self) -> u32 {
+        self.actual_length
+    }
+    pub fn original_length(&self) -> u32 {
+        self.original_length
+    }
+    pub fn payload(&self) -> &Bytes { &self.payload }
+
+    ///
+    /// Copy into an owned record, e.g. for flow conversion
+    ///
+    pub fn to_record(&self) -> PcapRecord {
+        PcapRecord::new(self.timestamp, self.actual_length, self.original_length, self.payload.to_vec())
+    }
+
+    ///
+    /// Locate the layer 3, layer 4 and application payloads of an ethernet frame, as slices of the
+    /// shared buffer. Layer 4 and application payloads are absent for protocols that aren't parsed.
+    ///
+    pub fn layers(&self) -> Result<SharedLayers, errors::Error> {
+        let (_, l2) = Ethernet::parse(&self.payload)
+            .map_err(|e| errors::Error::from_nom(e, errors::Layer::Ethernet, &self.payload))?;
+        let l3_offset = Ethernet::header_length(l2.vlans());
+        let layer3 = self.payload.slice(l3_offset..l3_offset + l2.payload().len());
+
+        let l3 = match *l2.ether_type() {
+            EthernetTypeId::L3(Layer3Id::IPv4) => {
+                let (_, ipv4) = IPv4::parse(&layer3)
+                    .map_err(|e| errors::Error::from_nom(e, errors::Layer::IPv4, &layer3).offset_by(l3_offset))?;
+                Some( (ipv4.header_length(), ipv4.payload().len(), ipv4.protocol().clone()) )
+            }
+            #[cfg(feature = "ipv6")]
+            EthernetTypeId::L3(Layer3Id::IPv6) => {
+                let (_, ipv6) = IPv6::parse(&layer3)
+                    .map_err(|e| errors::Error::from_nom(e, errors::Layer::IPv6, &layer3).offset_by(l3_offset))?;
+                Some( (ipv6.header_length(), ipv6.payload().len(), ipv6.protocol().clone()) )
+            }
+            _ => None
+        };
+
+        let (layer4, application) = match l3 {
+            Some( (header_length, length, protocol) ) => {
+                let l4_offset = l3_offset + header_length;
+                let layer4 = self.payload.slice(l4_offset..l4_offset + length);
+                let application = match protocol {
+                    InternetProtocolId::Tcp => {
+                        let (_, tcp) = Tcp::parse(&layer4)
+                            .map_err(|e| errors::Error::from_nom(e, errors::Layer::Tcp, &layer4).offset_by(l4_offset))?;
+                        Some(layer4.slice(layer4.len() - tcp.payload().len()..))
+                    }
+                    InternetProtocolId::Udp => {
+                        let (rem, udp) = Udp::parse(&layer4)
+                            .map_err(|e| errors::Error::from_nom(e, errors::Layer::Udp, &layer4).offset_by(l4_offset))?;
+                        let end = layer4.len() - rem.len();
+                        Some(layer4.slice(end - udp.payload().len()..end))
+                    }
+                    _ => None
+                };
+                (Some(layer4), application)
+            }
+            None => (None, None)
+        };
+
+        Ok(SharedLayers {
+            layer3: layer3,
+            layer4: layer4,
+            application: application
+        })
+    }
+}
+
+///
+/// Parse records from a shared buffer, without libpcap global header. Record payloads are slices of
+/// `input`, so no payload bytes are copied. Parsing stops at the first incomplete record, which is
+/// returned as the remainder.
+///
+pub fn parse_records(input: Bytes, endianness: Endianness) -> Result<(Bytes, std::vec::Vec<SharedRecord>), errors::Error> {
+    parse_records_with_precision(input, endianness, TimestampPrecision::Microsecond)
+}
+
+///
+/// Parse records from a shared buffer whose timestamps are in `precision`
+///
+pub fn parse_records_with_precision(
+    input: Bytes,
+    endianness: Endianness,
+    precision: TimestampPrecision
+) -> Result<(Bytes, std::vec::Vec<SharedRecord>), errors::Error> {
+    parse_records_with_variant(input, endianness, precision, PcapVariant::Standard)
+}
+
+///
+/// Parse records from a shared buffer whose record headers are laid out as `variant`
+///
+pub fn parse_records_with_variant(
+    input: Bytes,
+    endianness: Endianness,
+    precision: TimestampPrecision,
+    variant: PcapVariant
+) -> Result<(Bytes, std::vec::Vec<SharedRecord>), errors::Error> {
+    let header_length = variant.record_header_length();
+    let mut records = vec![];
+    let mut offset = 0;
+
+    while input.len() - offset >= header_length {
+        let (_, header) = RecordHeader::parse_with_variant(&input[offset..], endianness, precision, variant)
+            .map_err(|e| errors::Error::from_nom(e, errors::Layer::Record, &input[offset..]).offset_by(offset))?;
+        let start = offset + header_length;
+        let end = start + header.actual_length() as usize;
+
+        if end > input.len() {
+            debug!("Needed {} bytes for parsing, only had {}", end - offset, input.len() - offset);
+            break
+        }
+
+        records.push(SharedRecord {
+            timestamp: *header.timestamp(),
+            actual_length: header.actual_length(),
+            original_length: header.original_length(),
+            payload: input.slice(start..end)
+        });
+        offset = end;
+    }
+
+    Ok( (input.slice(offset..), records) )
+}
+
+///
+/// Parse a shared buffer that starts with a libpcap global header
+///
+pub fn parse_file(input: Bytes) -> Result<(GlobalHeader, std::vec::Vec<SharedRecord>), errors::Error> {
+    let (rem, header) = GlobalHeader::parse(&input)
+        .map_err(|e| errors::Error::from_nom(e, errors::Layer::GlobalHeader, &input))?;
+    let records_start = input.len() - rem.len();
+    let (_, records) = parse_records_with_variant(input.slice(records_start..), header.endianness(), header.precision(), header.variant())?;
+    Ok( (header, records) )
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    const RAW_DATA: &'static [u8] = &[
+        0x4du8, 0x3c, 0x2b, 0x1au8, //magic number
+        0x00u8, 0x04u8, //version major, 4
+        0x00u8, 0x02u8, //version minor, 2
+        0x00u8, 0x00u8, 0x00u8, 0x00u8, //zone, 0
+        0x00u8, 0x00u8, 0x00u8, 0x04u8, //sig figs, 4
+        0x00u8, 0x00u8, 0x06u8, 0x13u8, //snap length, 1555
+        0x00u8, 0x00u8, 0x00u8, 0x02u8, //network, 2
+        //record
+        0x5Bu8, 0x11u8, 0x6Du8, 0xE3u8, //seconds, 1527868899
+        0x00u8, 0x02u8, 0x51u8, 0xF5u8, //microseconds, 152053
+        0x00u8, 0x00u8, 0x00u8, 0x3Au8, //actual length, 58: 14 (ethernet) + 20 (ipv4 header) + 20 (tcp header) + 4 (tcp payload)
+        0x00u8, 0x00u8, 0x00u8, 0x3Au8, //original length, 58
+        //ethernet
+        0x01u8, 0x02u8, 0x03u8, 0x04u8, 0x05u8, 0x06u8, //dst mac 01:02:03:04:05:06
+        0xFFu8, 0xFEu8, 0xFDu8, 0xFCu8, 0xFBu8, 0xFAu8, //src mac FF:FE:FD:FC:FB:FA
+        0x08u8, 0x00u8, //ipv4
+        //ipv4
+        0x45u8, //version and header length
+        0x00u8, //tos
+        0x00u8, 0x2Cu8, //length, 20 bytes for header, 24 bytes for tcp
+        0x00u8, 0x00u8, //id
+        0x00u8, 0x00u8, //flags
+        0x64u8, //ttl
+        0x06u8, //protocol, tcp
+        0x00u8, 0x00u8, //checksum
+        0x01u8, 0x02u8, 0x03u8, 0x04u8, //src ip 1.2.3.4
+        0x0Au8, 0x0Bu8, 0x0Cu8, 0x0Du8, //dst ip 10.11.12.13
+        //tcp
+        0xC6u8, 0xB7u8, //src port, 50871
+        0x00u8, 0x50u8, //dst port, 80
+        0x00u8, 0x00u8, 0x00u8, 0x01u8, //sequence number, 1
+        0x00u8, 0x00u8, 0x00u8, 0x02u8, //acknowledgement number, 2
+        0x50u8, 0x00u8, //header and flags, 0
+        0x00u8, 0x00u8, //window
+        0x00u8, 0x00u8, //check
+        0x00u8, 0x00u8, //urgent
+        //no options
+        //payload
+        0xfcu8, 0xfdu8, 0xfeu8, 0xffu8
+    ];
+
+    #[test]
+    fn parse_shared_file() {
+        let _ = env_logger::try_init();
+
+        let buffer = Bytes::from_static(RAW_DATA);
+        let (header, records) = parse_file(buffer.clone()).expect("Failed to parse");
+
+        assert_eq!(header.endianness(), Endianness::Big);
+        assert_eq!(records.len(), 1);
+
+        let record = &records[0];
+        assert_eq!(record.actual_length(), 58);
+        assert_eq!(record.payload().as_ptr(), buffer[40..].as_ptr());
+    }
+
+    #[test]
+    fn shared_layers() {
+        let _ = env_logger::try_init();
+
+        let (_, records) = parse_file(Bytes::from_static(RAW_DATA)).expect("Failed to parse");
+
+        let layers = records[0].layers().expect("Failed to locate layers");
+
+        assert_eq!(layers.layer3.len(), 44);
+        assert_eq!(layers.layer4.as_ref().map(|b| b.len()), Some(24));
+        assert_eq!(layers.application.as_ref().map(|b| b.as_ref()), Some(&[0xfcu8, 0xfdu8, 0xfeu8, 0xffu8][..]));
+    }
+
+    #[cfg(feature = "flows")]
+    #[test]
+    fn shared_to_record() {
+        let _ = env_logger::try_init();
+
+        let (_, records) = parse_file(Bytes::from_static(RAW_DATA)).expect("Failed to parse");
+
+        let flow = Flow::try_from(records[0].to_record()).expect("Failed to convert");
+
+        assert_eq!(flow.source.port, 50871);
+        assert_eq!(flow.destination.port, 80);
+    }
+}
@@ -0,0 +1,218 @@
+use super::prelude::*;
+use super::global_header::GlobalHeader;
+use super::link_type::LinkType;
+
+use self::nom::*;
+
+use std;
+
+///
+/// Identification pattern starting a snoop capture
+///
+pub const SNOOP_MAGIC: [u8; 8] = [0x73u8, 0x6Eu8, 0x6Fu8, 0x6Fu8, 0x70u8, 0x00u8, 0x00u8, 0x00u8];
+pub const SNOOP_HEADER_LENGTH: usize = 16;
+pub const SNOOP_RECORD_HEADER_LENGTH: usize = 24;
+
+const VERSION: u32 = 2;
+///
+/// Snoop headers don't record a snap length, so captures are given tcpdump's maximum
+///
+const SNAP_LENGTH: u32 = 262144;
+
+const DATALINK_IEEE8023: u32 = 0;
+const DATALINK_IEEE8025: u32 = 2;
+const DATALINK_ETHERNET: u32 = 4;
+const DATALINK_FDDI: u32 = 8;
+
+fn snoop_error<'a, T>(input: &'a [u8], code: errors::ParseCode) -> IResult<&'a [u8], T> {
+    Err(Err::Error(error_position!(input, ErrorKind::Custom(code as u32))))
+}
+
+///
+/// Whether `input` starts with a snoop file header rather than a libpcap global header
+///
+pub fn is_snoop(input: &[u8]) -> bool {
+    input.starts_with(&SNOOP_MAGIC)
+}
+
+///
+/// File header of a snoop capture (https://tools.ietf.org/html/rfc1761), as written by Solaris
+/// `snoop`. Every field of a snoop capture is big endian.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SnoopHeader {
+    version: u32,
+    datalink: u32
+}
+
+impl SnoopHeader {
+    pub fn version(&self) -> u32 { self.version }
+
+    ///
+    /// Datalink type as numbered by RFC 1761, which differs from the libpcap link types
+    ///
+    pub fn datalink(&self) -> u32 { self.datalink }
+
+    ///
+    /// Libpcap link type of the datalink type. Datalink types without a libpcap equivalent are
+    /// kept as `Unknown`.
+    ///
+    pub fn link_type(&self) -> LinkType {
+        match self.datalink {
+            DATALINK_IEEE8023 | DATALINK_ETHERNET => LinkType::Ethernet,
+            DATALINK_IEEE8025 => LinkType::Ieee8025,
+            DATALINK_FDDI => LinkType::Fddi,
+            other => LinkType::Unknown(other)
+        }
+    }
+
+    ///
+    /// Libpcap global header equivalent to this header, big endian with microsecond timestamps
+    ///
+    pub fn to_global_header(&self) -> GlobalHeader {
+        GlobalHeader::new(self.link_type(), SNAP_LENGTH).with_endianness(Endianness::Big)
+    }
+
+    pub fn parse(input: &[u8]) -> IResult<&[u8], SnoopHeader> {
+        let (rem, (_, version, datalink)) = tuple!(input, tag!(SNOOP_MAGIC), be_u32, be_u32)?;
+        if version != VERSION {
+            return snoop_error(input, errors::ParseCode::SnoopVersion)
+        }
+        debug!("Snoop header version {}, with datalink {}", version, datalink);
+
+        Ok( (rem, SnoopHeader { version: version, datalink: datalink }) )
+    }
+}
+
+///
+/// Parse a snoop packet record, skipping the padding after its data
+///
+pub fn parse_record(input: &[u8]) -> IResult<&[u8], PcapRecord> {
+    let (rem, (original_length, included_length, record_length, _drops, seconds, microseconds)) =
+        tuple!(input, be_u32, be_u32, be_u32, be_u32, be_u32, be_u32)?;
+    if (record_length as u64) < (SNOOP_RECORD_HEADER_LENGTH as u64) + included_length as u64 {
+        return snoop_error(input, errors::ParseCode::SnoopRecordLength)
+    }
+    let padding = record_length as usize - SNOOP_RECORD_HEADER_LENGTH - included_length as usize;
+    let (rem, (payload, _)) = tuple!(rem, take!(included_length), take!(padding))?;
+
+    Ok( (rem, PcapRecord::new(
+        PcapRecord::convert_packet_time(seconds, microseconds),
+        included_length,
+        original_length,
+        payload.to_vec()
+    )) )
+}
+
+///
+/// Parse a snoop capture in memory into the libpcap global header and records equivalent to it.
+/// Bytes of a trailing incomplete record are left unparsed.
+///
+pub fn parse_file(input: &[u8]) -> IResult<&[u8], (GlobalHeader, std::vec::Vec<PcapRecord>)> {
+    let (mut current, header) = SnoopHeader::parse(input)?;
+    let global_header = header.to_global_header();
+    let mut records = vec![];
+
+    loop {
+        layer_span!("record", index = records.len(), offset = input.len() - current.len());
+
+        match parse_record(current) {
+            Ok( (rem, record) ) => {
+                current = rem;
+                records.push(record.with_header(&global_header));
+            }
+            Err(Err::Incomplete(_)) => {
+                debug!("Capture ended within a record, {} bytes left", current.len());
+                break
+            }
+            Err(e) => return Err(e)
+        }
+    }
+
+    Ok( (current, (global_header, records)) )
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+    use super::super::CaptureParser;
+
+    const RAW_DATA: &'static [u8] = &[
+        0x73u8, 0x6Eu8, 0x6Fu8, 0x6Fu8, 0x70u8, 0x00u8, 0x00u8, 0x00u8, //snoop
+        0x00u8, 0x00u8, 0x00u8, 0x02u8, //version, 2
+        0x00u8, 0x00u8, 0x00u8, 0x04u8, //datalink, ethernet
+        //record
+        0x00u8, 0x00u8, 0x00u8, 0x06u8, //original length, 6
+        0x00u8, 0x00u8, 0x00u8, 0x05u8, //included length, 5
+        0x00u8, 0x00u8, 0x00u8, 0x20u8, //record length, 32
+        0x00u8, 0x00u8, 0x00u8, 0x00u8, //cumulative drops
+        0x5Bu8, 0x11u8, 0x6Du8, 0xE3u8, //seconds, 1527868899
+        0x00u8, 0x02u8, 0x51u8, 0xF5u8, //microseconds, 152053
+        0x01u8, 0x02u8, 0x03u8, 0x04u8, 0x05u8, //data
+        0x00u8, 0x00u8, 0x00u8, //padding
+        //record
+        0x00u8, 0x00u8, 0x00u8, 0x04u8, //original length, 4
+        0x00u8, 0x00u8, 0x00u8, 0x04u8, //included length, 4
+        0x00u8, 0x00u8, 0x00u8, 0x1Cu8, //record length, 28
+        0x00u8, 0x00u8, 0x00u8, 0x00u8, //cumulative drops
+        0x5Bu8, 0x11u8, 0x6Du8, 0xE4u8, //seconds, 1527868900
+        0x00u8, 0x00u8, 0x00u8, 0x00u8, //microseconds, 0
+        0x06u8, 0x07u8, 0x08u8, 0x09u8 //data
+    ];
+
+    #[test]
+    fn parse_snoop() {
+        let _ = env_logger::try_init();
+
+        assert!(is_snoop(RAW_DATA));
+
+        let (rem, (header, records)) = parse_file(RAW_DATA).expect("Could not parse");
+        assert!(rem.is_empty());
+        assert_eq!(header.link_type(), LinkType::Ethernet);
+        assert_eq!(header.endianness(), Endianness::Big);
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(*records[0].timestamp(), PcapRecord::convert_packet_time(1527868899, 152053));
+        assert_eq!(records[0].actual_length(), 5);
+        assert_eq!(records[0].original_length(), 6);
+        assert_eq!(records[0].payload(), &vec![0x01u8, 0x02u8, 0x03u8, 0x04u8, 0x05u8]);
+        assert_eq!(records[1].payload(), &vec![0x06u8, 0x07u8, 0x08u8, 0x09u8]);
+
+        //records take the link type of the datalink type
+        let mut fddi = RAW_DATA.to_vec();
+        fddi[15] = 0x08u8;
+        let (_, (_, records)) = parse_file(&fddi).expect("Could not parse");
+        assert!(records.iter().all(|r| r.link_type() == LinkType::Fddi && r.interface_id() == 0));
+    }
+
+    #[test]
+    fn detect_snoop() {
+        let _ = env_logger::try_init();
+
+        let (_, (header, records)) = CaptureParser::parse_file(RAW_DATA).expect("Could not parse");
+        assert_eq!(header.link_type(), LinkType::Ethernet);
+        assert_eq!(records.len(), 2);
+
+        let length = RAW_DATA.len();
+        let (header, records) = CaptureParser::parse_reader(&RAW_DATA[..length - 2]).expect("Could not parse");
+        assert_eq!(header.snap_length(), SNAP_LENGTH);
+        assert_eq!(records.len(), 1);
+    }
+
+    #[test]
+    fn parse_snoop_invalid() {
+        let _ = env_logger::try_init();
+
+        let mut bytes = RAW_DATA.to_vec();
+        bytes[11] = 0x01u8;
+        let e = CaptureParser::parse_reader(&bytes[..]).expect_err("Parsed version 1");
+        assert!(format!("{}", e).contains("version 2"));
+
+        let mut bytes = RAW_DATA.to_vec();
+        bytes[27] = 0x10u8;
+        let e = CaptureParser::parse_reader(&bytes[..]).expect_err("Parsed short record length");
+        assert!(format!("{}", e).contains("record length"));
+    }
+}
@@ -0,0 +1,597 @@
+use super::prelude::*;
+#[cfg(feature = "flows")]
+use super::flow::FlowKey;
+use super::global_header::{GlobalHeader, GLOBAL_HEADER_LENGTH};
+#[cfg(feature = "flows")]
+use super::parsed::{ParseConfig, ParsedRecord};
+use super::record::RECORD_HEADER_LENGTH;
+use super::writer::CaptureWriter;
+
+use std;
+use std::collections::HashMap;
+#[cfg(feature = "flows")]
+use std::collections::HashSet;
+
+///
+/// How records are partitioned into chunks
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SplitBy {
+    ///
+    /// Consecutive windows of wall-clock time, the first starting at the first record. Windows
+    /// without records produce no chunk.
+    ///
+    Interval(std::time::Duration),
+    ///
+    /// Chunks of at most this many bytes as written, global header included. A record larger
+    /// than the budget gets a chunk of its own.
+    ///
+    Bytes(u64),
+    ///
+    /// A chunk per flow, both directions together, with records that aren't part of a flow
+    /// sharing a chunk
+    ///
+    #[cfg(feature = "flows")]
+    Flow
+}
+
+impl SplitBy {
+    ///
+    /// Whether chunks follow one another, so only the latest chunk receives records
+    ///
+    pub fn is_sequential(&self) -> bool {
+        match *self {
+            SplitBy::Interval(_) | SplitBy::Bytes(_) => true,
+            #[cfg(feature = "flows")]
+            SplitBy::Flow => false
+        }
+    }
+}
+
+///
+/// Output capture holding one partition of the records split
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Chunk {
+    header: GlobalHeader,
+    records: std::vec::Vec<PcapRecord>
+}
+
+impl Chunk {
+    pub fn header(&self) -> &GlobalHeader { &self.header }
+    pub fn records(&self) -> &std::vec::Vec<PcapRecord> { &self.records }
+    pub fn into_records(self) -> std::vec::Vec<PcapRecord> { self.records }
+}
+
+///
+/// Assigns records, in capture order, to the chunk each belongs in
+///
+pub struct Splitter {
+    header: GlobalHeader,
+    split_by: SplitBy,
+    chunks: usize,
+    window_start: Option<std::time::SystemTime>,
+    bytes: u64,
+    #[cfg(feature = "flows")]
+    flows: HashMap<FlowKey, usize>,
+    #[cfg(feature = "flows")]
+    unkeyed: Option<usize>
+}
+
+impl Splitter {
+    ///
+    /// Splitter of a capture with `header`, which each chunk is given
+    ///
+    pub fn new(header: GlobalHeader, split_by: SplitBy) -> Splitter {
+        Splitter {
+            header: header,
+            split_by: split_by,
+            chunks: 0,
+            window_start: None,
+            bytes: 0,
+            #[cfg(feature = "flows")]
+            flows: HashMap::new(),
+            #[cfg(feature = "flows")]
+            unkeyed: None
+        }
+    }
+
+    pub fn header(&self) -> &GlobalHeader { &self.header }
+    pub fn split_by(&self) -> SplitBy { self.split_by }
+
+    ///
+    /// Chunks started so far
+    ///
+    pub fn chunks(&self) -> usize { self.chunks }
+
+    fn start_chunk(&mut self) -> usize {
+        self.chunks += 1;
+        trace!("Starting chunk {}", self.chunks - 1);
+        self.chunks - 1
+    }
+
+    ///
+    /// Index of the chunk `record` belongs in, starting a chunk if needed
+    ///
+    pub fn assign(&mut self, record: &PcapRecord) -> usize {
+        match self.split_by {
+            SplitBy::Interval(interval) => self.assign_interval(record, interval),
+            SplitBy::Bytes(budget) => self.assign_bytes(record, budget),
+            #[cfg(feature = "flows")]
+            SplitBy::Flow => self.assign_flow(record)
+        }
+    }
+
+    fn assign_interval(&mut self, record: &PcapRecord, interval: std::time::Duration) -> usize {
+        let timestamp = *record.timestamp();
+        let window_start = match self.window_start {
+            Some(start) => start,
+            None => {
+                self.window_start = Some(timestamp);
+                return self.start_chunk()
+            }
+        };
+
+        //records earlier than the current window stay in it
+        let elapsed = timestamp.duration_since(window_start).unwrap_or(std::time::Duration::from_secs(0));
+        let interval_nanos = std::cmp::max(interval.as_nanos(), 1);
+        let windows = elapsed.as_nanos() / interval_nanos;
+        if windows == 0 {
+            return self.chunks - 1
+        }
+        let skipped = (windows * interval_nanos) as u64;
+        self.window_start = Some(window_start + std::time::Duration::from_nanos(skipped));
+        self.start_chunk()
+    }
+
+    fn assign_bytes(&mut self, record: &PcapRecord, budget: u64) -> usize {
+        //as written, truncated to the snap length
+        let snap_length = self.header.snap_length();
+        let captured = if snap_length > 0 { std::cmp::min(record.actual_length(), snap_length) } else { record.actual_length() };
+        let length = (RECORD_HEADER_LENGTH as u64) + captured as u64;
+        if self.chunks == 0 || self.bytes + length > budget {
+            self.bytes = (GLOBAL_HEADER_LENGTH as u64) + length;
+            return self.start_chunk()
+        }
+        self.bytes += length;
+        self.chunks - 1
+    }
+
+    #[cfg(feature = "flows")]
+    fn assign_flow(&mut self, record: &PcapRecord) -> usize {
+        let config = ParseConfig::for_header(&self.header);
+        let key = match ParsedRecord::parse_with(record.clone(), &config).and_then(|p| p.flow_key()) {
+            Ok(key) => key,
+            Err(e) => {
+                debug!("Record is not part of a flow: {:?}", e);
+                return match self.unkeyed {
+                    Some(chunk) => chunk,
+                    None => {
+                        let chunk = self.start_chunk();
+                        self.unkeyed = Some(chunk);
+                        chunk
+                    }
+                }
+            }
+        };
+
+        if let Some(chunk) = self.flows.get(&key).or_else(|| self.flows.get(&key.reversed())) {
+            return *chunk
+        }
+        let chunk = self.start_chunk();
+        self.flows.insert(key, chunk);
+        chunk
+    }
+}
+
+///
+/// Partition `records` of a capture with `header` into chunks, in the order each chunk was started
+///
+pub fn split<I>(header: GlobalHeader, records: I, split_by: SplitBy) -> std::vec::Vec<Chunk> where I: IntoIterator<Item=PcapRecord> {
+    let mut splitter = Splitter::new(header, split_by);
+    let mut chunks: std::vec::Vec<Chunk> = vec![];
+
+    for record in records {
+        let chunk = splitter.assign(&record);
+        if chunk == chunks.len() {
+            chunks.push(Chunk {
+                header: splitter.header().clone(),
+                records: vec![]
+            });
+        }
+        chunks[chunk].records.push(record);
+    }
+
+    chunks
+}
+
+///
+/// Write `records` of a capture with `header` out as chunks, each to the writer `create` returns
+/// for its index, e.g. a file named after it. When splitting by interval or size, a chunk's writer
+/// is flushed and dropped once the next chunk starts; when splitting by flow, writers are kept open
+/// until the end. Returns the number of chunks written.
+///
+pub fn split_to<I, W, F>(header: GlobalHeader, records: I, split_by: SplitBy, mut create: F) -> errors::Result<usize>
+    where I: IntoIterator<Item=PcapRecord>, W: std::io::Write, F: FnMut(usize) -> errors::Result<W>
+{
+    let mut splitter = Splitter::new(header, split_by);
+    let mut writers: std::vec::Vec<Option<CaptureWriter<W>>> = vec![];
+
+    for record in records {
+        let chunk = splitter.assign(&record);
+        if chunk == writers.len() {
+            if split_by.is_sequential() {
+                if let Some(previous) = writers.last_mut().and_then(|w| w.take()) {
+                    previous.into_inner()?;
+                }
+            }
+            writers.push(Some(CaptureWriter::new(create(chunk)?, splitter.header().clone())?));
+        }
+        match writers[chunk] {
+            Some(ref mut writer) => writer.write_record(&record)?,
+            None => return Err(format!("Chunk {} was already closed", chunk).into())
+        }
+    }
+
+    for writer in writers.into_iter().flatten() {
+        writer.into_inner()?;
+    }
+    Ok(splitter.chunks())
+}
+
+///
+/// Bounds on the output files of `split_keyed_to`
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct OutputLimits {
+    ///
+    /// Most writers open at once. Opening another closes the least recently written, which is
+    /// reopened for appending when its key next has a record. `None` keeps every writer open.
+    ///
+    pub max_open: Option<usize>,
+    ///
+    /// Bytes a file may grow to, global header included, before the records of its key rotate to
+    /// a new file. A record larger than the limit gets a file of its own. `None` never rotates.
+    ///
+    pub max_bytes: Option<u64>
+}
+
+///
+/// File of the records of a key, its `part` counting the rotations of the key before it
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct OutputFile<K> {
+    pub key: K,
+    pub part: usize
+}
+
+///
+/// How `split_keyed_to` opens an output file
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OpenMode {
+    ///
+    /// A new file, which the global header is written to
+    ///
+    Create,
+    ///
+    /// A file closed by the open writer limit, which records are appended to
+    ///
+    Append
+}
+
+struct Output<K, W> {
+    file: OutputFile<K>,
+    writer: Option<CaptureWriter<W>>,
+    created: bool,
+    bytes: u64,
+    last_written: u64
+}
+
+///
+/// Write `records` of a capture with `header` to a file per key `key` gives each record, e.g. a
+/// session or host, each from the writer `open` returns for it. Writers are kept within
+/// `limits`, closing the least recently written and rotating files as they fill. Returns every
+/// file written, in the order each was created.
+///
+pub fn split_keyed_to<I, K, KF, W, F>(
+    header: GlobalHeader,
+    records: I,
+    mut key: KF,
+    limits: OutputLimits,
+    mut open: F
+) -> errors::Result<std::vec::Vec<OutputFile<K>>>
+    where I: IntoIterator<Item=PcapRecord>, K: Clone + Eq + std::hash::Hash, KF: FnMut(&PcapRecord) -> K,
+          W: std::io::Write, F: FnMut(&OutputFile<K>, OpenMode) -> errors::Result<W>
+{
+    let max_open = std::cmp::max(limits.max_open.unwrap_or(usize::MAX), 1);
+    let snap_length = header.snap_length();
+    let mut outputs: HashMap<K, Output<K, W>> = HashMap::new();
+    let mut files = vec![];
+    let mut open_writers = 0;
+
+    for (written, record) in records.into_iter().enumerate() {
+        let record_key = key(&record);
+        //as written, truncated to the snap length
+        let captured = if snap_length > 0 { std::cmp::min(record.actual_length(), snap_length) } else { record.actual_length() };
+        let length = (RECORD_HEADER_LENGTH as u64) + captured as u64;
+
+        if !outputs.contains_key(&record_key) {
+            let file = OutputFile { key: record_key.clone(), part: 0 };
+            files.push(file.clone());
+            outputs.insert(record_key.clone(), Output { file: file, writer: None, created: false, bytes: GLOBAL_HEADER_LENGTH as u64, last_written: 0 });
+        }
+
+        let rotate = match outputs.get(&record_key) {
+            Some(output) => {
+                let full = limits.max_bytes.map(|max| output.bytes + length > max).unwrap_or(false);
+                output.created && full && output.bytes > GLOBAL_HEADER_LENGTH as u64
+            }
+            None => false
+        };
+        if rotate {
+            if let Some(output) = outputs.get_mut(&record_key) {
+                if let Some(writer) = output.writer.take() {
+                    writer.into_inner()?;
+                    open_writers -= 1;
+                }
+                output.file.part += 1;
+                output.created = false;
+                output.bytes = GLOBAL_HEADER_LENGTH as u64;
+                trace!("Rotating to part {}", output.file.part);
+                files.push(output.file.clone());
+            }
+        }
+
+        let needs_writer = outputs.get(&record_key).map(|o| o.writer.is_none()).unwrap_or(false);
+        if needs_writer && open_writers >= max_open {
+            let least_recent = outputs.values_mut()
+                .filter(|o| o.writer.is_some())
+                .min_by_key(|o| o.last_written);
+            if let Some(writer) = least_recent.and_then(|o| o.writer.take()) {
+                writer.into_inner()?;
+                open_writers -= 1;
+            }
+        }
+
+        let output = match outputs.get_mut(&record_key) {
+            Some(output) => output,
+            None => return Err("Output for record was not started".into())
+        };
+        if output.writer.is_none() {
+            output.writer = Some(if output.created {
+                CaptureWriter::appending(open(&output.file, OpenMode::Append)?, header.clone())
+            } else {
+                CaptureWriter::new(open(&output.file, OpenMode::Create)?, header.clone())?
+            });
+            output.created = true;
+            open_writers += 1;
+        }
+        if let Some(ref mut writer) = output.writer {
+            writer.write_record(&record)?;
+        }
+        output.bytes += length;
+        output.last_written = written as u64;
+    }
+
+    for output in outputs.into_iter().map(|(_, o)| o) {
+        if let Some(writer) = output.writer {
+            writer.into_inner()?;
+        }
+    }
+    Ok(files)
+}
+
+///
+/// Write `records` of a capture with `header` to a file per flow, both directions together, with
+/// records that aren't part of a flow sharing the file keyed `None`, as `split_keyed_to` does
+///
+#[cfg(feature = "flows")]
+pub fn split_flows_to<I, W, F>(header: GlobalHeader, records: I, limits: OutputLimits, open: F) -> errors::Result<std::vec::Vec<OutputFile<Option<FlowKey>>>>
+    where I: IntoIterator<Item=PcapRecord>, W: std::io::Write, F: FnMut(&OutputFile<Option<FlowKey>>, OpenMode) -> errors::Result<W>
+{
+    let mut flows = HashSet::new();
+    let config = ParseConfig::for_header(&header);
+    let flow = move |record: &PcapRecord| {
+        let key = match ParsedRecord::parse_with(record.clone(), &config).and_then(|p| p.flow_key()) {
+            Ok(key) => key,
+            Err(e) => {
+                debug!("Record is not part of a flow: {:?}", e);
+                return None
+            }
+        };
+        let reversed = key.reversed();
+        if !flows.contains(&key) && flows.contains(&reversed) {
+            return Some(reversed)
+        }
+        flows.insert(key.clone());
+        Some(key)
+    };
+    split_keyed_to(header, records, flow, limits, open)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+    use super::super::CaptureParser;
+    use super::super::link_type::LinkType;
+
+    fn record_at(seconds: u32, microseconds: u32, length: usize) -> PcapRecord {
+        PcapRecord::new(PcapRecord::convert_packet_time(seconds, microseconds), length as u32, length as u32, vec![0u8; length])
+    }
+
+    fn lengths(chunks: &[Chunk]) -> std::vec::Vec<usize> {
+        chunks.iter().map(|c| c.records().len()).collect()
+    }
+
+    struct Shared(std::rc::Rc<std::cell::RefCell<std::vec::Vec<u8>>>);
+
+    impl std::io::Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> { self.0.borrow_mut().write(buf) }
+        fn flush(&mut self) -> std::io::Result<()> { Ok(()) }
+    }
+
+    #[test]
+    fn split_interval() {
+        let _ = env_logger::try_init();
+
+        let records = vec![
+            record_at(10, 0, 1),
+            record_at(10, 999999, 1),
+            record_at(11, 0, 1),
+            record_at(14, 500000, 1),
+            record_at(14, 0, 1),
+            record_at(15, 0, 1)
+        ];
+        let header = GlobalHeader::new(LinkType::Ethernet, 65535);
+
+        let chunks = split(header.clone(), records, SplitBy::Interval(std::time::Duration::from_secs(1)));
+        assert_eq!(lengths(&chunks), vec![2, 1, 2, 1]);
+        assert!(chunks.iter().all(|c| *c.header() == header));
+    }
+
+    #[test]
+    fn split_bytes() {
+        let _ = env_logger::try_init();
+
+        let records = vec![
+            record_at(1, 0, 20),
+            record_at(2, 0, 20),
+            record_at(3, 0, 100),
+            record_at(4, 0, 4)
+        ];
+        //global header and two 36 byte records
+        let chunks = split(GlobalHeader::new(LinkType::Ethernet, 65535), records, SplitBy::Bytes(96));
+        assert_eq!(lengths(&chunks), vec![2, 1, 1]);
+
+        let mut writer = CaptureWriter::new(vec![], chunks[0].header().clone()).expect("Could not write header");
+        writer.write_records(chunks[0].records()).expect("Could not write records");
+        assert_eq!(writer.into_inner().expect("Could not flush").len(), 96);
+    }
+
+    #[test]
+    fn split_to_writers() {
+        let _ = env_logger::try_init();
+
+        let records = vec![record_at(1, 0, 4), record_at(3, 0, 4), record_at(4, 0, 4)];
+        let mut outputs: std::vec::Vec<std::rc::Rc<std::cell::RefCell<std::vec::Vec<u8>>>> = vec![];
+
+        let count = split_to(GlobalHeader::new(LinkType::Ethernet, 65535), records, SplitBy::Interval(std::time::Duration::from_secs(2)), |_| {
+            let output = std::rc::Rc::new(std::cell::RefCell::new(vec![]));
+            outputs.push(output.clone());
+            Ok(Shared(output))
+        }).expect("Could not split");
+        assert_eq!(count, 2);
+
+        let parsed = outputs.iter()
+            .map(|o| CaptureParser::parse_file(&*o.borrow()).expect("Could not parse").1 .1.len())
+            .collect::<std::vec::Vec<_>>();
+        assert_eq!(parsed, vec![1, 2]);
+    }
+
+    #[cfg(feature = "flows")]
+    #[test]
+    fn split_flow() {
+        use super::super::analysis::reassembly::tests::tcp_record;
+
+        let _ = env_logger::try_init();
+
+        //same addresses, another client port
+        let other = tcp_record(4, true, 1, 0x02, &[]);
+        let mut payload = other.payload().clone();
+        payload[35] = 0x51u8;
+        let other = PcapRecord::new(*other.timestamp(), other.actual_length(), other.original_length(), payload);
+
+        let records = vec![
+            tcp_record(1, true, 1, 0x02, &[]),
+            tcp_record(2, false, 1, 0x12, &[]),
+            record_at(3, 0, 4),
+            other,
+            tcp_record(5, true, 1, 0x10, &[]),
+            record_at(6, 0, 4)
+        ];
+
+        let chunks = split(GlobalHeader::new(LinkType::Ethernet, 65535), records, SplitBy::Flow);
+        assert_eq!(lengths(&chunks), vec![3, 2, 1]);
+        assert!(!SplitBy::Flow.is_sequential());
+    }
+
+    #[test]
+    fn split_keyed_limits() {
+        let _ = env_logger::try_init();
+
+        //keyed on payload length
+        let records = vec![
+            record_at(1, 0, 1),
+            record_at(2, 0, 2),
+            record_at(3, 0, 1),
+            record_at(4, 0, 3),
+            record_at(5, 0, 2),
+            record_at(6, 0, 1)
+        ];
+        let mut outputs: HashMap<OutputFile<usize>, std::rc::Rc<std::cell::RefCell<std::vec::Vec<u8>>>> = HashMap::new();
+        let mut opened = vec![];
+
+        //two writers open at once, files of the global header and two records of up to 18 bytes
+        let limits = OutputLimits { max_open: Some(2), max_bytes: Some(60) };
+        let files = split_keyed_to(GlobalHeader::new(LinkType::Ethernet, 65535), records, |r| r.payload().len(), limits, |file, mode| {
+            opened.push( (file.clone(), mode) );
+            let output = outputs.entry(file.clone()).or_insert_with(|| std::rc::Rc::new(std::cell::RefCell::new(vec![])));
+            Ok(Shared(output.clone()))
+        }).expect("Could not split");
+
+        assert_eq!(files, vec![
+            OutputFile { key: 1, part: 0 },
+            OutputFile { key: 2, part: 0 },
+            OutputFile { key: 3, part: 0 },
+            OutputFile { key: 1, part: 1 }
+        ]);
+        assert_eq!(opened, vec![
+            (OutputFile { key: 1, part: 0 }, OpenMode::Create),
+            (OutputFile { key: 2, part: 0 }, OpenMode::Create),
+            (OutputFile { key: 3, part: 0 }, OpenMode::Create),
+            (OutputFile { key: 2, part: 0 }, OpenMode::Append),
+            (OutputFile { key: 1, part: 1 }, OpenMode::Create)
+        ]);
+
+        let records = |file: OutputFile<usize>| {
+            let output = outputs[&file].borrow();
+            CaptureParser::parse_file(&*output).expect("Could not parse").1 .1.iter().map(|r| r.timestamp().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs()).collect::<std::vec::Vec<_>>()
+        };
+        assert_eq!(records(OutputFile { key: 1, part: 0 }), vec![1, 3]);
+        assert_eq!(records(OutputFile { key: 1, part: 1 }), vec![6]);
+        assert_eq!(records(OutputFile { key: 2, part: 0 }), vec![2, 5]);
+    }
+
+    #[cfg(feature = "flows")]
+    #[test]
+    fn split_flows_to_files() {
+        use super::super::analysis::reassembly::tests::tcp_record;
+
+        let _ = env_logger::try_init();
+
+        let records = vec![
+            tcp_record(1, true, 1, 0x02, &[]),
+            record_at(2, 0, 4),
+            tcp_record(3, false, 1, 0x12, &[]),
+            tcp_record(4, true, 1, 0x10, &[])
+        ];
+        let mut outputs = vec![];
+        let files = split_flows_to(GlobalHeader::new(LinkType::Ethernet, 65535), records, OutputLimits { max_open: Some(1), max_bytes: None }, |_, mode| {
+            let output = std::rc::Rc::new(std::cell::RefCell::new(vec![]));
+            outputs.push( (mode, output.clone()) );
+            Ok(Shared(output))
+        }).expect("Could not split");
+
+        assert_eq!(files.len(), 2);
+        assert!(files[0].key.is_some());
+        assert!(files[1].key.is_none());
+        assert_eq!(outputs.iter().map(|o| o.0).collect::<std::vec::Vec<_>>(), vec![OpenMode::Create, OpenMode::Create, OpenMode::Append]);
+
+        let mut flow = outputs[0].1.borrow().clone();
+        flow.extend_from_slice(&outputs[2].1.borrow());
+        assert_eq!(CaptureParser::parse_file(&flow).expect("Could not parse").1 .1.len(), 3);
+    }
+}
@@ -0,0 +1,144 @@
+use super::prelude::*;
+use super::CaptureParser;
+use super::global_header::{GlobalHeader, TimestampPrecision};
+use super::pcapng::{self, Capture, Interface};
+use super::writer::CaptureWriter;
+
+use std;
+
+pub use super::pcapng::UNLIMITED_SNAP_LENGTH;
+
+///
+/// Write `records` of a libpcap capture with `header` as a pcapng capture of a single interface,
+/// with the link type, snap length and byte order of `header`. Nanosecond captures get an interface
+/// with nanosecond timestamps, so no precision is lost. Record comments are written as
+/// opt_comment options.
+///
+pub fn pcap_to_pcapng<'a, W, I>(writer: W, header: &GlobalHeader, records: I) -> errors::Result<pcapng::Writer<W>>
+    where W: std::io::Write, I: IntoIterator<Item=&'a PcapRecord>
+{
+    let mut interface = Interface::new(header.link_type(), header.snap_length());
+    interface.set_timestamp_resolution(header.precision().into());
+
+    let mut writer = pcapng::Writer::with_endianness(writer, header.endianness())?;
+    let interface_id = writer.add_interface(interface)?;
+    writer.write_records(interface_id, records)?;
+    Ok(writer)
+}
+
+///
+/// Global header for the records of `capture` written as a libpcap capture: the byte order of its
+/// first section, the largest snap length of its interfaces, and nanosecond timestamps if any
+/// interface is finer than microseconds. A libpcap capture has a single link type, so every
+/// interface must share one.
+///
+pub fn pcapng_header(capture: &Capture) -> errors::Result<GlobalHeader> {
+    capture.global_header()
+}
+
+///
+/// Write the records of `capture` as a libpcap capture with the header from `pcapng_header`.
+/// Comments and interfaces of records aren't kept, libpcap has no place for them.
+///
+pub fn pcapng_to_pcap<W: std::io::Write>(writer: W, capture: &Capture) -> errors::Result<CaptureWriter<W>> {
+    let header = pcapng_header(capture)?;
+    let mut writer = CaptureWriter::new(writer, header)?;
+    writer.write_records(capture.records())?;
+    Ok(writer)
+}
+
+///
+/// Read an entire capture from `reader` and write it to `writer` in the other container: pcapng
+/// captures as libpcap, and libpcap (or snoop and NetMon) captures as pcapng, as editcap does with
+/// `-F`. With the `compression` feature, compressed captures are decompressed first.
+///
+pub fn transcode<R: std::io::Read, W: std::io::Write>(reader: R, writer: W) -> errors::Result<W> {
+    use std::io::Read;
+
+    #[cfg(feature = "compression")]
+    let mut reader = super::compression::Decoder::new(reader)?;
+    #[cfg(not(feature = "compression"))]
+    let mut reader = reader;
+
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes)?;
+
+    if pcapng::is_pcapng(&bytes) {
+        let capture = Capture::parse_reader(&bytes[..])?;
+        pcapng_to_pcap(writer, &capture)?.into_inner()
+    } else {
+        let (header, records) = CaptureParser::parse_reader(&bytes[..])?;
+        Ok(pcap_to_pcapng(writer, &header, &records)?.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+    use super::super::link_type::LinkType;
+    use super::super::nom::Endianness;
+    use super::super::pcapng::TimestampResolution;
+
+    fn records() -> std::vec::Vec<PcapRecord> {
+        vec![
+            PcapRecord::new(std::time::UNIX_EPOCH + std::time::Duration::new(1527868899, 152053789), 5, 5, vec![0x01u8, 0x02u8, 0x03u8, 0x04u8, 0x05u8]),
+            PcapRecord::new(std::time::UNIX_EPOCH + std::time::Duration::new(1527868900, 0), 8, 1500, vec![0xFFu8; 8])
+        ]
+    }
+
+    #[test]
+    fn transcode_pcap_to_pcapng() {
+        let _ = env_logger::try_init();
+
+        let header = GlobalHeader::new(LinkType::Raw, 1500).with_endianness(Endianness::Big)
+            .with_precision(TimestampPrecision::Nanosecond);
+        let mut writer = CaptureWriter::new(vec![], header.clone()).expect("Could not write header");
+        writer.write_records(&records()).expect("Could not write records");
+        let pcap = writer.into_inner().expect("Could not flush");
+
+        let pcapng = transcode(&pcap[..], vec![]).expect("Could not transcode");
+        let capture = Capture::parse_reader(&pcapng[..]).expect("Could not parse pcapng");
+        assert_eq!(capture.sections()[0].header().endianness(), Endianness::Big);
+        let interface = &capture.sections()[0].interfaces()[0];
+        assert_eq!(interface.link_type(), LinkType::Raw);
+        assert_eq!(interface.snap_length(), 1500);
+        assert_eq!(interface.timestamp_resolution(), TimestampResolution::Decimal(9));
+        let expected: std::vec::Vec<_> = records().into_iter().map(|r| r.with_header(&header)).collect();
+        assert_eq!(capture.records(), &expected);
+
+        //and back again
+        let transcoded = transcode(&pcapng[..], vec![]).expect("Could not transcode");
+        assert_eq!(transcoded, pcap);
+    }
+
+    #[test]
+    fn transcode_pcapng_to_pcap() {
+        let _ = env_logger::try_init();
+
+        let mut writer = pcapng::Writer::with_endianness(vec![], Endianness::Little).expect("Could not write section header");
+        let micros = writer.add_interface(Interface::new(LinkType::Ethernet, 0)).expect("Could not add interface");
+        let mut records = records();
+        records[0].set_comments(vec!["dropped".to_string()]);
+        writer.write_records(micros, &records).expect("Could not write records");
+        let (_, capture) = Capture::parse(&writer.into_inner()).expect("Could not parse pcapng");
+
+        let pcap = pcapng_to_pcap(vec![], &capture).expect("Could not transcode").into_inner().expect("Could not flush");
+        let (header, written) = CaptureParser::parse_reader(&pcap[..]).expect("Could not parse pcap");
+        assert_eq!(header.link_type(), LinkType::Ethernet);
+        assert_eq!(header.endianness(), Endianness::Little);
+        assert_eq!(header.snap_length(), UNLIMITED_SNAP_LENGTH);
+        assert_eq!(header.precision(), TimestampPrecision::Microsecond);
+        assert_eq!(written.len(), 2);
+        assert_eq!(*written[0].timestamp(), std::time::UNIX_EPOCH + std::time::Duration::new(1527868899, 152053000));
+        assert!(written[0].comments().is_empty());
+        assert_eq!(written[1].original_length(), 1500);
+
+        let mut writer = pcapng::Writer::new(vec![]).expect("Could not write section header");
+        writer.add_interface(Interface::new(LinkType::Ethernet, 0)).expect("Could not add interface");
+        writer.add_interface(Interface::new(LinkType::Raw, 0)).expect("Could not add interface");
+        let (_, mixed) = Capture::parse(&writer.into_inner()).expect("Could not parse pcapng");
+        assert!(pcapng_to_pcap(vec![], &mixed).is_err());
+    }
+}
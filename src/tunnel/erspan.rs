@@ -0,0 +1,200 @@
+use super::prelude::*;
+
+use self::nom::*;
+use std;
+
+///
+/// GRE protocol type of ERSPAN type II, and of type I when the GRE header has no sequence number
+///
+pub const TYPE_II_PROTOCOL: u16 = 0x88BE;
+///
+/// GRE protocol type of ERSPAN type III
+///
+pub const TYPE_III_PROTOCOL: u16 = 0x22EB;
+
+const TYPE_II_VERSION: u8 = 1;
+const TYPE_III_VERSION: u8 = 2;
+const PLATFORM_SUBHEADER_LENGTH: usize = 8;
+
+///
+/// Direction of the mirrored frame through the monitored port
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Ingress,
+    Egress
+}
+
+///
+/// Mirroring metadata of an ERSPAN packet, describing the frame as the monitored switch saw it
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Mirror {
+    ///
+    /// Vlan of the mirrored frame as received, 0 when it wasn't tagged
+    ///
+    pub vlan: u16,
+    ///
+    /// Class of service of the mirrored frame
+    ///
+    pub cos: u8,
+    ///
+    /// Whether the mirrored frame was truncated by the switch
+    ///
+    pub truncated: bool,
+    ///
+    /// Direction through the monitored port, for type III
+    ///
+    pub direction: Option<Direction>,
+    ///
+    /// Timestamp from the switch, for type III, in units of its timestamp granularity
+    ///
+    pub timestamp: Option<u32>
+}
+
+///
+/// Encapsulated remote switched port analyzer header of type II or III, carried in GRE and
+/// carrying a mirrored ethernet frame.
+/// https://tools.ietf.org/html/draft-foschiano-erspan-03
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Erspan {
+    version: u8,
+    session_id: u16,
+    mirror: Mirror,
+    index: Option<u32>,
+    granularity: Option<u8>,
+    payload: std::vec::Vec<u8>
+}
+
+impl Erspan {
+    ///
+    /// Header version, 1 for type II and 2 for type III
+    ///
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    ///
+    /// 10 bit id of the monitor session the frame was mirrored by
+    ///
+    pub fn session_id(&self) -> u16 {
+        self.session_id
+    }
+    pub fn mirror(&self) -> Mirror {
+        self.mirror
+    }
+
+    ///
+    /// Port index of the monitored port, for type II
+    ///
+    pub fn index(&self) -> Option<u32> {
+        self.index
+    }
+
+    ///
+    /// Granularity of the timestamp for type III: 100 microseconds (0), 100 nanoseconds (1),
+    /// IEEE 1588 (2) or user configured (3)
+    ///
+    pub fn granularity(&self) -> Option<u8> {
+        self.granularity
+    }
+    pub fn payload(&self) -> &std::vec::Vec<u8> {
+        &self.payload
+    }
+
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Erspan> {
+        layer_span!("erspan", length = input.len());
+
+        do_parse!(input,
+
+            version_vlan: add_return_error!(ErrorKind::Custom(errors::ParseCode::ErspanVersion as u32), verify!(be_u16, |v: u16| {
+                let version = (v >> 12) as u8;
+                version == TYPE_II_VERSION || version == TYPE_III_VERSION
+            })) >>
+            cos_session: be_u16 >>
+            index: cond!(version_vlan >> 12 == u16::from(TYPE_II_VERSION), map!(be_u32, |i: u32| i & 0x000F_FFFF)) >>
+            type_iii: cond!(version_vlan >> 12 == u16::from(TYPE_III_VERSION), do_parse!(
+                timestamp: be_u32 >>
+                sgt: be_u16 >>
+                flags: be_u16 >>
+                platform: cond!(flags & 0x0001 != 0, take!(PLATFORM_SUBHEADER_LENGTH)) >>
+                ( (timestamp, flags) )
+            )) >>
+            payload: rest >>
+
+            (
+                Erspan {
+                    version: (version_vlan >> 12) as u8,
+                    session_id: cos_session & 0x03FF,
+                    mirror: Mirror {
+                        vlan: version_vlan & 0x0FFF,
+                        cos: (cos_session >> 13) as u8,
+                        truncated: cos_session & 0x0400 != 0,
+                        direction: type_iii.map(|(_, flags)| if flags & 0x0008 != 0 { Direction::Egress } else { Direction::Ingress }),
+                        timestamp: type_iii.map(|(timestamp, _)| timestamp)
+                    },
+                    index: index,
+                    granularity: type_iii.map(|(_, flags)| ((flags >> 1) & 0x03) as u8),
+                    payload: payload.into()
+                }
+            )
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    #[test]
+    fn parse_type_ii() {
+        let _ = env_logger::try_init();
+
+        let data = [
+            0x10u8, 0x64u8, //version 1, vlan 100
+            0xA4u8, 0x2Au8, //cos 5, truncated, session 42
+            0x00u8, 0x00u8, 0x00u8, 0x07u8, //index, 7
+            0xFFu8 //payload
+        ];
+        let (rem, erspan) = Erspan::parse(&data).expect("Could not parse");
+
+        assert!(rem.is_empty());
+        assert_eq!(erspan.version(), 1);
+        assert_eq!(erspan.session_id(), 42);
+        assert_eq!(erspan.index(), Some(7));
+        assert_eq!(erspan.granularity(), None);
+        assert_eq!(erspan.mirror(), Mirror { vlan: 100, cos: 5, truncated: true, direction: None, timestamp: None });
+        assert_eq!(erspan.payload(), &vec![0xFFu8]);
+    }
+
+    #[test]
+    fn parse_type_iii() {
+        let _ = env_logger::try_init();
+
+        let data = [
+            0x20u8, 0x00u8, //version 2, untagged
+            0x00u8, 0x2Au8, //cos 0, session 42
+            0x00u8, 0x01u8, 0xE2u8, 0x40u8, //timestamp, 123456
+            0x00u8, 0x00u8, //security group tag
+            0x00u8, 0x0Bu8, //egress, 100ns granularity, platform subheader present
+            0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8, //platform subheader
+            0xFFu8 //payload
+        ];
+        let (rem, erspan) = Erspan::parse(&data).expect("Could not parse");
+
+        assert!(rem.is_empty());
+        assert_eq!(erspan.version(), 2);
+        assert_eq!(erspan.session_id(), 42);
+        assert_eq!(erspan.index(), None);
+        assert_eq!(erspan.granularity(), Some(1));
+        assert_eq!(erspan.mirror().direction, Some(Direction::Egress));
+        assert_eq!(erspan.mirror().timestamp, Some(123456));
+        assert_eq!(erspan.payload(), &vec![0xFFu8]);
+
+        let err = Erspan::parse(&[0u8; 8]).err().map(|e| errors::Error::from_nom(e, errors::Layer::Erspan, &[0u8; 8])).expect("Parsed version 0");
+        assert_eq!(format!("{}", err), "ERSPAN header at offset 0: expected version 1 (type II) or 2 (type III)");
+    }
+}
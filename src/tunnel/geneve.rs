@@ -0,0 +1,125 @@
+use super::prelude::*;
+use super::InnerProtocol;
+
+use self::nom::*;
+use std;
+
+///
+/// UDP port Geneve is carried on
+///
+pub const PORT: u16 = 6081;
+
+const OPTIONS_LENGTH_MASK: u8 = 0x3F;
+const OAM_FLAG: u8 = 0x80;
+
+///
+/// Generic network virtualization encapsulation header https://tools.ietf.org/html/rfc8926
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Geneve {
+    oam: bool,
+    protocol_type: u16,
+    vni: u32,
+    options: std::vec::Vec<u8>,
+    payload: std::vec::Vec<u8>
+}
+
+impl Geneve {
+    ///
+    /// Control message rather than tenant traffic
+    ///
+    pub fn is_oam(&self) -> bool {
+        self.oam
+    }
+    ///
+    /// Ethernet type of the payload
+    ///
+    pub fn protocol_type(&self) -> u16 {
+        self.protocol_type
+    }
+    ///
+    /// 24 bit virtual network identifier
+    ///
+    pub fn vni(&self) -> u32 {
+        self.vni
+    }
+    ///
+    /// Variable length options, undecoded
+    ///
+    pub fn options(&self) -> &std::vec::Vec<u8> {
+        &self.options
+    }
+    pub fn payload(&self) -> &std::vec::Vec<u8> {
+        &self.payload
+    }
+
+    pub fn inner_protocol(&self) -> Option<InnerProtocol> {
+        InnerProtocol::from_ether_type(self.protocol_type)
+    }
+
+    fn parse_geneve(input: &[u8], version_and_length: u8) -> IResult<&[u8], Geneve> {
+        let options_length = (version_and_length & OPTIONS_LENGTH_MASK) as usize * 4;
+
+        do_parse!(input,
+
+            flags: be_u8 >>
+            protocol_type: be_u16 >>
+            vni: be_u32 >>
+            options: take!(options_length) >>
+            payload: rest >>
+
+            (
+                Geneve {
+                    oam: flags & OAM_FLAG != 0,
+                    protocol_type: protocol_type,
+                    vni: vni >> 8,
+                    options: options.into(),
+                    payload: payload.into()
+                }
+            )
+        )
+    }
+
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Geneve> {
+        layer_span!("geneve", length = input.len());
+
+        be_u8(input).and_then(|r| {
+            let (rem, version_and_length) = r;
+            if version_and_length >> 6 != 0 {
+                Err(Err::Error(error_position!(input, ErrorKind::Custom(errors::ParseCode::GeneveVersion as u32))))
+            } else {
+                Geneve::parse_geneve(rem, version_and_length)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    #[test]
+    fn parse_geneve() {
+        let _ = env_logger::try_init();
+
+        let data = [
+            0x01u8, //version 0, 1 word of options
+            0x00u8, //flags
+            0x65u8, 0x58u8, //transparent ethernet bridging
+            0x00u8, 0x00u8, 0x0Au8, //vni, 10
+            0x00u8, //reserved
+            0x01u8, 0x02u8, 0x03u8, 0x04u8, //options
+            0xFFu8 //payload
+        ];
+        let (rem, geneve) = Geneve::parse(&data).expect("Could not parse");
+
+        assert!(rem.is_empty());
+        assert!(!geneve.is_oam());
+        assert_eq!(geneve.inner_protocol(), Some(InnerProtocol::Ethernet));
+        assert_eq!(geneve.vni(), 10);
+        assert_eq!(geneve.options(), &vec![0x01u8, 0x02u8, 0x03u8, 0x04u8]);
+        assert_eq!(geneve.payload(), &vec![0xFFu8]);
+    }
+}
@@ -0,0 +1,115 @@
+use super::prelude::*;
+use super::InnerProtocol;
+
+use self::nom::*;
+use std;
+
+const CHECKSUM_FLAG: u16 = 0x8000;
+const KEY_FLAG: u16 = 0x2000;
+const SEQUENCE_FLAG: u16 = 0x1000;
+const VERSION_MASK: u16 = 0x0007;
+
+///
+/// Generic routing encapsulation header https://tools.ietf.org/html/rfc2784 with the key and
+/// sequence number extensions of https://tools.ietf.org/html/rfc2890
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Gre {
+    protocol_type: u16,
+    key: Option<u32>,
+    sequence_number: Option<u32>,
+    payload: std::vec::Vec<u8>
+}
+
+impl Gre {
+    ///
+    /// Ethernet type of the payload
+    ///
+    pub fn protocol_type(&self) -> u16 {
+        self.protocol_type
+    }
+    pub fn key(&self) -> Option<u32> {
+        self.key
+    }
+    pub fn sequence_number(&self) -> Option<u32> {
+        self.sequence_number
+    }
+    pub fn payload(&self) -> &std::vec::Vec<u8> {
+        &self.payload
+    }
+
+    pub fn inner_protocol(&self) -> Option<InnerProtocol> {
+        InnerProtocol::from_ether_type(self.protocol_type)
+    }
+
+    fn parse_gre(input: &[u8], flags: u16) -> IResult<&[u8], Gre> {
+        do_parse!(input,
+
+            protocol_type: be_u16 >>
+            checksum: cond!(flags & CHECKSUM_FLAG != 0, take!(4)) >>
+            key: cond!(flags & KEY_FLAG != 0, be_u32) >>
+            sequence_number: cond!(flags & SEQUENCE_FLAG != 0, be_u32) >>
+            payload: rest >>
+
+            (
+                Gre {
+                    protocol_type: protocol_type,
+                    key: key,
+                    sequence_number: sequence_number,
+                    payload: payload.into()
+                }
+            )
+        )
+    }
+
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Gre> {
+        layer_span!("gre", length = input.len());
+
+        be_u16(input).and_then(|r| {
+            let (rem, flags) = r;
+            if flags & VERSION_MASK != 0 {
+                Err(Err::Error(error_position!(input, ErrorKind::Custom(errors::ParseCode::GreVersion as u32))))
+            } else {
+                Gre::parse_gre(rem, flags)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    const RAW_DATA: &'static [u8] = &[
+        0x30u8, 0x00u8, //key and sequence number present, version 0
+        0x08u8, 0x00u8, //ipv4
+        0x00u8, 0x00u8, 0x00u8, 0x2Au8, //key, 42
+        0x00u8, 0x00u8, 0x00u8, 0x07u8, //sequence number, 7
+        0x45u8, 0x00u8 //payload
+    ];
+
+    #[test]
+    fn parse_gre() {
+        let _ = env_logger::try_init();
+
+        let (rem, gre) = Gre::parse(RAW_DATA).expect("Could not parse");
+
+        assert!(rem.is_empty());
+        assert_eq!(gre.inner_protocol(), Some(InnerProtocol::IPv4));
+        assert_eq!(gre.key(), Some(42));
+        assert_eq!(gre.sequence_number(), Some(7));
+        assert_eq!(gre.payload(), &vec![0x45u8, 0x00u8]);
+    }
+
+    #[test]
+    fn parse_gre_version() {
+        let _ = env_logger::try_init();
+
+        let data = &[0x00u8, 0x01u8, 0x88u8, 0x0Bu8][..]; //enhanced gre (pptp)
+        let err = Gre::parse(data).err().map(|e| errors::Error::from_nom(e, errors::Layer::Gre, data)).expect("Parsed version 1");
+
+        assert_eq!(format!("{}", err), "GRE header at offset 0: expected version 0");
+    }
+}
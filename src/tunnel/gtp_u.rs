@@ -0,0 +1,170 @@
+use super::prelude::*;
+use super::InnerProtocol;
+
+use self::nom::*;
+use std;
+
+///
+/// UDP port GTP-U user plane traffic is carried on
+///
+pub const PORT: u16 = 2152;
+
+///
+/// Message type of a G-PDU, carrying a user packet
+///
+pub const G_PDU: u8 = 0xFF;
+
+const VERSION: u8 = 1;
+const PROTOCOL_TYPE_FLAG: u8 = 0x10;
+const OPTIONAL_FLAGS: u8 = 0x07; //extension header, sequence number and n-pdu number
+const EXTENSION_FLAG: u8 = 0x04;
+const SEQUENCE_FLAG: u8 = 0x02;
+
+///
+/// GTPv1 user plane header https://www.etsi.org/deliver/etsi_ts/129200_129299/129281/
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GtpU {
+    message_type: u8,
+    teid: u32,
+    sequence_number: Option<u16>,
+    payload: std::vec::Vec<u8>
+}
+
+///
+/// Skip the chain of extension headers starting with `next_type`, each a length in 4 byte words
+/// followed by content and the type of the next header
+///
+fn extension_headers(input: &[u8], next_type: u8) -> IResult<&[u8], ()> {
+    let mut current = input;
+    let mut next_type = next_type;
+    while next_type != 0 {
+        let (rem, length) = be_u8(current)?;
+        if length == 0 {
+            return Err(Err::Error(error_position!(current, ErrorKind::Custom(errors::ParseCode::GtpUExtensionLength as u32))))
+        }
+        let (rem, content) = take!(rem, length as usize * 4 - 1)?;
+        next_type = content[content.len() - 1];
+        current = rem;
+    }
+    Ok( (current, ()) )
+}
+
+impl GtpU {
+    pub fn message_type(&self) -> u8 {
+        self.message_type
+    }
+    pub fn teid(&self) -> u32 {
+        self.teid
+    }
+    pub fn sequence_number(&self) -> Option<u16> {
+        self.sequence_number
+    }
+    pub fn payload(&self) -> &std::vec::Vec<u8> {
+        &self.payload
+    }
+
+    ///
+    /// Ip version of the user packet, for G-PDUs
+    ///
+    pub fn inner_protocol(&self) -> Option<InnerProtocol> {
+        if self.message_type != G_PDU {
+            return None
+        }
+        self.payload.first().and_then(|b| InnerProtocol::from_ip_version(b >> 4))
+    }
+
+    fn parse_body(input: &[u8], flags: u8) -> IResult<&[u8], (Option<u16>, &[u8])> {
+        if flags & OPTIONAL_FLAGS == 0 {
+            return Ok( (&input[input.len()..], (None, input)) )
+        }
+        let (rem, (sequence_number, next_type)) = do_parse!(input,
+
+            sequence_number: be_u16 >>
+            n_pdu_number: be_u8 >>
+            next_type: be_u8 >>
+
+            ( (sequence_number, next_type) )
+        )?;
+        let (payload, _) = if flags & EXTENSION_FLAG != 0 { extension_headers(rem, next_type)? } else { (rem, ()) };
+
+        Ok( (&payload[payload.len()..], (Some(sequence_number).filter(|_| flags & SEQUENCE_FLAG != 0), payload)) )
+    }
+
+    fn parse_gtp_u(input: &[u8], flags: u8) -> IResult<&[u8], GtpU> {
+        do_parse!(input,
+
+            message_type: be_u8 >>
+            length: be_u16 >>
+            teid: be_u32 >>
+            body: flat_map!(take!(length), apply!(GtpU::parse_body, flags)) >>
+
+            (
+                GtpU {
+                    message_type: message_type,
+                    teid: teid,
+                    sequence_number: body.0,
+                    payload: body.1.into()
+                }
+            )
+        )
+    }
+
+    pub fn parse(input: &[u8]) -> IResult<&[u8], GtpU> {
+        layer_span!("gtp_u", length = input.len());
+
+        be_u8(input).and_then(|r| {
+            let (rem, flags) = r;
+            if flags >> 5 != VERSION || flags & PROTOCOL_TYPE_FLAG == 0 {
+                Err(Err::Error(error_position!(input, ErrorKind::Custom(errors::ParseCode::GtpUVersion as u32))))
+            } else {
+                GtpU::parse_gtp_u(rem, flags)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    #[test]
+    fn parse_gtp_u() {
+        let _ = env_logger::try_init();
+
+        let data = [
+            0x36u8, //version 1, gtp, extension header and sequence number present
+            0xFFu8, //g-pdu
+            0x00u8, 0x0Du8, //length, 13
+            0x00u8, 0x00u8, 0x12u8, 0x34u8, //teid, 4660
+            0x00u8, 0x05u8, //sequence number, 5
+            0x00u8, //n-pdu number
+            0x85u8, //pdu session container
+            0x01u8, 0x00u8, 0x09u8, 0x00u8, //length 1 word, qfi 9, no next extension
+            0x45u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8 //user packet
+        ];
+        let (rem, gtp) = GtpU::parse(&data).expect("Could not parse");
+
+        assert!(rem.is_empty());
+        assert_eq!(gtp.message_type(), G_PDU);
+        assert_eq!(gtp.teid(), 0x1234);
+        assert_eq!(gtp.sequence_number(), Some(5));
+        assert_eq!(gtp.payload(), &vec![0x45u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8]);
+        assert_eq!(gtp.inner_protocol(), Some(InnerProtocol::IPv4));
+    }
+
+    #[test]
+    fn parse_gtp_u_invalid() {
+        let _ = env_logger::try_init();
+
+        let data = &[0x48u8, 0x20u8, 0x00u8, 0x00u8][..]; //gtpv2-c
+        let err = GtpU::parse(data).err().map(|e| errors::Error::from_nom(e, errors::Layer::GtpU, data)).expect("Parsed gtpv2");
+        assert_eq!(format!("{}", err), "GTP-U header at offset 0: expected version 1 with the GTP protocol type");
+
+        let data = &[0x34u8, 0xFFu8, 0x00u8, 0x08u8, 0x00u8, 0x00u8, 0x00u8, 0x01u8, 0x00u8, 0x00u8, 0x00u8, 0x85u8, 0x00u8, 0x00u8, 0x00u8, 0x00u8][..];
+        let err = GtpU::parse(data).err().map(|e| errors::Error::from_nom(e, errors::Layer::GtpU, data)).expect("Parsed empty extension");
+        assert_eq!(format!("{}", err), "GTP-U header at offset 12: expected extension header length of at least 1 word");
+    }
+}
@@ -0,0 +1,145 @@
+pub mod prelude {
+    pub use super::super::prelude::*;
+    pub use super::super::{layer3, layer4};
+}
+
+pub mod erspan;
+pub mod geneve;
+pub mod gre;
+pub mod gtp_u;
+pub mod vxlan;
+
+use self::prelude::*;
+use self::layer3::{InternetProtocolId, Layer3};
+use self::layer4::Layer4;
+
+use std;
+
+///
+/// Encapsulation protocols that can be decapsulated
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TunnelType {
+    Gre,
+    ///
+    /// ERSPAN mirroring session, carried in GRE
+    ///
+    Erspan,
+    Vxlan,
+    Geneve,
+    GtpU
+}
+
+///
+/// Protocol of the packet carried inside a tunnel
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum InnerProtocol {
+    Ethernet,
+    IPv4,
+    IPv6
+}
+
+impl InnerProtocol {
+    pub fn from_ether_type(value: u16) -> Option<InnerProtocol> {
+        match value {
+            0x6558u16 => Some(InnerProtocol::Ethernet), //transparent ethernet bridging
+            0x0800u16 => Some(InnerProtocol::IPv4),
+            0x86ddu16 => Some(InnerProtocol::IPv6),
+            x => {
+                debug!("Encountered {:02x} when parsing tunnel protocol type", x);
+                None
+            }
+        }
+    }
+
+    pub fn from_ip_version(version: u8) -> Option<InnerProtocol> {
+        match version {
+            4 => Some(InnerProtocol::IPv4),
+            6 => Some(InnerProtocol::IPv6),
+            _ => None
+        }
+    }
+}
+
+///
+/// Tunnel a packet was carried in: the encapsulation, its identifier (VXLAN/Geneve VNI, GTP-U
+/// TEID, GRE key or ERSPAN session id) and the outer addressing. Ports are zero for GRE and ERSPAN.
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TunnelInfo {
+    pub tunnel_type: TunnelType,
+    pub id: Option<u32>,
+    pub src_ip: std::net::IpAddr,
+    pub src_port: u16,
+    pub dst_ip: std::net::IpAddr,
+    pub dst_port: u16,
+    ///
+    /// Mirroring metadata of ERSPAN type II and III packets
+    ///
+    pub erspan: Option<erspan::Mirror>
+}
+
+fn is_port(udp: &layer4::udp::Udp, port: u16) -> bool {
+    udp.dst_port() == port || udp.src_port() == port
+}
+
+///
+/// Tunnel carried by a packet with the given outer layers, with the protocol and bytes of the
+/// encapsulated packet
+///
+pub fn encapsulated(layer3: &Layer3, layer4: Option<&Layer4>) -> Option<(TunnelInfo, InnerProtocol, std::vec::Vec<u8>)> {
+    let (src_ip, dst_ip, protocol, l3_payload) = match *layer3 {
+        Layer3::IPv4(ref ipv4) => (*ipv4.src_ip(), *ipv4.dst_ip(), ipv4.protocol(), ipv4.payload()),
+        #[cfg(feature = "ipv6")]
+        Layer3::IPv6(ref ipv6) => (*ipv6.src_ip(), *ipv6.dst_ip(), ipv6.protocol(), ipv6.payload())
+    };
+    let info = |tunnel_type, id, src_port, dst_port| TunnelInfo {
+        tunnel_type: tunnel_type,
+        id: id,
+        src_ip: src_ip,
+        src_port: src_port,
+        dst_ip: dst_ip,
+        dst_port: dst_port,
+        erspan: None
+    };
+
+    match (protocol, layer4) {
+        (&InternetProtocolId::Gre, _) => {
+            let (_, gre) = gre::Gre::parse(l3_payload).map_err(|e| debug!("Could not parse gre: {:?}", e)).ok()?;
+            match gre.protocol_type() {
+                erspan::TYPE_II_PROTOCOL if gre.sequence_number().is_none() => {
+                    //type I, the mirrored frame directly follows the gre header
+                    Some( (info(TunnelType::Erspan, None, 0, 0), InnerProtocol::Ethernet, gre.payload().clone()) )
+                }
+                erspan::TYPE_II_PROTOCOL | erspan::TYPE_III_PROTOCOL => {
+                    let (_, erspan) = erspan::Erspan::parse(gre.payload()).map_err(|e| debug!("Could not parse erspan: {:?}", e)).ok()?;
+                    let tunnel = TunnelInfo {
+                        erspan: Some(erspan.mirror()),
+                        ..info(TunnelType::Erspan, Some(u32::from(erspan.session_id())), 0, 0)
+                    };
+                    Some( (tunnel, InnerProtocol::Ethernet, erspan.payload().clone()) )
+                }
+                _ => {
+                    let inner = gre.inner_protocol()?;
+                    Some( (info(TunnelType::Gre, gre.key(), 0, 0), inner, gre.payload().clone()) )
+                }
+            }
+        }
+        (_, Some(&Layer4::Udp(ref udp))) if is_port(udp, vxlan::PORT) => {
+            let (_, vxlan) = vxlan::Vxlan::parse(udp.payload()).map_err(|e| debug!("Could not parse vxlan: {:?}", e)).ok()?;
+            Some( (info(TunnelType::Vxlan, Some(vxlan.vni()), udp.src_port(), udp.dst_port()), InnerProtocol::Ethernet, vxlan.payload().clone()) )
+        }
+        (_, Some(&Layer4::Udp(ref udp))) if is_port(udp, geneve::PORT) => {
+            let (_, geneve) = geneve::Geneve::parse(udp.payload()).map_err(|e| debug!("Could not parse geneve: {:?}", e)).ok()?;
+            let inner = geneve.inner_protocol()?;
+            Some( (info(TunnelType::Geneve, Some(geneve.vni()), udp.src_port(), udp.dst_port()), inner, geneve.payload().clone()) )
+        }
+        (_, Some(&Layer4::Udp(ref udp))) if is_port(udp, gtp_u::PORT) => {
+            let (_, gtp) = gtp_u::GtpU::parse(udp.payload()).map_err(|e| debug!("Could not parse gtp-u: {:?}", e)).ok()?;
+            let inner = gtp.inner_protocol()?;
+            Some( (info(TunnelType::GtpU, Some(gtp.teid()), udp.src_port(), udp.dst_port()), inner, gtp.payload().clone()) )
+        }
+        _ => None
+    }
+}
@@ -0,0 +1,79 @@
+use super::prelude::*;
+
+use self::nom::*;
+use std;
+
+///
+/// UDP port VXLAN is carried on
+///
+pub const PORT: u16 = 4789;
+
+const VNI_FLAG: u8 = 0x08;
+
+///
+/// Virtual extensible lan header https://tools.ietf.org/html/rfc7348, carrying an ethernet frame
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Vxlan {
+    vni: u32,
+    payload: std::vec::Vec<u8>
+}
+
+impl Vxlan {
+    ///
+    /// 24 bit VXLAN network identifier
+    ///
+    pub fn vni(&self) -> u32 {
+        self.vni
+    }
+    pub fn payload(&self) -> &std::vec::Vec<u8> {
+        &self.payload
+    }
+
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Vxlan> {
+        layer_span!("vxlan", length = input.len());
+
+        do_parse!(input,
+
+            flags: add_return_error!(ErrorKind::Custom(errors::ParseCode::VxlanFlags as u32), verify!(be_u8, |f: u8| f & VNI_FLAG != 0)) >>
+            reserved: take!(3) >>
+            vni: be_u32 >>
+            payload: rest >>
+
+            (
+                Vxlan {
+                    vni: vni >> 8,
+                    payload: payload.into()
+                }
+            )
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+
+    #[test]
+    fn parse_vxlan() {
+        let _ = env_logger::try_init();
+
+        let data = [
+            0x08u8, //vni present
+            0x00u8, 0x00u8, 0x00u8, //reserved
+            0x01u8, 0x23u8, 0x45u8, //vni, 74565
+            0x00u8, //reserved
+            0xFFu8 //payload
+        ];
+        let (rem, vxlan) = Vxlan::parse(&data).expect("Could not parse");
+
+        assert!(rem.is_empty());
+        assert_eq!(vxlan.vni(), 0x012345);
+        assert_eq!(vxlan.payload(), &vec![0xFFu8]);
+
+        let err = Vxlan::parse(&[0u8; 8]).err().map(|e| errors::Error::from_nom(e, errors::Layer::Vxlan, &[0u8; 8])).expect("Parsed without vni");
+        assert_eq!(format!("{}", err), "VXLAN header at offset 0: expected VNI flag set");
+    }
+}
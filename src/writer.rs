@@ -0,0 +1,225 @@
+use super::prelude::*;
+use super::global_header::{GlobalHeader, PcapVariant};
+use super::record::PcapRecord;
+
+use std;
+use std::io::Write;
+
+///
+/// Writer of a libpcap capture, e.g. to persist records kept by a filter or edited after parsing.
+/// Writes the global header up front, then each record in the header's byte order. Wrap file or
+/// socket writers in a `std::io::BufWriter`, as each record is a separate write.
+///
+pub struct CaptureWriter<W> {
+    writer: W,
+    header: GlobalHeader,
+    records: usize
+}
+
+impl<W: Write> CaptureWriter<W> {
+    ///
+    /// Write `header` to `writer`, starting a capture. Captures are always written in the standard
+    /// format, as the extended record header fields of the modified format aren't kept on records.
+    ///
+    pub fn new(mut writer: W, header: GlobalHeader) -> errors::Result<CaptureWriter<W>> {
+        let header = header.with_variant(PcapVariant::Standard);
+        header.write_to(&mut writer)?;
+
+        Ok(CaptureWriter {
+            writer: writer,
+            header: header,
+            records: 0
+        })
+    }
+
+    ///
+    /// Continue a capture whose `header` was already written to `writer`, e.g. a file reopened for
+    /// appending. Nothing is written until the next record.
+    ///
+    pub fn appending(writer: W, header: GlobalHeader) -> CaptureWriter<W> {
+        CaptureWriter {
+            writer: writer,
+            header: header.with_variant(PcapVariant::Standard),
+            records: 0
+        }
+    }
+
+    pub fn header(&self) -> &GlobalHeader { &self.header }
+
+    ///
+    /// Records written so far
+    ///
+    pub fn records(&self) -> usize { self.records }
+
+    ///
+    /// Write `record`, truncating its payload to the snap length of the capture. The original
+    /// length is written as is.
+    ///
+    pub fn write_record(&mut self, record: &PcapRecord) -> errors::Result<()> {
+        let snap_length = self.header.snap_length() as usize;
+        if snap_length > 0 && record.payload().len() > snap_length {
+            debug!("Truncating record of {} bytes to snap length {}", record.payload().len(), snap_length);
+            let truncated = PcapRecord::new(
+                *record.timestamp(),
+                snap_length as u32,
+                record.original_length(),
+                record.payload()[..snap_length].to_vec()
+            );
+            self.writer.write_all(&truncated.to_bytes_with_precision(self.header.endianness(), self.header.precision()))?;
+        } else {
+            self.writer.write_all(&record.to_bytes_with_precision(self.header.endianness(), self.header.precision()))?;
+        }
+        self.records += 1;
+        Ok(())
+    }
+
+    pub fn write_records<'a, I>(&mut self, records: I) -> errors::Result<()> where I: IntoIterator<Item=&'a PcapRecord> {
+        for record in records {
+            self.write_record(record)?;
+        }
+        Ok(())
+    }
+
+    ///
+    /// Write the `records` matching `predicate`, e.g. a flow, port or time range, as a new capture
+    /// with the link type, byte order and precision of `header`. The snap length is raised to the
+    /// longest record kept, so records captured under a larger snap length aren't truncated.
+    /// Records are buffered until the header is written.
+    ///
+    pub fn write_where<'a, I, F>(writer: W, header: &GlobalHeader, records: I, mut predicate: F) -> errors::Result<CaptureWriter<W>>
+        where I: IntoIterator<Item=&'a PcapRecord>, F: FnMut(&PcapRecord) -> bool
+    {
+        let kept: std::vec::Vec<&PcapRecord> = records.into_iter().filter(|r| predicate(r)).collect();
+        let longest = kept.iter().map(|r| r.payload().len() as u32).max().unwrap_or(0);
+        let header = if header.snap_length() > 0 && longest > header.snap_length() {
+            debug!("Raising snap length {} to the longest record kept, {}", header.snap_length(), longest);
+            header.clone().with_snap_length(longest)
+        } else {
+            header.clone()
+        };
+
+        let mut capture = CaptureWriter::new(writer, header)?;
+        capture.write_records(kept)?;
+        Ok(capture)
+    }
+
+    ///
+    /// Flush and hand back the underlying writer
+    ///
+    pub fn into_inner(mut self) -> errors::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate env_logger;
+
+    use super::*;
+    use super::super::CaptureParser;
+    use super::super::global_header::TimestampPrecision;
+    use super::super::link_type::LinkType;
+    use super::super::nom::Endianness;
+
+    fn record(seconds: u64, payload: &[u8]) -> PcapRecord {
+        let timestamp = std::time::UNIX_EPOCH + std::time::Duration::from_secs(seconds) + std::time::Duration::from_micros(250);
+        PcapRecord::new(timestamp, payload.len() as u32, payload.len() as u32, payload.to_vec())
+    }
+
+    #[test]
+    fn write_capture() {
+        let _ = env_logger::try_init();
+
+        let records = vec![
+            record(1527868899, &[0x01u8, 0x02u8, 0x03u8, 0x04u8]),
+            record(1527868900, &[0x05u8, 0x06u8])
+        ];
+
+        for endianness in &[Endianness::Big, Endianness::Little] {
+            let header = GlobalHeader::new(LinkType::Ethernet, 65535).with_endianness(*endianness);
+            let mut writer = CaptureWriter::new(vec![], header.clone()).expect("Could not write header");
+            writer.write_records(&records).expect("Could not write records");
+            assert_eq!(writer.records(), 2);
+
+            let bytes = writer.into_inner().expect("Could not flush");
+            let (rem, (parsed_header, parsed_records)) = CaptureParser::parse_file(&bytes).expect("Could not parse");
+            assert!(rem.is_empty());
+            assert_eq!(parsed_header, header);
+            assert_eq!(parsed_records, records);
+        }
+    }
+
+    #[test]
+    fn write_capture_nanoseconds() {
+        let _ = env_logger::try_init();
+
+        let timestamp = std::time::UNIX_EPOCH + std::time::Duration::new(1527868899, 152053123);
+        let header = GlobalHeader::new(LinkType::Ethernet, 65535).with_precision(TimestampPrecision::Nanosecond);
+        let records = vec![PcapRecord::new(timestamp, 2, 2, vec![0x01u8, 0x02u8]).with_header(&header)];
+
+        let mut writer = CaptureWriter::new(vec![], header.clone()).expect("Could not write header");
+        writer.write_records(&records).expect("Could not write records");
+
+        let bytes = writer.into_inner().expect("Could not flush");
+        let (_, (parsed_header, parsed_records)) = CaptureParser::parse_file(&bytes).expect("Could not parse");
+        assert_eq!(parsed_header.precision(), TimestampPrecision::Nanosecond);
+        assert_eq!(parsed_records, records);
+    }
+
+    #[test]
+    fn write_capture_where() {
+        let _ = env_logger::try_init();
+
+        let records = vec![
+            record(1527868899, &[0x01u8, 0x02u8]),
+            record(1527868900, &[0x03u8, 0x04u8, 0x05u8, 0x06u8]),
+            record(1527868901, &[0x07u8])
+        ];
+
+        let header = GlobalHeader::new(LinkType::Ethernet, 3).with_precision(TimestampPrecision::Nanosecond);
+        let writer = CaptureWriter::write_where(vec![], &header, &records, |r| r.payload().len() > 1).expect("Could not write capture");
+        assert_eq!(writer.records(), 2);
+        assert_eq!(writer.header().snap_length(), 4);
+
+        let bytes = writer.into_inner().expect("Could not flush");
+        let (_, (parsed_header, parsed_records)) = CaptureParser::parse_file(&bytes).expect("Could not parse");
+        assert_eq!(parsed_header, header.clone().with_snap_length(4));
+        assert_eq!(parsed_records, records[..2].iter().map(|r| r.clone().with_header(&header)).collect::<std::vec::Vec<_>>());
+
+        let writer = CaptureWriter::write_where(vec![], &header, &records, |_| false).expect("Could not write capture");
+        assert_eq!(writer.records(), 0);
+        assert_eq!(writer.header(), &header);
+    }
+
+    #[test]
+    fn write_capture_appending() {
+        let _ = env_logger::try_init();
+
+        let header = GlobalHeader::new(LinkType::Ethernet, 65535);
+        let mut writer = CaptureWriter::new(vec![], header.clone()).expect("Could not write header");
+        writer.write_record(&record(1527868899, &[0x01u8])).expect("Could not write record");
+        let mut writer = CaptureWriter::appending(writer.into_inner().expect("Could not flush"), header);
+        writer.write_record(&record(1527868900, &[0x02u8])).expect("Could not write record");
+        assert_eq!(writer.records(), 1);
+
+        let bytes = writer.into_inner().expect("Could not flush");
+        let (_, (_, records)) = CaptureParser::parse_file(&bytes).expect("Could not parse");
+        assert_eq!(records, vec![record(1527868899, &[0x01u8]), record(1527868900, &[0x02u8])]);
+    }
+
+    #[test]
+    fn write_capture_truncated() {
+        let _ = env_logger::try_init();
+
+        let mut writer = CaptureWriter::new(vec![], GlobalHeader::new(LinkType::Ethernet, 3)).expect("Could not write header");
+        writer.write_record(&record(1527868899, &[0x01u8, 0x02u8, 0x03u8, 0x04u8])).expect("Could not write record");
+
+        let bytes = writer.into_inner().expect("Could not flush");
+        let (_, (_, records)) = CaptureParser::parse_file(&bytes).expect("Could not parse");
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].payload(), &vec![0x01u8, 0x02u8, 0x03u8]);
+        assert_eq!(records[0].actual_length(), 3);
+        assert_eq!(records[0].original_length(), 4);
+    }
+}